@@ -0,0 +1,33 @@
+//! Stand-in for the archive when built without the "recorders" feature.
+//! Configurations that ask for archiving are rejected at startup, so this
+//! never has anything to do.
+use crate::config::ArchiveConfig;
+use crate::datasource::{Channel, SeismoData};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ArchiveError {}
+
+pub struct Archiver;
+
+impl Archiver {
+    pub fn from_config(_config: &ArchiveConfig) -> Self {
+        Archiver
+    }
+
+    pub fn track_channel(&mut self, _channel: Channel, _sample_rate_hz: f32) {}
+
+    pub fn set_event_active(&mut self, _active: bool, _as_of: f64) {}
+
+    pub fn record(&mut self, _data: &SeismoData) -> Result<(), ArchiveError> {
+        Ok(())
+    }
+
+    pub fn read_backfill(&self, _now: f64) -> Result<Vec<SeismoData>, ArchiveError> {
+        Ok(Vec::new())
+    }
+
+    pub fn flush(&mut self) -> Result<(), ArchiveError> {
+        Ok(())
+    }
+}
@@ -0,0 +1,254 @@
+//! Archival of received channel data as miniSEED in an SDS directory tree.
+mod mseed;
+mod sds;
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+
+use crate::config::{ArchiveConfig, ArchiveMode};
+use crate::datasource::{Channel, SeismoData};
+use mseed::{encode_record, BTime, RecordError, StreamId, SAMPLES_PER_RECORD};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ArchiveError {
+    #[error("unable to write archive file")]
+    WriteError(#[from] std::io::Error),
+    #[error("unable to read archive record")]
+    ReadError(#[from] RecordError),
+}
+
+/// Writes the data of one seismometer into an SDS archive, either
+/// continuously or only around triggered events.
+pub struct Archiver {
+    root: PathBuf,
+    mode: ArchiveMode,
+    pre_event_s: f64,
+    post_event_s: f64,
+    backfill_s: Option<f64>,
+    template: StreamId,
+    streams: Vec<Option<StreamArchive>>,
+
+    /// Whether any flow on the seismometer is currently triggered.
+    event_active: bool,
+
+    /// In event mode, the data time up to which post-event samples are
+    /// still archived.
+    keep_until: Option<f64>,
+}
+
+/// Archive state for a single channel.
+struct StreamArchive {
+    id: StreamId,
+    sample_rate_hz: f32,
+    sequence: u32,
+
+    /// Data time of the first pending sample.
+    start: f64,
+
+    /// Samples not yet written out as a full record.
+    pending: Vec<i32>,
+
+    /// In event mode, recent packets retained as pre-event data.
+    history: VecDeque<(f64, Vec<i32>)>,
+}
+
+impl Archiver {
+    pub fn from_config(config: &ArchiveConfig) -> Self {
+        let mut streams = Vec::with_capacity(Channel::max());
+        streams.extend((0..Channel::max()).map(|_| None));
+        Archiver {
+            root: config.path.clone(),
+            mode: config.mode,
+            pre_event_s: config.pre_event_s as f64,
+            post_event_s: config.post_event_s as f64,
+            backfill_s: config.backfill_s.map(|s| s as f64),
+            template: StreamId {
+                network: config.network.clone(),
+                station: config.station.clone(),
+                location: config.location.clone(),
+                channel: String::new(),
+            },
+            streams,
+            event_active: false,
+            keep_until: None,
+        }
+    }
+
+    /// Start archiving a channel, sent at the given sample rate.
+    pub fn track_channel(&mut self, channel: Channel, sample_rate_hz: f32) {
+        let stream = &mut self.streams[channel as usize];
+        if stream.is_none() {
+            let id = StreamId {
+                channel: channel.as_str().to_owned(),
+                ..self.template.clone()
+            };
+            stream.replace(StreamArchive {
+                id,
+                sample_rate_hz,
+                sequence: 1,
+                start: 0.0,
+                pending: Vec::with_capacity(SAMPLES_PER_RECORD),
+                history: VecDeque::new(),
+            });
+        }
+    }
+
+    /// Note whether an event is in progress as of the given data time.
+    pub fn set_event_active(&mut self, active: bool, as_of: f64) {
+        if self.event_active && !active {
+            self.keep_until = Some(as_of + self.post_event_s);
+        }
+        self.event_active = active;
+    }
+
+    /// Archive (or retain, pending an event) a packet of data.
+    pub fn record(&mut self, data: &SeismoData) -> Result<(), ArchiveError> {
+        let Some(stream) = self.streams[data.channel as usize].as_mut() else {
+            return Ok(());
+        };
+        let samples: Vec<i32> = data.data.iter().map(|v| v.round() as i32).collect();
+        let keep = match self.mode {
+            ArchiveMode::Continuous => true,
+            ArchiveMode::Event => {
+                self.event_active || self.keep_until.is_some_and(|t| data.timestamp <= t)
+            }
+        };
+        if keep {
+            while let Some((timestamp, old)) = stream.history.pop_front() {
+                stream.append(&self.root, timestamp, &old)?;
+            }
+            stream.append(&self.root, data.timestamp, &samples)?;
+        } else {
+            stream.flush(&self.root)?;
+            let oldest_wanted = data.timestamp - self.pre_event_s;
+            stream.history.push_back((data.timestamp, samples));
+            while stream
+                .history
+                .front()
+                .is_some_and(|(t, _)| *t < oldest_wanted)
+            {
+                stream.history.pop_front();
+            }
+        }
+        Ok(())
+    }
+
+    /// Read back the configured backfill period of archived data, ending
+    /// at `now`, for every archived channel. Packets are returned in data
+    /// time order.
+    pub fn read_backfill(&self, now: f64) -> Result<Vec<SeismoData>, ArchiveError> {
+        let Some(backfill_s) = self.backfill_s else {
+            return Ok(Vec::new());
+        };
+        let since = now - backfill_s;
+        let mut packets = Vec::new();
+        for (index, stream) in self.streams.iter().enumerate() {
+            let Some(stream) = stream.as_ref() else {
+                continue;
+            };
+            let channel = Channel::try_from(index).expect("valid channel index");
+            let first_day = (since / 86_400.0).floor() as i64;
+            let last_day = (now / 86_400.0).floor() as i64;
+            for day in first_day..=last_day {
+                let when = BTime::from_epoch(day as f64 * 86_400.0);
+                for record in sds::read_day_file(&self.root, &stream.id, &when)? {
+                    if record.start >= since && record.start <= now {
+                        packets.push(SeismoData {
+                            timestamp: record.start,
+                            channel,
+                            data: record.samples.into(),
+                        });
+                    }
+                }
+            }
+        }
+        packets.sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+        Ok(packets)
+    }
+
+    /// Write out any partially filled records.
+    pub fn flush(&mut self) -> Result<(), ArchiveError> {
+        for stream in self.streams.iter_mut().flatten() {
+            stream.flush(&self.root)?;
+        }
+        Ok(())
+    }
+}
+
+impl StreamArchive {
+    fn append(&mut self, root: &Path, timestamp: f64, samples: &[i32]) -> Result<(), ArchiveError> {
+        let period = 1.0 / self.sample_rate_hz as f64;
+        if !self.pending.is_empty() {
+            // Start a new record on any gap or overlap in the data.
+            let expected = self.start + self.pending.len() as f64 * period;
+            if (timestamp - expected).abs() > period / 2.0 {
+                self.flush(root)?;
+            }
+        }
+        if self.pending.is_empty() {
+            self.start = timestamp;
+        }
+        self.pending.extend_from_slice(samples);
+        while self.pending.len() >= SAMPLES_PER_RECORD {
+            self.write_record(root, SAMPLES_PER_RECORD)?;
+            self.start += SAMPLES_PER_RECORD as f64 * period;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self, root: &Path) -> Result<(), ArchiveError> {
+        if !self.pending.is_empty() {
+            self.write_record(root, self.pending.len())?;
+        }
+        Ok(())
+    }
+
+    fn write_record(&mut self, root: &Path, n: usize) -> Result<(), ArchiveError> {
+        let when = BTime::from_epoch(self.start);
+        let record = encode_record(
+            &self.id,
+            self.sequence,
+            when,
+            self.sample_rate_hz,
+            &self.pending[..n],
+        );
+        sds::append_record(root, &self.id, &when, &record)?;
+        self.pending.drain(..n);
+        self.sequence = self.sequence % 999_999 + 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_back_the_backfill_period() {
+        let dir = std::env::temp_dir().join(format!("rs-udp-archive-{}", std::process::id()));
+        let config = serde_json::json!({ "path": dir, "station": "R1234", "backfill_s": 10 });
+        let config: ArchiveConfig = serde_json::from_value(config).expect("valid");
+        let mut archiver = Archiver::from_config(&config);
+        archiver.track_channel(Channel::Ehz, 100.0);
+        let packet = |timestamp: f64| SeismoData {
+            timestamp,
+            channel: Channel::Ehz,
+            data: (0..50).map(|v| v as f32).collect(),
+        };
+        let now = 1_700_000_000.0;
+        for timestamp in [now - 60.0, now - 3.0, now - 2.5] {
+            archiver.record(&packet(timestamp)).expect("records");
+        }
+        archiver.flush().expect("flushes");
+
+        // The packet from before the backfill period is left out, and the
+        // rest come back as the record they were written in.
+        let backfill = archiver.read_backfill(now).expect("reads");
+        std::fs::remove_dir_all(&dir).expect("clean up");
+        assert_eq!(backfill.len(), 1);
+        assert_eq!(backfill[0].timestamp, now - 3.0);
+        assert_eq!(backfill[0].data.len(), 100);
+        assert_eq!(backfill[0].data[50], 0.0);
+    }
+}
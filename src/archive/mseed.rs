@@ -0,0 +1,320 @@
+//! Minimal miniSEED (SEED 2.4) data record encoder.
+//!
+//! Records are always 512 bytes long, big-endian, and hold uncompressed
+//! 32-bit integer samples (encoding format 3), described by a single
+//! blockette 1000.
+use crate::time::{days_from_civil, UtcTime};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum RecordError {
+    #[error("record too short")]
+    TooShort,
+    #[error("record has no blockette 1000")]
+    NoBlockette1000,
+    #[error("unsupported data encoding {0}")]
+    UnsupportedEncoding(u8),
+    #[error("unsupported record length 2^{0}")]
+    UnsupportedLength(u8),
+}
+
+/// Length of every record we write.
+pub const RECORD_LENGTH: usize = 512;
+
+/// Offset of the sample data in a record.
+const DATA_OFFSET: usize = 64;
+
+/// Offset of the (single) blockette 1000 in a record, just past the fixed
+/// header, where the first blockette of any record may start.
+const BLOCKETTE_OFFSET: usize = 48;
+
+/// The record lengths, as powers of two, which records may have (from
+/// 256 bytes to 1 MiB).
+const RECORD_LENGTH_EXPONENTS: std::ops::RangeInclusive<u8> = 8..=20;
+
+/// Number of 32-bit samples that fit into one record.
+pub const SAMPLES_PER_RECORD: usize = (RECORD_LENGTH - DATA_OFFSET) / 4;
+
+/// The SEED "BTIME" representation of a point in time.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct BTime {
+    pub year: u16,
+    pub day_of_year: u16,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    /// Fractional seconds, in units of 0.0001 s.
+    pub ticks: u16,
+}
+
+impl BTime {
+    /// Convert a UNIX epoch timestamp (seconds) into a BTIME.
+    pub fn from_epoch(timestamp: f64) -> BTime {
+        let t = UtcTime::from_epoch_micros((timestamp * 10_000.0).round() as i64 * 100);
+        BTime {
+            year: t.year as u16,
+            day_of_year: t.day_of_year,
+            hour: t.hour,
+            minute: t.minute,
+            second: t.second,
+            ticks: (t.microsecond / 100) as u16,
+        }
+    }
+
+    /// Convert a BTIME into a UNIX epoch timestamp (seconds).
+    pub fn to_epoch(self) -> f64 {
+        let days = days_from_civil(self.year as i64, 1, 1) + self.day_of_year as i64 - 1;
+        let seconds = self.hour as i64 * 3600 + self.minute as i64 * 60 + self.second as i64;
+        (days * 86_400 + seconds) as f64 + self.ticks as f64 / 10_000.0
+    }
+
+    fn read(buf: &[u8]) -> BTime {
+        BTime {
+            year: u16::from_be_bytes([buf[0], buf[1]]),
+            day_of_year: u16::from_be_bytes([buf[2], buf[3]]),
+            hour: buf[4],
+            minute: buf[5],
+            second: buf[6],
+            ticks: u16::from_be_bytes([buf[8], buf[9]]),
+        }
+    }
+
+    fn write(&self, out: &mut [u8]) {
+        out[0..2].copy_from_slice(&self.year.to_be_bytes());
+        out[2..4].copy_from_slice(&self.day_of_year.to_be_bytes());
+        out[4] = self.hour;
+        out[5] = self.minute;
+        out[6] = self.second;
+        out[7] = 0;
+        out[8..10].copy_from_slice(&self.ticks.to_be_bytes());
+    }
+}
+
+/// SEED stream identification for the records of one channel.
+#[derive(Debug, Clone)]
+pub struct StreamId {
+    pub network: String,
+    pub station: String,
+    pub location: String,
+    pub channel: String,
+}
+
+/// Convert a sample rate into a SEED sample rate factor and multiplier.
+fn sample_rate_factor(sample_rate_hz: f32) -> (i16, i16) {
+    if sample_rate_hz >= 1.0 {
+        (sample_rate_hz.round() as i16, 1)
+    } else if sample_rate_hz > 0.0 {
+        (-((1.0 / sample_rate_hz).round() as i16), 1)
+    } else {
+        (0, 0)
+    }
+}
+
+fn write_padded(out: &mut [u8], value: &str) {
+    for (i, byte) in out.iter_mut().enumerate() {
+        *byte = value.as_bytes().get(i).copied().unwrap_or(b' ');
+    }
+}
+
+/// Encode one data record. At most `SAMPLES_PER_RECORD` samples are taken
+/// from `samples`.
+pub fn encode_record(
+    id: &StreamId,
+    sequence: u32,
+    start: BTime,
+    sample_rate_hz: f32,
+    samples: &[i32],
+) -> [u8; RECORD_LENGTH] {
+    let samples = &samples[..samples.len().min(SAMPLES_PER_RECORD)];
+    let mut record = [0_u8; RECORD_LENGTH];
+    let sequence = format!("{:06}", sequence % 1_000_000);
+    record[0..6].copy_from_slice(sequence.as_bytes());
+    record[6] = b'D';
+    record[7] = b' ';
+    write_padded(&mut record[8..13], &id.station);
+    write_padded(&mut record[13..15], &id.location);
+    write_padded(&mut record[15..18], &id.channel);
+    write_padded(&mut record[18..20], &id.network);
+    start.write(&mut record[20..30]);
+    record[30..32].copy_from_slice(&(samples.len() as u16).to_be_bytes());
+    let (factor, multiplier) = sample_rate_factor(sample_rate_hz);
+    record[32..34].copy_from_slice(&factor.to_be_bytes());
+    record[34..36].copy_from_slice(&multiplier.to_be_bytes());
+    // Activity, I/O and data quality flags (36..39) are left clear.
+    // One blockette follows; the time correction (40..44) is left at zero.
+    record[39] = 1;
+    record[44..46].copy_from_slice(&(DATA_OFFSET as u16).to_be_bytes());
+    record[46..48].copy_from_slice(&(BLOCKETTE_OFFSET as u16).to_be_bytes());
+
+    // Blockette 1000: data only SEED blockette.
+    let b = &mut record[BLOCKETTE_OFFSET..DATA_OFFSET];
+    b[0..2].copy_from_slice(&1000_u16.to_be_bytes());
+    b[2..4].copy_from_slice(&0_u16.to_be_bytes());
+    b[4] = 3; // Encoding: 32-bit integers.
+    b[5] = 1; // Word order: big endian.
+    b[6] = RECORD_LENGTH.trailing_zeros() as u8;
+    b[7] = 0;
+
+    for (i, sample) in samples.iter().enumerate() {
+        let at = DATA_OFFSET + i * 4;
+        record[at..at + 4].copy_from_slice(&sample.to_be_bytes());
+    }
+    record
+}
+
+/// A data record read back from a miniSEED file.
+pub struct DecodedRecord {
+    pub start: f64,
+    pub samples: Vec<f32>,
+
+    /// Total length of the record, in bytes.
+    pub length: usize,
+}
+
+/// Decode one data record from the start of `buf`. Only the uncompressed
+/// integer and floating point encodings are understood. Each blockette
+/// must follow the last, so that a corrupt chain can't loop.
+pub fn decode_record(buf: &[u8]) -> Result<DecodedRecord, RecordError> {
+    if buf.len() < DATA_OFFSET {
+        return Err(RecordError::TooShort);
+    }
+    let start = BTime::read(&buf[20..30]).to_epoch();
+    let n = u16::from_be_bytes([buf[30], buf[31]]) as usize;
+    let data_offset = u16::from_be_bytes([buf[44], buf[45]]) as usize;
+    let mut blockette = u16::from_be_bytes([buf[46], buf[47]]) as usize;
+    let mut earliest = BLOCKETTE_OFFSET;
+    let (encoding, big_endian, length) = loop {
+        if blockette < earliest || blockette + 8 > buf.len() {
+            return Err(RecordError::NoBlockette1000);
+        }
+        let b = &buf[blockette..blockette + 8];
+        if u16::from_be_bytes([b[0], b[1]]) == 1000 {
+            if !RECORD_LENGTH_EXPONENTS.contains(&b[6]) {
+                return Err(RecordError::UnsupportedLength(b[6]));
+            }
+            break (b[4], b[5] == 1, 1_usize << b[6]);
+        }
+        earliest = blockette + 4;
+        blockette = u16::from_be_bytes([b[2], b[3]]) as usize;
+    };
+    let width = match encoding {
+        1 => 2,
+        3 | 4 => 4,
+        5 => 8,
+        e => return Err(RecordError::UnsupportedEncoding(e)),
+    };
+    if buf.len() < length || data_offset + n * width > length {
+        return Err(RecordError::TooShort);
+    }
+    let samples = buf[data_offset..data_offset + n * width]
+        .chunks_exact(width)
+        .map(|w| {
+            let mut bytes = [0_u8; 8];
+            bytes[..width].copy_from_slice(w);
+            if !big_endian {
+                bytes[..width].reverse();
+            }
+            match encoding {
+                1 => i16::from_be_bytes([bytes[0], bytes[1]]) as f32,
+                3 => i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32,
+                4 => f32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+                _ => f64::from_be_bytes(bytes) as f32,
+            }
+        })
+        .collect();
+    Ok(DecodedRecord {
+        start,
+        samples,
+        length,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn btime_from_epoch() {
+        // 2024-12-12T22:21:46.042Z
+        let t = BTime::from_epoch(1734042106.042);
+        assert_eq!(
+            t,
+            BTime {
+                year: 2024,
+                day_of_year: 347,
+                hour: 22,
+                minute: 21,
+                second: 46,
+                ticks: 420,
+            }
+        );
+        assert!((t.to_epoch() - 1734042106.042).abs() < 1e-6);
+        assert_eq!(BTime::from_epoch(0.0).day_of_year, 1);
+    }
+
+    #[test]
+    fn record_header() {
+        let id = StreamId {
+            network: "AM".into(),
+            station: "R1234".into(),
+            location: "00".into(),
+            channel: "EHZ".into(),
+        };
+        let record = encode_record(&id, 7, BTime::from_epoch(0.0), 100.0, &[1, -1, 3]);
+        assert_eq!(&record[0..8], b"000007D ");
+        assert_eq!(&record[8..20], b"R123400EHZAM");
+        assert_eq!(&record[30..32], &3_u16.to_be_bytes());
+        assert_eq!(&record[32..34], &100_i16.to_be_bytes());
+        assert_eq!(&record[48..50], &1000_u16.to_be_bytes());
+        assert_eq!(record[54], 9);
+        assert_eq!(&record[68..72], &(-1_i32).to_be_bytes());
+
+        let decoded = decode_record(&record).expect("decodes");
+        assert_eq!(decoded.start, 0.0);
+        assert_eq!(decoded.samples, vec![1.0, -1.0, 3.0]);
+        assert_eq!(decoded.length, RECORD_LENGTH);
+    }
+
+    #[test]
+    fn malformed_records() {
+        let id = StreamId {
+            network: "AM".into(),
+            station: "R1234".into(),
+            location: "00".into(),
+            channel: "EHZ".into(),
+        };
+        let record = encode_record(&id, 1, BTime::from_epoch(0.0), 100.0, &[1, 2]);
+
+        // A blockette which points back at itself, or at an earlier one.
+        let mut looped = record;
+        looped[48..50].copy_from_slice(&1001_u16.to_be_bytes());
+        looped[50..52].copy_from_slice(&48_u16.to_be_bytes());
+        assert!(matches!(
+            decode_record(&looped),
+            Err(RecordError::NoBlockette1000)
+        ));
+        looped[50..52].copy_from_slice(&20_u16.to_be_bytes());
+        assert!(matches!(
+            decode_record(&looped),
+            Err(RecordError::NoBlockette1000)
+        ));
+
+        for exponent in [0, 7, 21, 64, 255] {
+            let mut huge = record;
+            huge[54] = exponent;
+            let decoded = decode_record(&huge);
+            assert!(matches!(decoded, Err(RecordError::UnsupportedLength(e)) if e == exponent));
+        }
+
+        assert!(matches!(
+            decode_record(&record[..40]),
+            Err(RecordError::TooShort)
+        ));
+        let mut overfull = record;
+        overfull[30..32].copy_from_slice(&200_u16.to_be_bytes());
+        assert!(matches!(
+            decode_record(&overfull),
+            Err(RecordError::TooShort)
+        ));
+    }
+}
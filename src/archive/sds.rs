@@ -0,0 +1,82 @@
+//! SeisComP Data Structure (SDS) archive layout.
+//!
+//! Files are organized as
+//! `<root>/<YEAR>/<NET>/<STA>/<CHAN>.D/<NET>.<STA>.<LOC>.<CHAN>.D.<YEAR>.<DAY>`.
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use super::mseed::{decode_record, BTime, DecodedRecord, StreamId};
+use super::ArchiveError;
+
+/// The path of the day file that holds data for a stream starting at `when`.
+pub fn day_file_path(root: &Path, id: &StreamId, when: &BTime) -> PathBuf {
+    let mut path = root.to_path_buf();
+    path.push(format!("{:04}", when.year));
+    path.push(&id.network);
+    path.push(&id.station);
+    path.push(format!("{}.D", id.channel));
+    path.push(format!(
+        "{}.{}.{}.{}.D.{:04}.{:03}",
+        id.network, id.station, id.location, id.channel, when.year, when.day_of_year
+    ));
+    path
+}
+
+/// Append an encoded record to the day file it belongs to, creating
+/// directories as needed.
+pub fn append_record(
+    root: &Path,
+    id: &StreamId,
+    when: &BTime,
+    record: &[u8],
+) -> std::io::Result<()> {
+    let path = day_file_path(root, id, when);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut f = OpenOptions::new().create(true).append(true).open(path)?;
+    f.write_all(record)
+}
+
+/// Read all records of a stream from the day file covering `when`. A
+/// missing file is treated as an empty one.
+pub fn read_day_file(
+    root: &Path,
+    id: &StreamId,
+    when: &BTime,
+) -> Result<Vec<DecodedRecord>, ArchiveError> {
+    let buf = match fs::read(day_file_path(root, id, when)) {
+        Ok(buf) => buf,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut records = Vec::new();
+    let mut at = 0;
+    while at < buf.len() {
+        let record = decode_record(&buf[at..])?;
+        at += record.length;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout() {
+        let id = StreamId {
+            network: "AM".into(),
+            station: "R1234".into(),
+            location: "00".into(),
+            channel: "EHZ".into(),
+        };
+        let path = day_file_path(Path::new("/sds"), &id, &BTime::from_epoch(1734042106.0));
+        assert_eq!(
+            path,
+            Path::new("/sds/2024/AM/R1234/EHZ.D/AM.R1234.00.EHZ.D.2024.347")
+        );
+    }
+}
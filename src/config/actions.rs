@@ -1,8 +1,22 @@
 use std::path::PathBuf;
 
+use schemars::JsonSchema;
 use serde::Deserialize;
 
-#[derive(Deserialize)]
+use super::secret::SecretError;
+use super::{AudioConfig, CapConfig, MQTTQoS, TelegramConfig, WebhookConfig};
+
+#[derive(Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PayloadFormat {
+    /// The configured payloads, with placeholders expanded.
+    #[default]
+    Text,
+    /// A JSON object describing the event.
+    Json,
+}
+
+#[derive(Deserialize, JsonSchema)]
 pub struct ActionsConfig {
     /// Executable to spawn when seismometer is deemed to be sending
     /// data and running.
@@ -21,6 +35,80 @@ pub struct ActionsConfig {
     /// triggered state to calm state. (When an earthquake is over).
     pub reset_cmd: Option<PathBuf>,
 
+    /// Executable to spawn when an operational problem is detected (such
+    /// as a burst of undecodable packets).
+    pub warning_cmd: Option<PathBuf>,
+
+    /// Executable to spawn when the seismometer's clock is found to
+    /// differ from the host's by more than its configured threshold. The
+    /// offset in seconds (host minus seismometer) is passed as an argument.
+    pub clock_drift_cmd: Option<PathBuf>,
+
+    /// Executable to spawn when an event is over, with the peak ground
+    /// acceleration, velocity and displacement seen during it, and the
+    /// estimated intensity (numeric, then as a Roman numeral), passed as
+    /// arguments.
+    pub ground_motion_cmd: Option<PathBuf>,
+
+    /// Extra arguments to pass to every executable spawned, after the
+    /// usual ones. Placeholders in them are expanded, as in payloads.
+    ///
+    /// Every executable spawned is also given the event's details in its
+    /// environment: RS_EVENT (the event, as in the first argument),
+    /// RS_FLOW, RS_SEISMOMETER, RS_CHANNEL, RS_TIMESTAMP, RS_PEAK (the
+    /// energy, as the "{energy}" placeholder expands to) and RS_EVENT_ID.
+    #[serde(default)]
+    pub cmd_args: Vec<String>,
+
+    /// Longest an executable spawned may run for, in seconds, after which
+    /// it is killed. Executables run in the background, so one which
+    /// hangs doesn't hold up other actions, but those for the same flow
+    /// run one after another, so it does hold up the flow's later ones.
+    /// Default: no limit
+    pub cmd_timeout_s: Option<f32>,
+
+    /// How many more times to try an action that fails: an executable
+    /// that can't be spawned, exits unsuccessfully or times out, or a
+    /// webhook or CAP alert that can't be delivered. Retries are made in
+    /// the background, holding up no other actions. Actions which still
+    /// fail are logged and counted (as "failed_actions" in the daemon
+    /// status). MQTT posts aren't retried, but held while the broker is
+    /// unreachable.
+    /// Default: 0
+    #[serde(default)]
+    pub retries: u32,
+
+    /// How long to wait before the first retry, in seconds. Each wait
+    /// after that is twice as long as the last, up to retry_max_s.
+    /// Default: 1
+    #[serde(default = "default_retry_s")]
+    pub retry_s: f32,
+
+    /// Longest wait between retries, in seconds.
+    /// Default: 30
+    #[serde(default = "default_retry_max_s")]
+    pub retry_max_s: f32,
+
+    /// Least time between the trigger actions taken for the flow, in
+    /// seconds. Triggers which come sooner after the last one acted on
+    /// aren't acted on (nor are their resets), and once the time is up,
+    /// a warning is sent saying how many there were.
+    /// Default: no limit
+    pub trigger_rate_limit_s: Option<f32>,
+
+    /// Least time between the warning actions taken for the flow, in
+    /// seconds. Warnings which come sooner are counted, and summarized
+    /// as for triggers.
+    /// Default: no limit
+    pub warning_rate_limit_s: Option<f32>,
+
+    /// The name of the broker (from the root `mqtt_brokers`) to make
+    /// these actions' MQTT posts to, in place of the main one. Commands
+    /// are still listened for on the main broker, and flows posting
+    /// elsewhere aren't announced to Home Assistant.
+    /// Default: the main broker
+    pub mqtt_broker: Option<String>,
+
     /// MQTT topic to post to when an earthquake is detected.
     pub mqtt_topic: Option<String>,
 
@@ -28,8 +116,90 @@ pub struct ActionsConfig {
     /// seemds to have timed out.
     pub mqtt_available_topic: Option<String>,
 
+    /// Whether posts to the main topic are retained by the broker, so
+    /// that subscribers see the current trigger state as soon as they
+    /// subscribe.
+    /// Default: false
+    #[serde(default)]
+    pub mqtt_retain: bool,
+
+    /// Whether posts to the availability topic are retained by the
+    /// broker.
+    /// Default: false
+    #[serde(default)]
+    pub mqtt_available_retain: bool,
+
+    /// Quality of service (0, 1 or 2) of posts to the main topic.
+    /// Default: 1
+    #[serde(default)]
+    pub mqtt_qos: MQTTQoS,
+
+    /// Quality of service of posts to the availability topic.
+    /// Default: 1
+    #[serde(default)]
+    pub mqtt_available_qos: MQTTQoS,
+
+    /// Quality of service of posts to the warning topic.
+    /// Default: 1
+    #[serde(default)]
+    pub mqtt_warning_qos: MQTTQoS,
+
+    /// Quality of service of posts to the clock drift topic.
+    /// Default: 1
+    #[serde(default)]
+    pub mqtt_clock_drift_qos: MQTTQoS,
+
+    /// Quality of service of posts to the ground motion topic.
+    /// Default: 1
+    #[serde(default)]
+    pub mqtt_ground_motion_qos: MQTTQoS,
+
+    /// Quality of service of posts to the status topic. As these are
+    /// frequent and soon superseded, they are posted at most once by
+    /// default.
+    /// Default: 0
+    #[serde(default = "default_status_qos")]
+    pub mqtt_status_qos: MQTTQoS,
+
+    /// MQTT topic to post a description of operational problems to.
+    pub mqtt_warning_topic: Option<String>,
+
+    /// MQTT topic to post the clock offset, in seconds, to when the
+    /// seismometer's clock drifts beyond its configured threshold.
+    pub mqtt_clock_drift_topic: Option<String>,
+
+    /// MQTT topic to post the peak ground motion of an event to, when
+    /// it is over, as a JSON object with "pga", "pgv", "pgd", "mmi" and
+    /// "intensity" members.
+    pub mqtt_ground_motion_topic: Option<String>,
+
+    /// MQTT topic to post the flow's periodic status to (see the flow's
+    /// status_interval_s), as a JSON object with "dc" and "energy"
+    /// members, and a "profile" member naming the profile in force of a
+    /// flow on a threshold schedule.
+    pub mqtt_status_topic: Option<String>,
+
+    /// MQTT topic on which to listen for commands for the flow: "arm"
+    /// and "disarm" (while disarmed, the flow's trigger actions aren't
+    /// taken, as for the session as a whole), and "test" (which takes its
+    /// trigger actions, then its reset actions, as though an event had
+    /// come and gone).
+    pub mqtt_command_topic: Option<String>,
+
+    /// MQTT topic to post the flow's armed state to, retained, whenever
+    /// it changes. The payloads are those of the session's armed state.
+    pub mqtt_armed_topic: Option<String>,
+
     /// Payload to post to main topic when an earthquake is detected.
     /// Will be sent in UTF-8 encoding.
+    ///
+    /// Payloads may contain placeholders, which are expanded when they
+    /// are posted: "{flow}", "{seismometer}" and "{channel}" expand to
+    /// the flow's names, "{timestamp}" to the data time of the event (in
+    /// seconds since the epoch), "{energy}" to the energy fed to the
+    /// trigger when it asserted (or its peak, once reset), and
+    /// "{event_id}" to the event's identifier (a ULID, which its trigger
+    /// and reset share).
     /// (Only used if mqtt_topic is present.)
     #[serde(default = "default_on_payload")]
     pub mqtt_triggered_payload: String,
@@ -53,6 +223,71 @@ pub struct ActionsConfig {
     /// (Only used if mqtt_availabile_topic is present.)
     #[serde(default = "default_off_payload")]
     pub mqtt_unavailable_payload: String,
+
+    /// Format of the payloads posted to the main and availability
+    /// topics. With "json", the configured payloads are ignored, and a
+    /// JSON object is posted instead, with "event" ("triggered", "reset",
+    /// "available" or "unavailable"), "flow", "seismometer", "channel"
+    /// and "timestamp" members. A trigger's also has "energy", and a
+    /// reset's has "duration_s", "peak_energy" and "ground_motion" (as
+    /// posted to the ground motion topic), where known.
+    /// Default: text
+    #[serde(default)]
+    pub mqtt_payload_format: PayloadFormat,
+
+    /// Telegram bot to send messages through when an earthquake is
+    /// detected.
+    pub telegram: Option<TelegramConfig>,
+
+    /// Sound to play on the host's own speaker when an earthquake is
+    /// detected. (Only if built with the "audio" feature.) A flow
+    /// triggering again while its sound plays starts it over.
+    pub audio: Option<AudioConfig>,
+
+    /// CAP alert to issue when an earthquake is detected.
+    pub cap: Option<CapConfig>,
+
+    /// Web service to post to when an earthquake is detected and over.
+    pub webhook: Option<WebhookConfig>,
+
+    /// Whether to send the flow's events to syslog (as configured under
+    /// outputs), with their details as structured data.
+    /// Default: false
+    #[serde(default)]
+    pub syslog: bool,
+
+    /// Whether to send traps to the SNMP manager (as configured under
+    /// outputs) when an earthquake is detected and over, and when the
+    /// seismometer becomes available or unavailable.
+    /// Default: false
+    #[serde(default)]
+    pub snmp: bool,
+}
+
+impl ActionsConfig {
+    /// Read the actions' secrets from their files or commands, where given
+    /// them.
+    pub(super) fn resolve_secrets(&mut self) -> Result<(), SecretError> {
+        if let Some(telegram) = self.telegram.as_mut() {
+            telegram.resolve_secrets()?;
+        }
+        if let Some(webhook) = self.webhook.as_mut() {
+            webhook.resolve_secrets()?;
+        }
+        Ok(())
+    }
+}
+
+fn default_status_qos() -> MQTTQoS {
+    MQTTQoS::AtMostOnce
+}
+
+fn default_retry_s() -> f32 {
+    1.0
+}
+
+fn default_retry_max_s() -> f32 {
+    30.0
 }
 
 fn default_on_payload() -> String {
@@ -0,0 +1,72 @@
+use std::path::PathBuf;
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+#[derive(Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchiveMode {
+    /// Archive every sample received.
+    #[default]
+    Continuous,
+
+    /// Archive only the samples around triggered events.
+    Event,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ArchiveConfig {
+    /// Root of the SDS directory tree to write miniSEED files into.
+    pub path: PathBuf,
+
+    /// SEED network code (up to two characters).
+    /// Default: "AM"
+    #[serde(default = "default_network")]
+    pub network: String,
+
+    /// SEED station code (up to five characters).
+    pub station: String,
+
+    /// SEED location code (up to two characters).
+    /// Default: "00"
+    #[serde(default = "default_location")]
+    pub location: String,
+
+    /// Whether to archive continuously or only around events.
+    /// Default: continuous
+    #[serde(default)]
+    pub mode: ArchiveMode,
+
+    /// In event mode, seconds of data to keep from before a trigger.
+    /// Default: 30
+    #[serde(default = "default_pre_event_s")]
+    pub pre_event_s: f32,
+
+    /// In event mode, seconds of data to keep after the last reset.
+    /// Default: 60
+    #[serde(default = "default_post_event_s")]
+    pub post_event_s: f32,
+
+    /// Seconds of archived data to replay through the flows at startup,
+    /// with actions suppressed, to warm up filter and trigger state. The
+    /// live data source is opened first, so that its data waits for the
+    /// replay to finish, and then carries on from where the archive ends
+    /// (skipping any of it which the archive already covered).
+    pub backfill_s: Option<f32>,
+}
+
+fn default_network() -> String {
+    String::from("AM")
+}
+
+fn default_location() -> String {
+    String::from("00")
+}
+
+fn default_pre_event_s() -> f32 {
+    30.0
+}
+
+fn default_post_event_s() -> f32 {
+    60.0
+}
@@ -0,0 +1,67 @@
+use std::path::PathBuf;
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ArmedConfig {
+    /// Whether trigger actions are enabled when the program starts.
+    /// Default: true
+    #[serde(default = "default_initially_armed")]
+    pub initially_armed: bool,
+
+    /// MQTT topic on which to listen for arm and disarm commands: either
+    /// the armed and disarmed payloads, or "arm" and "disarm". A "test"
+    /// command tests every flow's actions (as a flow's own command topic
+    /// does).
+    pub mqtt_command_topic: Option<String>,
+
+    /// MQTT topic to post the armed state to, retained, whenever it
+    /// changes.
+    pub mqtt_state_topic: Option<String>,
+
+    /// Payload which arms the session, and which is posted to the state
+    /// topic when it becomes armed.
+    /// Default: "ON"
+    #[serde(default = "default_armed_payload")]
+    pub mqtt_armed_payload: String,
+
+    /// Payload which disarms the session, and which is posted to the state
+    /// topic when it becomes disarmed.
+    /// Default: "OFF"
+    #[serde(default = "default_disarmed_payload")]
+    pub mqtt_disarmed_payload: String,
+
+    /// Path to a GPIO value file (such as
+    /// "/sys/class/gpio/gpio17/value") to read a physical arming switch
+    /// from. A value of "1" arms the session, "0" disarms it. The switch
+    /// is read at startup and then only acted upon when it changes, so
+    /// that other controls may override it in the meantime.
+    pub gpio_path: Option<PathBuf>,
+
+    /// Invert the sense of the GPIO input, so that "0" arms the session.
+    /// Default: false
+    #[serde(default)]
+    pub gpio_active_low: bool,
+
+    /// How often to poll the GPIO input, in seconds.
+    /// Default: 0.5
+    #[serde(default = "default_gpio_poll_s")]
+    pub gpio_poll_s: f32,
+}
+
+fn default_initially_armed() -> bool {
+    true
+}
+
+fn default_armed_payload() -> String {
+    String::from("ON")
+}
+
+fn default_disarmed_payload() -> String {
+    String::from("OFF")
+}
+
+fn default_gpio_poll_s() -> f32 {
+    0.5
+}
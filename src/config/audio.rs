@@ -0,0 +1,40 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::path::PathBuf;
+
+/// A sound to play through the host's default audio output (ALSA, or
+/// PulseAudio through its ALSA plugin) when an earthquake is detected.
+#[derive(Deserialize, JsonSchema)]
+pub struct AudioConfig {
+    /// Path of the sound file to play: a WAV or Ogg Vorbis file.
+    pub file: PathBuf,
+
+    /// Number of times to play the file, one after another, or 0 to play
+    /// it over and over (until the duration is up or, if so configured,
+    /// the trigger resets).
+    /// Default: 1
+    #[serde(default = "default_repeat")]
+    pub repeat: u32,
+
+    /// Longest time to play for, in seconds, however many times the file
+    /// is yet to be played.
+    pub duration_s: Option<f32>,
+
+    /// Whether to stop playing when the flow's trigger resets.
+    /// Default: false
+    #[serde(default)]
+    pub stop_on_reset: bool,
+
+    /// Volume to play at, as a multiple of the file's own.
+    /// Default: 1
+    #[serde(default = "default_volume")]
+    pub volume: f32,
+}
+
+fn default_repeat() -> u32 {
+    1
+}
+
+fn default_volume() -> f32 {
+    1.0
+}
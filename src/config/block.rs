@@ -0,0 +1,501 @@
+use std::path::PathBuf;
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+#[derive(Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OnePolePass {
+    #[default]
+    LowPass,
+    HighPass,
+}
+
+#[derive(Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RectifyMode {
+    #[default]
+    #[serde(alias = "abs")]
+    Absolute,
+    Square,
+}
+
+#[derive(Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TaperWindow {
+    #[default]
+    Hann,
+    Tukey,
+}
+
+/// Which of a filter bank's band energies it outputs: `{ "band": n }`
+/// for the energy in band `n` (counting from zero, in the order listed),
+/// or `{ "ratio": [n, m] }` for the energy in band `n` divided by that in
+/// band `m`.
+#[derive(Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterBankOutputConfig {
+    Band(usize),
+    Ratio([usize; 2]),
+}
+
+impl Default for FilterBankOutputConfig {
+    fn default() -> Self {
+        Self::Band(0)
+    }
+}
+
+/// One stage of a flow's processing pipeline. Signal blocks are applied
+/// in the order listed, and the list must end with a trigger (threshold,
+/// adaptive threshold, Z-detector, kurtosis or template match) block.
+#[derive(Deserialize, JsonSchema, Clone, PartialEq, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BlockConfig {
+    /// Remove an offset from every sample, then multiply by a gain.
+    Affine {
+        /// Default: 0
+        #[serde(default)]
+        offset: f32,
+
+        /// Default: 1
+        #[serde(default = "default_gain")]
+        gain: f32,
+
+        /// If provided, `offset` is ignored, and the offset is instead
+        /// calibrated as the mean of the first this many seconds of
+        /// samples, during which the output is held at zero.
+        offset_calibration_s: Option<f32>,
+    },
+
+    /// Butterworth low-pass filter.
+    LowPass {
+        /// The cutoff frequency, in hertz.
+        cutoff: f32,
+
+        /// Default: 8
+        #[serde(default = "default_order")]
+        order: u8,
+    },
+
+    /// Notch filter, removing a single frequency.
+    Notch {
+        /// The frequency to remove, in hertz.
+        frequency: f32,
+
+        /// The center frequency divided by the width of the stop band.
+        /// Default: 30
+        #[serde(default = "default_notch_q")]
+        q: f32,
+    },
+
+    /// Instrument response removal, converting counts to ground velocity
+    /// or acceleration.
+    Response {
+        /// Zeros of the instrument's transfer function, each as [real,
+        /// imaginary] in radians per second.
+        #[serde(default)]
+        zeros: Vec<[f32; 2]>,
+
+        /// Poles of the instrument's transfer function, each as [real,
+        /// imaginary] in radians per second.
+        #[serde(default)]
+        poles: Vec<[f32; 2]>,
+
+        /// Factor normalizing the poles and zeros' response to one at the
+        /// reference frequency ("A0").
+        /// Default: 1
+        #[serde(default = "default_gain")]
+        normalization: f32,
+
+        /// Counts per unit of ground motion at the reference frequency.
+        sensitivity: f32,
+
+        /// Frequency below which the output is high-passed, in hertz.
+        /// Default: .1
+        #[serde(default = "default_low_corner_hz")]
+        low_corner_hz: f32,
+
+        /// Frequency above which the output is low-passed, if the
+        /// response has more poles than zeros, in hertz. If not provided,
+        /// 80% of the Nyquist frequency.
+        high_corner_hz: Option<f32>,
+    },
+
+    /// One-pole, "alpha/beta" filter.
+    OnePole {
+        /// Decay rate.
+        alpha: f32,
+
+        /// Default: lowpass
+        #[serde(default)]
+        pass: OnePolePass,
+    },
+
+    /// Rectification.
+    Rectify {
+        /// Default: absolute
+        #[serde(default)]
+        mode: RectifyMode,
+    },
+
+    /// Envelope follower, for use after rectification. Trigger times are
+    /// moved back by the lag of its rise behind the signal's.
+    Envelope {
+        /// Decay rate while the signal is rising.
+        /// Default: .5
+        #[serde(default = "default_attack_alpha")]
+        attack_alpha: f32,
+
+        /// Decay rate while the signal is falling.
+        /// Default: .99
+        #[serde(default = "default_release_alpha")]
+        release_alpha: f32,
+    },
+
+    /// Taper each consecutive chunk of the signal to zero at its edges,
+    /// ahead of blocks which analyze it chunk by chunk.
+    Taper {
+        /// Default: hann
+        #[serde(default)]
+        window: TaperWindow,
+
+        /// The fraction of each chunk taken up by its tapered edges, for
+        /// a Tukey window.
+        /// Default: .5
+        #[serde(default = "default_tukey_alpha")]
+        tukey_alpha: f32,
+
+        /// Length of each chunk, in seconds.
+        /// Default: 1
+        #[serde(default = "default_taper_chunk_s")]
+        chunk_s: f32,
+    },
+
+    /// Running RMS over a sliding window.
+    Rms {
+        /// Length of the window, in seconds.
+        /// Default: 1
+        #[serde(default = "default_rms_window_s")]
+        window_s: f32,
+    },
+
+    /// Power in a frequency band, estimated from the power spectral
+    /// density over a sliding window, and optionally divided by the
+    /// power in a reference band.
+    BandPower {
+        /// The band, as [low, high] in hertz.
+        band: [f32; 2],
+
+        /// The reference band, as [low, high] in hertz.
+        reference_band: Option<[f32; 2]>,
+
+        /// Length of the window, in seconds.
+        /// Default: 5
+        #[serde(default = "default_band_power_window_s")]
+        window_s: f32,
+    },
+
+    /// Bank of band-pass filters, measuring the smoothed energy in each
+    /// of several bands and outputting one band's energy, or the ratio of
+    /// two bands' energies.
+    FilterBank {
+        /// The bands, each as [low, high] in hertz.
+        bands: Vec<[f32; 2]>,
+
+        /// The order of each band's band-pass filter.
+        /// Default: 4
+        #[serde(default = "default_filter_bank_order")]
+        order: u8,
+
+        /// Energy smoothing decay rate.
+        /// Default: .99
+        #[serde(default = "default_energy_alpha")]
+        energy_alpha: f32,
+
+        /// Default: { "band": 0 }
+        #[serde(default)]
+        output: FilterBankOutputConfig,
+    },
+
+    /// Threshold trigger.
+    Threshold {
+        /// Level required to enable the trigger.
+        /// Default: 1
+        #[serde(default = "default_trigger_level")]
+        trigger_level: f32,
+
+        /// Level required to reset the trigger.
+        /// Default: 0
+        #[serde(default)]
+        reset_level: f32,
+
+        /// Number of samples to process before enabling the trigger.
+        /// Default: 0
+        #[serde(default)]
+        holdoff: usize,
+
+        /// How long the trigger level must be exceeded for before
+        /// triggering, in seconds.
+        /// Default: 0
+        #[serde(default)]
+        min_duration_s: f32,
+
+        /// How long the trigger may stay asserted before it is forcibly
+        /// reset, in seconds. If not provided, it may stay asserted
+        /// indefinitely.
+        max_event_s: Option<f32>,
+    },
+
+    /// Threshold trigger with levels relative to the signal's long-term
+    /// noise floor.
+    AdaptiveThreshold {
+        /// Multiple of the noise floor required to enable the trigger.
+        /// Default: 4
+        #[serde(default = "default_trigger_ratio")]
+        trigger_ratio: f32,
+
+        /// Multiple of the noise floor required to reset the trigger.
+        /// Default: 2
+        #[serde(default = "default_reset_ratio")]
+        reset_ratio: f32,
+
+        /// Length of the window over which the noise floor is estimated,
+        /// in seconds.
+        /// Default: 300
+        #[serde(default = "default_noise_window_s")]
+        window_s: f32,
+
+        /// Number of samples to process before enabling the trigger.
+        /// Default: 0
+        #[serde(default)]
+        holdoff: usize,
+
+        /// How long the trigger level must be exceeded for before
+        /// triggering, in seconds.
+        /// Default: 0
+        #[serde(default)]
+        min_duration_s: f32,
+
+        /// How long the trigger may stay asserted before it is forcibly
+        /// reset, in seconds. If not provided, it may stay asserted
+        /// indefinitely.
+        max_event_s: Option<f32>,
+    },
+
+    /// Z-detector: triggers on the short-term average energy of the
+    /// signal, in standard deviations from its long-term mean.
+    ZDetector {
+        /// Length of the short-term average window, in seconds.
+        /// Default: 1
+        #[serde(default = "default_z_short_s")]
+        short_s: f32,
+
+        /// Time constant of the long-term statistics, in seconds.
+        /// Default: 30
+        #[serde(default = "default_z_long_s")]
+        long_s: f32,
+
+        /// Standard deviations above the mean required to enable the
+        /// trigger.
+        /// Default: 3
+        #[serde(default = "default_trigger_z")]
+        trigger_z: f32,
+
+        /// Standard deviations above the mean required to reset the
+        /// trigger.
+        /// Default: 1
+        #[serde(default = "default_reset_z")]
+        reset_z: f32,
+
+        /// Number of samples to process before enabling the trigger.
+        /// Default: 0
+        #[serde(default)]
+        holdoff: usize,
+
+        /// How long the trigger level must be exceeded for before
+        /// triggering, in seconds.
+        /// Default: 0
+        #[serde(default)]
+        min_duration_s: f32,
+
+        /// How long the trigger may stay asserted before it is forcibly
+        /// reset, in seconds. If not provided, it may stay asserted
+        /// indefinitely.
+        max_event_s: Option<f32>,
+    },
+
+    /// Onset detector triggering on the kurtosis of the signal, which
+    /// rises sharply for impulsive arrivals but not for emergent noise.
+    Kurtosis {
+        /// Length of the window over which kurtosis is measured, in
+        /// seconds.
+        /// Default: 3
+        #[serde(default = "default_kurtosis_window_s")]
+        window_s: f32,
+
+        /// Excess kurtosis required to enable the trigger.
+        /// Default: 5
+        #[serde(default = "default_kurtosis_trigger_level")]
+        trigger_level: f32,
+
+        /// Excess kurtosis required to reset the trigger.
+        /// Default: 1
+        #[serde(default = "default_kurtosis_reset_level")]
+        reset_level: f32,
+
+        /// Number of samples to process before enabling the trigger.
+        /// Default: 0
+        #[serde(default)]
+        holdoff: usize,
+
+        /// How long the trigger level must be exceeded for before
+        /// triggering, in seconds.
+        /// Default: 0
+        #[serde(default)]
+        min_duration_s: f32,
+
+        /// How long the trigger may stay asserted before it is forcibly
+        /// reset, in seconds. If not provided, it may stay asserted
+        /// indefinitely.
+        max_event_s: Option<f32>,
+    },
+
+    /// Detector triggering when the signal correlates well with any of a
+    /// set of recorded waveform templates, such as those of a nearby
+    /// quarry's blasts. Templates must be recorded at the same point in
+    /// the pipeline as the detector (the filtered column of a debug dump
+    /// taken there will do), and sources which should be told apart
+    /// need flows of their own.
+    TemplateMatch {
+        /// Files holding the templates, one sample per line. If a line
+        /// has several columns, the last is taken as the sample.
+        templates: Vec<PathBuf>,
+
+        /// Normalized correlation (at most 1) required to enable the
+        /// trigger.
+        /// Default: 0.7
+        #[serde(default = "default_template_trigger_level")]
+        trigger_level: f32,
+
+        /// Normalized correlation required to reset the trigger.
+        /// Default: 0.5
+        #[serde(default = "default_template_reset_level")]
+        reset_level: f32,
+
+        /// Number of samples to process before enabling the trigger.
+        /// Default: 0
+        #[serde(default)]
+        holdoff: usize,
+
+        /// How long the trigger level must be exceeded for before
+        /// triggering, in seconds.
+        /// Default: 0
+        #[serde(default)]
+        min_duration_s: f32,
+
+        /// How long the trigger may stay asserted before it is forcibly
+        /// reset, in seconds. If not provided, it may stay asserted
+        /// indefinitely.
+        max_event_s: Option<f32>,
+    },
+}
+
+fn default_gain() -> f32 {
+    1.0
+}
+
+fn default_order() -> u8 {
+    8
+}
+
+fn default_notch_q() -> f32 {
+    30.0
+}
+
+fn default_low_corner_hz() -> f32 {
+    0.1
+}
+
+fn default_attack_alpha() -> f32 {
+    0.5
+}
+
+fn default_release_alpha() -> f32 {
+    0.99
+}
+
+fn default_tukey_alpha() -> f32 {
+    0.5
+}
+
+fn default_taper_chunk_s() -> f32 {
+    1.0
+}
+
+fn default_rms_window_s() -> f32 {
+    1.0
+}
+
+fn default_band_power_window_s() -> f32 {
+    5.0
+}
+
+fn default_filter_bank_order() -> u8 {
+    4
+}
+
+fn default_energy_alpha() -> f32 {
+    0.99
+}
+
+fn default_trigger_level() -> f32 {
+    1.0
+}
+
+pub(super) fn default_trigger_ratio() -> f32 {
+    4.0
+}
+
+pub(super) fn default_reset_ratio() -> f32 {
+    2.0
+}
+
+fn default_noise_window_s() -> f32 {
+    300.0
+}
+
+fn default_z_short_s() -> f32 {
+    1.0
+}
+
+fn default_z_long_s() -> f32 {
+    30.0
+}
+
+fn default_trigger_z() -> f32 {
+    3.0
+}
+
+fn default_reset_z() -> f32 {
+    1.0
+}
+
+fn default_kurtosis_window_s() -> f32 {
+    3.0
+}
+
+fn default_kurtosis_trigger_level() -> f32 {
+    5.0
+}
+
+fn default_kurtosis_reset_level() -> f32 {
+    1.0
+}
+
+fn default_template_trigger_level() -> f32 {
+    0.7
+}
+
+fn default_template_reset_level() -> f32 {
+    0.5
+}
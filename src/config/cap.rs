@@ -0,0 +1,140 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::path::PathBuf;
+
+/// The status of an alert, as CAP names it.
+#[derive(Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum CapStatus {
+    #[default]
+    Actual,
+    Exercise,
+    System,
+    Test,
+    Draft,
+}
+
+/// How soon action should be taken, as CAP names it.
+#[derive(Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum CapUrgency {
+    #[default]
+    Immediate,
+    Expected,
+    Future,
+    Past,
+    Unknown,
+}
+
+/// How severe the threat is, as CAP names it.
+#[derive(Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum CapSeverity {
+    Extreme,
+    #[default]
+    Severe,
+    Moderate,
+    Minor,
+    Unknown,
+}
+
+/// How certain the threat is, as CAP names it.
+#[derive(Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum CapCertainty {
+    Observed,
+    #[default]
+    Likely,
+    Possible,
+    Unlikely,
+    Unknown,
+}
+
+/// A Common Alerting Protocol (CAP 1.2) alert to issue when an
+/// earthquake is detected. It is dropped into a directory, posted to a
+/// URL, or both. Placeholders in its texts are expanded, as in MQTT
+/// payloads.
+#[derive(Deserialize, JsonSchema)]
+pub struct CapConfig {
+    /// Directory to drop each alert into, as a file named after its
+    /// identifier (with ".xml" appended).
+    pub directory: Option<PathBuf>,
+
+    /// URL to post each alert to. Only plain HTTP is supported.
+    pub url: Option<String>,
+
+    /// Identifier of the sender of alerts, such as an email address.
+    /// Default: "seismo"
+    #[serde(default = "default_sender")]
+    pub sender: String,
+
+    /// Status of alerts: "Actual", "Exercise", "System", "Test" or
+    /// "Draft".
+    /// Default: "Actual"
+    #[serde(default)]
+    pub status: CapStatus,
+
+    /// Type of event the alert is about.
+    /// Default: "Earthquake"
+    #[serde(default = "default_event")]
+    pub event: String,
+
+    /// Urgency of alerts: "Immediate", "Expected", "Future", "Past" or
+    /// "Unknown".
+    /// Default: "Immediate"
+    #[serde(default)]
+    pub urgency: CapUrgency,
+
+    /// Severity of alerts: "Extreme", "Severe", "Moderate", "Minor" or
+    /// "Unknown". (A tier's actions may issue alerts of their own, more
+    /// severe ones.)
+    /// Default: "Severe"
+    #[serde(default)]
+    pub severity: CapSeverity,
+
+    /// Certainty of alerts: "Observed", "Likely", "Possible", "Unlikely"
+    /// or "Unknown".
+    /// Default: "Likely"
+    #[serde(default)]
+    pub certainty: CapCertainty,
+
+    /// Headline of alerts.
+    /// Default: "Earthquake detected by {flow}"
+    #[serde(default = "default_headline")]
+    pub headline: String,
+
+    /// Longer description of alerts, if any.
+    pub description: Option<String>,
+
+    /// Recommended action to take, if any.
+    pub instruction: Option<String>,
+
+    /// How long alerts are in effect for, in seconds from when they are
+    /// sent.
+    /// Default: 3600
+    #[serde(default = "default_expires_s")]
+    pub expires_s: f32,
+
+    /// Description of the area affected, such as the name of the site.
+    pub area_desc: String,
+
+    /// The area affected, as a circle: "latitude,longitude radius", with
+    /// the radius in kilometers.
+    pub circle: Option<String>,
+
+    /// The area affected, as a polygon: space-separated
+    /// "latitude,longitude" pairs, the first and last the same.
+    pub polygon: Option<String>,
+}
+
+fn default_sender() -> String {
+    String::from("seismo")
+}
+
+fn default_event() -> String {
+    String::from("Earthquake")
+}
+
+fn default_headline() -> String {
+    String::from("Earthquake detected by {flow}")
+}
+
+fn default_expires_s() -> f32 {
+    3600.0
+}
@@ -0,0 +1,29 @@
+use std::path::PathBuf;
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+#[derive(Deserialize, JsonSchema)]
+pub struct CaptureConfig {
+    /// Directory in which to save waveform snippets. Files are named
+    /// after the flow and the data time of the trigger.
+    pub directory: PathBuf,
+
+    /// Seconds of input to include from before the trigger.
+    /// Default: 10
+    #[serde(default = "default_pre_trigger_s")]
+    pub pre_trigger_s: f32,
+
+    /// Seconds of input to include after the trigger resets.
+    /// Default: 30
+    #[serde(default = "default_post_trigger_s")]
+    pub post_trigger_s: f32,
+}
+
+fn default_pre_trigger_s() -> f32 {
+    10.0
+}
+
+fn default_post_trigger_s() -> f32 {
+    30.0
+}
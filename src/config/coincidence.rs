@@ -0,0 +1,39 @@
+use super::actions::ActionsConfig;
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+/// A seismometer-level trigger which fires only when several of the
+/// seismometer's flows trigger at around the same time, as an earthquake
+/// would make them (but a noise spike on one channel wouldn't).
+#[derive(Deserialize, JsonSchema)]
+pub struct CoincidenceConfig {
+    /// A name for the coincidence trigger.
+    pub name: String,
+
+    /// The names of the seismometer's flows to watch.
+    pub flows: Vec<String>,
+
+    /// How many of the flows must trigger for the coincidence trigger to
+    /// fire.
+    /// Default: 2
+    #[serde(default = "default_min_flows")]
+    pub min_flows: usize,
+
+    /// How close together the flows must trigger, in seconds of data
+    /// time. A flow counts towards the coincidence while triggered, and
+    /// with any flow triggering within this long of its trigger.
+    /// Default: 5
+    #[serde(default = "default_window_s")]
+    pub window_s: f32,
+
+    /// Actions to take on coincidence trigger events.
+    pub actions: ActionsConfig,
+}
+
+fn default_min_flows() -> usize {
+    2
+}
+
+fn default_window_s() -> f32 {
+    5.0
+}
@@ -0,0 +1,19 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::path::PathBuf;
+
+/// A local control socket, through which the daemon may be inspected and
+/// adjusted from the shell (with the "ctl" command).
+#[derive(Deserialize, JsonSchema)]
+pub struct ControlConfig {
+    /// Path of the Unix-domain socket to listen on, such as
+    /// "/run/seismo/control.sock". Anyone who can write to it may arm and
+    /// disarm the daemon, so its directory's permissions should keep it
+    /// to the operators.
+    pub socket: PathBuf,
+
+    /// Directory into which the "dump-start" command may write dumps of
+    /// flows' filter processes, each to a new file named in the request.
+    /// Default: dumps can't be started over the socket
+    pub dump_dir: Option<PathBuf>,
+}
@@ -0,0 +1,40 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+#[derive(Clone, Deserialize, JsonSchema)]
+pub struct EarthwormConfig {
+    /// Address ("host:port") of the Earthworm export module to connect to.
+    pub address: String,
+
+    /// Only accept trace data for this station code. If not provided,
+    /// data from every station is accepted.
+    pub station: Option<String>,
+
+    /// Installation id to send heartbeats as.
+    /// Default: 0
+    #[serde(default)]
+    pub installation: u8,
+
+    /// Module id to send heartbeats as.
+    /// Default: 0
+    #[serde(default)]
+    pub module: u8,
+
+    /// Heartbeat text the export module expects ("RcvAliveText").
+    /// Default: "alive"
+    #[serde(default = "default_heartbeat_text")]
+    pub heartbeat_text: String,
+
+    /// How often to send heartbeats, in seconds ("RcvAliveInt").
+    /// Default: 30
+    #[serde(default = "default_heartbeat_interval_s")]
+    pub heartbeat_interval_s: f32,
+}
+
+fn default_heartbeat_text() -> String {
+    String::from("alive")
+}
+
+fn default_heartbeat_interval_s() -> f32 {
+    30.0
+}
@@ -1,19 +1,57 @@
+use super::block::{default_reset_ratio, default_trigger_ratio};
+use super::block::{BlockConfig, OnePolePass, RectifyMode};
+use super::flow::FlowTap;
+use schemars::JsonSchema;
 use serde::Deserialize;
+use serde_json::Value;
+use thiserror::Error;
 
-#[derive(Deserialize)]
+#[derive(Debug, Error)]
+pub enum FilterProfileError {
+    #[error("flow {flow} names filter profile {profile}, which isn't defined")]
+    Unknown { flow: String, profile: String },
+    #[error("filter profile {0} is not a JSON object")]
+    NotAnObject(String),
+}
+
+#[derive(Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum EnergyDetector {
+    /// Square the signal and smooth it with a one-pole filter, giving a
+    /// measure of signal power.
+    #[default]
+    Power,
+
+    /// Follow the envelope of the rectified signal, rising quickly and
+    /// decaying slowly. This gives a measure of signal amplitude (not
+    /// power) and so needs different trigger levels.
+    Envelope,
+
+    /// Take the RMS of the signal over a sliding window, which forgets a
+    /// transient once it has passed through the window. This too
+    /// measures amplitude.
+    Rms,
+}
+
+#[derive(Deserialize, JsonSchema)]
 pub struct FilterConfig {
     /// Energy level required to enable the trigger (after all filtering)
-    #[serde(default = "default_trigger_level")]
-    pub trigger_level: f32,
+    /// Default: 1, or 4 times the noise floor, if it is estimated
+    pub trigger_level: Option<f32>,
 
     /// Energy level requried to reset the trigger
-    #[serde(default = "default_reset_level")]
-    pub reset_level: f32,
+    /// Default: 0, or 2 times the noise floor, if it is estimated
+    pub reset_level: Option<f32>,
 
     /// A value to remove from every sample before processing.
     #[serde(default = "default_offset")]
     pub offset: f32,
 
+    /// If provided, `offset` is ignored, and the offset is instead
+    /// calibrated as the mean of the raw samples over this many seconds
+    /// after startup (during which the trigger can't fire).
+    pub offset_calibration_s: Option<f32>,
+
     /// A value to mutiply each sample by after removing any offset.
     #[serde(default = "default_gain")]
     pub gain: f32,
@@ -28,6 +66,17 @@ pub struct FilterConfig {
     #[serde(default = "default_cutoff_freq")]
     pub cutoff: f32,
 
+    /// The frequency of a notch filter to apply ahead of the detection
+    /// filter, in hertz, for removing mains hum. If not provided, no
+    /// notch filter is used.
+    pub notch_hz: Option<f32>,
+
+    /// The quality factor of the notch filter: its center frequency
+    /// divided by the width of the stop band.
+    /// Default: 30
+    #[serde(default = "default_notch_q")]
+    pub notch_q: f32,
+
     /// DC-offset tracking decay rate/'alpha'
     /// Default: .99
     #[serde(default = "default_dc_alpha")]
@@ -38,9 +87,158 @@ pub struct FilterConfig {
     #[serde(default = "default_energy_alpha")]
     pub energy_alpha: f32,
 
+    /// How signal energy is measured ahead of the trigger.
+    /// Default: power
+    #[serde(default)]
+    pub energy_detector: EnergyDetector,
+
+    /// How the signal is rectified ahead of energy detection. (The RMS
+    /// detector doesn't rectify.)
+    /// Default: square for the power detector, absolute for the envelope
+    /// detector
+    pub rectify: Option<RectifyMode>,
+
+    /// Envelope detector decay rate/'alpha' while the signal is rising
+    /// (`energy_alpha` is used while it falls).
+    /// Default: .5
+    #[serde(default = "default_envelope_attack_alpha")]
+    pub envelope_attack_alpha: f32,
+
+    /// Length of the RMS detector's window, in seconds.
+    /// Default: 1
+    #[serde(default = "default_rms_window_s")]
+    pub rms_window_s: f32,
+
+    /// If provided, `trigger_level` and `reset_level` are taken as
+    /// multiples of the signal's noise floor, estimated over this many
+    /// seconds, rather than as absolute levels, and default to those of
+    /// the adaptive threshold block.
+    pub noise_floor_window_s: Option<f32>,
+
     /// Number of samples to process before enabling trigger.
     #[serde(default = "default_holdoff")]
     pub holdoff: usize,
+
+    /// How long the energy must stay above the trigger level before
+    /// triggering, in seconds, so that a lone outlying sample can't.
+    /// Default: 0
+    #[serde(default)]
+    pub min_duration_s: f32,
+
+    /// How long the trigger may stay asserted before it is forcibly
+    /// reset, in seconds, as when a knocked sensor leaves a step in the
+    /// signal. If not provided, it may stay asserted indefinitely.
+    pub max_event_s: Option<f32>,
+}
+
+impl FilterConfig {
+    /// The classic processing pipeline these settings describe: affine
+    /// transform, (notch and) low-pass filter, DC removal, energy
+    /// detection and threshold trigger.
+    pub fn blocks(&self, tap: FlowTap) -> Vec<BlockConfig> {
+        let mut blocks = Vec::new();
+        if tap == FlowTap::Filtered {
+            blocks.push(BlockConfig::Affine {
+                offset: self.offset,
+                gain: self.gain,
+                offset_calibration_s: self.offset_calibration_s,
+            });
+            if let Some(frequency) = self.notch_hz {
+                blocks.push(BlockConfig::Notch {
+                    frequency,
+                    q: self.notch_q,
+                });
+            }
+            blocks.push(BlockConfig::LowPass {
+                cutoff: self.cutoff,
+                order: self.order,
+            });
+        }
+        blocks.push(BlockConfig::OnePole {
+            alpha: self.dc_alpha,
+            pass: OnePolePass::HighPass,
+        });
+        match self.energy_detector {
+            EnergyDetector::Power => {
+                blocks.push(BlockConfig::Rectify {
+                    mode: self.rectify.unwrap_or(RectifyMode::Square),
+                });
+                blocks.push(BlockConfig::OnePole {
+                    alpha: self.energy_alpha,
+                    pass: OnePolePass::LowPass,
+                });
+            }
+            EnergyDetector::Envelope => {
+                blocks.push(BlockConfig::Rectify {
+                    mode: self.rectify.unwrap_or(RectifyMode::Absolute),
+                });
+                blocks.push(BlockConfig::Envelope {
+                    attack_alpha: self.envelope_attack_alpha,
+                    release_alpha: self.energy_alpha,
+                });
+            }
+            EnergyDetector::Rms => {
+                blocks.push(BlockConfig::Rms {
+                    window_s: self.rms_window_s,
+                });
+            }
+        }
+        blocks.push(match self.noise_floor_window_s {
+            Some(window_s) => BlockConfig::AdaptiveThreshold {
+                trigger_ratio: self.trigger_level.unwrap_or_else(default_trigger_ratio),
+                reset_ratio: self.reset_level.unwrap_or_else(default_reset_ratio),
+                window_s,
+                holdoff: self.holdoff,
+                min_duration_s: self.min_duration_s,
+                max_event_s: self.max_event_s,
+            },
+            None => BlockConfig::Threshold {
+                trigger_level: self.trigger_level.unwrap_or_else(default_trigger_level),
+                reset_level: self.reset_level.unwrap_or_else(default_reset_level),
+                holdoff: self.holdoff,
+                min_duration_s: self.min_duration_s,
+                max_event_s: self.max_event_s,
+            },
+        });
+        blocks
+    }
+}
+
+/// Give each flow of a configuration (as JSON) which names a filter
+/// profile the profile's parameters, except where its own filter gives
+/// them.
+pub fn apply_filter_profiles(config: &mut Value) -> Result<(), FilterProfileError> {
+    let profiles = config.get("filter_profiles").cloned().unwrap_or_default();
+    let Some(seismometers) = config.get_mut("seismometers").and_then(Value::as_array_mut) else {
+        return Ok(());
+    };
+    let flows = seismometers
+        .iter_mut()
+        .filter_map(|seismometer| seismometer.get_mut("flows")?.as_array_mut())
+        .flatten();
+    for flow in flows {
+        let Some(profile) = flow.get("filter_profile").and_then(Value::as_str) else {
+            continue;
+        };
+        let Some(parameters) = profiles.get(profile) else {
+            return Err(FilterProfileError::Unknown {
+                flow: flow
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_owned(),
+                profile: profile.to_owned(),
+            });
+        };
+        let Value::Object(mut filter) = parameters.clone() else {
+            return Err(FilterProfileError::NotAnObject(profile.to_owned()));
+        };
+        if let Some(Value::Object(own)) = flow.get_mut("filter").map(Value::take) {
+            filter.extend(own);
+        }
+        flow["filter"] = Value::Object(filter);
+    }
+    Ok(())
 }
 
 fn default_trigger_level() -> f32 {
@@ -67,6 +265,10 @@ fn default_cutoff_freq() -> f32 {
     8.0
 }
 
+fn default_notch_q() -> f32 {
+    30.0
+}
+
 fn default_dc_alpha() -> f32 {
     0.99
 }
@@ -75,6 +277,72 @@ fn default_energy_alpha() -> f32 {
     0.99
 }
 
+fn default_envelope_attack_alpha() -> f32 {
+    0.5
+}
+
+fn default_rms_window_s() -> f32 {
+    1.0
+}
+
 fn default_holdoff() -> usize {
     0
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_overrides_rectification() {
+        let filter: FilterConfig = serde_json::from_str(r#"{"rectify": "abs"}"#).expect("parse");
+        assert!(filter
+            .blocks(FlowTap::Filtered)
+            .contains(&BlockConfig::Rectify {
+                mode: RectifyMode::Absolute
+            }));
+    }
+
+    #[test]
+    fn it_defaults_levels_relative_to_the_noise_floor() {
+        let filter: FilterConfig =
+            serde_json::from_str(r#"{"noise_floor_window_s": 60}"#).expect("parse");
+        let Some(BlockConfig::AdaptiveThreshold {
+            trigger_ratio,
+            reset_ratio,
+            ..
+        }) = filter.blocks(FlowTap::Filtered).pop()
+        else {
+            panic!("not an adaptive threshold");
+        };
+        assert_eq!((trigger_ratio, reset_ratio), (4.0, 2.0));
+    }
+
+    #[test]
+    fn it_applies_profiles() {
+        let mut config = serde_json::json!({
+            "filter_profiles": { "quiet": { "cutoff": 4, "trigger_level": 20 } },
+            "seismometers": [{ "flows": [
+                { "name": "a", "filter_profile": "quiet" },
+                { "name": "b", "filter_profile": "quiet", "filter": { "trigger_level": 30 } },
+                { "name": "c", "filter": { "trigger_level": 40 } },
+            ]}],
+        });
+        apply_filter_profiles(&mut config).expect("applies");
+        let flows = &config["seismometers"][0]["flows"];
+        assert_eq!(
+            flows[0]["filter"],
+            serde_json::json!({ "cutoff": 4, "trigger_level": 20 })
+        );
+        assert_eq!(
+            flows[1]["filter"],
+            serde_json::json!({ "cutoff": 4, "trigger_level": 30 })
+        );
+        assert_eq!(
+            flows[2]["filter"],
+            serde_json::json!({ "trigger_level": 40 })
+        );
+        config["seismometers"][0]["flows"][0]["filter_profile"] = "loud".into();
+        assert!(apply_filter_profiles(&mut config).is_err());
+    }
+}
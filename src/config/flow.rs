@@ -1,18 +1,391 @@
 use super::actions::ActionsConfig;
+use super::block::BlockConfig;
+use super::capture::CaptureConfig;
 use super::filter::FilterConfig;
+use super::picker::PickerConfig;
+use super::schedule::ThresholdProfileConfig;
+use super::tier::TierConfig;
+use crate::datasource::Channel;
+use schemars::JsonSchema;
 use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use thiserror::Error;
 
-#[derive(Deserialize)]
+#[derive(Debug, Error)]
+pub enum ChannelListError {
+    #[error("flow {0} lists no channels")]
+    Empty(String),
+    #[error("flow {0} lists a channel which isn't a string")]
+    NotAString(String),
+    #[error("flow {0} would be copied for a channel under the name of another flow")]
+    DuplicateName(String),
+    #[error("flow {0} would post every channel to the same {1}; put \"{{channel}}\" in it")]
+    SharedTopic(String, &'static str),
+}
+
+/// Topics which each copy of a flow must post to on its own, the posts to
+/// them being retained as the copy's state.
+const OWN_TOPICS: [&str; 2] = ["mqtt_topic", "mqtt_available_topic"];
+
+#[derive(Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FlowTap {
+    /// Process the stream after the affine transform and low-pass filter.
+    /// (Only meaningful for the classic processing pipeline.)
+    #[default]
+    Filtered,
+
+    /// Process the raw counts stream, bypassing the affine transform and
+    /// low-pass filter (for clipping detection, DC monitoring, etc).
+    Raw,
+}
+
+#[derive(Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Precision {
+    /// Single precision, which is enough for most pipelines.
+    #[default]
+    F32,
+
+    /// Double precision, for pipelines whose filters have long time
+    /// constants (such as one-pole filters with alpha close to 1).
+    F64,
+}
+
+#[derive(Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DumpFormat {
+    /// Columns separated by spaces, as the -o option writes.
+    #[default]
+    Text,
+
+    /// Columns separated by commas, after a header line naming them.
+    Csv,
+}
+
+#[derive(Deserialize, JsonSchema)]
 pub struct FlowConfig {
     /// A name for the flow (so that it can be targetted later).
     pub name: String,
 
-    /// The channel to observe from the seismometer.
-    pub channel: String,
+    /// Whether the flow is run. A disabled flow is kept in the
+    /// configuration, tuning and all, but isn't run, and coincidence and
+    /// network triggers watching it go without it.
+    /// Default: true
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
 
-    /// Filter and trigger parameters.
-    pub filter: FilterConfig,
+    /// The channel to observe from the seismometer, by its SEED code (in
+    /// any case). Given a list of channels, the flow is copied for each
+    /// of them as the configuration is loaded, each copy (and its tiers)
+    /// named after the channel too (as "garage-EHZ"), and coincidence and
+    /// network triggers watching the flow watch every copy of it. Any
+    /// "{channel}" in the copies' MQTT topics is replaced by the channel,
+    /// which their `mqtt_topic` and `mqtt_available_topic` must contain,
+    /// as the copies can't share them.
+    #[schemars(with = "ChannelList")]
+    pub channel: Channel,
+
+    /// How long to wait for data on the flow's channel before declaring
+    /// it unavailable, in seconds, in place of the seismometer's
+    /// timeout_s (as for a channel which is only sent while it is
+    /// shaken). If flows on the same channel give different timeouts,
+    /// the longest is used.
+    pub timeout_s: Option<f32>,
+
+    /// The sample rate of the flow's channel, in hertz, in place of the
+    /// seismometer's sample_rate (as for a channel sent at a rate of its
+    /// own). Filters are designed, and durations in seconds converted to
+    /// samples, at this rate. Flows on the same channel must agree on it.
+    pub sample_rate: Option<f32>,
+
+    /// Which point of the stream the trigger stages are fed from.
+    /// Default: filtered
+    #[serde(default)]
+    pub tap: FlowTap,
+
+    /// The precision at which the flow's pipeline processes samples.
+    /// Default: f32
+    #[serde(default)]
+    pub precision: Precision,
+
+    /// Filter and trigger parameters for the classic processing pipeline.
+    /// Those not given here are taken from the filter profile, if the
+    /// flow names one.
+    pub filter: Option<FilterConfig>,
+
+    /// The name of a filter profile (from the root `filter_profiles`) to
+    /// take the flow's filter parameters from.
+    pub filter_profile: Option<String>,
+
+    /// A processing pipeline to use instead of the classic one: signal
+    /// blocks applied in order, ending with a trigger block.
+    pub blocks: Option<Vec<BlockConfig>>,
 
     /// Actions to take on events.
     pub actions: ActionsConfig,
+
+    /// Optional capture of the input waveform around each trigger.
+    pub capture: Option<CaptureConfig>,
+
+    /// Optional AR-AIC picking of the onset time of each trigger, which
+    /// is reported along with it.
+    pub picker: Option<PickerConfig>,
+
+    /// How often to report the flow's DC level (the mean of its raw
+    /// input) and the peak energy fed to its trigger, in seconds.
+    /// Default: never
+    pub status_interval_s: Option<f32>,
+
+    /// Tiers of severity that the flow's events may escalate to, in
+    /// order of increasing level. The flow's own actions are taken when
+    /// any event triggers, and each tier's as an event escalates to it
+    /// (and each tier below it).
+    #[serde(default)]
+    pub tiers: Vec<TierConfig>,
+
+    /// Trigger levels for parts of the day (by the host's local time of
+    /// the data), in place of the flow's own, such as higher ones while
+    /// the household is up and about. The first profile in force at a
+    /// time is the one used, and the flow's own levels outside them all.
+    /// Levels adjusted while the daemon runs last until the profile in
+    /// force next changes. Only flows with threshold triggers may have
+    /// a schedule.
+    #[serde(default)]
+    pub threshold_schedule: Vec<ThresholdProfileConfig>,
+
+    /// File to dump the flow's filter process to, a line per sample (its
+    /// time and its input, affined, filtered, DC-removed and energy
+    /// values), added to for as long as the flow runs, across restarts.
+    /// The -o option takes precedence. Rotated files have ".1", ".2" and
+    /// so on appended, ".1" being the newest.
+    pub dump_path: Option<PathBuf>,
+
+    /// How to write the dump: "text" or "csv".
+    /// Default: text
+    #[serde(default)]
+    pub dump_format: DumpFormat,
+
+    /// Size, in megabytes, past which the dump is rotated, if any.
+    pub dump_max_size_mb: Option<f32>,
+
+    /// Number of rotated dumps to keep. Older ones are deleted.
+    /// Default: 5
+    #[serde(default = "default_dump_keep")]
+    pub dump_keep: usize,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_dump_keep() -> usize {
+    5
+}
+
+/// A flow's channel, as a configuration file may give it.
+#[derive(JsonSchema)]
+#[serde(untagged)]
+#[allow(dead_code)]
+enum ChannelList {
+    One(Channel),
+    Several(Vec<Channel>),
+}
+
+/// Replace each flow which lists several channels with a copy of it for
+/// each channel, and have the triggers watching it watch every copy.
+///
+/// Flows are numbered after this (as by `--set` and the environment, which
+/// override the merged configuration), so a flow following one which
+/// lists several channels is found further down the list.
+pub fn expand_channel_lists(config: &mut Value) -> Result<(), ChannelListError> {
+    let mut copies: HashMap<String, Vec<Value>> = HashMap::new();
+    let Some(seismometers) = config.get_mut("seismometers").and_then(Value::as_array_mut) else {
+        return Ok(());
+    };
+    for seismometer in seismometers.iter_mut() {
+        let Some(flows) = seismometer.get_mut("flows").and_then(Value::as_array_mut) else {
+            continue;
+        };
+        let mut taken: HashSet<String> = flows
+            .iter()
+            .filter(|flow| !flow.get("channel").is_some_and(Value::is_array))
+            .filter_map(|flow| flow.get("name").and_then(Value::as_str))
+            .map(str::to_owned)
+            .collect();
+        for flow in std::mem::take(flows) {
+            let Some(channels) = flow.get("channel").and_then(Value::as_array) else {
+                flows.push(flow);
+                continue;
+            };
+            let name = flow
+                .get("name")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_owned();
+            if channels.is_empty() {
+                return Err(ChannelListError::Empty(name));
+            }
+            let mut names = Vec::new();
+            for channel in channels {
+                let Some(channel) = channel.as_str().map(str::to_ascii_uppercase) else {
+                    return Err(ChannelListError::NotAString(name));
+                };
+                let mut copy = flow.clone();
+                copy["name"] = format!("{name}-{channel}").into();
+                let tiers = copy.get_mut("tiers").and_then(Value::as_array_mut);
+                for tier in tiers.into_iter().flatten() {
+                    if let Some(tier_name) = tier.get("name").and_then(Value::as_str) {
+                        tier["name"] = format!("{tier_name}-{channel}").into();
+                    }
+                }
+                copy_topics(&mut copy, &channel);
+                if !taken.insert(format!("{name}-{channel}")) {
+                    return Err(ChannelListError::DuplicateName(name));
+                }
+                copy["channel"] = channel.into();
+                names.push(copy["name"].clone());
+                flows.push(copy);
+            }
+            let first = flows.len() - names.len();
+            for key in OWN_TOPICS {
+                let mut topics = HashSet::new();
+                for copy in flows[first..].iter() {
+                    let own: HashSet<&Value> = all_actions(copy)
+                        .filter_map(|actions| actions.get(key))
+                        .collect();
+                    if own.iter().any(|topic| topics.contains(topic)) {
+                        return Err(ChannelListError::SharedTopic(name, key));
+                    }
+                    topics.extend(own);
+                }
+            }
+            copies.insert(name, names);
+        }
+    }
+    for seismometer in seismometers.iter_mut() {
+        if let Some(coincidence) = seismometer.get_mut("coincidence") {
+            watch_copies(coincidence, &copies);
+        }
+    }
+    let network_triggers = config
+        .get_mut("network_triggers")
+        .and_then(Value::as_array_mut);
+    for network_trigger in network_triggers.into_iter().flatten() {
+        watch_copies(network_trigger, &copies);
+    }
+    Ok(())
+}
+
+/// A flow's actions, and those of its tiers.
+fn all_actions(flow: &Value) -> impl Iterator<Item = &Value> {
+    let tiers = flow.get("tiers").and_then(Value::as_array);
+    let tier_actions = tiers
+        .into_iter()
+        .flatten()
+        .filter_map(|tier| tier.get("actions"));
+    flow.get("actions").into_iter().chain(tier_actions)
+}
+
+/// Put a copy's channel in place of any "{channel}" in its MQTT topics.
+fn copy_topics(copy: &mut Value, channel: &str) {
+    let tiers = copy.get_mut("tiers").and_then(Value::as_array_mut);
+    for tier in tiers.into_iter().flatten() {
+        copy_actions_topics(tier, channel);
+    }
+    copy_actions_topics(copy, channel);
+}
+
+fn copy_actions_topics(owner: &mut Value, channel: &str) {
+    let actions = owner.get_mut("actions").and_then(Value::as_object_mut);
+    for (key, value) in actions.into_iter().flatten() {
+        if let (true, Some(topic)) = (key.ends_with("_topic"), value.as_str()) {
+            *value = topic.replace("{channel}", channel).into();
+        }
+    }
+}
+
+/// Have a trigger watching flows watch the copies of any which were
+/// copied for each of their channels instead.
+fn watch_copies(trigger: &mut Value, copies: &HashMap<String, Vec<Value>>) {
+    let Some(flows) = trigger.get_mut("flows").and_then(Value::as_array_mut) else {
+        return;
+    };
+    *flows = std::mem::take(flows)
+        .into_iter()
+        .flat_map(
+            |flow| match flow.as_str().and_then(|name| copies.get(name)) {
+                Some(names) => names.clone(),
+                None => vec![flow],
+            },
+        )
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_expands_channel_lists() {
+        let mut config = serde_json::json!({
+            "seismometers": [{
+                "flows": [
+                    { "name": "z", "channel": "EHZ" },
+                    { "name": "g", "channel": ["ehn", "EHE"], "tiers": [{ "name": "big" }] },
+                ],
+                "coincidence": { "flows": ["z", "g"] },
+            }],
+            "network_triggers": [{ "flows": ["g"] }],
+        });
+        expand_channel_lists(&mut config).expect("expands");
+        let flows = &config["seismometers"][0]["flows"];
+        assert_eq!(flows.as_array().map(Vec::len), Some(3));
+        assert_eq!(flows[1]["name"], "g-EHN");
+        assert_eq!(flows[1]["channel"], "EHN");
+        assert_eq!(flows[2]["tiers"][0]["name"], "big-EHE");
+        assert_eq!(
+            config["seismometers"][0]["coincidence"]["flows"],
+            serde_json::json!(["z", "g-EHN", "g-EHE"])
+        );
+        assert_eq!(
+            config["network_triggers"][0]["flows"],
+            serde_json::json!(["g-EHN", "g-EHE"])
+        );
+        config["seismometers"][0]["flows"][0]["channel"] = serde_json::json!([]);
+        assert!(expand_channel_lists(&mut config).is_err());
+    }
+
+    #[test]
+    fn copies_post_to_their_own_topics() {
+        let config = |topic: &str, name: &str| {
+            let mut config = serde_json::json!({
+                "seismometers": [{
+                    "flows": [
+                        { "name": name, "channel": "EHZ" },
+                        {
+                            "name": "g",
+                            "channel": ["EHN", "EHE"],
+                            "actions": { "mqtt_topic": topic, "mqtt_warning_topic": "w" },
+                        },
+                    ],
+                }],
+            });
+            expand_channel_lists(&mut config).map(|()| config)
+        };
+        let config_ok = config("quake/{channel}", "z").expect("expands");
+        let flows = &config_ok["seismometers"][0]["flows"];
+        assert_eq!(flows[1]["actions"]["mqtt_topic"], "quake/EHN");
+        assert_eq!(flows[2]["actions"]["mqtt_topic"], "quake/EHE");
+        assert!(matches!(
+            config("quake", "z"),
+            Err(ChannelListError::SharedTopic(_, "mqtt_topic"))
+        ));
+        assert!(matches!(
+            config("quake/{channel}", "g-EHE"),
+            Err(ChannelListError::DuplicateName(_))
+        ));
+    }
 }
@@ -0,0 +1,18 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+/// A small HTTP server from which the daemon's state may be read, as by
+/// a dashboard.
+#[derive(Deserialize, JsonSchema)]
+pub struct HttpConfig {
+    /// Address and port to listen on, such as "127.0.0.1:8080". Anyone
+    /// who can reach it may read the daemon's state, so it is best kept
+    /// to the host or a trusted network.
+    pub listen: String,
+
+    /// Interval, in seconds of data, over which flows' energy is streamed
+    /// to live clients as its peak. 0 streams the peak of every packet.
+    /// Default: 0
+    #[serde(default)]
+    pub energy_interval_s: f32,
+}
@@ -0,0 +1,254 @@
+//! Configuration spread over several files: the main file may name others
+//! to include (as each seismometer's own file under a conf.d directory),
+//! which are merged into it as it is loaded.
+//!
+//! The main file comes first, then each file it includes, in the order
+//! its patterns are listed (and the files a pattern matches in name
+//! order). A later file's settings take precedence over an earlier one's,
+//! except that lists of named objects (such as seismometers, or flows) are
+//! added to, rather than replaced: those with the same "name" as one
+//! already listed are merged into it, and others are appended. Lists of
+//! anything else, including objects without names (as a flow's blocks),
+//! are replaced whole.
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum IncludeError {
+    #[error("unable to read {0}")]
+    Io(PathBuf, #[source] std::io::Error),
+    #[error("{0} is not valid JSON")]
+    Parse(PathBuf, #[source] serde_json::Error),
+    #[error("{0} is not a JSON object")]
+    NotAnObject(PathBuf),
+    #[error("include patterns must be strings")]
+    BadPattern,
+    #[error("{0} includes more files, which only the main file may")]
+    Nested(PathBuf),
+}
+
+/// The main configuration file, with every file it includes merged into
+/// it. Its "include" setting is kept as it is.
+pub fn merged_config(path: &Path) -> Result<Value, IncludeError> {
+    let mut config = read_object(path)?;
+    let Some(patterns) = config.get("include") else {
+        return Ok(Value::Object(config));
+    };
+    let patterns: Vec<String> =
+        serde_json::from_value(patterns.clone()).map_err(|_| IncludeError::BadPattern)?;
+    for pattern in patterns.iter() {
+        for included in expand(&base(path).join(pattern))? {
+            let object = read_object(&included)?;
+            if object.contains_key("include") {
+                return Err(IncludeError::Nested(included));
+            }
+            merge_object(&mut config, object);
+        }
+    }
+    Ok(Value::Object(config))
+}
+
+/// The paths whose changes change the configuration which the main file
+/// at `path` includes with the given patterns: the files each pattern
+/// names, and the directory any pattern names (or looks in), whose files
+/// may come and go. Patterns which can't be looked into are left out.
+pub fn included_paths(path: &Path, patterns: &[String]) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    for pattern in patterns.iter() {
+        let pattern = base(path).join(pattern);
+        let Ok(included) = expand(&pattern) else {
+            continue;
+        };
+        if let Some((directory, _)) = looks_in(&pattern) {
+            paths.push(directory.to_owned());
+        }
+        paths.extend(included);
+    }
+    paths
+}
+
+/// The directory which a file's include patterns are relative to.
+fn base(path: &Path) -> &Path {
+    path.parent().unwrap_or(Path::new("."))
+}
+
+fn read_object(path: &Path) -> Result<Map<String, Value>, IncludeError> {
+    let text = std::fs::read_to_string(path).map_err(|e| IncludeError::Io(path.to_owned(), e))?;
+    match serde_json::from_str(&text) {
+        Ok(Value::Object(object)) => Ok(object),
+        Ok(_) => Err(IncludeError::NotAnObject(path.to_owned())),
+        Err(e) => Err(IncludeError::Parse(path.to_owned(), e)),
+    }
+}
+
+/// The files an include pattern names, in name order: every ".json" file
+/// in a directory, the files matching a pattern with "*" or "?" in its
+/// last component, or else the one file named.
+fn expand(pattern: &Path) -> Result<Vec<PathBuf>, IncludeError> {
+    let Some((directory, wanted)) = looks_in(pattern) else {
+        return Ok(vec![pattern.to_owned()]);
+    };
+    let entries =
+        std::fs::read_dir(directory).map_err(|e| IncludeError::Io(directory.to_owned(), e))?;
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| matches(wanted, n))
+        })
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// The directory an include pattern looks in, and the pattern of the names
+/// of the files it wants there, unless it names one file.
+fn looks_in(pattern: &Path) -> Option<(&Path, &str)> {
+    let name = pattern
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    if pattern.is_dir() {
+        Some((pattern, "*.json"))
+    } else if name.contains(['*', '?']) {
+        Some((pattern.parent().unwrap_or(Path::new(".")), name))
+    } else {
+        None
+    }
+}
+
+/// Whether a file name matches a pattern in which "*" stands for any run
+/// of characters and "?" for any one. Hidden files only match patterns
+/// which start with a dot themselves.
+fn matches(pattern: &str, name: &str) -> bool {
+    if name.starts_with('.') && !pattern.starts_with('.') {
+        return false;
+    }
+    fn matches_from(pattern: &[char], name: &[char]) -> bool {
+        match pattern.split_first() {
+            None => name.is_empty(),
+            Some(('*', rest)) => (0..=name.len()).any(|skip| matches_from(rest, &name[skip..])),
+            Some((&p, rest)) => name
+                .split_first()
+                .is_some_and(|(&c, name)| (p == '?' || p == c) && matches_from(rest, name)),
+        }
+    }
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    matches_from(&pattern, &name)
+}
+
+fn merge_object(into: &mut Map<String, Value>, from: Map<String, Value>) {
+    for (key, value) in from {
+        match into.get_mut(&key) {
+            Some(existing) => merge(existing, value),
+            None => {
+                into.insert(key, value);
+            }
+        }
+    }
+}
+
+fn merge(into: &mut Value, from: Value) {
+    match (into, from) {
+        (Value::Object(into), Value::Object(from)) => merge_object(into, from),
+        (Value::Array(into), Value::Array(from)) if all_named(into) && all_named(&from) => {
+            for entry in from {
+                let same = into.iter_mut().find(|existing| {
+                    entry
+                        .get("name")
+                        .is_some_and(|name| existing.get("name") == Some(name))
+                });
+                match same {
+                    Some(existing) => merge(existing, entry),
+                    None => into.push(entry),
+                }
+            }
+        }
+        (into, from) => *into = from,
+    }
+}
+
+/// Whether every entry of a list is an object with a name.
+fn all_named(list: &[Value]) -> bool {
+    list.iter().all(|entry| entry.get("name").is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_merges_by_name() {
+        let mut config = serde_json::json!({
+            "seismometers": [{ "name": "s1", "timeout_s": 5, "flows": [{ "name": "z" }] }],
+            "max_running_cmds": 4,
+            "actions": { "cmd_args": ["-v"] },
+        });
+        let included = serde_json::json!({
+            "actions": { "cmd_args": ["-q"] },
+            "seismometers": [
+                { "name": "s1", "timeout_s": 10, "flows": [{ "name": "n" }] },
+                { "name": "s2" },
+            ],
+        });
+        merge(&mut config, included);
+        assert_eq!(
+            config,
+            serde_json::json!({
+                "seismometers": [
+                    { "name": "s1", "timeout_s": 10, "flows": [{ "name": "z" }, { "name": "n" }] },
+                    { "name": "s2" },
+                ],
+                "max_running_cmds": 4,
+                "actions": { "cmd_args": ["-q"] },
+            })
+        );
+        assert!(matches("*.json", "garage.json"));
+        assert!(matches("s?.json", "s1.json"));
+        assert!(!matches("*.json", "garage.json.bak"));
+        assert!(!matches("*.json", ".hidden.json"));
+    }
+
+    #[test]
+    fn it_replaces_unnamed_lists() {
+        let mut flow = serde_json::json!({
+            "name": "z",
+            "blocks": [{ "type": "rms" }, { "type": "threshold", "trigger_level": 5 }],
+            "tiers": [{ "name": "strong", "level": 10 }],
+        });
+        let included = serde_json::json!({
+            "blocks": [{ "type": "threshold", "trigger_level": 9 }],
+            "tiers": [{ "name": "severe", "level": 20 }],
+        });
+        merge(&mut flow, included);
+        assert_eq!(
+            flow["blocks"],
+            serde_json::json!([{ "type": "threshold", "trigger_level": 9 }])
+        );
+        assert_eq!(flow["tiers"].as_array().map(Vec::len), Some(2));
+    }
+
+    #[test]
+    fn it_lists_included_paths() {
+        let dir = std::env::temp_dir().join(format!("rs-udp-include-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("conf.d")).expect("create dir");
+        let main = dir.join("seismo.json");
+        let write = |name: &str, text: &str| std::fs::write(dir.join(name), text).expect("write");
+        write("seismo.json", r#"{ "include": ["conf.d", "extra.json"] }"#);
+        write("conf.d/garage.json", r#"{ "strict": true }"#);
+        write("extra.json", r#"{ "max_running_cmds": 2 }"#);
+        let merged = merged_config(&main).expect("merges");
+        let patterns = ["conf.d".to_owned(), "extra.json".to_owned()];
+        let paths = included_paths(&main, &patterns);
+        std::fs::remove_dir_all(&dir).expect("clean up");
+        assert_eq!(merged["include"], serde_json::json!(patterns));
+        assert_eq!(merged["strict"], true);
+        let expected = ["conf.d", "conf.d/garage.json", "extra.json"].map(|path| dir.join(path));
+        assert_eq!(paths, expected);
+    }
+}
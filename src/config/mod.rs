@@ -1,13 +1,61 @@
 mod actions;
+mod archive;
+mod audio;
+mod armed;
+mod block;
+mod cap;
+mod capture;
+mod coincidence;
+mod control;
+mod earthworm;
+mod reload;
 mod root;
+mod rsudp;
+mod schedule;
+mod secret;
 mod filter;
 mod flow;
+mod http;
+mod include;
 mod mqtt;
+mod network;
+mod outputs;
+mod picker;
+mod pointer;
 mod seismometer;
+mod stats;
+mod telegram;
+mod tier;
+mod validate;
+mod webhook;
 
-pub use actions::ActionsConfig;
-pub use root::Config;
+pub use actions::{ActionsConfig, PayloadFormat};
+pub use archive::{ArchiveConfig, ArchiveMode};
+pub use audio::AudioConfig;
+pub use armed::ArmedConfig;
+pub use block::{BlockConfig, FilterBankOutputConfig, OnePolePass, RectifyMode, TaperWindow};
+pub use cap::{CapCertainty, CapConfig, CapSeverity, CapStatus, CapUrgency};
+pub use capture::CaptureConfig;
+pub use coincidence::CoincidenceConfig;
+pub use control::ControlConfig;
+pub use earthworm::EarthwormConfig;
+pub use reload::ThresholdChange;
+pub use root::{ActionErrorPolicy, Config, StatusOverflowPolicy};
+pub use rsudp::{migrate_rsudp, RsudpError};
 pub use filter::FilterConfig;
-pub use flow::FlowConfig;
-pub use mqtt::MQTTConfig;
+pub use flow::{DumpFormat, FlowConfig, FlowTap, Precision};
+pub use http::HttpConfig;
+pub use mqtt::{MQTTBrokerConfig, MQTTConfig, MQTTQoS, QueueDropPolicy};
+pub use network::NetworkTriggerConfig;
+pub use outputs::{
+    EventLogConfig, InfluxDBConfig, InfluxVersion, OutputsConfig, SnmpAuthProtocol, SnmpConfig,
+    SnmpVersion, SyslogConfig, SyslogFacility,
+};
+pub use picker::PickerConfig;
+pub use schedule::{ThresholdProfileConfig, TimeOfDay};
+pub use secret::SecretError;
 pub use seismometer::SeismometerConfig;
+pub use stats::StatsConfig;
+pub use telegram::{TelegramChat, TelegramConfig};
+pub use tier::TierConfig;
+pub use webhook::{SignatureAlgorithm, WebhookConfig};
@@ -1,6 +1,63 @@
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Schema, SchemaObject};
+use schemars::JsonSchema;
 use serde::Deserialize;
+use std::path::PathBuf;
 
-#[derive(Deserialize)]
+use super::secret::{resolve_secret, SecretError};
+
+/// MQTT quality of service, given in configuration as its level (0, 1
+/// or 2).
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(try_from = "u8")]
+pub enum MQTTQoS {
+    AtMostOnce,
+    #[default]
+    AtLeastOnce,
+    ExactlyOnce,
+}
+
+/// Which post to drop when the queue of posts held while the broker is
+/// unreachable is full.
+#[derive(Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum QueueDropPolicy {
+    /// Drop the oldest post held, to make room for the new one.
+    #[default]
+    Oldest,
+    /// Drop the new post.
+    Newest,
+}
+
+impl TryFrom<u8> for MQTTQoS {
+    type Error = String;
+
+    fn try_from(level: u8) -> Result<Self, Self::Error> {
+        match level {
+            0 => Ok(Self::AtMostOnce),
+            1 => Ok(Self::AtLeastOnce),
+            2 => Ok(Self::ExactlyOnce),
+            _ => Err(format!("QoS level {level} is not 0, 1 or 2")),
+        }
+    }
+}
+
+impl JsonSchema for MQTTQoS {
+    fn schema_name() -> String {
+        String::from("MQTTQoS")
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        SchemaObject {
+            instance_type: Some(InstanceType::Integer.into()),
+            enum_values: Some(vec![0.into(), 1.into(), 2.into()]),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
 pub struct MQTTConfig {
     /// Hostname or IP address of broker to contact.
     pub host: String,
@@ -18,6 +75,172 @@ pub struct MQTTConfig {
 
     /// MQTT password (requires username, if set)
     pub password: Option<String>,
+
+    /// File holding the MQTT password, in place of `password`, so that it
+    /// can be kept readable only by the daemon.
+    pub password_file: Option<PathBuf>,
+
+    /// Shell command printing the MQTT password (as a password manager's
+    /// client), in place of `password`. It is run as the configuration is
+    /// loaded (and reloaded), and given thirty seconds.
+    pub password_cmd: Option<String>,
+
+    /// Topic on which to periodically publish daemon status (packet
+    /// arrival and latency statistics per channel) as JSON.
+    pub status_topic: Option<String>,
+
+    /// How often to publish to the status topic, in seconds.
+    /// Default: 60
+    #[serde(default = "default_status_interval_s")]
+    pub status_interval_s: f32,
+
+    /// Topic prefix under which to publish Home Assistant MQTT discovery
+    /// messages at startup (Home Assistant's default is "homeassistant"),
+    /// so that each flow with an MQTT topic appears in it as a binary
+    /// sensor. The flows' triggered and reset payloads mustn't contain
+    /// placeholders which vary from one event to the next (unless the
+    /// JSON payload format is used).
+    pub discovery_prefix: Option<String>,
+
+    /// Topic on which to post the daemon's own availability: the online
+    /// payload whenever it connects to the broker, and the offline
+    /// payload, which the broker posts on its behalf (as its last will)
+    /// if the connection is lost. Both are retained. Flows' discovery
+    /// configs depend on it too.
+    pub availability_topic: Option<String>,
+
+    /// Topic on which to post, retained, the online payload while the
+    /// channels of every seismometer's flows are all alive, and the
+    /// offline payload while any of them isn't (or once the daemon stops,
+    /// which the broker posts as the last will of a connection made for
+    /// the topic, should the daemon vanish).
+    pub channels_available_topic: Option<String>,
+
+    /// Default: online
+    #[serde(default = "default_online_payload")]
+    pub online_payload: String,
+
+    /// Default: offline
+    #[serde(default = "default_offline_payload")]
+    pub offline_payload: String,
+
+    /// Topic on which to listen for adjustments to flows' trigger
+    /// levels, as JSON: {"flow": name, "trigger_level": level,
+    /// "reset_level": level}, leaving either level as it is if not given.
+    /// A "seismometer" may be given too, to pick between flows of the
+    /// same name on different ones. Adjustments last until the daemon
+    /// restarts. Only flows with threshold triggers may be adjusted.
+    pub thresholds_topic: Option<String>,
+
+    /// Topic to post the active trigger levels of every flow with a
+    /// threshold trigger to, retained, as a JSON list, at startup and
+    /// whenever they are adjusted.
+    pub thresholds_state_topic: Option<String>,
+
+    /// Longest time to wait between attempts to reconnect to the broker,
+    /// in seconds. Waits start at a second, and double with each failed
+    /// attempt.
+    /// Default: 60
+    #[serde(default = "default_reconnect_max_s")]
+    pub reconnect_max_s: f32,
+
+    /// Number of action posts to hold while the broker is unreachable,
+    /// which are posted once it is reachable again. (Posts at a quality
+    /// of service of 0 are not held.)
+    /// Default: 100
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
+
+    /// Which post to drop when the queue is full: "oldest" or "newest".
+    /// Default: oldest
+    #[serde(default)]
+    pub queue_drop: QueueDropPolicy,
+}
+
+/// A further broker, which actions may post to in place of the main one.
+/// Only connection settings may be given, as nothing else is done with
+/// it; any other setting (as a status topic, which would be ignored) is
+/// refused.
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct MQTTBrokerConfig {
+    /// Hostname or IP address of broker to contact.
+    pub host: String,
+
+    /// TCP port for MQTT connection.
+    #[serde(default = "default_mqtt_port")]
+    pub port: u16,
+
+    /// MQTT client id (optional).
+    #[serde(default = "default_mqtt_client_id")]
+    pub client_id: String,
+
+    /// MQTT username (requires password, if set)
+    pub username: Option<String>,
+
+    /// MQTT password (requires username, if set)
+    pub password: Option<String>,
+
+    /// File holding the MQTT password, in place of `password`.
+    pub password_file: Option<PathBuf>,
+
+    /// Shell command printing the MQTT password, in place of `password`,
+    /// run as the configuration is loaded (and reloaded).
+    pub password_cmd: Option<String>,
+
+    /// Topic on which to post the daemon's availability on this broker:
+    /// the online payload whenever it connects, and the offline payload,
+    /// which the broker posts on its behalf (as its last will) if the
+    /// connection is lost. Both are retained.
+    pub availability_topic: Option<String>,
+
+    /// Default: online
+    #[serde(default = "default_online_payload")]
+    pub online_payload: String,
+
+    /// Default: offline
+    #[serde(default = "default_offline_payload")]
+    pub offline_payload: String,
+
+    /// Longest time to wait between attempts to reconnect to the broker,
+    /// in seconds.
+    /// Default: 60
+    #[serde(default = "default_reconnect_max_s")]
+    pub reconnect_max_s: f32,
+
+    /// Number of action posts to hold while the broker is unreachable.
+    /// Default: 100
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
+
+    /// Which post to drop when the queue is full: "oldest" or "newest".
+    /// Default: oldest
+    #[serde(default)]
+    pub queue_drop: QueueDropPolicy,
+}
+
+impl MQTTConfig {
+    /// Read the password from its file or command, if given one.
+    pub(super) fn resolve_secrets(&mut self) -> Result<(), SecretError> {
+        resolve_secret(
+            "the MQTT password",
+            &mut self.password,
+            self.password_file.as_deref(),
+            self.password_cmd.as_deref(),
+        )
+    }
+}
+
+impl MQTTBrokerConfig {
+    /// Read the password from its file or command, if given one.
+    pub(super) fn resolve_secrets(&mut self) -> Result<(), SecretError> {
+        resolve_secret(
+            "the MQTT password",
+            &mut self.password,
+            self.password_file.as_deref(),
+            self.password_cmd.as_deref(),
+        )
+    }
 }
 
 fn default_mqtt_port() -> u16 {
@@ -27,3 +250,23 @@ fn default_mqtt_port() -> u16 {
 fn default_mqtt_client_id() -> String {
     String::from("")
 }
+
+fn default_status_interval_s() -> f32 {
+    60.0
+}
+
+fn default_reconnect_max_s() -> f32 {
+    60.0
+}
+
+fn default_queue_size() -> usize {
+    100
+}
+
+fn default_online_payload() -> String {
+    String::from("online")
+}
+
+fn default_offline_payload() -> String {
+    String::from("offline")
+}
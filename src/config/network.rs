@@ -0,0 +1,40 @@
+use super::actions::ActionsConfig;
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+/// A network-wide trigger which fires only when flows on several
+/// different seismometers trigger at around the same time, as an
+/// earthquake would make them (but a truck passing one station wouldn't).
+#[derive(Deserialize, JsonSchema)]
+pub struct NetworkTriggerConfig {
+    /// A name for the network trigger.
+    pub name: String,
+
+    /// The names of the flows to watch, from any of the seismometers.
+    /// Flows on the same seismometer count as one station.
+    pub flows: Vec<String>,
+
+    /// How many stations must trigger for the network trigger to fire.
+    /// Default: 2
+    #[serde(default = "default_min_stations")]
+    pub min_stations: usize,
+
+    /// How close together the stations must trigger, in seconds of data
+    /// time (so that stations with different latencies still agree). A
+    /// station counts towards the trigger while triggered, and with any
+    /// station triggering within this long of its trigger.
+    /// Default: 10
+    #[serde(default = "default_window_s")]
+    pub window_s: f32,
+
+    /// Actions to take on network trigger events.
+    pub actions: ActionsConfig,
+}
+
+fn default_min_stations() -> usize {
+    2
+}
+
+fn default_window_s() -> f32 {
+    10.0
+}
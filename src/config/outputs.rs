@@ -0,0 +1,376 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::path::PathBuf;
+
+use super::secret::{resolve_secret, SecretError};
+
+/// Which InfluxDB write API to use.
+#[derive(Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum InfluxVersion {
+    /// The 1.x API, writing to a database.
+    #[default]
+    V1,
+    /// The 2.x API, writing to a bucket of an organization.
+    V2,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct InfluxDBConfig {
+    /// Base URL of the server (such as "http://localhost:8086"). Only
+    /// plain HTTP is supported.
+    pub url: String,
+
+    /// Which write API to use: "v1" or "v2".
+    /// Default: v1
+    #[serde(default)]
+    pub version: InfluxVersion,
+
+    /// Database to write to. (Required for v1.)
+    pub database: Option<String>,
+
+    /// Username and password to write as, if the server requires them.
+    /// (Only used for v1.)
+    pub username: Option<String>,
+    pub password: Option<String>,
+
+    /// File holding the password, in place of `password`.
+    pub password_file: Option<PathBuf>,
+
+    /// Shell command printing the password, in place of `password`.
+    pub password_cmd: Option<String>,
+
+    /// Organization and bucket to write to. (Required for v2.)
+    pub org: Option<String>,
+    pub bucket: Option<String>,
+
+    /// API token to write with. (Only used for v2.)
+    pub token: Option<String>,
+
+    /// File holding the token, in place of `token`.
+    pub token_file: Option<PathBuf>,
+
+    /// Shell command printing the token, in place of `token`.
+    pub token_cmd: Option<String>,
+
+    /// Measurement to write each flow's periodic status (its DC level
+    /// and peak energy) to. Only flows which report their status (see
+    /// the flow's status_interval_s) are written.
+    /// Default: "rs_udp"
+    #[serde(default = "default_measurement")]
+    pub measurement: String,
+
+    /// Measurement to write event markers (triggers and resets) to.
+    /// Default: "rs_udp_events"
+    #[serde(default = "default_event_measurement")]
+    pub event_measurement: String,
+
+    /// Measurement to write flows' statistics to, if they are published
+    /// (see the root `stats` section).
+    /// Default: "rs_udp_stats"
+    #[serde(default = "default_stats_measurement")]
+    pub stats_measurement: String,
+
+    /// How often to write the points gathered, in seconds.
+    /// Default: 10
+    #[serde(default = "default_batch_s")]
+    pub batch_s: f32,
+}
+
+/// Syslog facility to send messages as.
+#[derive(Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SyslogFacility {
+    User,
+    #[default]
+    Daemon,
+    Local0,
+    Local1,
+    Local2,
+    Local3,
+    Local4,
+    Local5,
+    Local6,
+    Local7,
+}
+
+impl SyslogFacility {
+    /// The facility's numeric code.
+    pub fn code(self) -> u8 {
+        match self {
+            Self::User => 1,
+            Self::Daemon => 3,
+            Self::Local0 => 16,
+            Self::Local1 => 17,
+            Self::Local2 => 18,
+            Self::Local3 => 19,
+            Self::Local4 => 20,
+            Self::Local5 => 21,
+            Self::Local6 => 22,
+            Self::Local7 => 23,
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct SyslogConfig {
+    /// Address ("host:port") of a syslog server to send messages to over
+    /// UDP. If not provided, they are sent to the local syslog socket,
+    /// so it must be on Windows, which has none.
+    pub address: Option<String>,
+
+    /// Path of the local syslog socket (on Unix).
+    /// Default: "/dev/log"
+    #[serde(default = "default_syslog_socket")]
+    pub socket: PathBuf,
+
+    /// Facility to send messages as.
+    /// Default: daemon
+    #[serde(default)]
+    pub facility: SyslogFacility,
+
+    /// Application name to send messages as.
+    /// Default: "seismo"
+    #[serde(default = "default_app_name")]
+    pub app_name: String,
+
+    /// Whether to send the daemon's own log messages to syslog too.
+    /// Events are only sent for flows whose actions ask for them.
+    /// Default: false
+    #[serde(default)]
+    pub log: bool,
+}
+
+/// Which SNMP version to send traps as.
+#[derive(Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SnmpVersion {
+    /// SNMPv2c, sent in a community.
+    #[default]
+    V2c,
+    /// SNMPv3, sent as a user, optionally authenticated and encrypted.
+    V3,
+}
+
+/// Which hash SNMPv3 traps are authenticated with.
+#[derive(Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SnmpAuthProtocol {
+    Md5,
+    #[default]
+    Sha,
+    Sha256,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct SnmpConfig {
+    /// Address ("host:port") of the manager to send traps to over UDP.
+    /// Managers usually listen on port 162.
+    pub address: String,
+
+    /// Which SNMP version to send traps as: "v2c" or "v3".
+    /// Default: v2c
+    #[serde(default)]
+    pub version: SnmpVersion,
+
+    /// Community to send traps in. (Only used for v2c.)
+    /// Default: "public"
+    #[serde(default = "default_community")]
+    pub community: String,
+
+    /// User to send traps as. (Required for v3.)
+    pub user: Option<String>,
+
+    /// Hash to authenticate traps with: "md5", "sha" or "sha256".
+    /// (Only used for v3.)
+    /// Default: sha
+    #[serde(default)]
+    pub auth_protocol: SnmpAuthProtocol,
+
+    /// Password to authenticate traps with. If not provided, they are
+    /// sent unauthenticated. (Only used for v3.)
+    pub auth_password: Option<String>,
+
+    /// File holding the authentication password, in place of
+    /// `auth_password`.
+    pub auth_password_file: Option<PathBuf>,
+
+    /// Shell command printing the authentication password, in place of
+    /// `auth_password`.
+    pub auth_password_cmd: Option<String>,
+
+    /// Password to encrypt traps with, using AES-128. If not provided,
+    /// they are sent in the clear. Traps are only encrypted if they are
+    /// authenticated too. (Only used for v3.)
+    pub priv_password: Option<String>,
+
+    /// File holding the encryption password, in place of `priv_password`.
+    pub priv_password_file: Option<PathBuf>,
+
+    /// Shell command printing the encryption password, in place of
+    /// `priv_password`.
+    pub priv_password_cmd: Option<String>,
+
+    /// Engine ID to send traps from, in hex. The manager must be told of
+    /// it, along with the user. (Only used for v3.)
+    /// Default: "80007ed904736569736d6f"
+    #[serde(default = "default_engine_id")]
+    pub engine_id: String,
+
+    /// OIDs of the notifications sent when a flow triggers, when it
+    /// resets, when its seismometer becomes available and when it
+    /// becomes unavailable.
+    /// Default: "1.3.6.1.4.1.32473.1.0.1", "1.3.6.1.4.1.32473.1.0.2",
+    /// "1.3.6.1.4.1.32473.1.0.3" and "1.3.6.1.4.1.32473.1.0.4"
+    #[serde(default = "default_triggered_oid")]
+    pub triggered_oid: String,
+    #[serde(default = "default_reset_oid")]
+    pub reset_oid: String,
+    #[serde(default = "default_available_oid")]
+    pub available_oid: String,
+    #[serde(default = "default_unavailable_oid")]
+    pub unavailable_oid: String,
+
+    /// OID under which the details of each notification are sent, as
+    /// strings: the flow's name (.1), its seismometer's (.2), its
+    /// channel (.3), the time of the event (.4) and its energy (.5).
+    /// Default: "1.3.6.1.4.1.32473.1.1"
+    #[serde(default = "default_details_oid")]
+    pub details_oid: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct EventLogConfig {
+    /// Path of the file to append events to, one JSON object per line.
+    /// Rotated files have ".1", ".2" and so on appended, ".1" being the
+    /// newest.
+    pub path: PathBuf,
+
+    /// Size, in megabytes, past which the file is rotated, if any.
+    pub max_size_mb: Option<f32>,
+
+    /// Time, in seconds, after which the file is rotated (86400 for
+    /// daily, say), counted from when the daemon began writing it, if
+    /// any.
+    pub rotate_s: Option<f32>,
+
+    /// Number of rotated files to keep. Older ones are deleted.
+    /// Default: 5
+    #[serde(default = "default_keep")]
+    pub keep: usize,
+
+    /// Whether to log flows' periodic status too, which is written
+    /// as often as each flow reports it.
+    /// Default: false
+    #[serde(default)]
+    pub status: bool,
+}
+
+/// Destinations, other than MQTT, that measurements are written to.
+#[derive(Deserialize, JsonSchema, Default)]
+pub struct OutputsConfig {
+    /// An InfluxDB server to write flow status and events to.
+    pub influxdb: Option<InfluxDBConfig>,
+
+    /// Syslog, to send flow events (and log messages) to.
+    pub syslog: Option<SyslogConfig>,
+
+    /// An SNMP manager, to send flow events to as traps.
+    pub snmp: Option<SnmpConfig>,
+
+    /// A local file of every flow's events, as JSON lines.
+    pub event_log: Option<EventLogConfig>,
+}
+
+impl OutputsConfig {
+    /// Read the outputs' secrets from their files or commands, where
+    /// given them.
+    pub(super) fn resolve_secrets(&mut self) -> Result<(), SecretError> {
+        if let Some(influxdb) = self.influxdb.as_mut() {
+            resolve_secret(
+                "the InfluxDB password",
+                &mut influxdb.password,
+                influxdb.password_file.as_deref(),
+                influxdb.password_cmd.as_deref(),
+            )?;
+            resolve_secret(
+                "the InfluxDB token",
+                &mut influxdb.token,
+                influxdb.token_file.as_deref(),
+                influxdb.token_cmd.as_deref(),
+            )?;
+        }
+        if let Some(snmp) = self.snmp.as_mut() {
+            resolve_secret(
+                "the SNMP authentication password",
+                &mut snmp.auth_password,
+                snmp.auth_password_file.as_deref(),
+                snmp.auth_password_cmd.as_deref(),
+            )?;
+            resolve_secret(
+                "the SNMP encryption password",
+                &mut snmp.priv_password,
+                snmp.priv_password_file.as_deref(),
+                snmp.priv_password_cmd.as_deref(),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn default_measurement() -> String {
+    String::from("rs_udp")
+}
+
+fn default_event_measurement() -> String {
+    String::from("rs_udp_events")
+}
+
+fn default_stats_measurement() -> String {
+    String::from("rs_udp_stats")
+}
+
+fn default_batch_s() -> f32 {
+    10.0
+}
+
+fn default_syslog_socket() -> PathBuf {
+    PathBuf::from("/dev/log")
+}
+
+fn default_app_name() -> String {
+    String::from("seismo")
+}
+
+fn default_keep() -> usize {
+    5
+}
+
+fn default_community() -> String {
+    String::from("public")
+}
+
+// Enterprise 32473 (reserved for documentation), followed by "seismo".
+fn default_engine_id() -> String {
+    String::from("80007ed904736569736d6f")
+}
+
+fn default_triggered_oid() -> String {
+    String::from("1.3.6.1.4.1.32473.1.0.1")
+}
+
+fn default_reset_oid() -> String {
+    String::from("1.3.6.1.4.1.32473.1.0.2")
+}
+
+fn default_available_oid() -> String {
+    String::from("1.3.6.1.4.1.32473.1.0.3")
+}
+
+fn default_unavailable_oid() -> String {
+    String::from("1.3.6.1.4.1.32473.1.0.4")
+}
+
+fn default_details_oid() -> String {
+    String::from("1.3.6.1.4.1.32473.1.1")
+}
@@ -0,0 +1,25 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+#[derive(Deserialize, JsonSchema)]
+pub struct PickerConfig {
+    /// Seconds of input, up to the newest sample, in which to pick the
+    /// onset of each trigger.
+    /// Default: 10
+    #[serde(default = "default_window_s")]
+    pub window_s: f32,
+
+    /// Order of the autoregressive models fitted to the noise and the
+    /// signal.
+    /// Default: 4
+    #[serde(default = "default_order")]
+    pub order: usize,
+}
+
+fn default_window_s() -> f32 {
+    10.0
+}
+
+fn default_order() -> usize {
+    4
+}
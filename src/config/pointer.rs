@@ -0,0 +1,99 @@
+//! Naming settings by their JSON pointers (as "/seismometers/0/timeout_s"),
+//! as editors and JSON tools do, in reports of settings that are wrong or
+//! unknown.
+use config::ConfigError;
+
+/// The JSON pointer of a setting which deserializing the configuration
+/// ignored.
+pub fn ignored_pointer(path: &serde_ignored::Path) -> String {
+    use serde_ignored::Path;
+    match path {
+        Path::Root => String::new(),
+        Path::Seq { parent, index } => format!("{}/{index}", ignored_pointer(parent)),
+        Path::Map { parent, key } => format!("{}/{}", ignored_pointer(parent), escape(key)),
+        Path::Some { parent }
+        | Path::NewtypeStruct { parent }
+        | Path::NewtypeVariant { parent } => ignored_pointer(parent),
+    }
+}
+
+/// A configuration error's key, as a JSON pointer, and the error without
+/// it, if it has one.
+pub fn pointer_of(error: ConfigError) -> (Option<String>, ConfigError) {
+    match error {
+        ConfigError::At {
+            error,
+            key: Some(key),
+            origin,
+        } => {
+            let error = match origin {
+                None => *error,
+                origin => ConfigError::At {
+                    error,
+                    key: None,
+                    origin,
+                },
+            };
+            (Some(key_pointer(&key)), error)
+        }
+        ConfigError::Type {
+            origin,
+            unexpected,
+            expected,
+            key: Some(key),
+        } => {
+            let error = ConfigError::Type {
+                origin,
+                unexpected,
+                expected,
+                key: None,
+            };
+            (Some(key_pointer(&key)), error)
+        }
+        error => (None, error),
+    }
+}
+
+/// The JSON pointer of a key as the config crate writes them, as
+/// "seismometers[0]flows[1]channel".
+fn key_pointer(key: &str) -> String {
+    let mut pointer = String::new();
+    for segment in key.split(['.', '[', ']']).filter(|s| !s.is_empty()) {
+        pointer.push('/');
+        pointer.push_str(&escape(segment));
+    }
+    pointer
+}
+
+pub(super) fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_points_at_settings() {
+        assert_eq!(
+            key_pointer("seismometers[0]flows[12]channel"),
+            "/seismometers/0/flows/12/channel"
+        );
+        assert_eq!(key_pointer("mqtt.port"), "/mqtt/port");
+        assert_eq!(key_pointer("filter_profiles.a/b"), "/filter_profiles/a~1b");
+        let root = serde_ignored::Path::Root;
+        let seismometers = serde_ignored::Path::Map {
+            parent: &root,
+            key: String::from("seismometers"),
+        };
+        let first = serde_ignored::Path::Seq {
+            parent: &seismometers,
+            index: 0,
+        };
+        let typo = serde_ignored::Path::Map {
+            parent: &first,
+            key: String::from("timout_s"),
+        };
+        assert_eq!(ignored_pointer(&typo), "/seismometers/0/timout_s");
+    }
+}
@@ -0,0 +1,202 @@
+//! Telling whether a reloaded configuration differs from the one running
+//! only in its flows' threshold trigger levels (and holdoffs), which can
+//! be put into effect without restarting the flows, so that their filters
+//! stay warm while the levels are tuned.
+use super::block::BlockConfig;
+use super::flow::FlowConfig;
+use super::root::{Config, ConfigurationError};
+use super::validate::levels;
+use serde_json::Value;
+
+/// The settings of a threshold trigger which can change on reload.
+const THRESHOLD_SETTINGS: [&str; 3] = ["trigger_level", "reset_level", "holdoff"];
+
+/// New settings for a flow's threshold trigger.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ThresholdChange {
+    pub seismometer: String,
+    pub flow: String,
+    pub trigger_level: f32,
+    pub reset_level: f32,
+    pub holdoff: usize,
+}
+
+impl Config {
+    /// The changes to flows' threshold triggers which bring them into line
+    /// with a reloaded configuration, if those are all that differs between
+    /// the two (and there are some). Flows with threshold schedules, and
+    /// triggers of other kinds, can only change by restarting. Levels the
+    /// triggers couldn't take (as a reset level above the trigger level)
+    /// are refused, naming the first by its JSON pointer.
+    pub fn threshold_changes(
+        &self,
+        reloaded: &Config,
+    ) -> Result<Option<Vec<ThresholdChange>>, ConfigurationError> {
+        if without_thresholds(&self.settings) != without_thresholds(&reloaded.settings)
+            || self.secrets() != reloaded.secrets()
+        {
+            return Ok(None);
+        }
+        let mut changes = Vec::new();
+        let seismometers = self.seismometers.iter().zip(reloaded.seismometers.iter());
+        for (i, (old, seismometer)) in seismometers.enumerate() {
+            for (j, (old, flow)) in old.flows.iter().zip(seismometer.flows.iter()).enumerate() {
+                let trigger = trigger_block(flow);
+                if trigger_block(old) == trigger {
+                    continue;
+                }
+                let Some(BlockConfig::Threshold {
+                    trigger_level,
+                    reset_level,
+                    holdoff,
+                    ..
+                }) = trigger
+                else {
+                    return Ok(None);
+                };
+                if !flow.threshold_schedule.is_empty() {
+                    return Ok(None);
+                }
+                let at = format!("/seismometers/{i}/flows/{j}/{}", trigger_pointer(flow));
+                levels(&at, trigger_level, reset_level)?;
+                changes.push(ThresholdChange {
+                    seismometer: seismometer.name.clone(),
+                    flow: flow.name.clone(),
+                    trigger_level,
+                    reset_level,
+                    holdoff,
+                });
+            }
+        }
+        Ok((!changes.is_empty()).then_some(changes))
+    }
+}
+
+/// The block a flow's pipeline ends with, which decides when it triggers.
+pub(super) fn trigger_block(flow: &FlowConfig) -> Option<BlockConfig> {
+    match (flow.filter.as_ref(), flow.blocks.as_ref()) {
+        (Some(filter), None) => filter.blocks(flow.tap).pop(),
+        (None, Some(blocks)) => blocks.last().cloned(),
+        _ => None,
+    }
+}
+
+/// Where a flow's trigger settings are, relative to the flow.
+pub(super) fn trigger_pointer(flow: &FlowConfig) -> String {
+    match flow.blocks.as_ref() {
+        Some(blocks) => format!("blocks/{}", blocks.len().saturating_sub(1)),
+        None => String::from("filter"),
+    }
+}
+
+/// Settings (as JSON) without any threshold trigger settings, of filter
+/// profiles, flows' filters, or threshold blocks.
+fn without_thresholds(settings: &Value) -> Value {
+    let mut settings = settings.clone();
+    if let Some(profiles) = settings
+        .get_mut("filter_profiles")
+        .and_then(Value::as_object_mut)
+    {
+        profiles.values_mut().for_each(remove_thresholds);
+    }
+    let flows = settings
+        .get_mut("seismometers")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+        .filter_map(|seismometer| seismometer.get_mut("flows")?.as_array_mut())
+        .flatten();
+    for flow in flows {
+        if let Some(filter) = flow.get_mut("filter") {
+            remove_thresholds(filter);
+        }
+        let blocks = flow
+            .get_mut("blocks")
+            .and_then(Value::as_array_mut)
+            .into_iter()
+            .flatten()
+            .filter(|block| block.get("type").and_then(Value::as_str) == Some("threshold"));
+        blocks.for_each(remove_thresholds);
+    }
+    settings
+}
+
+fn remove_thresholds(settings: &mut Value) {
+    if let Some(settings) = settings.as_object_mut() {
+        for key in THRESHOLD_SETTINGS {
+            settings.remove(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(filter: Value) -> Config {
+        let settings = serde_json::json!({
+            "seismometers": [{
+                "name": "garage",
+                "flows": [{ "name": "z", "channel": "EHZ", "filter": filter, "actions": {} }],
+            }],
+        });
+        let mut config: Config = serde_json::from_value(settings.clone()).expect("valid");
+        config.settings = settings;
+        config
+    }
+
+    #[test]
+    fn it_finds_threshold_changes() {
+        let running = load(serde_json::json!({ "trigger_level": 1000, "cutoff": 4 }));
+        let tuned = load(serde_json::json!({ "trigger_level": 1200, "holdoff": 50, "cutoff": 4 }));
+        let hot = |running: &Config, reloaded: &Config| {
+            running.threshold_changes(reloaded).expect("valid")
+        };
+        let changes = hot(&running, &tuned).expect("hot");
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].flow, "z");
+        assert_eq!(changes[0].trigger_level, 1200.0);
+        assert_eq!(changes[0].holdoff, 50);
+
+        assert!(hot(&running, &running).is_none());
+        let refiltered = load(serde_json::json!({ "trigger_level": 1200, "cutoff": 5 }));
+        assert!(hot(&running, &refiltered).is_none());
+        let adaptive =
+            |level| load(serde_json::json!({ "trigger_level": level, "noise_floor_window_s": 60 }));
+        assert!(hot(&adaptive(4), &adaptive(5)).is_none());
+    }
+
+    #[test]
+    fn it_refuses_bad_levels() {
+        let running = load(serde_json::json!({ "trigger_level": 1000, "cutoff": 4 }));
+        let retune = |trigger: f32, reset: f32| {
+            let mut tuned = load(serde_json::json!({ "trigger_level": 1, "cutoff": 4 }));
+            let filter = tuned.seismometers[0].flows[0]
+                .filter
+                .as_mut()
+                .expect("filter");
+            filter.trigger_level = Some(trigger);
+            filter.reset_level = Some(reset);
+            running.threshold_changes(&tuned)
+        };
+        retune(1200.0, 100.0).expect("valid").expect("hot");
+        for (trigger, reset) in [(100.0, 1200.0), (f32::NAN, 0.0), (1200.0, f32::INFINITY)] {
+            let refused = retune(trigger, reset).expect_err("refused").to_string();
+            assert!(
+                refused.contains("/seismometers/0/flows/0/filter/"),
+                "{refused}"
+            );
+        }
+    }
+
+    #[test]
+    fn it_restarts_for_changed_secrets() {
+        let running = load(serde_json::json!({ "trigger_level": 1000 }));
+        let mut tuned = load(serde_json::json!({ "trigger_level": 1200 }));
+        tuned.seismometers[0].flows[0].actions.webhook = serde_json::from_value(
+            serde_json::json!({ "url": "http://localhost/", "secret": "rotated" }),
+        )
+        .expect("webhook");
+        assert!(running.threshold_changes(&tuned).expect("valid").is_none());
+    }
+}
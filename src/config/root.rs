@@ -1,24 +1,178 @@
-use super::mqtt::MQTTConfig;
+use super::actions::ActionsConfig;
+use super::armed::ArmedConfig;
+use super::control::ControlConfig;
+use super::filter::{apply_filter_profiles, FilterConfig, FilterProfileError};
+use super::flow::{expand_channel_lists, ChannelListError};
+use super::http::HttpConfig;
+use super::include::{included_paths, merged_config, IncludeError};
+use super::mqtt::{MQTTBrokerConfig, MQTTConfig};
+use super::network::NetworkTriggerConfig;
+use super::outputs::OutputsConfig;
+use super::pointer::{ignored_pointer, pointer_of};
+use super::secret::SecretError;
 use super::seismometer::SeismometerConfig;
+use super::stats::StatsConfig;
+use crate::overrides::SettingOverride;
 
 use config::{ConfigError, Environment, File, FileFormat};
+use schemars::schema::RootSchema;
+use schemars::JsonSchema;
 use serde::Deserialize;
-use std::path::Path;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum ConfigurationError {
     #[error("configuration error")]
     ParseError(#[from] ConfigError),
+    #[error("invalid setting at {0}")]
+    Invalid(String, #[source] ConfigError),
+    #[error("invalid setting at {0}: {1}")]
+    OutOfRange(String, &'static str),
+    #[error("unknown settings, which strict parsing refuses: {}", .0.join(", "))]
+    Unknown(Vec<String>),
+    #[error("unable to read configuration files")]
+    Include(#[from] IncludeError),
+    #[error("bad filter profile")]
+    FilterProfile(#[from] FilterProfileError),
+    #[error("bad channel list")]
+    ChannelList(#[from] ChannelListError),
+    #[error("unable to read a secret")]
+    Secret(#[from] SecretError),
 }
 
-#[derive(Deserialize)]
+/// What to do when the actions for an event fail part way.
+#[derive(Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ActionErrorPolicy {
+    /// Skip the rest of the event's actions, and carry on with the next.
+    #[default]
+    Continue,
+    /// Stop the daemon.
+    Stop,
+}
+
+/// What to do with a flow's status report when the queue of events
+/// waiting for the action loop is full. Other events always wait for room.
+#[derive(Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StatusOverflowPolicy {
+    /// Hold the report back until there is room, in place of any report
+    /// from the flow that is already held back.
+    #[default]
+    Coalesce,
+    /// Drop the report.
+    Drop,
+}
+
+#[derive(Deserialize, JsonSchema)]
 pub struct Config {
+    /// Other files to merge into this one, as they are loaded (only from
+    /// the main file): paths, relative to its directory, of files, of
+    /// directories (each of whose ".json" files is included), or of files
+    /// matching a pattern with "*" or "?" in its last component. Later
+    /// files take precedence, but add to lists of named objects, merging
+    /// those of the same name (as seismometers and flows).
+    #[serde(default)]
+    pub include: Vec<String>,
+
+    /// Whether to refuse a configuration with settings the daemon doesn't
+    /// know of (as a misspelled "triger_level", which would otherwise
+    /// leave the trigger level at its default), rather than only warn of
+    /// them.
+    /// Default: false
+    #[serde(default)]
+    pub strict: bool,
+
+    /// Settings the daemon didn't know of, by their JSON pointers (as
+    /// "/seismometers/0/timout_s").
+    #[serde(skip)]
+    pub unknown_settings: Vec<String>,
+
+    /// Every setting, as loaded (with environment and command line
+    /// overrides), to tell what a reload changes.
+    #[serde(skip)]
+    pub(super) settings: Value,
+
     /// A list of seismometers to monitor.
     pub seismometers: Vec<SeismometerConfig>,
 
+    /// Filter parameters shared by flows, by name, which flows take by
+    /// naming one as their `filter_profile` (and giving any parameters
+    /// of their own in their `filter`). Profiles are applied as the
+    /// configuration files are loaded, ahead of environment overrides.
+    #[serde(default)]
+    pub filter_profiles: BTreeMap<String, FilterConfig>,
+
     /// MQTT settings.
     pub mqtt: Option<MQTTConfig>,
+
+    /// Further MQTT brokers, by name, which actions may post to in place
+    /// of the main one (as a cloud broker fed a summary of the alarms
+    /// posted on the LAN). Only connection settings (host, port, client
+    /// id, credentials, availability, reconnection and queueing) may be
+    /// given for them.
+    #[serde(default)]
+    pub mqtt_brokers: BTreeMap<String, MQTTBrokerConfig>,
+
+    /// Settings for the session-wide armed switch. If not provided, the
+    /// session is always armed.
+    pub armed: Option<ArmedConfig>,
+
+    /// Triggers which fire only when several seismometers trigger
+    /// together.
+    #[serde(default)]
+    pub network_triggers: Vec<NetworkTriggerConfig>,
+
+    /// Actions to take when any flow triggers, and when every flow has
+    /// reset again, as well as each flow's own. The "{flow}" placeholder
+    /// expands to "global".
+    pub actions: Option<ActionsConfig>,
+
+    /// Destinations, other than MQTT, to write measurements to.
+    #[serde(default)]
+    pub outputs: OutputsConfig,
+
+    /// HTTP server settings, if the daemon's state is to be served.
+    pub http: Option<HttpConfig>,
+
+    /// Control socket settings, if the daemon may be controlled locally.
+    pub control: Option<ControlConfig>,
+
+    /// Settings for publishing statistics of each flow's events, if they
+    /// are to be kept.
+    pub stats: Option<StatsConfig>,
+
+    /// Most executables that actions may have running at once. Any more
+    /// called for while this many run are skipped.
+    /// Default: 16
+    #[serde(default = "default_max_running_cmds")]
+    pub max_running_cmds: usize,
+
+    /// What to do when the actions for an event fail part way (as when
+    /// the MQTT broker refuses a post): "continue" with the next event, or
+    /// "stop" the daemon. Either way, the failure is logged and counted
+    /// (as "action_errors" in the daemon status).
+    /// Default: continue
+    #[serde(default)]
+    pub action_errors: ActionErrorPolicy,
+
+    /// Most events which may wait for the action loop before the
+    /// seismometers have to wait for it (or, for flows' status reports, the
+    /// status overflow policy applies).
+    /// Default: 32
+    #[serde(default = "default_event_queue_size")]
+    pub event_queue_size: usize,
+
+    /// What to do with a flow's status report when the event queue is full:
+    /// "coalesce" it with the flow's next report, or "drop" it. Either way,
+    /// reports which are never sent are counted (as
+    /// "dropped_status_events" in the daemon status).
+    /// Default: coalesce
+    #[serde(default)]
+    pub status_overflow: StatusOverflowPolicy,
 }
 
 impl Config {
@@ -26,24 +180,235 @@ impl Config {
         path: &Path,
         env_prefix: &str,
         env_separator: &str,
+        overrides: &[SettingOverride],
     ) -> Result<Self, ConfigurationError> {
-        let config_file =
-            File::with_name(path.to_str().expect("file name")).format(FileFormat::Json);
-        config::Config::builder()
+        let mut merged = merged_config(path)?;
+        expand_channel_lists(&mut merged)?;
+        apply_filter_profiles(&mut merged)?;
+        let config_file = File::from_str(&merged.to_string(), FileFormat::Json);
+        let mut unknown_settings = Vec::new();
+        let mut settings = Value::Null;
+        let mut builder = config::Config::builder()
             .add_source(config_file)
-            .add_source(Environment::with_prefix(env_prefix).separator(env_separator))
+            .add_source(Environment::with_prefix(env_prefix).separator(env_separator));
+        for setting in overrides {
+            builder = builder.set_override(setting.key.as_str(), setting.value.as_str())?;
+        }
+        let mut config: Self = builder
             .build()
-            .and_then(|config| config.try_deserialize())
-            .map_err(|e| e.into())
+            .and_then(|config| {
+                settings = config.clone().try_deserialize()?;
+                serde_ignored::deserialize(config, |path| {
+                    unknown_settings.push(ignored_pointer(&path))
+                })
+            })
+            .map_err(|e| match pointer_of(e) {
+                (Some(pointer), e) => ConfigurationError::Invalid(pointer, e),
+                (None, e) => ConfigurationError::ParseError(e),
+            })?;
+        if config.strict && !unknown_settings.is_empty() {
+            return Err(ConfigurationError::Unknown(unknown_settings));
+        }
+        config.unknown_settings = unknown_settings;
+        config.settings = settings;
+        config.validate()?;
+        config.resolve_secrets()?;
+        Ok(config)
+    }
+
+    /// Read every secret given by a file or command, after any
+    /// environment overrides.
+    fn resolve_secrets(&mut self) -> Result<(), SecretError> {
+        if let Some(mqtt) = self.mqtt.as_mut() {
+            mqtt.resolve_secrets()?;
+        }
+        for mqtt in self.mqtt_brokers.values_mut() {
+            mqtt.resolve_secrets()?;
+        }
+        self.outputs.resolve_secrets()?;
+        for seismometer in self.seismometers.iter_mut() {
+            for flow in seismometer.flows.iter_mut() {
+                flow.actions.resolve_secrets()?;
+                for tier in flow.tiers.iter_mut() {
+                    tier.actions.resolve_secrets()?;
+                }
+            }
+            if let Some(coincidence) = seismometer.coincidence.as_mut() {
+                coincidence.actions.resolve_secrets()?;
+            }
+        }
+        for network_trigger in self.network_triggers.iter_mut() {
+            network_trigger.actions.resolve_secrets()?;
+        }
+        if let Some(actions) = self.actions.as_mut() {
+            actions.resolve_secrets()?;
+        }
+        Ok(())
+    }
+
+    /// The paths whose changes change the configuration, besides the main
+    /// file at `path`: the files it includes, and the directories it looks
+    /// in for them.
+    pub fn included_paths(&self, path: &Path) -> Vec<PathBuf> {
+        included_paths(path, &self.include)
+    }
+
+    /// The topics on which the availability of seismometers' channels is
+    /// posted, if there is an MQTT broker to post it to: that of every
+    /// seismometer's, if any, then each seismometer's own (by name).
+    pub fn availability_topics(&self) -> Vec<(Option<&str>, &str)> {
+        let Some(mqtt) = self.mqtt.as_ref() else {
+            return Vec::new();
+        };
+        let seismometers = self.seismometers.iter().filter_map(|seismometer| {
+            let topic = seismometer.mqtt_available_topic.as_deref()?;
+            Some((Some(seismometer.name.as_str()), topic))
+        });
+        let every = mqtt
+            .channels_available_topic
+            .as_deref()
+            .map(|topic| (None, topic));
+        every.into_iter().chain(seismometers).collect()
+    }
+
+    /// Every secret, as read, to tell whether a reload changes any (as
+    /// when a command gives a new password) which its settings can't.
+    pub(super) fn secrets(&self) -> Vec<Option<&str>> {
+        let mut secrets = Vec::new();
+        secrets.push(self.mqtt.as_ref().and_then(|mqtt| mqtt.password.as_deref()));
+        let brokers = self.mqtt_brokers.values();
+        secrets.extend(brokers.map(|broker| broker.password.as_deref()));
+        if let Some(influxdb) = self.outputs.influxdb.as_ref() {
+            secrets.push(influxdb.password.as_deref());
+            secrets.push(influxdb.token.as_deref());
+        }
+        if let Some(snmp) = self.outputs.snmp.as_ref() {
+            secrets.push(snmp.auth_password.as_deref());
+            secrets.push(snmp.priv_password.as_deref());
+        }
+        for (_, actions) in self.all_actions() {
+            let (telegram, webhook) = (actions.telegram.as_ref(), actions.webhook.as_ref());
+            secrets.push(telegram.and_then(|telegram| telegram.bot_token.as_deref()));
+            secrets.push(webhook.and_then(|webhook| webhook.secret.as_deref()));
+        }
+        secrets
+    }
+
+    /// A JSON Schema describing configuration files, as derived from the
+    /// configuration's own types (and their documentation).
+    pub fn schema() -> RootSchema {
+        schemars::schema_for!(Config)
     }
 }
 
+fn default_max_running_cmds() -> usize {
+    16
+}
+
+fn default_event_queue_size() -> usize {
+    32
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::datasource::Channel;
 
     #[test]
     fn it_decodes() {
-        let _c: Config = serde_json::from_str("{\"seismometers\": []}").expect("parse");
+        let c: Config = serde_json::from_str("{\"seismometers\": []}").expect("parse");
+        assert_eq!(c.action_errors, ActionErrorPolicy::Continue);
+        assert_eq!(c.event_queue_size, 32);
+        assert_eq!(c.status_overflow, StatusOverflowPolicy::Coalesce);
+        let c: Config = serde_json::from_str(r#"{"seismometers": [], "action_errors": "stop"}"#)
+            .expect("parse");
+        assert_eq!(c.action_errors, ActionErrorPolicy::Stop);
+    }
+
+    #[test]
+    fn it_decodes_network_triggers() {
+        let c: Config = serde_json::from_str(
+            r#"{"seismometers": [], "network_triggers": [
+                {"name": "net", "flows": ["a", "b"], "actions": {}}
+            ]}"#,
+        )
+        .expect("parse");
+        assert_eq!(c.network_triggers[0].min_stations, 2);
+        assert_eq!(c.network_triggers[0].window_s, 10.0);
+    }
+
+    #[test]
+    fn it_decodes_brokers() {
+        let c: Config = serde_json::from_str(
+            r#"{"seismometers": [], "mqtt_brokers": {
+                "lab": {"host": "lab.example", "port": 8883}
+            }}"#,
+        )
+        .expect("parse");
+        assert!(c.mqtt.is_none());
+        assert_eq!(c.mqtt_brokers["lab"].host, "lab.example");
+        assert_eq!(c.mqtt_brokers["lab"].port, 8883);
+    }
+
+    #[test]
+    fn it_refuses_topics_of_named_brokers() {
+        let refused = serde_json::from_str::<Config>(
+            r#"{"seismometers": [], "mqtt_brokers": {
+                "lab": {"host": "lab.example", "status_topic": "lab/status"}
+            }}"#,
+        )
+        .err()
+        .expect("refused");
+        assert!(refused.to_string().contains("status_topic"), "{refused}");
+    }
+
+    #[test]
+    fn it_lists_availability_topics() {
+        let seismometer = |name: &str, topic: Option<&str>| {
+            serde_json::json!({
+                "name": name, "sample_rate": 100.0, "mqtt_available_topic": topic, "flows": [],
+            })
+        };
+        let config = serde_json::from_value::<Config>(serde_json::json!({
+            "seismometers": [seismometer("garage", Some("garage/up")), seismometer("shed", None)],
+            "mqtt": { "host": "lab", "channels_available_topic": "all/up" },
+        }))
+        .expect("valid");
+        let topics = config.availability_topics();
+        assert_eq!(topics, [(None, "all/up"), (Some("garage"), "garage/up")]);
+    }
+
+    #[test]
+    fn it_decodes_channels() {
+        let config = |channel: &str| {
+            serde_json::from_value::<Config>(serde_json::json!({
+                "seismometers": [{
+                    "name": "s1",
+                    "sample_rate": 100.0,
+                    "timeout_s": 5.0,
+                    "flows": [{
+                        "name": "f",
+                        "channel": channel,
+                        "filter": { "trigger_level": 1.0, "reset_level": 0.1 },
+                        "actions": {},
+                    }],
+                }],
+            }))
+        };
+        let c = config("ehz").expect("parse");
+        assert_eq!(c.seismometers[0].flows[0].channel, Channel::Ehz);
+        let c = config("EnN").expect("parse");
+        assert_eq!(c.seismometers[0].flows[0].channel, Channel::Enn);
+        let e = config("XYZ").err().expect("refused");
+        assert!(e.to_string().contains("unknown channel \"XYZ\""));
+    }
+
+    #[test]
+    fn it_describes_itself() {
+        let schema = serde_json::to_value(Config::schema()).expect("serializes");
+        assert_eq!(schema["required"], serde_json::json!(["seismometers"]));
+        assert_eq!(schema["properties"]["event_queue_size"]["default"], 32);
+        let flow = &schema["definitions"]["FlowConfig"]["properties"];
+        assert!(flow["threshold_schedule"].is_object());
     }
 }
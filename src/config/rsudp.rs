@@ -0,0 +1,189 @@
+//! Migrating from rsudp: an rsudp settings file's station, alert and
+//! notification settings, as an equivalent configuration for this daemon.
+//!
+//! rsudp's alert module triggers when the ratio of a short-term average of
+//! the band-passed signal's energy to a long-term average exceeds its
+//! threshold, and resets when it falls below its reset ratio. The classic
+//! pipeline does much the same with the power detector smoothed over the
+//! short-term window, and trigger and reset levels taken as multiples of
+//! the noise floor estimated over the long-term window.
+use crate::datasource::Channel;
+use serde_json::{json, Map, Value};
+use thiserror::Error;
+
+/// rsudp's sample rate, that of Raspberry Shakes.
+const SAMPLE_RATE: f64 = 100.0;
+
+#[derive(Debug, Error)]
+pub enum RsudpError {
+    #[error("the settings have no {0} section")]
+    Missing(&'static str),
+    #[error("{0} is not a channel rs-udp can monitor")]
+    UnknownChannel(String),
+}
+
+/// A configuration equivalent to rsudp settings, and notes of the settings
+/// which couldn't be carried over.
+pub fn migrate_rsudp(settings: &Value) -> Result<(Value, Vec<String>), RsudpError> {
+    let mut notes = Vec::new();
+    let general = settings
+        .get("settings")
+        .ok_or(RsudpError::Missing("settings"))?;
+    let alert = settings.get("alert").ok_or(RsudpError::Missing("alert"))?;
+    let station = general
+        .get("station")
+        .and_then(Value::as_str)
+        .unwrap_or("shake");
+    let port = general.get("port").and_then(Value::as_u64).unwrap_or(8888);
+    let number = |section: &Value, key: &str, default: f64| {
+        section.get(key).and_then(Value::as_f64).unwrap_or(default)
+    };
+
+    let channel_name = alert.get("channel").and_then(Value::as_str).unwrap_or("HZ");
+    let channel = channel_for(channel_name)?;
+    if !enabled(alert) {
+        notes.push(String::from(
+            "the alert module is disabled, but its flow is configured anyway",
+        ));
+    }
+    let sta_s = number(alert, "sta", 6.0);
+    let lta_s = number(alert, "lta", 30.0);
+    let highpass_hz = number(alert, "highpass", 0.0);
+    let filter = json!({
+        "cutoff": number(alert, "lowpass", 9.0),
+        "dc_alpha": decay_alpha(highpass_hz),
+        "energy_detector": "power",
+        "energy_alpha": (-1.0 / (sta_s * SAMPLE_RATE)).exp(),
+        "noise_floor_window_s": lta_s.max(1.0),
+        "trigger_level": number(alert, "threshold", 1.7),
+        "reset_level": number(alert, "reset", 1.6),
+    });
+    if alert.get("deconvolve").and_then(Value::as_bool) == Some(true) {
+        notes.push(String::from(
+            "alert.deconvolve: the flow watches counts; add a response block to watch ground motion",
+        ));
+    }
+
+    let mut actions = Map::new();
+    if let Some(telegram) = settings.get("telegram").filter(|t| enabled(t)) {
+        let chat_id = match telegram.get("chat_id") {
+            Some(Value::String(id)) => id.parse::<i64>().map_or(json!(id), |id| json!(id)),
+            Some(id) => id.clone(),
+            None => json!(""),
+        };
+        let mut message = String::from("Earthquake detected by {flow}");
+        if let Some(extra) = telegram.get("extra_text").and_then(Value::as_str) {
+            if !extra.is_empty() {
+                message = format!("{message} {extra}");
+            }
+        }
+        actions.insert(
+            String::from("telegram"),
+            json!({
+                "bot_token": telegram.get("token"),
+                "chat_id": chat_id,
+                "triggered_message": message,
+            }),
+        );
+        if telegram.get("send_images").and_then(Value::as_bool) == Some(true) {
+            notes.push(String::from(
+                "telegram.send_images: give the flow a capture and set send_waveform to send plots",
+            ));
+        }
+    }
+
+    let mut seismometer = json!({
+        "name": station,
+        "listen": format!("0.0.0.0:{port}"),
+        "sample_rate": SAMPLE_RATE,
+        "timeout_s": 10.0,
+        "flows": [{
+            "name": format!("{station}-{channel}"),
+            "channel": channel.as_str(),
+            "filter": filter,
+            "actions": actions,
+        }],
+    });
+    if let Some(write) = settings.get("write").filter(|w| enabled(w)) {
+        let output_dir = general
+            .get("output_dir")
+            .and_then(Value::as_str)
+            .unwrap_or(".");
+        seismometer["archive"] = json!({
+            "path": format!("{output_dir}/data"),
+            "station": station,
+        });
+        if write.get("channels") != Some(&json!(["all"])) {
+            notes.push(String::from(
+                "write.channels: every channel is archived, not only those listed",
+            ));
+        }
+    }
+
+    for (section, what) in [
+        (
+            "alertsound",
+            "play a WAV or Ogg Vorbis file with the audio action",
+        ),
+        ("custom", "run the code as a trigger_cmd"),
+        ("forward", "forward the Shake's data to other hosts itself"),
+        ("plot", "use the tui, or the HTTP server's live feed"),
+        ("rsam", "publish flow status over MQTT"),
+        ("tweets", "no equivalent"),
+    ] {
+        if settings.get(section).is_some_and(enabled) {
+            notes.push(format!("{section} is not migrated: {what}"));
+        }
+    }
+    Ok((json!({ "seismometers": [seismometer] }), notes))
+}
+
+fn enabled(section: &Value) -> bool {
+    section.get("enabled").and_then(Value::as_bool) == Some(true)
+}
+
+/// The channel rsudp means by a channel name, which may be just the end
+/// of one (as "HZ", for whichever vertical channel the Shake has).
+fn channel_for(name: &str) -> Result<Channel, RsudpError> {
+    let name = name.to_ascii_uppercase();
+    (0..Channel::max())
+        .filter_map(|channel| Channel::try_from(channel).ok())
+        .find(|channel| channel.as_str().ends_with(&name))
+        .ok_or(RsudpError::UnknownChannel(name))
+}
+
+/// The decay rate of a one-pole filter with a corner frequency, at rsudp's
+/// sample rate.
+fn decay_alpha(corner_hz: f64) -> f64 {
+    (-2.0 * std::f64::consts::PI * corner_hz / SAMPLE_RATE).exp()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_migrates_alerts() {
+        let settings = json!({
+            "settings": { "port": 8888, "station": "R1234", "output_dir": "/srv/rsudp" },
+            "write": { "enabled": true, "channels": ["all"] },
+            "alert": {
+                "enabled": true, "channel": "HZ", "sta": 6, "lta": 30,
+                "threshold": 3.95, "reset": 0.9, "highpass": 0.8, "lowpass": 9,
+            },
+            "telegram": { "enabled": true, "token": "123:abc", "chat_id": "-100", "extra_text": "" },
+            "alertsound": { "enabled": true, "mp3file": "doorbell" },
+        });
+        let (config, notes) = migrate_rsudp(&settings).expect("migrates");
+        let seismometer = &config["seismometers"][0];
+        assert_eq!(seismometer["listen"], "0.0.0.0:8888");
+        assert_eq!(seismometer["archive"]["path"], "/srv/rsudp/data");
+        let flow = &seismometer["flows"][0];
+        assert_eq!(flow["channel"], "EHZ");
+        assert_eq!(flow["filter"]["noise_floor_window_s"], 30.0);
+        assert_eq!(flow["actions"]["telegram"]["chat_id"], -100);
+        assert_eq!(notes.len(), 1);
+        serde_json::from_value::<super::super::Config>(config).expect("valid");
+        assert!(channel_for("HDF").is_err());
+    }
+}
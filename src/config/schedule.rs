@@ -0,0 +1,102 @@
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Schema, SchemaObject, StringValidation};
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+/// A local time of day, given in configuration as "HH:MM".
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(try_from = "String")]
+pub struct TimeOfDay {
+    /// Minutes since midnight.
+    pub minute: u16,
+}
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = String;
+
+    fn try_from(time: String) -> Result<Self, Self::Error> {
+        let parsed = time.split_once(':').and_then(|(hour, minute)| {
+            Some((hour.parse::<u16>().ok()?, minute.parse::<u16>().ok()?))
+        });
+        match parsed {
+            Some((hour, minute)) if hour < 24 && minute < 60 => Ok(Self {
+                minute: hour * 60 + minute,
+            }),
+            _ => Err(format!("time of day {time:?} is not HH:MM")),
+        }
+    }
+}
+
+impl JsonSchema for TimeOfDay {
+    fn schema_name() -> String {
+        String::from("TimeOfDay")
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            string: Some(Box::new(StringValidation {
+                pattern: Some(String::from("^([01]?[0-9]|2[0-3]):[0-5]?[0-9]$")),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+/// Trigger levels for part of each day, in place of the flow's own.
+#[derive(Deserialize, JsonSchema, Clone)]
+pub struct ThresholdProfileConfig {
+    /// A name for the profile (such as "day"), as reported in the status.
+    pub name: String,
+
+    /// The local time of day at which the profile comes into force.
+    pub from: TimeOfDay,
+
+    /// The local time of day at which the profile is no longer in force.
+    /// This may be earlier than `from`, for a profile which spans
+    /// midnight, or the same, for one which is always in force.
+    pub until: TimeOfDay,
+
+    /// The level at which the trigger asserts while the profile is in
+    /// force.
+    /// Default: the flow's own
+    pub trigger_level: Option<f32>,
+
+    /// The level at which the trigger resets while the profile is in
+    /// force.
+    /// Default: the flow's own
+    pub reset_level: Option<f32>,
+}
+
+impl ThresholdProfileConfig {
+    /// Whether the profile is in force at a local time of day, in
+    /// minutes since midnight.
+    pub fn covers(&self, minute: u16) -> bool {
+        let (from, until) = (self.from.minute, self.until.minute);
+        if from < until {
+            (from..until).contains(&minute)
+        } else {
+            minute >= from || minute < until
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_spans_midnight() {
+        let profile: ThresholdProfileConfig = serde_json::from_str(
+            r#"{ "name": "night", "from": "22:00", "until": "07:00", "trigger_level": 2.0 }"#,
+        )
+        .expect("parses");
+        assert!(profile.covers(23 * 60));
+        assert!(profile.covers(6 * 60 + 59));
+        assert!(!profile.covers(7 * 60));
+        assert!(!profile.covers(12 * 60));
+        assert!(TimeOfDay::try_from(String::from("24:00")).is_err());
+    }
+}
@@ -0,0 +1,137 @@
+//! Secrets, such as passwords and tokens, which may be read from a file
+//! (which, unlike the configuration, can be left readable only by the
+//! daemon) or from the output of a command (as a secrets manager's client)
+//! as the configuration is loaded, rather than given in it.
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// How long a command giving a secret may take, before it is killed.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often to check whether a command giving a secret has finished.
+const COMMAND_POLL: Duration = Duration::from_millis(20);
+
+#[derive(Debug, Error)]
+pub enum SecretError {
+    #[error("{0} is given more than one way")]
+    Ambiguous(&'static str),
+    #[error("unable to read {0} from {1}")]
+    Io(&'static str, PathBuf, #[source] std::io::Error),
+    #[error("unable to run the command for {0}")]
+    Spawn(&'static str, #[source] std::io::Error),
+    #[error("the command for {0} failed ({1})")]
+    Command(&'static str, ExitStatus),
+    #[error("the command for {0} took too long")]
+    TimedOut(&'static str),
+    #[error("{0} is not UTF-8")]
+    NotUtf8(&'static str),
+    #[error("{0} must be given")]
+    Missing(&'static str),
+}
+
+/// Fill in a secret from the file or command given for it, if either is.
+/// A file's contents or a command's output are taken without any trailing
+/// line break. Commands are run by the shell, with the daemon's own
+/// environment and standard error, and killed if they take longer than
+/// thirty seconds. This blocks, so a running daemon reads secrets (as it
+/// reloads its configuration) on a thread of their own.
+pub fn resolve_secret(
+    name: &'static str,
+    secret: &mut Option<String>,
+    file: Option<&Path>,
+    cmd: Option<&str>,
+) -> Result<(), SecretError> {
+    let text = match (file, cmd) {
+        (None, None) => return Ok(()),
+        (Some(_), Some(_)) => return Err(SecretError::Ambiguous(name)),
+        _ if secret.is_some() => return Err(SecretError::Ambiguous(name)),
+        (Some(file), None) => {
+            let bytes = std::fs::read(file).map_err(|e| SecretError::Io(name, file.into(), e))?;
+            String::from_utf8(bytes).map_err(|_| SecretError::NotUtf8(name))?
+        }
+        (None, Some(cmd)) => run_command(name, cmd, COMMAND_TIMEOUT)?,
+    };
+    *secret = Some(text.trim_end_matches(['\n', '\r']).to_owned());
+    Ok(())
+}
+
+/// The output of a shell command, which is killed if it runs too long.
+fn run_command(name: &'static str, cmd: &str, timeout: Duration) -> Result<String, SecretError> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(cmd)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|e| SecretError::Spawn(name, e))?;
+    // Read the output as it comes, so that the command can't stall on a
+    // full pipe while it is waited for.
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let reader = std::thread::spawn(move || {
+        let mut output = Vec::new();
+        stdout.read_to_end(&mut output).map(|_| output)
+    });
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait().map_err(|e| SecretError::Spawn(name, e))? {
+            Some(status) => break status,
+            None if Instant::now() >= deadline => {
+                // The reader finishes once the command's output is closed
+                // as it dies, unless something it started holds it open.
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(SecretError::TimedOut(name));
+            }
+            None => std::thread::sleep(COMMAND_POLL),
+        }
+    };
+    if !status.success() {
+        return Err(SecretError::Command(name, status));
+    }
+    let output = reader
+        .join()
+        .expect("reader doesn't panic")
+        .map_err(|e| SecretError::Spawn(name, e))?;
+    String::from_utf8(output).map_err(|_| SecretError::NotUtf8(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_reads_secrets() {
+        let mut secret = Some(String::from("inline"));
+        resolve_secret("password", &mut secret, None, None).expect("kept");
+        assert_eq!(secret.as_deref(), Some("inline"));
+
+        let mut secret = None;
+        resolve_secret("password", &mut secret, None, Some("echo hunter2")).expect("runs");
+        assert_eq!(secret.as_deref(), Some("hunter2"));
+
+        let path = std::env::temp_dir().join(format!("rs-udp-secret-{}", std::process::id()));
+        std::fs::write(&path, "s3cret\n").expect("writes");
+        let mut secret = None;
+        let read = resolve_secret("password", &mut secret, Some(&path), None);
+        std::fs::remove_file(&path).expect("removes");
+        read.expect("reads");
+        assert_eq!(secret.as_deref(), Some("s3cret"));
+
+        let mut secret = Some(String::from("inline"));
+        let both = resolve_secret("password", &mut secret, None, Some("echo x"));
+        assert!(matches!(both, Err(SecretError::Ambiguous(_))));
+        let mut secret = None;
+        let failed = resolve_secret("password", &mut secret, None, Some("exit 3"));
+        assert!(matches!(failed, Err(SecretError::Command(..))));
+        let hung = run_command(
+            "password",
+            "while :; do :; done",
+            Duration::from_millis(100),
+        );
+        assert!(matches!(hung, Err(SecretError::TimedOut(_))));
+    }
+}
@@ -1,15 +1,28 @@
+use super::archive::ArchiveConfig;
+use super::coincidence::CoincidenceConfig;
+use super::earthworm::EarthwormConfig;
 use super::flow::FlowConfig;
+use schemars::JsonSchema;
 use serde::Deserialize;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 pub struct SeismometerConfig {
     /// A name for the sensor
     pub name: String,
 
-    /// The listen address ("ip:port") to listen on.
-    pub listen: String,
+    /// The listen address ("ip:port") to listen on for rsudp packets.
+    pub listen: Option<String>,
 
-    /// The sample rate of the seismometer, in hertz.
+    /// A WebSocket URL ("ws://host:port/path") to read data frames from,
+    /// instead of listening for rsudp packets.
+    pub websocket: Option<String>,
+
+    /// An Earthworm export module to read trace data from, instead of
+    /// listening for rsudp packets.
+    pub earthworm: Option<EarthwormConfig>,
+
+    /// The sample rate of the seismometer, in hertz. Flows may give their
+    /// channels rates of their own.
     /// Default: 100
     #[serde(default = "default_sample_rate")]
     pub sample_rate: f32,
@@ -17,13 +30,85 @@ pub struct SeismometerConfig {
     /// How long to wait for data before declaring a timeout, in seconds.
     /// If provided, the timeout will be used to announce the "availability"
     /// of all flows from the seismometer. If not provided, no timeout will be used and the
-    /// sensor will become "available" as soon as the program starts.
+    /// sensor will become "available" as soon as the program starts. Flows
+    /// may give their channels timeouts of their own.
     pub timeout_s: Option<f32>,
 
+    /// Whether the timeout also applies to the data's own timestamps: if
+    /// set, packets only count as signs of life while their data time
+    /// advances, so that a device which keeps sending the same stretch of
+    /// data (as when its firmware hangs) is declared unavailable. Only
+    /// used for channels with a timeout.
+    /// Default: false
+    #[serde(default)]
+    pub timeout_data_time: bool,
+
+    /// The longest to wait between attempts to reopen the data source
+    /// (listening socket, WebSocket or Earthworm connection) after it
+    /// fails, in seconds. Attempts start a second apart and back off.
+    /// Default: 60
+    #[serde(default = "default_restart_max_s")]
+    pub restart_max_s: f32,
+
+    /// Topic on which to post, retained, the MQTT online payload while the
+    /// channels of all this seismometer's flows are alive, and the offline
+    /// payload while any of them isn't (or once the daemon stops, as with
+    /// the MQTT `channels_available_topic`). A seismometer with no flows
+    /// running is never online.
+    pub mqtt_available_topic: Option<String>,
+
+    /// Raise a warning on all flows when more than this many undecodable
+    /// packets are received within a minute.
+    /// Default: 10
+    #[serde(default = "default_decode_error_threshold")]
+    pub decode_error_threshold: usize,
+
+    /// Announce a clock drift event on all flows when packet timestamps
+    /// differ from the host clock by more than this many seconds. If not
+    /// provided, the seismometer's clock is not checked.
+    pub clock_drift_threshold_s: Option<f32>,
+
+    /// Raise a warning on all flows when the sample rate measured from
+    /// packet timestamps differs from `sample_rate` by more than this
+    /// fraction of it.
+    /// Default: 0.05
+    #[serde(default = "default_sample_rate_tolerance")]
+    pub sample_rate_tolerance: f32,
+
+    /// Raise a warning on a channel's flows when its noise floor (the
+    /// RMS of its raw counts while none of them are triggered) falls
+    /// below this. The noise floor of each channel is published in the
+    /// status regardless.
+    pub noise_floor_min_rms: Option<f32>,
+
+    /// Raise a warning on a channel's flows when its noise floor rises
+    /// above this.
+    pub noise_floor_max_rms: Option<f32>,
+
     /// Filter and threshold settings.
     pub flows: Vec<FlowConfig>,
+
+    /// Optional trigger which fires only when several of the flows
+    /// trigger together.
+    pub coincidence: Option<CoincidenceConfig>,
+
+    /// Optional miniSEED archive of the channels used by this
+    /// seismometer's flows.
+    pub archive: Option<ArchiveConfig>,
 }
 
 fn default_sample_rate() -> f32 {
     100.0
 }
+
+fn default_restart_max_s() -> f32 {
+    60.0
+}
+
+fn default_decode_error_threshold() -> usize {
+    10
+}
+
+fn default_sample_rate_tolerance() -> f32 {
+    0.05
+}
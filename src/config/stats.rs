@@ -0,0 +1,22 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+/// Statistics of each flow's events over the last hour and the last day
+/// (how often it triggered, for how long, and the greatest energy fed to
+/// its trigger), published every so often.
+#[derive(Deserialize, JsonSchema)]
+pub struct StatsConfig {
+    /// How often to publish the statistics, in seconds.
+    /// Default: 300
+    #[serde(default = "default_interval_s")]
+    pub interval_s: f32,
+
+    /// Topic on which to post the statistics, retained, as a JSON object
+    /// keyed by flow name. (A flow's count of quakes over the last day is
+    /// at `value_json[flow].day.triggers`.)
+    pub mqtt_topic: Option<String>,
+}
+
+fn default_interval_s() -> f32 {
+    300.0
+}
@@ -0,0 +1,82 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::path::PathBuf;
+
+use super::secret::{resolve_secret, SecretError};
+
+/// A Telegram chat, given either by its numeric id or, for public
+/// channels and groups, by its "@username".
+#[derive(Deserialize, JsonSchema, Clone, PartialEq, Eq, Debug)]
+#[serde(untagged)]
+pub enum TelegramChat {
+    Id(i64),
+    Username(String),
+}
+
+impl std::fmt::Display for TelegramChat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Id(id) => write!(f, "{id}"),
+            Self::Username(username) => write!(f, "{username}"),
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct TelegramConfig {
+    /// Token of the bot to send messages as, as given by @BotFather.
+    /// (Required, unless read from a file or command.)
+    pub bot_token: Option<String>,
+
+    /// File holding the bot token, in place of `bot_token`, so that the
+    /// token can be kept readable only by the daemon.
+    pub bot_token_file: Option<PathBuf>,
+
+    /// Shell command printing the bot token, in place of `bot_token`.
+    pub bot_token_cmd: Option<String>,
+
+    /// Chat to send messages to.
+    pub chat_id: TelegramChat,
+
+    /// Message to send when an earthquake is detected. Placeholders in it
+    /// are expanded, as in MQTT payloads.
+    /// Default: "Earthquake detected by {flow}"
+    #[serde(default = "default_triggered_message")]
+    pub triggered_message: String,
+
+    /// Message to send when an earthquake has subsided, if any.
+    pub reset_message: Option<String>,
+
+    /// Whether to send a plot of the waveform around each event, once
+    /// the flow's capture of it has been saved. (Only used if the flow
+    /// has a capture.)
+    /// Default: false
+    #[serde(default)]
+    pub send_waveform: bool,
+}
+
+impl TelegramConfig {
+    /// Read the bot token from its file or command, if given one.
+    pub(super) fn resolve_secrets(&mut self) -> Result<(), SecretError> {
+        const NAME: &str = "the Telegram bot token";
+        resolve_secret(
+            NAME,
+            &mut self.bot_token,
+            self.bot_token_file.as_deref(),
+            self.bot_token_cmd.as_deref(),
+        )?;
+        match self.bot_token {
+            Some(_) => Ok(()),
+            None => Err(SecretError::Missing(NAME)),
+        }
+    }
+
+    /// The bot token, which a loaded configuration always has.
+    pub fn bot_token(&self) -> &str {
+        self.bot_token.as_deref().unwrap_or_default()
+    }
+}
+
+fn default_triggered_message() -> String {
+    String::from("Earthquake detected by {flow}")
+}
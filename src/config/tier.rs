@@ -0,0 +1,21 @@
+use super::actions::ActionsConfig;
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+/// A level of severity that a flow's event may escalate to, with its own
+/// actions. The event escalates to the tier once the energy fed to the
+/// flow's trigger reaches the tier's level while the trigger is asserted.
+#[derive(Deserialize, JsonSchema)]
+pub struct TierConfig {
+    /// A name for the tier (such as "moderate"). The tier's actions are
+    /// taken as though it were a flow of its own, of this name.
+    pub name: String,
+
+    /// The energy at which the event escalates to the tier, in the units
+    /// of the energy fed to the flow's trigger.
+    pub level: f32,
+
+    /// Actions to take when an event escalates to the tier, and when it
+    /// is over.
+    pub actions: ActionsConfig,
+}
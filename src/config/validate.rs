@@ -0,0 +1,389 @@
+//! Checking settings which deserialize, but which the daemon couldn't act
+//! on (as a negative number of seconds), as the configuration is loaded,
+//! so that it is refused then rather than failing once it runs.
+use super::actions::ActionsConfig;
+use super::block::BlockConfig;
+use super::pointer::escape;
+use super::reload::{trigger_block, trigger_pointer};
+use super::root::{Config, ConfigurationError};
+use crate::datasource::Channel;
+use std::time::Duration;
+
+impl Config {
+    /// Check every setting which must be in range, naming the first which
+    /// isn't by its JSON pointer.
+    pub(super) fn validate(&self) -> Result<(), ConfigurationError> {
+        for (i, seismometer) in self.seismometers.iter().enumerate() {
+            let at = format!("/seismometers/{i}");
+            positive(&at, "sample_rate", seismometer.sample_rate)?;
+            seconds(&at, "restart_max_s", seismometer.restart_max_s)?;
+            if let Some(timeout_s) = seismometer.timeout_s {
+                positive(&at, "timeout_s", timeout_s)?;
+            }
+            if let Some(earthworm) = seismometer.earthworm.as_ref() {
+                let at = format!("{at}/earthworm");
+                positive(&at, "heartbeat_interval_s", earthworm.heartbeat_interval_s)?;
+            }
+            if let Some(coincidence) = seismometer.coincidence.as_ref() {
+                let at = format!("{at}/coincidence");
+                seconds(&at, "window_s", coincidence.window_s)?;
+            }
+            // A channel is sent at one rate, whichever flows it feeds.
+            let mut sample_rates = vec![None; Channel::max()];
+            for (j, flow) in seismometer.flows.iter().enumerate() {
+                let at = format!("{at}/flows/{j}");
+                if let Some(sample_rate) = flow.sample_rate {
+                    positive(&at, "sample_rate", sample_rate)?;
+                }
+                let sample_rate = flow.sample_rate.unwrap_or(seismometer.sample_rate);
+                let channel_rate = sample_rates[flow.channel as usize].get_or_insert(sample_rate);
+                if *channel_rate != sample_rate {
+                    return Err(out_of_range(
+                        &at,
+                        "sample_rate",
+                        "must be that of the other flows on the channel",
+                    ));
+                }
+                if let Some(timeout_s) = flow.timeout_s {
+                    positive(&at, "timeout_s", timeout_s)?;
+                }
+                if let Some(max_size_mb) = flow.dump_max_size_mb {
+                    positive(&at, "dump_max_size_mb", max_size_mb)?;
+                }
+                if let Some(BlockConfig::Threshold {
+                    trigger_level,
+                    reset_level,
+                    ..
+                }) = trigger_block(flow)
+                {
+                    levels(
+                        &format!("{at}/{}", trigger_pointer(flow)),
+                        trigger_level,
+                        reset_level,
+                    )?;
+                }
+            }
+        }
+        for (i, network_trigger) in self.network_triggers.iter().enumerate() {
+            let at = format!("/network_triggers/{i}");
+            seconds(&at, "window_s", network_trigger.window_s)?;
+        }
+        if let Some(mqtt) = self.mqtt.as_ref() {
+            positive("/mqtt", "status_interval_s", mqtt.status_interval_s)?;
+            seconds("/mqtt", "reconnect_max_s", mqtt.reconnect_max_s)?;
+        }
+        for (name, broker) in self.mqtt_brokers.iter() {
+            let at = format!("/mqtt_brokers/{}", escape(name));
+            seconds(&at, "reconnect_max_s", broker.reconnect_max_s)?;
+        }
+        if let Some(armed) = self.armed.as_ref() {
+            positive("/armed", "gpio_poll_s", armed.gpio_poll_s)?;
+        }
+        let event_log = self.outputs.event_log.as_ref();
+        if let Some(rotate_s) = event_log.and_then(|event_log| event_log.rotate_s) {
+            positive("/outputs/event_log", "rotate_s", rotate_s)?;
+        }
+        for (at, actions) in self.all_actions() {
+            validate_actions(&at, actions)?;
+            // Actions may only post to brokers which are configured.
+            let broker = actions.mqtt_broker.as_ref();
+            if broker.is_some_and(|broker| !self.mqtt_brokers.contains_key(broker)) {
+                return Err(out_of_range(
+                    &at,
+                    "mqtt_broker",
+                    "must name one of the mqtt_brokers",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Every set of actions, whether of a flow (or one of its tiers), of
+    /// a coincidence or network trigger, or global, by JSON pointer.
+    pub fn all_actions(&self) -> Vec<(String, &ActionsConfig)> {
+        let mut all = Vec::new();
+        for (i, seismometer) in self.seismometers.iter().enumerate() {
+            for (j, flow) in seismometer.flows.iter().enumerate() {
+                let at = format!("/seismometers/{i}/flows/{j}");
+                all.push((format!("{at}/actions"), &flow.actions));
+                for (k, tier) in flow.tiers.iter().enumerate() {
+                    all.push((format!("{at}/tiers/{k}/actions"), &tier.actions));
+                }
+            }
+            if let Some(coincidence) = seismometer.coincidence.as_ref() {
+                let at = format!("/seismometers/{i}/coincidence/actions");
+                all.push((at, &coincidence.actions));
+            }
+        }
+        for (i, network_trigger) in self.network_triggers.iter().enumerate() {
+            let at = format!("/network_triggers/{i}/actions");
+            all.push((at, &network_trigger.actions));
+        }
+        if let Some(actions) = self.actions.as_ref() {
+            all.push((String::from("/actions"), actions));
+        }
+        all
+    }
+}
+
+fn validate_actions(at: &str, actions: &ActionsConfig) -> Result<(), ConfigurationError> {
+    seconds(at, "retry_s", actions.retry_s)?;
+    seconds(at, "retry_max_s", actions.retry_max_s)?;
+    if let Some(timeout_s) = actions.cmd_timeout_s {
+        seconds(at, "cmd_timeout_s", timeout_s)?;
+    }
+    if let Some(limit_s) = actions.trigger_rate_limit_s {
+        seconds(at, "trigger_rate_limit_s", limit_s)?;
+    }
+    if let Some(limit_s) = actions.warning_rate_limit_s {
+        seconds(at, "warning_rate_limit_s", limit_s)?;
+    }
+    if let Some(audio) = actions.audio.as_ref() {
+        let at = format!("{at}/audio");
+        if let Some(duration_s) = audio.duration_s {
+            seconds(&at, "duration_s", duration_s)?;
+        }
+        if !(audio.volume >= 0.0 && audio.volume.is_finite()) {
+            return Err(out_of_range(
+                &at,
+                "volume",
+                "must be a number, not negative",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// A setting in seconds, which must be a duration: not negative, nor NaN,
+/// nor too long to represent.
+pub(super) fn seconds(at: &str, key: &str, value: f32) -> Result<(), ConfigurationError> {
+    match Duration::try_from_secs_f32(value) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(out_of_range(
+            at,
+            key,
+            "must be a number of seconds, not negative",
+        )),
+    }
+}
+
+/// A threshold trigger's levels, which must be numbers, with the reset
+/// level no higher than the trigger level.
+pub(super) fn levels(at: &str, trigger: f32, reset: f32) -> Result<(), ConfigurationError> {
+    if !trigger.is_finite() {
+        return Err(out_of_range(at, "trigger_level", "must be a number"));
+    }
+    if !reset.is_finite() {
+        return Err(out_of_range(at, "reset_level", "must be a number"));
+    }
+    if reset > trigger {
+        return Err(out_of_range(
+            at,
+            "reset_level",
+            "must not be above the trigger level",
+        ));
+    }
+    Ok(())
+}
+
+/// A setting which must be more than zero (as a size or an interval).
+pub(super) fn positive(at: &str, key: &str, value: f32) -> Result<(), ConfigurationError> {
+    if value > 0.0 && value.is_finite() {
+        Ok(())
+    } else {
+        Err(out_of_range(at, key, "must be more than zero"))
+    }
+}
+
+fn out_of_range(at: &str, key: &str, problem: &'static str) -> ConfigurationError {
+    ConfigurationError::OutOfRange(format!("{at}/{key}"), problem)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    /// A configuration with every setting which is checked set in range.
+    fn fixture() -> Value {
+        let flow = |name: &str, channel: &str, sample_rate: Option<f32>| {
+            json!({
+                "name": name,
+                "channel": channel,
+                "sample_rate": sample_rate,
+                "timeout_s": 600.0,
+                "filter": { "trigger_level": 1.0, "reset_level": 0.1 },
+                "actions": {},
+            })
+        };
+        json!({
+            "seismometers": [{
+                "name": "s1",
+                "timeout_s": 5.0,
+                "earthworm": { "address": "127.0.0.1:16005", "heartbeat_interval_s": 30.0 },
+                "flows": [flow("z", "EHZ", None), flow("slow", "ENZ", Some(20.0))],
+                "coincidence": {
+                    "name": "both",
+                    "flows": ["z", "slow"],
+                    "window_s": 0.0,
+                    "actions": {},
+                },
+            }],
+            "network_triggers": [{
+                "name": "regional",
+                "flows": ["s1-z", "s2-z"],
+                "window_s": 10.0,
+                "actions": {},
+            }],
+            "mqtt": { "host": "main", "status_interval_s": 60.0, "reconnect_max_s": 60.0 },
+            "mqtt_brokers": { "lab": { "host": "lab", "reconnect_max_s": 0.0 } },
+            "armed": { "gpio_path": "/sys/class/gpio/gpio17/value", "gpio_poll_s": 0.5 },
+            "outputs": {
+                "event_log": { "path": "/var/log/seismo.jsonl", "rotate_s": 86400.0 },
+            },
+            "actions": {
+                "trigger_cmd": "/usr/local/bin/alarm",
+                "cmd_timeout_s": 10.0,
+                "trigger_rate_limit_s": 60.0,
+                "warning_rate_limit_s": 60.0,
+                "mqtt_broker": "lab",
+                "mqtt_topic": "seismo/any",
+                "audio": { "file": "/usr/share/sounds/alarm.wav", "duration_s": 30.0 },
+            },
+        })
+    }
+
+    /// Validate the fixture with a setting, by JSON pointer, replaced.
+    fn validate_with(pointer: &str, value: Value) -> Result<(), ConfigurationError> {
+        let mut settings = fixture();
+        let (parent, key) = pointer.rsplit_once('/').expect("pointer");
+        settings
+            .pointer_mut(parent)
+            .and_then(Value::as_object_mut)
+            .expect("parent in fixture")
+            .insert(key.to_owned(), value);
+        let config: Config = serde_json::from_value(settings).expect("parse");
+        config.validate()
+    }
+
+    #[test]
+    fn it_accepts_settings_in_range() {
+        let config: Config = serde_json::from_value(fixture()).expect("parse");
+        config.validate().expect("valid");
+    }
+
+    #[test]
+    fn it_refuses_settings_out_of_range() {
+        let retry_s = "/seismometers/0/flows/0/actions/retry_s";
+        // Each setting to replace, and the setting then refused.
+        let cases = [
+            (retry_s, json!(-1.0), retry_s),
+            (retry_s, json!(f32::MAX), retry_s),
+            (
+                "/actions/cmd_timeout_s",
+                json!(-1.0),
+                "/actions/cmd_timeout_s",
+            ),
+            (
+                "/actions/trigger_rate_limit_s",
+                json!(-60.0),
+                "/actions/trigger_rate_limit_s",
+            ),
+            (
+                "/actions/warning_rate_limit_s",
+                json!(-60.0),
+                "/actions/warning_rate_limit_s",
+            ),
+            (
+                "/seismometers/0/coincidence/window_s",
+                json!(-5.0),
+                "/seismometers/0/coincidence/window_s",
+            ),
+            (
+                "/network_triggers/0/window_s",
+                json!(-10.0),
+                "/network_triggers/0/window_s",
+            ),
+            (
+                "/seismometers/0/timeout_s",
+                json!(-5.0),
+                "/seismometers/0/timeout_s",
+            ),
+            (
+                "/seismometers/0/flows/0/timeout_s",
+                json!(0.0),
+                "/seismometers/0/flows/0/timeout_s",
+            ),
+            (
+                "/seismometers/0/earthworm/heartbeat_interval_s",
+                json!(0.0),
+                "/seismometers/0/earthworm/heartbeat_interval_s",
+            ),
+            (
+                "/seismometers/0/earthworm/heartbeat_interval_s",
+                json!(-1.0),
+                "/seismometers/0/earthworm/heartbeat_interval_s",
+            ),
+            (
+                "/mqtt/status_interval_s",
+                json!(0.0),
+                "/mqtt/status_interval_s",
+            ),
+            (
+                "/mqtt/status_interval_s",
+                json!(-60.0),
+                "/mqtt/status_interval_s",
+            ),
+            (
+                "/mqtt/reconnect_max_s",
+                json!(-1.0),
+                "/mqtt/reconnect_max_s",
+            ),
+            (
+                "/mqtt_brokers/lab/reconnect_max_s",
+                json!(-1.0),
+                "/mqtt_brokers/lab/reconnect_max_s",
+            ),
+            ("/armed/gpio_poll_s", json!(0.0), "/armed/gpio_poll_s"),
+            (
+                "/outputs/event_log/rotate_s",
+                json!(0.0),
+                "/outputs/event_log/rotate_s",
+            ),
+            (
+                "/outputs/event_log/rotate_s",
+                json!(-86400.0),
+                "/outputs/event_log/rotate_s",
+            ),
+            (
+                "/seismometers/0/sample_rate",
+                json!(-5.0),
+                "/seismometers/0/sample_rate",
+            ),
+            (
+                "/seismometers/0/flows/1/sample_rate",
+                json!(0.0),
+                "/seismometers/0/flows/1/sample_rate",
+            ),
+            (
+                "/seismometers/0/flows/1/channel",
+                json!("EHZ"),
+                "/seismometers/0/flows/1/sample_rate",
+            ),
+            (
+                "/actions/mqtt_broker",
+                json!("cloud"),
+                "/actions/mqtt_broker",
+            ),
+        ];
+        for (pointer, value, refused_at) in cases {
+            let refused = validate_with(pointer, value.clone())
+                .expect_err(&format!("{pointer} = {value} refused"))
+                .to_string();
+            assert!(
+                refused.contains(refused_at),
+                "{pointer} = {value}: {refused}"
+            );
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::path::PathBuf;
+
+use super::secret::{resolve_secret, SecretError};
+
+/// Hash to sign webhook requests with.
+#[derive(Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureAlgorithm {
+    Sha1,
+    #[default]
+    Sha256,
+    Sha512,
+}
+
+impl SignatureAlgorithm {
+    /// The algorithm's name, as it leads signatures.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Sha1 => "sha1",
+            Self::Sha256 => "sha256",
+            Self::Sha512 => "sha512",
+        }
+    }
+}
+
+/// A web service to post a JSON description of each earthquake to, as
+/// MQTT payloads describe them in the "json" format.
+#[derive(Deserialize, JsonSchema)]
+pub struct WebhookConfig {
+    /// URL to post to, over HTTP or HTTPS.
+    pub url: String,
+
+    /// Secret, shared with the service, to sign each request with. The
+    /// signature is an HMAC of the time the request was made (as sent in
+    /// the timestamp header), a ".", and the body, in hex, after the
+    /// algorithm's name and "=" (as in "sha256=5bdc..."), so that the
+    /// service can refuse old requests replayed to it. If not provided,
+    /// requests aren't signed.
+    pub secret: Option<String>,
+
+    /// File holding the secret, in place of `secret`.
+    pub secret_file: Option<PathBuf>,
+
+    /// Shell command printing the secret, in place of `secret`, run as the
+    /// configuration is loaded.
+    pub secret_cmd: Option<String>,
+
+    /// Hash to sign requests with: "sha1", "sha256" or "sha512".
+    /// Default: sha256
+    #[serde(default)]
+    pub signature_algorithm: SignatureAlgorithm,
+
+    /// Header to send the signature in.
+    /// Default: "X-Signature"
+    #[serde(default = "default_signature_header")]
+    pub signature_header: String,
+
+    /// Header to send the time a signed request was made in, in seconds
+    /// since the Unix epoch.
+    /// Default: "X-Signature-Timestamp"
+    #[serde(default = "default_timestamp_header")]
+    pub timestamp_header: String,
+
+    /// Whether to post when an earthquake is over, as well as when it is
+    /// detected.
+    /// Default: true
+    #[serde(default = "default_reset")]
+    pub reset: bool,
+}
+
+impl WebhookConfig {
+    /// Read the secret from its file or command, if given one.
+    pub(super) fn resolve_secrets(&mut self) -> Result<(), SecretError> {
+        resolve_secret(
+            "the webhook secret",
+            &mut self.secret,
+            self.secret_file.as_deref(),
+            self.secret_cmd.as_deref(),
+        )
+    }
+}
+
+fn default_signature_header() -> String {
+    String::from("X-Signature")
+}
+
+fn default_timestamp_header() -> String {
+    String::from("X-Signature-Timestamp")
+}
+
+fn default_reset() -> bool {
+    true
+}
@@ -1,3 +1,7 @@
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Schema, SchemaObject, StringValidation};
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer};
 use thiserror::Error;
 use variant_count::VariantCount;
 
@@ -21,6 +25,24 @@ impl Channel {
     pub const fn max() -> usize {
         Channel::VARIANT_COUNT
     }
+
+    /// The SEED channel code for this channel.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Channel::Ehz => "EHZ",
+            Channel::Ehn => "EHN",
+            Channel::Ehe => "EHE",
+            Channel::Enz => "ENZ",
+            Channel::Enn => "ENN",
+            Channel::Ene => "ENE",
+        }
+    }
+}
+
+impl std::fmt::Display for Channel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 impl From<Channel> for usize {
@@ -70,3 +92,33 @@ impl TryFrom<&str> for Channel {
         Ok(res)
     }
 }
+
+/// Channels are named in configurations by their SEED codes, in any case.
+impl<'de> Deserialize<'de> for Channel {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Channel::try_from(name.to_ascii_uppercase().as_str()).map_err(|_| {
+            serde::de::Error::custom(format!(
+                "unknown channel \"{name}\", expected one of EHZ, EHN, EHE, ENZ, ENN or ENE"
+            ))
+        })
+    }
+}
+
+impl JsonSchema for Channel {
+    fn schema_name() -> String {
+        String::from("Channel")
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            string: Some(Box::new(StringValidation {
+                pattern: Some(String::from("^[Ee][HhNn][ZzNnEe]$")),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
@@ -1,6 +1,7 @@
 use super::channel::Channel;
 use ndarray::Array1;
 
+#[derive(Clone)]
 pub struct SeismoData {
     #[allow(dead_code)]
     pub timestamp: f64,
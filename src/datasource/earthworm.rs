@@ -0,0 +1,369 @@
+//! A client for the Earthworm `export_generic` TCP protocol, as spoken to
+//! `import_generic`. Only TRACEBUF2 messages are used; everything else,
+//! including the exporter's own heartbeats, is ignored.
+pub use super::channel::Channel;
+use super::data::SeismoData;
+use crate::config::EarthwormConfig;
+use std::collections::VecDeque;
+use thiserror::Error;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{Duration, Interval};
+
+/// Start of message.
+const STX: u8 = 0x02;
+/// End of message.
+const ETX: u8 = 0x03;
+/// Escapes a following STX, ETX or ESC byte inside a message.
+const ESC: u8 = 0x1b;
+
+const TYPE_HEARTBEAT: u8 = 3;
+const TYPE_TRACEBUF2: u8 = 19;
+
+/// Length of the ASCII "logo" (installation, module, type) prefix.
+const LOGO_LENGTH: usize = 9;
+
+/// Length of a TRACE2_HEADER.
+const TRACE_HEADER_LENGTH: usize = 64;
+
+/// How long to wait before reconnecting after the connection is lost.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Error, Debug)]
+pub enum EarthwormSourceError {
+    #[error("unable to connect to Earthworm export")]
+    ConnectError(#[source] std::io::Error),
+    #[error("malformed message logo")]
+    BadLogo,
+    #[error("trace message too short")]
+    TraceTooShort,
+    #[error("unsupported trace data type")]
+    UnsupportedDataType,
+}
+
+/// Reassembles framed messages from a byte stream.
+#[derive(Default)]
+struct FrameDecoder {
+    in_message: bool,
+    escaped: bool,
+    message: Vec<u8>,
+}
+
+impl FrameDecoder {
+    /// Feed one byte, returning a message if it completes one.
+    fn push(&mut self, byte: u8) -> Option<Vec<u8>> {
+        if self.escaped {
+            self.escaped = false;
+            if self.in_message {
+                self.message.push(byte);
+            }
+            return None;
+        }
+        match byte {
+            ESC => self.escaped = true,
+            STX => {
+                self.in_message = true;
+                self.message.clear();
+            }
+            ETX if self.in_message => {
+                self.in_message = false;
+                return Some(std::mem::take(&mut self.message));
+            }
+            _ if self.in_message => self.message.push(byte),
+            _ => (),
+        }
+        None
+    }
+}
+
+/// Frame a message, escaping any special bytes.
+fn frame_message(message: &[u8]) -> Vec<u8> {
+    let mut framed = Vec::with_capacity(message.len() + 2);
+    framed.push(STX);
+    for &byte in message {
+        if matches!(byte, STX | ETX | ESC) {
+            framed.push(ESC);
+        }
+        framed.push(byte);
+    }
+    framed.push(ETX);
+    framed
+}
+
+/// Decode the message type from a message's logo.
+fn message_type(message: &[u8]) -> Result<u8, EarthwormSourceError> {
+    message
+        .get(6..LOGO_LENGTH)
+        .and_then(|t| std::str::from_utf8(t).ok())
+        .and_then(|t| t.trim().parse().ok())
+        .ok_or(EarthwormSourceError::BadLogo)
+}
+
+/// A decoded TRACEBUF2 message.
+struct TraceBuf {
+    station: String,
+    channel: String,
+    start: f64,
+    samples: Vec<f32>,
+}
+
+fn header_str(field: &[u8]) -> String {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).trim().to_owned()
+}
+
+impl TraceBuf {
+    fn decode(body: &[u8]) -> Result<TraceBuf, EarthwormSourceError> {
+        if body.len() < TRACE_HEADER_LENGTH {
+            return Err(EarthwormSourceError::TraceTooShort);
+        }
+        // "i" and "f" types are little endian, "s" and "t" big endian.
+        let datatype = &body[57..59];
+        let little_endian = match datatype[0] {
+            b'i' | b'f' => true,
+            b's' | b't' => false,
+            _ => return Err(EarthwormSourceError::UnsupportedDataType),
+        };
+        let is_float = matches!(datatype[0], b'f' | b't');
+        let width = match (datatype[1], is_float) {
+            (b'2', false) => 2,
+            (b'4', _) => 4,
+            (b'8', true) => 8,
+            _ => return Err(EarthwormSourceError::UnsupportedDataType),
+        };
+        let nsamp_bytes: [u8; 4] = body[4..8].try_into().expect("four bytes");
+        let start_bytes: [u8; 8] = body[8..16].try_into().expect("eight bytes");
+        let (nsamp, start) = if little_endian {
+            (
+                i32::from_le_bytes(nsamp_bytes),
+                f64::from_le_bytes(start_bytes),
+            )
+        } else {
+            (
+                i32::from_be_bytes(nsamp_bytes),
+                f64::from_be_bytes(start_bytes),
+            )
+        };
+        let length = nsamp.max(0) as usize * width;
+        let data = &body[TRACE_HEADER_LENGTH..];
+        if data.len() < length {
+            return Err(EarthwormSourceError::TraceTooShort);
+        }
+        let samples = data[..length]
+            .chunks_exact(width)
+            .map(|w| {
+                // Normalize to big endian, then decode.
+                let mut bytes = [0_u8; 8];
+                bytes[..width].copy_from_slice(w);
+                if little_endian {
+                    bytes[..width].reverse();
+                }
+                let b4 = [bytes[0], bytes[1], bytes[2], bytes[3]];
+                match (width, is_float) {
+                    (2, _) => i16::from_be_bytes([bytes[0], bytes[1]]) as f32,
+                    (4, false) => i32::from_be_bytes(b4) as f32,
+                    (4, true) => f32::from_be_bytes(b4),
+                    _ => f64::from_be_bytes(bytes) as f32,
+                }
+            })
+            .collect();
+        Ok(TraceBuf {
+            station: header_str(&body[32..39]),
+            channel: header_str(&body[48..52]),
+            start,
+            samples,
+        })
+    }
+}
+
+/// A data source that connects to an Earthworm export module and reads
+/// TRACEBUF2 messages from it, sending heartbeats as the exporter expects.
+pub struct EarthwormSource {
+    address: String,
+    station: Option<String>,
+    stream: Option<TcpStream>,
+    decoder: FrameDecoder,
+    buf: Box<[u8; 8192]>,
+    heartbeat: Vec<u8>,
+    heartbeat_interval: Interval,
+    /// How much of the heartbeat has been sent, while one is being sent.
+    /// It is sent a piece at a time, so that no heartbeat is left half
+    /// sent should the wait for data be given up on.
+    heartbeat_sent: Option<usize>,
+    channels: Option<Vec<bool>>,
+    pending: VecDeque<SeismoData>,
+    decode_errors: usize,
+}
+
+impl EarthwormSource {
+    pub async fn new(config: &EarthwormConfig) -> Result<EarthwormSource, EarthwormSourceError> {
+        let stream = TcpStream::connect(&config.address)
+            .await
+            .map_err(EarthwormSourceError::ConnectError)?;
+        let mut heartbeat = format!(
+            "{:3}{:3}{:3}",
+            config.installation, config.module, TYPE_HEARTBEAT
+        )
+        .into_bytes();
+        heartbeat.extend_from_slice(config.heartbeat_text.as_bytes());
+        Ok(EarthwormSource {
+            address: config.address.clone(),
+            station: config.station.clone(),
+            stream: Some(stream),
+            decoder: FrameDecoder::default(),
+            buf: Box::new([0_u8; 8192]),
+            heartbeat: frame_message(&heartbeat),
+            heartbeat_interval: tokio::time::interval(Duration::from_secs_f32(
+                config.heartbeat_interval_s,
+            )),
+            heartbeat_sent: None,
+            channels: None,
+            pending: VecDeque::new(),
+            decode_errors: 0,
+        })
+    }
+
+    pub fn subscribe(&mut self, channel: Channel) {
+        let channel_interest = self
+            .channels
+            .get_or_insert_with(|| vec![false; Channel::max()]);
+        channel_interest[channel as usize] = true;
+    }
+
+    /// Number of undecodable messages skipped since the last call.
+    pub fn take_decode_errors(&mut self) -> usize {
+        std::mem::take(&mut self.decode_errors)
+    }
+
+    /// Interpret a complete message, returning data if it is trace data
+    /// of interest.
+    fn handle_message(&self, message: &[u8]) -> Result<Option<SeismoData>, EarthwormSourceError> {
+        if message_type(message)? != TYPE_TRACEBUF2 {
+            return Ok(None);
+        }
+        let trace = TraceBuf::decode(&message[LOGO_LENGTH..])?;
+        if self
+            .station
+            .as_ref()
+            .is_some_and(|station| *station != trace.station)
+        {
+            return Ok(None);
+        }
+        let Ok(channel) = Channel::try_from(trace.channel.as_str()) else {
+            return Ok(None);
+        };
+        if let Some(interested) = self.channels.as_ref() {
+            if !interested[channel as usize] {
+                return Ok(None);
+            }
+        }
+        Ok(Some(SeismoData {
+            timestamp: trace.start,
+            channel,
+            data: trace.samples.into(),
+        }))
+    }
+
+    /// Receive the next trace of interest, sending heartbeats and
+    /// reconnecting as necessary.
+    pub async fn next(&mut self) -> Option<Result<SeismoData, EarthwormSourceError>> {
+        loop {
+            if let Some(data) = self.pending.pop_front() {
+                return Some(Ok(data));
+            }
+            let stream = match self.stream.as_mut() {
+                Some(stream) => stream,
+                None => {
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                    match TcpStream::connect(&self.address).await {
+                        Ok(stream) => {
+                            self.decoder = FrameDecoder::default();
+                            self.heartbeat_sent = None;
+                            self.stream.insert(stream)
+                        }
+                        Err(_) => continue,
+                    }
+                }
+            };
+            // Reading and writing are both cancel safe, so either may be
+            // given up on and picked up again on the next call.
+            let (mut reader, mut writer) = stream.split();
+            let sent = self.heartbeat_sent;
+            let unsent = &self.heartbeat[sent.unwrap_or(0)..];
+            let n = tokio::select! {
+                read = reader.read(self.buf.as_mut_slice()) => match read {
+                    Ok(n) if n > 0 => n,
+                    _ => {
+                        self.stream = None;
+                        continue;
+                    }
+                },
+                _ = self.heartbeat_interval.tick(), if sent.is_none() => {
+                    self.heartbeat_sent = Some(0);
+                    continue;
+                },
+                written = writer.write(unsent), if sent.is_some() => {
+                    match written {
+                        Ok(n) if n > 0 => {
+                            let done = n == unsent.len();
+                            self.heartbeat_sent = sent.map(|sent| sent + n).filter(|_| !done);
+                        }
+                        _ => self.stream = None,
+                    }
+                    continue;
+                },
+            };
+            for i in 0..n {
+                let Some(message) = self.decoder.push(self.buf[i]) else {
+                    continue;
+                };
+                match self.handle_message(&message) {
+                    Ok(Some(data)) => self.pending.push_back(data),
+                    Ok(None) => (),
+                    Err(_) => self.decode_errors += 1,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracebuf2(samples: &[i32]) -> Vec<u8> {
+        let mut body = b" 13  0 19".to_vec();
+        let mut header = [0_u8; TRACE_HEADER_LENGTH];
+        header[4..8].copy_from_slice(&(samples.len() as i32).to_le_bytes());
+        header[8..16].copy_from_slice(&1734044506.5_f64.to_le_bytes());
+        header[32..37].copy_from_slice(b"R1234");
+        header[48..51].copy_from_slice(b"EHZ");
+        header[57..59].copy_from_slice(b"i4");
+        body.extend_from_slice(&header);
+        for sample in samples {
+            body.extend_from_slice(&sample.to_le_bytes());
+        }
+        body
+    }
+
+    #[test]
+    fn decodes_framed_trace() {
+        // A sample value of 2 is an STX byte and must survive escaping.
+        let framed = frame_message(&tracebuf2(&[2, -3, 0x1b]));
+        let mut decoder = FrameDecoder::default();
+        let mut messages: Vec<_> = b"junk"
+            .iter()
+            .chain(framed.iter())
+            .filter_map(|&b| decoder.push(b))
+            .collect();
+        assert_eq!(messages.len(), 1);
+        let message = messages.pop().unwrap();
+        assert_eq!(message_type(&message).unwrap(), TYPE_TRACEBUF2);
+
+        let trace = TraceBuf::decode(&message[LOGO_LENGTH..]).unwrap();
+        assert_eq!(trace.station, "R1234");
+        assert_eq!(trace.channel, "EHZ");
+        assert_eq!(trace.start, 1734044506.5);
+        assert_eq!(trace.samples, vec![2.0, -3.0, 27.0]);
+    }
+}
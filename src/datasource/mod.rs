@@ -1,17 +1,25 @@
 mod channel;
 mod data;
+mod earthworm;
 mod rsudp;
+mod synthetic;
 mod txtfile;
 mod udp_source;
+#[cfg_attr(not(feature = "websocket"), path = "websocket_disabled.rs")]
+mod websocket;
 
 pub use channel::Channel;
 pub use channel::ChannelError;
 pub use data::SeismoData;
 
+use crate::config::EarthwormConfig;
+use earthworm::{EarthwormSource, EarthwormSourceError};
 use std::path::Path;
+use synthetic::SyntheticSource;
 use thiserror::Error;
 use txtfile::{TextFileSource, TextSourceError};
 use udp_source::{RSUDPSource, UDPSourceError};
+use websocket::{WebSocketSource, WebSocketSourceError};
 
 #[derive(Error, Debug)]
 pub enum DataSourceError {
@@ -19,10 +27,27 @@ pub enum DataSourceError {
     UDPSourceError(#[from] UDPSourceError),
     #[error("text parse error")]
     TextSourceError(#[from] TextSourceError),
+    #[error("websocket source error")]
+    WebSocketSourceError(#[from] WebSocketSourceError),
+    #[error("earthworm source error")]
+    EarthwormSourceError(#[from] EarthwormSourceError),
 }
+
+/// Where a source on the network gets its data from, so that it may be
+/// opened again should it fail.
+#[derive(Clone)]
+pub enum SourceAddress {
+    Rsudp(String),
+    WebSocket(String),
+    Earthworm(EarthwormConfig),
+}
+
 pub enum DataSource {
     UDPSource(RSUDPSource),
     TextSource(TextFileSource),
+    WebSocketSource(Box<WebSocketSource>),
+    EarthwormSource(Box<EarthwormSource>),
+    SyntheticSource(SyntheticSource),
 }
 
 impl DataSource {
@@ -31,6 +56,31 @@ impl DataSource {
         Ok(DataSource::UDPSource(ds))
     }
 
+    pub async fn new_websocket_source(url: &str) -> Result<DataSource, DataSourceError> {
+        let ds = WebSocketSource::new(url).await?;
+        Ok(DataSource::WebSocketSource(Box::new(ds)))
+    }
+
+    pub async fn new_earthworm_source(
+        config: &EarthwormConfig,
+    ) -> Result<DataSource, DataSourceError> {
+        let ds = EarthwormSource::new(config).await?;
+        Ok(DataSource::EarthwormSource(Box::new(ds)))
+    }
+
+    pub async fn open(address: &SourceAddress) -> Result<DataSource, DataSourceError> {
+        match address {
+            SourceAddress::Rsudp(listen_address) => Self::new_rsudp_source(listen_address).await,
+            SourceAddress::WebSocket(url) => Self::new_websocket_source(url).await,
+            SourceAddress::Earthworm(config) => Self::new_earthworm_source(config).await,
+        }
+    }
+
+    /// A source of generated data, for soak testing.
+    pub fn new_synthetic_source(sample_rate: f32, phase_s: f32) -> DataSource {
+        DataSource::SyntheticSource(SyntheticSource::new(sample_rate, phase_s))
+    }
+
     pub async fn new_textfile_source(
         path: &Path,
         as_channel: Channel,
@@ -43,6 +93,27 @@ impl DataSource {
         match self {
             DataSource::UDPSource(s) => s.subscribe(channel),
             DataSource::TextSource(s) => s.subscribe(channel),
+            DataSource::WebSocketSource(s) => s.subscribe(channel),
+            DataSource::EarthwormSource(s) => s.subscribe(channel),
+            DataSource::SyntheticSource(s) => s.subscribe(channel),
+        }
+    }
+
+    /// Hand back a packet that has been fully processed, so that its
+    /// storage may be reused by sources that support it.
+    pub fn recycle(&mut self, data: SeismoData) {
+        if let DataSource::UDPSource(s) = self {
+            s.recycle(data);
+        }
+    }
+
+    /// Number of undecodable packets skipped since the last call.
+    pub fn take_decode_errors(&mut self) -> usize {
+        match self {
+            DataSource::UDPSource(s) => s.take_decode_errors(),
+            DataSource::TextSource(_) | DataSource::SyntheticSource(_) => 0,
+            DataSource::WebSocketSource(s) => s.take_decode_errors(),
+            DataSource::EarthwormSource(s) => s.take_decode_errors(),
         }
     }
 
@@ -56,6 +127,15 @@ impl DataSource {
                 .next()
                 .await
                 .map(|i| i.map_err(DataSourceError::TextSourceError)),
+            DataSource::WebSocketSource(s) => s
+                .next()
+                .await
+                .map(|i| i.map_err(DataSourceError::WebSocketSourceError)),
+            DataSource::EarthwormSource(s) => s
+                .next()
+                .await
+                .map(|i| i.map_err(DataSourceError::EarthwormSourceError)),
+            DataSource::SyntheticSource(s) => s.next().await.map(Ok),
         }
     }
 }
@@ -1,6 +1,5 @@
 use super::channel::{Channel, ChannelError};
 use ndarray::{self, Array1};
-use std::num::ParseFloatError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -70,16 +69,25 @@ impl<'a> RSUDPFrame<'a> {
         Ok(result)
     }
 
+    #[cfg_attr(not(feature = "websocket"), allow(dead_code))]
     pub fn decode(&self) -> Result<Array1<f32>, RSUDPError> {
-        let data = self
-            .data
-            .split(",")
-            .map(|s| s.trim().parse::<f32>())
-            .collect::<Result<Vec<f32>, ParseFloatError>>()
-            .ok()
-            .ok_or(RSUDPError::UnparsableData)?;
-        let array = ndarray::Array1::from_iter(data);
-        Ok(array)
+        let mut data = Vec::new();
+        self.decode_into(&mut data)?;
+        Ok(Array1::from(data))
+    }
+
+    /// Decode the samples into an existing buffer, replacing its contents.
+    /// No allocation takes place if the buffer is already large enough.
+    pub fn decode_into(&self, out: &mut Vec<f32>) -> Result<(), RSUDPError> {
+        out.clear();
+        for s in self.data.split(",") {
+            let sample = s
+                .trim()
+                .parse::<f32>()
+                .map_err(|_| RSUDPError::UnparsableData)?;
+            out.push(sample);
+        }
+        Ok(())
     }
 }
 
@@ -120,6 +128,19 @@ mod tests {
         assert!(peeked.is_err())
     }
 
+    #[test]
+    fn decodes_into_buffer() {
+        let mut buf = Vec::with_capacity(16);
+        let ptr = buf.as_ptr();
+        let peeked = RSUDPFrame::from_str("{'EHZ',12345678.000,0,1,2,3}").unwrap();
+        peeked.decode_into(&mut buf).unwrap();
+        assert_eq!(buf, [0.0, 1.0, 2.0, 3.0]);
+        let peeked = RSUDPFrame::from_str("{'EHZ',12345679.000,7,8}").unwrap();
+        peeked.decode_into(&mut buf).unwrap();
+        assert_eq!(buf, [7.0, 8.0]);
+        assert_eq!(buf.as_ptr(), ptr);
+    }
+
     #[test]
     fn spaces_ok() {
         let peeked = RSUDPFrame::from_str("{'EHZ',12345678.000,0,1, 2,3, 4,5,6}");
@@ -0,0 +1,112 @@
+//! A data source that generates data on a realtime schedule, for soak
+//! testing without any seismometers.
+pub use super::channel::Channel;
+use super::data::SeismoData;
+use ndarray::Array1;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::time::{Duration, Instant};
+
+/// Duration of each packet, matching what a Raspberry Shake sends.
+const PACKET_S: f64 = 0.25;
+
+/// A synthetic "quake" starts every this many seconds...
+const QUAKE_PERIOD_S: f64 = 120.0;
+
+/// ...and lasts this many seconds.
+const QUAKE_DURATION_S: f64 = 5.0;
+
+/// Frequency of the synthetic quake, in hertz.
+const QUAKE_HZ: f64 = 2.0;
+
+/// Amplitude of the synthetic quake, in counts.
+const QUAKE_AMPLITUDE: f32 = 5.0;
+
+/// Amplitude of the background noise, in counts.
+const NOISE_AMPLITUDE: f32 = 0.1;
+
+pub struct SyntheticSource {
+    sample_rate: f32,
+    samples_per_packet: usize,
+    channels: Vec<Channel>,
+    start: Instant,
+    start_epoch: f64,
+    phase_s: f64,
+    packet: u64,
+    next_channel: usize,
+    rng: u32,
+}
+
+impl SyntheticSource {
+    /// Generate data at the given sample rate. The quakes of different
+    /// sources are staggered by `phase_s`, so that they don't all trigger
+    /// at once.
+    pub fn new(sample_rate: f32, phase_s: f32) -> SyntheticSource {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        SyntheticSource {
+            sample_rate,
+            samples_per_packet: ((sample_rate as f64 * PACKET_S).round() as usize).max(1),
+            channels: Vec::new(),
+            start: Instant::now(),
+            start_epoch: now,
+            phase_s: phase_s as f64,
+            packet: 0,
+            next_channel: 0,
+            rng: 0x2545_f491,
+        }
+    }
+
+    pub fn subscribe(&mut self, channel: Channel) {
+        if !self.channels.contains(&channel) {
+            self.channels.push(channel);
+        }
+    }
+
+    fn noise(&mut self) -> f32 {
+        // xorshift32
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        (self.rng as f32 / u32::MAX as f32 - 0.5) * 2.0 * NOISE_AMPLITUDE
+    }
+
+    fn generate(&mut self, channel: Channel, timestamp: f64) -> SeismoData {
+        let period = 1.0 / self.sample_rate as f64;
+        let data = Array1::from_iter((0..self.samples_per_packet).map(|i| {
+            let t = timestamp + i as f64 * period;
+            let quake = if (t + self.phase_s) % QUAKE_PERIOD_S < QUAKE_DURATION_S {
+                QUAKE_AMPLITUDE * (2.0 * std::f64::consts::PI * QUAKE_HZ * t).sin() as f32
+            } else {
+                0.0
+            };
+            quake + self.noise()
+        }));
+        SeismoData {
+            timestamp,
+            channel,
+            data,
+        }
+    }
+
+    /// Wait for the next packet to come due and generate it. Every
+    /// subscribed channel receives a packet per packet period.
+    pub async fn next(&mut self) -> Option<SeismoData> {
+        if self.channels.is_empty() {
+            self.channels.push(Channel::Ehz);
+        }
+        let elapsed = self.packet as f64 * self.samples_per_packet as f64 / self.sample_rate as f64;
+        if self.next_channel == 0 {
+            tokio::time::sleep_until(self.start + Duration::from_secs_f64(elapsed)).await;
+        }
+        let channel = self.channels[self.next_channel];
+        let data = self.generate(channel, self.start_epoch + elapsed);
+        self.next_channel += 1;
+        if self.next_channel == self.channels.len() {
+            self.next_channel = 0;
+            self.packet += 1;
+        }
+        Some(data)
+    }
+}
@@ -2,6 +2,8 @@ pub use super::channel::Channel;
 use super::data::SeismoData;
 use super::rsudp::RSUDPFrame;
 use core::str;
+use ndarray::Array1;
+use std::collections::VecDeque;
 use std::io;
 use thiserror::Error;
 use tokio::net::UdpSocket;
@@ -19,10 +21,26 @@ pub enum UDPSourceError {
     DecodeError(#[source] RSUDPError),
 }
 
+/// Most packets to take from the socket per wakeup.
+const RECV_BATCH: usize = 16;
+
+/// How many sample buffers to keep for reuse.
+const SPARE_BUFFERS: usize = 2 * RECV_BATCH;
+
+/// Room for a Raspberry Shake packet (25 samples at 100 Hz) with spare.
+const SAMPLES_PER_BUFFER: usize = 64;
+
 pub struct RSUDPSource {
     s: UdpSocket,
     channels: Option<Vec<bool>>,
-    buf: [u8; 8192],
+    buf: Box<[u8; 8192]>,
+    decode_errors: usize,
+
+    /// Sample buffers returned by consumers, ready to decode into.
+    spare: Vec<Vec<f32>>,
+
+    /// Packets received in the current batch, not yet handed out.
+    pending: VecDeque<SeismoData>,
 }
 
 impl RSUDPSource {
@@ -33,7 +51,12 @@ impl RSUDPSource {
         Ok(RSUDPSource {
             s,
             channels: None,
-            buf: [0_u8; 8192],
+            buf: Box::new([0_u8; 8192]),
+            decode_errors: 0,
+            spare: (0..SPARE_BUFFERS)
+                .map(|_| Vec::with_capacity(SAMPLES_PER_BUFFER))
+                .collect(),
+            pending: VecDeque::with_capacity(RECV_BATCH),
         })
     }
 
@@ -52,47 +75,143 @@ impl RSUDPSource {
         channel_interest[channel as usize] = true;
     }
 
-    async fn recv_packet(&mut self) -> Result<SeismoData, UDPSourceError> {
-        loop {
-            let packet_sz = self
-                .s
-                .recv(&mut self.buf)
-                .await
-                .map_err(UDPSourceError::UDPReceiveError)?;
-            let buf = &self.buf[0..packet_sz];
-            if let Some(data) = self.parse_packet(buf)? {
-                return Ok(data);
+    /// Wait for a packet, then drain whatever else is already queued on
+    /// the socket, so that a backlog (after a scheduling hiccup, say) is
+    /// processed in one wakeup rather than one per packet.
+    async fn recv_batch(&mut self) -> Result<(), UDPSourceError> {
+        let packet_sz = self
+            .s
+            .recv(self.buf.as_mut_slice())
+            .await
+            .map_err(UDPSourceError::UDPReceiveError)?;
+        self.accept_packet(packet_sz);
+        for _ in 1..RECV_BATCH {
+            match self.s.try_recv(self.buf.as_mut_slice()) {
+                Ok(packet_sz) => self.accept_packet(packet_sz),
+                // Nothing more is queued, or there is an error that the
+                // next blocking receive will report.
+                Err(_) => break,
             }
         }
+        Ok(())
     }
 
-    pub fn parse_packet(&self, buf: &[u8]) -> Result<Option<SeismoData>, UDPSourceError> {
-        let packet = str::from_utf8(buf).map_err(|_| UDPSourceError::UnparseableUTF8)?;
-        let peek = RSUDPFrame::from_str(packet).map_err(UDPSourceError::DecodeError)?;
-        if let Some(interested) = self.channels.as_ref() {
-            if !interested[peek.channel as usize] {
-                return Ok(None);
+    /// Parse a received packet onto the pending queue, counting it if it
+    /// can't be decoded.
+    fn accept_packet(&mut self, packet_sz: usize) {
+        let mut samples = self.spare.pop().unwrap_or_default();
+        let buf = &self.buf[0..packet_sz];
+        match parse_packet_into(buf, self.channels.as_deref(), &mut samples) {
+            Ok(Some((channel, timestamp))) => self.pending.push_back(SeismoData {
+                timestamp,
+                channel,
+                data: Array1::from(samples),
+            }),
+            Ok(None) => self.spare.push(samples),
+            Err(_) => {
+                self.spare.push(samples);
+                self.decode_errors += 1;
             }
         }
-        let data = peek.decode().map_err(UDPSourceError::DecodeError)?;
-        let result = SeismoData {
-            timestamp: peek.timestamp,
-            channel: peek.channel,
-            data,
-        };
-        Ok(Some(result))
+    }
+
+    pub fn parse_packet(&mut self, buf: &[u8]) -> Result<Option<SeismoData>, UDPSourceError> {
+        let mut samples = self.spare.pop().unwrap_or_default();
+        let parsed = parse_packet_into(buf, self.channels.as_deref(), &mut samples)?;
+        Ok(parsed.map(|(channel, timestamp)| SeismoData {
+            timestamp,
+            channel,
+            data: Array1::from(samples),
+        }))
+    }
+
+    /// Return a packet's sample buffer for reuse, once it is no longer
+    /// needed, so that steady-state reception allocates nothing.
+    pub fn recycle(&mut self, data: SeismoData) {
+        if self.spare.len() < SPARE_BUFFERS {
+            let (samples, _) = data.data.into_raw_vec_and_offset();
+            self.spare.push(samples);
+        }
+    }
+
+    /// Number of undecodable packets skipped since the last call.
+    pub fn take_decode_errors(&mut self) -> usize {
+        std::mem::take(&mut self.decode_errors)
     }
 
     pub async fn next(&mut self) -> Option<Result<SeismoData, UDPSourceError>> {
         loop {
-            match self.recv_packet().await {
-                Ok(result) => return Some(Ok(result)),
-                Err(e) => match e {
-                    UDPSourceError::DecodeError(_) => continue,
-                    UDPSourceError::UnparseableUTF8 => continue,
-                    x => return Some(Err(x)),
-                },
+            if let Some(data) = self.pending.pop_front() {
+                return Some(Ok(data));
+            }
+            if let Err(e) = self.recv_batch().await {
+                return Some(Err(e));
             }
         }
     }
 }
+
+/// Parse a packet, decoding its samples into `samples`. Returns the
+/// packet's channel and timestamp, or `None` if the channel is not of
+/// interest.
+fn parse_packet_into(
+    buf: &[u8],
+    channels: Option<&[bool]>,
+    samples: &mut Vec<f32>,
+) -> Result<Option<(Channel, f64)>, UDPSourceError> {
+    let packet = str::from_utf8(buf).map_err(|_| UDPSourceError::UnparseableUTF8)?;
+    let peek = RSUDPFrame::from_str(packet).map_err(UDPSourceError::DecodeError)?;
+    if let Some(interested) = channels {
+        if !interested[peek.channel as usize] {
+            return Ok(None);
+        }
+    }
+    peek.decode_into(samples)
+        .map_err(UDPSourceError::DecodeError)?;
+    Ok(Some((peek.channel, peek.timestamp)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn recycles_sample_buffers() {
+        let mut source = RSUDPSource::new("127.0.0.1:0").await.unwrap();
+        let data = source
+            .parse_packet(b"{'EHZ', 1734044506.042, 1, 2, 3}")
+            .unwrap()
+            .unwrap();
+        let ptr = data.data.as_ptr();
+        source.recycle(data);
+        let data = source
+            .parse_packet(b"{'EHZ', 1734044506.292, 4, 5, 6}")
+            .unwrap()
+            .unwrap();
+        assert_eq!(data.data.as_ptr(), ptr);
+        assert_eq!(data.data[2], 6.0);
+    }
+
+    #[tokio::test]
+    async fn drains_queued_packets() {
+        let mut source = RSUDPSource::new("127.0.0.1:0").await.unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let address = source.s.local_addr().unwrap();
+        for packet in [
+            "{'EHZ', 1.0, 1}",
+            "garbage",
+            "{'EHZ', 2.0, 2}",
+            "{'EHZ', 3.0, 3}",
+        ] {
+            sender.send_to(packet.as_bytes(), address).await.unwrap();
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+
+        let first = source.next().await.unwrap().unwrap();
+        assert_eq!(first.timestamp, 1.0);
+        assert_eq!(source.pending.len(), 2);
+        assert_eq!(source.take_decode_errors(), 1);
+        assert_eq!(source.next().await.unwrap().unwrap().timestamp, 2.0);
+        assert_eq!(source.next().await.unwrap().unwrap().timestamp, 3.0);
+    }
+}
@@ -0,0 +1,177 @@
+pub use super::channel::Channel;
+use super::channel::ChannelError;
+use super::data::SeismoData;
+use super::rsudp::{RSUDPError, RSUDPFrame};
+use futures_util::StreamExt;
+use serde::Deserialize;
+use thiserror::Error;
+use tokio::net::TcpStream;
+use tokio::time::Duration;
+use tokio_tungstenite::tungstenite::{self, Message};
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+/// How long to wait before reconnecting after the connection is lost.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Error, Debug)]
+pub enum WebSocketSourceError {
+    #[error("unable to connect to WebSocket")]
+    ConnectError(#[source] tungstenite::Error),
+    #[error("packet decode error")]
+    DecodeError(#[from] RSUDPError),
+    #[error("unparsable JSON frame")]
+    JsonError(#[from] serde_json::Error),
+    #[error("unsupported channel")]
+    UnsupportedChannelName(#[from] ChannelError),
+}
+
+/// A data frame in JSON form.
+#[derive(Deserialize)]
+struct JsonFrame {
+    channel: String,
+    timestamp: f64,
+    data: Vec<f32>,
+}
+
+/// A data source that reads frames from a WebSocket server. Each message
+/// is expected to hold one frame, either in rsudp's packet format or as a
+/// JSON object with "channel", "timestamp" and "data" members.
+pub struct WebSocketSource {
+    url: String,
+    ws: Option<WebSocketStream<MaybeTlsStream<TcpStream>>>,
+    channels: Option<Vec<bool>>,
+    decode_errors: usize,
+}
+
+impl WebSocketSource {
+    pub async fn new(url: &str) -> Result<WebSocketSource, WebSocketSourceError> {
+        let (ws, _) = connect_async(url)
+            .await
+            .map_err(WebSocketSourceError::ConnectError)?;
+        Ok(WebSocketSource {
+            url: url.to_owned(),
+            ws: Some(ws),
+            channels: None,
+            decode_errors: 0,
+        })
+    }
+
+    pub fn subscribe(&mut self, channel: Channel) {
+        let channel_interest = self
+            .channels
+            .get_or_insert_with(|| vec![false; Channel::max()]);
+        channel_interest[channel as usize] = true;
+    }
+
+    fn interested(&self, channel: Channel) -> bool {
+        self.channels
+            .as_ref()
+            .is_none_or(|interested| interested[channel as usize])
+    }
+
+    pub fn parse_frame(&self, text: &str) -> Result<Option<SeismoData>, WebSocketSourceError> {
+        let text = text.trim();
+        if text.starts_with("{'") {
+            let peek = RSUDPFrame::from_str(text)?;
+            if !self.interested(peek.channel) {
+                return Ok(None);
+            }
+            return Ok(Some(SeismoData {
+                timestamp: peek.timestamp,
+                channel: peek.channel,
+                data: peek.decode()?,
+            }));
+        }
+        let frame: JsonFrame = serde_json::from_str(text)?;
+        let channel = Channel::try_from(frame.channel.as_str())?;
+        if !self.interested(channel) {
+            return Ok(None);
+        }
+        Ok(Some(SeismoData {
+            timestamp: frame.timestamp,
+            channel,
+            data: frame.data.into(),
+        }))
+    }
+
+    /// Number of undecodable frames skipped since the last call.
+    pub fn take_decode_errors(&mut self) -> usize {
+        std::mem::take(&mut self.decode_errors)
+    }
+
+    /// Receive the next frame of interest, reconnecting as necessary
+    /// should the connection be lost.
+    pub async fn next(&mut self) -> Option<Result<SeismoData, WebSocketSourceError>> {
+        loop {
+            let ws = match self.ws.as_mut() {
+                Some(ws) => ws,
+                None => {
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                    match connect_async(self.url.as_str()).await {
+                        Ok((ws, _)) => self.ws.insert(ws),
+                        Err(_) => continue,
+                    }
+                }
+            };
+            let message = match ws.next().await {
+                Some(Ok(message)) => message,
+                Some(Err(_)) | None => {
+                    self.ws = None;
+                    continue;
+                }
+            };
+            let parsed = match &message {
+                Message::Text(text) => self.parse_frame(text.as_str()),
+                Message::Binary(bytes) => match std::str::from_utf8(bytes) {
+                    Ok(text) => self.parse_frame(text),
+                    Err(_) => {
+                        self.decode_errors += 1;
+                        continue;
+                    }
+                },
+                _ => continue,
+            };
+            match parsed {
+                Ok(Some(data)) => return Some(Ok(data)),
+                Ok(None) => continue,
+                Err(_) => self.decode_errors += 1,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unconnected() -> WebSocketSource {
+        WebSocketSource {
+            url: String::new(),
+            ws: None,
+            channels: None,
+            decode_errors: 0,
+        }
+    }
+
+    #[test]
+    fn parses_both_formats() {
+        let mut source = unconnected();
+        let rsudp = source
+            .parse_frame("{'EHZ', 1734044506.042, 1, 2, 3}")
+            .unwrap()
+            .unwrap();
+        assert_eq!(rsudp.channel, Channel::Ehz);
+        let json = source
+            .parse_frame(r#"{"channel": "ENZ", "timestamp": 1.5, "data": [4, 5]}"#)
+            .unwrap()
+            .unwrap();
+        assert_eq!(json.channel, Channel::Enz);
+        assert_eq!(json.data[1], 5.0);
+
+        source.subscribe(Channel::Ehz);
+        let skipped = source
+            .parse_frame(r#"{"channel": "ENZ", "timestamp": 1.5, "data": [4, 5]}"#)
+            .unwrap();
+        assert!(skipped.is_none());
+    }
+}
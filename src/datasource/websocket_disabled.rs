@@ -0,0 +1,32 @@
+//! Stand-in for the WebSocket data source when built without the
+//! "websocket" feature.
+pub use super::channel::Channel;
+use super::data::SeismoData;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum WebSocketSourceError {
+    #[error("WebSocket support was not built in")]
+    NotBuilt,
+}
+
+/// Cannot be constructed.
+pub enum WebSocketSource {}
+
+impl WebSocketSource {
+    pub async fn new(_url: &str) -> Result<WebSocketSource, WebSocketSourceError> {
+        Err(WebSocketSourceError::NotBuilt)
+    }
+
+    pub fn subscribe(&mut self, _channel: Channel) {
+        match *self {}
+    }
+
+    pub fn take_decode_errors(&mut self) -> usize {
+        match *self {}
+    }
+
+    pub async fn next(&mut self) -> Option<Result<SeismoData, WebSocketSourceError>> {
+        match *self {}
+    }
+}
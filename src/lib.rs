@@ -1,5 +1,8 @@
+#[cfg_attr(not(feature = "recorders"), path = "archive/disabled.rs")]
+pub mod archive;
 pub mod config;
 pub mod datasource;
 pub mod overrides;
 pub mod session;
 pub mod signal;
+pub mod time;
@@ -1,64 +1,307 @@
 //! Realtime seismometer monitor daemon which can execute programs and
 //! publish topics to an MQTT server when certain events are detected.
-use rs_udp::config::{Config, FlowConfig, SeismometerConfig};
-use rs_udp::datasource::DataSource;
-use rs_udp::overrides::{FlowTiedPath, SeismometerTiedPath};
-use rs_udp::session::{action_loop_message_channel, SensorFlow, MQTT};
-use rs_udp::session::{ActionLoop, InstrumentLoop};
-use rs_udp::session::{AlarmSession, OutChannel};
-
-use anyhow::{Context, Result};
-use clap::Parser;
-use std::collections::HashMap;
-use std::path::PathBuf;
+use rs_udp::archive::Archiver;
+use rs_udp::config::{migrate_rsudp, Config, FlowConfig, SeismometerConfig};
+use rs_udp::datasource::{Channel, DataSource, SourceAddress};
+use rs_udp::overrides::{FlowTiedPath, SeismometerTiedPath, SettingOverride};
+use rs_udp::session::{
+    action_loop_message_channel, check_alert_config, check_webhook_config, command_channel,
+    SensorFlow, MQTT,
+};
+use rs_udp::session::{event_log_output, influx_output, ActionLoop, Coincidence, InstrumentLoop};
+use rs_udp::session::{
+    send_control_request, AlarmSession, ApiServer, ArmedControl, ArmedSwitch, AudioPlayer,
+    DaemonPresence, LiveFeed, MqttConnection, OutChannel, Outbox, ReloadTrigger,
+};
+use rs_udp::session::{
+    ControlReply, ControlRequest, ControlServer, LogTail, MonitorLogger, SnmpTraps, SoakMonitor,
+    StatusBoard, StatusPublisher, Syslog, SyslogLogger, Telegram, TerminalMonitor, TuningControl,
+};
+#[cfg(unix)]
+use rs_udp::session::{daemonize, PidFile};
+
+use anyhow::{anyhow, Context, Result};
+use clap::{Parser, Subcommand};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+#[cfg(windows)]
+mod service;
 
 #[derive(Debug, Parser)]
 #[command(version = env!("CARGO_PKG_VERSION"))]
 #[command(name = env!("CARGO_BIN_NAME"))]
 /// Real-time seismometer monitor
 ///
-/// JSON Configuration Syntax:
+/// JSON Configuration Syntax (the "schema" command gives it in full, as a
+/// JSON Schema):
 ///
 /// Config = {
+///     ( "include" : [ string* ] )*,
+///     ( "strict" : bool )*,
 ///     "seismometers" : [ Seismometer+ ],
-///     ( "mqtt" : MQTT )*
+///     ( "filter_profiles" : { ( string : Filter )* } )*,
+///     ( "mqtt" : MQTT )*,
+///     ( "mqtt_brokers" : { ( string : MQTTBroker )* } )*,
+///     ( "armed" : Armed )*,
+///     ( "network_triggers" : [ NetworkTrigger* ] )*,
+///     ( "actions" : Actions )*,
+///     ( "outputs" : Outputs )*,
+///     ( "http" : Http )*,
+///     ( "control" : Control )*,
+///     ( "stats" : Stats )*,
+///     ( "max_running_cmds" : number )*,
+///     ( "action_errors" : "continue" | "stop" )*,
+///     ( "event_queue_size" : number )*,
+///     ( "status_overflow" : "coalesce" | "drop" )*
 /// };
 /// Seismometer = {
 ///     "name": string,
-///     "listen": UDPListenSpec,
+///     ( "listen": UDPListenSpec )*,
+///     ( "websocket": WebSocketURL )*,
+///     ( "earthworm": Earthworm )*,
 ///     "sample_rate": number,
 ///     ( "timeout_s" : number )*,
+///     ( "timeout_data_time" : bool )*,
+///     ( "restart_max_s" : number )*,
+///     ( "mqtt_available_topic" : string )*,
+///     ( "decode_error_threshold" : number )*,
+///     ( "clock_drift_threshold_s" : number )*,
+///     ( "sample_rate_tolerance" : number )*,
+///     ( "noise_floor_min_rms" : number )*,
+///     ( "noise_floor_max_rms" : number )*,
 ///     ( "flows" : [ Flow* ] )*,
+///     ( "archive" : Archive )*,
+///     ( "coincidence" : Coincidence )*,
+/// };
+/// Coincidence = {
+///     "name" : string,
+///     "flows" : [ string+ ],
+///     ( "min_flows" : number )*,
+///     ( "window_s" : number )*,
+///     "actions" : Actions,
+/// };
+/// NetworkTrigger = {
+///     "name" : string,
+///     "flows" : [ string+ ],
+///     ( "min_stations" : number )*,
+///     ( "window_s" : number )*,
+///     "actions" : Actions,
+/// };
+/// Archive = {
+///     "path" : string,
+///     "station" : string,
+///     ( "network" : string )*,
+///     ( "location" : string )*,
+///     ( "mode" : "continuous" | "event" )*,
+///     ( "pre_event_s" : number )*,
+///     ( "post_event_s" : number )*,
+///     ( "backfill_s" : number )*,
+/// };
+/// Earthworm = {
+///     "address" : string,
+///     ( "station" : string )*,
+///     ( "installation" : number )*,
+///     ( "module" : number )*,
+///     ( "heartbeat_text" : string )*,
+///     ( "heartbeat_interval_s" : number )*,
 /// };
 /// Flow = {
 ///     "name" : string,
-///     "channel" : Channel,
-///     "filter" : Filter,
+///     ( "enabled" : bool )*,
+///     "channel" : Channel | [ Channel+ ],
+///     ( "timeout_s" : number )*,
+///     ( "sample_rate" : number )*,
+///     ( "tap" : "filtered" | "raw" )*,
+///     ( "precision" : "f32" | "f64" )*,
+///     ( "filter" : Filter | "blocks" : [ Block* ] ),
+///     ( "filter_profile" : string )*,
 ///     "actions" : Actions,
+///     ( "capture" : Capture )*,
+///     ( "picker" : Picker )*,
+///     ( "status_interval_s" : number )*,
+///     ( "tiers" : [ Tier* ] )*,
+///     ( "threshold_schedule" : [ ThresholdProfile* ] )*,
+///     ( "dump_path" : string )*,
+///     ( "dump_format" : "text" | "csv" )*,
+///     ( "dump_max_size_mb" : number )*,
+///     ( "dump_keep" : number )*,
+/// };
+/// Tier = {
+///     "name" : string,
+///     "level" : number,
+///     "actions" : Actions,
+/// };
+/// ThresholdProfile = {
+///     "name" : string,
+///     "from" : TimeOfDay,
+///     "until" : TimeOfDay,
+///     ( "trigger_level" : number )*,
+///     ( "reset_level" : number )*,
 /// };
-/// Channel = "EHZ" | "EHN" | "EHE" | "ENZ" | "ENN" | "ENE";
+/// Channel = "EHZ" | "EHN" | "EHE" | "ENZ" | "ENN" | "ENE"; (in any case)
 /// Filter = {
 ///     ( "trigger_level" : number )*,
 ///     ( "reset_level" : number )*,
 ///     ( "offset" : number )*,
 ///     ( "gain" : number )*,
+///     ( "offset_calibration_s" : number )*,
 ///     ( "order" : number )*,
 ///     ( "cutoff" : number )*,
+///     ( "notch_hz" : number )*,
+///     ( "notch_q" : number )*,
 ///     ( "dc_alpha" : number )*,
 ///     ( "energy_alpha" : number )*,
+///     ( "energy_detector" : "power" | "envelope" | "rms" )*,
+///     ( "rectify" : "square" | "absolute" | "abs" )*,
+///     ( "envelope_attack_alpha" : number )*,
+///     ( "rms_window_s" : number )*,
+///     ( "noise_floor_window_s" : number )*,
 ///     ( "holdoff" : number )*,
+///     ( "min_duration_s" : number )*,
+///     ( "max_event_s" : number )*,
+/// };
+/// Block = { "type" : "affine", ( "offset" : number )*, ( "gain" : number )*,
+///         ( "offset_calibration_s" : number )* }
+///     | { "type" : "low_pass", "cutoff" : number, ( "order" : number )* }
+///     | { "type" : "notch", "frequency" : number, ( "q" : number )* }
+///     | { "type" : "response", ( "zeros" : [ [ number, number ]* ] )*,
+///         ( "poles" : [ [ number, number ]* ] )*, ( "normalization" : number )*,
+///         "sensitivity" : number, ( "low_corner_hz" : number )*,
+///         ( "high_corner_hz" : number )* }
+///     | { "type" : "one_pole", "alpha" : number, ( "pass" : "lowpass" | "highpass" )* }
+///     | { "type" : "rectify", ( "mode" : "absolute" | "abs" | "square" )* }
+///     | { "type" : "envelope", ( "attack_alpha" : number )*, ( "release_alpha" : number )* }
+///     | { "type" : "taper", ( "window" : "hann" | "tukey" )*, ( "tukey_alpha" : number )*,
+///         ( "chunk_s" : number )* }
+///     | { "type" : "rms", ( "window_s" : number )* }
+///     | { "type" : "band_power", "band" : [ number, number ],
+///         ( "reference_band" : [ number, number ] )*, ( "window_s" : number )* }
+///     | { "type" : "filter_bank", "bands" : [ [ number, number ]* ], ( "order" : number )*,
+///         ( "energy_alpha" : number )*,
+///         ( "output" : { "band" : number } | { "ratio" : [ number, number ] } )* }
+///     | { "type" : "threshold", ( "trigger_level" : number )*, ( "reset_level" : number )*,
+///         ( "holdoff" : number )*, ( "min_duration_s" : number )*, ( "max_event_s" : number )* }
+///     | { "type" : "adaptive_threshold", ( "trigger_ratio" : number )*,
+///         ( "reset_ratio" : number )*, ( "window_s" : number )*, ( "holdoff" : number )*,
+///         ( "min_duration_s" : number )*, ( "max_event_s" : number )* }
+///     | { "type" : "z_detector", ( "short_s" : number )*, ( "long_s" : number )*,
+///         ( "trigger_z" : number )*, ( "reset_z" : number )*, ( "holdoff" : number )*,
+///         ( "min_duration_s" : number )*, ( "max_event_s" : number )* }
+///     | { "type" : "kurtosis", ( "window_s" : number )*, ( "trigger_level" : number )*,
+///         ( "reset_level" : number )*, ( "holdoff" : number )*,
+///         ( "min_duration_s" : number )*, ( "max_event_s" : number )* }
+///     | { "type" : "template_match", "templates" : [ string* ], ( "trigger_level" : number )*,
+///         ( "reset_level" : number )*, ( "holdoff" : number )*,
+///         ( "min_duration_s" : number )*, ( "max_event_s" : number )* };
+/// Picker = {
+///     ( "window_s" : number )*,
+///     ( "order" : number )*,
+/// };
+/// Capture = {
+///     "directory" : string,
+///     ( "pre_trigger_s" : number )*,
+///     ( "post_trigger_s" : number )*,
 /// };
 /// Actions = {
 ///     ( "available_cmd" : string )*,
 ///     ( "unavailable_cmd" : string )*,
 ///     ( "trigger_cmd" : string )*,
 ///     ( "reset_cmd" : string )*,
+///     ( "warning_cmd" : string )*,
+///     ( "clock_drift_cmd" : string )*,
+///     ( "ground_motion_cmd" : string )*,
+///     ( "cmd_args" : [ string* ] )*,
+///     ( "cmd_timeout_s" : number )*,
+///     ( "retries" : number )*,
+///     ( "retry_s" : number )*,
+///     ( "retry_max_s" : number )*,
+///     ( "trigger_rate_limit_s" : number )*,
+///     ( "warning_rate_limit_s" : number )*,
+///     ( "mqtt_broker" : string )*,
 ///     ( "mqtt_topic": string )*,
 ///     ( "mqtt_available_topic" : string )*,
+///     ( "mqtt_retain" : bool )*,
+///     ( "mqtt_available_retain" : bool )*,
+///     ( "mqtt_qos" : 0 | 1 | 2 )*,
+///     ( "mqtt_available_qos" : 0 | 1 | 2 )*,
+///     ( "mqtt_warning_qos" : 0 | 1 | 2 )*,
+///     ( "mqtt_clock_drift_qos" : 0 | 1 | 2 )*,
+///     ( "mqtt_ground_motion_qos" : 0 | 1 | 2 )*,
+///     ( "mqtt_status_qos" : 0 | 1 | 2 )*,
+///     ( "mqtt_warning_topic" : string )*,
+///     ( "mqtt_clock_drift_topic" : string )*,
+///     ( "mqtt_ground_motion_topic" : string )*,
+///     ( "mqtt_status_topic" : string )*,
+///     ( "mqtt_command_topic" : string )*,
+///     ( "mqtt_armed_topic" : string )*,
 ///     ( "mqtt_triggered_payload" : string )*,
 ///     ( "mqtt_reset_payload" : string )*,
 ///     ( "mqtt_available_payload" : string )*,
-///     ( "mqtt_unavailable_payload" : string )*
+///     ( "mqtt_unavailable_payload" : string )*,
+///     ( "mqtt_payload_format" : "text" | "json" )*,
+///     ( "telegram" : Telegram )*,
+///     ( "audio" : Audio )*,
+///     ( "cap" : CAP )*,
+///     ( "webhook" : Webhook )*,
+///     ( "syslog" : bool )*,
+///     ( "snmp" : bool )*
+/// };
+/// Telegram = {
+///     ( "bot_token" : string )*,
+///     ( "bot_token_file" : string )*,
+///     ( "bot_token_cmd" : string )*,
+///     "chat_id" : number | string,
+///     ( "triggered_message" : string )*,
+///     ( "reset_message" : string )*,
+///     ( "send_waveform" : bool )*,
+/// };
+/// Audio = {
+///     "file" : string,
+///     ( "repeat" : number )*,
+///     ( "duration_s" : number )*,
+///     ( "stop_on_reset" : bool )*,
+///     ( "volume" : number )*,
+/// };
+/// CAP = {
+///     ( "directory" : string )*,
+///     ( "url" : string )*,
+///     ( "sender" : string )*,
+///     ( "status" : "Actual" | "Exercise" | "System" | "Test" | "Draft" )*,
+///     ( "event" : string )*,
+///     ( "urgency" : "Immediate" | "Expected" | "Future" | "Past" | "Unknown" )*,
+///     ( "severity" : "Extreme" | "Severe" | "Moderate" | "Minor" | "Unknown" )*,
+///     ( "certainty" : "Observed" | "Likely" | "Possible" | "Unlikely" | "Unknown" )*,
+///     ( "headline" : string )*,
+///     ( "description" : string )*,
+///     ( "instruction" : string )*,
+///     ( "expires_s" : number )*,
+///     "area_desc" : string,
+///     ( "circle" : string )*,
+///     ( "polygon" : string )*,
+/// };
+/// Webhook = {
+///     "url" : string,
+///     ( "secret" : string )*,
+///     ( "secret_file" : string )*,
+///     ( "secret_cmd" : string )*,
+///     ( "signature_algorithm" : "sha1" | "sha256" | "sha512" )*,
+///     ( "signature_header" : string )*,
+///     ( "timestamp_header" : string )*,
+///     ( "reset" : bool )*,
+/// };
+/// Armed = {
+///     ( "initially_armed" : bool )*,
+///     ( "mqtt_command_topic" : string )*,
+///     ( "mqtt_state_topic" : string )*,
+///     ( "mqtt_armed_payload" : string )*,
+///     ( "mqtt_disarmed_payload" : string )*,
+///     ( "gpio_path" : string )*,
+///     ( "gpio_active_low" : bool )*,
+///     ( "gpio_poll_s" : number )*,
 /// };
 /// MQTT = {
 ///     "host" : string,
@@ -66,20 +309,214 @@ use std::path::PathBuf;
 ///     ( "client_id" : number )*,
 ///     ( "username" : number )*,
 ///     ( "password" : string )*,
+///     ( "password_file" : string )*,
+///     ( "password_cmd" : string )*,
+///     ( "status_topic" : string )*,
+///     ( "status_interval_s" : number )*,
+///     ( "discovery_prefix" : string )*,
+///     ( "availability_topic" : string )*,
+///     ( "channels_available_topic" : string )*,
+///     ( "online_payload" : string )*,
+///     ( "offline_payload" : string )*,
+///     ( "thresholds_topic" : string )*,
+///     ( "thresholds_state_topic" : string )*,
+///     ( "reconnect_max_s" : number )*,
+///     ( "queue_size" : number )*,
+///     ( "queue_drop" : "oldest" | "newest" )*,
+/// };
+/// MQTTBroker = {
+///     "host" : string,
+///     ( "port" : number )*,
+///     ( "client_id" : number )*,
+///     ( "username" : number )*,
+///     ( "password" : string )*,
+///     ( "password_file" : string )*,
+///     ( "password_cmd" : string )*,
+///     ( "availability_topic" : string )*,
+///     ( "online_payload" : string )*,
+///     ( "offline_payload" : string )*,
+///     ( "reconnect_max_s" : number )*,
+///     ( "queue_size" : number )*,
+///     ( "queue_drop" : "oldest" | "newest" )*,
+/// };
+/// Http = {
+///     "listen" : string,
+///     ( "energy_interval_s" : number )*
+/// };
+/// Control = {
+///     "socket" : string,
+///     ( "dump_dir" : string )*,
+/// };
+/// Stats = {
+///     ( "interval_s" : number )*,
+///     ( "mqtt_topic" : string )*,
+/// };
+/// Outputs = {
+///     ( "influxdb" : InfluxDB )*,
+///     ( "syslog" : Syslog )*,
+///     ( "snmp" : SNMP )*,
+///     ( "event_log" : EventLog )*,
+/// };
+/// EventLog = {
+///     "path" : string,
+///     ( "max_size_mb" : number )*,
+///     ( "rotate_s" : number )*,
+///     ( "keep" : number )*,
+///     ( "status" : bool )*,
+/// };
+/// Syslog = {
+///     ( "address" : string )*,
+///     ( "socket" : string )*,
+///     ( "facility" : "user" | "daemon" | "local0" | ... | "local7" )*,
+///     ( "app_name" : string )*,
+///     ( "log" : bool )*,
+/// };
+/// SNMP = {
+///     "address" : string,
+///     ( "version" : "v2c" | "v3" )*,
+///     ( "community" : string )*,
+///     ( "user" : string )*,
+///     ( "auth_protocol" : "md5" | "sha" | "sha256" )*,
+///     ( "auth_password" : string )*,
+///     ( "auth_password_file" : string )*,
+///     ( "auth_password_cmd" : string )*,
+///     ( "priv_password" : string )*,
+///     ( "priv_password_file" : string )*,
+///     ( "priv_password_cmd" : string )*,
+///     ( "engine_id" : string )*,
+///     ( "triggered_oid" : string )*,
+///     ( "reset_oid" : string )*,
+///     ( "available_oid" : string )*,
+///     ( "unavailable_oid" : string )*,
+///     ( "details_oid" : string )*,
+/// };
+/// InfluxDB = {
+///     "url" : string,
+///     ( "version" : "v1" | "v2" )*,
+///     ( "database" : string )*,
+///     ( "username" : string )*,
+///     ( "password" : string )*,
+///     ( "password_file" : string )*,
+///     ( "password_cmd" : string )*,
+///     ( "org" : string )*,
+///     ( "bucket" : string )*,
+///     ( "token" : string )*,
+///     ( "token_file" : string )*,
+///     ( "token_cmd" : string )*,
+///     ( "measurement" : string )*,
+///     ( "event_measurement" : string )*,
+///     ( "stats_measurement" : string )*,
+///     ( "batch_s" : number )*,
 /// };
+#[command(subcommand_negates_reqs = true)]
 pub struct Cli {
     /// Configuration file to use (JSON format)
-    #[arg(short = 'c')]
-    config_path: PathBuf,
+    #[arg(short = 'c', required_unless_present = "soak")]
+    config_path: Option<PathBuf>,
+
+    /// Watch the configuration file, and the files it includes, reloading
+    /// it whenever they change, as on SIGHUP.
+    #[arg(long)]
+    watch_config: bool,
+
+    /// Override a setting of the configuration file (and the environment),
+    /// as "mqtt.port=1884" or "seismometers[0].flows[0].filter.trigger_level=1200".
+    /// May be given more than once; also applied on reloading. Flows are
+    /// numbered as they are once any listing several channels is copied
+    /// for each of them.
+    #[arg(long = "set", value_name = "key=value")]
+    set: Vec<SettingOverride>,
 
     /// Supply data to a particular seismometer from a text file, masquerading
     /// as data from a specific seismometer channel.
     #[arg(short = 'f', value_names = [ "seismometer=channel:input-path"])]
     text_source: Vec<SeismometerTiedPath>,
 
-    /// Dump filter process for a particular sensor to a file.
+    /// Dump filter process for a particular sensor to a file (afresh, in
+    /// place of any dump_path the flow is configured with).
     #[arg(short = 'o', value_names = [ "flow=dump-path" ])]
     debug_output: Vec<FlowTiedPath>,
+
+    /// Instead of monitoring the configured seismometers, run a soak test
+    /// for this many seconds against synthetic stations, reporting on
+    /// memory use, queue depth and latency as it goes.
+    #[arg(long, value_name = "seconds", value_parser = positive_seconds)]
+    soak: Option<f32>,
+
+    /// Number of synthetic stations to run in a soak test.
+    #[arg(long, value_name = "count", default_value_t = 4)]
+    soak_stations: usize,
+
+    /// Number of channels per synthetic station.
+    #[arg(long, value_name = "count", default_value_t = 3,
+          value_parser = clap::value_parser!(u8).range(1..=6))]
+    soak_channels: u8,
+
+    /// Sample rate of the synthetic stations, in hertz.
+    #[arg(long, value_name = "hz", default_value_t = 100.0)]
+    soak_rate: f32,
+
+    /// How often to report during a soak test, in seconds.
+    #[arg(long, value_name = "seconds", default_value_t = 60.0,
+          value_parser = positive_seconds)]
+    soak_report_s: f32,
+
+    /// Show a live view of the flows on the terminal (their energy, state
+    /// and packet rates, and the latest log messages) rather than logging
+    /// to it.
+    #[arg(long)]
+    tui: bool,
+
+    /// Put the daemon in the background (forking, in a session of its own,
+    /// with standard I/O on /dev/null), as BSD and sysvinit scripts expect.
+    /// Log messages are lost unless sent to syslog. (Unix only.)
+    #[cfg(unix)]
+    #[arg(long, conflicts_with = "tui")]
+    daemon: bool,
+
+    /// Write the daemon's process id to this file, and remove it on exit.
+    /// Refuses to start if the file names a process still running. (Unix
+    /// only.)
+    #[cfg(unix)]
+    #[arg(long = "pidfile", value_name = "path")]
+    pid_file: Option<PathBuf>,
+
+    #[command(subcommand)]
+    mode: Option<Mode>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Mode {
+    /// Inspect or adjust a running daemon through its control socket,
+    /// which is found in its configuration (given with -c) unless given.
+    Ctl {
+        /// Control socket to connect to.
+        #[arg(short = 's', long)]
+        socket: Option<PathBuf>,
+
+        #[command(subcommand)]
+        request: ControlRequest,
+    },
+
+    /// Print a JSON Schema of the configuration file, for editors to
+    /// complete and check configurations with.
+    Schema,
+
+    /// Print a configuration equivalent to an rsudp settings file's
+    /// station, alert, archiving and Telegram settings, noting those which
+    /// aren't carried over.
+    MigrateRsudp {
+        /// rsudp settings file to read.
+        settings: PathBuf,
+    },
+
+    /// Install the daemon as a Windows service, or uninstall it. (The
+    /// service control manager runs it as one with "service run".)
+    #[cfg(windows)]
+    Service {
+        #[command(subcommand)]
+        action: service::ServiceAction,
+    },
 }
 
 // Seismometer stream replacements by seismometer name.
@@ -87,6 +524,9 @@ type SeismometerRedirects<'a> = HashMap<&'a str, &'a SeismometerTiedPath>;
 // Stream output inspections by flow name.
 type FlowDumps<'a> = HashMap<&'a str, &'a FlowTiedPath>;
 
+// Seconds between the synthetic quakes of successive soak test stations.
+const SOAK_QUAKE_STAGGER_S: f32 = 7.0;
+
 /// Nearly all configuration items can be overridden from the environment.
 /// To do so, one must set an environment variable named in such a way
 /// that it will be picked up by this configuration builder. Use this
@@ -100,40 +540,542 @@ type FlowDumps<'a> = HashMap<&'a str, &'a FlowTiedPath>;
 /// To set the MQTT password, for example, one would use the environment
 /// variable name "SEISMO__MQTT__PASSWORD". `SEISMO__MQTT__PASSWORD=pass`
 ///
-#[tokio::main]
-async fn main() -> Result<()> {
+/// Items in lists are numbered as with --set, flows as they are once any
+/// listing several channels is copied for each of them.
+///
+fn main() -> Result<()> {
     let cli = Cli::parse();
+    let runtime = || tokio::runtime::Runtime::new().context("Failed to start the runtime");
+    match &cli.mode {
+        Some(Mode::Ctl { socket, request }) => {
+            let control = control_daemon(
+                cli.config_path.as_deref(),
+                &cli.set,
+                socket.as_deref(),
+                request,
+            );
+            return runtime()?.block_on(control);
+        }
+        Some(Mode::Schema) => {
+            println!("{}", serde_json::to_string_pretty(&Config::schema())?);
+            return Ok(());
+        }
+        Some(Mode::MigrateRsudp { settings }) => {
+            let text = std::fs::read_to_string(settings)
+                .with_context(|| format!("Failed to read {}", settings.display()))?;
+            let settings = serde_json::from_str(&text).context("Failed to parse rsudp settings")?;
+            let (config, notes) = migrate_rsudp(&settings)?;
+            println!("{}", serde_json::to_string_pretty(&config)?);
+            for note in notes {
+                eprintln!("note: {note}");
+            }
+            return Ok(());
+        }
+        #[cfg(windows)]
+        Some(Mode::Service { action }) => return service::manage(&cli, action),
+        None => (),
+    }
+    run(&cli, None)
+}
+
+// Run the daemon until it is told to stop, by a signal or, if given a
+// receiver to tell it by, by whatever runs it.
+fn run(cli: &Cli, told_to_stop: Option<watch::Receiver<bool>>) -> Result<()> {
+    let runtime = || tokio::runtime::Runtime::new().context("Failed to start the runtime");
+    // A soak test has no configuration file to reload.
+    let config_path = cli.config_path.as_deref().filter(|_| cli.soak.is_none());
+    let config = match config_path {
+        Some(path) => read_config(path, &cli.set)?,
+        None => soak_config(cli)?,
+    };
+
+    let syslog = open_syslog(&config)?;
+    let logger =
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).build();
+    // The terminal view shows the log itself, rather than have it written
+    // over the view.
+    let log_tail = cli.tui.then(LogTail::new);
+    match (syslog.as_ref(), log_tail.as_ref()) {
+        (_, Some(tail)) => MonitorLogger::new(logger, tail.clone()).init()?,
+        (Some(syslog), None) if config.outputs.syslog.as_ref().is_some_and(|s| s.log) => {
+            SyslogLogger::new(logger, syslog.clone()).init()?
+        }
+        _ => {
+            log::set_max_level(logger.filter());
+            log::set_boxed_logger(Box::new(logger))?
+        }
+    }
+    warn_of_unknown_settings(&config);
+
+    // The pid file is claimed before going into the background (which has
+    // to be done before the runtime starts its threads), so that another
+    // instance still running is reported where it can be seen.
+    #[cfg(unix)]
+    let _pid_file = {
+        let pid_file = cli.pid_file.as_deref().map(PidFile::claim).transpose()?;
+        match pid_file {
+            pid_file if cli.daemon => daemonize(pid_file)?,
+            Some(pid_file) => {
+                pid_file.write()?;
+                Some(pid_file)
+            }
+            None => None,
+        }
+    };
+    let running = run_daemon(cli, config_path, config, log_tail, syslog, told_to_stop);
+    runtime()?.block_on(running)
+}
+
+// Run seismo sessions until told to stop, starting a new one whenever the
+// configuration is reloaded.
+async fn run_daemon(
+    cli: &Cli,
+    config_path: Option<&Path>,
+    mut config: Config,
+    log_tail: Option<LogTail>,
+    syslog: Option<Syslog>,
+    told_to_stop: Option<watch::Receiver<bool>>,
+) -> Result<()> {
+    // On reload the session is stopped and started afresh with the new
+    // configuration, which is first read and checked, so that a broken
+    // one is refused without interrupting the old. Should the new one
+    // still fail to start, the old one is restarted. Where the daemon's
+    // own log goes is fixed at startup, though, as is the syslog
+    // destination events are sent to. A new configuration which
+    // changes only flows' threshold trigger levels (or holdoffs) has them
+    // put into effect without restarting, so that the filters stay warm.
+    let status = StatusBoard::new();
+    let mut reloads =
+        ReloadTrigger::new(config_path, cli.watch_config).context("Failed to handle SIGHUP")?;
+    let mut previous = None;
+    loop {
+        if let Some(path) = config_path {
+            reloads.watch_included(config.included_paths(path));
+        }
+        let configuring =
+            configure_seismo_session(cli, &config, &status, log_tail.as_ref(), syslog.as_ref());
+        let mut session = match configuring.await {
+            Ok(session) => session,
+            Err(e) => match previous.take() {
+                Some(previous) => {
+                    log::error!("New configuration failed, restoring the old one: {e:#}");
+                    config = previous;
+                    continue;
+                }
+                None => return Err(e),
+            },
+        };
+        if let Some(told_to_stop) = told_to_stop.as_ref() {
+            session.stop_on(told_to_stop.clone());
+        }
+        let (reloaded, retuned) = {
+            let restart = session.restart_handle();
+            let tuning = session.tuning_handle();
+            let running = run_seismo_session(cli, session, &status);
+            tokio::pin!(running);
+            let mut reloaded = None;
+            let mut retuned: Option<Config> = None;
+            // Reading the configuration may run commands for secrets, so it
+            // is read on a thread of its own while the session carries on.
+            let mut reading: Option<JoinHandle<Result<Config>>> = None;
+            loop {
+                tokio::select! {
+                    result = &mut running => break result?,
+                    () = reloads.requested(), if reloaded.is_none() && reading.is_none() => {
+                        let Some(path) = config_path else {
+                            continue;
+                        };
+                        let (path, overrides) = (path.to_owned(), cli.set.clone());
+                        reading = Some(tokio::task::spawn_blocking(move || {
+                            read_config(&path, &overrides)
+                        }));
+                    }
+                    read = async { reading.as_mut().expect("reading").await },
+                        if reading.is_some() =>
+                    {
+                        reading = None;
+                        let read = read.map_err(anyhow::Error::from).and_then(|read| read);
+                        let new_config = match read {
+                            Ok(new_config) => new_config,
+                            Err(e) => {
+                                log::error!("Not reloading configuration: {e:#}");
+                                continue;
+                            }
+                        };
+                        warn_of_unknown_settings(&new_config);
+                        let running_config = retuned.as_ref().unwrap_or(&config);
+                        let changes = match running_config.threshold_changes(&new_config) {
+                            Ok(changes) => changes,
+                            Err(e) => {
+                                log::error!("Not reloading configuration: {e}");
+                                continue;
+                            }
+                        };
+                        match changes.zip(tuning.as_ref()) {
+                            Some((changes, tuning)) => {
+                                log::info!("Only trigger levels changed, applying them in place");
+                                for change in changes.iter() {
+                                    tuning.apply(change);
+                                }
+                                retuned = Some(new_config);
+                            }
+                            None => {
+                                reloaded = Some(new_config);
+                                restart.restart();
+                            }
+                        }
+                    }
+                }
+            }
+            (reloaded, retuned)
+        };
+        if let Some(retuned) = retuned {
+            config = retuned;
+        }
+        match reloaded {
+            Some(reloaded) => previous = Some(std::mem::replace(&mut config, reloaded)),
+            None => return Ok(()),
+        }
+    }
+}
 
+// Send a request to a running daemon's control socket, and print its reply.
+async fn control_daemon(
+    config_path: Option<&Path>,
+    overrides: &[SettingOverride],
+    socket: Option<&Path>,
+    request: &ControlRequest,
+) -> Result<()> {
+    let config = match socket {
+        Some(_) => None,
+        None => {
+            let path = config_path.ok_or_else(|| anyhow!("Either -c or --socket is needed"))?;
+            Some(read_config(path, overrides)?)
+        }
+    };
+    let socket = match (socket, config.as_ref()) {
+        (Some(socket), _) => socket,
+        (None, Some(config)) => match config.control.as_ref() {
+            Some(control) => control.socket.as_path(),
+            None => return Err(anyhow!("No control socket is configured")),
+        },
+        (None, None) => return Err(anyhow!("Either -c or --socket is needed")),
+    };
+    match send_control_request(socket, request).await? {
+        ControlReply::Done(done) => println!("{done}"),
+        ControlReply::Status(status) => println!("{}", serde_json::to_string_pretty(&status)?),
+        ControlReply::Error(e) => return Err(anyhow!("Refused: {e}")),
+    }
+    Ok(())
+}
+
+// Read a configuration file, with any settings overridden, and check that
+// this binary can act on it.
+fn read_config(path: &Path, overrides: &[SettingOverride]) -> Result<Config> {
     let config =
-        Config::new(&cli.config_path, "SEISMO", "__").context("Failed to read config file")?;
+        Config::new(path, "SEISMO", "__", overrides).context("Failed to read config file")?;
+    check_features_built(&config)?;
+    Ok(config)
+}
+
+// Warn of settings the daemon didn't know of, which strict parsing would
+// have refused.
+fn warn_of_unknown_settings(config: &Config) {
+    for pointer in config.unknown_settings.iter() {
+        log::warn!("Ignoring unknown setting {pointer}");
+    }
+}
 
-    let session = configure_seismo_session(&cli, &config).await?;
-    session.run().await?;
+fn open_syslog(config: &Config) -> Result<Option<Syslog>> {
+    config
+        .outputs
+        .syslog
+        .as_ref()
+        .map(Syslog::from_config)
+        .transpose()
+        .context("Failed to open syslog")
+}
 
+async fn run_seismo_session(
+    cli: &Cli,
+    session: AlarmSession<'_>,
+    status: &StatusBoard,
+) -> Result<()> {
+    match cli.soak {
+        Some(duration_s) => {
+            let monitor = SoakMonitor::new(status.clone(), duration_s, cli.soak_report_s);
+            tokio::select! {
+                result = session.run() => result?,
+                _ = monitor.run() => (),
+            }
+        }
+        None => session.run().await?,
+    }
+    Ok(())
+}
+
+// Refuse configurations that need features this binary was built without,
+// rather than silently ignoring parts of them.
+fn check_features_built(config: &Config) -> Result<()> {
+    if !cfg!(feature = "mqtt") && (config.mqtt.is_some() || !config.mqtt_brokers.is_empty()) {
+        return Err(anyhow!("MQTT is configured, but support was not built in"));
+    }
+    let all_actions = config.all_actions();
+    if !cfg!(feature = "telegram") && all_actions.iter().any(|(_, a)| a.telegram.is_some()) {
+        return Err(anyhow!(
+            "Telegram is configured, but support was not built in"
+        ));
+    }
+    if !cfg!(feature = "audio") && all_actions.iter().any(|(_, a)| a.audio.is_some()) {
+        return Err(anyhow!(
+            "a sound is configured, but audio support was not built in"
+        ));
+    }
+    if !cfg!(feature = "snmp") && config.outputs.snmp.is_some() {
+        return Err(anyhow!("SNMP is configured, but support was not built in"));
+    }
+    if !cfg!(feature = "recorders") {
+        for seismometer in config.seismometers.iter() {
+            let capture = seismometer.flows.iter().any(|f| f.capture.is_some());
+            if seismometer.archive.is_some() || capture {
+                return Err(anyhow!(
+                    "seismometer {} records data, but recorder support was not built in",
+                    seismometer.name
+                ));
+            }
+        }
+    }
     Ok(())
 }
 
+// Parse a number of seconds to run or report for, which must be more than
+// zero (and short enough to wait for).
+fn positive_seconds(arg: &str) -> Result<f32, String> {
+    let seconds: f32 = arg.parse().map_err(|e| format!("{e}"))?;
+    match Duration::try_from_secs_f32(seconds) {
+        Ok(duration) if !duration.is_zero() => Ok(seconds),
+        _ => Err(String::from("must be a number of seconds, more than zero")),
+    }
+}
+
+// Build a configuration for a soak test: a number of synthetic stations,
+// each with a flow (and no actions) on each of its channels.
+fn soak_config(cli: &Cli) -> Result<Config> {
+    let seismometers: Vec<_> = (0..cli.soak_stations)
+        .map(|station| {
+            let flows: Vec<_> = (0..cli.soak_channels as usize)
+                .map(|channel| {
+                    let channel = Channel::try_from(channel).expect("channel in range");
+                    serde_json::json!({
+                        "name": format!("soak-{station}-{channel}"),
+                        "channel": channel.as_str(),
+                        "filter": { "trigger_level": 1.0, "reset_level": 0.1 },
+                        "actions": {},
+                    })
+                })
+                .collect();
+            serde_json::json!({
+                "name": format!("soak-{station}"),
+                "sample_rate": cli.soak_rate,
+                "timeout_s": 5.0,
+                "flows": flows,
+            })
+        })
+        .collect();
+    let config = serde_json::from_value(serde_json::json!({ "seismometers": seismometers }))?;
+    Ok(config)
+}
+
 // Configure an entire daemon session from command line arguments and
 // configuration file.
 async fn configure_seismo_session<'a>(
     cli: &'a Cli,
     config: &'a Config,
+    status: &StatusBoard,
+    log_tail: Option<&LogTail>,
+    syslog: Option<&Syslog>,
 ) -> Result<AlarmSession<'a>> {
     let source_overrides = redirects_by_seismometer(&cli.text_source);
     let dump_requests = dump_requests_by_flow_name(&cli.debug_output);
-    let (tx_chan, rx_chan) = action_loop_message_channel();
-    let MQTT(mqtt_client, mqtt_loop) = MQTT::from_config(config);
-    let mut action_loop = ActionLoop::new(rx_chan, mqtt_client);
-    let seismometer_loops = configure_seismometers_and_actions(
+    let (tx_chan, rx_chan) = action_loop_message_channel(config.event_queue_size.max(1));
+    let MQTT(mqtt_client, mqtt_loop, brokers, availability) = MQTT::from_config(config);
+    let status_publisher = StatusPublisher::new(
+        status.clone(),
+        mqtt_client.clone(),
+        config.mqtt.as_ref().and_then(|m| m.status_topic.clone()),
+        config.mqtt.as_ref().map_or(60.0, |m| m.status_interval_s),
+    );
+    let armed = ArmedSwitch::new(config.armed.as_ref().is_none_or(|a| a.initially_armed));
+    let mut armed_control =
+        ArmedControl::new(armed.clone(), config.armed.as_ref(), mqtt_client.clone());
+    let presence = DaemonPresence::new(config.mqtt.as_ref(), mqtt_client.clone());
+    let thresholds_topic = config
+        .mqtt
+        .as_ref()
+        .and_then(|m| m.thresholds_topic.as_deref());
+    let mut tuning = TuningControl::new(thresholds_topic, mqtt_client.clone());
+    let mut action_loop = ActionLoop::new(rx_chan, mqtt_client, &armed, config.armed.as_ref());
+    action_loop.limit_running_cmds(config.max_running_cmds);
+    action_loop.set_error_policy(config.action_errors);
+    action_loop.report_failures(status.clone());
+    action_loop.report_flow_states(status.clone());
+    let connection = MqttConnection::new(config.mqtt.as_ref().map_or(60.0, |m| m.reconnect_max_s));
+    let mut broker_loops = Vec::new();
+    if let Some(mqtt_config) = config.mqtt.as_ref() {
+        action_loop.publish_discovery(mqtt_config);
+        for ((seismometer, topic), broker) in
+            config.availability_topics().into_iter().zip(availability)
+        {
+            let topic_connection = MqttConnection::new(mqtt_config.reconnect_max_s);
+            action_loop.report_availability(
+                seismometer,
+                topic,
+                broker.client,
+                &topic_connection,
+                mqtt_config,
+            );
+            broker_loops.push((broker.name, broker.event_loop, topic_connection));
+        }
+        action_loop.queue_while_disconnected(Outbox::new(
+            &connection,
+            mqtt_config.queue_size,
+            mqtt_config.queue_drop,
+        ));
+    }
+    for broker in brokers {
+        let broker_config = &config.mqtt_brokers[&broker.name];
+        let broker_connection = MqttConnection::new(broker_config.reconnect_max_s);
+        action_loop.post_to_broker(
+            broker.name.clone(),
+            broker.client,
+            &broker_connection,
+            broker_config,
+        );
+        broker_loops.push((broker.name, broker.event_loop, broker_connection));
+    }
+    if let Some(syslog) = syslog {
+        action_loop.send_to_syslog(syslog.clone());
+    }
+    let all_actions = config.all_actions();
+    if all_actions.iter().any(|(_, a)| a.telegram.is_some()) {
+        let telegram = Telegram::new().context("Failed to set up Telegram client")?;
+        action_loop.notify_telegram(telegram);
+    }
+    if all_actions.iter().any(|(_, a)| a.audio.is_some()) {
+        let audio = AudioPlayer::new().context("Failed to open audio output")?;
+        action_loop.play_audio(audio);
+    }
+    for cap_config in all_actions.iter().filter_map(|(_, a)| a.cap.as_ref()) {
+        check_alert_config(cap_config).context("Bad CAP alert")?;
+    }
+    for webhook_config in all_actions.iter().filter_map(|(_, a)| a.webhook.as_ref()) {
+        check_webhook_config(webhook_config).context("Bad webhook")?;
+    }
+    if let Some(snmp_config) = config.outputs.snmp.as_ref() {
+        let snmp = SnmpTraps::from_config(snmp_config).context("Failed to set up SNMP traps")?;
+        action_loop.send_snmp_traps(snmp);
+    }
+    let influx_writer = match config.outputs.influxdb.as_ref() {
+        Some(influx_config) => {
+            let (sink, writer) =
+                influx_output(influx_config).context("Failed to set up InfluxDB output")?;
+            action_loop.write_to_influx(sink);
+            Some(writer)
+        }
+        None => None,
+    };
+    if let Some(stats_config) = config.stats.as_ref() {
+        action_loop.publish_stats(stats_config);
+    }
+    let event_log_writer = config.outputs.event_log.as_ref().map(|log_config| {
+        let (sink, writer) = event_log_output(log_config);
+        action_loop.write_to_event_log(sink);
+        writer
+    });
+    let mut seismometer_loops = configure_seismometers_and_actions(
         config,
         &mut action_loop,
         tx_chan,
+        status.clone(),
         source_overrides,
         dump_requests,
+        cli.soak.is_some(),
     )
     .await?;
+    let (commands, control) = command_channel();
+    armed_control.control_flows(action_loop.command_topics(), commands.clone());
+    action_loop.take_commands(control);
+    let control_server = match config.control.as_ref() {
+        Some(control_config) => {
+            let mut server = ControlServer::bind(control_config, status.clone(), armed.clone())
+                .await
+                .context("Failed to open control socket")?;
+            server.control_flows(action_loop.sensor_flows(), commands, tuning.sender());
+            for instrument in seismometer_loops.iter_mut() {
+                instrument.accept_dump_requests(server.dump_requests());
+            }
+            Some(server)
+        }
+        None => None,
+    };
+    let thresholds_state_topic = config
+        .mqtt
+        .as_ref()
+        .and_then(|m| m.thresholds_state_topic.clone());
+    // Trigger levels may always be adjusted by reloading the configuration.
+    tuning.control_flows(action_loop.sensor_flows());
+    for instrument in seismometer_loops.iter_mut() {
+        instrument.accept_tuning(tuning.subscribe());
+    }
+    if let Some(topic) = thresholds_state_topic {
+        action_loop.report_thresholds(topic);
+    }
+    // The terminal view wants every packet's energy, unless the HTTP
+    // server's clients (who share the feed) want less.
+    let live = (config.http.is_some() || log_tail.is_some()).then(|| {
+        let live = LiveFeed::new(action_loop.sensor_flows());
+        action_loop.stream_live(live.clone());
+        let interval_s = config.http.as_ref().map_or(0.0, |h| h.energy_interval_s);
+        for instrument in seismometer_loops.iter_mut() {
+            instrument.stream_energy(live.clone(), interval_s);
+        }
+        live
+    });
+    let monitor = log_tail
+        .zip(live.as_ref())
+        .map(|(tail, live)| TerminalMonitor::new(status.clone(), live, tail.clone()));
 
-    let result = AlarmSession::new(seismometer_loops, action_loop, mqtt_loop);
+    let mut result = AlarmSession::new(
+        seismometer_loops,
+        action_loop,
+        mqtt_loop,
+        status_publisher,
+        armed_control,
+        presence,
+        connection,
+    );
+    for (name, event_loop, broker_connection) in broker_loops {
+        result.run_broker(name, event_loop, broker_connection);
+    }
+    if let Some(writer) = influx_writer {
+        result.write_to_influx(writer);
+    }
+    if let Some(writer) = event_log_writer {
+        result.write_to_event_log(writer);
+    }
+    result.tune_thresholds(tuning);
+    if let Some(server) = control_server {
+        result.serve_control(server);
+    }
+    if let Some(monitor) = monitor {
+        result.monitor_terminal(monitor);
+    }
+    if let Some((http, live)) = config.http.as_ref().zip(live) {
+        let server = ApiServer::bind(http, status.clone(), live)
+            .await
+            .context("Failed to start HTTP server")?;
+        result.serve_api(server);
+    }
     Ok(result)
 }
 
@@ -147,47 +1089,161 @@ async fn configure_seismometers_and_actions<'a>(
     config: &'a Config,
     action_loop: &mut ActionLoop<'a>,
     action_channel: OutChannel,
+    status: StatusBoard,
     source_overrides: SeismometerRedirects<'a>,
     dump_requests: FlowDumps<'a>,
+    synthetic: bool,
 ) -> Result<Vec<InstrumentLoop>, anyhow::Error> {
     let mut loops: Vec<InstrumentLoop> = Vec::new();
     let mut flow_id: usize = 0;
+    // Flow ids and seismometer indexes by flow name, across all
+    // seismometers. A name used on more than one has no entry.
+    let mut network_flows: HashMap<&str, Option<(usize, usize)>> = HashMap::new();
+    // The names of flows which are disabled on any seismometer, which
+    // network triggers watching them go without.
+    let mut disabled_network_flows: HashSet<&str> = HashSet::new();
+    // The global actions are taken while any flow is triggered.
+    let mut any_flow = Coincidence::of_any();
 
-    for seismometer_config in config.seismometers.iter() {
-        let mut instrument = instrument_loop_from_config_and_overrides(
-            seismometer_config,
-            &action_channel,
-            &source_overrides,
-        )
-        .await?;
-        for flow_config in seismometer_config.flows.iter() {
-            let flow = flow_from_config_and_dump_requests(
+    for (index, seismometer_config) in config.seismometers.iter().enumerate() {
+        let (source, address) = if synthetic {
+            let source = DataSource::new_synthetic_source(
                 seismometer_config.sample_rate,
-                flow_config,
-                &dump_requests,
-            )
-            .await?;
-            instrument.add_flow(flow_id, flow_config.channel.as_str().try_into()?, flow);
-            action_loop.add_flow(flow_id, &flow_config.name, &flow_config.actions);
+                index as f32 * SOAK_QUAKE_STAGGER_S,
+            );
+            (source, None)
+        } else {
+            datasource_for_seismometer(seismometer_config, &source_overrides).await?
+        };
+        let mut instrument =
+            instrument_loop_from_config(seismometer_config, source, &action_channel, &status);
+        if let Some(address) = address {
+            let wait_max = Duration::from_secs_f32(seismometer_config.restart_max_s);
+            instrument.reopen_source(address, wait_max);
+        }
+        instrument.set_status_overflow(config.status_overflow);
+        let mut flow_ids: HashMap<&str, usize> = HashMap::new();
+        // The seismometer's disabled flows, which its coincidence trigger
+        // goes without.
+        let mut disabled_flows: HashSet<&str> = HashSet::new();
+        for flow_config in seismometer_config.flows.iter() {
+            if !flow_config.enabled {
+                log::info!("{}: disabled, not running it", flow_config.name);
+                status.update(|status| {
+                    let (seismometer, channel) = (&seismometer_config.name, flow_config.channel);
+                    status.disable_flow(&flow_config.name, seismometer, channel)
+                });
+                disabled_flows.insert(&flow_config.name);
+                disabled_network_flows.insert(&flow_config.name);
+                continue;
+            }
+            let sample_rate = flow_config
+                .sample_rate
+                .unwrap_or(seismometer_config.sample_rate);
+            let flow = flow_from_config_and_dump_requests(sample_rate, flow_config, &dump_requests)
+                .await?;
+            instrument.add_flow(flow_id, flow_config.channel, flow);
+            if let Some(timeout_s) = flow_config.timeout_s {
+                instrument.set_channel_timeout(flow_config.channel, timeout_s);
+            }
+            action_loop.add_flow(
+                flow_id,
+                &flow_config.name,
+                Some(&seismometer_config.name),
+                Some(flow_config.channel.as_str()),
+                &flow_config.actions,
+            );
+            flow_ids.insert(&flow_config.name, flow_id);
+            network_flows
+                .entry(&flow_config.name)
+                .and_modify(|entry| *entry = None)
+                .or_insert(Some((flow_id, index)));
+            any_flow.add_member(flow_id, flow_id);
+            let parent_id = flow_id;
+            flow_id += 1;
+            for (severity, tier) in (1..).zip(flow_config.tiers.iter()) {
+                action_loop.add_tier(flow_id, parent_id, severity, &tier.name, &tier.actions);
+                flow_id += 1;
+            }
+        }
+        if let Some(coincidence_config) = &seismometer_config.coincidence {
+            let coincidence =
+                Coincidence::of_flows(coincidence_config, &flow_ids, &disabled_flows)?;
+            action_loop.add_coincidence(
+                flow_id,
+                &coincidence_config.name,
+                Some(&seismometer_config.name),
+                &coincidence_config.actions,
+                coincidence,
+            );
             flow_id += 1;
         }
         loops.push(instrument);
     }
+    for network_config in config.network_triggers.iter() {
+        let network =
+            Coincidence::of_stations(network_config, &network_flows, &disabled_network_flows)?;
+        action_loop.add_coincidence(
+            flow_id,
+            &network_config.name,
+            None,
+            &network_config.actions,
+            network,
+        );
+        flow_id += 1;
+    }
+    if let Some(actions) = config.actions.as_ref() {
+        action_loop.add_coincidence(flow_id, "global", None, actions, any_flow);
+    }
+    // What the board shows of flows (and seismometers) a reload removed
+    // would otherwise linger there.
+    let flow_names: HashSet<&str> = action_loop
+        .flow_names()
+        .chain(disabled_network_flows.iter().copied())
+        .collect();
+    let seismometer_names: HashSet<&str> = config
+        .seismometers
+        .iter()
+        .map(|s| s.name.as_str())
+        .collect();
+    status.update(|status| status.keep_only(&flow_names, &seismometer_names));
     Ok(loops)
 }
 
-async fn instrument_loop_from_config_and_overrides(
+fn instrument_loop_from_config(
     seismometer_config: &SeismometerConfig,
+    source: DataSource,
     action_channel: &OutChannel,
-    source_overrides: &SeismometerRedirects<'_>,
-) -> Result<InstrumentLoop> {
-    let source = datasource_for_seismometer(seismometer_config, source_overrides).await?;
-    let iloop = InstrumentLoop::new_for_datasource(
+    status: &StatusBoard,
+) -> InstrumentLoop {
+    let archiver = seismometer_config
+        .archive
+        .as_ref()
+        .map(Archiver::from_config);
+    let mut iloop = InstrumentLoop::new_for_datasource(
+        &seismometer_config.name,
         source,
         seismometer_config.timeout_s,
+        seismometer_config.decode_error_threshold,
+        archiver,
         action_channel.clone(),
+        status.clone(),
     );
-    Ok(iloop)
+    if seismometer_config.timeout_data_time {
+        iloop.watch_data_time();
+    }
+    if let Some(threshold_s) = seismometer_config.clock_drift_threshold_s {
+        iloop.monitor_clock_drift(threshold_s);
+    }
+    iloop.monitor_sample_rate(
+        seismometer_config.sample_rate,
+        seismometer_config.sample_rate_tolerance,
+    );
+    iloop.monitor_noise_floor(
+        seismometer_config.noise_floor_min_rms,
+        seismometer_config.noise_floor_max_rms,
+    );
+    iloop
 }
 
 // Set up a signal flow processor, with an option to inspect its flow and
@@ -203,21 +1259,34 @@ async fn flow_from_config_and_dump_requests(
 }
 
 // Set up a data source for a particular seismometer, allowing for it to be
-// overriden from the command line.
+// overriden from the command line. A source on the network is also returned
+// by its address, for its instrument loop to reopen should it fail later.
 async fn datasource_for_seismometer(
     config: &SeismometerConfig,
     overrides: &SeismometerRedirects<'_>,
-) -> Result<DataSource> {
-    Ok(match overrides.get(config.name.as_str()) {
-        Some(&path) => DataSource::new_textfile_source(&path.path, path.channel).await?,
-        None => DataSource::new_rsudp_source(&config.listen).await?,
-    })
+) -> Result<(DataSource, Option<SourceAddress>)> {
+    if let Some(&path) = overrides.get(config.name.as_str()) {
+        let source = DataSource::new_textfile_source(&path.path, path.channel).await?;
+        return Ok((source, None));
+    }
+    let address = match (&config.websocket, &config.earthworm, &config.listen) {
+        (Some(url), _, _) => SourceAddress::WebSocket(url.clone()),
+        (None, Some(ew), _) => SourceAddress::Earthworm(ew.clone()),
+        (None, None, Some(listen)) => SourceAddress::Rsudp(listen.clone()),
+        (None, None, None) => {
+            return Err(anyhow!(
+                "seismometer {} has no data source configured",
+                config.name
+            ))
+        }
+    };
+    let source = DataSource::open(&address).await?;
+    Ok((source, Some(address)))
 }
-//}
 
 /// Build a quick lookup table to query whether a seismometer should be
 /// "faked" by data from a text file.
-fn redirects_by_seismometer(specs: &[SeismometerTiedPath]) -> SeismometerRedirects {
+fn redirects_by_seismometer(specs: &[SeismometerTiedPath]) -> SeismometerRedirects<'_> {
     specs
         .iter()
         .map(|x| (x.seismometer_name.as_str(), x))
@@ -226,7 +1295,7 @@ fn redirects_by_seismometer(specs: &[SeismometerTiedPath]) -> SeismometerRedirec
 
 /// Build a quick lookup table to query whether a filesystem path has been
 /// associated with a flow output by the user.
-fn dump_requests_by_flow_name(specs: &[FlowTiedPath]) -> FlowDumps {
+fn dump_requests_by_flow_name(specs: &[FlowTiedPath]) -> FlowDumps<'_> {
     specs
         .iter()
         .map(|spec| (spec.flow_name.as_str(), spec))
@@ -51,6 +51,35 @@ impl FromStr for SeismometerTiedPath {
     }
 }
 
+#[derive(Error, Debug)]
+pub enum SettingOverrideError {
+    #[error("setting override missing key=value separator")]
+    MissingValueSeparator,
+}
+
+#[derive(Debug, Clone)]
+/// A setting to override the configuration's with, given as "key=value".
+/// Keys are paths into the configuration, as "mqtt.port" or
+/// "seismometers[0].flows[1].filter.trigger_level".
+pub struct SettingOverride {
+    pub key: String,
+    pub value: String,
+}
+
+impl FromStr for SettingOverride {
+    type Err = SettingOverrideError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (key, value) = s
+            .split_once('=')
+            .ok_or(SettingOverrideError::MissingValueSeparator)?;
+        Ok(Self {
+            key: key.to_owned(),
+            value: value.to_owned(),
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 /// A specification that pairs a text file with a signal flow's output,
 /// typically to ask that a copy of a diagnostic data stream from the flow be
@@ -78,10 +107,18 @@ impl FromStr for FlowTiedPath {
 mod tests {
     use std::str::FromStr;
 
-    use super::SeismometerTiedPath;
+    use super::{SeismometerTiedPath, SettingOverride};
 
     #[test]
     fn test_one() {
         SeismometerTiedPath::from_str("shake4d=EHZ:/tmp/test").expect("works");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_setting() {
+        let setting = SettingOverride::from_str("seismometers[0].name=a=b").expect("works");
+        assert_eq!(setting.key, "seismometers[0].name");
+        assert_eq!(setting.value, "a=b");
+        assert!(SettingOverride::from_str("mqtt.port").is_err());
+    }
+}
@@ -0,0 +1,175 @@
+//! Running the daemon as a Windows service: installing it with the service
+//! control manager, which then starts it (with "service run") as Windows
+//! starts, and stops it as Windows shuts down or when told to, and
+//! uninstalling it again.
+//!
+//! A service has no console, so its log messages are lost unless sent to
+//! syslog.
+use super::{run, Cli};
+
+use anyhow::{anyhow, Context, Result};
+use clap::{Parser, Subcommand};
+use std::ffi::OsString;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use windows_service::service::{
+    ServiceAccess, ServiceControl, ServiceControlAccept, ServiceErrorControl, ServiceExitCode,
+    ServiceInfo, ServiceStartType, ServiceState, ServiceStatus, ServiceType,
+};
+use windows_service::service_control_handler::{self, ServiceControlHandlerResult};
+use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+use windows_service::{define_windows_service, service_dispatcher};
+
+/// Name the service is installed under.
+const SERVICE_NAME: &str = "seismo";
+
+const DISPLAY_NAME: &str = "Seismometer monitor";
+
+const DESCRIPTION: &str = "Real-time seismometer monitor";
+
+/// How long to wait for the service to stop when uninstalling it. The
+/// daemon's loops have five seconds to finish up once told to stop.
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Subcommand)]
+pub enum ServiceAction {
+    /// Install the service, to start with Windows, running with the
+    /// configuration file given with -c (and any --set overrides, and
+    /// --watch-config). The service runs as LocalSystem.
+    Install,
+
+    /// Stop the service, if it is running, and uninstall it.
+    Uninstall,
+
+    /// Run as the service. Only the service control manager does this.
+    Run,
+}
+
+define_windows_service!(ffi_service_main, service_main);
+
+pub fn manage(cli: &Cli, action: &ServiceAction) -> Result<()> {
+    match action {
+        ServiceAction::Install => install(cli),
+        ServiceAction::Uninstall => uninstall(),
+        ServiceAction::Run => service_dispatcher::start(SERVICE_NAME, ffi_service_main)
+            .context("Failed to run as a service (only the service control manager may)"),
+    }
+}
+
+fn install(cli: &Cli) -> Result<()> {
+    let config_path = cli
+        .config_path
+        .as_deref()
+        .ok_or_else(|| anyhow!("A configuration file must be given with -c"))?;
+    // The service runs in the system directory, not this one.
+    let config_path = std::path::absolute(config_path)
+        .with_context(|| format!("Failed to find {}", config_path.display()))?;
+    let mut launch_arguments = vec![OsString::from("-c"), config_path.into_os_string()];
+    for setting in cli.set.iter() {
+        launch_arguments.push("--set".into());
+        launch_arguments.push(format!("{}={}", setting.key, setting.value).into());
+    }
+    if cli.watch_config {
+        launch_arguments.push("--watch-config".into());
+    }
+    launch_arguments.extend(["service".into(), "run".into()]);
+    let info = ServiceInfo {
+        name: SERVICE_NAME.into(),
+        display_name: DISPLAY_NAME.into(),
+        service_type: ServiceType::OWN_PROCESS,
+        start_type: ServiceStartType::AutoStart,
+        error_control: ServiceErrorControl::Normal,
+        executable_path: std::env::current_exe().context("Failed to find this executable")?,
+        launch_arguments,
+        dependencies: Vec::new(),
+        account_name: None,
+        account_password: None,
+    };
+    let access = ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE;
+    let manager = ServiceManager::local_computer(None::<&str>, access)
+        .context("Failed to connect to the service control manager")?;
+    let service = manager
+        .create_service(&info, ServiceAccess::CHANGE_CONFIG)
+        .context("Failed to install the service")?;
+    service.set_description(DESCRIPTION)?;
+    println!("installed the {SERVICE_NAME} service");
+    Ok(())
+}
+
+fn uninstall() -> Result<()> {
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)
+        .context("Failed to connect to the service control manager")?;
+    let access = ServiceAccess::QUERY_STATUS | ServiceAccess::STOP | ServiceAccess::DELETE;
+    let service = manager
+        .open_service(SERVICE_NAME, access)
+        .context("Failed to open the service")?;
+    // It is only marked for deletion until it stops.
+    service
+        .delete()
+        .context("Failed to uninstall the service")?;
+    if service.query_status()?.current_state != ServiceState::Stopped {
+        service.stop().context("Failed to stop the service")?;
+        let start = Instant::now();
+        while service.query_status()?.current_state != ServiceState::Stopped {
+            if start.elapsed() > STOP_TIMEOUT {
+                println!("the {SERVICE_NAME} service will be uninstalled once it stops");
+                return Ok(());
+            }
+            std::thread::sleep(Duration::from_millis(250));
+        }
+    }
+    println!("uninstalled the {SERVICE_NAME} service");
+    Ok(())
+}
+
+/// Run as the service, as the service control manager starts it.
+fn service_main(_arguments: Vec<OsString>) {
+    if let Err(e) = run_service() {
+        log::error!("{e:#}");
+    }
+}
+
+fn run_service() -> Result<()> {
+    let (stop, told_to_stop) = watch::channel(false);
+    let handler = move |control| match control {
+        ServiceControl::Stop | ServiceControl::Shutdown => {
+            stop.send_replace(true);
+            ServiceControlHandlerResult::NoError
+        }
+        ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+        _ => ServiceControlHandlerResult::NotImplemented,
+    };
+    let status = service_control_handler::register(SERVICE_NAME, handler)
+        .context("Failed to take service controls")?;
+    let accepted = ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN;
+    status.set_service_status(service_status(ServiceState::Running, accepted, 0))?;
+    // The service's arguments are those it was installed with.
+    let result = run(&Cli::parse(), Some(told_to_stop));
+    let exit_code = if result.is_ok() { 0 } else { 1 };
+    let stopped = service_status(
+        ServiceState::Stopped,
+        ServiceControlAccept::empty(),
+        exit_code,
+    );
+    status.set_service_status(stopped)?;
+    result
+}
+
+fn service_status(
+    state: ServiceState,
+    accepted: ServiceControlAccept,
+    exit_code: u32,
+) -> ServiceStatus {
+    ServiceStatus {
+        service_type: ServiceType::OWN_PROCESS,
+        current_state: state,
+        controls_accepted: accepted,
+        exit_code: match exit_code {
+            0 => ServiceExitCode::Win32(0),
+            code => ServiceExitCode::ServiceSpecific(code),
+        },
+        checkpoint: 0,
+        wait_hint: Duration::default(),
+        process_id: None,
+    }
+}
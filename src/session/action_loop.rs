@@ -1,26 +1,185 @@
-use crate::config::ActionsConfig;
+use super::armed::{ArmedSwitch, Command, CommandReceiver};
+use super::audio::AudioPlayer;
+use super::availability::AvailabilityTopics;
+use super::brokers::NamedBrokers;
+use super::cap;
+use super::coincidence::Coincidence;
+use super::commands::CommandRunner;
+use super::event_id::EventId;
+use super::event_log::EventLogSink;
+use super::ground_motion::GroundMotion;
+use super::influx::{InfluxSink, PointTags};
+use super::live::LiveFeed;
+use super::mqtt::{AsyncClient, ClientError, QoS};
+use super::outbox::{post_or_hold, MqttConnection, Outbox, Post};
+use super::placeholders::Placeholders;
+use super::rate_limit::RateLimiter;
+use super::restart::describe;
+use super::retry::RetryPolicy;
+use super::snmp::{SnmpTraps, Trap};
+use super::stats::{FlowStats, WindowStats};
+use super::status::StatusBoard;
+use super::syslog::{Severity, Syslog};
+use super::systemd::{heartbeat_due, Heartbeat};
+use super::telegram::Telegram;
+use super::webhook::post_webhook;
+use crate::config::{
+    ActionErrorPolicy, ActionsConfig, ArmedConfig, MQTTBrokerConfig, MQTTConfig, MQTTQoS,
+    PayloadFormat, StatsConfig,
+};
+use crate::time::UtcTime;
 
-use rumqttc::{AsyncClient, ClientError};
-use std::collections::HashMap;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use thiserror::Error;
-use tokio::process::Command;
+use tokio::sync::watch;
+use tokio::time::Instant;
 
 #[derive(Debug, Error)]
 pub enum ActionLoopError {
     #[error("error publishing MQTT topic")]
     MQTTClientError(#[from] ClientError),
-    #[error("failed to execute external program")]
-    ExecuteFailure(#[from] std::io::Error),
+}
+
+/// An operational problem, reported alongside seismic events.
+#[derive(Debug, Clone)]
+pub enum Warning {
+    /// Some number of undecodable packets were received within an
+    /// interval of some seconds.
+    DecodeErrors { count: usize, seconds: f32 },
+    /// The flow's filters produced a non-finite value and were reset.
+    NonFiniteReset,
+    /// The flow's trigger stayed asserted for too long and was forcibly
+    /// reset.
+    StuckReset,
+    /// The sample rate measured from packet timestamps disagrees with the
+    /// configured one.
+    SampleRateMismatch { measured: f64, configured: f64 },
+    /// The channel's noise floor (the RMS of its raw counts while quiet)
+    /// is outside its healthy range.
+    NoiseFloor { rms: f64 },
+    /// Some number of the flow's notifications of some kind weren't sent,
+    /// as they came within some seconds of the last one that was.
+    Suppressed {
+        count: usize,
+        kind: &'static str,
+        seconds: f32,
+    },
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Warning::DecodeErrors { count, seconds } => {
+                write!(f, "{count} undecodable packets in {seconds} s")
+            }
+            Warning::NonFiniteReset => write!(f, "non-finite filter output, filters reset"),
+            Warning::StuckReset => write!(f, "trigger stuck, forcibly reset"),
+            Warning::SampleRateMismatch {
+                measured,
+                configured,
+            } => {
+                write!(
+                    f,
+                    "measured sample rate {measured:.2} Hz, configured {configured} Hz"
+                )
+            }
+            Warning::NoiseFloor { rms } => {
+                write!(f, "noise floor {rms:.2} counts RMS, outside healthy range")
+            }
+            Warning::Suppressed {
+                count,
+                kind,
+                seconds,
+            } => {
+                write!(
+                    f,
+                    "{count} {kind} notifications suppressed within {seconds} s of the last sent"
+                )
+            }
+        }
+    }
+}
+
+/// What was seen of a triggered event, once its trigger has reset.
+#[derive(Debug, Clone, Copy)]
+pub struct EventSummary {
+    /// The data time at which the trigger reset.
+    pub at: f64,
+    /// How long the trigger was asserted for, in seconds.
+    pub duration_s: f64,
+    /// The peak energy fed to the trigger while it was asserted.
+    pub peak_energy: f64,
+}
+
+impl std::fmt::Display for EventSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "reset at {:.2} after {:.2} s, peak energy {}",
+            self.at, self.duration_s, self.peak_energy
+        )
+    }
 }
 
 /// A seismometer event.
 pub enum Event {
-    Status { dc: f32, energy: f32 },
+    Status {
+        dc: f32,
+        energy: f32,
+    },
     Available,
     Unavailable,
-    Triggered,
-    Reset,
+    /// The flow's trigger has asserted, at a data time and with the
+    /// energy fed to it then, along with the data time of the onset of
+    /// the phase that set it off (if picked).
+    Triggered {
+        at: f64,
+        energy: f64,
+        onset: Option<f64>,
+    },
+    /// The flow's event has escalated to one of its tiers (counting from
+    /// 1), and so to every tier below it, at a data time and with the
+    /// energy fed to the trigger then.
+    Escalated {
+        severity: usize,
+        at: f64,
+        energy: f64,
+    },
+    /// The flow's trigger has reset at a data time, with a summary of the
+    /// event (if it was seen to trigger) and the peak ground motion seen
+    /// while it was triggered (if measured).
+    Reset {
+        at: f64,
+        summary: Option<EventSummary>,
+        ground_motion: Option<GroundMotion>,
+    },
+    /// The flow's trigger was forcibly reset at a data time, after staying
+    /// asserted for too long.
+    StuckReset {
+        at: f64,
+    },
+    Warning(Warning),
+    /// The seismometer's clock differs from the host's by this many
+    /// seconds (host time minus seismometer time).
+    ClockDrift {
+        offset_s: f64,
+    },
+    /// A capture of the waveform around the flow's latest event has
+    /// been saved to a file.
+    Captured {
+        path: PathBuf,
+    },
+    /// The levels at which the flow's trigger asserts and resets, as at
+    /// startup or since they were adjusted, and the profile of the flow's
+    /// threshold schedule they are from (if it is on one).
+    Levels {
+        trigger_level: f32,
+        reset_level: f32,
+        profile: Option<String>,
+    },
 }
 
 /// A seismometer event from a particular seismometer.
@@ -31,7 +190,422 @@ pub struct TriggerMessage {
 
 struct Flow<'a> {
     name: &'a str,
+    seismometer: Option<&'a str>,
+    channel: Option<&'a str>,
     actions: &'a ActionsConfig,
+    /// The data time and energy of the flow's latest trigger, and the
+    /// identifier of its latest event (which its reset shares).
+    trigger: Option<(f64, f64)>,
+    event_id: Option<EventId>,
+    /// The data time of the flow's latest reset, the summary of its latest
+    /// event, and its peak ground motion.
+    reset_at: Option<f64>,
+    summary: Option<EventSummary>,
+    ground_motion: Option<GroundMotion>,
+    /// Limits on how often trigger and warning actions are taken.
+    trigger_limit: Option<RateLimiter>,
+    warning_limit: Option<RateLimiter>,
+}
+
+/// An event of a flow, as posted in JSON payloads.
+#[derive(Serialize)]
+struct EventPayload<'a> {
+    event: &'a str,
+    flow: &'a str,
+    seismometer: Option<&'a str>,
+    channel: Option<&'a str>,
+    timestamp: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    event_id: Option<EventId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    energy: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration_s: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    peak_energy: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ground_motion: Option<GroundMotion>,
+}
+
+impl<'a> Flow<'a> {
+    /// Placeholder values for an event of the flow, which happened at a
+    /// data time (if known).
+    fn placeholders(&self, timestamp: Option<f64>, energy: Option<f64>) -> Placeholders<'a> {
+        let placeholders = Placeholders::now(self.name);
+        Placeholders {
+            seismometer: self.seismometer,
+            channel: self.channel,
+            timestamp: timestamp.unwrap_or(placeholders.timestamp),
+            energy,
+            ..placeholders
+        }
+    }
+
+    /// Placeholder values for an event of the flow's trigger, which also
+    /// identify the event.
+    fn event_placeholders(&self, timestamp: Option<f64>, energy: Option<f64>) -> Placeholders<'a> {
+        Placeholders {
+            event_id: self.event_id,
+            ..self.placeholders(timestamp, energy)
+        }
+    }
+
+    /// The payload to post for an event of the flow: either some text,
+    /// with placeholders expanded, or a JSON description of the event.
+    fn payload(&self, event: &str, text: &str, placeholders: &Placeholders) -> String {
+        if self.actions.mqtt_payload_format == PayloadFormat::Text {
+            return placeholders.expand(text);
+        }
+        self.json_payload(event, placeholders)
+    }
+
+    /// A JSON description of an event of the flow.
+    fn json_payload(&self, event: &str, placeholders: &Placeholders) -> String {
+        let reset = event == "reset";
+        let summary = self.summary.filter(|_| reset);
+        let payload = EventPayload {
+            event,
+            flow: self.name,
+            seismometer: self.seismometer,
+            channel: self.channel,
+            timestamp: placeholders.timestamp,
+            event_id: placeholders.event_id,
+            energy: placeholders.energy.filter(|_| !reset),
+            duration_s: summary.map(|s| s.duration_s),
+            peak_energy: summary.map(|s| s.peak_energy),
+            ground_motion: self.ground_motion.filter(|_| reset),
+        };
+        serde_json::to_string(&payload).unwrap_or_default()
+    }
+
+    /// Write a point to an InfluxDB output for an event of the flow, if
+    /// it is one that is trended.
+    fn record(&self, influx: &InfluxSink, event: &Event) {
+        let tags = PointTags {
+            flow: self.name,
+            seismometer: self.seismometer,
+            channel: self.channel,
+        };
+        let now = self.placeholders(None, None).timestamp;
+        let event_id = self.event_id.map(|id| id.to_string());
+        let event_id = event_id.as_deref();
+        match *event {
+            Event::Status { dc, energy } => influx.status(&tags, dc, energy, now),
+            Event::Triggered { at, energy, .. } => {
+                influx.event(&tags, "triggered", event_id, &[("energy", energy)], at)
+            }
+            Event::Escalated {
+                severity,
+                at,
+                energy,
+            } => {
+                let fields = [("severity", severity as f64), ("energy", energy)];
+                influx.event(&tags, "escalated", event_id, &fields, at)
+            }
+            // Resets of triggers which weren't seen to assert (as when
+            // the flow's initial state is announced) mark no event.
+            Event::Reset {
+                summary: Some(summary),
+                ..
+            } => {
+                let fields = [
+                    ("duration_s", summary.duration_s),
+                    ("peak_energy", summary.peak_energy),
+                ];
+                influx.event(&tags, "reset", event_id, &fields, summary.at)
+            }
+            Event::StuckReset { at } => influx.event(&tags, "stuck_reset", event_id, &[], at),
+            _ => (),
+        }
+    }
+
+    /// Write an event of the flow to the event log, if it is one that is
+    /// logged.
+    fn write_to_log(&self, log: &EventLogSink, event: &Event) {
+        if let Some(record) = self.log_record(event, log.logs_status()) {
+            log.write(&record);
+        }
+    }
+
+    /// Send an event of the flow to the live feed: as it is logged, or
+    /// if it is a change of trigger levels, the new levels.
+    fn stream(&self, live: &LiveFeed, event: &Event) {
+        let message = match *event {
+            Event::Levels {
+                trigger_level,
+                reset_level,
+                ref profile,
+            } => serde_json::json!({
+                "type": "levels",
+                "flow": self.name,
+                "seismometer": self.seismometer,
+                "trigger_level": trigger_level,
+                "reset_level": reset_level,
+                "profile": profile,
+            }),
+            _ => match self.log_record(event, false) {
+                Some(mut record) => {
+                    record["type"] = "event".into();
+                    record
+                }
+                None => return,
+            },
+        };
+        live.send(&message);
+    }
+
+    /// An event of the flow, if it is one that is logged (with its status
+    /// reports, if `status` is set), as a JSON object of its time, its
+    /// kind, the flow and its details.
+    fn log_record(&self, event: &Event, status: bool) -> Option<serde_json::Value> {
+        let now = self.placeholders(None, None).timestamp;
+        let (kind, at, details) = match *event {
+            Event::Status { dc, energy } if status => (
+                "status",
+                now,
+                serde_json::json!({ "dc": dc, "energy": energy }),
+            ),
+            Event::Triggered { at, energy, onset } => {
+                let mut details = serde_json::json!({ "energy": energy });
+                if let Some(onset) = onset {
+                    details["onset"] = UtcTime::from_epoch(onset).to_string().into();
+                }
+                ("triggered", at, details)
+            }
+            Event::Escalated {
+                severity,
+                at,
+                energy,
+            } => (
+                "escalated",
+                at,
+                serde_json::json!({ "severity": severity, "energy": energy }),
+            ),
+            // Resets of triggers which weren't seen to assert (as when
+            // the flow's initial state is announced) aren't events.
+            Event::Reset {
+                summary: Some(summary),
+                ..
+            } => {
+                let details = serde_json::json!({
+                    "duration_s": summary.duration_s,
+                    "peak_energy": summary.peak_energy,
+                });
+                ("reset", summary.at, details)
+            }
+            Event::StuckReset { at } => ("stuck_reset", at, serde_json::json!({})),
+            Event::Available => ("available", now, serde_json::json!({})),
+            Event::Unavailable => ("unavailable", now, serde_json::json!({})),
+            Event::Warning(ref warning) => (
+                "warning",
+                now,
+                serde_json::json!({ "warning": warning.to_string() }),
+            ),
+            Event::ClockDrift { offset_s } => (
+                "clock_drift",
+                now,
+                serde_json::json!({ "offset_s": offset_s }),
+            ),
+            _ => return None,
+        };
+        let mut record = details;
+        record["time"] = UtcTime::from_epoch(at).to_string().into();
+        record["event"] = kind.into();
+        if let Some(event_id) = self.event_id.filter(|_| is_event_of_trigger(kind)) {
+            record["event_id"] = event_id.to_string().into();
+        }
+        record["flow"] = self.name.into();
+        if let Some(seismometer) = self.seismometer {
+            record["seismometer"] = seismometer.into();
+        }
+        if let Some(channel) = self.channel {
+            record["channel"] = channel.into();
+        }
+        Some(record)
+    }
+
+    /// Send an event of the flow to syslog, with its details as
+    /// structured data.
+    fn log_to_syslog(&self, syslog: &Syslog, event: &Event) {
+        let name = self.name;
+        let mut data = vec![("flow", name.to_owned())];
+        data.extend(self.seismometer.map(|s| ("seismometer", s.to_owned())));
+        data.extend(self.channel.map(|c| ("channel", c.to_owned())));
+        let (severity, msg_id, message) = match event {
+            Event::Triggered { at, energy, onset } => {
+                data.push(("timestamp", UtcTime::from_epoch(*at).to_string()));
+                data.push(("energy", energy.to_string()));
+                data.extend(onset.map(|o| ("onset", UtcTime::from_epoch(o).to_string())));
+                (
+                    Severity::Warning,
+                    "triggered",
+                    format!("{name}: earthquake detected"),
+                )
+            }
+            Event::Escalated {
+                severity,
+                at,
+                energy,
+            } => {
+                data.push(("timestamp", UtcTime::from_epoch(*at).to_string()));
+                data.push(("energy", energy.to_string()));
+                data.push(("severity", severity.to_string()));
+                (
+                    Severity::Warning,
+                    "escalated",
+                    format!("{name}: earthquake escalated"),
+                )
+            }
+            // Resets of triggers which weren't seen to assert (as when
+            // the flow's initial state is announced) aren't events.
+            Event::Reset {
+                summary: Some(summary),
+                ..
+            } => {
+                data.push(("timestamp", UtcTime::from_epoch(summary.at).to_string()));
+                data.push(("duration_s", format!("{:.2}", summary.duration_s)));
+                data.push(("peak_energy", summary.peak_energy.to_string()));
+                (
+                    Severity::Notice,
+                    "reset",
+                    format!("{name}: earthquake over"),
+                )
+            }
+            Event::StuckReset { at } => {
+                data.push(("timestamp", UtcTime::from_epoch(*at).to_string()));
+                (
+                    Severity::Warning,
+                    "stuck_reset",
+                    format!("{name}: {}", Warning::StuckReset),
+                )
+            }
+            Event::Available => (Severity::Notice, "available", format!("{name}: available")),
+            Event::Unavailable => (
+                Severity::Warning,
+                "unavailable",
+                format!("{name}: unavailable"),
+            ),
+            Event::Warning(warning) => (Severity::Warning, "warning", format!("{name}: {warning}")),
+            Event::ClockDrift { offset_s } => {
+                data.push(("offset_s", format!("{offset_s:.3}")));
+                (
+                    Severity::Warning,
+                    "clock_drift",
+                    format!("{name}: clock drift"),
+                )
+            }
+            _ => return,
+        };
+        if let Some(event_id) = self.event_id.filter(|_| is_event_of_trigger(msg_id)) {
+            data.push(("event_id", event_id.to_string()));
+        }
+        syslog.send(severity, msg_id, &data, &message);
+    }
+
+    /// A Home Assistant discovery config describing the flow as a binary
+    /// sensor whose state is posted to a topic. It is available only
+    /// while both it and the daemon are (as far as they are announced).
+    fn discovery_config(
+        &self,
+        topic: &str,
+        object_id: &str,
+        mqtt: &MQTTConfig,
+    ) -> serde_json::Value {
+        let actions = self.actions;
+        // Flows are grouped by seismometer, and those which span several
+        // are grouped together.
+        let (identifier, device_name) = match self.seismometer {
+            Some(seismometer) => (format!("rs_udp_{}", discovery_id(seismometer)), seismometer),
+            None => (String::from("rs_udp"), "rs-udp"),
+        };
+        let mut config = serde_json::json!({
+            "name": self.name,
+            "unique_id": format!("rs_udp_{object_id}"),
+            "device_class": "vibration",
+            "state_topic": topic,
+            "device": {
+                "identifiers": [identifier],
+                "name": device_name,
+                "sw_version": env!("CARGO_PKG_VERSION"),
+            },
+        });
+        let json = actions.mqtt_payload_format == PayloadFormat::Json;
+        if json {
+            config["value_template"] =
+                "{{ 'ON' if value_json.event == 'triggered' else 'OFF' }}".into();
+        } else {
+            config["payload_on"] = actions.mqtt_triggered_payload.as_str().into();
+            config["payload_off"] = actions.mqtt_reset_payload.as_str().into();
+        }
+        let mut availability = Vec::new();
+        if let Some(topic) = actions.mqtt_available_topic.as_ref() {
+            availability.push(if json {
+                serde_json::json!({
+                    "topic": topic,
+                    "value_template":
+                        "{{ 'online' if value_json.event == 'available' else 'offline' }}",
+                })
+            } else {
+                serde_json::json!({
+                    "topic": topic,
+                    "payload_available": actions.mqtt_available_payload,
+                    "payload_not_available": actions.mqtt_unavailable_payload,
+                })
+            });
+        }
+        if let Some(topic) = mqtt.availability_topic.as_ref() {
+            availability.push(serde_json::json!({
+                "topic": topic,
+                "payload_available": mqtt.online_payload,
+                "payload_not_available": mqtt.offline_payload,
+            }));
+        }
+        if !availability.is_empty() {
+            config["availability"] = availability.into();
+            config["availability_mode"] = "all".into();
+        }
+        config
+    }
+}
+
+/// A flow's trigger levels, as posted to the thresholds state topic.
+#[derive(Serialize)]
+struct LevelsPayload<'a> {
+    flow: &'a str,
+    seismometer: Option<&'a str>,
+    trigger_level: f32,
+    reset_level: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    profile: Option<&'a str>,
+}
+
+/// A flow's statistics, as posted to the statistics topic by its name.
+#[derive(Serialize)]
+struct StatsPayload<'a> {
+    seismometer: &'a str,
+    channel: Option<&'a str>,
+    hour: WindowStats,
+    day: WindowStats,
+}
+
+/// Whether a kind of event (as logged) is of a trigger's event, and so
+/// carries the event's identifier.
+fn is_event_of_trigger(kind: &str) -> bool {
+    matches!(kind, "triggered" | "escalated" | "reset" | "stuck_reset")
+}
+
+/// A name, reduced to the characters allowed in Home Assistant discovery
+/// topics and unique IDs.
+fn discovery_id(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
 }
 
 /// A set of actions to take on seismometer events, indexed by siesmometer.
@@ -44,140 +618,1850 @@ pub type OutChannel = tokio::sync::mpsc::Sender<TriggerMessage>;
 pub type InChannel = tokio::sync::mpsc::Receiver<TriggerMessage>;
 
 /// Construct a channel pair for seismometers to post events into, and
-/// from which the main thread can receive them.
-pub fn message_channel() -> (OutChannel, InChannel) {
-    tokio::sync::mpsc::channel::<TriggerMessage>(32)
+/// from which the main thread can receive them, with room for some number
+/// of events to wait.
+pub fn message_channel(capacity: usize) -> (OutChannel, InChannel) {
+    tokio::sync::mpsc::channel::<TriggerMessage>(capacity)
 }
 
 pub struct ActionLoop<'a> {
     flows: FlowsMap<'a>,
     mqtt: Option<AsyncClient>,
     chan: InChannel,
+    armed: watch::Receiver<bool>,
+    armed_config: Option<&'a ArmedConfig>,
+    /// Commands for flows, or for every flow, if any are listened for.
+    control: Option<CommandReceiver>,
+    /// Flows which have been disarmed on their own.
+    disarmed: HashSet<usize>,
+    /// Flows which are currently triggered.
+    triggered: HashSet<usize>,
+    /// Whether flows' channels are currently alive, once told, and the
+    /// topics on which whole seismometers' availability is posted.
+    alive: HashMap<usize, bool>,
+    availability: AvailabilityTopics<'a>,
+    /// Flows whose trigger actions have been taken, and which are owed
+    /// reset actions.
+    announced: HashSet<usize>,
+    /// Coincidence triggers, which act as flows of their own.
+    coincidences: Vec<(usize, Coincidence)>,
+    /// The tiers of flows' events, which also act as flows of their own,
+    /// by their own id, their flow's id and their severity.
+    tiers: Vec<(usize, usize, usize)>,
+    /// MQTT configuration, if Home Assistant discovery messages are to
+    /// be published.
+    discovery: Option<&'a MQTTConfig>,
+    /// Posts held while the broker is unreachable.
+    outbox: Option<Outbox>,
+    /// Named brokers, which flows may post to in place of the main one.
+    brokers: NamedBrokers,
+    /// InfluxDB output, if flow status and events are written to one.
+    influx: Option<InfluxSink>,
+    /// Event log, if events are appended to one.
+    event_log: Option<EventLogSink>,
+    /// Live feed, if events are streamed to one.
+    live: Option<LiveFeed>,
+    /// Telegram bot API client, if any flow sends messages through one.
+    telegram: Option<Telegram>,
+    /// Syslog, for flows which send their events to it.
+    syslog: Option<Syslog>,
+    /// The host's audio output, if any flow plays a sound through it.
+    audio: Option<AudioPlayer>,
+    /// An SNMP manager, for flows which send it traps.
+    snmp: Option<SnmpTraps>,
+    /// Runs the executables that flows' actions call for.
+    commands: CommandRunner,
+    /// Where to count actions which fail for good, if anywhere.
+    failures: Option<StatusBoard>,
+    /// Whether an event's actions failing part way stops the loop.
+    error_policy: ActionErrorPolicy,
+    /// Where to keep the flows' states, if anywhere.
+    flow_states: Option<StatusBoard>,
+    /// The trigger levels of flows which have announced them (and the
+    /// profiles they are from), and the topic to post them to, if any.
+    levels: BTreeMap<usize, (f32, f32, Option<String>)>,
+    thresholds_topic: Option<String>,
+    /// The loop's sign of life, if it gives one.
+    heartbeat: Option<Heartbeat>,
+    /// Set when the daemon is stopping for a restart, rather than for good.
+    restarting: Option<watch::Receiver<bool>>,
+    /// Statistics of the flows' events, by flow id, if they are published,
+    /// and when they are next due to be.
+    stats: HashMap<usize, FlowStats>,
+    stats_config: Option<&'a StatsConfig>,
+    stats_due: Option<Instant>,
 }
 
 impl<'a> ActionLoop<'a> {
-    pub fn new(chan: InChannel, mqtt: Option<AsyncClient>) -> Self {
+    pub fn new(
+        chan: InChannel,
+        mqtt: Option<AsyncClient>,
+        armed: &ArmedSwitch,
+        armed_config: Option<&'a ArmedConfig>,
+    ) -> Self {
         Self {
             flows: FlowsMap::new(),
             chan,
             mqtt,
-         }
+            armed: armed.subscribe(),
+            armed_config,
+            control: None,
+            disarmed: HashSet::new(),
+            triggered: HashSet::new(),
+            alive: HashMap::new(),
+            availability: AvailabilityTopics::new(),
+            announced: HashSet::new(),
+            coincidences: Vec::new(),
+            tiers: Vec::new(),
+            discovery: None,
+            outbox: None,
+            brokers: NamedBrokers::new(),
+            influx: None,
+            event_log: None,
+            live: None,
+            telegram: None,
+            syslog: None,
+            audio: None,
+            snmp: None,
+            commands: CommandRunner::new(tokio::sync::Semaphore::MAX_PERMITS),
+            failures: None,
+            error_policy: ActionErrorPolicy::default(),
+            flow_states: None,
+            levels: BTreeMap::new(),
+            thresholds_topic: None,
+            heartbeat: None,
+            restarting: None,
+            stats: HashMap::new(),
+            stats_config: None,
+            stats_due: None,
+        }
     }
 
-    /// Introduce a new sensor and its actions to the loop.
-    pub fn add_flow(&mut self, flow_id: usize, name: &'a str, actions: &'a ActionsConfig) {
-        let flow = Flow { name, actions };
+    /// Hold posts in an outbox while the broker is unreachable, rather
+    /// than waiting for it, and post them once it is reachable again.
+    pub fn queue_while_disconnected(&mut self, outbox: Outbox) {
+        self.outbox = Some(outbox);
+    }
+
+    /// Post to a named broker for flows which select it, holding posts in
+    /// its outbox while it is unreachable.
+    pub fn post_to_broker(
+        &mut self,
+        name: String,
+        client: AsyncClient,
+        connection: &MqttConnection,
+        config: &MQTTBrokerConfig,
+    ) {
+        self.brokers.add(name, client, connection, config);
+    }
+
+    /// Write each flow's periodic status, and markers for its triggers
+    /// and resets, to an InfluxDB output.
+    pub fn write_to_influx(&mut self, sink: InfluxSink) {
+        self.influx = Some(sink);
+    }
+
+    /// Append every flow's events to an event log.
+    pub fn write_to_event_log(&mut self, sink: EventLogSink) {
+        self.event_log = Some(sink);
+    }
+
+    /// Stream every flow's events to a live feed.
+    pub fn stream_live(&mut self, feed: LiveFeed) {
+        self.live = Some(feed);
+    }
+
+    /// Send Telegram messages for flows which have a bot configured.
+    pub fn notify_telegram(&mut self, telegram: Telegram) {
+        self.telegram = Some(telegram);
+    }
+
+    /// Send the events of flows which ask for it to syslog.
+    pub fn send_to_syslog(&mut self, syslog: Syslog) {
+        self.syslog = Some(syslog);
+    }
+
+    /// Play sounds for flows which have one configured.
+    pub fn play_audio(&mut self, audio: AudioPlayer) {
+        self.audio = Some(audio);
+    }
+
+    /// Send traps for the events of flows which ask for them to an SNMP
+    /// manager.
+    pub fn send_snmp_traps(&mut self, snmp: SnmpTraps) {
+        self.snmp = Some(snmp);
+    }
+
+    /// Run at most some number of executables for actions at once,
+    /// skipping any more that are called for while that many run.
+    pub fn limit_running_cmds(&mut self, max_running: usize) {
+        self.commands.limit(max_running);
+    }
+
+    /// Stop the loop when an event's actions fail part way, rather than
+    /// carry on with the next event, if the policy says to.
+    pub fn set_error_policy(&mut self, policy: ActionErrorPolicy) {
+        self.error_policy = policy;
+    }
+
+    /// Count actions which fail, even after any retries, on the daemon's
+    /// status board.
+    pub fn report_failures(&mut self, status: StatusBoard) {
+        self.commands.report_failures(status.clone());
+        self.failures = Some(status);
+    }
+
+    /// Keep each flow's state, as the loop sees it, on a status board.
+    pub fn report_flow_states(&mut self, status: StatusBoard) {
+        self.flow_states = Some(status);
+    }
+
+    /// Post the trigger levels of every flow which has them to a topic,
+    /// retained, whenever they are announced.
+    pub fn report_thresholds(&mut self, topic: String) {
+        self.thresholds_topic = Some(topic);
+    }
+
+    /// Post, retained, whether the channels of all of a seismometer's flows
+    /// (or of every seismometer's, if none is named) are alive to a topic,
+    /// as the MQTT configuration's online or offline payload, over a
+    /// connection of the topic's own.
+    pub fn report_availability(
+        &mut self,
+        seismometer: Option<&'a str>,
+        topic: &str,
+        client: AsyncClient,
+        connection: &MqttConnection,
+        config: &'a MQTTConfig,
+    ) {
+        self.availability
+            .add(seismometer, topic, client, connection, config);
+    }
+
+    /// Keep statistics of every flow's events over the last hour and the
+    /// last day, and publish them as often as configured.
+    pub fn publish_stats(&mut self, config: &'a StatsConfig) {
+        self.stats_config = Some(config);
+        self.stats_due = Some(Instant::now() + Duration::from_secs_f32(config.interval_s.max(1.0)));
+    }
+
+    /// Give a sign of life regularly for as long as the loop runs.
+    pub fn report_liveness(&mut self, heartbeat: Heartbeat) {
+        self.heartbeat = Some(heartbeat);
+    }
+
+    /// Leave what was posted standing as the loop stops, if the daemon is
+    /// then restarting, for the new session to take on. Flows' events are
+    /// reset all the same, as the new session starts afresh.
+    pub fn note_restarts(&mut self, restarting: watch::Receiver<bool>) {
+        self.restarting = Some(restarting);
+    }
+
+    /// Arm, disarm and test flows as commanded.
+    pub fn take_commands(&mut self, control: CommandReceiver) {
+        self.control = Some(control);
+    }
+
+    /// The topics on which to listen for commands for flows, by flow id.
+    pub fn command_topics(&self) -> Vec<(&'a str, usize)> {
+        self.flows
+            .iter()
+            .filter_map(|(&flow_id, flow)| {
+                Some((flow.actions.mqtt_command_topic.as_deref()?, flow_id))
+            })
+            .collect()
+    }
+
+    /// The names of every flow, tier and coincidence.
+    pub fn flow_names(&self) -> impl Iterator<Item = &'a str> + '_ {
+        self.flows.values().map(|flow| flow.name)
+    }
+
+    /// The names of flows which process a seismometer's data (rather than
+    /// being tiers or coincidences of them) and of their seismometers, by
+    /// flow id.
+    pub fn sensor_flows(&self) -> Vec<(&'a str, &'a str, usize)> {
+        self.flows
+            .iter()
+            .filter(|(flow_id, flow)| {
+                flow.channel.is_some() && !self.tiers.iter().any(|&(id, ..)| id == **flow_id)
+            })
+            .filter_map(|(&flow_id, flow)| Some((flow.name, flow.seismometer?, flow_id)))
+            .collect()
+    }
+
+    /// Publish Home Assistant discovery messages for every flow with an
+    /// MQTT topic when the loop starts, if the MQTT configuration has a
+    /// discovery prefix.
+    pub fn publish_discovery(&mut self, config: &'a MQTTConfig) {
+        self.discovery = Some(config);
+    }
+
+    /// Introduce a new sensor and its actions to the loop, along with the
+    /// seismometer and channel it listens to (if any).
+    pub fn add_flow(
+        &mut self,
+        flow_id: usize,
+        name: &'a str,
+        seismometer: Option<&'a str>,
+        channel: Option<&'a str>,
+        actions: &'a ActionsConfig,
+    ) {
+        let flow = Flow {
+            name,
+            seismometer,
+            channel,
+            actions,
+            trigger: None,
+            event_id: None,
+            reset_at: None,
+            summary: None,
+            ground_motion: None,
+            trigger_limit: actions.trigger_rate_limit_s.map(RateLimiter::new),
+            warning_limit: actions.warning_rate_limit_s.map(RateLimiter::new),
+        };
         self.flows.insert(flow_id, flow);
     }
 
+    /// Introduce a coincidence trigger and its actions to the loop, along
+    /// with the seismometer it spans (if just one). It is triggered and
+    /// reset as though it were a flow itself.
+    pub fn add_coincidence(
+        &mut self,
+        flow_id: usize,
+        name: &'a str,
+        seismometer: Option<&'a str>,
+        actions: &'a ActionsConfig,
+        coincidence: Coincidence,
+    ) {
+        self.add_flow(flow_id, name, seismometer, None, actions);
+        self.coincidences.push((flow_id, coincidence));
+    }
+
+    /// Introduce a tier of a flow's events and its actions to the loop,
+    /// along with the tier's severity (counting from 1). It is triggered
+    /// as its flow's events escalate to it, and reset as they reset.
+    pub fn add_tier(
+        &mut self,
+        flow_id: usize,
+        parent_id: usize,
+        severity: usize,
+        name: &'a str,
+        actions: &'a ActionsConfig,
+    ) {
+        let (seismometer, channel) = self
+            .flows
+            .get(&parent_id)
+            .map_or((None, None), |parent| (parent.seismometer, parent.channel));
+        self.add_flow(flow_id, name, seismometer, channel, actions);
+        self.tiers.push((flow_id, parent_id, severity));
+    }
+
     /// Listen for events from all seismometers. When they are received, take
     /// action on them from the configured actions.
     pub async fn run(mut self) -> Result<(), ActionLoopError> {
-        while let Some(msg) = self.chan.recv().await {
-            self.handle_seismometer_event(msg).await?;
+        let armed = *self.armed.borrow_and_update();
+        let result = self.publish_armed_state(armed).await;
+        self.tolerate(result)?;
+        let mut flow_ids: Vec<usize> = self.flows.keys().copied().collect();
+        flow_ids.sort();
+        for flow_id in flow_ids {
+            let result = self.publish_flow_armed_state(flow_id).await;
+            self.tolerate(result)?;
+        }
+        self.note_armed_states();
+        let result = self.publish_discovery_configs().await;
+        self.tolerate(result)?;
+        let result = self.publish_availability().await;
+        self.tolerate(result)?;
+        loop {
+            if let Some(heartbeat) = self.heartbeat.as_ref() {
+                heartbeat.beat();
+            }
+            let summaries_due = self.summaries_due();
+            let result = tokio::select! {
+                msg = self.chan.recv() => match msg {
+                    Some(msg) => self.handle_seismometer_event(msg).await,
+                    None => break,
+                },
+                Ok(()) = self.armed.changed() => {
+                    let armed = *self.armed.borrow_and_update();
+                    self.handle_armed_change(armed).await
+                }
+                () = reconnected(&mut self.outbox) => self.flush_outbox().await,
+                index = self.brokers.reconnected() => self.rejoin_broker(index).await,
+                _ = self.availability.reconnected() => self.publish_availability().await,
+                Some((flow_id, command)) = next_command(&mut self.control) => {
+                    self.handle_command(flow_id, command).await
+                }
+                () = sleep_until(summaries_due) => self.announce_summaries().await,
+                () = sleep_until(self.stats_due) => self.announce_stats().await,
+                () = heartbeat_due(self.heartbeat.as_ref()) => Ok(()),
+            };
+            self.tolerate(result)?;
+        }
+        let restarting = self
+            .restarting
+            .as_ref()
+            .is_some_and(|restarting| *restarting.borrow());
+        if restarting {
+            self.reset_for_restart().await;
+        } else {
+            self.announce_unavailable().await;
+        }
+        self.availability.leave(restarting).await;
+        self.brokers.leave(restarting).await;
+        Ok(())
+    }
+
+    /// Log and count a failure to act on an event (or to post some state),
+    /// and carry on with the next unless the policy is to stop. Whatever
+    /// actions were left undone for the event are skipped.
+    fn tolerate(&self, result: Result<(), ActionLoopError>) -> Result<(), ActionLoopError> {
+        let Err(e) = result else {
+            return Ok(());
+        };
+        if let Some(failures) = self.failures.as_ref() {
+            failures.update(|status| status.action_errors += 1);
+        }
+        if self.error_policy == ActionErrorPolicy::Stop {
+            return Err(e);
+        }
+        log::error!("{}, carrying on", describe(&e));
+        Ok(())
+    }
+
+    /// Post that every flow is unavailable, as the daemon stops, so that
+    /// retained states don't claim otherwise. A failure is only logged,
+    /// as there is no time left to retry it.
+    async fn announce_unavailable(&mut self) {
+        let mut flow_ids: Vec<usize> = self.flows.keys().copied().collect();
+        flow_ids.sort();
+        for flow_id in flow_ids {
+            let Some(flow) = self.flows.get(&flow_id) else {
+                continue;
+            };
+            let (name, actions) = (flow.name, flow.actions);
+            let placeholders = flow.placeholders(None, None);
+            let payload = flow.payload(
+                "unavailable",
+                &actions.mqtt_unavailable_payload,
+                &placeholders,
+            );
+            let result = self
+                .mqtt_publish(
+                    actions.mqtt_broker.as_deref(),
+                    &actions.mqtt_available_topic,
+                    &payload,
+                    actions.mqtt_available_qos,
+                    actions.mqtt_available_retain,
+                )
+                .await;
+            if let Err(e) = result {
+                log::warn!("{name}: can't announce unavailability: {e}");
+            }
+        }
+    }
+
+    /// Take the reset actions of every flow whose trigger actions were
+    /// taken, as the daemon restarts, so that none is left triggered with
+    /// nothing to reset it. A failure is only logged, as the loop is done.
+    async fn reset_for_restart(&mut self) {
+        let mut flow_ids: Vec<usize> = self.announced.iter().copied().collect();
+        flow_ids.sort();
+        for flow_id in flow_ids {
+            if let Err(e) = self.announce_trigger(flow_id, false).await {
+                log::warn!(
+                    "{}: can't reset for restart: {e}",
+                    self.flows[&flow_id].name
+                );
+            }
+        }
+    }
+
+    /// Whether the channels of all of a seismometer's flows (or of every
+    /// seismometer's, if none is named) are alive, once each has been told
+    /// to be alive or not. Tiers and coincidences have no channels of their
+    /// own. A seismometer with no flows running has nothing alive.
+    fn all_alive(&self, seismometer: Option<&str>) -> Option<bool> {
+        let alive: Option<Vec<bool>> = self
+            .flows
+            .iter()
+            .filter(|(_, flow)| {
+                flow.channel.is_some() && seismometer.is_none_or(|s| flow.seismometer == Some(s))
+            })
+            .filter(|(flow_id, _)| !self.tiers.iter().any(|&(id, ..)| id == **flow_id))
+            .map(|(flow_id, _)| self.alive.get(flow_id).copied())
+            .collect();
+        alive.map(|alive| !alive.is_empty() && alive.into_iter().all(|alive| alive))
+    }
+
+    /// Post the availability of seismometers, as a whole, wherever it is
+    /// known and has changed since it was last posted.
+    async fn publish_availability(&mut self) -> Result<(), ActionLoopError> {
+        let alive: Vec<Option<bool>> = self
+            .availability
+            .seismometers()
+            .map(|seismometer| self.all_alive(seismometer))
+            .collect();
+        Ok(self.availability.publish(&alive).await?)
+    }
+
+    /// Bring the trigger actions in line with a new armed state. Disarming
+    /// resets every flow whose trigger actions were taken; arming takes
+    /// the trigger actions of every flow that is still triggered.
+    async fn handle_armed_change(&mut self, armed: bool) -> Result<(), ActionLoopError> {
+        self.publish_armed_state(armed).await?;
+        self.note_armed_states();
+        let mut flow_ids: Vec<usize> = if armed {
+            self.triggered
+                .difference(&self.announced)
+                .filter(|flow_id| !self.disarmed.contains(flow_id))
+                .copied()
+                .collect()
+        } else {
+            self.announced.iter().copied().collect()
+        };
+        flow_ids.sort();
+        for flow_id in flow_ids {
+            self.announce_trigger(flow_id, armed).await?;
+        }
+        Ok(())
+    }
+
+    /// Post a Home Assistant discovery config for each flow with an MQTT
+    /// topic, describing it as a binary sensor, if so configured. The
+    /// configs are retained, so that Home Assistant sees them whenever it
+    /// (re)connects.
+    async fn publish_discovery_configs(&mut self) -> Result<(), ActionLoopError> {
+        let Some((client, config)) = self.mqtt.as_ref().zip(self.discovery) else {
+            return Ok(());
+        };
+        let Some(prefix) = config.discovery_prefix.as_ref() else {
+            return Ok(());
+        };
+        let mut flow_ids: Vec<&usize> = self.flows.keys().collect();
+        flow_ids.sort();
+        for flow_id in flow_ids {
+            let flow = &self.flows[flow_id];
+            let Some(topic) = flow.actions.mqtt_topic.as_ref() else {
+                continue;
+            };
+            if flow.actions.mqtt_broker.is_some() {
+                continue;
+            }
+            let object_id = discovery_id(flow.name);
+            let config_topic = format!("{prefix}/binary_sensor/rs_udp/{object_id}/config");
+            let payload = flow.discovery_config(topic, &object_id, config).to_string();
+            client
+                .publish(config_topic, QoS::AtLeastOnce, true, payload.as_bytes())
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Note every flow's armed state on the status board.
+    fn note_armed_states(&self) {
+        let Some(board) = self.flow_states.as_ref() else {
+            return;
+        };
+        let armed = *self.armed.borrow();
+        board.update(|status| {
+            for (flow_id, flow) in self.flows.iter() {
+                let state = status.flows.entry(flow.name.to_owned()).or_default();
+                state.seismometer = flow.seismometer.map(str::to_owned);
+                state.channel = flow.channel.map(str::to_owned);
+                state.disabled = false;
+                state.armed = armed && !self.disarmed.contains(flow_id);
+            }
+        });
+    }
+
+    /// Note an event of a flow on the status board.
+    fn note_flow_event(&self, flow_id: usize, event: &Event) {
+        let Some((board, flow)) = self.flow_states.as_ref().zip(self.flows.get(&flow_id)) else {
+            return;
+        };
+        board.update(|status| {
+            status
+                .flows
+                .entry(flow.name.to_owned())
+                .or_default()
+                .note(event);
+        });
+    }
+
+    /// Post the armed state to MQTT, if so configured.
+    async fn publish_armed_state(&mut self, armed: bool) -> Result<(), ActionLoopError> {
+        let Some(config) = self.armed_config else {
+            return Ok(());
+        };
+        let payload = if armed {
+            &config.mqtt_armed_payload
+        } else {
+            &config.mqtt_disarmed_payload
+        };
+        self.mqtt_publish(
+            None,
+            &config.mqtt_state_topic,
+            payload,
+            MQTTQoS::AtLeastOnce,
+            true,
+        )
+        .await
+    }
+
+    /// Post a flow's own armed state to MQTT, if so configured.
+    async fn publish_flow_armed_state(&mut self, flow_id: usize) -> Result<(), ActionLoopError> {
+        let Some(flow) = self.flows.get(&flow_id) else {
+            return Ok(());
+        };
+        let armed = !self.disarmed.contains(&flow_id);
+        let payload = match (self.armed_config, armed) {
+            (Some(config), true) => config.mqtt_armed_payload.clone(),
+            (Some(config), false) => config.mqtt_disarmed_payload.clone(),
+            (None, true) => String::from("ON"),
+            (None, false) => String::from("OFF"),
+        };
+        let broker = flow.actions.mqtt_broker.as_deref();
+        let topic = &flow.actions.mqtt_armed_topic;
+        self.mqtt_publish(broker, topic, &payload, MQTTQoS::AtLeastOnce, true)
+            .await
+    }
+
+    /// Post the trigger levels of every flow which has announced them, if
+    /// so configured.
+    async fn publish_levels(&mut self) -> Result<(), ActionLoopError> {
+        if self.thresholds_topic.is_none() {
+            return Ok(());
+        }
+        let levels: Vec<LevelsPayload> = self
+            .levels
+            .iter()
+            .filter_map(|(flow_id, (trigger_level, reset_level, profile))| {
+                let flow = self.flows.get(flow_id)?;
+                Some(LevelsPayload {
+                    flow: flow.name,
+                    seismometer: flow.seismometer,
+                    trigger_level: *trigger_level,
+                    reset_level: *reset_level,
+                    profile: profile.as_deref(),
+                })
+            })
+            .collect();
+        let payload = serde_json::to_string(&levels).unwrap_or_default();
+        let topic = self.thresholds_topic.clone();
+        self.mqtt_publish(None, &topic, &payload, MQTTQoS::AtLeastOnce, true)
+            .await
+    }
+
+    /// Carry out a command for a flow, or for every flow. (The session as
+    /// a whole is armed and disarmed by its switch.)
+    async fn handle_command(
+        &mut self,
+        flow_id: Option<usize>,
+        command: Command,
+    ) -> Result<(), ActionLoopError> {
+        let Some(flow_id) = flow_id else {
+            if command == Command::Test {
+                let mut flow_ids: Vec<usize> = self.flows.keys().copied().collect();
+                flow_ids.sort();
+                for flow_id in flow_ids {
+                    self.test_actions(flow_id).await?;
+                }
+            }
+            return Ok(());
+        };
+        let Some(flow) = self.flows.get(&flow_id) else {
+            return Ok(());
+        };
+        match command {
+            Command::Arm => {
+                if !self.disarmed.remove(&flow_id) {
+                    return Ok(());
+                }
+                log::info!("{}: armed", flow.name);
+                self.note_armed_states();
+                self.publish_flow_armed_state(flow_id).await?;
+                let owed = self.triggered.contains(&flow_id) && !self.announced.contains(&flow_id);
+                if owed && *self.armed.borrow() {
+                    self.announce_trigger(flow_id, true).await?;
+                }
+            }
+            Command::Disarm => {
+                if !self.disarmed.insert(flow_id) {
+                    return Ok(());
+                }
+                log::info!("{}: disarmed", flow.name);
+                self.note_armed_states();
+                self.publish_flow_armed_state(flow_id).await?;
+                if self.announced.contains(&flow_id) {
+                    self.announce_trigger(flow_id, false).await?;
+                }
+            }
+            Command::Test => self.test_actions(flow_id).await?,
+        }
+        Ok(())
+    }
+
+    /// Take a flow's trigger actions, then its reset actions, as though an
+    /// event had come and gone, whether or not it is armed. A flow whose
+    /// trigger actions are in effect is left alone.
+    async fn test_actions(&mut self, flow_id: usize) -> Result<(), ActionLoopError> {
+        let Some(flow) = self.flows.get_mut(&flow_id) else {
+            return Ok(());
+        };
+        if self.announced.contains(&flow_id) {
+            log::info!("{}: not tested, as it is triggered", flow.name);
+            return Ok(());
+        }
+        log::info!("{}: testing actions", flow.name);
+        // The details of the flow's last event aren't those of the test.
+        flow.trigger = None;
+        flow.event_id = Some(EventId::generate());
+        flow.summary = None;
+        flow.ground_motion = None;
+        self.take_trigger_actions(flow_id, true).await?;
+        self.take_trigger_actions(flow_id, false).await
+    }
+
+    /// Take the trigger or reset actions for a flow, unless its trigger
+    /// actions are taken too often.
+    async fn announce_trigger(
+        &mut self,
+        flow_id: usize,
+        triggered: bool,
+    ) -> Result<(), ActionLoopError> {
+        let Some(flow) = self.flows.get_mut(&flow_id) else {
+            return Ok(());
+        };
+        if triggered && !allow(&mut flow.trigger_limit) {
+            log::info!("{}: trigger notification suppressed", flow.name);
+            return Ok(());
+        }
+        self.take_trigger_actions(flow_id, triggered).await
+    }
+
+    /// Take the trigger or reset actions for a flow.
+    async fn take_trigger_actions(
+        &mut self,
+        flow_id: usize,
+        triggered: bool,
+    ) -> Result<(), ActionLoopError> {
+        let Some(flow) = self.flows.get(&flow_id) else {
+            return Ok(());
+        };
+        let actions = flow.actions;
+        let name = flow.name;
+        if triggered {
+            self.announced.insert(flow_id);
+            let (at, energy) = flow.trigger.unzip();
+            let placeholders = flow.event_placeholders(at, energy);
+            let extra = placeholders.expand_all(&actions.cmd_args);
+            let retry = RetryPolicy::new(actions);
+            let payload = flow.payload("triggered", &actions.mqtt_triggered_payload, &placeholders);
+            if let Some((telegram, config)) = self.telegram.as_ref().zip(actions.telegram.as_ref())
+            {
+                telegram.send_message(config, placeholders.expand(&config.triggered_message));
+            }
+            if let Some(snmp) = self.snmp.as_ref().filter(|_| actions.snmp) {
+                snmp.send(Trap::Triggered, &placeholders);
+            }
+            if let Some(config) = actions.cap.as_ref() {
+                cap::issue_alert(config, &placeholders, retry, self.failures.as_ref());
+            }
+            if let Some(config) = actions.webhook.as_ref() {
+                let body = flow.json_payload("triggered", &placeholders);
+                post_webhook(config, body, retry, self.failures.as_ref());
+            }
+            if let Some((audio, config)) = self.audio.as_mut().zip(actions.audio.as_ref()) {
+                if let Err(e) = audio.play(flow_id, config) {
+                    log::error!("action failed: {e}");
+                    if let Some(failures) = self.failures.as_ref() {
+                        failures.update(|status| status.failed_actions += 1);
+                    }
+                }
+            }
+            self.cmd_run(
+                actions,
+                &actions.trigger_cmd,
+                ["triggered", name],
+                &placeholders,
+                &extra,
+            );
+            self.mqtt_publish(
+                actions.mqtt_broker.as_deref(),
+                &actions.mqtt_topic,
+                &payload,
+                actions.mqtt_qos,
+                actions.mqtt_retain,
+            )
+            .await?;
+        } else {
+            self.announced.remove(&flow_id);
+            let placeholders =
+                flow.event_placeholders(flow.reset_at, flow.summary.map(|s| s.peak_energy));
+            let extra = placeholders.expand_all(&actions.cmd_args);
+            let retry = RetryPolicy::new(actions);
+            let payload = flow.payload("reset", &actions.mqtt_reset_payload, &placeholders);
+            let telegram = self.telegram.as_ref().zip(actions.telegram.as_ref());
+            if let Some((telegram, config)) = telegram {
+                if let Some(message) = config.reset_message.as_ref() {
+                    telegram.send_message(config, placeholders.expand(message));
+                }
+            }
+            if let Some(snmp) = self.snmp.as_ref().filter(|_| actions.snmp) {
+                snmp.send(Trap::Reset, &placeholders);
+            }
+            if let Some(config) = actions.webhook.as_ref().filter(|w| w.reset) {
+                let body = flow.json_payload("reset", &placeholders);
+                post_webhook(config, body, retry, self.failures.as_ref());
+            }
+            if let Some((audio, config)) = self.audio.as_mut().zip(actions.audio.as_ref()) {
+                if config.stop_on_reset {
+                    audio.stop(flow_id);
+                }
+            }
+            self.cmd_run(
+                actions,
+                &actions.reset_cmd,
+                ["reset", name],
+                &placeholders,
+                &extra,
+            );
+            self.mqtt_publish(
+                actions.mqtt_broker.as_deref(),
+                &actions.mqtt_topic,
+                &payload,
+                actions.mqtt_qos,
+                actions.mqtt_retain,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Log the data time and energy of a flow's trigger, and the picked
+    /// onset time of the phase that set it off.
+    fn report_trigger(&self, flow_id: usize, at: f64, energy: f64, onset: Option<f64>) {
+        let Some(flow) = self.flows.get(&flow_id) else {
+            return;
+        };
+        let event_id = flow.event_id.map(|id| id.to_string()).unwrap_or_default();
+        log::info!(
+            "{}: triggered at {:.2}, energy {}, event {event_id}",
+            flow.name,
+            at,
+            energy
+        );
+        if let Some(onset) = onset {
+            log::info!("{}: onset picked at {:.2}", flow.name, onset);
+        }
+    }
+
+    /// Log the summary of a flow's event.
+    fn report_summary(&self, flow_id: usize, summary: &EventSummary) {
+        let Some(flow) = self.flows.get(&flow_id) else {
+            return;
+        };
+        match flow.event_id {
+            Some(event_id) => log::info!("{}: {summary}, event {event_id}", flow.name),
+            None => log::info!("{}: {summary}", flow.name),
+        }
+    }
+
+    /// Note a change in a flow's trigger state at a data time, and in the
+    /// state of any coincidence triggers it is a member of, which change
+    /// at the data time that it does. Its tiers are reset with it.
+    async fn handle_trigger(
+        &mut self,
+        flow_id: usize,
+        triggered: bool,
+        at: f64,
+    ) -> Result<(), ActionLoopError> {
+        self.set_triggered(flow_id, triggered).await?;
+        if !triggered {
+            self.escalate(flow_id, None).await?;
+        }
+        let changes: Vec<(usize, bool)> = self
+            .coincidences
+            .iter_mut()
+            .filter_map(|(id, coincidence)| {
+                coincidence
+                    .update(flow_id, triggered, at)
+                    .map(|state| (*id, state))
+            })
+            .collect();
+        let (trigger, reset_at) = self
+            .flows
+            .get(&flow_id)
+            .map_or((None, None), |flow| (flow.trigger, flow.reset_at));
+        for (id, state) in changes {
+            if let Some(coincidence) = self.flows.get_mut(&id) {
+                if state {
+                    coincidence.trigger = trigger;
+                    coincidence.event_id = Some(EventId::generate());
+                } else {
+                    coincidence.reset_at = reset_at;
+                }
+            }
+            self.set_triggered(id, state).await?;
+        }
+        Ok(())
+    }
+
+    /// Trigger the tiers of a flow up to a severity, with the data time
+    /// and energy at which its event escalated, or reset them all. The
+    /// tiers share the summary of the flow's event.
+    async fn escalate(
+        &mut self,
+        flow_id: usize,
+        to: Option<(usize, f64, f64)>,
+    ) -> Result<(), ActionLoopError> {
+        let Some(parent) = self.flows.get(&flow_id) else {
+            return Ok(());
+        };
+        let (reset_at, summary, ground_motion) =
+            (parent.reset_at, parent.summary, parent.ground_motion);
+        let event_id = parent.event_id;
+        let tiers: Vec<(usize, usize)> = self
+            .tiers
+            .iter()
+            .filter(|&&(_, parent_id, _)| parent_id == flow_id)
+            .map(|&(id, _, severity)| (id, severity))
+            .collect();
+        for (id, severity) in tiers {
+            let Some(tier) = self.flows.get_mut(&id) else {
+                continue;
+            };
+            match to {
+                Some((reached, at, energy)) if severity <= reached => {
+                    if self.triggered.contains(&id) {
+                        continue;
+                    }
+                    tier.trigger = Some((at, energy));
+                    tier.event_id = event_id;
+                    log::info!("{}: escalated at {at:.2}, energy {energy}", tier.name);
+                    self.set_triggered(id, true).await?;
+                }
+                Some(_) => (),
+                None => {
+                    tier.reset_at = reset_at;
+                    tier.summary = summary;
+                    tier.ground_motion = ground_motion;
+                    self.set_triggered(id, false).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Track a flow's trigger state. Nothing is done for a trigger while
+    /// the session (or the flow) is disarmed, and reset actions are only
+    /// owed if the flow's trigger actions were taken.
+    async fn set_triggered(
+        &mut self,
+        flow_id: usize,
+        triggered: bool,
+    ) -> Result<(), ActionLoopError> {
+        if triggered {
+            self.triggered.insert(flow_id);
+            if *self.armed.borrow() && !self.disarmed.contains(&flow_id) {
+                self.announce_trigger(flow_id, true).await?;
+            }
+        } else {
+            self.triggered.remove(&flow_id);
+            if self.announced.contains(&flow_id) {
+                self.announce_trigger(flow_id, false).await?;
+            }
         }
         Ok(())
     }
 
     /// Handle an event that has been noted by a particular seismometer.
-    async fn handle_seismometer_event(&mut self, msg: TriggerMessage) -> Result<(), ActionLoopError> {
+    async fn handle_seismometer_event(
+        &mut self,
+        msg: TriggerMessage,
+    ) -> Result<(), ActionLoopError> {
         //
         // Look up the reporting seismometer and see if there are any actions
         // configured for its events.
         //
-        if let Some(flow) = self.flows.get(&msg.source_id) {
+        self.note_flow_event(msg.source_id, &msg.event);
+        if self.stats_config.is_some() {
+            self.stats
+                .entry(msg.source_id)
+                .or_default()
+                .note(epoch_now(), &msg.event);
+        }
+        match msg.event {
+            Event::Available => {
+                self.alive.insert(msg.source_id, true);
+            }
+            Event::Unavailable => {
+                self.alive.insert(msg.source_id, false);
+            }
+            _ => (),
+        }
+        if let Some(flow) = self.flows.get_mut(&msg.source_id) {
             let actions = flow.actions;
             let name = flow.name;
+            match msg.event {
+                Event::Triggered { at, energy, .. } => {
+                    flow.trigger = Some((at, energy));
+                    flow.event_id = Some(EventId::generate());
+                }
+                Event::Reset {
+                    at,
+                    summary,
+                    ground_motion,
+                } => {
+                    flow.reset_at = Some(at);
+                    flow.summary = summary;
+                    flow.ground_motion = ground_motion;
+                }
+                // A stuck trigger's event is no earthquake to summarize.
+                Event::StuckReset { at } => {
+                    flow.reset_at = Some(at);
+                    flow.summary = None;
+                    flow.ground_motion = None;
+                }
+                _ => (),
+            }
+            if let Some(influx) = self.influx.as_ref() {
+                flow.record(influx, &msg.event);
+            }
+            if let Some(event_log) = self.event_log.as_ref() {
+                flow.write_to_log(event_log, &msg.event);
+            }
+            if let Some(live) = self.live.as_ref() {
+                flow.stream(live, &msg.event);
+            }
+            if let Some(syslog) = self.syslog.as_ref().filter(|_| actions.syslog) {
+                flow.log_to_syslog(syslog, &msg.event);
+            }
+            let placeholders = flow.placeholders(None, None);
+            let extra = placeholders.expand_all(&actions.cmd_args);
             match msg.event {
                 //
                 // A seismometer appears to have come online.
                 //
                 Event::Available => {
-                    tokio::try_join!(
-                        self.mqtt_publish(
-                            &actions.mqtt_available_topic,
-                            &actions.mqtt_available_payload,
-                        ),
-                        cmd_run(&actions.available_cmd, "available", name)
-                    )?;
+                    let payload =
+                        flow.payload("available", &actions.mqtt_available_payload, &placeholders);
+                    if let Some(snmp) = self.snmp.as_ref().filter(|_| actions.snmp) {
+                        snmp.send(Trap::Available, &placeholders);
+                    }
+                    self.cmd_run(
+                        actions,
+                        &actions.available_cmd,
+                        ["available", name],
+                        &placeholders,
+                        &extra,
+                    );
+                    self.mqtt_publish(
+                        actions.mqtt_broker.as_deref(),
+                        &actions.mqtt_available_topic,
+                        &payload,
+                        actions.mqtt_available_qos,
+                        actions.mqtt_available_retain,
+                    )
+                    .await?;
+                    self.publish_availability().await?;
                 }
 
                 //
                 // A seismometer is reporting a running status.
                 //
-                Event::Status {
-                    dc: _dc,
-                    energy: _energy,
-                } => {}
+                Event::Status { dc, energy } => {
+                    log::debug!("{name}: dc {dc}, energy {energy}");
+                    let mut payload = serde_json::json!({ "dc": dc, "energy": energy });
+                    // Flows on a threshold schedule report the profile in force.
+                    if let Some((.., Some(profile))) = self.levels.get(&msg.source_id) {
+                        payload["profile"] = profile.as_str().into();
+                    }
+                    let payload = payload.to_string();
+                    self.mqtt_publish(
+                        actions.mqtt_broker.as_deref(),
+                        &actions.mqtt_status_topic,
+                        &payload,
+                        actions.mqtt_status_qos,
+                        false,
+                    )
+                    .await?;
+                }
+
+                //
+                // A seismometer is reporting an earthquake. Nothing is done
+                // while the session is disarmed.
+                //
+                Event::Triggered { at, energy, onset } => {
+                    self.report_trigger(msg.source_id, at, energy, onset);
+                    self.handle_trigger(msg.source_id, true, at).await?;
+                }
 
                 //
-                // A seismometer is reporting an earthquake.
+                // A seismometer's earthquake has grown severe enough to
+                // take the actions of more of its tiers.
                 //
-                Event::Triggered => {
-                    tokio::try_join!(
-                        self.mqtt_publish(
-                            &actions.mqtt_topic,
-                            &actions.mqtt_triggered_payload,
-                        ),
-                        cmd_run(&actions.trigger_cmd, "triggered", name)
-                    )?;
+                Event::Escalated {
+                    severity,
+                    at,
+                    energy,
+                } => {
+                    self.escalate(msg.source_id, Some((severity, at, energy)))
+                        .await?;
                 }
 
                 //
                 // A seismometer that was previously reporting an earthquake
-                // is now no longer reporting one.
+                // is now no longer reporting one. Its reset actions are only
+                // owed if its trigger actions were taken.
                 //
-                Event::Reset => {
-                    tokio::try_join!(
-                        self.mqtt_publish(
-                            &actions.mqtt_topic,
-                            &actions.mqtt_reset_payload,
-                        ),
-                        cmd_run(&actions.reset_cmd, "reset", name)
-                    )?;
+                Event::Reset {
+                    at,
+                    summary,
+                    ground_motion,
+                } => {
+                    if let Some(summary) = summary {
+                        self.report_summary(msg.source_id, &summary);
+                    }
+                    self.handle_trigger(msg.source_id, false, at).await?;
+                    if let Some(ground_motion) = ground_motion {
+                        self.report_ground_motion(msg.source_id, &ground_motion)
+                            .await?;
+                    }
+                }
+
+                //
+                // A seismometer's trigger was stuck and has been forcibly
+                // reset. It is reset as usual, and warned about.
+                //
+                Event::StuckReset { at } => {
+                    self.handle_trigger(msg.source_id, false, at).await?;
+                    self.announce_warning(msg.source_id, &Warning::StuckReset)
+                        .await?;
                 }
 
                 //
                 // A seismometer is reporting that it has come online.
                 //
                 Event::Unavailable => {
-                    tokio::try_join!(
-                        self.mqtt_publish(
-                            &actions.mqtt_available_topic,
-                            &actions.mqtt_unavailable_payload,
-                        ),
-                        cmd_run(&actions.unavailable_cmd, "unavailable", name)
-                    )?;
+                    let payload = flow.payload(
+                        "unavailable",
+                        &actions.mqtt_unavailable_payload,
+                        &placeholders,
+                    );
+                    if let Some(snmp) = self.snmp.as_ref().filter(|_| actions.snmp) {
+                        snmp.send(Trap::Unavailable, &placeholders);
+                    }
+                    self.cmd_run(
+                        actions,
+                        &actions.unavailable_cmd,
+                        ["unavailable", name],
+                        &placeholders,
+                        &extra,
+                    );
+                    self.mqtt_publish(
+                        actions.mqtt_broker.as_deref(),
+                        &actions.mqtt_available_topic,
+                        &payload,
+                        actions.mqtt_available_qos,
+                        actions.mqtt_available_retain,
+                    )
+                    .await?;
+                    self.publish_availability().await?;
+                }
+
+                //
+                // Something is amiss with the seismometer or its processing.
+                //
+                Event::Warning(warning) => {
+                    if allow(&mut flow.warning_limit) {
+                        self.announce_warning(msg.source_id, &warning).await?;
+                    } else {
+                        log::info!("{name}: warning notification suppressed: {warning}");
+                    }
+                }
+
+                //
+                // A waveform capture has been saved, which is sent on to
+                // Telegram if so configured (and the session is armed).
+                //
+                Event::Captured { path } => {
+                    log::info!("{name}: waveform captured to {}", path.display());
+                    let caption = match flow.summary {
+                        Some(summary) => format!("{name}: {summary}"),
+                        None => name.to_owned(),
+                    };
+                    let telegram = self.telegram.as_ref().zip(actions.telegram.as_ref());
+                    if let Some((telegram, config)) = telegram {
+                        if config.send_waveform && *self.armed.borrow() {
+                            telegram.send_waveform(config, path, caption);
+                        }
+                    }
+                }
+
+                //
+                // The seismometer's clock has strayed from the host's.
+                //
+                Event::ClockDrift { offset_s } => {
+                    let offset = format!("{offset_s:.3}");
+                    self.cmd_run(
+                        actions,
+                        &actions.clock_drift_cmd,
+                        ["clock_drift", name, &offset],
+                        &placeholders,
+                        &extra,
+                    );
+                    self.mqtt_publish(
+                        actions.mqtt_broker.as_deref(),
+                        &actions.mqtt_clock_drift_topic,
+                        &offset,
+                        actions.mqtt_clock_drift_qos,
+                        false,
+                    )
+                    .await?;
+                }
+
+                //
+                // A flow's trigger levels have been announced, or adjusted.
+                //
+                Event::Levels {
+                    trigger_level,
+                    reset_level,
+                    profile,
+                } => {
+                    let previous = self
+                        .levels
+                        .insert(msg.source_id, (trigger_level, reset_level, profile.clone()));
+                    let previous = previous.and_then(|(.., profile)| profile);
+                    if previous != profile {
+                        let profile = profile.as_deref().unwrap_or("own");
+                        log::info!("{name}: {profile} trigger levels in force, trigger level {trigger_level}, reset level {reset_level}");
+                    }
+                    self.publish_levels().await?;
                 }
             }
         }
         Ok(())
     }
 
-    /// Publish a payload over MQTT, but only if so configured.
-    async fn mqtt_publish(&mut self, topic: &Option<String>, payload: &String) -> Result<(), ActionLoopError> {
-        let config = self.mqtt.as_mut().zip(topic.as_ref());
-        if let Some((client, topic)) = config {
+    /// Log and take the actions for the peak ground motion of an event.
+    async fn report_ground_motion(
+        &mut self,
+        flow_id: usize,
+        ground_motion: &GroundMotion,
+    ) -> Result<(), ActionLoopError> {
+        let Some(flow) = self.flows.get(&flow_id) else {
+            return Ok(());
+        };
+        let actions = flow.actions;
+        let name = flow.name;
+        log::info!("{name}: event over, {ground_motion}");
+        let payload = serde_json::to_string(ground_motion).unwrap_or_default();
+        let pga = ground_motion.pga.to_string();
+        let pgv = ground_motion.pgv.to_string();
+        let pgd = ground_motion.pgd.to_string();
+        let mmi = format!("{:.1}", ground_motion.mmi);
+        let placeholders =
+            flow.event_placeholders(flow.reset_at, flow.summary.map(|s| s.peak_energy));
+        let extra = placeholders.expand_all(&actions.cmd_args);
+        self.cmd_run(
+            actions,
+            &actions.ground_motion_cmd,
+            [
+                "ground_motion",
+                name,
+                &pga,
+                &pgv,
+                &pgd,
+                &mmi,
+                ground_motion.intensity,
+            ],
+            &placeholders,
+            &extra,
+        );
+        self.mqtt_publish(
+            actions.mqtt_broker.as_deref(),
+            &actions.mqtt_ground_motion_topic,
+            &payload,
+            actions.mqtt_ground_motion_qos,
+            false,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Take the warning actions for a flow.
+    async fn announce_warning(
+        &mut self,
+        flow_id: usize,
+        warning: &Warning,
+    ) -> Result<(), ActionLoopError> {
+        let Some(flow) = self.flows.get(&flow_id) else {
+            return Ok(());
+        };
+        let actions = flow.actions;
+        let placeholders = flow.placeholders(None, None);
+        let extra = placeholders.expand_all(&actions.cmd_args);
+        let message = warning.to_string();
+        self.cmd_run(
+            actions,
+            &actions.warning_cmd,
+            ["warning", flow.name, &message],
+            &placeholders,
+            &extra,
+        );
+        self.mqtt_publish(
+            actions.mqtt_broker.as_deref(),
+            &actions.mqtt_warning_topic,
+            &message,
+            actions.mqtt_warning_qos,
+            false,
+        )
+        .await
+    }
+
+    /// When the next summary of suppressed notifications is due, if any
+    /// are owed.
+    fn summaries_due(&self) -> Option<Instant> {
+        self.flows
+            .values()
+            .flat_map(|flow| [flow.trigger_limit.as_ref(), flow.warning_limit.as_ref()])
+            .flatten()
+            .filter_map(RateLimiter::summary_due)
+            .min()
+    }
+
+    /// Warn of the notifications suppressed by each flow, where a summary
+    /// of them is due. The warnings themselves are never suppressed.
+    async fn announce_summaries(&mut self) -> Result<(), ActionLoopError> {
+        let now = Instant::now();
+        let mut summaries = Vec::new();
+        for (&flow_id, flow) in self.flows.iter_mut() {
+            let limits = [
+                ("trigger", &mut flow.trigger_limit),
+                ("warning", &mut flow.warning_limit),
+            ];
+            for (kind, limit) in limits {
+                let Some(limit) = limit.as_mut() else {
+                    continue;
+                };
+                if let Some(count) = limit.take_summary(now) {
+                    let seconds = limit.interval().as_secs_f32();
+                    log::info!("{}: {count} {kind} notifications suppressed", flow.name);
+                    summaries.push((
+                        flow_id,
+                        Warning::Suppressed {
+                            count,
+                            kind,
+                            seconds,
+                        },
+                    ));
+                }
+            }
+        }
+        summaries.sort_by_key(|(flow_id, _)| *flow_id);
+        for (flow_id, warning) in summaries {
+            self.announce_warning(flow_id, &warning).await?;
+        }
+        Ok(())
+    }
+
+    /// Publish the statistics of every flow which processes a
+    /// seismometer's data, as they are now, to the topic and InfluxDB
+    /// output (if any) and on the status board.
+    async fn announce_stats(&mut self) -> Result<(), ActionLoopError> {
+        let Some(config) = self.stats_config else {
+            return Ok(());
+        };
+        self.stats_due = Some(Instant::now() + Duration::from_secs_f32(config.interval_s.max(1.0)));
+        let now = epoch_now();
+        let mut payload = BTreeMap::new();
+        for (name, seismometer, flow_id) in self.sensor_flows() {
+            let recent = self
+                .stats
+                .get(&flow_id)
+                .map(|stats| stats.recent(now))
+                .unwrap_or_default();
+            let flow = &self.flows[&flow_id];
+            if let Some(influx) = self.influx.as_ref() {
+                let tags = PointTags {
+                    flow: name,
+                    seismometer: Some(seismometer),
+                    channel: flow.channel,
+                };
+                influx.stats(&tags, &recent, now);
+            }
+            if let Some(board) = self.flow_states.as_ref() {
+                board.update(|status| {
+                    status.flows.entry(name.to_owned()).or_default().stats = Some(recent)
+                });
+            }
+            payload.insert(
+                name,
+                StatsPayload {
+                    seismometer,
+                    channel: flow.channel,
+                    hour: recent.hour,
+                    day: recent.day,
+                },
+            );
+        }
+        let payload = serde_json::to_string(&payload).unwrap_or_default();
+        self.mqtt_publish(
+            None,
+            &config.mqtt_topic,
+            &payload,
+            MQTTQoS::AtLeastOnce,
+            true,
+        )
+        .await
+    }
+
+    /// Publish a payload over MQTT, but only if so configured, at a
+    /// quality of service and asking the broker to retain it if need be.
+    /// While the broker is unreachable, it is held in the outbox (if
+    /// there is one); see post_or_hold. It goes to the named broker if
+    /// one is given, or else the main one.
+    async fn mqtt_publish(
+        &mut self,
+        broker: Option<&str>,
+        topic: &Option<String>,
+        payload: &String,
+        qos: MQTTQoS,
+        retain: bool,
+    ) -> Result<(), ActionLoopError> {
+        let Some(topic) = topic.as_ref() else {
+            return Ok(());
+        };
+        let post = Post {
+            topic: topic.clone(),
+            payload: payload.as_bytes().to_vec(),
+            qos: match qos {
+                MQTTQoS::AtMostOnce => QoS::AtMostOnce,
+                MQTTQoS::AtLeastOnce => QoS::AtLeastOnce,
+                MQTTQoS::ExactlyOnce => QoS::ExactlyOnce,
+            },
+            retain,
+        };
+        let failures = self.failures.as_ref();
+        match broker {
+            None => {
+                if let Some(client) = self.mqtt.as_ref() {
+                    post_or_hold(client, self.outbox.as_mut(), post, failures).await?
+                }
+            }
+            Some(name) => self.brokers.post(name, post, failures).await?,
+        }
+        Ok(())
+    }
+
+    /// Make the posts held while the broker was unreachable.
+    async fn flush_outbox(&mut self) -> Result<(), ActionLoopError> {
+        let Some((client, outbox)) = self.mqtt.as_ref().zip(self.outbox.as_mut()) else {
+            return Ok(());
+        };
+        while let Some(post) = outbox.pop() {
             client
-                .publish(
-                    topic.as_str(),
-                    rumqttc::QoS::AtLeastOnce,
-                    false,
-                    payload.as_bytes(),
-                )
+                .publish(post.topic, post.qos, post.retain, post.payload)
                 .await?;
         }
         Ok(())
     }
 
+    /// Tell a named broker which is reachable again that the daemon is
+    /// online, and make the posts held while it wasn't.
+    async fn rejoin_broker(&mut self, index: usize) -> Result<(), ActionLoopError> {
+        Ok(self.brokers.rejoin(index).await?)
+    }
+
+    /// Start an external executable for an action, if so configured, in
+    /// the background, subject to the actions' timeout and retries.
+    fn cmd_run<const N: usize>(
+        &self,
+        actions: &ActionsConfig,
+        cmd: &Option<PathBuf>,
+        args: [&str; N],
+        placeholders: &Placeholders<'_>,
+        extra: &[String],
+    ) {
+        let _ = self.commands.run(actions, cmd, args, placeholders, extra);
+    }
+}
+
+/// Whether an action may be taken now under a rate limit, if any.
+fn allow(limit: &mut Option<RateLimiter>) -> bool {
+    limit
+        .as_mut()
+        .is_none_or(|limit| limit.allow(Instant::now()))
+}
+
+/// Wait for the next command, if they are listened for.
+async fn next_command(control: &mut Option<CommandReceiver>) -> Option<(Option<usize>, Command)> {
+    match control.as_mut() {
+        Some(control) => control.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Wait until an instant, if any, or forever.
+async fn sleep_until(at: Option<Instant>) {
+    match at {
+        Some(at) => tokio::time::sleep_until(at).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// The host's time, in seconds since the epoch.
+fn epoch_now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |d| d.as_secs_f64())
+}
+
+/// Wait until the broker is reachable again, if posts are held while it
+/// isn't.
+async fn reconnected(outbox: &mut Option<Outbox>) {
+    match outbox.as_mut() {
+        Some(outbox) => outbox.reconnected().await,
+        None => std::future::pending().await,
+    }
 }
 
-/// Execute an external executable, if so configured.
-async fn cmd_run(cmd: &Option<PathBuf>, arg1: &str, arg2: &str) -> Result<(), ActionLoopError> {
-    if let Some(path) = cmd.as_ref() {
-        let _ = Command::new(path)
-            .args(&[ arg1, arg2 ])
-            .status().await?;
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::session::armed::command_channel;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// An executable which notes down each event it is run for, with the
+    /// flow and the event's data time, in a log of its own.
+    struct Notes {
+        script: PathBuf,
+        log: PathBuf,
+    }
+
+    impl Notes {
+        fn new(test: &str) -> Self {
+            let name = format!("rs-udp-action-loop-{test}-{}", std::process::id());
+            let script = std::env::temp_dir().join(name);
+            let log = script.with_extension("log");
+            let body = "#!/bin/sh\necho \"$1 $2 $RS_TIMESTAMP\" >> \"$0.log\"\n";
+            std::fs::write(&script, body).expect("writes");
+            let mode = std::fs::Permissions::from_mode(0o755);
+            std::fs::set_permissions(&script, mode).expect("makes executable");
+            Self { script, log }
+        }
+
+        /// Actions which run the script on triggers and resets, and take
+        /// some others besides.
+        fn actions(&self, others: serde_json::Value) -> ActionsConfig {
+            let mut actions = serde_json::json!({
+                "trigger_cmd": self.script,
+                "reset_cmd": self.script,
+            });
+            if let (Some(actions), Some(others)) = (actions.as_object_mut(), others.as_object()) {
+                actions.extend(others.clone());
+            }
+            serde_json::from_value(actions).expect("valid actions")
+        }
+
+        /// Wait for at least some number of notes, then give every one.
+        async fn read(&self, count: usize) -> Vec<String> {
+            let read = || -> Vec<String> {
+                let notes = std::fs::read_to_string(&self.log).unwrap_or_default();
+                notes.lines().map(str::to_owned).collect()
+            };
+            until(|| read().len() >= count).await;
+            read()
+        }
+    }
+
+    impl Drop for Notes {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.script);
+            let _ = std::fs::remove_file(&self.log);
+        }
+    }
+
+    /// Wait for something to come true, for a while.
+    async fn until(done: impl Fn() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !done() {
+            assert!(Instant::now() < deadline, "timed out");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    fn triggered(source_id: usize, at: f64) -> TriggerMessage {
+        let event = Event::Triggered {
+            at,
+            energy: 1.0,
+            onset: None,
+        };
+        TriggerMessage { source_id, event }
+    }
+
+    fn reset(source_id: usize, at: f64) -> TriggerMessage {
+        let event = Event::Reset {
+            at,
+            summary: None,
+            ground_motion: None,
+        };
+        TriggerMessage { source_id, event }
+    }
+
+    /// Wait for the loop to take every event sent to it so far, which it
+    /// is done acting on before it looks for anything else.
+    async fn taken(events: &OutChannel) {
+        while events.capacity() < events.max_capacity() {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    /// The events noted for a flow, in order, with their data times.
+    fn of(notes: &[String], flow: &str) -> Vec<String> {
+        notes
+            .iter()
+            .filter_map(|note| {
+                let (event, rest) = note.split_once(' ')?;
+                let (name, at) = rest.split_once(' ')?;
+                (name == flow).then(|| format!("{event} {at}"))
+            })
+            .collect()
+    }
+
+    /// A client whose every post fails, as its connection is gone.
+    #[cfg(feature = "mqtt")]
+    fn failing_client() -> AsyncClient {
+        let options = rumqttc::MqttOptions::new("test", "localhost", 1883);
+        let (client, _event_loop) = AsyncClient::new(options, 10);
+        client
+    }
+
+    #[cfg(feature = "mqtt")]
+    #[tokio::test]
+    async fn carries_on_after_failed_actions() {
+        let notes = Notes::new("carries-on");
+        let actions = notes.actions(serde_json::json!({ "mqtt_topic": "quakes" }));
+        let (events, chan) = message_channel(4);
+        let switch = ArmedSwitch::new(true);
+        let board = StatusBoard::new();
+        let mut action_loop = ActionLoop::new(chan, Some(failing_client()), &switch, None);
+        action_loop.report_failures(board.clone());
+        action_loop.set_error_policy(ActionErrorPolicy::Continue);
+        action_loop.add_flow(0, "f", Some("s"), Some("EHZ"), &actions);
+        events.send(triggered(0, 1.0)).await.expect("sends");
+        events.send(reset(0, 2.0)).await.expect("sends");
+        drop(events);
+
+        // Both events' posts fail, and are counted, but the reset is
+        // still acted on.
+        action_loop.run().await.expect("carries on");
+        assert_eq!(board.snapshot().action_errors, 2);
+        assert_eq!(notes.read(2).await, ["triggered f 1.000", "reset f 2.000"]);
+    }
+
+    #[cfg(feature = "mqtt")]
+    #[tokio::test]
+    async fn stops_after_failed_actions_if_told_to() {
+        let notes = Notes::new("stops");
+        let actions = notes.actions(serde_json::json!({ "mqtt_topic": "quakes" }));
+        let (events, chan) = message_channel(4);
+        let switch = ArmedSwitch::new(true);
+        let board = StatusBoard::new();
+        let mut action_loop = ActionLoop::new(chan, Some(failing_client()), &switch, None);
+        action_loop.report_failures(board.clone());
+        action_loop.set_error_policy(ActionErrorPolicy::Stop);
+        action_loop.add_flow(0, "f", Some("s"), Some("EHZ"), &actions);
+        events.send(triggered(0, 1.0)).await.expect("sends");
+        events.send(reset(0, 2.0)).await.expect("sends");
+
+        let stopped = action_loop.run().await;
+        assert!(matches!(stopped, Err(ActionLoopError::MQTTClientError(_))));
+        assert_eq!(board.snapshot().action_errors, 1);
+        assert_eq!(notes.read(1).await, ["triggered f 1.000"]);
+    }
+
+    #[tokio::test]
+    async fn tracks_triggers_while_disarmed() {
+        let notes = Notes::new("disarmed");
+        let actions = notes.actions(serde_json::json!({}));
+        let (events, chan) = message_channel(4);
+        let (commands, control) = command_channel();
+        let switch = ArmedSwitch::new(false);
+        let board = StatusBoard::new();
+        let mut action_loop = ActionLoop::new(chan, None, &switch, None);
+        action_loop.take_commands(control);
+        action_loop.report_flow_states(board.clone());
+        action_loop.add_flow(0, "f", Some("s"), Some("EHZ"), &actions);
+        let armed = || board.snapshot().flows["f"].armed;
+        let notes = &notes;
+        let drive = async move {
+            // Nothing is done for an event while the session is disarmed,
+            // but a trigger still standing as it is armed is acted on.
+            events.send(triggered(0, 1.0)).await.expect("sends");
+            events.send(reset(0, 2.0)).await.expect("sends");
+            events.send(triggered(0, 3.0)).await.expect("sends");
+            taken(&events).await;
+            switch.set(true);
+            notes.read(1).await;
+            events.send(reset(0, 4.0)).await.expect("sends");
+            taken(&events).await;
+
+            // Likewise while the flow is disarmed on its own.
+            commands
+                .send((Some(0), Command::Disarm))
+                .await
+                .expect("sends");
+            until(|| !armed()).await;
+            events.send(triggered(0, 5.0)).await.expect("sends");
+            events.send(reset(0, 6.0)).await.expect("sends");
+            events.send(triggered(0, 7.0)).await.expect("sends");
+            taken(&events).await;
+            commands.send((Some(0), Command::Arm)).await.expect("sends");
+            notes.read(3).await;
+            events.send(reset(0, 8.0)).await.expect("sends");
+        };
+        let (result, ()) = tokio::join!(action_loop.run(), drive);
+        result.expect("runs");
+        let expected = [
+            "triggered f 3.000",
+            "reset f 4.000",
+            "triggered f 7.000",
+            "reset f 8.000",
+        ];
+        assert_eq!(notes.read(4).await, expected);
+    }
+
+    #[tokio::test]
+    async fn tests_actions_once() {
+        let notes = Notes::new("tests");
+        let actions = notes.actions(serde_json::json!({}));
+        let (events, chan) = message_channel(4);
+        let (commands, control) = command_channel();
+        let switch = ArmedSwitch::new(false);
+        let mut action_loop = ActionLoop::new(chan, None, &switch, None);
+        action_loop.take_commands(control);
+        action_loop.add_flow(0, "f", Some("s"), Some("EHZ"), &actions);
+        action_loop.add_flow(1, "g", Some("s"), Some("EHN"), &actions);
+        let notes = &notes;
+        let drive = async move {
+            // Tests are run whether or not anything is armed: first of
+            // one flow, then of every flow.
+            commands
+                .send((Some(0), Command::Test))
+                .await
+                .expect("sends");
+            notes.read(2).await;
+            commands.send((None, Command::Test)).await.expect("sends");
+            notes.read(6).await;
+            drop(events);
+        };
+        let (result, ()) = tokio::join!(action_loop.run(), drive);
+        result.expect("runs");
+        let notes = notes.read(6).await;
+        let events_of = |flow| -> Vec<String> {
+            of(&notes, flow)
+                .iter()
+                .map(|note| note.split(' ').next().unwrap_or_default().to_owned())
+                .collect()
+        };
+        assert_eq!(events_of("f"), ["triggered", "reset", "triggered", "reset"]);
+        assert_eq!(events_of("g"), ["triggered", "reset"]);
+    }
+
+    #[tokio::test]
+    async fn escalates_through_tiers() {
+        let notes = Notes::new("tiers");
+        let actions = notes.actions(serde_json::json!({}));
+        let (events, chan) = message_channel(16);
+        let switch = ArmedSwitch::new(true);
+        let mut action_loop = ActionLoop::new(chan, None, &switch, None);
+        action_loop.add_flow(0, "f", Some("s"), Some("EHZ"), &actions);
+        action_loop.add_tier(1, 0, 1, "f-strong", &actions);
+        action_loop.add_tier(2, 0, 2, "f-severe", &actions);
+        let escalated = |severity, at| TriggerMessage {
+            source_id: 0,
+            event: Event::Escalated {
+                severity,
+                at,
+                energy: 10.0,
+            },
+        };
+
+        // Escalating to a tier triggers every tier up to it, and the
+        // flow's reset resets them all.
+        events.send(triggered(0, 1.0)).await.expect("sends");
+        events.send(escalated(2, 2.0)).await.expect("sends");
+        events.send(reset(0, 3.0)).await.expect("sends");
+        events.send(triggered(0, 4.0)).await.expect("sends");
+        events.send(escalated(1, 5.0)).await.expect("sends");
+        events.send(reset(0, 6.0)).await.expect("sends");
+        drop(events);
+        action_loop.run().await.expect("runs");
+
+        let notes = notes.read(10).await;
+        let flow = [
+            "triggered 1.000",
+            "reset 3.000",
+            "triggered 4.000",
+            "reset 6.000",
+        ];
+        assert_eq!(of(&notes, "f"), flow);
+        let strong = [
+            "triggered 2.000",
+            "reset 3.000",
+            "triggered 5.000",
+            "reset 6.000",
+        ];
+        assert_eq!(of(&notes, "f-strong"), strong);
+        assert_eq!(of(&notes, "f-severe"), ["triggered 2.000", "reset 3.000"]);
+    }
+
+    #[tokio::test]
+    async fn takes_global_actions_while_any_flow_is_triggered() {
+        let notes = Notes::new("global");
+        let actions = notes.actions(serde_json::json!({}));
+        let (events, chan) = message_channel(16);
+        let switch = ArmedSwitch::new(true);
+        let mut action_loop = ActionLoop::new(chan, None, &switch, None);
+        // As the daemon sets them up: every enabled flow is a member, but
+        // their tiers aren't, and disabled flows (as 4 would be) are left
+        // out altogether.
+        action_loop.add_flow(0, "a", Some("s1"), Some("EHZ"), &actions);
+        action_loop.add_tier(1, 0, 1, "a-strong", &actions);
+        action_loop.add_flow(2, "b", Some("s2"), Some("EHZ"), &actions);
+        let mut any_flow = Coincidence::of_any();
+        any_flow.add_member(0, 0);
+        any_flow.add_member(2, 2);
+        action_loop.add_coincidence(3, "global", None, &actions, any_flow);
+        let escalated = TriggerMessage {
+            source_id: 0,
+            event: Event::Escalated {
+                severity: 1,
+                at: 2.0,
+                energy: 10.0,
+            },
+        };
+
+        // The global actions are taken once, on the first flow's trigger,
+        // and reset with the last flow's reset.
+        events.send(triggered(0, 1.0)).await.expect("sends");
+        events.send(escalated).await.expect("sends");
+        events.send(triggered(2, 3.0)).await.expect("sends");
+        events.send(reset(0, 4.0)).await.expect("sends");
+        events.send(triggered(4, 5.0)).await.expect("sends");
+        events.send(reset(2, 6.0)).await.expect("sends");
+        drop(events);
+        action_loop.run().await.expect("runs");
+
+        let notes = notes.read(8).await;
+        assert_eq!(of(&notes, "a"), ["triggered 1.000", "reset 4.000"]);
+        assert_eq!(of(&notes, "a-strong"), ["triggered 2.000", "reset 4.000"]);
+        assert_eq!(of(&notes, "b"), ["triggered 3.000", "reset 6.000"]);
+        assert_eq!(of(&notes, "global"), ["triggered 1.000", "reset 6.000"]);
     }
-    Ok(())
 }
@@ -1,9 +1,29 @@
 use super::action_loop::{ActionLoop, ActionLoopError};
+use super::api::ApiServer;
+use super::armed::ArmedControl;
+use super::control::ControlServer;
+use super::event_log::EventLogWriter;
+use super::influx::InfluxWriter;
+use super::monitor::TerminalMonitor;
+use super::outbox::MqttConnection;
+use super::presence::DaemonPresence;
 use super::instrument_loop::{InstrumentLoop, LoopError};
+use super::mqtt::{is_connected, is_disconnecting, ClientError, Event, EventLoop};
+use super::status::StatusPublisher;
+use super::systemd::Systemd;
+use super::tuning::{TuningControl, TuningHandle};
 
-use rumqttc::{ConnectionError, EventLoop};
+use std::sync::Arc;
 use thiserror::Error;
+#[cfg(unix)]
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::watch;
 use tokio::task::{JoinError, JoinSet};
+use tokio::time::Duration;
+
+/// How long the loops have to finish up, once told to stop, before the
+/// daemon exits regardless.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
 
 #[derive(Debug, Error)]
 pub enum AlarmSessionError {
@@ -11,10 +31,14 @@ pub enum AlarmSessionError {
     DataLoop(#[from] LoopError),
     #[error("error waiting for seismometer loop")]
     LoopJoin(#[from] JoinError),
-    #[error("MQTT connection failed")]
-    MQTTConnection(#[from] ConnectionError),
     #[error("failure while taking action")]
     Action(#[from] ActionLoopError),
+    #[error("failure while publishing status")]
+    StatusPublish(#[from] ClientError),
+    #[error("failed to read arming switch")]
+    ArmedInput(#[from] std::io::Error),
+    #[error("unable to handle termination signals")]
+    Signal(#[source] std::io::Error),
 }
 
 pub struct AlarmSession<'a> {
@@ -28,6 +52,62 @@ pub struct AlarmSession<'a> {
     /// An optional MQTT event loop that must be run in order to provide
     /// MQTT service.
     mqtt_loop: Option<EventLoop>,
+
+    /// A task which periodically reports the daemon status.
+    status_publisher: StatusPublisher,
+
+    /// The inputs which arm and disarm the session.
+    armed_control: ArmedControl<'a>,
+
+    /// The daemon's own availability, as posted over MQTT.
+    presence: DaemonPresence<'a>,
+
+    /// The state of the connection to the MQTT broker.
+    connection: MqttConnection,
+
+    /// The event loops of named MQTT brokers, and the states of their
+    /// connections.
+    brokers: Vec<(String, EventLoop, MqttConnection)>,
+
+    /// An optional task which writes flow status and events to InfluxDB.
+    influx_writer: Option<InfluxWriter>,
+
+    /// An optional task which appends flow events to a local file.
+    event_log_writer: Option<EventLogWriter>,
+
+    /// The inputs which adjust flows' trigger levels, if any.
+    tuning_control: Option<TuningControl<'a>>,
+
+    /// An optional server from which the daemon's state may be read.
+    api_server: Option<ApiServer>,
+
+    /// Control socket, if the daemon may be controlled locally.
+    control_server: Option<ControlServer>,
+
+    /// A live view on the terminal, if one is shown.
+    monitor: Option<TerminalMonitor>,
+
+    /// Set when the session has been told to stop.
+    stop: Arc<watch::Sender<bool>>,
+
+    /// Set when the session has been told to stop for a restart.
+    restarting: Arc<watch::Sender<bool>>,
+
+    /// Set when whatever runs the daemon tells it to stop, if anything
+    /// but a signal may.
+    told_to_stop: Option<watch::Receiver<bool>>,
+}
+
+/// A handle on a session with which to stop it, so that it may be
+/// restarted with a new configuration. Unlike on SIGTERM, what the session
+/// has posted is left standing, rather than posted offline, for the new
+/// session to take on.
+pub struct RestartHandle(Arc<watch::Sender<bool>>);
+
+impl RestartHandle {
+    pub fn restart(&self) {
+        self.0.send_replace(true);
+    }
 }
 
 impl<'a> AlarmSession<'a> {
@@ -35,20 +115,174 @@ impl<'a> AlarmSession<'a> {
         instrument_loops: Vec<InstrumentLoop>,
         action_loop: ActionLoop<'a>,
         mqtt_loop: Option<EventLoop>,
+        status_publisher: StatusPublisher,
+        armed_control: ArmedControl<'a>,
+        presence: DaemonPresence<'a>,
+        connection: MqttConnection,
     ) -> Self {
         Self {
             instrument_loops,
             action_loop,
             mqtt_loop,
+            status_publisher,
+            armed_control,
+            presence,
+            connection,
+            brokers: Vec::new(),
+            influx_writer: None,
+            event_log_writer: None,
+            tuning_control: None,
+            api_server: None,
+            control_server: None,
+            monitor: None,
+            stop: Arc::new(watch::Sender::new(false)),
+            restarting: Arc::new(watch::Sender::new(false)),
+            told_to_stop: None,
         }
     }
 
-    pub async fn run(self) -> Result<(), AlarmSessionError> {
-        tokio::try_join!(
-            Self::run_all_instrument_loops(self.instrument_loops),
-            Self::run_mqtt_connection(self.mqtt_loop),
-            Self::run_actions_loop(self.action_loop),
-        )?;
+    /// Run a named MQTT broker's event loop alongside the loops.
+    pub fn run_broker(&mut self, name: String, event_loop: EventLoop, connection: MqttConnection) {
+        self.brokers.push((name, event_loop, connection));
+    }
+
+    /// Run a task which writes flow status and events to InfluxDB
+    /// alongside the loops.
+    pub fn write_to_influx(&mut self, writer: InfluxWriter) {
+        self.influx_writer = Some(writer);
+    }
+
+    /// Run a task which appends flow events to a local file alongside
+    /// the loops.
+    pub fn write_to_event_log(&mut self, writer: EventLogWriter) {
+        self.event_log_writer = Some(writer);
+    }
+
+    /// Serve the daemon's state over HTTP alongside the loops.
+    pub fn serve_api(&mut self, server: ApiServer) {
+        self.api_server = Some(server);
+    }
+
+    /// Take requests from the local control socket alongside the loops.
+    pub fn serve_control(&mut self, server: ControlServer) {
+        self.control_server = Some(server);
+    }
+
+    /// Show a live view of the flows on the terminal alongside the loops.
+    pub fn monitor_terminal(&mut self, monitor: TerminalMonitor) {
+        self.monitor = Some(monitor);
+    }
+
+    /// Stop, as on SIGTERM, once told to by whatever runs the daemon (as
+    /// the Windows service control manager does).
+    pub fn stop_on(&mut self, told: watch::Receiver<bool>) {
+        self.told_to_stop = Some(told);
+    }
+
+    /// A handle with which to stop the session for a restart.
+    pub fn restart_handle(&self) -> RestartHandle {
+        RestartHandle(self.restarting.clone())
+    }
+
+    /// Adjust flows' trigger levels as told to over MQTT (if a topic is
+    /// configured) and through tuning handles.
+    pub fn tune_thresholds(&mut self, control: TuningControl<'a>) {
+        self.tuning_control = Some(control);
+    }
+
+    /// A handle with which to adjust flows' trigger levels while the
+    /// session runs, if they may be.
+    pub fn tuning_handle(&self) -> Option<TuningHandle> {
+        self.tuning_control.as_ref().map(TuningControl::handle)
+    }
+
+    pub async fn run(mut self) -> Result<(), AlarmSessionError> {
+        // The data sources were all opened as the session was configured
+        // (a failed source is only reopened in the background later), so
+        // the daemon is ready unless it has yet to connect to the broker.
+        let mut systemd = Systemd::from_env();
+        for instrument in self.instrument_loops.iter_mut() {
+            instrument.stop_on(self.stop.subscribe());
+            if let Some(heartbeat) = systemd.heartbeat(&format!("seismometer {}", instrument.name())) {
+                instrument.report_liveness(heartbeat);
+            }
+        }
+        if let Some(heartbeat) = systemd.heartbeat("action loop") {
+            self.action_loop.report_liveness(heartbeat);
+        }
+        self.action_loop.note_restarts(self.restarting.subscribe());
+        if self.mqtt_loop.is_none() {
+            systemd.ready();
+        }
+        let armed_control = self.armed_control;
+        // Once the instrument loops stop, the action loop runs dry and
+        // stops in turn, and then the daemon leaves the broker and the
+        // writers write what they were left with.
+        let orderly = async {
+            tokio::try_join!(
+                Self::run_all_instrument_loops(self.instrument_loops),
+                Self::run_mqtt_connection(
+                    self.mqtt_loop,
+                    &armed_control,
+                    &self.presence,
+                    &self.connection,
+                    self.tuning_control.as_ref(),
+                    &systemd,
+                    self.stop.subscribe(),
+                ),
+                Self::run_brokers(self.brokers, self.stop.subscribe()),
+                Self::run_actions_loop(self.action_loop, &self.presence, &self.restarting),
+                Self::run_influx_writer(self.influx_writer),
+                Self::run_event_log_writer(self.event_log_writer),
+            )
+        };
+        // These run for as long as the daemon does, if they run at all.
+        let background = async {
+            tokio::try_join!(
+                Self::run_watchdog(&systemd),
+                Self::run_status_publisher(self.status_publisher, self.stop.subscribe()),
+                Self::run_armed_gpio(&armed_control),
+                Self::run_api_server(self.api_server),
+                Self::run_control_server(self.control_server),
+                Self::run_monitor(self.monitor),
+            )?;
+            std::future::pending::<Result<(), AlarmSessionError>>().await
+        };
+        tokio::select! {
+            result = orderly => {
+                result?;
+            }
+            result = background => result?,
+            result = Self::stop_when_told(&self.stop, &self.restarting, self.told_to_stop, &systemd) => result?,
+        }
+        Ok(())
+    }
+
+    /// Wait for SIGINT or SIGTERM (or their like, or a restart), then tell
+    /// the loops to stop, giving them a while to finish up before giving up
+    /// on them.
+    async fn stop_when_told(
+        stop: &watch::Sender<bool>,
+        restarting: &watch::Sender<bool>,
+        told_to_stop: Option<watch::Receiver<bool>>,
+        systemd: &Systemd,
+    ) -> Result<(), AlarmSessionError> {
+        let mut restart = restarting.subscribe();
+        tokio::select! {
+            signalled = terminated() => {
+                let signalled = signalled.map_err(AlarmSessionError::Signal)?;
+                log::info!("{signalled}, stopping");
+                systemd.stopping();
+            }
+            () = told(told_to_stop) => {
+                log::info!("told to stop, stopping");
+                systemd.stopping();
+            }
+            _ = restart.wait_for(|&restarting| restarting) => systemd.reloading(),
+        }
+        stop.send_replace(true);
+        tokio::time::sleep(SHUTDOWN_GRACE).await;
+        log::warn!("loops did not stop within {SHUTDOWN_GRACE:?}, exiting anyway");
         Ok(())
     }
 
@@ -68,19 +302,194 @@ impl<'a> AlarmSession<'a> {
 
     async fn run_mqtt_connection(
         mqtt_event_loop: Option<EventLoop>,
+        armed_control: &ArmedControl<'a>,
+        presence: &DaemonPresence<'a>,
+        connection: &MqttConnection,
+        tuning_control: Option<&TuningControl<'a>>,
+        systemd: &Systemd,
+        stopping: watch::Receiver<bool>,
     ) -> Result<(), AlarmSessionError> {
-        if let Some(mut conn) = mqtt_event_loop {
-            loop {
-                let _event = conn.poll().await?;
-            }
+        if let Some(conn) = mqtt_event_loop {
+            Self::poll_connection("MQTT connection", conn, connection, stopping, |event| {
+                if is_connected(event) {
+                    systemd.ready();
+                }
+                armed_control.handle_mqtt_event(event);
+                presence.handle_mqtt_event(event);
+                if let Some(tuning_control) = tuning_control {
+                    tuning_control.handle_mqtt_event(event);
+                }
+            })
+            .await;
         }
         Ok(())
     }
 
+    /// Run the named brokers' event loops, which need only be polled, as
+    /// nothing is listened for on them.
+    async fn run_brokers(
+        brokers: Vec<(String, EventLoop, MqttConnection)>,
+        stopping: watch::Receiver<bool>,
+    ) -> Result<(), AlarmSessionError> {
+        let mut broker_tasks = JoinSet::new();
+        for (name, conn, connection) in brokers {
+            let stopping = stopping.clone();
+            broker_tasks.spawn(async move {
+                let what = format!("MQTT broker {name} connection");
+                Self::poll_connection(&what, conn, &connection, stopping, |_| ()).await;
+            });
+        }
+        while let Some(res) = broker_tasks.join_next().await {
+            res?
+        }
+        Ok(())
+    }
+
+    /// Poll a broker's event loop, handing its events on, until the daemon
+    /// leaves the broker. Polling again after an error reconnects, so the
+    /// connection is retried (after a wait) for as long as the session
+    /// runs, and no longer once it is stopping.
+    async fn poll_connection(
+        what: &str,
+        mut conn: EventLoop,
+        connection: &MqttConnection,
+        mut stopping: watch::Receiver<bool>,
+        mut handle_event: impl FnMut(&Event),
+    ) {
+        let mut backoff = None;
+        loop {
+            let event = match conn.poll().await {
+                Ok(event) => event,
+                Err(e) if *stopping.borrow() => {
+                    log::warn!("{what} failed ({e}) while stopping");
+                    break;
+                }
+                Err(e) => {
+                    connection.set_connected(false);
+                    let wait = connection.backoff(backoff);
+                    log::warn!("{what} failed ({e}), retrying in {wait:?}");
+                    tokio::select! {
+                        () = tokio::time::sleep(wait) => (),
+                        _ = stopping.wait_for(|&stopping| stopping) => break,
+                    }
+                    backoff = Some(wait);
+                    continue;
+                }
+            };
+            if is_disconnecting(&event) {
+                break;
+            }
+            if is_connected(&event) {
+                backoff = None;
+                connection.set_connected(true);
+            }
+            handle_event(&event);
+        }
+    }
+
     async fn run_actions_loop(
-        action_loop: ActionLoop<'a>
+        action_loop: ActionLoop<'a>,
+        presence: &DaemonPresence<'a>,
+        restarting: &watch::Sender<bool>,
     ) -> Result<(), AlarmSessionError> {
         action_loop.run().await?;
+        presence.leave(*restarting.borrow()).await;
         Ok(())
     }
+
+    // The status is no longer published once the daemon is stopping, as
+    // the connection to the broker is about to go.
+    async fn run_status_publisher(
+        publisher: StatusPublisher,
+        mut stopping: watch::Receiver<bool>,
+    ) -> Result<(), AlarmSessionError> {
+        tokio::select! {
+            result = publisher.run() => result?,
+            _ = stopping.wait_for(|&stopping| stopping) => (),
+        }
+        Ok(())
+    }
+
+    async fn run_influx_writer(writer: Option<InfluxWriter>) -> Result<(), AlarmSessionError> {
+        if let Some(writer) = writer {
+            writer.run().await;
+        }
+        Ok(())
+    }
+
+    async fn run_event_log_writer(writer: Option<EventLogWriter>) -> Result<(), AlarmSessionError> {
+        if let Some(writer) = writer {
+            writer.run().await;
+        }
+        Ok(())
+    }
+
+    async fn run_api_server(server: Option<ApiServer>) -> Result<(), AlarmSessionError> {
+        if let Some(server) = server {
+            server.run().await;
+        }
+        Ok(())
+    }
+
+    async fn run_control_server(server: Option<ControlServer>) -> Result<(), AlarmSessionError> {
+        if let Some(server) = server {
+            server.run().await;
+        }
+        Ok(())
+    }
+
+    async fn run_monitor(monitor: Option<TerminalMonitor>) -> Result<(), AlarmSessionError> {
+        if let Some(monitor) = monitor {
+            monitor.run().await;
+        }
+        Ok(())
+    }
+
+    async fn run_watchdog(systemd: &Systemd) -> Result<(), AlarmSessionError> {
+        systemd.run_watchdog().await;
+        Ok(())
+    }
+
+    async fn run_armed_gpio(armed_control: &ArmedControl<'a>) -> Result<(), AlarmSessionError> {
+        armed_control.run_gpio().await?;
+        Ok(())
+    }
+}
+
+/// Wait for SIGINT or SIGTERM, returning which it was.
+#[cfg(unix)]
+async fn terminated() -> std::io::Result<&'static str> {
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut terminate = signal(SignalKind::terminate())?;
+    tokio::select! {
+        _ = interrupt.recv() => Ok("interrupted"),
+        _ = terminate.recv() => Ok("terminated"),
+    }
+}
+
+/// Wait for Ctrl-C or Ctrl-Break, or for the console to be closed or the
+/// system shut down, returning which it was.
+#[cfg(windows)]
+async fn terminated() -> std::io::Result<&'static str> {
+    use tokio::signal::windows;
+    let mut ctrl_c = windows::ctrl_c()?;
+    let mut ctrl_break = windows::ctrl_break()?;
+    let mut close = windows::ctrl_close()?;
+    let mut shutdown = windows::ctrl_shutdown()?;
+    tokio::select! {
+        _ = ctrl_c.recv() => Ok("interrupted"),
+        _ = ctrl_break.recv() => Ok("interrupted"),
+        _ = close.recv() => Ok("console closed"),
+        _ = shutdown.recv() => Ok("system shutting down"),
+    }
+}
+
+/// Wait to be told to stop, if the daemon may be, or forever.
+async fn told(told_to_stop: Option<watch::Receiver<bool>>) {
+    if let Some(mut told_to_stop) = told_to_stop {
+        if told_to_stop.wait_for(|&told| told).await.is_ok() {
+            return;
+        }
+    }
+    std::future::pending().await
 }
@@ -0,0 +1,158 @@
+//! A small, read-only HTTP API serving the daemon's state as JSON, for
+//! dashboards which would rather poll it than mirror it all over MQTT.
+//!
+//! * `GET /api/status` - everything on the status board.
+//! * `GET /api/flows` - the state of every flow, by name.
+//! * `GET /api/flows/<name>` - the state of one flow.
+//! * `GET /api/live` - a WebSocket streaming flows' events and energy as
+//!   they happen, after the state of every flow.
+use super::live::{LiveFeed, LiveSubscriber};
+use super::live_socket;
+use super::status::StatusBoard;
+use crate::config::HttpConfig;
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use thiserror::Error;
+use tokio::net::TcpListener;
+
+#[derive(Debug, Error)]
+pub enum ApiError {
+    #[error("unable to listen on {0}: {1}")]
+    Bind(String, #[source] std::io::Error),
+}
+
+pub struct ApiServer {
+    listener: TcpListener,
+    router: Router,
+}
+
+/// What the API's requests are served from.
+#[derive(Clone)]
+pub(super) struct Api {
+    pub status: StatusBoard,
+
+    /// Only a way to subscribe is held, so that live clients are let go
+    /// once the session (and its feed) ends.
+    #[cfg_attr(not(feature = "websocket"), allow(dead_code))]
+    pub live: LiveSubscriber,
+}
+
+impl ApiServer {
+    /// Listen where configured, to serve what is on a status board and
+    /// stream what is sent to a live feed.
+    pub async fn bind(
+        config: &HttpConfig,
+        status: StatusBoard,
+        live: LiveFeed,
+    ) -> Result<Self, ApiError> {
+        let listener = TcpListener::bind(&config.listen)
+            .await
+            .map_err(|e| ApiError::Bind(config.listen.clone(), e))?;
+        Ok(Self {
+            listener,
+            router: router(status, &live),
+        })
+    }
+
+    /// Serve requests, each connection in the background, for as long as
+    /// the session runs.
+    pub async fn run(self) {
+        if let Err(e) = axum::serve(self.listener, self.router).await {
+            log::warn!("HTTP server failed: {e}");
+        }
+    }
+}
+
+fn router(status: StatusBoard, live: &LiveFeed) -> Router {
+    let api = Api {
+        status,
+        live: live.subscriber(),
+    };
+    Router::new()
+        .route("/api/status", get(get_status))
+        .route("/api/flows", get(get_flows))
+        .route("/api/flows/{name}", get(get_flow))
+        .route("/api/live", get(live_socket::serve))
+        .fallback(|| async { error(StatusCode::NOT_FOUND) })
+        .with_state(api)
+}
+
+async fn get_status(State(api): State<Api>) -> Response {
+    Json(api.status.snapshot()).into_response()
+}
+
+async fn get_flows(State(api): State<Api>) -> Response {
+    Json(api.status.snapshot().flows).into_response()
+}
+
+async fn get_flow(State(api): State<Api>, Path(name): Path<String>) -> Response {
+    match api.status.snapshot().flows.remove(&name) {
+        Some(flow) => Json(flow).into_response(),
+        None => error(StatusCode::NOT_FOUND),
+    }
+}
+
+/// A response reporting an error, as JSON like the rest of the API.
+pub(super) fn error(status: StatusCode) -> Response {
+    let body = serde_json::json!({ "error": status.to_string() });
+    (status, Json(body)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    /// Make a request of a server, returning the status code and body of
+    /// its response.
+    async fn request(address: std::net::SocketAddr, head: &str) -> (u16, String) {
+        let mut stream = TcpStream::connect(address).await.expect("connects");
+        let request = format!("{head}\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+        stream.write_all(request.as_bytes()).await.expect("sends");
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.expect("reads");
+        let (head, body) = response.split_once("\r\n\r\n").expect("has a head");
+        let code = head.split_whitespace().nth(1).expect("status").parse();
+        (code.expect("numeric status"), body.to_owned())
+    }
+
+    /// Serve a status board on a port of its own.
+    async fn serve(status: StatusBoard) -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("binds");
+        let address = listener.local_addr().expect("address");
+        let live = LiveFeed::new(Vec::new());
+        let router = router(status, &live);
+        tokio::spawn(async move {
+            let _live = live;
+            axum::serve(listener, router).await
+        });
+        address
+    }
+
+    #[tokio::test]
+    async fn serves_flows() {
+        let status = StatusBoard::new();
+        status.update(|status| {
+            let flow = status.flows.entry("garage floor".to_owned()).or_default();
+            flow.triggered = true;
+            flow.triggers = 2;
+        });
+        let address = serve(status).await;
+        let (code, body) = request(address, "GET /api/flows/garage%20floor HTTP/1.1").await;
+        assert_eq!(code, 200);
+        let flow: serde_json::Value = serde_json::from_str(&body).expect("JSON");
+        assert_eq!(flow["triggered"], true);
+        assert_eq!(flow["triggers"], 2);
+        let (_, body) = request(address, "GET /api/flows?pretty HTTP/1.1").await;
+        assert!(body.starts_with("{\"garage floor\":{"));
+        let (code, body) = request(address, "GET /api/flows/attic HTTP/1.1").await;
+        assert_eq!(code, 404);
+        assert!(body.contains("\"error\""));
+        assert_eq!(request(address, "POST /api/flows HTTP/1.1").await.0, 405);
+    }
+}
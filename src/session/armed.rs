@@ -0,0 +1,207 @@
+//! The session-wide "armed" master switch. While disarmed, no trigger
+//! actions are taken for any flow. Flows may also be disarmed (and have
+//! their actions tested) one at a time, by commands sent to the action
+//! loop.
+use crate::config::ArmedConfig;
+
+use super::mqtt::{incoming_publish, is_connected, AsyncClient, Event, QoS};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch};
+use tokio::time::Duration;
+
+/// A command received for a flow, or for the whole session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Arm,
+    Disarm,
+    /// Take the trigger actions, then the reset actions, as though an
+    /// event had come and gone.
+    Test,
+}
+
+impl Command {
+    fn parse(payload: &[u8]) -> Option<Self> {
+        match payload.trim_ascii().to_ascii_lowercase().as_slice() {
+            b"arm" => Some(Self::Arm),
+            b"disarm" => Some(Self::Disarm),
+            b"test" => Some(Self::Test),
+            _ => None,
+        }
+    }
+}
+
+/// Commands for flows (by id), or for every flow (with none).
+pub type CommandSender = mpsc::Sender<(Option<usize>, Command)>;
+pub type CommandReceiver = mpsc::Receiver<(Option<usize>, Command)>;
+
+pub fn command_channel() -> (CommandSender, CommandReceiver) {
+    mpsc::channel(16)
+}
+
+/// A cloneable handle to the armed state, which may be flipped from
+/// anywhere in the program.
+#[derive(Clone)]
+pub struct ArmedSwitch(Arc<watch::Sender<bool>>);
+
+impl ArmedSwitch {
+    pub fn new(armed: bool) -> Self {
+        Self(Arc::new(watch::Sender::new(armed)))
+    }
+
+    pub fn is_armed(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Arm or disarm. Returns whether the state changed.
+    pub fn set(&self, armed: bool) -> bool {
+        self.0
+            .send_if_modified(|state| std::mem::replace(state, armed) != armed)
+    }
+
+    /// Watch for changes to the armed state.
+    pub fn subscribe(&self) -> watch::Receiver<bool> {
+        self.0.subscribe()
+    }
+}
+
+/// The external inputs (MQTT commands and a physical switch) which flip
+/// the armed switch, and pass on commands for flows.
+pub struct ArmedControl<'a> {
+    switch: ArmedSwitch,
+    config: Option<&'a ArmedConfig>,
+    mqtt: Option<AsyncClient>,
+    /// Flows' command topics, by flow id, and where to send the commands
+    /// received on them (and tests of the whole session).
+    flow_topics: Vec<(&'a str, usize)>,
+    commands: Option<CommandSender>,
+}
+
+impl<'a> ArmedControl<'a> {
+    pub fn new(
+        switch: ArmedSwitch,
+        config: Option<&'a ArmedConfig>,
+        mqtt: Option<AsyncClient>,
+    ) -> Self {
+        Self {
+            switch,
+            config,
+            mqtt,
+            flow_topics: Vec::new(),
+            commands: None,
+        }
+    }
+
+    /// Listen for commands for flows on their command topics (by flow
+    /// id), and send them on, along with tests of the whole session.
+    pub fn control_flows(&mut self, topics: Vec<(&'a str, usize)>, commands: CommandSender) {
+        self.flow_topics = topics;
+        self.commands = Some(commands);
+    }
+
+    /// React to an event from the MQTT connection, (re-)subscribing to the
+    /// command topics whenever a connection is made and applying any
+    /// commands received on them.
+    pub fn handle_mqtt_event(&self, event: &Event) {
+        let config = self.config;
+        let topic = config.and_then(|config| config.mqtt_command_topic.as_deref());
+        if is_connected(event) {
+            if let Some(client) = self.mqtt.as_ref() {
+                let flow_topics = self.flow_topics.iter().map(|(topic, _)| *topic);
+                for topic in topic.into_iter().chain(flow_topics) {
+                    // The event loop is busy running this, so the request
+                    // must not wait for room in the queue.
+                    let _ = client.try_subscribe(topic, QoS::AtLeastOnce);
+                }
+            }
+            return;
+        }
+        let Some((received, payload)) = incoming_publish(event) else {
+            return;
+        };
+        if let Some(config) = config.filter(|_| topic == Some(received)) {
+            if payload == config.mqtt_armed_payload.as_bytes() {
+                self.switch.set(true);
+            } else if payload == config.mqtt_disarmed_payload.as_bytes() {
+                self.switch.set(false);
+            } else {
+                match Command::parse(payload) {
+                    Some(Command::Arm) => _ = self.switch.set(true),
+                    Some(Command::Disarm) => _ = self.switch.set(false),
+                    Some(Command::Test) => self.send(None, Command::Test),
+                    None => (),
+                }
+            }
+        }
+        let flows = self
+            .flow_topics
+            .iter()
+            .filter(|(topic, _)| *topic == received);
+        for &(_, flow_id) in flows {
+            if let Some(command) = Command::parse(payload) {
+                self.send(Some(flow_id), command);
+            }
+        }
+    }
+
+    /// Pass a command on to the action loop. The event loop is busy
+    /// running this, so it is dropped if there's no room for it.
+    fn send(&self, flow_id: Option<usize>, command: Command) {
+        let Some(commands) = self.commands.as_ref() else {
+            return;
+        };
+        if commands.try_send((flow_id, command)).is_err() {
+            log::warn!("too many commands waiting, dropping {command:?}");
+        }
+    }
+
+    /// Follow the physical arming switch, if one is configured.
+    pub async fn run_gpio(&self) -> std::io::Result<()> {
+        let Some((config, path)) = self
+            .config
+            .and_then(|config| config.gpio_path.as_ref().map(|path| (config, path)))
+        else {
+            return Ok(());
+        };
+        let mut last = read_gpio(path).await?;
+        self.switch.set(last != config.gpio_active_low);
+        let mut ticker = tokio::time::interval(Duration::from_secs_f32(config.gpio_poll_s));
+        loop {
+            ticker.tick().await;
+            let value = read_gpio(path).await?;
+            if value != last {
+                self.switch.set(value != config.gpio_active_low);
+                last = value;
+            }
+        }
+    }
+}
+
+async fn read_gpio(path: &Path) -> std::io::Result<bool> {
+    let value = tokio::fs::read_to_string(path).await?;
+    Ok(value.trim() != "0")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_reports_changes() {
+        let switch = ArmedSwitch::new(true);
+        let watcher = switch.subscribe();
+        assert!(!switch.set(true));
+        assert!(!watcher.has_changed().unwrap());
+        assert!(switch.set(false));
+        assert!(watcher.has_changed().unwrap());
+        assert!(!switch.is_armed());
+    }
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(Command::parse(b"arm"), Some(Command::Arm));
+        assert_eq!(Command::parse(b" Disarm\n"), Some(Command::Disarm));
+        assert_eq!(Command::parse(b"TEST"), Some(Command::Test));
+        assert_eq!(Command::parse(b"ON"), None);
+    }
+}
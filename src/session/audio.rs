@@ -0,0 +1,91 @@
+//! Playing sounds through the host's own audio output, so that a speaker
+//! attached to it can sound an alarm without a player of its own.
+use crate::config::AudioConfig;
+
+use rodio::decoder::DecoderError;
+use rodio::{Decoder, OutputStreamBuilder, Sink, Source};
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::PathBuf;
+use thiserror::Error;
+use tokio::time::Duration;
+
+#[derive(Debug, Error)]
+pub enum AudioError {
+    #[error("can't open audio output: {0}")]
+    Output(String),
+    #[error("can't open {}", .0.display())]
+    Open(PathBuf, #[source] std::io::Error),
+    #[error("can't decode {}: {}", .0.display(), .1)]
+    Decode(PathBuf, DecoderError),
+}
+
+/// The default audio output, and the sounds playing through it for each
+/// flow. Sounds are played in the background, mixed with any others.
+pub struct AudioPlayer {
+    mixer: rodio::mixer::Mixer,
+    /// Sounds playing, by flow id.
+    playing: HashMap<usize, Sink>,
+}
+
+impl AudioPlayer {
+    /// Open the default audio output, which is held open for as long as
+    /// the daemon runs.
+    pub fn new() -> Result<Self, AudioError> {
+        let (opened, opening) = std::sync::mpsc::channel();
+        // The output mayn't move between threads, so it is left on one
+        // of its own.
+        std::thread::spawn(move || match OutputStreamBuilder::open_default_stream() {
+            Ok(mut stream) => {
+                stream.log_on_drop(false);
+                let _ = opened.send(Ok(stream.mixer().clone()));
+                loop {
+                    std::thread::park();
+                }
+            }
+            Err(e) => {
+                let _ = opened.send(Err(e.to_string()));
+            }
+        });
+        let mixer = opening
+            .recv()
+            .map_err(|e| AudioError::Output(e.to_string()))?
+            .map_err(AudioError::Output)?;
+        Ok(Self {
+            mixer,
+            playing: HashMap::new(),
+        })
+    }
+
+    /// Start playing a sound for a flow, in place of any it is playing.
+    pub fn play(&mut self, flow_id: usize, config: &AudioConfig) -> Result<(), AudioError> {
+        self.playing.retain(|_, sink| !sink.empty());
+        let path = &config.file;
+        let file = File::open(path).map_err(|e| AudioError::Open(path.clone(), e))?;
+        let sound = Decoder::try_from(file)
+            .map_err(|e| AudioError::Decode(path.clone(), e))?
+            .buffered();
+        let times = match config.repeat {
+            0 => usize::MAX,
+            n => n as usize,
+        };
+        let sounds = rodio::source::from_iter(std::iter::repeat_n(sound, times));
+        let sink = Sink::connect_new(&self.mixer);
+        sink.set_volume(config.volume);
+        match config.duration_s {
+            Some(duration_s) => {
+                sink.append(sounds.take_duration(Duration::from_secs_f32(duration_s)))
+            }
+            None => sink.append(sounds),
+        }
+        self.playing.insert(flow_id, sink);
+        Ok(())
+    }
+
+    /// Stop the sound playing for a flow, if any.
+    pub fn stop(&mut self, flow_id: usize) {
+        if let Some(sink) = self.playing.remove(&flow_id) {
+            sink.stop();
+        }
+    }
+}
@@ -0,0 +1,182 @@
+//! Topics on which it is posted whether the channels of a seismometer's
+//! flows (or of every seismometer's) are all alive. Each is posted over a
+//! connection to the main broker of its own, as a connection has only the
+//! one last will, so that the broker posts each offline should the daemon
+//! vanish.
+use super::mqtt::{AsyncClient, ClientError, QoS};
+use super::outbox::MqttConnection;
+use crate::config::MQTTConfig;
+
+use tokio::sync::{mpsc, watch};
+
+struct AvailabilityTopic<'a> {
+    seismometer: Option<&'a str>,
+    topic: String,
+    client: AsyncClient,
+    connected: watch::Receiver<bool>,
+    config: &'a MQTTConfig,
+    /// Whether the channels were last posted as alive, if posted at all
+    /// since the connection was last made.
+    posted: Option<bool>,
+}
+
+pub struct AvailabilityTopics<'a> {
+    topics: Vec<AvailabilityTopic<'a>>,
+    /// The topics (by index) whose connections have been made again, as
+    /// told by a task watching each one.
+    reconnected: (mpsc::UnboundedSender<usize>, mpsc::UnboundedReceiver<usize>),
+}
+
+impl<'a> AvailabilityTopics<'a> {
+    pub fn new() -> Self {
+        Self {
+            topics: Vec::new(),
+            reconnected: mpsc::unbounded_channel(),
+        }
+    }
+
+    /// Post, retained, whether the channels of all of a seismometer's flows
+    /// (or of every seismometer's, if none is named) are alive to a topic,
+    /// as the MQTT configuration's online or offline payload, over a
+    /// connection whose last will posts the offline payload.
+    pub fn add(
+        &mut self,
+        seismometer: Option<&'a str>,
+        topic: &str,
+        client: AsyncClient,
+        connection: &MqttConnection,
+        config: &'a MQTTConfig,
+    ) {
+        connection.report_reconnections(self.topics.len(), self.reconnected.0.clone());
+        self.topics.push(AvailabilityTopic {
+            seismometer,
+            topic: topic.to_owned(),
+            client,
+            connected: connection.subscribe(),
+            config,
+            posted: None,
+        });
+    }
+
+    /// The seismometers whose availability each topic is posted for.
+    pub fn seismometers(&self) -> impl Iterator<Item = Option<&'a str>> + '_ {
+        self.topics.iter().map(|topic| topic.seismometer)
+    }
+
+    /// Post whether the channels of each topic's seismometers are alive,
+    /// given in the order of `seismometers()`, wherever it is known and has
+    /// changed since it was last posted. Until it is known, whatever was
+    /// posted before (as by the session before a restart) stands. Topics
+    /// whose connections are down are posted once they are made again (the
+    /// broker having posted their last wills in the meantime).
+    pub async fn publish(&mut self, alive: &[Option<bool>]) -> Result<(), ClientError> {
+        for (topic, &alive) in self.topics.iter_mut().zip(alive) {
+            let Some(alive) = alive else {
+                continue;
+            };
+            if topic.posted == Some(alive) || !*topic.connected.borrow() {
+                continue;
+            }
+            let config = topic.config;
+            let payload = if alive {
+                &config.online_payload
+            } else {
+                &config.offline_payload
+            };
+            topic
+                .client
+                .publish(&topic.topic, QoS::AtLeastOnce, true, payload.as_bytes())
+                .await?;
+            topic.posted = Some(alive);
+        }
+        Ok(())
+    }
+
+    /// Wait until any of the topics' connections is made again, and tell
+    /// which, once it is due to be posted again.
+    pub async fn reconnected(&mut self) -> usize {
+        // A sender is held, so there is always a next one to wait for.
+        let index = self.reconnected.1.recv().await.expect("sender is held");
+        self.topics[index].posted = None;
+        index
+    }
+
+    /// Post that the channels are unavailable, unless the daemon is
+    /// restarting, and disconnect, as the daemon stops. (A restarting
+    /// daemon leaves what was posted standing, and the last wills aren't
+    /// posted on a clean disconnection.) A failure is only logged, as
+    /// there is no time left to retry it.
+    pub async fn leave(&self, restarting: bool) {
+        for topic in self.topics.iter() {
+            if !restarting {
+                let payload = topic.config.offline_payload.as_bytes();
+                let result = topic
+                    .client
+                    .publish(&topic.topic, QoS::AtLeastOnce, true, payload);
+                if let Err(e) = result.await {
+                    log::warn!("can't announce unavailability: {e}");
+                }
+            }
+            if let Err(e) = topic.client.disconnect().await {
+                log::warn!("can't leave MQTT broker for {}: {e}", topic.topic);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "mqtt"))]
+mod tests {
+    use super::*;
+    use rumqttc::Request;
+
+    fn posted(requests: &flume::Receiver<Request>) -> Vec<(String, Vec<u8>)> {
+        let publish = |request| match request {
+            Request::Publish(publish) => Some((publish.topic, publish.payload.to_vec())),
+            _ => None,
+        };
+        requests.try_iter().filter_map(publish).collect()
+    }
+
+    #[tokio::test]
+    async fn posts_changes_and_reposts_on_reconnection() {
+        let config = serde_json::json!({ "host": "lab" });
+        let config: MQTTConfig = serde_json::from_value(config).expect("valid");
+        let (requests_tx, requests) = flume::unbounded();
+        let connection = MqttConnection::new(60.0);
+        let mut topics = AvailabilityTopics::new();
+        let client = AsyncClient::from_senders(requests_tx);
+        topics.add(
+            Some("garage"),
+            "garage/available",
+            client,
+            &connection,
+            &config,
+        );
+        assert_eq!(topics.seismometers().collect::<Vec<_>>(), [Some("garage")]);
+
+        // Nothing is posted until the connection is made, nor until the
+        // channels' availability is known.
+        topics.publish(&[Some(true)]).await.expect("posts");
+        assert!(posted(&requests).is_empty());
+        connection.set_connected(true);
+        assert_eq!(topics.reconnected().await, 0);
+        topics.publish(&[None]).await.expect("posts");
+        assert!(posted(&requests).is_empty());
+        topics.publish(&[Some(true)]).await.expect("posts");
+        topics.publish(&[Some(true)]).await.expect("posts");
+        let online = || ("garage/available".to_owned(), b"online".to_vec());
+        assert_eq!(posted(&requests), [online()]);
+
+        // Once the connection is made again, the broker will have posted
+        // the last will, so the state is posted again.
+        connection.set_connected(false);
+        connection.set_connected(true);
+        assert_eq!(topics.reconnected().await, 0);
+        topics.publish(&[Some(true)]).await.expect("posts");
+        assert_eq!(posted(&requests), [online()]);
+
+        // A restart leaves the topic as it was posted.
+        topics.leave(true).await;
+        assert!(posted(&requests).is_empty());
+    }
+}
@@ -0,0 +1,207 @@
+//! MQTT brokers besides the main one, which actions may post to by name
+//! (as a cloud broker fed a summary of the alarms posted on the LAN).
+//! Each holds posts in an outbox of its own while it is unreachable, and
+//! is told the daemon's availability, if it has a topic for that.
+use super::mqtt::{AsyncClient, ClientError, QoS};
+use super::outbox::{post_or_hold, MqttConnection, Outbox, Post};
+use super::status::StatusBoard;
+use crate::config::MQTTBrokerConfig;
+
+use tokio::sync::mpsc;
+
+/// A named broker's client, the posts held while it is unreachable, and
+/// where and what to post of the daemon's availability.
+struct NamedBroker {
+    name: String,
+    client: AsyncClient,
+    outbox: Outbox,
+    availability: Option<Availability>,
+}
+
+struct Availability {
+    topic: String,
+    online_payload: String,
+    offline_payload: String,
+}
+
+pub struct NamedBrokers {
+    brokers: Vec<NamedBroker>,
+    /// The brokers (by index) which have become reachable again, as
+    /// told by a task watching each one's connection.
+    reconnected: (mpsc::UnboundedSender<usize>, mpsc::UnboundedReceiver<usize>),
+}
+
+impl NamedBrokers {
+    pub fn new() -> Self {
+        Self {
+            brokers: Vec::new(),
+            reconnected: mpsc::unbounded_channel(),
+        }
+    }
+
+    /// Post to a broker by name, holding posts while its connection is
+    /// down (as configured).
+    pub fn add(
+        &mut self,
+        name: String,
+        client: AsyncClient,
+        connection: &MqttConnection,
+        config: &MQTTBrokerConfig,
+    ) {
+        connection.report_reconnections(self.brokers.len(), self.reconnected.0.clone());
+        let availability = config
+            .availability_topic
+            .as_ref()
+            .map(|topic| Availability {
+                topic: topic.clone(),
+                online_payload: config.online_payload.clone(),
+                offline_payload: config.offline_payload.clone(),
+            });
+        self.brokers.push(NamedBroker {
+            name,
+            client,
+            outbox: Outbox::new(connection, config.queue_size, config.queue_drop),
+            availability,
+        });
+    }
+
+    /// Make a post to the broker of the given name, or hold it while the
+    /// broker is unreachable. Brokers not configured are never posted to
+    /// (and configurations naming them are refused as they are loaded).
+    pub async fn post(
+        &mut self,
+        name: &str,
+        post: Post,
+        failures: Option<&StatusBoard>,
+    ) -> Result<(), ClientError> {
+        match self.brokers.iter_mut().find(|broker| broker.name == name) {
+            Some(broker) => {
+                post_or_hold(&broker.client, Some(&mut broker.outbox), post, failures).await
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Wait until any of the brokers is reachable again, and tell which.
+    pub async fn reconnected(&mut self) -> usize {
+        // A sender is held, so there is always a next one to wait for.
+        self.reconnected.1.recv().await.expect("sender is held")
+    }
+
+    /// Post that the daemon is online to a broker which has become
+    /// reachable again, then make the posts held while it wasn't.
+    pub async fn rejoin(&mut self, index: usize) -> Result<(), ClientError> {
+        let NamedBroker {
+            client,
+            outbox,
+            availability,
+            ..
+        } = &mut self.brokers[index];
+        if let Some(availability) = availability.as_ref() {
+            let payload = availability.online_payload.as_bytes();
+            client
+                .publish(&availability.topic, QoS::AtLeastOnce, true, payload)
+                .await?;
+        }
+        while let Some(post) = outbox.pop() {
+            client
+                .publish(post.topic, post.qos, post.retain, post.payload)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Post that the daemon is offline, unless it is restarting, and
+    /// disconnect from each broker, as the daemon stops. (Brokers post the
+    /// last will only of clients which don't disconnect.)
+    pub async fn leave(&self, restarting: bool) {
+        for broker in self.brokers.iter() {
+            if let Some(availability) = broker.availability.as_ref().filter(|_| !restarting) {
+                let (topic, payload) = (&availability.topic, &availability.offline_payload);
+                let result =
+                    broker
+                        .client
+                        .publish(topic, QoS::AtLeastOnce, true, payload.as_bytes());
+                if let Err(e) = result.await {
+                    log::warn!("can't announce daemon offline to {}: {e}", broker.name);
+                }
+            }
+            if let Err(e) = broker.client.disconnect().await {
+                log::warn!("can't leave MQTT broker {}: {e}", broker.name);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "mqtt"))]
+mod tests {
+    use super::*;
+    use rumqttc::{Publish, Request};
+
+    fn broker(name: &str) -> (MqttConnection, MQTTBrokerConfig) {
+        let config = serde_json::json!({ "host": name, "availability_topic": "seismo/online" });
+        let config = serde_json::from_value(config).expect("valid");
+        (MqttConnection::new(60.0), config)
+    }
+
+    fn client(requests: &flume::Sender<Request>) -> AsyncClient {
+        AsyncClient::from_senders(requests.clone())
+    }
+
+    fn published(requests: &flume::Receiver<Request>) -> Vec<Publish> {
+        let publish = |request| match request {
+            Request::Publish(publish) => Some(publish),
+            _ => None,
+        };
+        requests.try_iter().filter_map(publish).collect()
+    }
+
+    fn post(topic: &str) -> Post {
+        Post {
+            topic: topic.to_owned(),
+            payload: b"triggered".to_vec(),
+            qos: QoS::AtLeastOnce,
+            retain: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn posts_to_the_named_broker() {
+        let mut brokers = NamedBrokers::new();
+        let (lab_tx, lab_rx) = flume::unbounded();
+        let (cloud_tx, cloud_rx) = flume::unbounded();
+        let (lab, lab_config) = broker("lab");
+        let (cloud, cloud_config) = broker("cloud");
+        brokers.add("lab".into(), client(&lab_tx), &lab, &lab_config);
+        brokers.add("cloud".into(), client(&cloud_tx), &cloud, &cloud_config);
+        lab.set_connected(true);
+        cloud.set_connected(true);
+        assert_eq!(brokers.reconnected().await, 0);
+        assert_eq!(brokers.reconnected().await, 1);
+
+        brokers
+            .post("cloud", post("cloud/garage"), None)
+            .await
+            .expect("posts");
+        assert!(published(&lab_rx).is_empty());
+        let posts = published(&cloud_rx);
+        assert_eq!(posts.len(), 1);
+        assert_eq!(posts[0].topic, "cloud/garage");
+
+        // While the lab broker is unreachable, its posts are held for it,
+        // and made after it is told the daemon is online again.
+        lab.set_connected(false);
+        brokers
+            .post("lab", post("lab/garage"), None)
+            .await
+            .expect("holds");
+        assert!(published(&lab_rx).is_empty());
+        lab.set_connected(true);
+        let index = brokers.reconnected().await;
+        assert_eq!(index, 0);
+        brokers.rejoin(index).await.expect("rejoins");
+        let topics: Vec<_> = published(&lab_rx).into_iter().map(|p| p.topic).collect();
+        assert_eq!(topics, ["seismo/online", "lab/garage"]);
+        assert!(published(&cloud_rx).is_empty());
+    }
+}
@@ -0,0 +1,268 @@
+//! Issuing Common Alerting Protocol (CAP 1.2) alerts about earthquakes,
+//! dropped into a directory or posted to a URL, to feed local
+//! emergency-alert tooling.
+use super::http;
+use super::placeholders::Placeholders;
+use super::retry::RetryPolicy;
+use super::status::StatusBoard;
+use crate::config::CapConfig;
+use crate::time::UtcTime;
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tokio::net::TcpStream;
+use tokio::time::Duration;
+
+const NAMESPACE: &str = "urn:oasis:names:tc:emergency:cap:1.2";
+
+/// How long to wait for the server to accept an alert.
+const POST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Error)]
+pub enum CapError {
+    #[error("CAP alert has neither a directory nor a URL to go to")]
+    NoDestination,
+    #[error("CAP URL {0} is not a plain HTTP URL")]
+    UnsupportedUrl(String),
+    #[error("unable to deliver CAP alert: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("CAP server did not respond in time")]
+    Timeout,
+    #[error("CAP server refused alert: {0}")]
+    Rejected(String),
+}
+
+/// Check that an alert has somewhere it can go.
+pub fn check_alert_config(config: &CapConfig) -> Result<(), CapError> {
+    if config.directory.is_none() && config.url.is_none() {
+        return Err(CapError::NoDestination);
+    }
+    if let Some(url) = config.url.as_ref() {
+        http::split_url(url).ok_or_else(|| CapError::UnsupportedUrl(url.clone()))?;
+    }
+    Ok(())
+}
+
+/// Issue an alert about an earthquake detected by a flow, in the
+/// background. Each delivery is retried as the actions say if it fails.
+pub fn issue_alert(
+    config: &CapConfig,
+    placeholders: &Placeholders<'_>,
+    retry: RetryPolicy,
+    failures: Option<&StatusBoard>,
+) {
+    let sent = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64();
+    let identifier = identifier(placeholders.flow, sent);
+    let document = alert(config, placeholders, &identifier, sent);
+    if let Some(directory) = config.directory.as_ref() {
+        let path = directory.join(format!("{identifier}.xml"));
+        let document = document.clone();
+        let failures = failures.cloned();
+        tokio::spawn(async move {
+            let what = format!("CAP alert {}", path.display());
+            let _ = retry
+                .attempt(&what, failures.as_ref(), || drop_file(&path, &document))
+                .await;
+        });
+    }
+    if let Some((address, path)) = config.url.as_deref().and_then(http::split_url) {
+        let path = if path.is_empty() {
+            String::from("/")
+        } else {
+            path
+        };
+        let failures = failures.cloned();
+        tokio::spawn(async move {
+            let what = format!("CAP alert {identifier} to {address}");
+            let _ = retry
+                .attempt(&what, failures.as_ref(), || {
+                    post(&address, &path, &document)
+                })
+                .await;
+        });
+    }
+}
+
+/// Write an alert file under a temporary name, then rename it into
+/// place, so that nothing watching the directory sees half of it.
+async fn drop_file(path: &Path, document: &str) -> Result<(), CapError> {
+    let mut partial = PathBuf::from(path);
+    partial.set_extension("xml.partial");
+    tokio::fs::write(&partial, document).await?;
+    tokio::fs::rename(&partial, path).await?;
+    Ok(())
+}
+
+async fn post(address: &str, path: &str, document: &str) -> Result<(), CapError> {
+    let exchange = async {
+        let stream = TcpStream::connect(address).await?;
+        let content_type = "application/cap+xml; charset=utf-8";
+        http::post(
+            stream,
+            address,
+            path,
+            &[],
+            content_type,
+            document.as_bytes(),
+        )
+        .await
+    };
+    let status = tokio::time::timeout(POST_TIMEOUT, exchange)
+        .await
+        .map_err(|_| CapError::Timeout)??;
+    if !http::is_success(&status) {
+        return Err(CapError::Rejected(status));
+    }
+    Ok(())
+}
+
+/// An identifier for an alert, unique to its sender, which is also
+/// usable as a file name.
+fn identifier(flow: &str, sent: f64) -> String {
+    let flow: String = flow
+        .chars()
+        .map(|c| match c {
+            'A'..='Z' | 'a'..='z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '_',
+        })
+        .collect();
+    format!("{flow}-{}", (sent * 1000.0) as u64)
+}
+
+/// A time as CAP writes it: to the second, with UTC as "-00:00".
+fn cap_time(timestamp: f64) -> String {
+    let t = UtcTime::from_epoch(timestamp.floor());
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}-00:00",
+        t.year, t.month, t.day, t.hour, t.minute, t.second
+    )
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The alert document. Its onset is the data time of the event.
+fn alert(
+    config: &CapConfig,
+    placeholders: &Placeholders<'_>,
+    identifier: &str,
+    sent: f64,
+) -> String {
+    let element = |indent: usize, name: &str, value: &str| {
+        format!("{:indent$}<{name}>{}</{name}>\n", "", escape(value))
+    };
+    let mut info = String::new();
+    info.push_str(&element(4, "category", "Geo"));
+    info.push_str(&element(4, "event", &config.event));
+    info.push_str(&element(4, "urgency", &format!("{:?}", config.urgency)));
+    info.push_str(&element(4, "severity", &format!("{:?}", config.severity)));
+    info.push_str(&element(4, "certainty", &format!("{:?}", config.certainty)));
+    info.push_str(&element(4, "onset", &cap_time(placeholders.timestamp)));
+    let expires = sent + f64::from(config.expires_s);
+    info.push_str(&element(4, "expires", &cap_time(expires)));
+    let headline = placeholders.expand(&config.headline);
+    info.push_str(&element(4, "headline", &headline));
+    for (name, text) in [
+        ("description", config.description.as_ref()),
+        ("instruction", config.instruction.as_ref()),
+    ] {
+        if let Some(text) = text {
+            info.push_str(&element(4, name, &placeholders.expand(text)));
+        }
+    }
+    let energy = placeholders.energy.map(|e| e.to_string());
+    let parameters = [
+        ("flow", Some(placeholders.flow)),
+        ("seismometer", placeholders.seismometer),
+        ("channel", placeholders.channel),
+        ("energy", energy.as_deref()),
+    ];
+    for (name, value) in parameters {
+        if let Some(value) = value {
+            info.push_str("    <parameter>\n");
+            info.push_str(&element(6, "valueName", name));
+            info.push_str(&element(6, "value", value));
+            info.push_str("    </parameter>\n");
+        }
+    }
+    info.push_str("    <area>\n");
+    let area_desc = placeholders.expand(&config.area_desc);
+    info.push_str(&element(6, "areaDesc", &area_desc));
+    if let Some(polygon) = config.polygon.as_ref() {
+        info.push_str(&element(6, "polygon", polygon));
+    }
+    if let Some(circle) = config.circle.as_ref() {
+        info.push_str(&element(6, "circle", circle));
+    }
+    info.push_str("    </area>\n");
+    let mut alert = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    alert.push_str(&format!("<alert xmlns=\"{NAMESPACE}\">\n"));
+    alert.push_str(&element(2, "identifier", identifier));
+    alert.push_str(&element(2, "sender", &config.sender));
+    alert.push_str(&element(2, "sent", &cap_time(sent)));
+    alert.push_str(&element(2, "status", &format!("{:?}", config.status)));
+    alert.push_str(&element(2, "msgType", "Alert"));
+    alert.push_str(&element(2, "scope", "Public"));
+    alert.push_str("  <info>\n");
+    alert.push_str(&info);
+    alert.push_str("  </info>\n");
+    alert.push_str("</alert>\n");
+    alert
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_cap_alerts() {
+        let config: CapConfig = serde_json::from_str(
+            r#"{"directory": "/tmp", "severity": "Extreme", "expires_s": 600,
+                "description": "Shaking at {seismometer} & nearby",
+                "area_desc": "Garage", "circle": "37.8,-122.3 5"}"#,
+        )
+        .expect("parse");
+        let placeholders = Placeholders {
+            flow: "garage floor",
+            seismometer: Some("rs1"),
+            timestamp: 1700000000.75,
+            energy: Some(1500.0),
+            ..Default::default()
+        };
+        let sent = 1700000002.5;
+        let id = identifier(placeholders.flow, sent);
+        assert_eq!(id, "garage_floor-1700000002500");
+        let document = alert(&config, &placeholders, &id, sent);
+        assert!(document.contains("<sent>2023-11-14T22:13:22-00:00</sent>\n"));
+        assert!(document.contains("<status>Actual</status>\n"));
+        assert!(document.contains("<severity>Extreme</severity>\n"));
+        assert!(document.contains("<certainty>Likely</certainty>\n"));
+        assert!(document.contains("<onset>2023-11-14T22:13:20-00:00</onset>\n"));
+        assert!(document.contains("<expires>2023-11-14T22:23:22-00:00</expires>\n"));
+        assert!(document.contains("<headline>Earthquake detected by garage floor</headline>"));
+        assert!(document.contains("<description>Shaking at rs1 &amp; nearby</description>"));
+        assert!(!document.contains("<instruction>"));
+        assert!(!document.contains("<valueName>channel</valueName>"));
+        assert!(document.contains(
+            "<valueName>energy</valueName>\n      <value>1500</value>\n    </parameter>"
+        ));
+        assert!(document.contains(
+            "<areaDesc>Garage</areaDesc>\n      <circle>37.8,-122.3 5</circle>\n    </area>"
+        ));
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+use crate::config::CaptureConfig;
+use crate::datasource::SeismoData;
+use crate::time::UtcTime;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum CaptureError {
+    #[error("unable to write waveform capture file")]
+    WriteError(#[from] std::io::Error),
+}
+
+/// Saves the input waveform around each trigger of a flow to a text file,
+/// in the same "time value" format that the text file data source reads.
+pub struct WaveformCapture {
+    directory: PathBuf,
+    flow_name: String,
+    sample_rate_hz: f32,
+    pre_trigger_s: f32,
+    post_trigger_s: f64,
+    active: Option<ActiveCapture>,
+}
+
+struct ActiveCapture {
+    file: BufWriter<File>,
+    path: PathBuf,
+
+    /// Data time after which the capture ends, once the trigger has reset.
+    until: Option<f64>,
+}
+
+impl WaveformCapture {
+    pub fn from_config(config: &CaptureConfig, flow_name: &str, sample_rate_hz: f32) -> Self {
+        Self {
+            directory: config.directory.clone(),
+            flow_name: flow_name.to_owned(),
+            sample_rate_hz,
+            pre_trigger_s: config.pre_trigger_s,
+            post_trigger_s: config.post_trigger_s as f64,
+            active: None,
+        }
+    }
+
+    /// Seconds of history that must be retained for pre-trigger capture.
+    pub fn pre_trigger_s(&self) -> f32 {
+        self.pre_trigger_s
+    }
+
+    /// Observe a packet that has been processed by the flow, given the
+    /// flow's trigger state after processing. `history` holds recently
+    /// received packets for the channel, including this one. Returns the
+    /// path of the capture file, if this packet finished it.
+    pub fn observe(
+        &mut self,
+        input: &SeismoData,
+        triggered: bool,
+        history: &VecDeque<SeismoData>,
+    ) -> Result<Option<PathBuf>, CaptureError> {
+        let period = 1.0 / self.sample_rate_hz as f64;
+        match self.active.as_mut() {
+            Some(active) => write_packet(&mut active.file, input, period)?,
+            None if triggered => {
+                let name = format!(
+                    "{}-{}.txt",
+                    self.flow_name,
+                    UtcTime::from_epoch(input.timestamp).compact()
+                );
+                let path = self.directory.join(name);
+                let mut file = BufWriter::new(File::create(&path)?);
+                for packet in history {
+                    write_packet(&mut file, packet, period)?;
+                }
+                self.active = Some(ActiveCapture {
+                    file,
+                    path,
+                    until: None,
+                });
+            }
+            None => return Ok(None),
+        }
+        let Some(active) = self.active.as_mut() else {
+            return Ok(None);
+        };
+        if triggered {
+            active.until = None;
+        } else if active.until.is_none() {
+            active.until = Some(input.timestamp + self.post_trigger_s);
+        }
+        if active.until.is_some_and(|until| input.timestamp >= until) {
+            if let Some(mut finished) = self.active.take() {
+                finished.file.flush()?;
+                return Ok(Some(finished.path));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Finish the capture in progress, if any, without waiting for its
+    /// post-trigger time. Returns the path of the capture file.
+    pub fn finish(&mut self) -> Result<Option<PathBuf>, CaptureError> {
+        let Some(mut finished) = self.active.take() else {
+            return Ok(None);
+        };
+        finished.file.flush()?;
+        Ok(Some(finished.path))
+    }
+}
+
+fn write_packet(
+    file: &mut BufWriter<File>,
+    packet: &SeismoData,
+    period: f64,
+) -> std::io::Result<()> {
+    for (i, v) in packet.data.iter().enumerate() {
+        let t = packet.timestamp + i as f64 * period;
+        writeln!(file, "{t:.3} {v}")?;
+    }
+    Ok(())
+}
@@ -0,0 +1,37 @@
+//! Stand-in for waveform capture when built without the "recorders"
+//! feature. Configurations that ask for capture are rejected at startup,
+//! so this never has anything to do.
+use std::collections::VecDeque;
+use std::path::PathBuf;
+
+use crate::config::CaptureConfig;
+use crate::datasource::SeismoData;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum CaptureError {}
+
+pub struct WaveformCapture;
+
+impl WaveformCapture {
+    pub fn from_config(_config: &CaptureConfig, _flow_name: &str, _sample_rate_hz: f32) -> Self {
+        WaveformCapture
+    }
+
+    pub fn pre_trigger_s(&self) -> f32 {
+        0.0
+    }
+
+    pub fn observe(
+        &mut self,
+        _input: &SeismoData,
+        _triggered: bool,
+        _history: &VecDeque<SeismoData>,
+    ) -> Result<Option<PathBuf>, CaptureError> {
+        Ok(None)
+    }
+
+    pub fn finish(&mut self) -> Result<Option<PathBuf>, CaptureError> {
+        Ok(None)
+    }
+}
@@ -0,0 +1,108 @@
+use crate::datasource::{Channel, SeismoData};
+
+/// Watches the offset between a seismometer's packet timestamps and the
+/// host clock. Transit delays only ever make packets look late, so the
+/// smallest offset seen over an interval is taken as the clock offset.
+pub struct ClockDriftMonitor {
+    threshold_s: f64,
+    /// The sample rates of the channels watched, which each may be sent
+    /// at a rate of its own.
+    sample_rate_hz_by_channel: Vec<Option<f64>>,
+    min_offset_s: Option<f64>,
+    drifting: bool,
+}
+
+impl ClockDriftMonitor {
+    pub fn new(threshold_s: f32) -> Self {
+        Self {
+            threshold_s: threshold_s as f64,
+            sample_rate_hz_by_channel: vec![None; Channel::max()],
+            min_offset_s: None,
+            drifting: false,
+        }
+    }
+
+    /// Watch a channel, sent at the given sample rate.
+    pub fn track_channel(&mut self, channel: Channel, sample_rate_hz: f32) {
+        self.sample_rate_hz_by_channel[channel as usize] = Some(sample_rate_hz as f64);
+    }
+
+    /// Note a packet's arrival at host time `now` (seconds since the
+    /// epoch). The packet can't have been sent before its last sample was
+    /// taken, so that is what is compared against. Packets on channels
+    /// not watched are ignored.
+    pub fn observe(&mut self, data: &SeismoData, now: f64) {
+        let Some(sample_rate_hz) = self.sample_rate_hz_by_channel[data.channel as usize] else {
+            return;
+        };
+        if data.timestamp <= 0.0 {
+            return;
+        }
+        let end = data.timestamp + data.data.len() as f64 / sample_rate_hz;
+        let offset = now - end;
+        self.min_offset_s = Some(self.min_offset_s.map_or(offset, |min| min.min(offset)));
+    }
+
+    /// Conclude an interval. Returns the clock offset (host time minus
+    /// seismometer time) if it has just gone beyond the threshold.
+    pub fn check(&mut self) -> Option<f64> {
+        let offset = self.min_offset_s.take()?;
+        let was_drifting = self.drifting;
+        self.drifting = offset.abs() > self.threshold_s;
+        (self.drifting && !was_drifting).then_some(offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::Array1;
+
+    fn packet_on(channel: Channel, timestamp: f64) -> SeismoData {
+        SeismoData {
+            timestamp,
+            channel,
+            data: Array1::zeros(25),
+        }
+    }
+
+    fn packet(timestamp: f64) -> SeismoData {
+        packet_on(Channel::Ehz, timestamp)
+    }
+
+    #[test]
+    fn reports_once_per_excursion() {
+        let mut monitor = ClockDriftMonitor::new(1.0);
+        monitor.track_channel(Channel::Ehz, 100.0);
+        monitor.observe(&packet(1000.0), 1000.3);
+        assert_eq!(monitor.check(), None);
+
+        // A seismometer clock running five seconds slow, with jitter.
+        monitor.observe(&packet(1000.0), 1005.5);
+        monitor.observe(&packet(1000.25), 1005.3);
+        let offset = monitor.check().unwrap();
+        assert!((offset - 4.8).abs() < 1e-9);
+
+        monitor.observe(&packet(1000.5), 1005.8);
+        assert_eq!(monitor.check(), None);
+        monitor.observe(&packet(1000.75), 1001.1);
+        assert_eq!(monitor.check(), None);
+        monitor.observe(&packet(1001.0), 999.0);
+        assert!(monitor.check().is_some());
+    }
+
+    #[test]
+    fn times_packets_by_their_channels_rates() {
+        let mut monitor = ClockDriftMonitor::new(1.0);
+        monitor.track_channel(Channel::Ehz, 100.0);
+        monitor.track_channel(Channel::Enz, 10.0);
+        // 25 samples at 10 Hz end two and a half seconds in, so this
+        // packet is only half a second late (where at 100 Hz it would be
+        // nearly three seconds).
+        monitor.observe(&packet_on(Channel::Enz, 1000.0), 1003.0);
+        assert_eq!(monitor.check(), None);
+        // Channels not watched are left out.
+        monitor.observe(&packet_on(Channel::Enn, 1000.0), 2000.0);
+        assert_eq!(monitor.check(), None);
+    }
+}
@@ -0,0 +1,245 @@
+//! Coincidence triggering: an aggregate trigger which fires only when
+//! enough of its member flows trigger at around the same time. Used both
+//! across the flows of one seismometer and, with flows grouped by
+//! seismometer, across a network of them.
+use crate::config::{CoincidenceConfig, NetworkTriggerConfig};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum CoincidenceError {
+    #[error("coincidence {0}: no such flow {1}")]
+    Missing(String, String),
+    #[error("network trigger {0}: no such flow {1}")]
+    MissingFromNetwork(String, String),
+    #[error("network trigger {0}: flow name {1} is used on more than one seismometer")]
+    Ambiguous(String, String),
+}
+
+pub struct Coincidence {
+    /// Member flows, and the group each belongs to. Triggers are counted
+    /// per group, so that (say) several flows on one seismometer count
+    /// as only one station.
+    groups: HashMap<usize, usize>,
+    min_groups: usize,
+    window: Duration,
+
+    /// Members which are currently triggered, and the data time at which
+    /// each member last triggered.
+    triggered: HashSet<usize>,
+    onsets: HashMap<usize, f64>,
+
+    asserted: bool,
+}
+
+impl Coincidence {
+    /// Fire when members from at least `min_groups` groups are triggered
+    /// or have triggered within the last `window`.
+    pub fn new(min_groups: usize, window: Duration) -> Self {
+        Self {
+            groups: HashMap::new(),
+            min_groups,
+            window,
+            triggered: HashSet::new(),
+            onsets: HashMap::new(),
+            asserted: false,
+        }
+    }
+
+    /// A trigger which fires as soon as any member triggers, and resets
+    /// once none is triggered, as for the global actions.
+    pub fn of_any() -> Self {
+        Self::new(1, Duration::ZERO)
+    }
+
+    /// A coincidence trigger over some of a seismometer's flows (whose ids
+    /// are given by name), each of which counts on its own. The
+    /// seismometer's disabled flows are left out.
+    pub fn of_flows(
+        config: &CoincidenceConfig,
+        flow_ids: &HashMap<&str, usize>,
+        disabled: &HashSet<&str>,
+    ) -> Result<Self, CoincidenceError> {
+        let window = Duration::from_secs_f32(config.window_s);
+        let mut coincidence = Self::new(config.min_flows, window);
+        for name in config.flows.iter() {
+            match flow_ids.get(name.as_str()) {
+                Some(&flow_id) => coincidence.add_member(flow_id, flow_id),
+                None if disabled.contains(name.as_str()) => continue,
+                None => {
+                    let name = name.clone();
+                    return Err(CoincidenceError::Missing(config.name.clone(), name));
+                }
+            }
+        }
+        coincidence.warn_if_unreachable(&format!("coincidence {}", config.name));
+        Ok(coincidence)
+    }
+
+    /// A coincidence trigger over flows from any seismometers (whose ids
+    /// and seismometer indexes are given by name, unless the name is used
+    /// on more than one), counting the flows of each seismometer together
+    /// as one station. Flows which are disabled, and not run on another
+    /// seismometer, are left out.
+    pub fn of_stations(
+        config: &NetworkTriggerConfig,
+        network_flows: &HashMap<&str, Option<(usize, usize)>>,
+        disabled: &HashSet<&str>,
+    ) -> Result<Self, CoincidenceError> {
+        let window = Duration::from_secs_f32(config.window_s);
+        let mut coincidence = Self::new(config.min_stations, window);
+        for name in config.flows.iter() {
+            let (trigger, flow) = (config.name.clone(), name.clone());
+            match network_flows.get(name.as_str()) {
+                Some(Some((flow_id, station))) => coincidence.add_member(*flow_id, *station),
+                Some(None) => return Err(CoincidenceError::Ambiguous(trigger, flow)),
+                None if disabled.contains(name.as_str()) => continue,
+                None => return Err(CoincidenceError::MissingFromNetwork(trigger, flow)),
+            }
+        }
+        coincidence.warn_if_unreachable(&format!("network trigger {}", config.name));
+        Ok(coincidence)
+    }
+
+    pub fn add_member(&mut self, flow_id: usize, group: usize) {
+        self.groups.insert(flow_id, group);
+    }
+
+    /// Whether there are members from enough groups for it ever to fire.
+    pub fn can_fire(&self) -> bool {
+        let groups: HashSet<usize> = self.groups.values().copied().collect();
+        groups.len() >= self.min_groups
+    }
+
+    // Warn of a trigger left with too few members to fire, as when some
+    // of its flows are disabled.
+    fn warn_if_unreachable(&self, what: &str) {
+        if !self.can_fire() {
+            log::warn!(
+                "{what}: too few of its flows are enabled for it ever to fire (it needs {})",
+                self.min_groups
+            );
+        }
+    }
+
+    /// Note a member flow's trigger state as of a data time `at`, which
+    /// the window is measured in (so that data handled late, as from a
+    /// backlog or an archive, is voted on as it was recorded). Returns the
+    /// aggregate trigger state if it has changed. Once fired, it resets
+    /// when no member remains triggered.
+    pub fn update(&mut self, flow_id: usize, triggered: bool, at: f64) -> Option<bool> {
+        if !self.groups.contains_key(&flow_id) {
+            return None;
+        }
+        if triggered {
+            self.triggered.insert(flow_id);
+            self.onsets.insert(flow_id, at);
+        } else {
+            self.triggered.remove(&flow_id);
+        }
+
+        if !self.asserted {
+            let window_s = self.window.as_secs_f64();
+            let active: HashSet<usize> = self
+                .groups
+                .iter()
+                .filter(|(flow_id, _)| {
+                    self.triggered.contains(flow_id)
+                        || self
+                            .onsets
+                            .get(flow_id)
+                            .is_some_and(|onset| (at - onset).abs() <= window_s)
+                })
+                .map(|(_, group)| *group)
+                .collect();
+            if active.len() >= self.min_groups {
+                self.asserted = true;
+                return Some(true);
+            }
+        } else if self.triggered.is_empty() {
+            self.asserted = false;
+            return Some(false);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn needs_enough_groups() {
+        let at = |s: u32| f64::from(s);
+        let mut coincidence = Coincidence::new(2, Duration::from_secs(5));
+        coincidence.add_member(0, 0);
+        coincidence.add_member(1, 0);
+        coincidence.add_member(2, 1);
+
+        // Two flows in one group aren't enough.
+        assert_eq!(coincidence.update(0, true, at(0)), None);
+        assert_eq!(coincidence.update(1, true, at(1)), None);
+        assert_eq!(coincidence.update(0, false, at(2)), None);
+        assert_eq!(coincidence.update(1, false, at(2)), None);
+
+        // Nor is a second group outside the window.
+        assert_eq!(coincidence.update(2, true, at(10)), None);
+        assert_eq!(coincidence.update(2, false, at(11)), None);
+
+        // But one within it is, even after the first has reset.
+        assert_eq!(coincidence.update(0, true, at(20)), None);
+        assert_eq!(coincidence.update(0, false, at(21)), None);
+        assert_eq!(coincidence.update(2, true, at(22)), Some(true));
+        assert_eq!(coincidence.update(5, true, at(22)), None);
+        assert_eq!(coincidence.update(2, false, at(30)), Some(false));
+    }
+
+    #[test]
+    fn votes_in_data_time() {
+        let mut coincidence = Coincidence::new(2, Duration::from_secs(5));
+        coincidence.add_member(0, 0);
+        coincidence.add_member(1, 1);
+
+        // Triggers drained together from a backlog, but recorded a minute
+        // apart, aren't coincident.
+        assert_eq!(coincidence.update(0, true, 1000.0), None);
+        assert_eq!(coincidence.update(0, false, 1001.0), None);
+        assert_eq!(coincidence.update(1, true, 1060.0), None);
+        assert_eq!(coincidence.update(1, false, 1061.0), None);
+
+        // A slower station reporting an onset from before the faster one's
+        // still votes with it.
+        assert_eq!(coincidence.update(0, true, 2003.0), None);
+        assert_eq!(coincidence.update(0, false, 2004.0), None);
+        assert_eq!(coincidence.update(1, true, 2000.0), Some(true));
+        assert_eq!(coincidence.update(1, false, 2010.0), Some(false));
+    }
+
+    #[test]
+    fn skips_disabled_flows() {
+        let config: CoincidenceConfig = serde_json::from_value(serde_json::json!({
+            "name": "garage",
+            "flows": ["z", "n", "e"],
+            "min_flows": 2,
+            "actions": {},
+        }))
+        .expect("valid");
+        let flow_ids = HashMap::from([("z", 0), ("e", 2)]);
+        let coincidence = Coincidence::of_flows(&config, &flow_ids, &HashSet::from(["n"]));
+        let mut coincidence = coincidence.expect("skips n");
+        assert!(coincidence.can_fire());
+        assert_eq!(coincidence.update(1, true, 0.0), None);
+        assert_eq!(coincidence.update(0, true, 0.0), None);
+        assert_eq!(coincidence.update(2, true, 0.0), Some(true));
+
+        // A flow disabled only on another seismometer is still missing.
+        let refused = Coincidence::of_flows(&config, &flow_ids, &HashSet::new());
+        assert!(matches!(refused, Err(CoincidenceError::Missing(..))));
+
+        // With two of three disabled, it can never fire.
+        let flow_ids = HashMap::from([("z", 0)]);
+        let coincidence = Coincidence::of_flows(&config, &flow_ids, &HashSet::from(["n", "e"]));
+        assert!(!coincidence.expect("skips n and e").can_fire());
+    }
+}
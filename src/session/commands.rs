@@ -0,0 +1,208 @@
+//! Running the executables that actions call for in the background, so
+//! that one which hangs doesn't hold up the rest of the actions.
+use super::placeholders::Placeholders;
+use super::retry::RetryPolicy;
+use super::status::StatusBoard;
+use crate::config::ActionsConfig;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+use tokio::process::Command;
+use tokio::sync::{oneshot, Semaphore};
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+
+#[derive(Debug, Error)]
+pub enum CommandError {
+    #[error("failed to execute: {0}")]
+    Spawn(#[source] std::io::Error),
+    #[error("{0}")]
+    Exit(ExitStatus),
+    #[error("ran for longer than {0:?}, killed")]
+    Timeout(Duration),
+}
+
+#[derive(Clone)]
+pub struct CommandRunner {
+    /// Permits for the executables which may run at once.
+    running: Arc<Semaphore>,
+    /// Where to count executables which fail for good, if anywhere.
+    failures: Option<StatusBoard>,
+    /// For each thing whose events executables are run for, when the
+    /// last one started for it is done with (its sender being dropped).
+    last_done: Arc<Mutex<HashMap<String, oneshot::Receiver<()>>>>,
+}
+
+impl CommandRunner {
+    /// Run at most `max_running` executables at once.
+    pub fn new(max_running: usize) -> Self {
+        Self {
+            running: Arc::new(Semaphore::new(max_running)),
+            failures: None,
+            last_done: Arc::default(),
+        }
+    }
+
+    /// Run at most `max_running` executables at once, from now on.
+    pub fn limit(&mut self, max_running: usize) {
+        self.running = Arc::new(Semaphore::new(max_running));
+    }
+
+    /// Count executables which fail, even after any retries, on a status
+    /// board.
+    pub fn report_failures(&mut self, status: StatusBoard) {
+        self.failures = Some(status);
+    }
+
+    /// Start an executable, if so configured, with some arguments (the
+    /// first of which names the event), then some extra ones, and the
+    /// event's details in its environment. It is killed if it runs for
+    /// longer than the actions' timeout (if any), and retried as they
+    /// say if it fails. It is not started at all if as many executables
+    /// as may run at once are already running. Executables for the same
+    /// thing (named by the second argument, as a flow) run one after
+    /// another, in the order they were started, so that one for a reset
+    /// doesn't overtake the one for the trigger before it.
+    pub fn run<const N: usize>(
+        &self,
+        actions: &ActionsConfig,
+        cmd: &Option<PathBuf>,
+        args: [&str; N],
+        placeholders: &Placeholders<'_>,
+        extra: &[String],
+    ) -> Option<JoinHandle<()>> {
+        let path = cmd.as_ref()?;
+        let event = args.first().copied().unwrap_or_default();
+        let Ok(permit) = self.running.clone().try_acquire_owned() else {
+            log::warn!(
+                "too many executables running, not running {} for {event}",
+                path.display()
+            );
+            return None;
+        };
+        let path = path.clone();
+        let args: Vec<String> = args
+            .iter()
+            .map(|&arg| arg.to_owned())
+            .chain(extra.iter().cloned())
+            .collect();
+        let env = placeholders.environment(event);
+        let timeout = actions.cmd_timeout_s.map(Duration::from_secs_f32);
+        let retry = RetryPolicy::new(actions);
+        let what = format!("{} for {event}", path.display());
+        let failures = self.failures.clone();
+        let (done, now_done) = oneshot::channel();
+        let name = args.get(1).cloned().unwrap_or_default();
+        let last_done = self
+            .last_done
+            .lock()
+            .expect("not poisoned")
+            .insert(name, now_done);
+        let task = tokio::spawn(async move {
+            let _permit = permit;
+            let _done = done;
+            if let Some(last_done) = last_done {
+                // Whether it finished or was dropped, it is done with.
+                let _ = last_done.await;
+            }
+            let _ = retry
+                .attempt(&what, failures.as_ref(), || {
+                    let mut command = Command::new(&path);
+                    command.args(&args).envs(env.clone()).kill_on_drop(true);
+                    execute(command, timeout)
+                })
+                .await;
+        });
+        Some(task)
+    }
+}
+
+/// Run a command to completion, killing it if it runs for longer than a
+/// timeout (if any).
+async fn execute(mut command: Command, timeout: Option<Duration>) -> Result<(), CommandError> {
+    let mut child = command.spawn().map_err(CommandError::Spawn)?;
+    let status = match timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, child.wait()).await {
+            Ok(status) => status,
+            Err(_) => {
+                let _ = child.kill().await;
+                return Err(CommandError::Timeout(timeout));
+            }
+        },
+        None => child.wait().await,
+    };
+    match status.map_err(CommandError::Spawn)? {
+        status if status.success() => Ok(()),
+        status => Err(CommandError::Exit(status)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The shell, to run scripts of its builtins, which every system has.
+    fn sh() -> Option<PathBuf> {
+        Some(PathBuf::from("/bin/sh"))
+    }
+
+    #[tokio::test]
+    async fn limits_retries_and_times_out() {
+        let mut runner = CommandRunner::new(1);
+        let board = StatusBoard::new();
+        runner.report_failures(board.clone());
+        let actions: ActionsConfig =
+            serde_json::from_str(r#"{"cmd_timeout_s": 0.1, "retries": 1, "retry_s": 0.01}"#)
+                .expect("parse");
+        let placeholders = Placeholders::now("flow");
+        let hang = ["-c", "while :; do :; done"];
+        let started = tokio::time::Instant::now();
+        let first = runner.run(&actions, &sh(), hang, &placeholders, &[]);
+        let second = runner.run(&actions, &sh(), hang, &placeholders, &[]);
+        assert!(second.is_none());
+        first.expect("runs").await.expect("finishes");
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(board.snapshot().failed_actions, 1);
+        let third = runner.run(&actions, &sh(), ["-c", "exit 0"], &placeholders, &[]);
+        third.expect("runs").await.expect("finishes");
+        assert_eq!(board.snapshot().failed_actions, 1);
+    }
+
+    #[tokio::test]
+    async fn runs_a_things_executables_in_order() {
+        let runner = CommandRunner::new(2);
+        let actions: ActionsConfig = serde_json::from_str("{}").expect("parse");
+        let placeholders = Placeholders::now("flow");
+        let log = std::env::temp_dir().join(format!("rs-udp-commands-{}", std::process::id()));
+        let log = log.to_str().expect("UTF-8");
+        // Both are run for the same thing (the script), and the first
+        // takes longer to get to writing its event down.
+        let script = r#"
+            if [ "$1" = triggered ]; then
+                i=0; while [ $i -lt 20000 ]; do i=$((i + 1)); done
+            fi
+            echo "$1" >> "$0""#;
+        let triggered = runner.run(
+            &actions,
+            &sh(),
+            ["-c", script, log, "triggered"],
+            &placeholders,
+            &[],
+        );
+        let reset = runner.run(
+            &actions,
+            &sh(),
+            ["-c", script, log, "reset"],
+            &placeholders,
+            &[],
+        );
+        triggered.expect("runs").await.expect("finishes");
+        reset.expect("runs").await.expect("finishes");
+        let written = std::fs::read_to_string(log).expect("reads");
+        std::fs::remove_file(log).expect("removes");
+        assert_eq!(written, "triggered\nreset\n");
+    }
+}
@@ -0,0 +1,520 @@
+//! A local control socket, through which operators may inspect and adjust
+//! a running daemon from the shell (with the "ctl" command) even when
+//! neither MQTT nor HTTP is enabled. Each connection carries one request
+//! and its reply, as lines of JSON, and connections are served side by
+//! side. There is no control socket but on Unix.
+use super::armed::{ArmedSwitch, Command, CommandSender};
+use super::status::StatusBoard;
+use super::tuning::{Tuning, TuningSender};
+use crate::config::ControlConfig;
+
+use clap::Subcommand;
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::broadcast;
+use tokio::task::JoinSet;
+use tokio::time::Duration;
+
+/// How long a client has to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The most of a request which is read.
+const MAX_REQUEST: u64 = 4096;
+
+#[derive(Debug, Error)]
+pub enum ControlError {
+    #[error("unable to listen on {0}: {1}")]
+    Bind(PathBuf, #[source] std::io::Error),
+    #[error("{0} is already in use by a running daemon")]
+    InUse(PathBuf),
+    #[error("unable to connect to {0}: {1}")]
+    Connect(PathBuf, #[source] std::io::Error),
+    #[error("control connection failed")]
+    Io(#[from] std::io::Error),
+    #[error("malformed request or reply")]
+    Malformed(#[from] serde_json::Error),
+    #[error("no such flow {0}")]
+    NoSuchFlow(String),
+    #[error("unable to write dump file {0}: {1}")]
+    Dump(PathBuf, #[source] std::io::Error),
+    #[error("no dump directory is configured")]
+    NoDumpDir,
+    #[error("dump file {0} must be a plain name within the dump directory")]
+    DumpOutside(PathBuf),
+    #[error("the session is stopping")]
+    Stopping,
+    #[error("control sockets are only supported on Unix")]
+    Unsupported,
+}
+
+/// A request of the daemon. These are also the "ctl" command's commands.
+#[derive(Debug, Clone, PartialEq, Subcommand, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum ControlRequest {
+    /// Print the daemon's status, as JSON.
+    Status,
+    /// Arm the session, or just one flow.
+    Arm {
+        /// Flow to arm, rather than the whole session.
+        flow: Option<String>,
+        /// Seismometer of the flow, if more than one has a flow so named.
+        #[arg(long, requires = "flow")]
+        seismometer: Option<String>,
+    },
+    /// Disarm the session, or just one flow.
+    Disarm {
+        /// Flow to disarm, rather than the whole session.
+        flow: Option<String>,
+        /// Seismometer of the flow, if more than one has a flow so named.
+        #[arg(long, requires = "flow")]
+        seismometer: Option<String>,
+    },
+    /// Change a flow's trigger and/or reset levels.
+    SetThreshold {
+        flow: String,
+        /// Seismometer of the flow, if more than one has a flow so named.
+        #[arg(long)]
+        seismometer: Option<String>,
+        #[arg(long, required_unless_present = "reset_level")]
+        trigger_level: Option<f32>,
+        #[arg(long)]
+        reset_level: Option<f32>,
+    },
+    /// Start dumping a flow's filter process to a file, as -o does.
+    DumpStart {
+        flow: String,
+        /// New file to dump to, within the daemon's dump directory.
+        path: PathBuf,
+        /// Seismometer of the flow, if more than one has a flow so named.
+        #[arg(long)]
+        seismometer: Option<String>,
+    },
+}
+
+/// The daemon's reply to a request.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ControlReply {
+    /// What was done.
+    Done(String),
+    Status(serde_json::Value),
+    Error(String),
+}
+
+/// A request to dump a flow's (by id) filter process to a file.
+#[derive(Clone, Debug)]
+pub struct DumpRequest {
+    pub flow_id: usize,
+    pub path: PathBuf,
+}
+
+/// Dump requests for every flow, which each instrument loop picks its
+/// own flows' out of.
+pub type DumpReceiver = broadcast::Receiver<DumpRequest>;
+
+pub struct ControlServer {
+    listener: Listener,
+    handler: Handler,
+}
+
+/// The socket listened on, and its file.
+#[cfg(unix)]
+struct Listener {
+    socket: UnixListener,
+    _file: SocketFile,
+}
+
+/// A connection to the socket.
+#[cfg(unix)]
+type Stream = UnixStream;
+
+/// There is nothing to listen on but on Unix.
+#[cfg(not(unix))]
+enum Listener {}
+
+#[cfg(not(unix))]
+type Stream = tokio::io::DuplexStream;
+
+/// What requests are handled with, which each connection shares.
+struct Handler {
+    status: StatusBoard,
+    armed: ArmedSwitch,
+    /// Flows' names and their seismometers' names, by flow id.
+    flows: Vec<(String, String, usize)>,
+    commands: Option<CommandSender>,
+    tuning: Option<TuningSender>,
+    dumps: broadcast::Sender<DumpRequest>,
+    dump_dir: Option<PathBuf>,
+}
+
+impl ControlServer {
+    /// Listen where configured, to report what is on a status board and
+    /// flip an armed switch. A socket left behind by a daemon which
+    /// didn't stop cleanly is replaced, but not one still in use.
+    pub async fn bind(
+        config: &ControlConfig,
+        status: StatusBoard,
+        armed: ArmedSwitch,
+    ) -> Result<Self, ControlError> {
+        Ok(Self {
+            listener: Listener::bind(&config.socket).await?,
+            handler: Handler {
+                status,
+                armed,
+                flows: Vec::new(),
+                commands: None,
+                tuning: None,
+                dumps: broadcast::channel(16).0,
+                dump_dir: config.dump_dir.clone(),
+            },
+        })
+    }
+
+    /// Accept requests for flows, by name and seismometer name, passing
+    /// commands for them on to the action loop and adjustments of their
+    /// levels on to the instrument loops.
+    pub fn control_flows(
+        &mut self,
+        flows: Vec<(&str, &str, usize)>,
+        commands: CommandSender,
+        tuning: TuningSender,
+    ) {
+        let flows = flows.into_iter();
+        self.handler.flows = flows
+            .map(|(name, seismometer, flow_id)| (name.to_owned(), seismometer.to_owned(), flow_id))
+            .collect();
+        self.handler.commands = Some(commands);
+        self.handler.tuning = Some(tuning);
+    }
+
+    /// Receive the dump requests accepted from now on.
+    pub fn dump_requests(&self) -> DumpReceiver {
+        self.handler.dumps.subscribe()
+    }
+
+    /// Serve requests for as long as the session runs, each connection in
+    /// a task of its own, so that a slow client holds up no one else.
+    pub async fn run(self) {
+        let handler = Arc::new(self.handler);
+        let mut connections = JoinSet::new();
+        loop {
+            let accepted = tokio::select! {
+                accepted = self.listener.accept() => accepted,
+                Some(_) = connections.join_next() => continue,
+            };
+            let stream = match accepted {
+                Ok(stream) => stream,
+                Err(e) => {
+                    log::warn!("control socket can't accept connection: {e}");
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            };
+            let handler = handler.clone();
+            connections.spawn(async move {
+                let served = tokio::time::timeout(REQUEST_TIMEOUT, handler.serve(stream)).await;
+                match served {
+                    Ok(Err(e)) => log::debug!("control request failed: {e}"),
+                    Err(_) => log::debug!("control request timed out"),
+                    Ok(Ok(())) => (),
+                }
+            });
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Listener {
+    async fn bind(path: &Path) -> Result<Self, ControlError> {
+        if UnixStream::connect(path).await.is_ok() {
+            return Err(ControlError::InUse(path.to_owned()));
+        }
+        let _ = std::fs::remove_file(path);
+        let socket =
+            UnixListener::bind(path).map_err(|e| ControlError::Bind(path.to_owned(), e))?;
+        Ok(Self {
+            socket,
+            _file: SocketFile(path.to_owned()),
+        })
+    }
+
+    async fn accept(&self) -> std::io::Result<Stream> {
+        let (stream, _) = self.socket.accept().await?;
+        Ok(stream)
+    }
+}
+
+#[cfg(not(unix))]
+impl Listener {
+    async fn bind(_path: &Path) -> Result<Self, ControlError> {
+        Err(ControlError::Unsupported)
+    }
+
+    async fn accept(&self) -> std::io::Result<Stream> {
+        match *self {}
+    }
+}
+
+#[cfg(unix)]
+async fn connect(path: &Path) -> Result<Stream, ControlError> {
+    UnixStream::connect(path)
+        .await
+        .map_err(|e| ControlError::Connect(path.to_owned(), e))
+}
+
+#[cfg(not(unix))]
+async fn connect(_path: &Path) -> Result<Stream, ControlError> {
+    Err(ControlError::Unsupported)
+}
+
+impl Handler {
+    async fn serve(&self, stream: Stream) -> Result<(), ControlError> {
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut line = String::new();
+        BufReader::new(reader.take(MAX_REQUEST))
+            .read_line(&mut line)
+            .await?;
+        let reply = match serde_json::from_str(&line) {
+            Ok(request) => self.handle(request).await,
+            Err(e) => Err(ControlError::Malformed(e)),
+        };
+        let reply = reply.unwrap_or_else(|e| ControlReply::Error(e.to_string()));
+        let mut line = serde_json::to_string(&reply)?;
+        line.push('\n');
+        writer.write_all(line.as_bytes()).await?;
+        Ok(())
+    }
+
+    async fn handle(&self, request: ControlRequest) -> Result<ControlReply, ControlError> {
+        log::debug!("control request: {request:?}");
+        let done = match request {
+            ControlRequest::Status => {
+                let status = serde_json::to_value(self.status.snapshot())?;
+                return Ok(ControlReply::Status(status));
+            }
+            ControlRequest::Arm { flow: None, .. } => {
+                self.armed.set(true);
+                String::from("session armed")
+            }
+            ControlRequest::Disarm { flow: None, .. } => {
+                self.armed.set(false);
+                String::from("session disarmed")
+            }
+            ControlRequest::Arm {
+                flow: Some(flow),
+                seismometer,
+            } => {
+                let count = self
+                    .command(&flow, seismometer.as_deref(), Command::Arm)
+                    .await?;
+                format!("{count} flow(s) armed")
+            }
+            ControlRequest::Disarm {
+                flow: Some(flow),
+                seismometer,
+            } => {
+                let count = self
+                    .command(&flow, seismometer.as_deref(), Command::Disarm)
+                    .await?;
+                format!("{count} flow(s) disarmed")
+            }
+            ControlRequest::SetThreshold {
+                flow,
+                seismometer,
+                trigger_level,
+                reset_level,
+            } => {
+                let flow_ids = self.resolve(&flow, seismometer.as_deref())?;
+                let tuning = self.tuning.as_ref().ok_or(ControlError::Stopping)?;
+                for &flow_id in flow_ids.iter() {
+                    let name = flow.clone();
+                    let tuning_request = Tuning {
+                        flow_id,
+                        name,
+                        trigger_level,
+                        reset_level,
+                        holdoff: None,
+                    };
+                    tuning
+                        .send(tuning_request)
+                        .map_err(|_| ControlError::Stopping)?;
+                }
+                format!("new levels sent to {} flow(s)", flow_ids.len())
+            }
+            ControlRequest::DumpStart {
+                flow,
+                path,
+                seismometer,
+            } => {
+                let flow_ids = self.resolve(&flow, seismometer.as_deref())?;
+                let dump_dir = self.dump_dir.as_ref().ok_or(ControlError::NoDumpDir)?;
+                let path = dump_path(dump_dir, &path)?;
+                // Created here, so that a file which can't be written is
+                // reported to the operator rather than just logged, and
+                // so that no existing file is overwritten.
+                std::fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(&path)
+                    .map_err(|e| ControlError::Dump(path.clone(), e))?;
+                for &flow_id in flow_ids.iter() {
+                    let path = path.clone();
+                    self.dumps
+                        .send(DumpRequest { flow_id, path })
+                        .map_err(|_| ControlError::Stopping)?;
+                }
+                format!("dumping {flow} to {}", path.display())
+            }
+        };
+        log::info!("control socket: {done}");
+        Ok(ControlReply::Done(done))
+    }
+
+    /// Pass a command on to the action loop for every flow of a name,
+    /// returning how many there were.
+    async fn command(
+        &self,
+        flow: &str,
+        seismometer: Option<&str>,
+        command: Command,
+    ) -> Result<usize, ControlError> {
+        let flow_ids = self.resolve(flow, seismometer)?;
+        let commands = self.commands.as_ref().ok_or(ControlError::Stopping)?;
+        for &flow_id in flow_ids.iter() {
+            commands
+                .send((Some(flow_id), command))
+                .await
+                .map_err(|_| ControlError::Stopping)?;
+        }
+        Ok(flow_ids.len())
+    }
+
+    /// The ids of the flows of a name (on a seismometer, if given).
+    fn resolve(&self, flow: &str, seismometer: Option<&str>) -> Result<Vec<usize>, ControlError> {
+        let flow_ids: Vec<usize> = self
+            .flows
+            .iter()
+            .filter(|(name, s, _)| name == flow && seismometer.is_none_or(|x| x == s))
+            .map(|&(_, _, flow_id)| flow_id)
+            .collect();
+        if flow_ids.is_empty() {
+            return Err(ControlError::NoSuchFlow(flow.to_owned()));
+        }
+        Ok(flow_ids)
+    }
+}
+
+/// Where in the dump directory to dump to, for a request's file, which
+/// must be named relative to the directory and stay within it.
+fn dump_path(dump_dir: &Path, name: &Path) -> Result<PathBuf, ControlError> {
+    let plain = name.components().all(|c| matches!(c, Component::Normal(_)));
+    if !plain || name.as_os_str().is_empty() {
+        return Err(ControlError::DumpOutside(name.to_owned()));
+    }
+    Ok(dump_dir.join(name))
+}
+
+/// A socket's file, which is removed once the socket is closed.
+#[cfg(unix)]
+struct SocketFile(PathBuf);
+
+#[cfg(unix)]
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Send a request to the daemon listening on a control socket, and wait
+/// for its reply.
+pub async fn send_control_request(
+    path: &Path,
+    request: &ControlRequest,
+) -> Result<ControlReply, ControlError> {
+    let stream = connect(path).await?;
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut line = serde_json::to_string(request)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await?;
+    let mut reply = String::new();
+    BufReader::new(reader).read_line(&mut reply).await?;
+    Ok(serde_json::from_str(&reply)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use crate::session::command_channel;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn serves_requests() {
+        let socket =
+            std::env::temp_dir().join(format!("rs-udp-control-{}.sock", std::process::id()));
+        let config = ControlConfig {
+            socket: socket.clone(),
+            dump_dir: None,
+        };
+        let armed = ArmedSwitch::new(true);
+        let mut server = ControlServer::bind(&config, StatusBoard::new(), armed.clone())
+            .await
+            .expect("bind");
+        let (commands, mut received) = command_channel();
+        let tuning = broadcast::channel(16).0;
+        server.control_flows(
+            vec![("z", "garage", 0), ("z", "attic", 2)],
+            commands,
+            tuning,
+        );
+        assert!(matches!(
+            ControlServer::bind(&config, StatusBoard::new(), armed.clone()).await,
+            Err(ControlError::InUse(_))
+        ));
+        tokio::spawn(server.run());
+
+        let disarm = ControlRequest::Disarm {
+            flow: None,
+            seismometer: None,
+        };
+        let reply = send_control_request(&socket, &disarm).await.expect("reply");
+        assert_eq!(reply, ControlReply::Done("session disarmed".into()));
+        assert!(!armed.is_armed());
+        let flow = Some(String::from("z"));
+        let seismometer = Some(String::from("attic"));
+        let disarm = ControlRequest::Disarm { flow, seismometer };
+        let reply = send_control_request(&socket, &disarm).await.expect("reply");
+        assert_eq!(reply, ControlReply::Done("1 flow(s) disarmed".into()));
+        assert_eq!(received.recv().await, Some((Some(2), Command::Disarm)));
+        let flow = Some(String::from("n"));
+        let arm = ControlRequest::Arm {
+            flow,
+            seismometer: None,
+        };
+        let reply = send_control_request(&socket, &arm).await.expect("reply");
+        assert_eq!(reply, ControlReply::Error("no such flow n".into()));
+        let reply = send_control_request(&socket, &ControlRequest::Status)
+            .await
+            .expect("reply");
+        assert!(matches!(reply, ControlReply::Status(status) if status.is_object()));
+    }
+
+    #[test]
+    fn it_keeps_dumps_in_their_directory() {
+        let dir = Path::new("/var/lib/seismo/dumps");
+        let dump = dump_path(dir, Path::new("garage/z.txt")).expect("within");
+        assert_eq!(dump, Path::new("/var/lib/seismo/dumps/garage/z.txt"));
+        for name in ["/etc/passwd", "../seismo.json", "a/../../b", "./", ""] {
+            let refused = dump_path(dir, Path::new(name));
+            assert!(
+                matches!(refused, Err(ControlError::DumpOutside(_))),
+                "{name}"
+            );
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! Putting the daemon in the background itself, for init systems (BSD rc
+//! scripts, sysvinit) which expect that of it: it forks twice, so that it
+//! is no session leader and can never gain a controlling terminal, starts a
+//! session of its own, and leaves its standard I/O on /dev/null. Its
+//! process id may be kept in a file, for the init scripts to signal it by.
+//!
+//! This has to be done before the runtime starts any threads.
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum DaemonError {
+    #[error("can't fork: {0}")]
+    Fork(#[source] std::io::Error),
+    #[error("can't start a new session: {0}")]
+    Session(#[source] std::io::Error),
+    #[error("can't redirect standard I/O to /dev/null: {0}")]
+    Redirect(#[source] std::io::Error),
+    #[error("can't write pid file {0}: {1}")]
+    PidFile(PathBuf, #[source] std::io::Error),
+    #[error("already running as process {1}, according to {0}")]
+    Running(PathBuf, i32),
+}
+
+/// A file holding the daemon's process id, removed when it is dropped.
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// Claim a pid file, unless it names a process which is still running
+    /// (and so, most likely, another instance of the daemon). A file left
+    /// by one which has died is taken over.
+    pub fn claim(path: &Path) -> Result<Self, DaemonError> {
+        if let Ok(contents) = std::fs::read_to_string(path) {
+            if let Ok(pid) = contents.trim().parse::<i32>() {
+                // Signal 0 only checks that the process exists.
+                if pid > 0 && unsafe { libc::kill(pid, 0) } == 0 {
+                    return Err(DaemonError::Running(path.to_owned(), pid));
+                }
+            }
+        }
+        Ok(Self {
+            path: path.to_owned(),
+        })
+    }
+
+    /// Write the current process's id into the file.
+    pub fn write(&self) -> Result<(), DaemonError> {
+        std::fs::write(&self.path, format!("{}\n", std::process::id()))
+            .map_err(|e| DaemonError::PidFile(self.path.clone(), e))
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Put the process in the background, writing its new process id to the
+/// pid file, if any. The process that was started exits once that is done
+/// (with a failure status if the daemon couldn't get that far), so that
+/// whatever started it knows that the daemon is on its way.
+///
+/// Standard error is left alone until the very end, so that a failure is
+/// reported where it can be seen.
+pub fn daemonize(pid_file: Option<PidFile>) -> Result<Option<PidFile>, DaemonError> {
+    let (mut started, mut ready) = pipe().map_err(DaemonError::Fork)?;
+    if fork()? {
+        // Wait to hear that the daemon is under way.
+        drop(ready);
+        let mut byte = [0];
+        let status = match started.read(&mut byte) {
+            Ok(1) => 0,
+            _ => 1,
+        };
+        unsafe { libc::_exit(status) };
+    }
+    drop(started);
+    if unsafe { libc::setsid() } < 0 {
+        return Err(DaemonError::Session(std::io::Error::last_os_error()));
+    }
+    if fork()? {
+        unsafe { libc::_exit(0) };
+    }
+    if let Some(pid_file) = pid_file.as_ref() {
+        pid_file.write()?;
+    }
+    redirect_stdio().map_err(DaemonError::Redirect)?;
+    // Should the first process not hear this, it fails all the same.
+    let _ = ready.write_all(&[1]);
+    Ok(pid_file)
+}
+
+/// Fork, returning true in the parent.
+fn fork() -> Result<bool, DaemonError> {
+    match unsafe { libc::fork() } {
+        pid if pid < 0 => Err(DaemonError::Fork(std::io::Error::last_os_error())),
+        0 => Ok(false),
+        _ => Ok(true),
+    }
+}
+
+fn pipe() -> std::io::Result<(File, File)> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // The descriptors are new, and owned by nothing else.
+    Ok(unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) })
+}
+
+fn redirect_stdio() -> std::io::Result<()> {
+    let null = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/null")?;
+    for fd in [libc::STDIN_FILENO, libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+        if unsafe { libc::dup2(null.as_raw_fd(), fd) } < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuses_a_running_pid() {
+        let path = std::env::temp_dir().join(format!("rs-udp-pid-{}", std::process::id()));
+        std::fs::write(&path, format!("{}\n", std::process::id())).expect("writes");
+        assert!(matches!(
+            PidFile::claim(&path),
+            Err(DaemonError::Running(..))
+        ));
+        // No process has pid 0 to claim it, so the file is taken over.
+        std::fs::write(&path, "0\n").expect("writes");
+        let pid_file = PidFile::claim(&path).expect("claims");
+        pid_file.write().expect("writes");
+        let pid = std::fs::read_to_string(&path).expect("reads");
+        assert_eq!(pid.trim(), std::process::id().to_string());
+        drop(pid_file);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,80 @@
+//! Identifiers for flows' events, so that whatever is told of an event's
+//! start and end can tell that they are of the same one. They are ULIDs:
+//! the host's time in milliseconds, then 80 random bits, written in
+//! Crockford's base 32, so that they sort by time.
+use serde::{Serialize, Serializer};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::Read;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// An event's identifier.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct EventId(u128);
+
+impl EventId {
+    /// A new identifier, as of now.
+    pub fn generate() -> Self {
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        Self::from_parts(millis, random_bits())
+    }
+
+    fn from_parts(millis: u128, random: u128) -> Self {
+        let time = millis & ((1 << 48) - 1);
+        let random = random & ((1 << 80) - 1);
+        Self((time << 80) | random)
+    }
+}
+
+impl std::fmt::Display for EventId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // 26 digits of 5 bits each make 130 bits, the top two of which are
+        // always zero.
+        let digits: String = (0..26)
+            .rev()
+            .map(|digit| ALPHABET[((self.0 >> (digit * 5)) & 0x1f) as usize] as char)
+            .collect();
+        f.write_str(&digits)
+    }
+}
+
+impl Serialize for EventId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// 80 random bits, from the system's random source if it can be read, or
+/// else from the keys of the standard library's hashers.
+fn random_bits() -> u128 {
+    let mut bytes = [0u8; 16];
+    let read = std::fs::File::open("/dev/urandom").and_then(|mut f| f.read_exact(&mut bytes[..10]));
+    if read.is_ok() {
+        return u128::from_le_bytes(bytes);
+    }
+    let mut bits = 0;
+    for _ in 0..2 {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(bits);
+        bits = (bits << 64) | u128::from(hasher.finish());
+    }
+    bits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_is_a_ulid() {
+        let id = EventId::from_parts(1_469_918_176_385, 0x0123_4567_89ab_cdef_0123);
+        assert_eq!(id.to_string(), "01ARYZ6S4104HMASW9NF6YY093");
+        assert!(EventId::generate() > id);
+        assert_ne!(EventId::generate(), EventId::generate());
+    }
+}
@@ -0,0 +1,172 @@
+//! Appending flow events to a local file as JSON lines, rotated by size
+//! or age, for those who would rather grep them or ship them elsewhere
+//! than run a database.
+use crate::config::EventLogConfig;
+
+use std::path::PathBuf;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+use tokio::time::{Duration, Instant};
+
+/// Number of events which may wait to be written before more are dropped.
+const BACKLOG: usize = 10000;
+
+/// The sending half of an event log, which hands events to the writer
+/// without waiting.
+pub struct EventLogSink {
+    lines: mpsc::Sender<String>,
+    status: bool,
+}
+
+impl EventLogSink {
+    /// Whether flows' periodic status is logged, as well as their events.
+    pub fn logs_status(&self) -> bool {
+        self.status
+    }
+
+    pub fn write(&self, record: &serde_json::Value) {
+        if self.lines.try_send(record.to_string()).is_err() {
+            log::warn!("event log backlog full, dropping an event");
+        }
+    }
+}
+
+/// The writing half of an event log, which appends events to the file
+/// and rotates it.
+pub struct EventLogWriter {
+    path: PathBuf,
+    max_size: Option<u64>,
+    max_age: Option<Duration>,
+    keep: usize,
+    lines: mpsc::Receiver<String>,
+}
+
+/// The file being written, how big it is and when it was opened.
+struct LogFile {
+    file: File,
+    size: u64,
+    opened: Instant,
+}
+
+/// Set up an event log.
+pub fn event_log_output(config: &EventLogConfig) -> (EventLogSink, EventLogWriter) {
+    let (tx, rx) = mpsc::channel(BACKLOG);
+    let sink = EventLogSink {
+        lines: tx,
+        status: config.status,
+    };
+    let writer = EventLogWriter {
+        path: config.path.clone(),
+        max_size: config.max_size_mb.map(|mb| (mb * 1_000_000.0) as u64),
+        max_age: config.rotate_s.map(Duration::from_secs_f32),
+        keep: config.keep,
+        lines: rx,
+    };
+    (sink, writer)
+}
+
+impl EventLogWriter {
+    /// Write events as they come, until the sink is gone. An event which
+    /// can't be written is dropped (and the file opened afresh for the
+    /// next), so that a full disk doesn't hold up the daemon.
+    pub async fn run(mut self) {
+        let mut current = None;
+        while let Some(line) = self.lines.recv().await {
+            if let Err(e) = self.write(&mut current, &line).await {
+                log::warn!("can't write event log {}: {e}", self.path.display());
+            }
+        }
+    }
+
+    async fn write(&self, current: &mut Option<LogFile>, line: &str) -> std::io::Result<()> {
+        let mut log = match current.take() {
+            Some(log) => log,
+            None => self.open().await?,
+        };
+        if log.size > 0 && self.rotation_due(&log) {
+            drop(log);
+            self.rotate().await?;
+            log = self.open().await?;
+        }
+        let line = format!("{line}\n");
+        log.file.write_all(line.as_bytes()).await?;
+        log.file.flush().await?;
+        log.size += line.len() as u64;
+        *current = Some(log);
+        Ok(())
+    }
+
+    async fn open(&self) -> std::io::Result<LogFile> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+        let size = file.metadata().await?.len();
+        Ok(LogFile {
+            file,
+            size,
+            opened: Instant::now(),
+        })
+    }
+
+    fn rotation_due(&self, log: &LogFile) -> bool {
+        self.max_size.is_some_and(|max| log.size >= max)
+            || self.max_age.is_some_and(|max| log.opened.elapsed() >= max)
+    }
+
+    /// Shift each rotated file along one, the oldest falling off the end,
+    /// and the current file into first place.
+    async fn rotate(&self) -> std::io::Result<()> {
+        if self.keep == 0 {
+            return tokio::fs::remove_file(&self.path).await;
+        }
+        for n in (1..self.keep).rev() {
+            match tokio::fs::rename(self.rotated(n), self.rotated(n + 1)).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+                _ => (),
+            }
+        }
+        tokio::fs::rename(&self.path, self.rotated(1)).await
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{n}"));
+        PathBuf::from(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn rotates_by_size() {
+        let dir = std::env::temp_dir().join(format!("rs-udp-event-log-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("create dir");
+        let path = dir.join("events.jsonl");
+        let config = EventLogConfig {
+            path: path.clone(),
+            max_size_mb: Some(0.00004),
+            rotate_s: None,
+            keep: 2,
+            status: false,
+        };
+        let (sink, writer) = event_log_output(&config);
+        for n in 0..7 {
+            sink.write(&serde_json::json!({ "event": "triggered", "n": n }));
+        }
+        drop(sink);
+        writer.run().await;
+        let read = |path: PathBuf| std::fs::read_to_string(path).unwrap_or_default();
+        let rotated = |n: usize| dir.join(format!("events.jsonl.{n}"));
+        // 28 bytes a line, so a file takes two before reaching 40.
+        assert_eq!(read(path.clone()), "{\"event\":\"triggered\",\"n\":6}\n");
+        assert!(read(rotated(1)).starts_with("{\"event\":\"triggered\",\"n\":4}\n"));
+        assert!(read(rotated(2)).starts_with("{\"event\":\"triggered\",\"n\":2}\n"));
+        assert!(!rotated(3).exists());
+        std::fs::remove_dir_all(&dir).expect("clean up");
+    }
+}
@@ -0,0 +1,79 @@
+//! Periodic measurement of a flow's DC level and trigger energy, for
+//! charting the detector's behaviour and tuning its thresholds.
+use ndarray::Array1;
+
+/// The readings reported for one interval.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FlowStatus {
+    /// The mean of the raw input, in counts.
+    pub dc: f32,
+    /// The peak energy fed to the trigger.
+    pub energy: f32,
+}
+
+pub struct FlowStatusMeter {
+    /// Number of samples in each interval.
+    interval: usize,
+
+    /// Readings accumulated over the current interval so far.
+    samples: usize,
+    sum: f64,
+    peak_energy: Option<f64>,
+}
+
+impl FlowStatusMeter {
+    /// Measure over intervals of `interval_s`, at least a sample long.
+    pub fn new(sample_rate_hz: f32, interval_s: f32) -> Self {
+        Self {
+            interval: ((interval_s * sample_rate_hz).round() as usize).max(1),
+            samples: 0,
+            sum: 0.0,
+            peak_energy: None,
+        }
+    }
+
+    /// Account for a packet of raw input, and the peak energy fed to the
+    /// trigger over it (if it was finite). Once an interval's worth of
+    /// samples has been seen, its readings are returned.
+    pub fn observe(&mut self, input: &Array1<f32>, energy: Option<f64>) -> Option<FlowStatus> {
+        self.samples += input.len();
+        self.sum += input.iter().map(|&x| f64::from(x)).sum::<f64>();
+        self.peak_energy = match (self.peak_energy, energy) {
+            (Some(peak), Some(energy)) => Some(peak.max(energy)),
+            (peak, energy) => peak.or(energy),
+        };
+        if self.samples < self.interval {
+            return None;
+        }
+        let status = FlowStatus {
+            dc: (self.sum / self.samples as f64) as f32,
+            energy: self.peak_energy.unwrap_or(0.0) as f32,
+        };
+        self.samples = 0;
+        self.sum = 0.0;
+        self.peak_energy = None;
+        Some(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FlowStatus, FlowStatusMeter};
+    use ndarray::Array1;
+
+    #[test]
+    fn reports_each_interval() {
+        let mut meter = FlowStatusMeter::new(10.0, 1.0);
+        let packet = Array1::from_elem(6, 4.0);
+        assert_eq!(meter.observe(&packet, Some(2.0)), None);
+        let packet = Array1::from_elem(6, 1.0);
+        assert_eq!(
+            meter.observe(&packet, Some(1.0)),
+            Some(FlowStatus {
+                dc: 2.5,
+                energy: 2.0
+            })
+        );
+        assert_eq!(meter.observe(&packet, None), None);
+    }
+}
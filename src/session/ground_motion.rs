@@ -0,0 +1,177 @@
+//! Peak ground motion measurement over the course of an event.
+use super::intensity;
+use crate::datasource::Channel;
+use ndarray::Array1;
+use serde::Serialize;
+
+/// Cutoff frequency below which offsets and integration drift are kept
+/// out of the measurements, in hertz.
+const HIGH_PASS_HZ: f32 = 0.075;
+
+/// The peak ground motion seen during an event, in the physical units
+/// that the flow's gain converts counts to (meters and seconds, for
+/// a gain of one over the sensor's sensitivity).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct GroundMotion {
+    pub pga: f32,
+    pub pgv: f32,
+    pub pgd: f32,
+
+    /// The estimated Modified Mercalli intensity, and its Roman numeral.
+    /// (Only meaningful if the gain gives meters and seconds.)
+    pub mmi: f32,
+    pub intensity: &'static str,
+}
+
+impl std::fmt::Display for GroundMotion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "PGA {:.3e} PGV {:.3e} PGD {:.3e} (felt: {})",
+            self.pga, self.pgv, self.pgd, self.intensity
+        )
+    }
+}
+
+/// One-pole high-pass filter.
+struct HighPass {
+    alpha: f32,
+    last_input: f32,
+    last_output: f32,
+}
+
+impl HighPass {
+    fn new(sample_rate_hz: f32) -> Self {
+        let rc = 1.0 / (2.0 * std::f32::consts::PI * HIGH_PASS_HZ);
+        Self {
+            alpha: rc / (rc + 1.0 / sample_rate_hz),
+            last_input: 0.0,
+            last_output: 0.0,
+        }
+    }
+
+    fn process(&mut self, x: f32) -> f32 {
+        self.last_output = self.alpha * (self.last_output + x - self.last_input);
+        self.last_input = x;
+        self.last_output
+    }
+}
+
+/// Which quantity a channel measures.
+#[derive(Clone, Copy)]
+enum Quantity {
+    Velocity,
+    Acceleration,
+}
+
+/// Derives acceleration, velocity and displacement from a channel's
+/// samples, and tracks their peaks while an event is in progress.
+pub struct GroundMotionMeter {
+    quantity: Quantity,
+    sample_rate_hz: f32,
+    gain: f32,
+    offset: f32,
+    input_hp: HighPass,
+    /// Decay of the (leaky) integrators.
+    leak: f32,
+    last_input: Option<f32>,
+    first: f32,
+    second: f32,
+    peaks: Option<GroundMotion>,
+}
+
+impl GroundMotionMeter {
+    pub fn new(channel: Channel, sample_rate_hz: f32, gain: f32, offset: f32) -> Self {
+        // Seismometer (EH?) channels measure velocity; accelerometer
+        // (EN?) channels measure acceleration.
+        let quantity = match channel {
+            Channel::Ehz | Channel::Ehn | Channel::Ehe => Quantity::Velocity,
+            Channel::Enz | Channel::Enn | Channel::Ene => Quantity::Acceleration,
+        };
+        let input_hp = HighPass::new(sample_rate_hz);
+        Self {
+            quantity,
+            sample_rate_hz,
+            gain,
+            offset,
+            leak: input_hp.alpha,
+            input_hp,
+            last_input: None,
+            first: 0.0,
+            second: 0.0,
+            peaks: None,
+        }
+    }
+
+    /// Process some samples, noting their peaks if an event is in
+    /// progress.
+    pub fn observe(&mut self, data: &Array1<f32>, in_event: bool) {
+        let dt = 1.0 / self.sample_rate_hz;
+        for &count in data {
+            let x = self.input_hp.process((count - self.offset) * self.gain);
+            let (a, v, d) = match self.quantity {
+                Quantity::Velocity => {
+                    // Differentiate for acceleration, integrate for
+                    // displacement.
+                    let a = (x - self.last_input.unwrap_or(x)) / dt;
+                    self.first = self.leak * self.first + x * dt;
+                    (a, x, self.first)
+                }
+                Quantity::Acceleration => {
+                    // Integrate once for velocity, twice for displacement.
+                    self.first = self.leak * self.first + x * dt;
+                    self.second = self.leak * self.second + self.first * dt;
+                    (x, self.first, self.second)
+                }
+            };
+            self.last_input = Some(x);
+            if in_event {
+                let peaks = self.peaks.get_or_insert_with(GroundMotion::default);
+                peaks.pga = peaks.pga.max(a.abs());
+                peaks.pgv = peaks.pgv.max(v.abs());
+                peaks.pgd = peaks.pgd.max(d.abs());
+            }
+        }
+    }
+
+    /// The peaks seen since the last call, if an event was in progress.
+    pub fn take(&mut self) -> Option<GroundMotion> {
+        let mut peaks = self.peaks.take()?;
+        peaks.mmi = intensity::mmi(peaks.pga, peaks.pgv);
+        peaks.intensity = intensity::roman(peaks.mmi);
+        Some(peaks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measures_sinusoid() {
+        // 1 Hz ground velocity of amplitude 2 (in counts, with a gain of
+        // one half).
+        let rate = 100.0;
+        let mut meter = GroundMotionMeter::new(Channel::Ehz, rate, 0.5, 0.0);
+        let w = 2.0 * std::f32::consts::PI;
+        let wave = Array1::from_iter((0..3000).map(|i| 2.0 * (w * i as f32 / rate).sin()));
+        meter.observe(&wave, false);
+        assert_eq!(meter.take(), None);
+
+        meter.observe(&wave, true);
+        let peaks = meter.take().unwrap();
+        assert!((peaks.pgv - 1.0).abs() < 0.02, "{peaks}");
+        assert!((peaks.pga - w).abs() < 0.1, "{peaks}");
+        assert!((peaks.pgd - 1.0 / w).abs() < 0.02, "{peaks}");
+        assert_eq!(peaks.intensity, "IX");
+
+        // The same, as acceleration.
+        let mut meter = GroundMotionMeter::new(Channel::Enz, rate, 0.5, 0.0);
+        meter.observe(&wave, false);
+        meter.observe(&wave, true);
+        let peaks = meter.take().unwrap();
+        assert!((peaks.pga - 1.0).abs() < 0.02, "{peaks}");
+        assert!((peaks.pgv - 1.0 / w).abs() < 0.02, "{peaks}");
+        assert!((peaks.pgd - 1.0 / (w * w)).abs() < 0.01, "{peaks}");
+    }
+}
@@ -0,0 +1,100 @@
+use crate::datasource::Channel;
+
+/// How far a live packet may start before the end of the data replayed
+/// from the archive, in seconds, and still be taken as following on from it.
+const OVERLAP_SLACK_S: f64 = 0.005;
+
+/// The span of data time replayed from the archive on a channel.
+#[derive(Clone, Copy)]
+struct Replayed {
+    from: f64,
+    until: f64,
+}
+
+/// What to make of a live packet on a channel whose archive was replayed.
+#[derive(Debug, PartialEq)]
+pub enum Handover {
+    /// The packet is live data, the archive having been taken over from.
+    Live,
+
+    /// The packet is one the replay already covered, to be skipped.
+    Replayed,
+
+    /// The packet takes over from the archive, starting the given number
+    /// of seconds after the end of the data replayed (or before it, if
+    /// negative).
+    TakesOver(f64),
+}
+
+/// Hands each channel over from the data replayed from the archive at
+/// startup to live data, skipping the live packets which were already
+/// replayed. Only those within the span replayed are skipped: a source
+/// whose clock is behind the archive's takes over straight away, rather
+/// than having its data skipped until it catches up.
+pub struct ArchiveHandover {
+    replayed_by_channel: Vec<Option<Replayed>>,
+}
+
+impl ArchiveHandover {
+    pub fn new() -> Self {
+        Self {
+            replayed_by_channel: vec![None; Channel::max()],
+        }
+    }
+
+    /// Note a packet replayed from the archive, spanning the given data
+    /// times. Packets are replayed in data time order.
+    pub fn replayed(&mut self, channel: Channel, from: f64, until: f64) {
+        let replayed = &mut self.replayed_by_channel[channel as usize];
+        match replayed.as_mut() {
+            Some(replayed) => replayed.until = until,
+            None => *replayed = Some(Replayed { from, until }),
+        }
+    }
+
+    /// What to make of a live packet starting at the given data time.
+    pub fn take_over(&mut self, channel: Channel, timestamp: f64) -> Handover {
+        let replayed = &mut self.replayed_by_channel[channel as usize];
+        let Some(Replayed { from, until }) = *replayed else {
+            return Handover::Live;
+        };
+        // Allow for timestamps which are a little out.
+        if timestamp >= from && timestamp < until - OVERLAP_SLACK_S {
+            return Handover::Replayed;
+        }
+        replayed.take();
+        Handover::TakesOver(timestamp - until)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_packets_already_replayed() {
+        let mut handover = ArchiveHandover::new();
+        handover.replayed(Channel::Ehz, 100.0, 101.0);
+        handover.replayed(Channel::Ehz, 101.0, 102.0);
+        assert_eq!(handover.take_over(Channel::Enz, 101.5), Handover::Live);
+        assert_eq!(handover.take_over(Channel::Ehz, 101.0), Handover::Replayed);
+        assert_eq!(
+            handover.take_over(Channel::Ehz, 101.99609375),
+            Handover::TakesOver(-0.00390625)
+        );
+        assert_eq!(handover.take_over(Channel::Ehz, 101.5), Handover::Live);
+    }
+
+    #[test]
+    fn takes_over_from_a_source_behind_the_archive() {
+        let mut handover = ArchiveHandover::new();
+        handover.replayed(Channel::Ehz, 100.0, 102.0);
+        // A packet from before anything replayed can't have been covered
+        // by it, however long the source takes to catch up.
+        assert_eq!(
+            handover.take_over(Channel::Ehz, 40.0),
+            Handover::TakesOver(-62.0)
+        );
+        assert_eq!(handover.take_over(Channel::Ehz, 101.0), Handover::Live);
+    }
+}
@@ -0,0 +1,78 @@
+//! Just enough HTTP/1.1 to post to web APIs, over any stream (so that it
+//! may be plain or TLS).
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Make a POST request, closing the connection afterwards, and return
+/// the status line of the response.
+pub async fn post<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: S,
+    host: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    content_type: &str,
+    body: &[u8],
+) -> std::io::Result<String> {
+    let mut request = format!(
+        "POST {path} HTTP/1.1\r\nHost: {host}\r\nContent-Type: {content_type}\r\n\
+         Content-Length: {}\r\nConnection: close\r\n",
+        body.len()
+    );
+    for (name, value) in headers {
+        request.push_str(&format!("{name}: {value}\r\n"));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes()).await?;
+    stream.write_all(body).await?;
+    stream.flush().await?;
+    let mut response = Vec::new();
+    match stream.read_to_end(&mut response).await {
+        Ok(_) => (),
+        // Servers often close TLS connections without notice once they
+        // have responded.
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof && !response.is_empty() => (),
+        Err(e) => return Err(e),
+    }
+    let response = String::from_utf8_lossy(&response);
+    Ok(response.lines().next().unwrap_or_default().to_owned())
+}
+
+/// Whether a status line reports success.
+pub fn is_success(status: &str) -> bool {
+    status
+        .split_ascii_whitespace()
+        .nth(1)
+        .is_some_and(|code| code.starts_with('2'))
+}
+
+/// Split a plain HTTP URL into the address to connect to and a path
+/// (without a trailing slash).
+pub fn split_url(url: &str) -> Option<(String, String)> {
+    let rest = url.strip_prefix("http://")?;
+    let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    if authority.is_empty() {
+        return None;
+    }
+    let address = match authority.rsplit_once(':') {
+        Some((_, port)) if port.parse::<u16>().is_ok() => authority.to_owned(),
+        _ => format!("{authority}:80"),
+    };
+    Some((address, path.trim_end_matches('/').to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_urls() {
+        assert_eq!(
+            split_url("http://influx:8086/"),
+            Some(("influx:8086".into(), "".into()))
+        );
+        assert_eq!(
+            split_url("http://influx/proxy/influx"),
+            Some(("influx:80".into(), "/proxy/influx".into()))
+        );
+        assert_eq!(split_url("https://influx:8086"), None);
+    }
+}
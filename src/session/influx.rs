@@ -0,0 +1,306 @@
+//! Writing flow status and event markers to an InfluxDB server, in line
+//! protocol, for long-term trending of detector levels.
+use super::http;
+use super::stats::RecentStats;
+use crate::config::{InfluxDBConfig, InfluxVersion};
+
+use thiserror::Error;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::time::Duration;
+
+/// Number of points which may wait to be written before more are dropped.
+const BACKLOG: usize = 10000;
+
+/// How long to wait for the server to accept a batch of points.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Error)]
+pub enum InfluxError {
+    #[error("InfluxDB URL {0} is not a plain HTTP URL")]
+    UnsupportedUrl(String),
+    #[error("InfluxDB v1 output needs a database")]
+    NoDatabase,
+    #[error("InfluxDB v2 output needs an org and a bucket")]
+    NoBucket,
+    #[error("unable to write to InfluxDB")]
+    Io(#[from] std::io::Error),
+    #[error("InfluxDB did not respond in time")]
+    Timeout,
+    #[error("InfluxDB refused write: {0}")]
+    Rejected(String),
+}
+
+/// The flow that a point is of, whose names tag it.
+pub struct PointTags<'a> {
+    pub flow: &'a str,
+    pub seismometer: Option<&'a str>,
+    pub channel: Option<&'a str>,
+}
+
+/// A field value of a point.
+pub enum FieldValue<'a> {
+    Float(f64),
+    Str(&'a str),
+}
+
+/// The sending half of an InfluxDB output, which formats points and
+/// hands them to the writer without waiting.
+pub struct InfluxSink {
+    measurement: String,
+    event_measurement: String,
+    stats_measurement: String,
+    points: mpsc::Sender<String>,
+}
+
+impl InfluxSink {
+    /// Write a flow's DC level and peak energy, at a time in seconds
+    /// since the epoch.
+    pub fn status(&self, tags: &PointTags, dc: f32, energy: f32, at: f64) {
+        let fields = [
+            ("dc", FieldValue::Float(dc.into())),
+            ("energy", FieldValue::Float(energy.into())),
+        ];
+        self.send(line(&self.measurement, tags, &fields, at));
+    }
+
+    /// Write a marker for an event of a flow ("triggered", say), with its
+    /// identifier (if it has one) and some more fields describing it.
+    pub fn event(
+        &self,
+        tags: &PointTags,
+        event: &str,
+        event_id: Option<&str>,
+        fields: &[(&str, f64)],
+        at: f64,
+    ) {
+        let fields: Vec<_> = std::iter::once(("event", FieldValue::Str(event)))
+            .chain(event_id.map(|id| ("event_id", FieldValue::Str(id))))
+            .chain(fields.iter().map(|&(k, v)| (k, FieldValue::Float(v))))
+            .collect();
+        self.send(line(&self.event_measurement, tags, &fields, at));
+    }
+
+    /// Write a flow's statistics over the last hour and the last day, as
+    /// of a time in seconds since the epoch.
+    pub fn stats(&self, tags: &PointTags, stats: &RecentStats, at: f64) {
+        let mut fields = Vec::new();
+        for (window, stats) in [("hour", &stats.hour), ("day", &stats.day)] {
+            fields.push((format!("{window}_triggers"), stats.triggers as f64));
+            fields.push((format!("{window}_triggered_s"), stats.triggered_s));
+            fields.extend(
+                stats
+                    .max_energy
+                    .map(|energy| (format!("{window}_max_energy"), energy)),
+            );
+        }
+        let fields: Vec<_> = fields
+            .iter()
+            .map(|(k, v)| (k.as_str(), FieldValue::Float(*v)))
+            .collect();
+        self.send(line(&self.stats_measurement, tags, &fields, at));
+    }
+
+    fn send(&self, line: String) {
+        if self.points.try_send(line).is_err() {
+            log::warn!("InfluxDB backlog full, dropping a point");
+        }
+    }
+}
+
+/// The writing half of an InfluxDB output, which writes the points
+/// gathered in batches.
+pub struct InfluxWriter {
+    /// Address to connect to, and the host and path to request.
+    address: String,
+    request_path: String,
+    token: Option<String>,
+    points: mpsc::Receiver<String>,
+    batch_interval: Duration,
+}
+
+/// Set up an InfluxDB output.
+pub fn influx_output(config: &InfluxDBConfig) -> Result<(InfluxSink, InfluxWriter), InfluxError> {
+    let (address, base_path) = http::split_url(&config.url)
+        .ok_or_else(|| InfluxError::UnsupportedUrl(config.url.clone()))?;
+    let mut query = vec![("precision", "ms")];
+    let token = match config.version {
+        InfluxVersion::V1 => {
+            let database = config.database.as_deref().ok_or(InfluxError::NoDatabase)?;
+            query.push(("db", database));
+            query.extend(config.username.as_deref().map(|u| ("u", u)));
+            query.extend(config.password.as_deref().map(|p| ("p", p)));
+            None
+        }
+        InfluxVersion::V2 => {
+            let org = config.org.as_deref().ok_or(InfluxError::NoBucket)?;
+            let bucket = config.bucket.as_deref().ok_or(InfluxError::NoBucket)?;
+            query.push(("org", org));
+            query.push(("bucket", bucket));
+            config.token.clone()
+        }
+    };
+    let endpoint = match config.version {
+        InfluxVersion::V1 => "write",
+        InfluxVersion::V2 => "api/v2/write",
+    };
+    let query: Vec<String> = query
+        .iter()
+        .map(|(k, v)| format!("{k}={}", percent_encode(v)))
+        .collect();
+    let request_path = format!("{base_path}/{endpoint}?{}", query.join("&"));
+    let (tx, rx) = mpsc::channel(BACKLOG);
+    let sink = InfluxSink {
+        measurement: config.measurement.clone(),
+        event_measurement: config.event_measurement.clone(),
+        stats_measurement: config.stats_measurement.clone(),
+        points: tx,
+    };
+    let writer = InfluxWriter {
+        address,
+        request_path,
+        token,
+        points: rx,
+        batch_interval: Duration::from_secs_f32(config.batch_s.max(0.1)),
+    };
+    Ok((sink, writer))
+}
+
+impl InfluxWriter {
+    /// Write the points gathered every so often, until the sink is gone.
+    /// A batch which can't be written is dropped, so that an outage
+    /// doesn't hold up the daemon.
+    pub async fn run(mut self) {
+        let mut ticker = tokio::time::interval(self.batch_interval);
+        ticker.tick().await;
+        let mut batch = String::new();
+        loop {
+            tokio::select! {
+                point = self.points.recv() => match point {
+                    Some(point) => {
+                        batch.push_str(&point);
+                        batch.push('\n');
+                    }
+                    None => break,
+                },
+                _ = ticker.tick() => {
+                    if batch.is_empty() {
+                        continue;
+                    }
+                    if let Err(e) = self.write(&batch).await {
+                        log::warn!("InfluxDB write failed, dropping points: {e}");
+                    }
+                    batch.clear();
+                }
+            }
+        }
+        // Whatever was left when the daemon stopped.
+        if !batch.is_empty() {
+            if let Err(e) = self.write(&batch).await {
+                log::warn!("InfluxDB write failed, dropping points: {e}");
+            }
+        }
+    }
+
+    /// Post a batch of points to the write endpoint.
+    async fn write(&self, batch: &str) -> Result<(), InfluxError> {
+        let authorization = self.token.as_ref().map(|token| format!("Token {token}"));
+        let headers: Vec<(&str, &str)> = authorization
+            .iter()
+            .map(|value| ("Authorization", value.as_str()))
+            .collect();
+        let exchange = async {
+            let stream = TcpStream::connect(&self.address).await?;
+            let content_type = "text/plain; charset=utf-8";
+            http::post(
+                stream,
+                &self.address,
+                &self.request_path,
+                &headers,
+                content_type,
+                batch.as_bytes(),
+            )
+            .await
+        };
+        let status = tokio::time::timeout(WRITE_TIMEOUT, exchange)
+            .await
+            .map_err(|_| InfluxError::Timeout)??;
+        if !http::is_success(&status) {
+            return Err(InfluxError::Rejected(status));
+        }
+        Ok(())
+    }
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// Escape a measurement name, tag key or tag value, or field key.
+fn escape(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for c in name.chars() {
+        if matches!(c, ',' | ' ' | '=') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// A point in line protocol, at a time in seconds since the epoch.
+fn line(measurement: &str, tags: &PointTags, fields: &[(&str, FieldValue)], at: f64) -> String {
+    let mut line = escape(measurement);
+    let tags = [
+        ("flow", Some(tags.flow)),
+        ("seismometer", tags.seismometer),
+        ("channel", tags.channel),
+    ];
+    for (key, value) in tags {
+        if let Some(value) = value.filter(|v| !v.is_empty()) {
+            line.push_str(&format!(",{key}={}", escape(value)));
+        }
+    }
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|(key, value)| match value {
+            FieldValue::Float(v) => format!("{}={v}", escape(key)),
+            FieldValue::Str(v) => {
+                let v = v.replace('\\', "\\\\").replace('"', "\\\"");
+                format!("{}=\"{v}\"", escape(key))
+            }
+        })
+        .collect();
+    let at_ms = (at * 1000.0).round() as i64;
+    format!("{line} {} {at_ms}", fields.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_line_protocol() {
+        let tags = PointTags {
+            flow: "garage floor",
+            seismometer: Some("rs1"),
+            channel: None,
+        };
+        let fields = [
+            ("event", FieldValue::Str("say \"hi\"")),
+            ("energy", FieldValue::Float(2.5)),
+        ];
+        assert_eq!(
+            line("rs_udp_events", &tags, &fields, 1700000000.25),
+            r#"rs_udp_events,flow=garage\ floor,seismometer=rs1 event="say \"hi\"",energy=2.5 1700000000250"#
+        );
+    }
+}
@@ -1,13 +1,32 @@
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::task::JoinError;
 use tokio::time::{Duration, Instant};
 
-use super::action_loop::{Event, OutChannel, TriggerMessage};
-use super::sensor_flow::SensorFlow;
+use super::action_loop::{Event, EventSummary, OutChannel, TriggerMessage, Warning};
+use super::capture::CaptureError;
+use super::clock_drift::ClockDriftMonitor;
+use super::control::{DumpReceiver, DumpRequest};
+use super::flow_status::FlowStatus;
+use super::handover::{ArchiveHandover, Handover};
+use super::live::{EnergyStream, LiveFeed};
+use super::noise_floor::NoiseFloorMonitor;
+use super::restart::{describe, SourceRestart};
+use super::sample_rate::SampleRateMonitor;
+use super::sensor_flow::{SensorFlow, TriggerResult};
+use super::status::StatusBoard;
+use super::systemd::{heartbeat_due, Heartbeat};
 use super::timeout::ChannelChecker;
-use crate::datasource::{Channel, DataSource, DataSourceError, SeismoData};
+use super::tuning::{Tuning, TuningReceiver};
+use super::worker::{Worker, WorkerPanic};
+use crate::archive::{ArchiveError, Archiver};
+use crate::config::StatusOverflowPolicy;
+use crate::datasource::{Channel, DataSource, DataSourceError, SeismoData, SourceAddress};
 
 use thiserror::Error;
-use tokio::sync::mpsc::error::SendError;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::mpsc::error::{SendError, TrySendError};
+use tokio::sync::watch;
 
 #[derive(Error, Debug)]
 pub enum LoopError {
@@ -17,68 +36,542 @@ pub enum LoopError {
     DataSourceError(#[from] DataSourceError),
     #[error("Error joining async spawn")]
     JoinError(#[from] JoinError),
+    #[error("Archive error")]
+    ArchiveError(#[from] ArchiveError),
+    #[error("Waveform capture error")]
+    CaptureError(#[from] CaptureError),
+    #[error("Unable to start signal processing")]
+    WorkerStart(#[source] std::io::Error),
+    #[error("Signal processing failure")]
+    WorkerPanic(#[from] WorkerPanic),
+}
+
+/// How often to check the data source for undecodable packets.
+const DECODE_ERROR_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Interval over which to estimate the seismometer's clock offset.
+const CLOCK_DRIFT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Interval over which to measure the seismometer's sample rate.
+const SAMPLE_RATE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Interval over which to estimate each channel's noise floor.
+const NOISE_FLOOR_INTERVAL: Duration = Duration::from_secs(60);
+
+/// What a flow's signal processing made of a packet.
+struct Processed {
+    result: TriggerResult,
+    status: Option<FlowStatus>,
 }
 
 struct FlowState {
     flow_id: usize,
     flow: SensorFlow,
     triggered: Option<bool>,
+
+    /// The data time at which the trigger last asserted, and the energy
+    /// fed to it then, while it stays asserted.
+    asserted: Option<(f64, f64)>,
+
+    /// Live feed to stream the flow's energy to, if it is streamed.
+    live: Option<(LiveFeed, EnergyStream)>,
+
+    /// A status report held back while the action loop's queue was full,
+    /// and the number dropped since they were last counted.
+    held_status: Option<Event>,
+    dropped_status: u64,
 }
 
 pub struct InstrumentLoop {
-    src: DataSource,
+    name: String,
+
+    /// The data source, while it is open.
+    src: Option<DataSource>,
+
+    /// The thread flows' signal processing runs on, once running.
+    worker: Option<Worker>,
+
+    /// How to reopen the data source should it fail, if it may be.
+    restart: Option<SourceRestart>,
+
     flows_for_channel: Vec<Vec<FlowState>>,
     action_channel: OutChannel,
     timeouts_by_channel: ChannelChecker,
+    archiver: Option<Archiver>,
+    status: StatusBoard,
+    decode_error_threshold: usize,
+    last_arrival_by_channel: Vec<Option<Instant>>,
+    clock_drift: Option<ClockDriftMonitor>,
+    sample_rate: Option<SampleRateMonitor>,
+    noise_floor: Option<NoiseFloorMonitor>,
+
+    /// Adjustments to flows' trigger levels, if any are accepted.
+    tuning: Option<TuningReceiver>,
+
+    /// Requests to dump flows' filter processes, if any are accepted.
+    dumps: Option<DumpReceiver>,
+
+    /// The loop's sign of life, if it gives one.
+    heartbeat: Option<Heartbeat>,
+
+    /// Set when the loop should stop, if it may be told to.
+    stop: Option<watch::Receiver<bool>>,
+
+    /// Live feed to stream flows' energy to, and the interval it is
+    /// streamed over, if it is.
+    live: Option<(LiveFeed, f32)>,
+
+    /// What to do with flows' status reports while the action loop's
+    /// queue is full.
+    status_overflow: StatusOverflowPolicy,
+
+    /// Recently received packets, retained for waveform capture.
+    history_by_channel: Vec<VecDeque<SeismoData>>,
+    history_s_by_channel: Vec<f32>,
+
+    /// What of the data replayed from the archive live data has yet to
+    /// take over from, on each channel.
+    handover: ArchiveHandover,
 }
 
 impl InstrumentLoop {
     // Construct a new instrument loop that pulls data from the given
-    // data source, passes it through various signal flows, and signals
-    // various events based on the results.
+    // data source (or from one opened once running, if none is given),
+    // passes it through various signal flows, and signals various events
+    // based on the results.
     pub fn new_for_datasource(
+        name: &str,
         src: DataSource,
         timeout_s: Option<f32>,
+        decode_error_threshold: usize,
+        archiver: Option<Archiver>,
         action_channel: OutChannel,
+        status: StatusBoard,
     ) -> InstrumentLoop {
         let timeout = timeout_s.map(Duration::from_secs_f32);
         let mut flows_for_channel = Vec::with_capacity(Channel::max());
         flows_for_channel.extend((0..Channel::max()).map(|_| Vec::new()));
 
         InstrumentLoop {
+            name: name.to_owned(),
             flows_for_channel,
-            src,
+            src: Some(src),
+            worker: None,
+            restart: None,
             action_channel,
             timeouts_by_channel: ChannelChecker::new_for_timeout(timeout),
+            archiver,
+            status,
+            decode_error_threshold,
+            last_arrival_by_channel: vec![None; Channel::max()],
+            clock_drift: None,
+            sample_rate: None,
+            noise_floor: None,
+            tuning: None,
+            heartbeat: None,
+            stop: None,
+            dumps: None,
+            live: None,
+            status_overflow: StatusOverflowPolicy::default(),
+            history_by_channel: (0..Channel::max()).map(|_| VecDeque::new()).collect(),
+            history_s_by_channel: vec![0.0; Channel::max()],
+            handover: ArchiveHandover::new(),
+        }
+    }
+
+    /// Reopen the data source, which was opened at an address, whenever it
+    /// fails (waiting up to `wait_max` between attempts), so that the
+    /// seismometer is only unavailable until it recovers.
+    pub fn reopen_source(&mut self, address: SourceAddress, wait_max: Duration) {
+        self.restart = Some(SourceRestart::new(address, wait_max));
+    }
+
+    /// Declare a channel unavailable once its data time stops advancing
+    /// for the timeout, even if its packets keep arriving.
+    pub fn watch_data_time(&mut self) {
+        self.timeouts_by_channel.watch_data_time();
+    }
+
+    /// Time a channel out after a time of its own without data, in place
+    /// of the seismometer's timeout.
+    pub fn set_channel_timeout(&mut self, channel: Channel, timeout_s: f32) {
+        self.timeouts_by_channel
+            .set_channel_timeout(channel, Duration::from_secs_f32(timeout_s));
+    }
+
+    /// Coalesce or drop flows' status reports which find the action loop's
+    /// queue full, rather than wait for room.
+    pub fn set_status_overflow(&mut self, policy: StatusOverflowPolicy) {
+        self.status_overflow = policy;
+    }
+
+    /// Announce a clock drift event on all flows whenever the packet
+    /// timestamps stray from the host clock by more than `threshold_s`,
+    /// on the channels of flows added after.
+    pub fn monitor_clock_drift(&mut self, threshold_s: f32) {
+        self.clock_drift = Some(ClockDriftMonitor::new(threshold_s));
+    }
+
+    /// Warn on all flows whenever the sample rate measured from packet
+    /// timestamps strays from `sample_rate_hz` by more than `tolerance`
+    /// (a fraction of it).
+    pub fn monitor_sample_rate(&mut self, sample_rate_hz: f32, tolerance: f32) {
+        self.sample_rate = Some(SampleRateMonitor::new(sample_rate_hz, tolerance));
+    }
+
+    /// Publish each channel's noise floor, and warn on its flows whenever
+    /// it strays below `min_rms` or above `max_rms` (in raw counts).
+    pub fn monitor_noise_floor(&mut self, min_rms: Option<f32>, max_rms: Option<f32>) {
+        self.noise_floor = Some(NoiseFloorMonitor::new(min_rms, max_rms));
+    }
+
+    /// Adjust the trigger levels of this loop's flows as told to, and
+    /// announce them at the start and whenever they change.
+    pub fn accept_tuning(&mut self, tuning: TuningReceiver) {
+        self.tuning = Some(tuning);
+    }
+
+    /// Start dumping flows' filter processes to files when asked to.
+    pub fn accept_dump_requests(&mut self, dumps: DumpReceiver) {
+        self.dumps = Some(dumps);
+    }
+
+    /// Give a sign of life regularly for as long as the loop runs.
+    pub fn report_liveness(&mut self, heartbeat: Heartbeat) {
+        self.heartbeat = Some(heartbeat);
+    }
+
+    /// Stop once told to, finishing the archive and any waveform
+    /// captures in progress, as the daemon shuts down.
+    pub fn stop_on(&mut self, stop: watch::Receiver<bool>) {
+        self.stop = Some(stop);
+    }
+
+    /// Stream every flow's peak energy over each `interval_s` of data to a
+    /// live feed.
+    pub fn stream_energy(&mut self, feed: LiveFeed, interval_s: f32) {
+        for flow in self.flows_for_channel.iter_mut().flatten() {
+            flow.live = Some((feed.clone(), EnergyStream::new(interval_s)));
         }
+        self.live = Some((feed, interval_s));
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
     }
 
     pub fn add_flow(&mut self, flow_id: usize, channel: Channel, flow: SensorFlow) {
+        if let Some(capture) = flow.capture.as_ref() {
+            let history_s = &mut self.history_s_by_channel[channel as usize];
+            *history_s = history_s.max(capture.pre_trigger_s());
+        }
         let state = FlowState {
             flow_id,
             flow,
             triggered: None,
+            asserted: None,
+            live: self
+                .live
+                .as_ref()
+                .map(|(feed, interval_s)| (feed.clone(), EnergyStream::new(*interval_s))),
+            held_status: None,
+            dropped_status: 0,
         };
         self.timeouts_by_channel.track_channel(channel);
+        // Channels are taken at their flows' sample rates, which may not
+        // be the seismometer's.
+        let sample_rate_hz = state.flow.sample_rate_hz;
+        if let Some(archiver) = self.archiver.as_mut() {
+            archiver.track_channel(channel, sample_rate_hz);
+        }
+        if let Some(clock_drift) = self.clock_drift.as_mut() {
+            clock_drift.track_channel(channel, sample_rate_hz);
+        }
+        if let Some(monitor) = self.sample_rate.as_mut() {
+            if sample_rate_hz as f64 != monitor.configured_hz() {
+                monitor.exclude(channel);
+            }
+        }
         self.flows_for_channel[channel as usize].push(state);
-        self.src.subscribe(channel);
+        if let Some(src) = self.src.as_mut() {
+            src.subscribe(channel);
+        }
     }
 
     pub async fn run(mut self) -> Result<(), LoopError> {
+        // The source is already open, so live data arriving while the
+        // archive is replayed waits in it rather than being lost.
+        self.replay_backfill().await?;
+        self.worker = Some(Worker::new(&self.name).map_err(LoopError::WorkerStart)?);
         self.timeouts_by_channel.start(Instant::now());
+        self.announce_levels().await?;
+        let mut decode_error_check = tokio::time::interval(DECODE_ERROR_INTERVAL);
+        let mut clock_drift_check = tokio::time::interval(CLOCK_DRIFT_INTERVAL);
+        let mut sample_rate_check = tokio::time::interval(SAMPLE_RATE_INTERVAL);
+        let mut noise_floor_check = tokio::time::interval(NOISE_FLOOR_INTERVAL);
 
         loop {
+            if let Some(heartbeat) = self.heartbeat.as_ref() {
+                heartbeat.beat();
+            }
             tokio::select! {
-                frame = self.src.next() => {
+                frame = next_frame(&mut self.src) => {
                     match frame {
-                        Some(data_result) => self.handle_data(data_result?, Instant::now()).await?,
+                        Some(Ok(data)) => self.handle_data(data, Instant::now()).await?,
+                        Some(Err(e)) if self.restart.is_some() => self.source_failed(describe(&e)).await?,
+                        Some(Err(e)) => return Err(e.into()),
+                        None if self.restart.is_some() => self.source_failed("closed".to_owned()).await?,
                         None => break,
                     };
                 },
+                _ = source_due(&self.restart) => {
+                    self.open_source().await?;
+                },
                 _ = tokio::time::sleep(self.timeouts_by_channel.next_timeout(Instant::now()).unwrap_or(Duration::MAX)) => {
                     // One or more channels just timed out
                     self.handle_timeout(Instant::now()).await?;
                 },
+                _ = decode_error_check.tick() => {
+                    self.check_decode_errors().await?;
+                },
+                _ = clock_drift_check.tick() => {
+                    self.check_clock_drift().await?;
+                },
+                _ = sample_rate_check.tick() => {
+                    self.check_sample_rate().await?;
+                },
+                _ = noise_floor_check.tick() => {
+                    self.check_noise_floor().await?;
+                },
+                tuning = next_request(&mut self.tuning, "threshold adjustments") => {
+                    self.handle_tuning(tuning).await?;
+                },
+                request = next_request(&mut self.dumps, "dump requests") => {
+                    self.handle_dump_request(request);
+                },
+                _ = heartbeat_due(self.heartbeat.as_ref()) => (),
+                _ = stop_requested(&mut self.stop) => break,
+            }
+        }
+        if let Some(archiver) = self.archiver.as_mut() {
+            archiver.flush()?;
+        }
+        // Partial captures are handed on like any other, rather than left
+        // for the next session (which starts afresh) to lose track of.
+        for flow in self.flows_for_channel.iter_mut().flatten() {
+            if let Some(capture) = flow.flow.capture.as_mut() {
+                if let Some(path) = capture.finish()? {
+                    log::info!("{}: saved partial capture {}", self.name, path.display());
+                    flow.send_event(Event::Captured { path }, &self.action_channel)
+                        .await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Announce the trigger levels of every flow that has them, so that
+    // they may be reported (and posted, if they may be adjusted). Flows
+    // with schedules start out on the levels in force now.
+    async fn announce_levels(&mut self) -> Result<(), LoopError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        for flow in self.flows_for_channel.iter_mut().flatten() {
+            flow.follow_schedule(now);
+            if let Some(event) = flow.levels_event() {
+                flow.send_event(event, &self.action_channel).await?;
+            }
+        }
+        Ok(())
+    }
+
+    // Adjust a flow's trigger levels, if the flow is one of ours.
+    async fn handle_tuning(&mut self, tuning: Tuning) -> Result<(), LoopError> {
+        let flows = self
+            .flows_for_channel
+            .iter_mut()
+            .flatten()
+            .filter(|flow| flow.flow_id == tuning.flow_id);
+        for flow in flows {
+            let name = &tuning.name;
+            let pipeline = &mut flow.flow.pipeline;
+            let tuned = pipeline
+                .set_trigger_levels(tuning.trigger_level, tuning.reset_level)
+                .and_then(|levels| {
+                    if let Some(holdoff) = tuning.holdoff {
+                        pipeline.set_trigger_holdoff(holdoff)?;
+                    }
+                    Ok(levels)
+                });
+            match tuned {
+                Ok((trigger_level, reset_level)) => {
+                    log::info!(
+                        "{name}: trigger level now {trigger_level}, reset level {reset_level}"
+                    );
+                    if let Some(holdoff) = tuning.holdoff {
+                        log::info!("{name}: trigger holdoff now {holdoff} samples");
+                    }
+                    if let Some(event) = flow.levels_event() {
+                        flow.send_event(event, &self.action_channel).await?;
+                    }
+                }
+                Err(e) => log::warn!("{name}: {e}"),
+            }
+        }
+        Ok(())
+    }
+
+    // Start dumping a flow's filter process, if the flow is one of ours.
+    fn handle_dump_request(&mut self, request: DumpRequest) {
+        let flows = self
+            .flows_for_channel
+            .iter_mut()
+            .flatten()
+            .filter(|flow| flow.flow_id == request.flow_id);
+        for flow in flows {
+            let path = request.path.display();
+            match flow
+                .flow
+                .pipeline
+                .start_dump(&request.path, flow.flow.sample_rate_hz)
+            {
+                Ok(()) => log::info!("{}: dumping filter process to {path}", self.name),
+                Err(e) => log::warn!("{}: can't dump filter process to {path}: {e}", self.name),
+            }
+        }
+    }
+
+    // Open the data source, now that it is due to be, and subscribe to the
+    // channels which flows are fed from.
+    async fn open_source(&mut self) -> Result<(), LoopError> {
+        let Some(restart) = self.restart.as_mut() else {
+            return Ok(());
+        };
+        let mut src = match DataSource::open(restart.address()).await {
+            Ok(src) => src,
+            Err(e) => return self.source_failed(describe(&e)).await,
+        };
+        restart.opened(Instant::now());
+        for (index, flows) in self.flows_for_channel.iter().enumerate() {
+            if let (false, Ok(channel)) = (flows.is_empty(), Channel::try_from(index)) {
+                src.subscribe(channel);
+            }
+        }
+        self.src = Some(src);
+        let recovered = self.status.update(|status| {
+            let seismometer = status.seismometers.entry(self.name.clone()).or_default();
+            seismometer.source_error.take().is_some()
+        });
+        if recovered {
+            log::info!("{}: data source reopened", self.name);
+        }
+        Ok(())
+    }
+
+    // Close a data source which has failed (or couldn't be opened), mark
+    // its channels unavailable, and wait a while to open it again. The
+    // other seismometers carry on meanwhile.
+    async fn source_failed(&mut self, error: String) -> Result<(), LoopError> {
+        let Some(restart) = self.restart.as_mut() else {
+            return Ok(());
+        };
+        self.src = None;
+        let wait = restart.failed(Instant::now());
+        log::error!(
+            "{}: data source failed ({error}), reopening it in {wait:?}",
+            self.name
+        );
+        self.status.update(|status| {
+            let seismometer = status.seismometers.entry(self.name.clone()).or_default();
+            seismometer.source_error = Some(error);
+        });
+        for channel in self.timeouts_by_channel.mark_all_dead() {
+            for flow in self.flows_for_channel[channel as usize].iter() {
+                flow.unavailable(&self.action_channel).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn check_decode_errors(&mut self) -> Result<(), LoopError> {
+        let Some(src) = self.src.as_mut() else {
+            return Ok(());
+        };
+        let count = src.take_decode_errors();
+        if count > self.decode_error_threshold {
+            let warning = Warning::DecodeErrors {
+                count,
+                seconds: DECODE_ERROR_INTERVAL.as_secs_f32(),
+            };
+            for flow in self.flows_for_channel.iter().flatten() {
+                flow.send_event(Event::Warning(warning.clone()), &self.action_channel)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn check_clock_drift(&mut self) -> Result<(), LoopError> {
+        let drift = self.clock_drift.as_mut().and_then(|c| c.check());
+        if let Some(offset_s) = drift {
+            for flow in self.flows_for_channel.iter().flatten() {
+                flow.send_event(Event::ClockDrift { offset_s }, &self.action_channel)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn check_sample_rate(&mut self) -> Result<(), LoopError> {
+        let Some(monitor) = self.sample_rate.as_mut() else {
+            return Ok(());
+        };
+        let configured = monitor.configured_hz();
+        let Some((measured, mismatched)) = monitor.check() else {
+            return Ok(());
+        };
+        self.status.update(|status| {
+            status
+                .seismometers
+                .entry(self.name.clone())
+                .or_default()
+                .measured_sample_rate = Some(measured);
+        });
+        if mismatched {
+            let warning = Warning::SampleRateMismatch {
+                measured,
+                configured,
+            };
+            log::warn!("{}: {warning}", self.name);
+            for flow in self.flows_for_channel.iter().flatten() {
+                flow.send_event(Event::Warning(warning.clone()), &self.action_channel)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn check_noise_floor(&mut self) -> Result<(), LoopError> {
+        let Some(monitor) = self.noise_floor.as_mut() else {
+            return Ok(());
+        };
+        let results = monitor.check();
+        self.status.update(|status| {
+            for &(channel, rms, _) in results.iter() {
+                status.channel_mut(&self.name, channel).noise_floor_rms = Some(rms);
+            }
+        });
+        for (channel, rms, unhealthy) in results {
+            if !unhealthy {
+                continue;
+            }
+            let warning = Warning::NoiseFloor { rms };
+            log::warn!("{} {channel}: {warning}", self.name);
+            for flow in self.flows_for_channel[channel as usize].iter() {
+                flow.send_event(Event::Warning(warning.clone()), &self.action_channel)
+                    .await?;
             }
         }
         Ok(())
@@ -94,42 +587,399 @@ impl InstrumentLoop {
     }
 
     async fn handle_data(&mut self, data: SeismoData, when: Instant) -> Result<(), LoopError> {
+        if !self.take_over_from_archive(&data) {
+            return Ok(());
+        }
         //
         // We have a valid new frame. If the source was previously
         // marked "offline", or it hasn't ever been seen yet,
-        // mark it "online".
+        // mark it "online". A frame which doesn't advance in data time
+        // (when that is watched) is the device repeating itself, and is
+        // neither archived nor processed.
         //
-        let already_active = self
-            .timeouts_by_channel
-            .mark_channel_alive(when, data.channel);
-        for flow in self.flows_for_channel[data.channel as usize].iter_mut() {
-            if ! already_active {
+        let Some(already_active) =
+            self.timeouts_by_channel
+                .mark_channel_alive(when, data.channel, data.timestamp)
+        else {
+            if let Some(src) = self.src.as_mut() {
+                src.recycle(data);
+            }
+            return Ok(());
+        };
+        self.record_arrival(&data, when);
+        self.retain_history(&data);
+        if let Some(archiver) = self.archiver.as_mut() {
+            archiver.record(&data)?;
+        }
+        if !already_active {
+            for flow in self.flows_for_channel[data.channel as usize].iter_mut() {
                 flow.available(&self.action_channel).await?;
-                flow.reset(&self.action_channel).await?;
+                flow.announce_trigger_state(data.timestamp, &self.action_channel)
+                    .await?;
+            }
+        }
+        for flow in self.flows_for_channel[data.channel as usize].iter_mut() {
+            if flow.follow_schedule(data.timestamp) {
+                if let Some(event) = flow.levels_event() {
+                    flow.send_event(event, &self.action_channel).await?;
+                }
+            }
+        }
+        let (data, processed) = self.compute(data).await?;
+        let mut dropped_status = 0;
+        let flows = self.flows_for_channel[data.channel as usize].iter_mut();
+        for (flow, processed) in flows.zip(processed) {
+            let history = &self.history_by_channel[data.channel as usize];
+            flow.process(
+                &data,
+                processed,
+                history,
+                &self.action_channel,
+                self.status_overflow,
+            )
+            .await?;
+            dropped_status += std::mem::take(&mut flow.dropped_status);
+        }
+        if dropped_status > 0 {
+            self.status
+                .update(|status| status.dropped_status_events += dropped_status);
+        }
+        if let Some(archiver) = self.archiver.as_mut() {
+            let any_triggered = self
+                .flows_for_channel
+                .iter()
+                .flatten()
+                .any(|flow| flow.triggered.unwrap_or(false));
+            archiver.set_event_active(any_triggered, data.timestamp);
+        }
+        if let Some(noise_floor) = self.noise_floor.as_mut() {
+            let quiet = !self.flows_for_channel[data.channel as usize]
+                .iter()
+                .any(|flow| flow.triggered.unwrap_or(false));
+            if quiet {
+                noise_floor.observe(&data);
+            }
+        }
+        if let Some(src) = self.src.as_mut() {
+            src.recycle(data);
+        }
+        Ok(())
+    }
+
+    // Run a packet through its channel's flows' signal processing on the
+    // worker thread. The packet is handed back with what each flow made of
+    // it, and the flows are put back even should their processing panic.
+    async fn compute(
+        &mut self,
+        data: SeismoData,
+    ) -> Result<(SeismoData, Vec<Processed>), LoopError> {
+        let channel = data.channel as usize;
+        let flows = std::mem::take(&mut self.flows_for_channel[channel]);
+        let Some(worker) = self.worker.as_ref().filter(|_| !flows.is_empty()) else {
+            self.flows_for_channel[channel] = flows;
+            return Ok((data, Vec::new()));
+        };
+        let ((flows, data), processed) = worker
+            .run((flows, data), |(flows, data)| {
+                flows.iter_mut().map(|flow| flow.compute(data)).collect()
+            })
+            .await;
+        self.flows_for_channel[channel] = flows;
+        Ok((data, processed?))
+    }
+
+    // Warm up the flows with recently archived data, if so configured.
+    // Actions are suppressed, but trigger state is carried over so that it
+    // is announced once live data arrives.
+    async fn replay_backfill(&mut self) -> Result<(), LoopError> {
+        let Some(archiver) = self.archiver.take() else {
+            return Ok(());
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        // The archive is read on a thread of its own, so as not to hold up
+        // the other seismometers' loops while it is.
+        let (archiver, backfill) = tokio::task::spawn_blocking(move || {
+            let backfill = archiver.read_backfill(now);
+            (archiver, backfill)
+        })
+        .await?;
+        self.archiver = Some(archiver);
+        for data in backfill? {
+            self.retain_history(&data);
+            for flow in self.flows_for_channel[data.channel as usize].iter_mut() {
+                flow.replay(&data);
+                let until = flow.sample_time(&data, data.data.len());
+                self.handover.replayed(data.channel, data.timestamp, until);
             }
-            flow.process(&data, &self.action_channel).await?;
         }
         Ok(())
     }
+
+    // Whether a live packet is to be processed, rather than skipped as one
+    // which the archive replayed at startup already covered. The first one
+    // after those takes over from the archive.
+    fn take_over_from_archive(&mut self, data: &SeismoData) -> bool {
+        match self.handover.take_over(data.channel, data.timestamp) {
+            Handover::Live => true,
+            Handover::Replayed => false,
+            Handover::TakesOver(after_s) => {
+                log::info!(
+                    "{} {}: live data takes over from the archive, {after_s:.2} s after it ends",
+                    self.name,
+                    data.channel,
+                );
+                true
+            }
+        }
+    }
+
+    // Keep a copy of the packet for as long as any flow on the channel might
+    // need it for pre-trigger capture.
+    fn retain_history(&mut self, data: &SeismoData) {
+        let history_s = self.history_s_by_channel[data.channel as usize] as f64;
+        if history_s <= 0.0 {
+            return;
+        }
+        let history = &mut self.history_by_channel[data.channel as usize];
+        history.push_back(data.clone());
+        while history
+            .front()
+            .is_some_and(|oldest| oldest.timestamp < data.timestamp - history_s)
+        {
+            history.pop_front();
+        }
+    }
+
+    // Note packet arrival timing statistics for the channel.
+    fn record_arrival(&mut self, data: &SeismoData, when: Instant) {
+        let last_arrival = self.last_arrival_by_channel[data.channel as usize].replace(when);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        let latency = (data.timestamp > 0.0).then_some(now - data.timestamp);
+        if let Some(clock_drift) = self.clock_drift.as_mut() {
+            clock_drift.observe(data, now);
+        }
+        if let Some(sample_rate) = self.sample_rate.as_mut() {
+            sample_rate.observe(data);
+        }
+        let queue_depth = self.action_channel.max_capacity() - self.action_channel.capacity();
+        self.status.update(|status| {
+            status.action_queue_depth = queue_depth;
+            status.max_action_queue_depth = status.max_action_queue_depth.max(queue_depth);
+            let channel = status.channel_mut(&self.name, data.channel);
+            channel.packets += 1;
+            if let Some(last_arrival) = last_arrival {
+                channel
+                    .arrival_interval
+                    .record(when.duration_since(last_arrival).as_secs_f64());
+            }
+            if let Some(latency) = latency {
+                channel.latency.record(latency);
+            }
+        });
+    }
+}
+
+// Wait until the loop is told to stop, if it ever may be.
+async fn stop_requested(stop: &mut Option<watch::Receiver<bool>>) {
+    if let Some(stop) = stop.as_mut() {
+        if stop.wait_for(|&stop| stop).await.is_ok() {
+            return;
+        }
+    }
+    std::future::pending().await
+}
+
+// The next frame from the data source, while it is open.
+async fn next_frame(src: &mut Option<DataSource>) -> Option<Result<SeismoData, DataSourceError>> {
+    match src.as_mut() {
+        Some(src) => src.next().await,
+        None => std::future::pending().await,
+    }
+}
+
+// The time to open the data source again, if it is closed and may be.
+async fn source_due(restart: &Option<SourceRestart>) {
+    match restart.as_ref() {
+        Some(restart) => restart.due().await,
+        None => std::future::pending().await,
+    }
+}
+
+// The next request for flows (as an adjustment to their trigger levels),
+// if any are accepted.
+async fn next_request<T: Clone>(requests: &mut Option<broadcast::Receiver<T>>, what: &str) -> T {
+    loop {
+        let Some(receiver) = requests.as_mut() else {
+            return std::future::pending().await;
+        };
+        match receiver.recv().await {
+            Ok(request) => return request,
+            Err(RecvError::Lagged(count)) => log::warn!("{count} {what} dropped"),
+            Err(RecvError::Closed) => *requests = None,
+        }
+    }
 }
 
 impl FlowState {
+    /// Run a packet through the flow's signal processing, which is the
+    /// heavy part of processing it.
+    fn compute(&mut self, input: &SeismoData) -> Processed {
+        let result = self.flow.pipeline.process(&input.data, input.timestamp);
+        let in_event = self.triggered.unwrap_or(false) || result.triggered;
+        self.flow.ground_motion.observe(&input.data, in_event);
+        if let Some(picker) = self.flow.picker.as_mut() {
+            picker.observe(&input.data);
+        }
+        let status = self
+            .flow
+            .status
+            .as_mut()
+            .and_then(|status| status.observe(&input.data, result.energy));
+        Processed { result, status }
+    }
+
+    /// Act on what the flow's signal processing made of a packet.
     pub async fn process(
         &mut self,
         input: &SeismoData,
+        processed: Processed,
+        history: &VecDeque<SeismoData>,
         post: &OutChannel,
+        status_overflow: StatusOverflowPolicy,
     ) -> Result<(), LoopError> {
-        let result = self
-            .flow.trigger.process(&input.data, &mut self.flow.dumper);
-        if result.triggered {
-            self.triggered(post).await?;
+        let Processed { result, status } = processed;
+        let report = status.map(|status| Event::Status {
+            dc: status.dc,
+            energy: status.energy,
+        });
+        self.post_status(report, post, status_overflow)?;
+        if let Some((feed, stream)) = self.live.as_mut() {
+            let duration_s = input.data.len() as f64 / self.flow.sample_rate_hz as f64;
+            if let Some((at, energy)) = stream.observe(input.timestamp, duration_s, result.energy) {
+                feed.energy(self.flow_id, at, energy);
+            }
+        }
+        if let Some(at) = result.triggered_at {
+            let onset = self.pick_onset(input, at);
+            let energy = result.trigger_energy.unwrap_or(0.0);
+            self.triggered(self.trigger_time(input, at), energy, onset, post)
+                .await?;
+        }
+        if let Some((severity, at)) = result.escalated_to.zip(result.escalated_at) {
+            let at = self.sample_time(input, at);
+            let energy = result.escalation_energy.unwrap_or(0.0);
+            self.send_event(
+                Event::Escalated {
+                    severity,
+                    at,
+                    energy,
+                },
+                post,
+            )
+            .await?;
         }
-        if result.reset {
-            self.reset(post).await?;
+        if let Some(at) = result.reset_at {
+            let at = self.sample_time(input, at);
+            let summary = self.summarize(at, result.peak_energy);
+            self.reset(at, summary, post).await?;
+        }
+        if let Some(at) = result.stuck_reset_at {
+            self.stuck_reset(self.sample_time(input, at), post).await?;
+        }
+        if result.non_finite_reset {
+            self.send_event(Event::Warning(Warning::NonFiniteReset), post)
+                .await?;
+        }
+        let captured = match self.flow.capture.as_mut() {
+            Some(capture) => capture.observe(input, self.triggered.unwrap_or(false), history)?,
+            None => None,
+        };
+        if let Some(path) = captured {
+            self.send_event(Event::Captured { path }, post).await?;
         }
         Ok(())
     }
 
+    /// Process a packet without taking any action other than tracking
+    /// the trigger state.
+    pub fn replay(&mut self, input: &SeismoData) {
+        self.follow_schedule(input.timestamp);
+        let result = self.flow.pipeline.process(&input.data, input.timestamp);
+        if let Some(at) = result.triggered_at {
+            self.triggered.replace(true);
+            let energy = result.trigger_energy.unwrap_or(0.0);
+            self.asserted
+                .replace((self.trigger_time(input, at), energy));
+        }
+        if result.reset || result.stuck_reset_at.is_some() {
+            self.triggered.replace(false);
+            self.asserted = None;
+        }
+    }
+
+    /// Announce the current trigger state, as when the flow's channel
+    /// becomes available with data from data time `now`. A flow with no
+    /// known state is announced as reset then.
+    pub async fn announce_trigger_state(
+        &mut self,
+        now: f64,
+        channel: &OutChannel,
+    ) -> Result<(), LoopError> {
+        match (self.triggered, self.asserted) {
+            (Some(true), Some((at, energy))) => {
+                let event = Event::Triggered {
+                    at,
+                    energy,
+                    onset: None,
+                };
+                self.send_event(event, channel).await?;
+            }
+            _ => self.reset(now, None, channel).await?,
+        }
+        Ok(())
+    }
+
+    /// Put the trigger levels of the profile in force at data time `now`
+    /// into effect, if the flow has a schedule, returning whether they
+    /// changed.
+    pub fn follow_schedule(&mut self, now: f64) -> bool {
+        let Some(schedule) = self.flow.schedule.as_mut() else {
+            return false;
+        };
+        let Some((trigger_level, reset_level)) = schedule.update(now) else {
+            return false;
+        };
+        // The levels were checked when the flow was set up.
+        let _ = self
+            .flow
+            .pipeline
+            .set_trigger_levels(Some(trigger_level), Some(reset_level));
+        true
+    }
+
+    /// An announcement of the flow's trigger levels, and the profile they
+    /// are from, if it has them.
+    pub fn levels_event(&self) -> Option<Event> {
+        let (trigger_level, reset_level) = self.flow.pipeline.trigger_levels()?;
+        let profile = self
+            .flow
+            .schedule
+            .as_ref()
+            .and_then(|schedule| schedule.active())
+            .map(str::to_owned);
+        Some(Event::Levels {
+            trigger_level,
+            reset_level,
+            profile,
+        })
+    }
+
     pub async fn available(&self, channel: &OutChannel) -> Result<(), LoopError> {
         self.send_event(Event::Available, channel).await?;
         Ok(())
@@ -140,22 +990,120 @@ impl FlowState {
         Ok(())
     }
 
-    pub async fn triggered(&mut self, channel: &OutChannel) -> Result<(), LoopError> {
+    /// The data time of the onset of the phase which triggered at sample
+    /// `at` of `input`, if the flow picks onsets.
+    fn pick_onset(&self, input: &SeismoData, at: usize) -> Option<f64> {
+        let picker = self.flow.picker.as_ref()?;
+        let before_last = picker.pick(input.data.len().saturating_sub(at + 1))?;
+        let last =
+            input.timestamp + input.data.len().saturating_sub(1) as f64 / picker.sample_rate_hz();
+        Some(last - before_last)
+    }
+
+    /// The data time of sample `at` of `input`.
+    fn sample_time(&self, input: &SeismoData, at: usize) -> f64 {
+        input.timestamp + at as f64 / self.flow.sample_rate_hz as f64
+    }
+
+    /// The data time at which the flow's signal rose to trigger at the
+    /// `at`th sample of a packet, allowing for the lag of its smoothing.
+    fn trigger_time(&self, input: &SeismoData, at: usize) -> f64 {
+        self.sample_time(input, at) - self.flow.pipeline.trigger_delay_s()
+    }
+
+    /// Summarize the event whose trigger reset at data time `at`, if it
+    /// was seen to trigger.
+    fn summarize(&mut self, at: f64, peak_energy: Option<f64>) -> Option<EventSummary> {
+        let (asserted_at, energy) = self.asserted.take()?;
+        Some(EventSummary {
+            at,
+            duration_s: at - asserted_at,
+            peak_energy: peak_energy.unwrap_or(energy),
+        })
+    }
+
+    pub async fn triggered(
+        &mut self,
+        at: f64,
+        energy: f64,
+        onset: Option<f64>,
+        channel: &OutChannel,
+    ) -> Result<(), LoopError> {
         if !self.triggered.unwrap_or(false) {
-            self.send_event(Event::Triggered, channel).await?;
+            self.send_event(Event::Triggered { at, energy, onset }, channel)
+                .await?;
             self.triggered.replace(true);
+            self.asserted.replace((at, energy));
         }
         Ok(())
     }
 
-    pub async fn reset(&mut self, channel: &OutChannel) -> Result<(), LoopError> {
+    pub async fn reset(
+        &mut self,
+        at: f64,
+        summary: Option<EventSummary>,
+        channel: &OutChannel,
+    ) -> Result<(), LoopError> {
         if self.triggered.unwrap_or(true) {
-            self.send_event(Event::Reset, channel).await?;
+            let ground_motion = self.flow.ground_motion.take();
+            self.send_event(
+                Event::Reset {
+                    at,
+                    summary,
+                    ground_motion,
+                },
+                channel,
+            )
+            .await?;
             self.triggered.replace(false);
         }
         Ok(())
     }
 
+    pub async fn stuck_reset(&mut self, at: f64, channel: &OutChannel) -> Result<(), LoopError> {
+        if self.triggered.unwrap_or(true) {
+            // A stuck trigger's measurements are of whatever it was
+            // stuck on, not of an earthquake.
+            self.flow.ground_motion.take();
+            self.asserted = None;
+            self.send_event(Event::StuckReset { at }, channel).await?;
+            self.triggered.replace(false);
+        }
+        Ok(())
+    }
+
+    // Post a status report (or one held back before) without waiting for
+    // room in the action loop's queue, which would hold up the flows. A
+    // report which finds the queue full is dropped, or held back in place
+    // of any older one until there is room.
+    fn post_status(
+        &mut self,
+        report: Option<Event>,
+        channel: &OutChannel,
+        overflow: StatusOverflowPolicy,
+    ) -> Result<(), LoopError> {
+        if let Some(report) = report {
+            if self.held_status.replace(report).is_some() {
+                self.dropped_status += 1;
+            }
+        }
+        let Some(event) = self.held_status.take() else {
+            return Ok(());
+        };
+        match channel.try_send(TriggerMessage {
+            source_id: self.flow_id,
+            event,
+        }) {
+            Ok(()) => (),
+            Err(TrySendError::Full(message)) => match overflow {
+                StatusOverflowPolicy::Coalesce => self.held_status = Some(message.event),
+                StatusOverflowPolicy::Drop => self.dropped_status += 1,
+            },
+            Err(TrySendError::Closed(message)) => return Err(SendError(message).into()),
+        }
+        Ok(())
+    }
+
     pub async fn send_event(&self, event: Event, channel: &OutChannel) -> Result<(), LoopError> {
         channel
             .send(TriggerMessage {
@@ -166,3 +1114,94 @@ impl FlowState {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::FlowConfig;
+    use crate::session::action_loop::message_channel;
+
+    async fn flow_state() -> FlowState {
+        let config: FlowConfig = serde_json::from_value(serde_json::json!({
+            "name": "z",
+            "channel": "EHZ",
+            "filter": { "trigger_level": 1.0, "reset_level": 0.1 },
+            "actions": {},
+        }))
+        .expect("parse");
+        FlowState {
+            flow_id: 0,
+            flow: SensorFlow::from_config(100.0, &config, None)
+                .await
+                .expect("builds"),
+            triggered: None,
+            asserted: None,
+            live: None,
+            held_status: None,
+            dropped_status: 0,
+        }
+    }
+
+    fn status(energy: f32) -> Option<Event> {
+        Some(Event::Status { dc: 0.0, energy })
+    }
+
+    fn energy_of(message: Option<TriggerMessage>) -> f32 {
+        match message.map(|message| message.event) {
+            Some(Event::Status { energy, .. }) => energy,
+            _ => panic!("not a status report"),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_coalesces_status_reports_but_not_events() {
+        let overflow = StatusOverflowPolicy::Coalesce;
+        let (post, mut received) = message_channel(1);
+        let mut flow = flow_state().await;
+        flow.post_status(status(1.0), &post, overflow)
+            .expect("posts");
+        // The queue is full, so the latest report is held in place of the
+        // one before it.
+        flow.post_status(status(2.0), &post, overflow)
+            .expect("posts");
+        flow.post_status(status(3.0), &post, overflow)
+            .expect("posts");
+        assert_eq!(flow.dropped_status, 1);
+
+        // An event waits for room.
+        let (sent, first) = tokio::join!(flow.triggered(10.0, 5.0, None, &post), received.recv());
+        sent.expect("sends");
+        assert_eq!(energy_of(first), 1.0);
+        let event = received.recv().await.map(|message| message.event);
+        assert!(matches!(event, Some(Event::Triggered { at: 10.0, .. })));
+
+        // The held report goes once there is room.
+        flow.post_status(None, &post, overflow).expect("posts");
+        assert_eq!(energy_of(received.recv().await), 3.0);
+        assert_eq!(flow.dropped_status, 1);
+    }
+
+    #[tokio::test]
+    async fn it_drops_status_reports_but_not_events() {
+        let overflow = StatusOverflowPolicy::Drop;
+        let (post, mut received) = message_channel(1);
+        let mut flow = flow_state().await;
+        flow.post_status(status(1.0), &post, overflow)
+            .expect("posts");
+        flow.post_status(status(2.0), &post, overflow)
+            .expect("posts");
+        flow.post_status(status(3.0), &post, overflow)
+            .expect("posts");
+        assert_eq!(flow.dropped_status, 2);
+
+        let (sent, first) = tokio::join!(flow.reset(20.0, None, &post), received.recv());
+        sent.expect("sends");
+        assert_eq!(energy_of(first), 1.0);
+        let event = received.recv().await.map(|message| message.event);
+        assert!(matches!(event, Some(Event::Reset { at: 20.0, .. })));
+
+        // Nothing was held back.
+        flow.post_status(None, &post, overflow).expect("posts");
+        assert!(received.try_recv().is_err());
+    }
+}
@@ -0,0 +1,83 @@
+//! Estimation of Modified Mercalli intensity from peak ground motion,
+//! using the ground motion/intensity conversion equations (GMICE) of
+//! Worden et al. (2012), combined as ShakeMap does.
+
+/// Coefficients of a two-segment GMICE: intensity is `c1 + c2 log10(y)`
+/// up to `log10(y) = t1`, and `c3 + c4 log10(y)` beyond.
+struct Gmice {
+    c1: f32,
+    c2: f32,
+    c3: f32,
+    c4: f32,
+    t1: f32,
+}
+
+impl Gmice {
+    fn intensity(&self, y: f32) -> f32 {
+        let log_y = y.max(f32::MIN_POSITIVE).log10();
+        if log_y <= self.t1 {
+            self.c1 + self.c2 * log_y
+        } else {
+            self.c3 + self.c4 * log_y
+        }
+    }
+}
+
+/// For PGA in cm/s².
+const PGA_GMICE: Gmice = Gmice {
+    c1: 1.78,
+    c2: 1.55,
+    c3: -1.60,
+    c4: 3.70,
+    t1: 1.57,
+};
+
+/// For PGV in cm/s.
+const PGV_GMICE: Gmice = Gmice {
+    c1: 3.78,
+    c2: 1.47,
+    c3: 2.89,
+    c4: 3.16,
+    t1: 0.53,
+};
+
+/// Estimate the Modified Mercalli intensity (1 to 10) of shaking with
+/// the given peak ground acceleration (m/s²) and velocity (m/s).
+/// Acceleration is the better predictor of weak shaking and velocity of
+/// strong shaking, so the estimate moves from one to the other between
+/// intensities V and VII.
+pub fn mmi(pga: f32, pgv: f32) -> f32 {
+    let from_pga = PGA_GMICE.intensity(pga * 100.0);
+    let from_pgv = PGV_GMICE.intensity(pgv * 100.0);
+    let mmi = if from_pga < 5.0 {
+        from_pga
+    } else if from_pga >= 7.0 {
+        from_pgv
+    } else {
+        let w = (from_pga - 5.0) / 2.0;
+        (1.0 - w) * from_pga + w * from_pgv
+    };
+    mmi.clamp(1.0, 10.0)
+}
+
+/// The conventional Roman numeral for an intensity.
+pub fn roman(mmi: f32) -> &'static str {
+    const NUMERALS: [&str; 10] = ["I", "II", "III", "IV", "V", "VI", "VII", "VIII", "IX", "X"];
+    NUMERALS[(mmi.round() as usize).clamp(1, 10) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_intensity() {
+        // Barely perceptible.
+        assert_eq!(roman(mmi(0.001, 0.0001)), "I");
+        // Light shaking: 2% g.
+        assert_eq!(roman(mmi(0.2, 0.01)), "IV");
+        // Severe: 0.3 g, 30 cm/s.
+        assert_eq!(roman(mmi(3.0, 0.3)), "VIII");
+        assert_eq!(roman(mmi(100.0, 10.0)), "X");
+    }
+}
@@ -0,0 +1,33 @@
+//! Keyed hashes (HMACs), with which SNMPv3 traps are authenticated and
+//! webhook posts signed.
+use hmac::digest::KeyInit;
+use hmac::Mac;
+
+/// The HMAC of a message, using the hash of `M`.
+pub fn hmac<M: Mac + KeyInit>(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac = <M as Mac>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(message);
+    mac.finalize().into_bytes().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hmac::Hmac;
+    use sha1::Sha1;
+    use sha2::Sha256;
+
+    #[test]
+    fn hashes_rfc_vectors() {
+        // From RFC 4231, test case 2.
+        let mac = hmac::<Hmac<Sha256>>(b"Jefe", b"what do ya want for nothing?");
+        let hex: String = mac.iter().map(|b| format!("{b:02x}")).collect();
+        assert_eq!(
+            hex,
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        let mac = hmac::<Hmac<Sha1>>(b"key", b"");
+        let hex: String = mac.iter().map(|b| format!("{b:02x}")).collect();
+        assert_eq!(hex, "f42bb0eeb018ebbd4597ae7213711ec60760843f");
+    }
+}
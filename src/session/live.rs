@@ -0,0 +1,132 @@
+//! A live feed of flows' events and energy, as JSON messages, for the
+//! HTTP server to stream to browsers watching the detector as it runs (as
+//! while tuning its thresholds).
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+/// Number of messages which may wait for a slow client before it misses
+/// some.
+const BACKLOG: usize = 1024;
+
+/// A cloneable handle on the feed, for its sources to send to and its
+/// clients to subscribe to.
+#[derive(Clone)]
+pub struct LiveFeed {
+    sender: broadcast::Sender<Arc<str>>,
+    names: Arc<HashMap<usize, (String, String)>>,
+}
+
+/// A handle on a feed for its clients to subscribe to while it lasts.
+#[derive(Clone)]
+pub struct LiveSubscriber(broadcast::WeakSender<Arc<str>>);
+
+impl LiveSubscriber {
+    /// Subscribe to the feed, unless it has ended.
+    pub fn subscribe(&self) -> Option<broadcast::Receiver<Arc<str>>> {
+        self.0.upgrade().map(|sender| sender.subscribe())
+    }
+}
+
+impl LiveFeed {
+    /// Set up a feed for some flows, given their names, seismometers and
+    /// flow ids.
+    pub fn new(flows: Vec<(&str, &str, usize)>) -> Self {
+        let names = flows
+            .into_iter()
+            .map(|(name, seismometer, flow_id)| {
+                (flow_id, (name.to_owned(), seismometer.to_owned()))
+            })
+            .collect();
+        Self {
+            sender: broadcast::Sender::new(BACKLOG),
+            names: Arc::new(names),
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<str>> {
+        self.sender.subscribe()
+    }
+
+    /// A way to subscribe to the feed which doesn't keep it going.
+    pub fn subscriber(&self) -> LiveSubscriber {
+        LiveSubscriber(self.sender.downgrade())
+    }
+
+    /// Send a message to every client, if there are any.
+    pub fn send(&self, message: &serde_json::Value) {
+        if self.sender.receiver_count() > 0 {
+            let _ = self.sender.send(message.to_string().into());
+        }
+    }
+
+    /// Send the peak energy of a flow over some data from a data time.
+    pub fn energy(&self, flow_id: usize, timestamp: f64, energy: f64) {
+        let Some((name, seismometer)) = self.names.get(&flow_id) else {
+            return;
+        };
+        self.send(&serde_json::json!({
+            "type": "energy",
+            "flow": name,
+            "seismometer": seismometer,
+            "timestamp": timestamp,
+            "energy": energy,
+        }));
+    }
+}
+
+/// The peak energy of a flow over intervals of data time, so that the
+/// feed may carry less than every packet's worth.
+pub struct EnergyStream {
+    interval_s: f64,
+    start: Option<f64>,
+    peak: Option<f64>,
+}
+
+impl EnergyStream {
+    /// Take peaks over `interval_s`, or over each packet if it is 0.
+    pub fn new(interval_s: f32) -> Self {
+        Self {
+            interval_s: f64::from(interval_s),
+            start: None,
+            peak: None,
+        }
+    }
+
+    /// Account for the peak energy over a packet of data, starting at a
+    /// data time and lasting for `duration_s`. Once an interval's worth
+    /// has been seen, its start and peak are returned.
+    pub fn observe(
+        &mut self,
+        timestamp: f64,
+        duration_s: f64,
+        energy: Option<f64>,
+    ) -> Option<(f64, f64)> {
+        let start = *self.start.get_or_insert(timestamp);
+        self.peak = match (self.peak, energy) {
+            (Some(peak), Some(energy)) => Some(peak.max(energy)),
+            (peak, energy) => peak.or(energy),
+        };
+        if timestamp + duration_s - start < self.interval_s {
+            return None;
+        }
+        self.start = None;
+        self.peak.take().map(|peak| (start, peak))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn downsamples_energy() {
+        let mut stream = EnergyStream::new(1.0);
+        assert_eq!(stream.observe(10.0, 0.5, Some(3.0)), None);
+        assert_eq!(stream.observe(10.5, 0.5, Some(2.0)), Some((10.0, 3.0)));
+        assert_eq!(stream.observe(11.0, 0.5, None), None);
+        assert_eq!(stream.observe(11.5, 0.5, None), None);
+        let mut stream = EnergyStream::new(0.0);
+        assert_eq!(stream.observe(10.0, 0.5, Some(3.0)), Some((10.0, 3.0)));
+    }
+}
@@ -0,0 +1,49 @@
+//! The WebSocket end of the live feed: a client which asks for it is sent
+//! the state of every flow, then everything sent to the feed, until it
+//! goes away or the session ends.
+use super::api::{error, Api};
+use super::status::StatusBoard;
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::Response;
+use std::sync::Arc;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// Upgrade a request to a WebSocket, and stream the live feed over it.
+pub async fn serve(State(api): State<Api>, upgrade: WebSocketUpgrade) -> Response {
+    let Some(live) = api.live.subscribe() else {
+        return error(StatusCode::SERVICE_UNAVAILABLE);
+    };
+    upgrade.on_upgrade(move |socket| async move {
+        if let Err(e) = stream(socket, &api.status, live).await {
+            log::debug!("live client failed: {e}");
+        }
+    })
+}
+
+async fn stream(
+    mut socket: WebSocket,
+    status: &StatusBoard,
+    mut live: broadcast::Receiver<Arc<str>>,
+) -> Result<(), axum::Error> {
+    let flows = serde_json::json!({ "type": "flows", "flows": status.snapshot().flows });
+    socket.send(Message::text(flows.to_string())).await?;
+    loop {
+        tokio::select! {
+            message = live.recv() => match message {
+                Ok(message) => socket.send(Message::text(&*message)).await?,
+                Err(RecvError::Lagged(count)) => {
+                    log::debug!("live client fell behind, missing {count} messages");
+                }
+                Err(RecvError::Closed) => break,
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_)) | Err(_)) | None => return Ok(()),
+                Some(Ok(_)) => (),
+            },
+        }
+    }
+    socket.send(Message::Close(None)).await
+}
@@ -0,0 +1,10 @@
+//! Stand-in for the live feed's WebSocket when built without the
+//! "websocket" feature. Clients which ask for it are told it isn't there.
+use super::api::error;
+
+use axum::http::StatusCode;
+use axum::response::Response;
+
+pub async fn serve() -> Response {
+    error(StatusCode::NOT_IMPLEMENTED)
+}
@@ -1,13 +1,90 @@
 mod action_loop;
 mod alarm_session;
+mod api;
+mod armed;
+mod brokers;
+#[cfg_attr(not(feature = "audio"), path = "audio_disabled.rs")]
+mod audio;
+mod availability;
+mod cap;
+#[cfg_attr(not(feature = "recorders"), path = "capture_disabled.rs")]
+mod capture;
+mod clock_drift;
+mod coincidence;
+mod commands;
+mod control;
+#[cfg(unix)]
+mod daemon;
+mod event_id;
+mod event_log;
+mod flow_status;
+mod ground_motion;
+mod handover;
+mod http;
+mod influx;
 mod instrument_loop;
+mod intensity;
+#[cfg(any(feature = "snmp", feature = "webhook"))]
+mod keyed_hash;
+mod live;
+#[cfg_attr(not(feature = "websocket"), path = "live_socket_disabled.rs")]
+mod live_socket;
+mod monitor;
+#[cfg_attr(not(feature = "mqtt"), path = "mqtt_disabled.rs")]
 mod mqtt;
+mod noise_floor;
+mod outbox;
+mod placeholders;
+#[cfg(feature = "telegram")]
+mod plot;
+mod presence;
+mod rate_limit;
+mod reload;
+mod restart;
+mod retry;
+mod sample_rate;
+mod schedule;
 mod sensor_flow;
+#[cfg_attr(not(feature = "snmp"), path = "snmp_disabled.rs")]
+mod snmp;
+mod soak;
+mod stats;
+mod status;
+mod syslog;
+mod systemd;
+#[cfg_attr(not(feature = "telegram"), path = "telegram_disabled.rs")]
+mod telegram;
 mod timeout;
+mod tuning;
+#[cfg_attr(not(feature = "webhook"), path = "webhook_disabled.rs")]
+mod webhook;
+mod worker;
 
 pub use action_loop::message_channel as action_loop_message_channel;
 pub use action_loop::{ActionLoop, InChannel, OutChannel};
-pub use alarm_session::AlarmSession;
+pub use alarm_session::{AlarmSession, RestartHandle};
+pub use api::{ApiError, ApiServer};
+pub use armed::{command_channel, ArmedControl, ArmedSwitch};
+pub use audio::{AudioError, AudioPlayer};
+pub use cap::{check_alert_config, CapError};
+pub use coincidence::Coincidence;
+pub use control::{send_control_request, ControlError, ControlReply, ControlRequest, ControlServer};
+#[cfg(unix)]
+pub use daemon::{daemonize, DaemonError, PidFile};
+pub use event_log::{event_log_output, EventLogWriter};
+pub use influx::{influx_output, InfluxError, InfluxWriter};
 pub use instrument_loop::InstrumentLoop;
-pub use mqtt::MQTT;
+pub use live::LiveFeed;
+pub use monitor::{LogTail, MonitorLogger, TerminalMonitor};
+pub use mqtt::{Broker, MQTT};
+pub use outbox::{MqttConnection, Outbox};
+pub use presence::DaemonPresence;
+pub use reload::ReloadTrigger;
 pub use sensor_flow::SensorFlow;
+pub use snmp::{SnmpError, SnmpTraps};
+pub use soak::SoakMonitor;
+pub use status::{FlowSnapshot, StatusBoard, StatusPublisher, StatusSnapshot};
+pub use syslog::{Syslog, SyslogLogger};
+pub use telegram::{Telegram, TelegramError};
+pub use tuning::{TuningControl, TuningHandle};
+pub use webhook::{check_webhook_config, WebhookError};
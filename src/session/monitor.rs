@@ -0,0 +1,356 @@
+//! A live view of the flows on the terminal, for when the feedback of a
+//! tapped floor is wanted right away: each flow's energy as a bar against
+//! its trigger level, its state, and its channel's packet rate, with the
+//! latest log messages below.
+use super::live::LiveFeed;
+use super::status::{FlowSnapshot, StatusBoard, StatusSnapshot};
+use crate::time::UtcTime;
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::time::{Duration, Instant};
+
+/// How often the view is redrawn.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(200);
+
+/// How long a flow's peak energy is held on its bar.
+const PEAK_HOLD: Duration = Duration::from_secs(3);
+
+/// How many log messages are shown.
+const LOG_LINES: usize = 8;
+
+/// Width of the energy bars, in characters.
+const BAR_WIDTH: usize = 30;
+
+/// Decades of energy the bars show below and above the trigger level.
+const DECADES_BELOW: f64 = 3.0;
+const DECADES_ABOVE: f64 = 1.0;
+
+/// The latest log messages, kept for the view rather than written over it.
+#[derive(Clone, Default)]
+pub struct LogTail(Arc<Mutex<VecDeque<String>>>);
+
+impl LogTail {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(&self, line: String) {
+        let mut lines = self.0.lock().expect("log tail lock");
+        if lines.len() == LOG_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    fn lines(&self) -> Vec<String> {
+        self.0
+            .lock()
+            .expect("log tail lock")
+            .iter()
+            .cloned()
+            .collect()
+    }
+}
+
+/// A logger which keeps the messages that its filter lets through for the
+/// terminal view.
+pub struct MonitorLogger {
+    logger: env_logger::Logger,
+    tail: LogTail,
+}
+
+impl MonitorLogger {
+    pub fn new(logger: env_logger::Logger, tail: LogTail) -> Self {
+        Self { logger, tail }
+    }
+
+    /// Install as the global logger.
+    pub fn init(self) -> Result<(), log::SetLoggerError> {
+        log::set_max_level(self.logger.filter());
+        log::set_boxed_logger(Box::new(self))
+    }
+}
+
+impl log::Log for MonitorLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.logger.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.logger.matches(record) {
+            return;
+        }
+        let now = utc_now();
+        let (hour, minute, second) = (now.hour, now.minute, now.second);
+        let time = format!("{hour:02}:{minute:02}:{second:02}");
+        self.tail
+            .push(format!("{time} {:<5} {}", record.level(), record.args()));
+    }
+
+    fn flush(&self) {}
+}
+
+/// A flow's latest energy, and the peak held on its bar.
+struct FlowEnergy {
+    latest: f64,
+    peak: f64,
+    peak_at: Instant,
+}
+
+pub struct TerminalMonitor {
+    status: StatusBoard,
+    live: broadcast::Receiver<Arc<str>>,
+    tail: LogTail,
+    energies: HashMap<String, FlowEnergy>,
+    /// Packets received on each channel (by seismometer and channel name)
+    /// when last counted, and the rates since.
+    packets: HashMap<(String, String), u64>,
+    rates: HashMap<(String, String), f64>,
+    counted_at: Instant,
+}
+
+impl TerminalMonitor {
+    pub fn new(status: StatusBoard, live: &LiveFeed, tail: LogTail) -> Self {
+        Self {
+            status,
+            live: live.subscribe(),
+            tail,
+            energies: HashMap::new(),
+            packets: HashMap::new(),
+            rates: HashMap::new(),
+            counted_at: Instant::now(),
+        }
+    }
+
+    /// Draw the view on the terminal's alternate screen for as long as the
+    /// session runs. The terminal is given back once the monitor is
+    /// dropped.
+    pub async fn run(mut self) {
+        print!("\x1b[?1049h\x1b[?25l");
+        let mut redraw = tokio::time::interval(REDRAW_INTERVAL);
+        loop {
+            tokio::select! {
+                message = self.live.recv() => match message {
+                    Ok(message) => self.note(&message),
+                    Err(RecvError::Lagged(_)) => (),
+                    Err(RecvError::Closed) => return,
+                },
+                _ = redraw.tick() => {
+                    let snapshot = self.status.snapshot();
+                    self.count_packets(&snapshot);
+                    let mut stdout = std::io::stdout().lock();
+                    let _ = stdout.write_all(self.render(&snapshot).as_bytes());
+                    let _ = stdout.flush();
+                }
+            }
+        }
+    }
+
+    /// Take note of a flow's energy from the live feed.
+    fn note(&mut self, message: &str) {
+        let Ok(message) = serde_json::from_str::<serde_json::Value>(message) else {
+            return;
+        };
+        if message["type"] != "energy" {
+            return;
+        }
+        let (Some(flow), Some(energy)) = (message["flow"].as_str(), message["energy"].as_f64())
+        else {
+            return;
+        };
+        let now = Instant::now();
+        let held = self.energies.entry(flow.to_owned()).or_insert(FlowEnergy {
+            latest: energy,
+            peak: energy,
+            peak_at: now,
+        });
+        held.latest = energy;
+        if energy >= held.peak || now.duration_since(held.peak_at) > PEAK_HOLD {
+            held.peak = energy;
+            held.peak_at = now;
+        }
+    }
+
+    /// Work out channels' packet rates, about once a second.
+    fn count_packets(&mut self, snapshot: &StatusSnapshot) {
+        let elapsed_s = self.counted_at.elapsed().as_secs_f64();
+        if elapsed_s < 1.0 {
+            return;
+        }
+        self.counted_at = Instant::now();
+        for (seismometer, status) in snapshot.seismometers.iter() {
+            for (channel, status) in status.channels.iter() {
+                let key = (seismometer.clone(), channel.clone());
+                let previous = self.packets.insert(key.clone(), status.packets);
+                let rate = status
+                    .packets
+                    .saturating_sub(previous.unwrap_or(status.packets));
+                self.rates.insert(key, rate as f64 / elapsed_s);
+            }
+        }
+    }
+
+    /// The whole view, as written over the last.
+    fn render(&self, snapshot: &StatusSnapshot) -> String {
+        let mut view = String::from("\x1b[H");
+        let mut line = |text: &str| {
+            view.push_str(text);
+            view.push_str("\x1b[K\r\n");
+        };
+        line(&format!(
+            "\x1b[1mseismo\x1b[0m  {}  (Ctrl-C to quit)",
+            utc_now()
+        ));
+        line("");
+        line(&format!(
+            "\x1b[1m{:<16} {:<9} {:>9} {:>9} {:<w$} {:>8}\x1b[0m",
+            "FLOW",
+            "STATE",
+            "ENERGY",
+            "TRIGGER",
+            "",
+            "PKT/S",
+            w = BAR_WIDTH + 2
+        ));
+        for (name, flow) in snapshot.flows.iter() {
+            let energy = self.energies.get(name);
+            let rate = flow
+                .seismometer
+                .clone()
+                .zip(flow.channel.clone())
+                .and_then(|key| self.rates.get(&key));
+            line(&format!(
+                "{:<16} {} {:>9} {:>9} [{}] {:>8}",
+                truncate(name, 16),
+                state(flow),
+                energy.map_or(String::from("-"), |e| format!("{:.3e}", e.latest)),
+                flow.trigger_level
+                    .map_or(String::from("-"), |l| format!("{l}")),
+                bar(flow, energy),
+                rate.map_or(String::from("-"), |r| format!("{r:.1}")),
+            ));
+        }
+        line("");
+        for message in self.tail.lines() {
+            line(&truncate(&message, 100));
+        }
+        // Clear whatever was below the view before.
+        view.push_str("\x1b[J");
+        view
+    }
+}
+
+impl Drop for TerminalMonitor {
+    fn drop(&mut self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = std::io::stdout().flush();
+    }
+}
+
+/// A flow's state, colored: disabled, offline, disarmed, triggered or
+/// quiet.
+fn state(flow: &FlowSnapshot) -> &'static str {
+    if flow.disabled {
+        "\x1b[2mdisabled \x1b[0m"
+    } else if flow.available == Some(false) {
+        "\x1b[2moffline  \x1b[0m"
+    } else if flow.triggered {
+        "\x1b[1;31mTRIGGERED\x1b[0m"
+    } else if !flow.armed {
+        "\x1b[33mdisarmed \x1b[0m"
+    } else {
+        "\x1b[32mquiet    \x1b[0m"
+    }
+}
+
+/// An energy bar, on a logarithmic scale about the trigger level (which is
+/// marked), filled to the latest energy and ticked at the held peak.
+fn bar(flow: &FlowSnapshot, energy: Option<&FlowEnergy>) -> String {
+    let Some(trigger_level) = flow.trigger_level.filter(|&l| l > 0.0) else {
+        return " ".repeat(BAR_WIDTH);
+    };
+    let trigger_level = f64::from(trigger_level);
+    let position = |energy: f64| -> usize {
+        let decades = (energy.max(f64::MIN_POSITIVE) / trigger_level).log10();
+        let fraction = (decades + DECADES_BELOW) / (DECADES_BELOW + DECADES_ABOVE);
+        (fraction.clamp(0.0, 1.0) * BAR_WIDTH as f64).round() as usize
+    };
+    let mark = position(trigger_level);
+    let (filled, peak) = energy.map_or((0, None), |e| (position(e.latest), Some(position(e.peak))));
+    let color = if filled >= mark {
+        "\x1b[31m"
+    } else {
+        "\x1b[32m"
+    };
+    let mut bar = String::from(color);
+    for i in 0..BAR_WIDTH {
+        let c = match i {
+            _ if i == mark => '|',
+            _ if i < filled => '#',
+            _ if Some(i + 1) == peak => '!',
+            _ => ' ',
+        };
+        if i == filled {
+            bar.push_str("\x1b[0m");
+        }
+        let _ = write!(bar, "{c}");
+    }
+    bar.push_str("\x1b[0m");
+    bar
+}
+
+fn utc_now() -> UtcTime {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64();
+    UtcTime::from_epoch(now)
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scales_bars_about_the_trigger_level() {
+        let flow = FlowSnapshot {
+            trigger_level: Some(100.0),
+            ..Default::default()
+        };
+        let plain = |energy: f64| -> String {
+            let now = Instant::now();
+            let held = FlowEnergy {
+                latest: energy,
+                peak: energy,
+                peak_at: now,
+            };
+            let bar = bar(&flow, Some(&held));
+            let mut plain = String::new();
+            let mut escaped = false;
+            for c in bar.chars() {
+                match c {
+                    '\x1b' => escaped = true,
+                    'm' if escaped => escaped = false,
+                    c if !escaped => plain.push(c),
+                    _ => (),
+                }
+            }
+            plain
+        };
+        let quiet = plain(0.1);
+        assert_eq!(quiet.chars().count(), BAR_WIDTH);
+        assert!(quiet.starts_with(' '));
+        assert_eq!(quiet.find('|'), Some(23));
+        let loud = plain(1e6);
+        assert_eq!(loud.matches('#').count(), BAR_WIDTH - 1);
+    }
+}
@@ -1,25 +1,118 @@
 use crate::config::Config;
-use rumqttc::{AsyncClient, EventLoop, MqttOptions};
+use rumqttc::{LastWill, MqttOptions, Outgoing, Packet};
 
-pub struct MQTT(pub Option<AsyncClient>, pub Option<EventLoop>);
+pub use rumqttc::{AsyncClient, ClientError, Event, EventLoop, QoS};
+
+/// The main broker's client and event loop, if one is configured, those
+/// of every named broker, and those of a further connection to the main
+/// broker for each availability topic (in the order of the configuration's
+/// `availability_topics()`), whose last will posts the topic offline.
+pub struct MQTT(
+    pub Option<AsyncClient>,
+    pub Option<EventLoop>,
+    pub Vec<Broker>,
+    pub Vec<Broker>,
+);
+
+/// A named broker, which actions may post to in place of the main one.
+pub struct Broker {
+    pub name: String,
+    pub client: AsyncClient,
+    pub event_loop: EventLoop,
+}
 
 impl MQTT {
     pub fn from_config(config: &Config) -> MQTT {
+        let brokers = config
+            .mqtt_brokers
+            .iter()
+            .map(|(name, broker_config)| {
+                let mut options = MqttOptions::new(
+                    &broker_config.client_id,
+                    &broker_config.host,
+                    broker_config.port,
+                );
+                let (username, password) = (&broker_config.username, &broker_config.password);
+                set_credentials(&mut options, username, password);
+                let availability = broker_config.availability_topic.as_deref();
+                set_last_will(&mut options, availability, &broker_config.offline_payload);
+                let (client, event_loop) = AsyncClient::new(options, 10);
+                Broker {
+                    name: name.clone(),
+                    client,
+                    event_loop,
+                }
+            })
+            .collect();
         let mqtt_config = match config.mqtt.as_ref() {
-            None => return MQTT(None, None),
+            None => return MQTT(None, None, brokers, Vec::new()),
             Some(mqtt_config) => mqtt_config,
         };
+        let availability_topics = config
+            .availability_topics()
+            .into_iter()
+            .enumerate()
+            .map(|(n, (_, topic))| {
+                let client_id = format!("{}-available-{n}", mqtt_config.client_id);
+                let mut options = MqttOptions::new(client_id, &mqtt_config.host, mqtt_config.port);
+                set_credentials(&mut options, &mqtt_config.username, &mqtt_config.password);
+                set_last_will(&mut options, Some(topic), &mqtt_config.offline_payload);
+                let (client, event_loop) = AsyncClient::new(options, 10);
+                Broker {
+                    name: format!("{} (for {topic})", mqtt_config.host),
+                    client,
+                    event_loop,
+                }
+            })
+            .collect();
         let mut options =
             MqttOptions::new(&mqtt_config.client_id, &mqtt_config.host, mqtt_config.port);
-        mqtt_config
-            .username
-            .as_ref()
-            .zip(mqtt_config.password.as_ref())
-            .and_then(|(username, password)| {
-                options.set_credentials(username, password);
-                None::<()>
-            });
+        set_credentials(&mut options, &mqtt_config.username, &mqtt_config.password);
+        let availability = mqtt_config.availability_topic.as_deref();
+        set_last_will(&mut options, availability, &mqtt_config.offline_payload);
         let (client, event_loop) = AsyncClient::new(options, 10);
-        MQTT(Some(client), Some(event_loop))
+        MQTT(Some(client), Some(event_loop), brokers, availability_topics)
+    }
+}
+
+/// Log in to a broker, if given both a username and a password.
+fn set_credentials(
+    options: &mut MqttOptions,
+    username: &Option<String>,
+    password: &Option<String>,
+) {
+    if let Some((username, password)) = username.as_ref().zip(password.as_ref()) {
+        options.set_credentials(username, password);
+    }
+}
+
+/// Have the broker post the offline payload to the availability topic, if
+/// there is one, on the daemon's behalf should the connection be lost.
+fn set_last_will(options: &mut MqttOptions, topic: Option<&str>, offline_payload: &str) {
+    if let Some(topic) = topic {
+        let payload = offline_payload.as_bytes();
+        options.set_last_will(LastWill::new(topic, payload, QoS::AtLeastOnce, true));
+    }
+}
+
+/// Whether the event marks a (re-)connection to the broker.
+pub fn is_connected(event: &Event) -> bool {
+    matches!(event, Event::Incoming(Packet::ConnAck(_)))
+}
+
+/// Whether the event marks the disconnection asked for as the daemon
+/// stops, after which there is nothing more to send.
+pub fn is_disconnecting(event: &Event) -> bool {
+    matches!(event, Event::Outgoing(Outgoing::Disconnect))
+}
+
+/// The topic and payload of an incoming published message, if the event
+/// is one.
+pub fn incoming_publish(event: &Event) -> Option<(&str, &[u8])> {
+    match event {
+        Event::Incoming(Packet::Publish(publish)) => {
+            Some((publish.topic.as_str(), publish.payload.as_ref()))
+        }
+        _ => None,
     }
 }
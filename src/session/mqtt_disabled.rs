@@ -0,0 +1,94 @@
+//! Stand-ins for the MQTT client when built without the "mqtt" feature.
+//! No client is ever created, so none of these types can be constructed
+//! and the code that handles them is unreachable.
+use crate::config::Config;
+use thiserror::Error;
+
+pub struct MQTT(
+    pub Option<AsyncClient>,
+    pub Option<EventLoop>,
+    pub Vec<Broker>,
+    pub Vec<Broker>,
+);
+
+pub struct Broker {
+    pub name: String,
+    pub client: AsyncClient,
+    pub event_loop: EventLoop,
+}
+
+impl MQTT {
+    pub fn from_config(_config: &Config) -> MQTT {
+        MQTT(None, None, Vec::new(), Vec::new())
+    }
+}
+
+// Named to match rumqttc.
+#[allow(clippy::enum_variant_names)]
+#[derive(Clone, Copy, PartialEq)]
+pub enum QoS {
+    AtMostOnce,
+    AtLeastOnce,
+    ExactlyOnce,
+}
+
+#[derive(Error, Debug)]
+pub enum ClientError {}
+
+#[derive(Error, Debug)]
+pub enum ConnectionError {}
+
+pub struct Event;
+
+#[derive(Clone)]
+pub enum AsyncClient {}
+
+impl AsyncClient {
+    pub async fn publish<S, V>(
+        &self,
+        _topic: S,
+        _qos: QoS,
+        _retain: bool,
+        _payload: V,
+    ) -> Result<(), ClientError> {
+        match *self {}
+    }
+
+    pub fn try_publish<S, V>(
+        &self,
+        _topic: S,
+        _qos: QoS,
+        _retain: bool,
+        _payload: V,
+    ) -> Result<(), ClientError> {
+        match *self {}
+    }
+
+    pub fn try_subscribe<S>(&self, _topic: S, _qos: QoS) -> Result<(), ClientError> {
+        match *self {}
+    }
+
+    pub async fn disconnect(&self) -> Result<(), ClientError> {
+        match *self {}
+    }
+}
+
+pub enum EventLoop {}
+
+impl EventLoop {
+    pub async fn poll(&mut self) -> Result<Event, ConnectionError> {
+        match *self {}
+    }
+}
+
+pub fn is_connected(_event: &Event) -> bool {
+    false
+}
+
+pub fn is_disconnecting(_event: &Event) -> bool {
+    false
+}
+
+pub fn incoming_publish(_event: &Event) -> Option<(&str, &[u8])> {
+    None
+}
@@ -0,0 +1,108 @@
+use crate::datasource::{Channel, SeismoData};
+
+/// Raw-count statistics for one channel over an interval.
+#[derive(Clone, Default)]
+struct ChannelNoise {
+    samples: usize,
+    sum: f64,
+    sum_sq: f64,
+    unhealthy: bool,
+}
+
+/// Estimates each channel's noise floor, as the RMS (about the mean) of
+/// its raw counts while none of its flows are triggered. A sensor going
+/// bad (a dying geophone, a loose cable) shows up as a change in its
+/// noise floor long before it makes the triggers misbehave.
+pub struct NoiseFloorMonitor {
+    min_rms: Option<f64>,
+    max_rms: Option<f64>,
+    by_channel: Vec<ChannelNoise>,
+}
+
+impl NoiseFloorMonitor {
+    /// Monitor against a healthy range of noise floors, either end of
+    /// which may be left open.
+    pub fn new(min_rms: Option<f32>, max_rms: Option<f32>) -> Self {
+        Self {
+            min_rms: min_rms.map(f64::from),
+            max_rms: max_rms.map(f64::from),
+            by_channel: vec![ChannelNoise::default(); Channel::max()],
+        }
+    }
+
+    /// Note a packet received while the channel was quiet.
+    pub fn observe(&mut self, data: &SeismoData) {
+        let noise = &mut self.by_channel[data.channel as usize];
+        for &v in &data.data {
+            let v = v as f64;
+            noise.samples += 1;
+            noise.sum += v;
+            noise.sum_sq += v * v;
+        }
+    }
+
+    /// Conclude an interval, returning the noise floor of each channel
+    /// which was quiet for some of it, and whether it has just gone
+    /// outside the healthy range.
+    pub fn check(&mut self) -> Vec<(Channel, f64, bool)> {
+        let (min_rms, max_rms) = (self.min_rms, self.max_rms);
+        let mut results = Vec::new();
+        for (index, noise) in self.by_channel.iter_mut().enumerate() {
+            if noise.samples == 0 {
+                continue;
+            }
+            let n = noise.samples as f64;
+            let mean = noise.sum / n;
+            let rms = (noise.sum_sq / n - mean * mean).max(0.0).sqrt();
+            noise.samples = 0;
+            noise.sum = 0.0;
+            noise.sum_sq = 0.0;
+            let was_unhealthy = noise.unhealthy;
+            noise.unhealthy =
+                min_rms.is_some_and(|min| rms < min) || max_rms.is_some_and(|max| rms > max);
+            if let Ok(channel) = Channel::try_from(index) {
+                results.push((channel, rms, noise.unhealthy && !was_unhealthy));
+            }
+        }
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::Array1;
+
+    fn packet(channel: Channel, amplitude: f32) -> SeismoData {
+        SeismoData {
+            timestamp: 0.0,
+            channel,
+            data: Array1::from_iter((0..100).map(|i| {
+                if i % 2 == 0 {
+                    16000.0 + amplitude
+                } else {
+                    16000.0 - amplitude
+                }
+            })),
+        }
+    }
+
+    #[test]
+    fn reports_once_per_excursion() {
+        let mut monitor = NoiseFloorMonitor::new(Some(10.0), Some(100.0));
+        monitor.observe(&packet(Channel::Ehz, 50.0));
+        let results = monitor.check();
+        assert_eq!(results.len(), 1);
+        let (channel, rms, newly) = results[0];
+        assert_eq!(channel, Channel::Ehz);
+        assert!((rms - 50.0).abs() < 1e-6, "{rms}");
+        assert!(!newly);
+
+        // A dead sensor is too quiet.
+        monitor.observe(&packet(Channel::Ehz, 1.0));
+        assert!(monitor.check()[0].2);
+        monitor.observe(&packet(Channel::Ehz, 1.0));
+        assert!(!monitor.check()[0].2);
+        assert!(monitor.check().is_empty());
+    }
+}
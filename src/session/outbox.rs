@@ -0,0 +1,199 @@
+//! Holding MQTT posts while the broker is unreachable, so that alarms
+//! raised during an outage are still delivered once it is over.
+use std::collections::VecDeque;
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch};
+use tokio::time::Duration;
+
+use super::mqtt::{AsyncClient, ClientError, QoS};
+use super::retry::RetryPolicy;
+use super::status::StatusBoard;
+use crate::config::QueueDropPolicy;
+
+/// Shortest wait before reconnecting to the broker.
+const RECONNECT_MIN: Duration = Duration::from_secs(1);
+
+/// A cloneable handle on the state of the connection to the MQTT broker.
+#[derive(Clone)]
+pub struct MqttConnection {
+    connected: Arc<watch::Sender<bool>>,
+    reconnect_max: Duration,
+}
+
+impl MqttConnection {
+    /// Track a connection which, when lost, is retried with waits which
+    /// double each time, up to `reconnect_max_s`.
+    pub fn new(reconnect_max_s: f32) -> Self {
+        Self {
+            connected: Arc::new(watch::Sender::new(false)),
+            reconnect_max: Duration::from_secs_f32(reconnect_max_s).max(RECONNECT_MIN),
+        }
+    }
+
+    pub fn set_connected(&self, connected: bool) {
+        self.connected.send_replace(connected);
+    }
+
+    /// Watch for changes to the connection state.
+    pub fn subscribe(&self) -> watch::Receiver<bool> {
+        self.connected.subscribe()
+    }
+
+    /// Send an index each time the connection is made (again), until the
+    /// receiver goes away, for one of several connections to tell which.
+    pub fn report_reconnections(&self, index: usize, reconnected: mpsc::UnboundedSender<usize>) {
+        let mut connected = self.subscribe();
+        tokio::spawn(async move {
+            while connected.changed().await.is_ok() {
+                if *connected.borrow_and_update() && reconnected.send(index).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    /// How long to wait before reconnecting after the last wait.
+    pub fn backoff(&self, last: Option<Duration>) -> Duration {
+        last.map_or(RECONNECT_MIN, |last| (last * 2).min(self.reconnect_max))
+    }
+}
+
+/// A post waiting to be made.
+pub struct Post {
+    pub topic: String,
+    pub payload: Vec<u8>,
+    pub qos: QoS,
+    pub retain: bool,
+}
+
+/// A bounded queue of posts made while the broker was unreachable.
+pub struct Outbox {
+    connected: watch::Receiver<bool>,
+    queue: VecDeque<Post>,
+    capacity: usize,
+    policy: QueueDropPolicy,
+}
+
+impl Outbox {
+    pub fn new(connection: &MqttConnection, capacity: usize, policy: QueueDropPolicy) -> Self {
+        Self {
+            connected: connection.subscribe(),
+            queue: VecDeque::new(),
+            capacity,
+            policy,
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        *self.connected.borrow()
+    }
+
+    /// Wait until the broker is reachable again.
+    pub async fn reconnected(&mut self) {
+        while self.connected.changed().await.is_ok() {
+            if *self.connected.borrow_and_update() {
+                return;
+            }
+        }
+        std::future::pending().await
+    }
+
+    /// Hold a post until the broker is reachable, dropping one if the
+    /// queue is full.
+    pub fn push(&mut self, post: Post) {
+        if self.queue.len() >= self.capacity {
+            log::warn!("MQTT queue full, dropping a post to {}", post.topic);
+            match self.policy {
+                QueueDropPolicy::Oldest => {
+                    self.queue.pop_front();
+                }
+                QueueDropPolicy::Newest => return,
+            }
+        }
+        if self.capacity > 0 {
+            self.queue.push_back(post);
+        }
+    }
+
+    /// Take the oldest post held.
+    pub fn pop(&mut self) -> Option<Post> {
+        self.queue.pop_front()
+    }
+}
+
+/// Make a post or, while the broker is unreachable, hold it in the outbox
+/// (if there is one), unless it is only to be posted at most once.
+/// Otherwise it is handed to the client just the once: the client only
+/// refuses it when its queue is closed, which retrying wouldn't help.
+/// Failures are counted on the status board, if any.
+pub async fn post_or_hold(
+    client: &AsyncClient,
+    outbox: Option<&mut Outbox>,
+    post: Post,
+    failures: Option<&StatusBoard>,
+) -> Result<(), ClientError> {
+    match outbox {
+        Some(outbox) if !outbox.is_connected() => {
+            if post.qos != QoS::AtMostOnce {
+                outbox.push(post);
+            }
+            Ok(())
+        }
+        _ => {
+            let what = format!("MQTT post to {}", post.topic);
+            RetryPolicy::default()
+                .attempt(&what, failures, || {
+                    client.publish(
+                        post.topic.as_str(),
+                        post.qos,
+                        post.retain,
+                        post.payload.as_slice(),
+                    )
+                })
+                .await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post(topic: &str) -> Post {
+        Post {
+            topic: topic.to_owned(),
+            payload: Vec::new(),
+            qos: QoS::AtLeastOnce,
+            retain: false,
+        }
+    }
+
+    #[test]
+    fn drops_by_policy() {
+        let connection = MqttConnection::new(60.0);
+        let mut oldest = Outbox::new(&connection, 2, QueueDropPolicy::Oldest);
+        let mut newest = Outbox::new(&connection, 2, QueueDropPolicy::Newest);
+        for outbox in [&mut oldest, &mut newest] {
+            for topic in ["a", "b", "c"] {
+                outbox.push(post(topic));
+            }
+        }
+        let topics = |outbox: &mut Outbox| -> Vec<String> {
+            std::iter::from_fn(|| outbox.pop().map(|p| p.topic)).collect()
+        };
+        assert_eq!(topics(&mut oldest), ["b", "c"]);
+        assert_eq!(topics(&mut newest), ["a", "b"]);
+    }
+
+    #[test]
+    fn backs_off() {
+        let connection = MqttConnection::new(5.0);
+        let mut waits = Vec::new();
+        let mut last = None;
+        for _ in 0..5 {
+            last = Some(connection.backoff(last));
+            waits.push(last.unwrap().as_secs());
+        }
+        assert_eq!(waits, [1, 2, 4, 5, 5]);
+    }
+}
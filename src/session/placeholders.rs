@@ -0,0 +1,136 @@
+use super::event_id::EventId;
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Values substituted for placeholders, such as `{flow}`, in MQTT
+/// payloads and command arguments.
+#[derive(Clone, Copy, Default)]
+pub struct Placeholders<'a> {
+    /// `{flow}`: the flow's name.
+    pub flow: &'a str,
+    /// `{seismometer}`: the name of the flow's seismometer, if it has
+    /// one (coincidence triggers across a network don't).
+    pub seismometer: Option<&'a str>,
+    /// `{channel}`: the flow's channel, if it has one.
+    pub channel: Option<&'a str>,
+    /// `{timestamp}`: the data time of the event, in seconds since the
+    /// epoch.
+    pub timestamp: f64,
+    /// `{energy}`: the energy fed to the trigger when it asserted, or
+    /// its peak while asserted once it resets.
+    pub energy: Option<f64>,
+    /// `{event_id}`: the identifier of the flow's latest event, shared by
+    /// its trigger and its reset.
+    pub event_id: Option<EventId>,
+}
+
+impl<'a> Placeholders<'a> {
+    /// Placeholders for an event with no data time of its own, which
+    /// takes the host's time instead.
+    pub fn now(flow: &'a str) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        Self {
+            flow,
+            timestamp,
+            ..Default::default()
+        }
+    }
+
+    fn value(&self, name: &str) -> Option<String> {
+        let value = match name {
+            "flow" => self.flow.to_owned(),
+            "seismometer" => self.seismometer.unwrap_or_default().to_owned(),
+            "channel" => self.channel.unwrap_or_default().to_owned(),
+            "timestamp" => format!("{:.3}", self.timestamp),
+            "energy" => self.energy.map(|e| e.to_string()).unwrap_or_default(),
+            "event_id" => self.event_id.map(|id| id.to_string()).unwrap_or_default(),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Expand every placeholder in a template. Placeholders without a
+    /// value expand to nothing, and unrecognized ones are left alone.
+    pub fn expand(&self, template: &str) -> String {
+        let mut result = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(open) = rest.find('{') {
+            result.push_str(&rest[..open]);
+            rest = &rest[open..];
+            let value = rest
+                .find('}')
+                .and_then(|close| Some((close, self.value(&rest[1..close])?)));
+            match value {
+                Some((close, value)) => {
+                    result.push_str(&value);
+                    rest = &rest[close + 1..];
+                }
+                None => {
+                    result.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        result.push_str(rest);
+        result
+    }
+
+    /// Environment variables describing an event (such as "triggered")
+    /// to the programs run on it: RS_EVENT, RS_FLOW, RS_SEISMOMETER,
+    /// RS_CHANNEL, RS_TIMESTAMP, RS_PEAK (the energy, as for the
+    /// "{energy}" placeholder) and RS_EVENT_ID. Those without a value are
+    /// empty.
+    pub fn environment(&self, event: &str) -> [(&'static str, String); 7] {
+        let value = |name| self.value(name).unwrap_or_default();
+        [
+            ("RS_EVENT", event.to_owned()),
+            ("RS_FLOW", value("flow")),
+            ("RS_SEISMOMETER", value("seismometer")),
+            ("RS_CHANNEL", value("channel")),
+            ("RS_TIMESTAMP", value("timestamp")),
+            ("RS_PEAK", value("energy")),
+            ("RS_EVENT_ID", value("event_id")),
+        ]
+    }
+
+    /// Expand every placeholder in each of some templates.
+    pub fn expand_all(&self, templates: &[String]) -> Vec<String> {
+        templates.iter().map(|t| self.expand(t)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Placeholders;
+
+    #[test]
+    fn expands_placeholders() {
+        let placeholders = Placeholders {
+            flow: "garage",
+            seismometer: Some("rs1"),
+            channel: Some("EHZ"),
+            timestamp: 1700000000.25,
+            energy: None,
+            event_id: None,
+        };
+        assert_eq!(
+            placeholders.expand("{seismometer}/{flow} {channel} at {timestamp}"),
+            "rs1/garage EHZ at 1700000000.250"
+        );
+        assert_eq!(placeholders.expand("{energy}{event_id}"), "");
+        assert_eq!(
+            placeholders.expand(r#"{"flow": "{flow}", "x": {unknown}"#),
+            r#"{"flow": "garage", "x": {unknown}"#
+        );
+        let environment = placeholders.environment("reset");
+        assert_eq!(environment[0], ("RS_EVENT", String::from("reset")));
+        assert_eq!(
+            environment[4],
+            ("RS_TIMESTAMP", String::from("1700000000.250"))
+        );
+        assert_eq!(environment[5], ("RS_PEAK", String::new()));
+    }
+}
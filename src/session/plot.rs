@@ -0,0 +1,124 @@
+//! Plotting waveforms as small greyscale PNG images, for sending to
+//! people rather than to analysis tools.
+
+/// Size of a plot, in pixels.
+const WIDTH: usize = 800;
+const HEIGHT: usize = 200;
+
+/// Largest block of data that an uncompressed deflate block may hold.
+const STORED_BLOCK_MAX: usize = 65535;
+
+/// Plot samples as a black trace on white, scaled to fill the image. Each
+/// column spans the range of the samples that fall in it, so that peaks
+/// survive however many samples there are.
+pub fn waveform_png(samples: &[f32]) -> Vec<u8> {
+    let finite = || samples.iter().copied().filter(|v| v.is_finite());
+    let min = finite().fold(f32::INFINITY, f32::min);
+    let max = finite().fold(f32::NEG_INFINITY, f32::max);
+    let span = if max > min { max - min } else { 1.0 };
+    let row_of = |v: f32| {
+        let y = (max - v) / span * (HEIGHT - 1) as f32;
+        (y.round() as usize).min(HEIGHT - 1)
+    };
+
+    let mut pixels = vec![255u8; WIDTH * HEIGHT];
+    let mut last_row = None;
+    for x in 0..WIDTH {
+        let from = x * samples.len() / WIDTH;
+        let to = ((x + 1) * samples.len() / WIDTH).max(from + 1);
+        let rows = samples
+            .get(from..to.min(samples.len()))
+            .unwrap_or_default()
+            .iter()
+            .filter(|v| v.is_finite())
+            .map(|&v| row_of(v));
+        let (Some(top), Some(bottom)) = (rows.clone().min(), rows.max()) else {
+            continue;
+        };
+        // Join the trace up with the previous column.
+        let (top, bottom) = match last_row {
+            Some(last) => (top.min(last), bottom.max(last)),
+            None => (top, bottom),
+        };
+        for y in top..=bottom {
+            pixels[y * WIDTH + x] = 0;
+        }
+        last_row = Some(row_of(samples[to.min(samples.len()) - 1]));
+    }
+    encode_png(&pixels)
+}
+
+/// Encode 8-bit greyscale pixels as a PNG, without compression.
+fn encode_png(pixels: &[u8]) -> Vec<u8> {
+    // Each scanline is preceded by its filter type (none).
+    let mut raw = Vec::with_capacity(pixels.len() + HEIGHT);
+    for row in pixels.chunks(WIDTH) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    let mut zlib = vec![0x78, 0x01];
+    let blocks = raw.chunks(STORED_BLOCK_MAX);
+    let count = blocks.len();
+    for (i, block) in blocks.enumerate() {
+        let len = block.len() as u16;
+        zlib.push(u8::from(i + 1 == count));
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(WIDTH as u32).to_be_bytes());
+    header.extend_from_slice(&(HEIGHT as u32).to_be_bytes());
+    header.extend_from_slice(&[8, 0, 0, 0, 0]);
+
+    let mut png = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+    for (kind, data) in [(b"IHDR", &header), (b"IDAT", &zlib), (b"IEND", &Vec::new())] {
+        png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        let start = png.len();
+        png.extend_from_slice(kind);
+        png.extend_from_slice(data);
+        let crc = crc32(&png[start..]);
+        png.extend_from_slice(&crc.to_be_bytes());
+    }
+    png
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), &byte| {
+        let a = (a + byte as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+    (b << 16) | a
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| {
+            if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb88320
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_png() {
+        assert_eq!(crc32(b"IEND"), 0xae426082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e60398);
+
+        let samples: Vec<f32> = (0..2000).map(|i| (i as f32 * 0.05).sin()).collect();
+        let png = waveform_png(&samples);
+        assert_eq!(&png[1..4], b"PNG");
+        assert_eq!(&png[png.len() - 8..png.len() - 4], b"IEND");
+        // The image data is stored, so is at least as large as the image.
+        assert!(png.len() > WIDTH * HEIGHT);
+    }
+}
@@ -0,0 +1,59 @@
+//! The daemon's own availability, as posted over MQTT. Whenever the daemon
+//! connects to the broker it posts that it is online, and the broker
+//! posts that it is offline on its behalf (as its last will) should the
+//! connection be lost, so that subscribers needn't trust stale states.
+//! When the daemon stops it posts that it is offline itself, as the
+//! broker doesn't post the last will of a client which disconnects.
+use crate::config::MQTTConfig;
+
+use super::mqtt::{is_connected, AsyncClient, Event, QoS};
+
+pub struct DaemonPresence<'a> {
+    config: Option<&'a MQTTConfig>,
+    mqtt: Option<AsyncClient>,
+}
+
+impl<'a> DaemonPresence<'a> {
+    pub fn new(config: Option<&'a MQTTConfig>, mqtt: Option<AsyncClient>) -> Self {
+        Self { config, mqtt }
+    }
+
+    /// React to an event from the MQTT connection, posting the online
+    /// payload whenever a connection is made.
+    pub fn handle_mqtt_event(&self, event: &Event) {
+        if !is_connected(event) {
+            return;
+        }
+        let Some(config) = self.config else {
+            return;
+        };
+        let client = self.mqtt.as_ref();
+        if let Some((client, topic)) = client.zip(config.availability_topic.as_ref()) {
+            // The event loop is busy running this, so the request must
+            // not wait for room in the queue.
+            let payload = config.online_payload.as_bytes();
+            let _ = client.try_publish(topic, QoS::AtLeastOnce, true, payload);
+        }
+    }
+
+    /// Post that the daemon is offline, as it stops (unless it is
+    /// restarting, to come straight back online), and then disconnect from
+    /// the broker once everything posted before has been sent.
+    pub async fn leave(&self, restarting: bool) {
+        let Some(client) = self.mqtt.as_ref() else {
+            return;
+        };
+        let availability = self.config.filter(|_| !restarting).and_then(|config| {
+            let topic = config.availability_topic.as_ref()?;
+            Some((topic, config.offline_payload.as_bytes()))
+        });
+        if let Some((topic, payload)) = availability {
+            if let Err(e) = client.publish(topic, QoS::AtLeastOnce, true, payload).await {
+                log::warn!("can't announce daemon offline: {e}");
+            }
+        }
+        if let Err(e) = client.disconnect().await {
+            log::warn!("can't disconnect from MQTT broker: {e}");
+        }
+    }
+}
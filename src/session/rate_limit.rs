@@ -0,0 +1,76 @@
+//! Limiting how often a flow's notifications are sent, so that a noisy
+//! night with a marginal threshold doesn't send dozens of them, and
+//! counting those held back so that they can be summarized instead.
+use tokio::time::{Duration, Instant};
+
+/// Allows one action per interval, counting those which come too soon.
+pub struct RateLimiter {
+    interval: Duration,
+    /// When the interval of the last action allowed ends.
+    until: Option<Instant>,
+    /// Actions which have come too soon since the last one allowed.
+    suppressed: usize,
+}
+
+impl RateLimiter {
+    pub fn new(interval_s: f32) -> Self {
+        Self {
+            interval: Duration::from_secs_f32(interval_s),
+            until: None,
+            suppressed: 0,
+        }
+    }
+
+    /// Whether an action may be taken now. If not, it is counted as
+    /// suppressed.
+    pub fn allow(&mut self, now: Instant) -> bool {
+        match self.until {
+            Some(until) if now < until => {
+                self.suppressed += 1;
+                false
+            }
+            _ => {
+                self.until = Some(now + self.interval);
+                true
+            }
+        }
+    }
+
+    /// When a summary of the actions suppressed is due, if any were.
+    pub fn summary_due(&self) -> Option<Instant> {
+        self.until.filter(|_| self.suppressed > 0)
+    }
+
+    /// The number of actions suppressed, once the interval in which they
+    /// came has ended.
+    pub fn take_summary(&mut self, now: Instant) -> Option<usize> {
+        let due = self.summary_due()?;
+        (now >= due).then(|| std::mem::take(&mut self.suppressed))
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allows_one_per_interval() {
+        let mut limiter = RateLimiter::new(60.0);
+        let start = Instant::now();
+        let at = |s: u64| start + Duration::from_secs(s);
+        assert!(limiter.allow(at(0)));
+        assert!(limiter.summary_due().is_none());
+        assert!(!limiter.allow(at(10)));
+        assert!(!limiter.allow(at(20)));
+        assert_eq!(limiter.summary_due(), Some(at(60)));
+        assert_eq!(limiter.take_summary(at(59)), None);
+        assert_eq!(limiter.take_summary(at(60)), Some(2));
+        assert_eq!(limiter.take_summary(at(61)), None);
+        assert!(limiter.allow(at(61)));
+        assert!(!limiter.allow(at(62)));
+    }
+}
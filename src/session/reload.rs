@@ -0,0 +1,170 @@
+//! Noticing when the daemon should reload its configuration: when it is
+//! sent SIGHUP (on Unix) and, if asked to, whenever the configuration file (or a
+//! file it includes) changes, as when it is managed by Ansible or edited
+//! in place.
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+#[cfg(unix)]
+use tokio::signal::unix::{signal, Signal, SignalKind};
+use tokio::time::Duration;
+
+/// How often to check watched configuration files for changes.
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// SIGHUP, which there is no such thing as but on Unix.
+#[cfg(unix)]
+type Hangup = Signal;
+#[cfg(not(unix))]
+type Hangup = std::convert::Infallible;
+
+pub struct ReloadTrigger {
+    hangup: Option<Hangup>,
+    /// The main configuration file, then any it includes, if watched.
+    watched: Option<Vec<WatchedFile>>,
+}
+
+/// A file (or directory), and its modification time and size when last
+/// checked (if it was there).
+struct WatchedFile {
+    path: PathBuf,
+    stamp: Option<(SystemTime, u64)>,
+}
+
+impl ReloadTrigger {
+    /// Reload the configuration at `path` on SIGHUP and, if `watch` is
+    /// set, whenever the file changes. With no file, there is nothing to
+    /// reload, and it is never time to.
+    pub fn new(path: Option<&Path>, watch: bool) -> std::io::Result<Self> {
+        let Some(path) = path else {
+            return Ok(Self {
+                hangup: None,
+                watched: None,
+            });
+        };
+        let watched = watch.then(|| vec![WatchedFile::new(path.to_owned())]);
+        Ok(Self {
+            hangup: hangup_signal()?,
+            watched,
+        })
+    }
+
+    /// Watch the paths which the configuration, as now loaded, includes
+    /// (if the configuration file is watched at all), in place of those
+    /// it included before.
+    pub fn watch_included(&mut self, paths: Vec<PathBuf>) {
+        if let Some(watched) = self.watched.as_mut() {
+            watched.truncate(1);
+            watched.extend(paths.into_iter().map(WatchedFile::new));
+        }
+    }
+
+    /// Wait until it is time to reload the configuration.
+    pub async fn requested(&mut self) {
+        tokio::select! {
+            () = hangup(&mut self.hangup) => log::info!("hung up, reloading configuration"),
+            () = changed(&mut self.watched) => log::info!("configuration changed, reloading it"),
+        }
+        // Whatever the files now hold is what is reloaded, so it is not
+        // to be reloaded again until they change once more.
+        for watched in self.watched.iter_mut().flatten() {
+            watched.stamp = stamp(&watched.path);
+        }
+    }
+}
+
+impl WatchedFile {
+    fn new(path: PathBuf) -> Self {
+        let stamp = stamp(&path);
+        Self { path, stamp }
+    }
+}
+
+#[cfg(unix)]
+fn hangup_signal() -> std::io::Result<Option<Hangup>> {
+    Ok(Some(signal(SignalKind::hangup())?))
+}
+
+#[cfg(not(unix))]
+fn hangup_signal() -> std::io::Result<Option<Hangup>> {
+    Ok(None)
+}
+
+#[cfg(unix)]
+async fn hangup(hangup: &mut Option<Hangup>) {
+    if let Some(hangup) = hangup.as_mut() {
+        if hangup.recv().await.is_some() {
+            return;
+        }
+    }
+    std::future::pending().await
+}
+
+#[cfg(not(unix))]
+async fn hangup(_hangup: &mut Option<Hangup>) {
+    std::future::pending().await
+}
+
+// Wait for any watched file to change. A file which goes missing (as
+// while it is being replaced) is waited for to come back.
+async fn changed(watched: &mut Option<Vec<WatchedFile>>) {
+    let Some(watched) = watched.as_mut() else {
+        return std::future::pending().await;
+    };
+    let mut ticker = tokio::time::interval(WATCH_INTERVAL);
+    loop {
+        ticker.tick().await;
+        let mut changed = false;
+        for watched in watched.iter_mut() {
+            let stamp = stamp(&watched.path);
+            if stamp != watched.stamp {
+                watched.stamp = stamp;
+                changed |= stamp.is_some();
+            }
+        }
+        if changed {
+            return;
+        }
+    }
+}
+
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn notices_changes() {
+        let path = std::env::temp_dir().join(format!("rs-udp-reload-{}.json", std::process::id()));
+        std::fs::write(&path, "{}").expect("write");
+        let mut trigger = ReloadTrigger::new(Some(&path), true).expect("trigger");
+        let quickly = Duration::from_millis(100);
+        let requested = tokio::time::timeout(quickly, trigger.requested()).await;
+        assert!(requested.is_err());
+        std::fs::write(&path, "{\"seismometers\": []}").expect("rewrite");
+        let requested = tokio::time::timeout(quickly, trigger.requested()).await;
+        assert!(requested.is_ok());
+        std::fs::remove_file(&path).expect("clean up");
+    }
+
+    #[tokio::test]
+    async fn notices_changes_to_included_files() {
+        let dir = std::env::temp_dir().join(format!("rs-udp-reload-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("create dir");
+        let (path, included) = (dir.join("seismo.json"), dir.join("garage.json"));
+        std::fs::write(&path, r#"{"include": ["garage.json"]}"#).expect("write");
+        std::fs::write(&included, "{}").expect("write");
+        let mut trigger = ReloadTrigger::new(Some(&path), true).expect("trigger");
+        trigger.watch_included(vec![included.clone()]);
+        let quickly = Duration::from_millis(100);
+        std::fs::write(&included, "{\"seismometers\": []}").expect("rewrite");
+        let requested = tokio::time::timeout(quickly, trigger.requested()).await;
+        assert!(requested.is_ok());
+        let requested = tokio::time::timeout(quickly, trigger.requested()).await;
+        assert!(requested.is_err());
+        std::fs::remove_dir_all(&dir).expect("clean up");
+    }
+}
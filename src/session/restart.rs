@@ -0,0 +1,103 @@
+//! Reopening a seismometer's data source after it fails, so that one
+//! instrument's trouble (a port already taken, a server gone away) doesn't
+//! bring down the others.
+use crate::datasource::SourceAddress;
+
+use std::error::Error;
+use tokio::time::{Duration, Instant};
+
+/// How long to wait before the first attempt to reopen a failed source.
+const FIRST_WAIT: Duration = Duration::from_secs(1);
+
+pub struct SourceRestart {
+    address: SourceAddress,
+    wait: Duration,
+    wait_max: Duration,
+
+    /// When the source is next to be opened, while it is closed.
+    due: Option<Instant>,
+
+    /// When the source was last opened, while it is open.
+    opened: Option<Instant>,
+}
+
+impl SourceRestart {
+    /// Reopen the source, just opened at an address, whenever it fails,
+    /// waiting twice as long (up to `wait_max`) after each failure in a row
+    /// (but never less than a second).
+    pub fn new(address: SourceAddress, wait_max: Duration) -> Self {
+        Self {
+            address,
+            wait: FIRST_WAIT,
+            wait_max: wait_max.max(FIRST_WAIT),
+            due: None,
+            opened: Some(Instant::now()),
+        }
+    }
+
+    pub fn address(&self) -> &SourceAddress {
+        &self.address
+    }
+
+    /// Wait until the source is to be opened again, or forever if it is
+    /// open.
+    pub async fn due(&self) {
+        match self.due {
+            Some(due) => tokio::time::sleep_until(due).await,
+            None => std::future::pending().await,
+        }
+    }
+
+    pub fn opened(&mut self, now: Instant) {
+        self.due = None;
+        self.opened = Some(now);
+    }
+
+    /// Note that the source failed (or couldn't be opened), and return how
+    /// long it will be before it is opened again. A source which had been
+    /// open for a good while is tried again soon.
+    pub fn failed(&mut self, now: Instant) -> Duration {
+        if let Some(opened) = self.opened.take() {
+            if now.duration_since(opened) >= self.wait_max {
+                self.wait = FIRST_WAIT;
+            }
+        }
+        let wait = self.wait;
+        self.due = Some(now + wait);
+        self.wait = wait.saturating_mul(2).min(self.wait_max);
+        wait
+    }
+}
+
+/// An error and what caused it, all on one line.
+pub fn describe(error: &dyn Error) -> String {
+    let mut description = error.to_string();
+    let mut source = error.source();
+    while let Some(error) = source {
+        description.push_str(&format!(": {error}"));
+        source = error.source();
+    }
+    description
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backs_off_until_stable() {
+        let address = SourceAddress::Rsudp("127.0.0.1:8888".to_owned());
+        let mut restart = SourceRestart::new(address, Duration::from_secs(5));
+        let start = Instant::now();
+        let waits: Vec<u64> = (0..4)
+            .map(|_| {
+                restart.opened(start);
+                restart.failed(start).as_secs()
+            })
+            .collect();
+        assert_eq!(waits, vec![1, 2, 4, 5]);
+        restart.opened(start);
+        let later = start + Duration::from_secs(5);
+        assert_eq!(restart.failed(later), Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,91 @@
+//! Retrying actions which fail, so that a passing problem (a webhook's
+//! server restarting, say) doesn't mean a missed alarm. Actions are
+//! retried in tasks of their own, so that the waits hold nothing else up.
+use super::status::StatusBoard;
+use crate::config::ActionsConfig;
+
+use std::future::Future;
+use tokio::time::Duration;
+
+/// How many times, and how often, to retry a failed action.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RetryPolicy {
+    retries: u32,
+    wait: Duration,
+    wait_max: Duration,
+}
+
+impl RetryPolicy {
+    /// The policy the actions give, whose waits were checked as the
+    /// configuration was loaded.
+    pub fn new(actions: &ActionsConfig) -> Self {
+        Self {
+            retries: actions.retries,
+            wait: Duration::from_secs_f32(actions.retry_s),
+            wait_max: Duration::from_secs_f32(actions.retry_max_s),
+        }
+    }
+
+    /// Attempt an action (described by `what`), retrying it while it
+    /// fails and retries remain. If it fails for good, that is logged and
+    /// counted on the status board, if any, and the last error returned.
+    pub async fn attempt<T, E, F>(
+        &self,
+        what: &str,
+        failures: Option<&StatusBoard>,
+        mut action: impl FnMut() -> F,
+    ) -> Result<T, E>
+    where
+        E: std::fmt::Display,
+        F: Future<Output = Result<T, E>>,
+    {
+        let mut wait = self.wait;
+        for _ in 0..self.retries {
+            match action().await {
+                Ok(value) => return Ok(value),
+                Err(e) => log::warn!("{what} failed ({e}), retrying in {wait:?}"),
+            }
+            tokio::time::sleep(wait).await;
+            wait = wait.saturating_mul(2).min(self.wait_max);
+        }
+        let result = action().await;
+        if let Err(e) = result.as_ref() {
+            log::error!("action failed: {what}: {e}");
+            if let Some(failures) = failures {
+                failures.update(|status| status.failed_actions += 1);
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn retries_then_gives_up() {
+        let policy = RetryPolicy {
+            retries: 2,
+            wait: Duration::from_millis(1),
+            wait_max: Duration::from_millis(2),
+        };
+        let board = StatusBoard::new();
+        let mut tries = 0;
+        let result: Result<u32, &str> = policy
+            .attempt("flaky", Some(&board), || {
+                tries += 1;
+                let result = if tries < 3 { Err("no") } else { Ok(tries) };
+                async move { result }
+            })
+            .await;
+        assert_eq!(result, Ok(3));
+        assert_eq!(board.snapshot().failed_actions, 0);
+
+        let result: Result<(), &str> = policy
+            .attempt("broken", Some(&board), || async { Err("no") })
+            .await;
+        assert_eq!(result, Err("no"));
+        assert_eq!(board.snapshot().failed_actions, 1);
+    }
+}
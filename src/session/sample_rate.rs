@@ -0,0 +1,123 @@
+use crate::datasource::{Channel, SeismoData};
+
+/// Packets arriving further apart than this many packet durations are
+/// taken to span a gap in the data, and don't count towards the measured
+/// sample rate.
+const GAP_FACTOR: f64 = 10.0;
+
+/// Measures a seismometer's actual sample rate from its packet timestamps
+/// and sample counts, for comparison against the configured rate.
+pub struct SampleRateMonitor {
+    configured_hz: f64,
+    tolerance: f64,
+    last_by_channel: Vec<Option<(f64, usize)>>,
+    /// Channels sent at rates of their own, which aren't measured.
+    excluded: Vec<bool>,
+    samples: usize,
+    seconds: f64,
+    mismatched: bool,
+}
+
+impl SampleRateMonitor {
+    /// Monitor against the configured sample rate, which the measured
+    /// rate may differ from by at most `tolerance` (a fraction).
+    pub fn new(configured_hz: f32, tolerance: f32) -> Self {
+        Self {
+            configured_hz: configured_hz as f64,
+            tolerance: tolerance as f64,
+            last_by_channel: vec![None; Channel::max()],
+            excluded: vec![false; Channel::max()],
+            samples: 0,
+            seconds: 0.0,
+            mismatched: false,
+        }
+    }
+
+    pub fn configured_hz(&self) -> f64 {
+        self.configured_hz
+    }
+
+    /// Leave a channel sent at a rate of its own out of the measurement.
+    pub fn exclude(&mut self, channel: Channel) {
+        self.excluded[channel as usize] = true;
+    }
+
+    /// Note a received packet. The previous packet on the same channel
+    /// held the samples taken between its timestamp and this one's.
+    pub fn observe(&mut self, data: &SeismoData) {
+        if data.timestamp <= 0.0 || self.excluded[data.channel as usize] {
+            return;
+        }
+        let last =
+            self.last_by_channel[data.channel as usize].replace((data.timestamp, data.data.len()));
+        let Some((last_timestamp, last_samples)) = last else {
+            return;
+        };
+        let elapsed = data.timestamp - last_timestamp;
+        let expected = last_samples as f64 / self.configured_hz;
+        if elapsed > 0.0 && elapsed < expected * GAP_FACTOR {
+            self.samples += last_samples;
+            self.seconds += elapsed;
+        }
+    }
+
+    /// Conclude an interval, returning the measured sample rate (if there
+    /// was enough data to measure one) and whether it has just gone out
+    /// of tolerance.
+    pub fn check(&mut self) -> Option<(f64, bool)> {
+        if self.seconds <= 0.0 {
+            return None;
+        }
+        let measured = self.samples as f64 / self.seconds;
+        self.samples = 0;
+        self.seconds = 0.0;
+        let was_mismatched = self.mismatched;
+        self.mismatched =
+            (measured - self.configured_hz).abs() > self.configured_hz * self.tolerance;
+        Some((measured, self.mismatched && !was_mismatched))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::Array1;
+
+    fn packet(channel: Channel, timestamp: f64) -> SeismoData {
+        SeismoData {
+            timestamp,
+            channel,
+            data: Array1::zeros(25),
+        }
+    }
+
+    #[test]
+    fn measures_rate() {
+        // Configured for 100 Hz, but really running at 50 Hz.
+        let mut monitor = SampleRateMonitor::new(100.0, 0.05);
+        for i in 0..8 {
+            monitor.observe(&packet(Channel::Ehz, 1000.0 + i as f64 * 0.5));
+            monitor.observe(&packet(Channel::Enz, 1000.1 + i as f64 * 0.5));
+        }
+        // A gap shouldn't count.
+        monitor.observe(&packet(Channel::Ehz, 2000.0));
+        let (measured, newly) = monitor.check().unwrap();
+        assert!((measured - 50.0).abs() < 1e-9);
+        assert!(newly);
+
+        monitor.observe(&packet(Channel::Ehz, 2000.5));
+        assert_eq!(monitor.check(), Some((50.0, false)));
+        assert_eq!(monitor.check(), None);
+    }
+
+    #[test]
+    fn leaves_out_excluded_channels() {
+        let mut monitor = SampleRateMonitor::new(100.0, 0.05);
+        monitor.exclude(Channel::Enz);
+        for i in 0..8 {
+            // Sent at 10 Hz, as that flow is configured for.
+            monitor.observe(&packet(Channel::Enz, 1000.0 + i as f64 * 2.5));
+        }
+        assert_eq!(monitor.check(), None);
+    }
+}
@@ -0,0 +1,112 @@
+//! Trigger levels which change with the time of day, as when a household
+//! is noisier by day than by night.
+use crate::config::ThresholdProfileConfig;
+use crate::time::local_minute_of_day;
+
+pub struct ThresholdSchedule {
+    profiles: Vec<ThresholdProfileConfig>,
+
+    /// The flow's own trigger and reset levels, in force outside every
+    /// profile.
+    own_levels: (f32, f32),
+
+    /// The profile in force (by index), if any, once known.
+    active: Option<Option<usize>>,
+
+    /// The minute (since the epoch) as of which it was last checked.
+    checked: Option<i64>,
+}
+
+impl ThresholdSchedule {
+    pub fn new(profiles: Vec<ThresholdProfileConfig>, own_levels: (f32, f32)) -> Self {
+        Self {
+            profiles,
+            own_levels,
+            active: None,
+            checked: None,
+        }
+    }
+
+    /// The levels which a profile (or the flow itself) would have the
+    /// trigger assert and reset at.
+    pub fn levels(&self, profile: Option<&ThresholdProfileConfig>) -> (f32, f32) {
+        let (trigger_level, reset_level) = self.own_levels;
+        match profile {
+            Some(profile) => (
+                profile.trigger_level.unwrap_or(trigger_level),
+                profile.reset_level.unwrap_or(reset_level),
+            ),
+            None => self.own_levels,
+        }
+    }
+
+    pub fn profiles(&self) -> &[ThresholdProfileConfig] {
+        &self.profiles
+    }
+
+    /// The name of the profile in force, if any.
+    pub fn active(&self) -> Option<&str> {
+        let index = self.active.flatten()?;
+        Some(self.profiles[index].name.as_str())
+    }
+
+    /// Work out which profile is in force at a time (seconds since the
+    /// epoch), and return the levels to set if that has changed.
+    pub fn update(&mut self, timestamp: f64) -> Option<(f32, f32)> {
+        let minute = (timestamp / 60.0).floor() as i64;
+        if self.checked == Some(minute) {
+            return None;
+        }
+        self.checked = Some(minute);
+        let time_of_day = local_minute_of_day(timestamp);
+        let active = self.profiles.iter().position(|p| p.covers(time_of_day));
+        if self.active == Some(active) {
+            return None;
+        }
+        self.active = Some(active);
+        Some(self.levels(active.map(|index| &self.profiles[index])))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_the_time_of_day() {
+        // Profiles covering the whole day, so that the host's time zone
+        // doesn't matter.
+        let profiles: Vec<ThresholdProfileConfig> = serde_json::from_str(
+            r#"[
+                { "name": "always", "from": "00:00", "until": "00:00", "trigger_level": 5.0 },
+                { "name": "never", "from": "01:00", "until": "02:00", "trigger_level": 9.0 }
+            ]"#,
+        )
+        .expect("parses");
+        let mut schedule = ThresholdSchedule::new(profiles, (2.0, 1.0));
+        assert_eq!(schedule.update(1_000_000.0), Some((5.0, 1.0)));
+        assert_eq!(schedule.active(), Some("always"));
+        assert_eq!(schedule.update(1_000_010.0), None);
+        assert_eq!(schedule.update(1_003_600.0), None);
+    }
+
+    #[test]
+    fn switches_profiles() {
+        // Profiles which switch an hour from now, in the host's time zone.
+        let now = 1_000_000.0;
+        let minute = local_minute_of_day(now);
+        let at = |minute: u16| format!("{:02}:{:02}", minute % 1440 / 60, minute % 60);
+        let (from, until) = (at(minute), at(minute + 60));
+        let profiles: Vec<ThresholdProfileConfig> = serde_json::from_value(serde_json::json!([
+            { "name": "day", "from": from, "until": until, "trigger_level": 5.0 },
+            { "name": "night", "from": until, "until": from, "reset_level": 0.5 },
+        ]))
+        .expect("parses");
+        let mut schedule = ThresholdSchedule::new(profiles, (2.0, 1.0));
+        assert_eq!(schedule.update(now), Some((5.0, 1.0)));
+        assert_eq!(schedule.active(), Some("day"));
+        assert_eq!(schedule.update(now + 3600.0), Some((2.0, 0.5)));
+        assert_eq!(schedule.active(), Some("night"));
+        assert_eq!(schedule.update(now + 7200.0), None);
+    }
+}
@@ -1,89 +1,509 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use crate::config::{FilterConfig, FlowConfig};
+use super::capture::WaveformCapture;
+use super::flow_status::FlowStatusMeter;
+use super::ground_motion::GroundMotionMeter;
+use super::schedule::ThresholdSchedule;
+use crate::config::{
+    BlockConfig, DumpFormat, FilterBankOutputConfig, FlowConfig, OnePolePass, Precision,
+    RectifyMode, TaperWindow, ThresholdProfileConfig,
+};
 use crate::signal::{
-    AffineError, AffineTransformBuilder, Event, EventBlock, EventGeneratingBlock, FilterObserver,
-    FilterStep, LPFError, LowPassFilterBuilder, ObserverError, OnePoleError, OnePoleFilterBuilder,
-    OnePoleFilterType, ProcessingBlock, RectifyBuilder, RectifyType, SignalBlock, ThresholdError,
-    ThresholdTriggerBuilder,
+    read_template, AdaptiveError, AdaptiveTriggerBuilder, AffineError, AffineTransformBuilder,
+    ArAicPicker, ArAicPickerBuilder, BandPowerBuilder, BandPowerError, EnvelopeError,
+    EnvelopeFollowerBuilder, Event, EventBlock, EventGeneratingBlock, FilterBankBuilder,
+    FilterBankError, FilterBankOutput, FilterObserver, FilterStep, KurtosisError,
+    KurtosisTriggerBuilder, LPFError, LowPassFilterBuilder, NotchError, NotchFilterBuilder,
+    ObserverError, OnePoleError, OnePoleFilterBuilder, OnePoleFilterType, PickerError,
+    ProcessingBlock, RectifyBuilder, RectifyType, ResponseError, ResponseRemovalBuilder, RmsError,
+    RunningRmsBuilder, SignalBlock, TaperBuilder, TaperError, TaperType, TemplateError,
+    TemplateMatcherBuilder, ThresholdError, ThresholdTriggerBuilder, ZDetectorBuilder,
+    ZDetectorError,
 };
+use ndarray::ScalarOperand;
+use num_traits::{Float, One, Zero};
+use sci_rs::na::RealField;
+use std::fmt::Display;
+use std::iter::Sum;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum FlowError {
     #[error("can't construct affine transform")]
     Affine(#[from] AffineError),
-    #[error("can't construct one-pole dc filter")]
-    DCOnePole(#[source] OnePoleError),
-    #[error("can't construct one-pole ac filter")]
-    ACOnePole(#[source] OnePoleError),
+    #[error("can't construct one-pole filter")]
+    OnePole(#[from] OnePoleError),
     #[error("can't construct filter")]
     FilterError(#[from] LPFError),
+    #[error("can't construct notch filter")]
+    Notch(#[from] NotchError),
+    #[error("can't construct response removal")]
+    Response(#[from] ResponseError),
+    #[error("can't construct envelope detector")]
+    Envelope(#[from] EnvelopeError),
+    #[error("can't construct taper")]
+    Taper(#[from] TaperError),
+    #[error("can't construct running RMS")]
+    Rms(#[from] RmsError),
+    #[error("can't construct band power estimator")]
+    BandPower(#[from] BandPowerError),
+    #[error("can't construct filter bank")]
+    FilterBank(#[from] FilterBankError),
     #[error("can't set up trigger")]
     Trigger(#[source] ThresholdError),
+    #[error("can't set up adaptive trigger")]
+    AdaptiveTrigger(#[from] AdaptiveError),
+    #[error("can't set up Z-detector")]
+    ZDetector(#[from] ZDetectorError),
+    #[error("can't set up kurtosis detector")]
+    Kurtosis(#[from] KurtosisError),
+    #[error("can't set up template matcher")]
+    Template(#[from] TemplateError),
+    #[error("can't set up phase picker")]
+    Picker(#[from] PickerError),
+    #[error("flow needs exactly one of \"filter\" or \"blocks\"")]
+    PipelineUnspecified,
+    #[error("flow's blocks must end with its only trigger block")]
+    TriggerNotLast,
+    #[error("flow's tiers must be in order of increasing level")]
+    TiersUnordered,
+    #[error("flow's trigger has no levels to adjust")]
+    NoLevels,
+    #[error("can't adjust trigger levels: {0}")]
+    Levels(#[source] ThresholdError),
     #[error("can't open debug dump file")]
     DebugDumpError(#[from] ObserverError),
 }
 
 pub struct TriggerResult {
     pub triggered: bool,
+
+    /// The index, within the input, of the sample at which the trigger
+    /// asserted.
+    pub triggered_at: Option<usize>,
+
+    /// The energy fed to the trigger at the sample at which it asserted.
+    pub trigger_energy: Option<f64>,
+
+    /// The tier (counting from 1) that the event escalated to, if it
+    /// escalated, the index within the input of the sample at which it
+    /// did, and the energy fed to the trigger then.
+    pub escalated_to: Option<usize>,
+    pub escalated_at: Option<usize>,
+    pub escalation_energy: Option<f64>,
+
     pub reset: bool,
+
+    /// The index, within the input, of the sample at which the trigger
+    /// reset.
+    pub reset_at: Option<usize>,
+
+    /// The peak energy fed to the trigger between asserting and
+    /// resetting, if it reset.
+    pub peak_energy: Option<f64>,
+
+    /// The index, within the input, of the sample at which the trigger was
+    /// forcibly reset, having stayed asserted for too long.
+    pub stuck_reset_at: Option<usize>,
+
+    /// The filters produced a non-finite value and had to be reset. An
+    /// event in progress is reset along with them, as of the start of the
+    /// input.
+    pub non_finite_reset: bool,
+
+    /// The greatest energy fed to the trigger over the input, unless the
+    /// filters had to be reset.
+    pub energy: Option<f64>,
+}
+
+/// A type that a flow's pipeline may process samples as.
+pub trait Sample: RealField + Float + Copy + Sum + One + Zero + ScalarOperand + Display {}
+
+impl Sample for f32 {}
+impl Sample for f64 {}
+
+/// A flow's pipeline and its debug dump observer, at the precision
+/// configured for the flow. Input always arrives as `f32`.
+pub enum FlowPipeline {
+    F32(Pipeline<f32>, FilterObserver<f32>),
+    F64(Pipeline<f64>, FilterObserver<f64>),
 }
 
-/// A reproduction of the all-in-one trigger processing flow that existed
-/// before the signal block refactoring. This interface will disappear
-/// and be replaced with one where the user needs to build their own
-/// blocks in the configuration file.
-pub struct ClassicTrigger {
-    affine: ProcessingBlock<f32>,
-    lpf: ProcessingBlock<f32>,
-    dc_remove: ProcessingBlock<f32>,
-    square: ProcessingBlock<f32>,
-    ac_remove: ProcessingBlock<f32>,
-    threshold: EventGeneratingBlock<f32>,
-    processed: usize,
+impl FlowPipeline {
+    /// Process a packet's samples, the first of which is at a data time.
+    pub fn process(&mut self, input: &ndarray::Array1<f32>, timestamp: f64) -> TriggerResult {
+        match self {
+            Self::F32(pipeline, obs) => {
+                obs.start_packet(timestamp);
+                pipeline.process(input, obs)
+            }
+            Self::F64(pipeline, obs) => {
+                obs.start_packet(timestamp);
+                pipeline.process(&input.mapv(f64::from), obs)
+            }
+        }
+    }
+
+    /// The levels at which the flow's trigger asserts and resets, if it
+    /// is a threshold trigger.
+    pub fn trigger_levels(&self) -> Option<(f32, f32)> {
+        match self {
+            Self::F32(pipeline, _) => pipeline.trigger_levels(),
+            Self::F64(pipeline, _) => pipeline.trigger_levels(),
+        }
+    }
+
+    /// Seconds by which the signal fed to the flow's trigger lags the
+    /// flow's input, and so by which trigger times are moved back.
+    pub fn trigger_delay_s(&self) -> f64 {
+        match self {
+            Self::F32(pipeline, _) => pipeline.trigger_delay_s(),
+            Self::F64(pipeline, _) => pipeline.trigger_delay_s(),
+        }
+    }
+
+    /// Change the levels at which the flow's trigger asserts and resets
+    /// (leaving either as it is if not given), returning them as they
+    /// now are.
+    pub fn set_trigger_levels(
+        &mut self,
+        trigger: Option<f32>,
+        reset: Option<f32>,
+    ) -> Result<(f32, f32), FlowError> {
+        match self {
+            Self::F32(pipeline, _) => pipeline.set_trigger_levels(trigger, reset),
+            Self::F64(pipeline, _) => pipeline.set_trigger_levels(trigger, reset),
+        }
+    }
+
+    /// Change the number of samples the flow's trigger is held off for
+    /// after starting, if it is a threshold trigger.
+    pub fn set_trigger_holdoff(&mut self, holdoff: usize) -> Result<(), FlowError> {
+        match self {
+            Self::F32(pipeline, _) => pipeline.set_trigger_holdoff(holdoff),
+            Self::F64(pipeline, _) => pipeline.set_trigger_holdoff(holdoff),
+        }
+    }
+
+    /// Start dumping the flow's filter process to a file (from scratch,
+    /// if it is already being dumped).
+    pub fn start_dump(&mut self, path: &Path, sample_rate_hz: f32) -> Result<(), FlowError> {
+        match self {
+            Self::F32(_, obs) => *obs = FilterObserver::new_channel_dumper(path, sample_rate_hz)?,
+            Self::F64(_, obs) => *obs = FilterObserver::new_channel_dumper(path, sample_rate_hz)?,
+        }
+        Ok(())
+    }
 }
 
-impl ClassicTrigger {
+/// Debug dump steps observed between the input and the energy steps, in
+/// the order that they are expected to occur.
+const INTERMEDIATE_STEPS: [FilterStep; 3] = [
+    FilterStep::Affined,
+    FilterStep::Filtered,
+    FilterStep::DCRemove,
+];
+
+/// A signal processing block, and the debug dump step which its output
+/// represents (if any).
+struct Stage<T: Sample> {
+    block: ProcessingBlock<T>,
+    step: Option<FilterStep>,
+}
+
+/// A flow's signal processing blocks, applied in order, followed by the
+/// trigger block that the result is fed to.
+pub struct Pipeline<T: Sample> {
+    stages: Vec<Stage<T>>,
+    trigger: EventGeneratingBlock<T>,
+
+    /// Samples fed to the trigger since it was last reset.
+    trigger_processed: usize,
+
+    /// Peak energy fed to the trigger since it asserted, while it stays
+    /// asserted.
+    event_peak: Option<T>,
+
+    /// The energy levels of the flow's tiers, in increasing order, and
+    /// the number of them that the latest event has reached.
+    tiers: Vec<T>,
+    severity: usize,
+
+    /// Buffers which the stages take turns to process into. After the
+    /// stages have run, the first holds the signal fed to the trigger.
+    scratch: [ndarray::Array1<T>; 2],
+}
+
+impl<T: Sample> Pipeline<T> {
     pub fn process(
         &mut self,
-        input: &ndarray::Array1<f32>,
-        obs: &mut FilterObserver<f32>,
+        input: &ndarray::Array1<T>,
+        obs: &mut FilterObserver<T>,
     ) -> TriggerResult {
-        let n = self.processed;
-        obs.observe(FilterStep::Input, n, input);
-        let post_affine = self.affine.process(input);
-        obs.observe(FilterStep::Affined, n, &post_affine);
-        let post_lpf = self.lpf.process(&post_affine);
-        obs.observe(FilterStep::Filtered, n, &post_lpf);
-        let post_dc_remove = self.dc_remove.process(&post_lpf);
-        obs.observe(FilterStep::DCRemove, n, &post_dc_remove);
-        let post_square = self.square.process(&post_dc_remove);
-        let post_ac_remove = self.ac_remove.process(&post_square);
-        obs.observe(FilterStep::Energy, n, &post_ac_remove);
-        let mut triggered = false;
-        let mut reset = false;
-        let obs = |event: Event<f32>| {
+        if obs.is_active() {
+            self.scratch[0] = self.run_stages_observed(input, obs);
+        } else {
+            self.run_stages(input);
+        }
+
+        if self.scratch[0].iter().any(|v| !v.is_finite()) {
+            // A NaN or infinity would otherwise stick in the filter
+            // memories forever.
+            let in_event = self.event_peak.is_some();
+            let peak_energy = self.event_peak.and_then(|peak| peak.to_f64());
+            self.reset();
+            return TriggerResult {
+                triggered: false,
+                triggered_at: None,
+                trigger_energy: None,
+                escalated_to: None,
+                escalated_at: None,
+                escalation_energy: None,
+                reset: in_event,
+                reset_at: in_event.then_some(0),
+                peak_energy,
+                stuck_reset_at: None,
+                non_finite_reset: true,
+                energy: None,
+            };
+        }
+        let mut triggered_at = None;
+        let mut trigger_energy = None;
+        let mut reset_at = None;
+        let mut peak_energy = None;
+        let mut stuck_reset_at = None;
+        let mut escalated = None;
+        let start = self.trigger_processed;
+        let energy = &self.scratch[0];
+        let event_peak = &mut self.event_peak;
+        let tiers = &self.tiers;
+        let severity = &mut self.severity;
+        // The samples before this one have been accounted for in the
+        // event peak.
+        let mut from = 0;
+        let obs = |event: Event<T>| {
             match event {
-                Event::Triggered(_when) => triggered = true,
-                Event::Reset(_when) => reset = true,
+                Event::Triggered(when) => {
+                    let at = when.saturating_sub(start);
+                    triggered_at = Some(at);
+                    *event_peak = energy.get(at).copied();
+                    trigger_energy = event_peak.and_then(|e| e.to_f64());
+                    *severity = 0;
+                    from = at;
+                }
+                Event::Reset(when) => {
+                    let at = when.saturating_sub(start);
+                    reset_at = Some(at);
+                    if event_peak.is_some() {
+                        escalated = escalate(tiers, severity, energy, from, at + 1).or(escalated);
+                    }
+                    peak_energy = event_peak
+                        .take()
+                        .map(|peak| peak_of(peak, energy.iter().take(at + 1).skip(from)))
+                        .and_then(|peak| peak.to_f64());
+                    from = at;
+                }
+                Event::StuckReset(when) => {
+                    stuck_reset_at = Some(when.saturating_sub(start));
+                    *event_peak = None;
+                }
                 _ => (),
             };
         };
-        self.threshold.process(&post_ac_remove, obs);
-        self.processed += input.len();
-        TriggerResult { triggered, reset }
+        self.trigger.process(energy, obs);
+        if let Some(peak) = self.event_peak.as_mut() {
+            let energy = &self.scratch[0];
+            *peak = peak_of(*peak, energy.iter().skip(from));
+            let end = energy.len();
+            escalated = escalate(&self.tiers, &mut self.severity, energy, from, end).or(escalated);
+        }
+        self.trigger_processed += input.len();
+        let energy = self.scratch[0].iter().copied().reduce(Float::max);
+        TriggerResult {
+            triggered: triggered_at.is_some(),
+            triggered_at,
+            trigger_energy,
+            escalated_to: escalated.map(|_| self.severity),
+            escalated_at: escalated.map(|(at, _)| at),
+            escalation_energy: escalated.and_then(|(_, e)| e.to_f64()),
+            reset: reset_at.is_some(),
+            reset_at,
+            peak_energy,
+            stuck_reset_at,
+            non_finite_reset: false,
+            energy: energy.and_then(|e| e.to_f64()),
+        }
     }
+
+    /// Run the input through every stage, leaving the result in the
+    /// first scratch buffer.
+    fn run_stages(&mut self, input: &ndarray::Array1<T>) {
+        for buffer in self.scratch.iter_mut() {
+            if buffer.len() != input.len() {
+                *buffer = ndarray::Array1::zeros(input.len());
+            }
+        }
+        let mut stages = self.stages.iter_mut();
+        let Some(first) = stages.next() else {
+            self.scratch[0].assign(input);
+            return;
+        };
+        first
+            .block
+            .process_into(&input.view(), &mut self.scratch[0]);
+        for stage in stages {
+            let [signal, out] = &mut self.scratch;
+            stage.block.process_into(&signal.view(), out);
+            self.scratch.swap(0, 1);
+        }
+    }
+
+    /// Run the input through every stage, dumping each step along the
+    /// way.
+    fn run_stages_observed(
+        &mut self,
+        input: &ndarray::Array1<T>,
+        obs: &mut FilterObserver<T>,
+    ) -> ndarray::Array1<T> {
+        obs.observe(FilterStep::Input, input);
+
+        // Steps the pipeline doesn't have are dumped as unchanged from
+        // the previous step.
+        let mut signal = input.clone();
+        let mut checkpoint = input.clone();
+        let mut next_step = 0;
+        for stage in self.stages.iter_mut() {
+            signal = stage.block.process(&signal);
+            let Some(k) = stage
+                .step
+                .and_then(|step| INTERMEDIATE_STEPS.iter().position(|s| *s == step))
+            else {
+                continue;
+            };
+            if k < next_step {
+                continue;
+            }
+            for step in &INTERMEDIATE_STEPS[next_step..k] {
+                obs.observe(*step, &checkpoint);
+            }
+            obs.observe(INTERMEDIATE_STEPS[k], &signal);
+            checkpoint.clone_from(&signal);
+            next_step = k + 1;
+        }
+        for step in &INTERMEDIATE_STEPS[next_step..] {
+            obs.observe(*step, &checkpoint);
+        }
+        obs.observe(FilterStep::Energy, &signal);
+        signal
+    }
+
+    /// The levels at which the trigger asserts and resets, if it is a
+    /// threshold trigger.
+    pub fn trigger_levels(&self) -> Option<(f32, f32)> {
+        let EventGeneratingBlock::ThresholdTrigger(trigger) = &self.trigger else {
+            return None;
+        };
+        let (trigger, reset) = trigger.levels();
+        trigger.to_f32().zip(reset.to_f32())
+    }
+
+    /// Seconds by which the signal fed to the trigger lags the input.
+    pub fn trigger_delay_s(&self) -> f64 {
+        let delay_s = self.stages.iter().map(|stage| stage.block.delay_s()).sum::<T>();
+        delay_s.to_f64().unwrap_or(0.0)
+    }
+
+    /// Change the levels at which the trigger asserts and resets, if it
+    /// is a threshold trigger, returning them as they now are.
+    pub fn set_trigger_levels(
+        &mut self,
+        trigger: Option<f32>,
+        reset: Option<f32>,
+    ) -> Result<(f32, f32), FlowError> {
+        let EventGeneratingBlock::ThresholdTrigger(threshold) = &mut self.trigger else {
+            return Err(FlowError::NoLevels);
+        };
+        threshold
+            .set_levels(trigger.map(param), reset.map(param))
+            .map_err(FlowError::Levels)?;
+        self.trigger_levels().ok_or(FlowError::NoLevels)
+    }
+
+    /// Change the number of samples the trigger is held off for after
+    /// starting, if it is a threshold trigger.
+    pub fn set_trigger_holdoff(&mut self, holdoff: usize) -> Result<(), FlowError> {
+        let EventGeneratingBlock::ThresholdTrigger(threshold) = &mut self.trigger else {
+            return Err(FlowError::NoLevels);
+        };
+        threshold.set_holdoff(holdoff);
+        Ok(())
+    }
+
+    /// Return every stage to its initial state.
+    pub fn reset(&mut self) {
+        for stage in self.stages.iter_mut() {
+            stage.block.reset();
+        }
+        self.trigger.reset();
+        self.trigger_processed = 0;
+        self.event_peak = None;
+        self.severity = 0;
+    }
+}
+
+/// Escalate an event through the tiers, as far as the energy of some of
+/// its samples (from index `from` up to `to`) takes it, given that it
+/// has reached `severity` of them. Returns the index of the sample at
+/// which it first escalated, and the energy then, if it did.
+fn escalate<T: Sample>(
+    tiers: &[T],
+    severity: &mut usize,
+    energy: &ndarray::Array1<T>,
+    from: usize,
+    to: usize,
+) -> Option<(usize, T)> {
+    let next = *tiers.get(*severity)?;
+    let samples = || energy.iter().copied().enumerate().take(to);
+    let (at, first) = samples().skip(from).find(|&(_, v)| v >= next)?;
+    let peak = peak_of(first, energy.iter().take(to).skip(at));
+    *severity = tiers.iter().take_while(|&&level| level <= peak).count();
+    Some((at, first))
+}
+
+/// The greater of a peak and the greatest of some samples.
+fn peak_of<'a, T: Sample>(peak: T, samples: impl Iterator<Item = &'a T>) -> T {
+    samples.fold(peak, |peak, &v| Float::max(peak, v))
 }
 
 pub struct SensorFlow {
-    pub trigger: ClassicTrigger,
-    pub dumper: FilterObserver<f32>,
+    pub pipeline: FlowPipeline,
+    pub capture: Option<WaveformCapture>,
+    pub ground_motion: GroundMotionMeter,
+    pub picker: Option<ArAicPicker<f32>>,
+    pub status: Option<FlowStatusMeter>,
+    pub schedule: Option<ThresholdSchedule>,
+    pub sample_rate_hz: f32,
 }
 
 impl SensorFlow {
-    pub fn new(trigger: ClassicTrigger, dumper: FilterObserver<f32>) -> Self {
-        SensorFlow { dumper, trigger }
+    pub fn new(
+        pipeline: FlowPipeline,
+        capture: Option<WaveformCapture>,
+        ground_motion: GroundMotionMeter,
+        picker: Option<ArAicPicker<f32>>,
+        status: Option<FlowStatusMeter>,
+        schedule: Option<ThresholdSchedule>,
+        sample_rate_hz: f32,
+    ) -> Self {
+        SensorFlow {
+            pipeline,
+            capture,
+            ground_motion,
+            picker,
+            status,
+            schedule,
+            sample_rate_hz,
+        }
     }
 
     pub async fn from_config(
@@ -91,63 +511,562 @@ impl SensorFlow {
         flow_config: &FlowConfig,
         dump_override: Option<&PathBuf>,
     ) -> Result<SensorFlow, FlowError> {
-        let trigger = trigger_from_config(sample_rate_hz, &flow_config.filter)?;
-        let dump = match dump_override {
-            Some(path) => FilterObserver::new_channel_dumper(path)?,
-            None => FilterObserver::null()?,
+        let blocks = match (flow_config.filter.as_ref(), flow_config.blocks.as_ref()) {
+            (Some(filter), None) => filter.blocks(flow_config.tap),
+            (None, Some(blocks)) => blocks.clone(),
+            _ => return Err(FlowError::PipelineUnspecified),
+        };
+        let tiers: Vec<f32> = flow_config.tiers.iter().map(|tier| tier.level).collect();
+        if tiers.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(FlowError::TiersUnordered);
+        }
+        let mut pipeline = match flow_config.precision {
+            Precision::F32 => FlowPipeline::F32(
+                pipeline_from_config(sample_rate_hz, &blocks, &tiers)?,
+                observer_for(sample_rate_hz, flow_config, dump_override)?,
+            ),
+            Precision::F64 => FlowPipeline::F64(
+                pipeline_from_config(sample_rate_hz, &blocks, &tiers)?,
+                observer_for(sample_rate_hz, flow_config, dump_override)?,
+            ),
+        };
+        let schedule = match flow_config.threshold_schedule.as_slice() {
+            [] => None,
+            profiles => Some(schedule_for(&mut pipeline, profiles)?),
         };
-        Ok(SensorFlow::new(trigger, dump))
+        let capture = flow_config
+            .capture
+            .as_ref()
+            .map(|c| WaveformCapture::from_config(c, &flow_config.name, sample_rate_hz));
+        // Ground motion is measured in the units that the (first) affine
+        // transform converts counts to.
+        let (gain, offset) = match flow_config.filter.as_ref() {
+            Some(filter) => (filter.gain, filter.offset),
+            None => blocks
+                .iter()
+                .find_map(|block| match *block {
+                    BlockConfig::Affine { offset, gain, .. } => Some((gain, offset)),
+                    _ => None,
+                })
+                .unwrap_or((1.0, 0.0)),
+        };
+        let ground_motion = GroundMotionMeter::new(flow_config.channel, sample_rate_hz, gain, offset);
+        let picker = flow_config
+            .picker
+            .as_ref()
+            .map(|picker| {
+                ArAicPickerBuilder::new()
+                    .sample_rate(sample_rate_hz)
+                    .window_s(picker.window_s)
+                    .order(picker.order)
+                    .build()
+            })
+            .transpose()?;
+        let status = flow_config
+            .status_interval_s
+            .map(|interval_s| FlowStatusMeter::new(sample_rate_hz, interval_s));
+        Ok(SensorFlow::new(
+            pipeline,
+            capture,
+            ground_motion,
+            picker,
+            status,
+            schedule,
+            sample_rate_hz,
+        ))
     }
 }
 
-fn trigger_from_config(
+/// A schedule of trigger levels for a flow's pipeline, having checked that
+/// its trigger will take the levels of every profile.
+fn schedule_for(
+    pipeline: &mut FlowPipeline,
+    profiles: &[ThresholdProfileConfig],
+) -> Result<ThresholdSchedule, FlowError> {
+    let (trigger_level, reset_level) = pipeline.trigger_levels().ok_or(FlowError::NoLevels)?;
+    let schedule = ThresholdSchedule::new(profiles.to_vec(), (trigger_level, reset_level));
+    for profile in schedule.profiles() {
+        let (trigger, reset) = schedule.levels(Some(profile));
+        pipeline.set_trigger_levels(Some(trigger), Some(reset))?;
+    }
+    pipeline.set_trigger_levels(Some(trigger_level), Some(reset_level))?;
+    Ok(schedule)
+}
+
+fn observer_for<T: Sample>(
+    sample_rate_hz: f32,
+    flow_config: &FlowConfig,
+    dump_override: Option<&PathBuf>,
+) -> Result<FilterObserver<T>, FlowError> {
+    let observer = match (dump_override, flow_config.dump_path.as_ref()) {
+        (Some(path), _) => FilterObserver::new_channel_dumper(path, sample_rate_hz)?,
+        (None, Some(path)) => FilterObserver::new_appending_dumper(
+            path,
+            sample_rate_hz,
+            flow_config.dump_format == DumpFormat::Csv,
+            flow_config.dump_max_size_mb.map(|mb| (mb * 1_000_000.0) as u64),
+            flow_config.dump_keep,
+        )?,
+        (None, None) => FilterObserver::null()?,
+    };
+    Ok(observer)
+}
+
+fn pipeline_from_config<T: Sample>(
+    sample_rate_hz: f32,
+    blocks: &[BlockConfig],
+    tiers: &[f32],
+) -> Result<Pipeline<T>, FlowError> {
+    let Some((last, signal_blocks)) = blocks.split_last() else {
+        return Err(FlowError::TriggerNotLast);
+    };
+    let stages = signal_blocks
+        .iter()
+        .map(|block| stage_from_config(sample_rate_hz, block))
+        .collect::<Result<Vec<_>, _>>()?;
+    let trigger = trigger_from_config(sample_rate_hz, last)?;
+    Ok(Pipeline {
+        stages,
+        trigger,
+        trigger_processed: 0,
+        event_peak: None,
+        tiers: tiers.iter().map(|&level| param(level)).collect(),
+        severity: 0,
+        scratch: [ndarray::Array1::zeros(0), ndarray::Array1::zeros(0)],
+    })
+}
+
+fn trigger_from_config<T: Sample>(
+    sample_rate_hz: f32,
+    block: &BlockConfig,
+) -> Result<EventGeneratingBlock<T>, FlowError> {
+    let trigger = match *block {
+        BlockConfig::Threshold {
+            trigger_level,
+            reset_level,
+            holdoff,
+            min_duration_s,
+            max_event_s,
+        } => {
+            let mut builder = ThresholdTriggerBuilder::new()
+                .trigger(param(trigger_level))
+                .reset(param(reset_level))
+                .holdoff(holdoff)
+                .min_duration(duration_samples(sample_rate_hz, min_duration_s));
+            if let Some(max_event_s) = max_event_s {
+                builder = builder.max_duration(duration_samples(sample_rate_hz, max_event_s));
+            }
+            builder.build().map_err(FlowError::Trigger)?.into()
+        }
+        BlockConfig::AdaptiveThreshold {
+            trigger_ratio,
+            reset_ratio,
+            window_s,
+            holdoff,
+            min_duration_s,
+            max_event_s,
+        } => {
+            let mut builder = AdaptiveTriggerBuilder::new()
+                .sample_rate(param(sample_rate_hz))
+                .window_s(param(window_s))
+                .trigger_ratio(param(trigger_ratio))
+                .reset_ratio(param(reset_ratio))
+                .holdoff(holdoff)
+                .min_duration(duration_samples(sample_rate_hz, min_duration_s));
+            if let Some(max_event_s) = max_event_s {
+                builder = builder.max_duration(duration_samples(sample_rate_hz, max_event_s));
+            }
+            builder.build()?.into()
+        }
+        BlockConfig::ZDetector {
+            short_s,
+            long_s,
+            trigger_z,
+            reset_z,
+            holdoff,
+            min_duration_s,
+            max_event_s,
+        } => {
+            let mut builder = ZDetectorBuilder::new()
+                .sample_rate(param(sample_rate_hz))
+                .short_s(param(short_s))
+                .long_s(param(long_s))
+                .trigger_z(param(trigger_z))
+                .reset_z(param(reset_z))
+                .holdoff(holdoff)
+                .min_duration(duration_samples(sample_rate_hz, min_duration_s));
+            if let Some(max_event_s) = max_event_s {
+                builder = builder.max_duration(duration_samples(sample_rate_hz, max_event_s));
+            }
+            builder.build()?.into()
+        }
+        BlockConfig::Kurtosis {
+            window_s,
+            trigger_level,
+            reset_level,
+            holdoff,
+            min_duration_s,
+            max_event_s,
+        } => {
+            let mut builder = KurtosisTriggerBuilder::new()
+                .sample_rate(param(sample_rate_hz))
+                .window_s(param(window_s))
+                .trigger(param(trigger_level))
+                .reset(param(reset_level))
+                .holdoff(holdoff)
+                .min_duration(duration_samples(sample_rate_hz, min_duration_s));
+            if let Some(max_event_s) = max_event_s {
+                builder = builder.max_duration(duration_samples(sample_rate_hz, max_event_s));
+            }
+            builder.build()?.into()
+        }
+        BlockConfig::TemplateMatch {
+            ref templates,
+            trigger_level,
+            reset_level,
+            holdoff,
+            min_duration_s,
+            max_event_s,
+        } => {
+            let mut builder = TemplateMatcherBuilder::new()
+                .trigger(param(trigger_level))
+                .reset(param(reset_level))
+                .holdoff(holdoff)
+                .min_duration(duration_samples(sample_rate_hz, min_duration_s));
+            for path in templates {
+                builder = builder.template(read_template(path)?);
+            }
+            if let Some(max_event_s) = max_event_s {
+                builder = builder.max_duration(duration_samples(sample_rate_hz, max_event_s));
+            }
+            builder.build()?.into()
+        }
+        _ => return Err(FlowError::TriggerNotLast),
+    };
+    Ok(trigger)
+}
+
+/// A configuration parameter, at the precision of a pipeline.
+fn param<T: Sample>(value: f32) -> T {
+    T::from(value).expect("f32 converts to any sample type")
+}
+
+/// The number of samples spanning a duration.
+fn duration_samples(sample_rate_hz: f32, duration_s: f32) -> usize {
+    (sample_rate_hz * duration_s).round() as usize
+}
+
+fn stage_from_config<T: Sample>(
     sample_rate_hz: f32,
-    filter: &FilterConfig,
-) -> Result<ClassicTrigger, FlowError> {
-    let affine: ProcessingBlock<f32> = AffineTransformBuilder::new()
-        .gain(filter.gain)
-        .offset(filter.offset)
-        .build()?
-        .into();
-    let lpf: ProcessingBlock<f32> = LowPassFilterBuilder::new()
-        .sample_rate(sample_rate_hz)
-        .cutoff_hz(filter.cutoff)
-        .order(filter.order as usize)
-        .build()?
-        .into();
-    let dc_remove: ProcessingBlock<f32> = OnePoleFilterBuilder::new()
-        .alpha(filter.dc_alpha)
-        .pass(OnePoleFilterType::HighPass)
-        .build()
-        .map_err(FlowError::DCOnePole)?
-        .into();
-    let square: ProcessingBlock<f32> = RectifyBuilder::new()
-        .rectify(RectifyType::Square)
-        .build()
-        .expect("how did you screw this one up?")
-        .into();
-    let ac_remove: ProcessingBlock<f32> = OnePoleFilterBuilder::new()
-        .alpha(filter.energy_alpha)
-        .pass(OnePoleFilterType::LowPass)
-        .build()
-        .map_err(FlowError::ACOnePole)?
-        .into();
-    let threshold: EventGeneratingBlock<f32> = ThresholdTriggerBuilder::new()
-        .trigger(filter.trigger_level)
-        .reset(filter.reset_level)
-        .holdoff(filter.holdoff)
-        .build()
-        .map_err(FlowError::Trigger)?
-        .into();
-    let processed: usize = 0;
-    let res = ClassicTrigger {
-        affine,
-        lpf,
-        dc_remove,
-        square,
-        ac_remove,
-        threshold,
-        processed,
+    block: &BlockConfig,
+) -> Result<Stage<T>, FlowError> {
+    let stage = match *block {
+        BlockConfig::Affine {
+            offset,
+            gain,
+            offset_calibration_s,
+        } => {
+            let mut builder = AffineTransformBuilder::new()
+                .gain(param(gain))
+                .offset(param(offset));
+            if let Some(calibration_s) = offset_calibration_s {
+                builder = builder.calibrate_offset(duration_samples(sample_rate_hz, calibration_s));
+            }
+            Stage {
+                block: builder.build()?.into(),
+                step: Some(FilterStep::Affined),
+            }
+        }
+        BlockConfig::LowPass { cutoff, order } => Stage {
+            block: LowPassFilterBuilder::new()
+                .sample_rate(param(sample_rate_hz))
+                .cutoff_hz(param(cutoff))
+                .order(order as usize)
+                .build()?
+                .into(),
+            step: Some(FilterStep::Filtered),
+        },
+        BlockConfig::Notch { frequency, q } => Stage {
+            block: NotchFilterBuilder::new()
+                .sample_rate(param(sample_rate_hz))
+                .center_hz(param(frequency))
+                .q(param(q))
+                .build()?
+                .into(),
+            step: None,
+        },
+        BlockConfig::Response {
+            ref zeros,
+            ref poles,
+            normalization,
+            sensitivity,
+            low_corner_hz,
+            high_corner_hz,
+        } => {
+            let mut builder = ResponseRemovalBuilder::new()
+                .sample_rate(param(sample_rate_hz))
+                .normalization(param(normalization))
+                .sensitivity(param(sensitivity))
+                .low_corner_hz(param(low_corner_hz));
+            for zero in zeros {
+                builder = builder.zero(param(zero[0]), param(zero[1]));
+            }
+            for pole in poles {
+                builder = builder.pole(param(pole[0]), param(pole[1]));
+            }
+            if let Some(hz) = high_corner_hz {
+                builder = builder.high_corner_hz(param(hz));
+            }
+            Stage {
+                block: builder.build()?.into(),
+                step: None,
+            }
+        }
+        BlockConfig::OnePole { alpha, pass } => {
+            let (pass, step) = match pass {
+                OnePolePass::LowPass => (OnePoleFilterType::LowPass, None),
+                OnePolePass::HighPass => (OnePoleFilterType::HighPass, Some(FilterStep::DCRemove)),
+            };
+            Stage {
+                block: OnePoleFilterBuilder::new()
+                    .alpha(param(alpha))
+                    .pass(pass)
+                    .build()?
+                    .into(),
+                step,
+            }
+        }
+        BlockConfig::Rectify { mode } => Stage {
+            block: RectifyBuilder::new()
+                .rectify(match mode {
+                    RectifyMode::Absolute => RectifyType::Absolute,
+                    RectifyMode::Square => RectifyType::Square,
+                })
+                .build()
+                .expect("how did you screw this one up?")
+                .into(),
+            step: None,
+        },
+        BlockConfig::Envelope {
+            attack_alpha,
+            release_alpha,
+        } => Stage {
+            block: EnvelopeFollowerBuilder::new()
+                .sample_rate(param(sample_rate_hz))
+                .attack(param(attack_alpha))
+                .release(param(release_alpha))
+                .build()?
+                .into(),
+            step: None,
+        },
+        BlockConfig::Taper {
+            window,
+            tukey_alpha,
+            chunk_s,
+        } => Stage {
+            block: TaperBuilder::new()
+                .sample_rate(param(sample_rate_hz))
+                .chunk_s(param(chunk_s))
+                .window(match window {
+                    TaperWindow::Hann => TaperType::Hann,
+                    TaperWindow::Tukey => TaperType::Tukey(param(tukey_alpha)),
+                })
+                .build()?
+                .into(),
+            step: None,
+        },
+        BlockConfig::Rms { window_s } => Stage {
+            block: RunningRmsBuilder::new()
+                .sample_rate(param(sample_rate_hz))
+                .window_s(param(window_s))
+                .build()?
+                .into(),
+            step: None,
+        },
+        BlockConfig::BandPower {
+            band,
+            reference_band,
+            window_s,
+        } => {
+            let mut builder = BandPowerBuilder::new()
+                .sample_rate(param(sample_rate_hz))
+                .window_s(param(window_s))
+                .band(param(band[0]), param(band[1]));
+            if let Some(reference) = reference_band {
+                builder = builder.reference_band(param(reference[0]), param(reference[1]));
+            }
+            Stage {
+                block: builder.build()?.into(),
+                step: Some(FilterStep::Energy),
+            }
+        }
+        BlockConfig::FilterBank {
+            ref bands,
+            order,
+            energy_alpha,
+            output,
+        } => {
+            let mut builder = FilterBankBuilder::new()
+                .sample_rate(param(sample_rate_hz))
+                .order(order as usize)
+                .alpha(param(energy_alpha))
+                .output(match output {
+                    FilterBankOutputConfig::Band(i) => FilterBankOutput::Band(i),
+                    FilterBankOutputConfig::Ratio([i, j]) => FilterBankOutput::Ratio(i, j),
+                });
+            for band in bands {
+                builder = builder.band(param(band[0]), param(band[1]));
+            }
+            Stage {
+                block: builder.build()?.into(),
+                step: Some(FilterStep::Energy),
+            }
+        }
+        BlockConfig::Threshold { .. }
+        | BlockConfig::AdaptiveThreshold { .. }
+        | BlockConfig::ZDetector { .. }
+        | BlockConfig::Kurtosis { .. }
+        | BlockConfig::TemplateMatch { .. } => return Err(FlowError::TriggerNotLast),
     };
-    Ok(res)
+    Ok(stage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blocks(json: &str) -> Vec<BlockConfig> {
+        serde_json::from_str(json).expect("valid block config")
+    }
+
+    #[test]
+    fn builds_custom_pipeline() {
+        let mut pipeline = pipeline_from_config(
+            100.0,
+            &blocks(
+                r#"[
+                    { "type": "rectify" },
+                    { "type": "one_pole", "alpha": 0.5 },
+                    { "type": "threshold", "trigger_level": 2.0 }
+                ]"#,
+            ),
+            &[],
+        )
+        .expect("works");
+        let mut obs = FilterObserver::null().unwrap();
+        let quiet = ndarray::Array1::from_elem(10, -1.0);
+        assert!(!pipeline.process(&quiet, &mut obs).triggered);
+        let loud = ndarray::Array1::from_elem(10, -5.0);
+        assert!(pipeline.process(&loud, &mut obs).triggered);
+
+        // Packets needn't all be the same length.
+        let loud = ndarray::Array1::from_elem(3, -5.0);
+        let result = pipeline.process(&loud, &mut obs);
+        assert!(!result.triggered && !result.reset);
+    }
+
+    #[test]
+    fn reports_event_peak() {
+        let mut pipeline = pipeline_from_config(
+            100.0,
+            &blocks(r#"[{ "type": "threshold", "trigger_level": 2.0, "reset_level": 1.0 }]"#),
+            &[],
+        )
+        .expect("works");
+        let mut obs = FilterObserver::null().unwrap();
+        let rising = ndarray::Array1::from_vec(vec![0.0, 3.0, 5.0]);
+        let result = pipeline.process(&rising, &mut obs);
+        assert_eq!(result.triggered_at, Some(1));
+        assert_eq!(result.trigger_energy, Some(3.0));
+        assert_eq!(result.peak_energy, None);
+
+        let falling = ndarray::Array1::from_vec(vec![7.0, 4.0, 0.5, 9.0]);
+        let result = pipeline.process(&falling, &mut obs);
+        assert_eq!(result.reset_at, Some(2));
+        assert_eq!(result.peak_energy, Some(7.0));
+    }
+
+    #[test]
+    fn non_finite_reset_ends_event() {
+        let mut pipeline = pipeline_from_config(
+            100.0,
+            &blocks(r#"[{ "type": "threshold", "trigger_level": 2.0, "reset_level": 1.0 }]"#),
+            &[],
+        )
+        .expect("works");
+        let mut obs = FilterObserver::null().unwrap();
+        let rising = ndarray::Array1::from_vec(vec![0.0, 3.0, 5.0]);
+        assert_eq!(pipeline.process(&rising, &mut obs).triggered_at, Some(1));
+
+        let broken = ndarray::Array1::from_vec(vec![4.0, f64::NAN]);
+        let result = pipeline.process(&broken, &mut obs);
+        assert!(result.non_finite_reset);
+        assert_eq!(result.reset_at, Some(0));
+        assert_eq!(result.peak_energy, Some(5.0));
+
+        // Once quiet, there is no event left to reset.
+        let result = pipeline.process(&broken, &mut obs);
+        assert!(result.non_finite_reset && !result.reset);
+        let loud = ndarray::Array1::from_elem(10, 3.0);
+        assert!(pipeline.process(&loud, &mut obs).triggered);
+    }
+
+    #[test]
+    fn escalates_through_tiers() {
+        let mut pipeline = pipeline_from_config(
+            100.0,
+            &blocks(r#"[{ "type": "threshold", "trigger_level": 2.0, "reset_level": 1.0 }]"#),
+            &[4.0, 6.0, 8.0],
+        )
+        .expect("works");
+        let mut obs = FilterObserver::null().unwrap();
+        let rising = ndarray::Array1::from_vec(vec![0.0, 3.0, 5.0]);
+        let result = pipeline.process(&rising, &mut obs);
+        assert_eq!(result.triggered_at, Some(1));
+        assert_eq!(result.escalated_to, Some(1));
+        assert_eq!(result.escalated_at, Some(2));
+        assert_eq!(result.escalation_energy, Some(5.0));
+
+        // Escalating past several tiers at once reaches the highest.
+        let result = pipeline.process(&ndarray::Array1::from_vec(vec![5.0, 9.0]), &mut obs);
+        assert_eq!(result.escalated_to, Some(3));
+        assert_eq!(result.escalated_at, Some(1));
+
+        // Falling back doesn't de-escalate, and a new event starts over.
+        let result = pipeline.process(&ndarray::Array1::from_vec(vec![0.5, 3.0]), &mut obs);
+        assert_eq!(result.reset_at, Some(0));
+        assert_eq!(result.triggered_at, Some(1));
+        assert_eq!(result.escalated_to, None);
+        let result = pipeline.process(&ndarray::Array1::from_vec(vec![7.0]), &mut obs);
+        assert_eq!(result.escalated_to, Some(2));
+    }
+
+    #[test]
+    fn trigger_must_be_last() {
+        let result = pipeline_from_config::<f32>(
+            100.0,
+            &blocks(r#"[{ "type": "threshold" }, { "type": "rectify" }]"#),
+            &[],
+        );
+        assert!(matches!(result, Err(FlowError::TriggerNotLast)));
+    }
+
+    #[test]
+    fn runs_in_double_precision() {
+        let blocks = blocks(
+            r#"[
+                { "type": "one_pole", "alpha": 0.999, "pass": "highpass" },
+                { "type": "rectify" },
+                { "type": "threshold", "trigger_level": 2.0 }
+            ]"#,
+        );
+        let mut pipeline = FlowPipeline::F64(
+            pipeline_from_config(100.0, &blocks, &[]).expect("works"),
+            FilterObserver::null().unwrap(),
+        );
+        let quiet = ndarray::Array1::from_elem(10, 0.0);
+        assert!(!pipeline.process(&quiet, 0.0).triggered);
+        let loud = ndarray::Array1::from_elem(10, 5.0);
+        assert!(pipeline.process(&loud, 1.0).triggered);
+    }
 }
@@ -0,0 +1,487 @@
+//! Sending flow events to an SNMP manager, as SNMPv2c or SNMPv3 traps,
+//! for facility monitoring systems which speak nothing else.
+use super::keyed_hash::hmac;
+use super::placeholders::Placeholders;
+use crate::config::{SnmpAuthProtocol, SnmpConfig, SnmpVersion};
+use crate::time::UtcTime;
+
+use aes::Aes128;
+use cfb_mode::cipher::{AsyncStreamCipher, KeyIvInit};
+use hmac::Hmac;
+use md5::Md5;
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+const INTEGER: u8 = 0x02;
+const OCTET_STRING: u8 = 0x04;
+const OBJECT_IDENTIFIER: u8 = 0x06;
+const SEQUENCE: u8 = 0x30;
+const TIME_TICKS: u8 = 0x43;
+const SNMPV2_TRAP: u8 = 0xa7;
+
+/// sysUpTime.0 and snmpTrapOID.0, which lead every trap's variables.
+const SYS_UP_TIME: &str = "1.3.6.1.2.1.1.3.0";
+const SNMP_TRAP_OID: &str = "1.3.6.1.6.3.1.1.4.1.0";
+
+/// The largest message we can receive, as told to the manager.
+const MAX_MESSAGE_SIZE: i64 = 65507;
+
+#[derive(Debug, Error)]
+pub enum SnmpError {
+    #[error("can't reach manager: {0}")]
+    Address(#[source] std::io::Error),
+    #[error("bad OID {0:?}")]
+    Oid(String),
+    #[error("bad engine ID {0:?}")]
+    EngineId(String),
+    #[error("SNMPv3 needs a user")]
+    NoUser,
+    #[error("SNMPv3 passwords must be at least 8 characters")]
+    ShortPassword,
+    #[error("encrypting traps needs an auth_password too")]
+    PrivWithoutAuth,
+}
+
+/// What a trap reports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trap {
+    Triggered,
+    Reset,
+    Available,
+    Unavailable,
+}
+
+/// An OID, BER-encoded (without its tag and length).
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Oid(Vec<u8>);
+
+impl Oid {
+    fn parse(oid: &str) -> Result<Self, SnmpError> {
+        let bad = || SnmpError::Oid(oid.to_owned());
+        let arcs = oid
+            .trim_start_matches('.')
+            .split('.')
+            .map(|arc| arc.parse::<u64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| bad())?;
+        let (first, second, rest) = match arcs.as_slice() {
+            [first @ 0..=1, second @ 0..=39, rest @ ..] => (*first, *second, rest),
+            [2, second, rest @ ..] => (2, *second, rest),
+            _ => return Err(bad()),
+        };
+        let mut encoded = Vec::new();
+        base128(first * 40 + second, &mut encoded);
+        for &arc in rest {
+            base128(arc, &mut encoded);
+        }
+        Ok(Self(encoded))
+    }
+
+    fn child(&self, arc: u64) -> Self {
+        let mut encoded = self.0.clone();
+        base128(arc, &mut encoded);
+        Self(encoded)
+    }
+}
+
+/// Append a value in base 128, most significant group first, with the
+/// top bit set on all but the last.
+fn base128(mut value: u64, out: &mut Vec<u8>) {
+    let mut groups = vec![(value & 0x7f) as u8];
+    value >>= 7;
+    while value > 0 {
+        groups.push(0x80 | (value & 0x7f) as u8);
+        value >>= 7;
+    }
+    out.extend(groups.iter().rev());
+}
+
+/// Encode a tag, length and content.
+fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes = len.to_be_bytes();
+        let skip = bytes.iter().take_while(|&&b| b == 0).count();
+        out.push(0x80 | (bytes.len() - skip) as u8);
+        out.extend(&bytes[skip..]);
+    }
+    out.extend(content);
+    out
+}
+
+/// Encode an integer in as few bytes as hold its sign.
+fn integer(tag: u8, value: i64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let mut skip = 0;
+    while skip < 7 {
+        let (byte, next) = (bytes[skip], bytes[skip + 1]);
+        if (byte == 0 && next & 0x80 == 0) || (byte == 0xff && next & 0x80 != 0) {
+            skip += 1;
+        } else {
+            break;
+        }
+    }
+    tlv(tag, &bytes[skip..])
+}
+
+fn sequence(items: &[Vec<u8>]) -> Vec<u8> {
+    tlv(SEQUENCE, &items.concat())
+}
+
+/// An SNMPv2-Trap PDU carrying some variables.
+fn trap_pdu(request_id: i32, variables: &[(Oid, Vec<u8>)]) -> Vec<u8> {
+    let variables: Vec<Vec<u8>> = variables
+        .iter()
+        .map(|(oid, value)| sequence(&[tlv(OBJECT_IDENTIFIER, &oid.0), value.clone()]))
+        .collect();
+    let content = [
+        integer(INTEGER, request_id.into()),
+        integer(INTEGER, 0),
+        integer(INTEGER, 0),
+        sequence(&variables),
+    ];
+    tlv(SNMPV2_TRAP, &content.concat())
+}
+
+/// Derive a user's key for an engine from their password (RFC 3414
+/// section 2.6): hash a megabyte of the password, repeated, then hash
+/// that with the engine ID.
+fn localized_key<D: Digest>(password: &[u8], engine_id: &[u8]) -> Vec<u8> {
+    let mut hasher = D::new();
+    let mut block = [0u8; 64];
+    let mut repeated = password.iter().cycle();
+    for _ in 0..(1 << 20) / block.len() {
+        block
+            .iter_mut()
+            .for_each(|b| *b = *repeated.next().unwrap_or(&0));
+        hasher.update(block);
+    }
+    let key = hasher.finalize();
+    let mut hasher = D::new();
+    hasher.update(&key);
+    hasher.update(engine_id);
+    hasher.update(&key);
+    hasher.finalize().to_vec()
+}
+
+impl SnmpAuthProtocol {
+    fn localized_key(self, password: &str, engine_id: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Md5 => localized_key::<Md5>(password.as_bytes(), engine_id),
+            Self::Sha => localized_key::<Sha1>(password.as_bytes(), engine_id),
+            Self::Sha256 => localized_key::<Sha256>(password.as_bytes(), engine_id),
+        }
+    }
+
+    /// A message's authentication parameters: its truncated HMAC.
+    fn authenticate(self, key: &[u8], message: &[u8]) -> Vec<u8> {
+        let mut mac = match self {
+            Self::Md5 => hmac::<Hmac<Md5>>(key, message),
+            Self::Sha => hmac::<Hmac<Sha1>>(key, message),
+            Self::Sha256 => hmac::<Hmac<Sha256>>(key, message),
+        };
+        mac.truncate(self.mac_len());
+        mac
+    }
+
+    fn mac_len(self) -> usize {
+        match self {
+            Self::Md5 | Self::Sha => 12,
+            Self::Sha256 => 24,
+        }
+    }
+}
+
+/// The user-based security (RFC 3414) of SNMPv3 messages, with privacy
+/// by AES-128 (RFC 3826).
+struct Usm {
+    engine_id: Vec<u8>,
+    user: Vec<u8>,
+    auth: Option<(SnmpAuthProtocol, Vec<u8>)>,
+    privacy: Option<Vec<u8>>,
+}
+
+impl Usm {
+    /// Wrap a PDU in a message, sent at some engine time. The engine is
+    /// taken to have booted once, with its time the seconds since the
+    /// epoch, so that a manager which has heard from it before doesn't
+    /// think messages sent after a restart are replays of older ones.
+    fn message(&self, id: i32, pdu: Vec<u8>, time: u32, salt: u64) -> Vec<u8> {
+        const BOOTS: u32 = 1;
+        let scoped_pdu = sequence(&[
+            tlv(OCTET_STRING, &self.engine_id),
+            tlv(OCTET_STRING, &[]),
+            pdu,
+        ]);
+        let (data, priv_params) = match self.privacy.as_ref() {
+            Some(key) => {
+                let mut iv = [0u8; 16];
+                iv[..4].copy_from_slice(&BOOTS.to_be_bytes());
+                iv[4..8].copy_from_slice(&time.to_be_bytes());
+                iv[8..].copy_from_slice(&salt.to_be_bytes());
+                let mut encrypted = scoped_pdu;
+                cfb_mode::Encryptor::<Aes128>::new_from_slices(&key[..16], &iv)
+                    .expect("AES-128 keys are 16 bytes")
+                    .encrypt(&mut encrypted);
+                (tlv(OCTET_STRING, &encrypted), salt.to_be_bytes().to_vec())
+            }
+            None => (scoped_pdu, Vec::new()),
+        };
+        let flags = match (self.auth.is_some(), self.privacy.is_some()) {
+            (false, _) => 0,
+            (true, false) => 1,
+            (true, true) => 3,
+        };
+        let mac_len = self
+            .auth
+            .as_ref()
+            .map_or(0, |(protocol, _)| protocol.mac_len());
+        let priv_params = tlv(OCTET_STRING, &priv_params);
+        let security = sequence(&[
+            tlv(OCTET_STRING, &self.engine_id),
+            integer(INTEGER, BOOTS.into()),
+            integer(INTEGER, time.into()),
+            tlv(OCTET_STRING, &self.user),
+            tlv(OCTET_STRING, &vec![0; mac_len]),
+            priv_params.clone(),
+        ]);
+        let header = sequence(&[
+            integer(INTEGER, id.into()),
+            integer(INTEGER, MAX_MESSAGE_SIZE),
+            tlv(OCTET_STRING, &[flags]),
+            integer(INTEGER, 3),
+        ]);
+        let mut message = sequence(&[
+            integer(INTEGER, 3),
+            header,
+            tlv(OCTET_STRING, &security),
+            data.clone(),
+        ]);
+        if let Some((protocol, key)) = self.auth.as_ref() {
+            // The MAC is computed with its own place zeroed, then filled
+            // in. Only the privacy parameters and data follow it.
+            let end = message.len() - data.len() - priv_params.len();
+            let mac = protocol.authenticate(key, &message);
+            message[end - mac_len..end].copy_from_slice(&mac);
+        }
+        message
+    }
+}
+
+enum Security {
+    Community(Vec<u8>),
+    User(Usm),
+}
+
+struct Sender {
+    socket: UdpSocket,
+    address: SocketAddr,
+    security: Security,
+    /// OIDs of the triggered, reset, available and unavailable traps.
+    traps: [Oid; 4],
+    details: Oid,
+    started: Instant,
+    next_id: AtomicU32,
+    next_salt: AtomicU64,
+}
+
+/// A handle on an SNMP manager, which may be shared.
+#[derive(Clone)]
+pub struct SnmpTraps {
+    sender: Arc<Sender>,
+}
+
+impl SnmpTraps {
+    pub fn from_config(config: &SnmpConfig) -> Result<Self, SnmpError> {
+        let address = config
+            .address
+            .to_socket_addrs()
+            .and_then(|mut addresses| {
+                addresses.next().ok_or_else(|| {
+                    std::io::Error::new(std::io::ErrorKind::NotFound, "no such host")
+                })
+            })
+            .map_err(SnmpError::Address)?;
+        let local: SocketAddr = match address {
+            SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+            SocketAddr::V6(_) => ([0u16; 8], 0).into(),
+        };
+        let socket = UdpSocket::bind(local).map_err(SnmpError::Address)?;
+        let security = match config.version {
+            SnmpVersion::V2c => Security::Community(config.community.clone().into_bytes()),
+            SnmpVersion::V3 => Security::User(usm(config)?),
+        };
+        let traps = [
+            Oid::parse(&config.triggered_oid)?,
+            Oid::parse(&config.reset_oid)?,
+            Oid::parse(&config.available_oid)?,
+            Oid::parse(&config.unavailable_oid)?,
+        ];
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let sender = Sender {
+            socket,
+            address,
+            security,
+            traps,
+            details: Oid::parse(&config.details_oid)?,
+            started: Instant::now(),
+            next_id: AtomicU32::new(now.subsec_nanos()),
+            next_salt: AtomicU64::new(now.as_nanos() as u64),
+        };
+        Ok(Self {
+            sender: Arc::new(sender),
+        })
+    }
+
+    /// Send a trap about an event of a flow, with its details. Failures
+    /// are logged, as there's no telling whether a trap arrived anyway.
+    pub fn send(&self, trap: Trap, placeholders: &Placeholders<'_>) {
+        let sender = &self.sender;
+        let oid = match trap {
+            Trap::Triggered => &sender.traps[0],
+            Trap::Reset => &sender.traps[1],
+            Trap::Available => &sender.traps[2],
+            Trap::Unavailable => &sender.traps[3],
+        };
+        let uptime = (sender.started.elapsed().as_millis() / 10) as u32;
+        let id = (sender.next_id.fetch_add(1, Ordering::Relaxed) & 0x7fff_ffff) as i32;
+        let pdu = trap_pdu(id, &variables(oid, &sender.details, uptime, placeholders));
+        let message = match &sender.security {
+            Security::Community(community) => {
+                sequence(&[integer(INTEGER, 1), tlv(OCTET_STRING, community), pdu])
+            }
+            Security::User(usm) => {
+                let time = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs() as u32
+                    & 0x7fff_ffff;
+                let salt = sender.next_salt.fetch_add(1, Ordering::Relaxed);
+                usm.message(id, pdu, time, salt)
+            }
+        };
+        if let Err(e) = sender.socket.send_to(&message, sender.address) {
+            log::warn!("{}: can't send SNMP trap: {e}", placeholders.flow);
+        }
+    }
+}
+
+/// Set up the user-based security of SNMPv3 traps.
+fn usm(config: &SnmpConfig) -> Result<Usm, SnmpError> {
+    let user = config.user.as_ref().ok_or(SnmpError::NoUser)?;
+    let engine_id = hex(&config.engine_id)
+        .filter(|id| (5..=32).contains(&id.len()))
+        .ok_or_else(|| SnmpError::EngineId(config.engine_id.clone()))?;
+    let protocol = config.auth_protocol;
+    let key = |password: &String| {
+        if password.len() < 8 {
+            return Err(SnmpError::ShortPassword);
+        }
+        Ok(protocol.localized_key(password, &engine_id))
+    };
+    let auth = config.auth_password.as_ref().map(key).transpose()?;
+    let privacy = config.priv_password.as_ref().map(key).transpose()?;
+    if privacy.is_some() && auth.is_none() {
+        return Err(SnmpError::PrivWithoutAuth);
+    }
+    Ok(Usm {
+        user: user.clone().into_bytes(),
+        auth: auth.map(|key| (protocol, key)),
+        privacy,
+        engine_id,
+    })
+}
+
+fn hex(text: &str) -> Option<Vec<u8>> {
+    let text = text.strip_prefix("0x").unwrap_or(text);
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// The variables of a trap: the uptime, the trap's OID, then the
+/// details of the event which are known.
+fn variables(
+    trap: &Oid,
+    details: &Oid,
+    uptime: u32,
+    placeholders: &Placeholders<'_>,
+) -> Vec<(Oid, Vec<u8>)> {
+    let string = |value: &str| tlv(OCTET_STRING, value.as_bytes());
+    let mut variables = vec![
+        (
+            Oid::parse(SYS_UP_TIME).expect("valid OID"),
+            integer(TIME_TICKS, uptime.into()),
+        ),
+        (
+            Oid::parse(SNMP_TRAP_OID).expect("valid OID"),
+            tlv(OBJECT_IDENTIFIER, &trap.0),
+        ),
+        (details.child(1), string(placeholders.flow)),
+    ];
+    variables.extend(
+        placeholders
+            .seismometer
+            .map(|s| (details.child(2), string(s))),
+    );
+    variables.extend(placeholders.channel.map(|c| (details.child(3), string(c))));
+    let timestamp = UtcTime::from_epoch(placeholders.timestamp).to_string();
+    variables.push((details.child(4), string(&timestamp)));
+    variables.extend(
+        placeholders
+            .energy
+            .map(|e| (details.child(5), string(&e.to_string()))),
+    );
+    variables
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_v2c_traps() {
+        let oid = Oid::parse("1.3.6.1.4.1.32473.1.0.1").expect("parse");
+        assert_eq!(oid.0, [0x2b, 6, 1, 4, 1, 0x81, 0xfd, 0x59, 1, 0, 1]);
+        assert_eq!(integer(INTEGER, 128), [2, 2, 0, 128]);
+        assert_eq!(integer(INTEGER, -129), [2, 2, 0xff, 0x7f]);
+        assert_eq!(integer(TIME_TICKS, 0), [0x43, 1, 0]);
+        assert_eq!(tlv(OCTET_STRING, &[0; 200])[..3], [4, 0x81, 200]);
+        assert!(Oid::parse("1.40").is_err());
+        assert!(Oid::parse("1.3.six").is_err());
+
+        let variables = [(oid.child(1), tlv(OCTET_STRING, b"x"))];
+        let pdu = trap_pdu(7, &variables);
+        assert_eq!(
+            pdu,
+            [
+                0xa7, 0x1e, 2, 1, 7, 2, 1, 0, 2, 1, 0, 0x30, 0x13, 0x30, 0x11, 6, 12, 0x2b, 6, 1,
+                4, 1, 0x81, 0xfd, 0x59, 1, 0, 1, 1, 4, 1, b'x'
+            ]
+        );
+    }
+
+    #[test]
+    fn localizes_keys() {
+        // From RFC 3414, appendix A.3.
+        let engine_id = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+        let key = SnmpAuthProtocol::Md5.localized_key("maplesyrup", &engine_id);
+        assert_eq!(hex("526f5eed9fcce26f8964c2930787d82b"), Some(key));
+        let key = SnmpAuthProtocol::Sha.localized_key("maplesyrup", &engine_id);
+        assert_eq!(hex("6695febc9288e36282235fc7151f128497b38f3f"), Some(key));
+    }
+}
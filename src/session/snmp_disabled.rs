@@ -0,0 +1,34 @@
+//! Stand-in for SNMP traps when built without the "snmp" feature.
+//! Configurations that ask for them are rejected at startup, so no
+//! manager is ever set up.
+use super::placeholders::Placeholders;
+use crate::config::SnmpConfig;
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SnmpError {
+    #[error("SNMP support was not built in")]
+    NotBuilt,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trap {
+    Triggered,
+    Reset,
+    Available,
+    Unavailable,
+}
+
+#[derive(Clone)]
+pub enum SnmpTraps {}
+
+impl SnmpTraps {
+    pub fn from_config(_config: &SnmpConfig) -> Result<Self, SnmpError> {
+        Err(SnmpError::NotBuilt)
+    }
+
+    pub fn send(&self, _trap: Trap, _placeholders: &Placeholders<'_>) {
+        match *self {}
+    }
+}
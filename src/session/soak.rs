@@ -0,0 +1,112 @@
+//! Stability monitoring for soak tests: periodically reports memory use,
+//! action queue depth and data latency while a session runs.
+use super::status::{Histogram, StatusBoard, StatusSnapshot};
+
+use tokio::time::{Duration, Instant};
+
+pub struct SoakMonitor {
+    board: StatusBoard,
+    duration: Duration,
+    report_interval: Duration,
+}
+
+/// One periodic measurement.
+struct SoakSample {
+    elapsed_s: f32,
+    rss_kb: Option<u64>,
+    packets: u64,
+    max_queue_depth: usize,
+    latency: Histogram,
+}
+
+impl SoakSample {
+    fn take(start: Instant, status: &StatusSnapshot) -> Self {
+        let mut packets = 0;
+        let mut latency = Histogram::default();
+        for channel in status
+            .seismometers
+            .values()
+            .flat_map(|s| s.channels.values())
+        {
+            packets += channel.packets;
+            latency.merge(&channel.latency);
+        }
+        Self {
+            elapsed_s: start.elapsed().as_secs_f32(),
+            rss_kb: resident_set_kb(),
+            packets,
+            max_queue_depth: status.max_action_queue_depth,
+            latency,
+        }
+    }
+}
+
+impl std::fmt::Display for SoakSample {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "t={:.0}s packets={}", self.elapsed_s, self.packets)?;
+        if let Some(rss_kb) = self.rss_kb {
+            write!(f, " rss={rss_kb}kB")?;
+        }
+        write!(
+            f,
+            " queue_max={} latency_mean={:.1}ms latency_max={:.1}ms",
+            self.max_queue_depth,
+            self.latency.mean_ms(),
+            self.latency.max_ms()
+        )
+    }
+}
+
+impl SoakMonitor {
+    pub fn new(board: StatusBoard, duration_s: f32, report_interval_s: f32) -> Self {
+        Self {
+            board,
+            duration: Duration::from_secs_f32(duration_s),
+            report_interval: Duration::from_secs_f32(report_interval_s),
+        }
+    }
+
+    /// Report on the session until the soak duration has passed, then
+    /// summarize. Memory is measured from the first report onward, so
+    /// that start-up allocations don't count as growth.
+    pub async fn run(self) {
+        let start = Instant::now();
+        let deadline = start + self.duration;
+        let mut ticker = tokio::time::interval(self.report_interval);
+        ticker.tick().await;
+        let mut first: Option<SoakSample> = None;
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => (),
+                _ = tokio::time::sleep_until(deadline) => break,
+            }
+            let sample = SoakSample::take(start, &self.board.snapshot());
+            println!("soak: {sample}");
+            first.get_or_insert(sample);
+        }
+        let last = SoakSample::take(start, &self.board.snapshot());
+        println!("soak: finished: {last}");
+        let growth = first
+            .as_ref()
+            .and_then(|first| Some((first, first.rss_kb?, last.rss_kb?)));
+        if let Some((first, first_kb, last_kb)) = growth {
+            let hours = (last.elapsed_s - first.elapsed_s) / 3600.0;
+            let growth_kb = last_kb as i64 - first_kb as i64;
+            if hours > 0.0 {
+                println!(
+                    "soak: memory growth {growth_kb}kB ({:.0}kB/hour)",
+                    growth_kb as f32 / hours
+                );
+            }
+        }
+    }
+}
+
+/// The process's resident set size, where the platform reveals it.
+fn resident_set_kb() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))
+        .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+}
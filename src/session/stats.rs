@@ -0,0 +1,177 @@
+//! Statistics of flows' events over the last hour and the last day, such
+//! as a count of the day's quakes, or the greatest energy seen to spot a
+//! trigger level drifting out of line with the noise.
+use super::action_loop::Event;
+
+use serde::Serialize;
+use std::collections::VecDeque;
+
+const HOUR_S: f64 = 3600.0;
+const DAY_S: f64 = 86400.0;
+
+/// What a flow did over some period of time.
+#[derive(Clone, Copy, Default, Serialize, PartialEq, Debug)]
+pub struct WindowStats {
+    /// Number of times the flow triggered.
+    pub triggers: u64,
+
+    /// Total time the flow's triggers were asserted for, in seconds, as of
+    /// when each reset.
+    pub triggered_s: f64,
+
+    /// The greatest energy fed to the flow's trigger, as seen in its
+    /// status reports and events.
+    pub max_energy: Option<f64>,
+}
+
+impl WindowStats {
+    fn add(&mut self, other: &WindowStats) {
+        self.triggers += other.triggers;
+        self.triggered_s += other.triggered_s;
+        self.note_energy(other.max_energy);
+    }
+
+    fn note_energy(&mut self, energy: Option<f64>) {
+        self.max_energy = match (self.max_energy, energy) {
+            (Some(max), Some(energy)) => Some(max.max(energy)),
+            (max, energy) => max.or(energy),
+        };
+    }
+}
+
+/// What a flow did over the last hour and the last day.
+#[derive(Clone, Copy, Default, Serialize, PartialEq, Debug)]
+pub struct RecentStats {
+    pub hour: WindowStats,
+    pub day: WindowStats,
+}
+
+/// A flow's statistics for the last day, kept a minute at a time (by the
+/// host's clock, in minutes since the epoch).
+#[derive(Default)]
+pub struct FlowStats {
+    minutes: VecDeque<(i64, WindowStats)>,
+}
+
+impl FlowStats {
+    /// Count an event of the flow, noted at a time in seconds since the
+    /// epoch.
+    pub fn note(&mut self, now: f64, event: &Event) {
+        let minute = self.minute(now);
+        match *event {
+            Event::Status { energy, .. } => minute.note_energy(Some(energy.into())),
+            Event::Triggered { energy, .. } => {
+                minute.triggers += 1;
+                minute.note_energy(Some(energy));
+            }
+            Event::Reset {
+                summary: Some(summary),
+                ..
+            } => {
+                minute.triggered_s += summary.duration_s;
+                minute.note_energy(Some(summary.peak_energy));
+            }
+            _ => (),
+        }
+    }
+
+    /// What the flow did over the last hour and the last day, as of a time
+    /// in seconds since the epoch.
+    pub fn recent(&self, now: f64) -> RecentStats {
+        RecentStats {
+            hour: self.over(now, HOUR_S),
+            day: self.over(now, DAY_S),
+        }
+    }
+
+    /// What the flow did over some seconds up to a time in seconds since
+    /// the epoch (to the minute).
+    fn over(&self, now: f64, span_s: f64) -> WindowStats {
+        let since = ((now - span_s) / 60.0).floor() as i64;
+        let mut stats = WindowStats::default();
+        for (_, minute) in self.minutes.iter().filter(|(at, _)| *at > since) {
+            stats.add(minute);
+        }
+        stats
+    }
+
+    /// The statistics for the minute of a time, forgetting any older than
+    /// a day by then.
+    fn minute(&mut self, now: f64) -> &mut WindowStats {
+        let minute = (now / 60.0).floor() as i64;
+        let since = minute - (DAY_S / 60.0) as i64;
+        while self.minutes.front().is_some_and(|(at, _)| *at <= since) {
+            self.minutes.pop_front();
+        }
+        if self.minutes.back().is_none_or(|(at, _)| *at < minute) {
+            self.minutes.push_back((minute, WindowStats::default()));
+        }
+        let (_, stats) = self.minutes.back_mut().expect("a minute");
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::action_loop::EventSummary;
+
+    #[test]
+    fn it_forgets_old_events() {
+        let mut stats = FlowStats::default();
+        let start = 1_000_000.0;
+        let triggered = Event::Triggered {
+            at: start,
+            energy: 8.0,
+            onset: None,
+        };
+        stats.note(start, &triggered);
+        let summary = EventSummary {
+            at: start + 4.0,
+            duration_s: 4.0,
+            peak_energy: 12.0,
+        };
+        let reset = Event::Reset {
+            at: start + 4.0,
+            summary: Some(summary),
+            ground_motion: None,
+        };
+        stats.note(start + 4.0, &reset);
+        stats.note(
+            start + 2.0 * HOUR_S,
+            &Event::Status {
+                dc: 0.0,
+                energy: 3.0,
+            },
+        );
+
+        let recent = stats.recent(start + 2.0 * HOUR_S);
+        assert_eq!(
+            recent.hour,
+            WindowStats {
+                triggers: 0,
+                triggered_s: 0.0,
+                max_energy: Some(3.0)
+            }
+        );
+        assert_eq!(
+            recent.day,
+            WindowStats {
+                triggers: 1,
+                triggered_s: 4.0,
+                max_energy: Some(12.0)
+            }
+        );
+
+        stats.note(
+            start + DAY_S + 60.0,
+            &Event::Status {
+                dc: 0.0,
+                energy: 1.0,
+            },
+        );
+        let recent = stats.recent(start + DAY_S + 60.0);
+        assert_eq!(recent.day.triggers, 0);
+        assert_eq!(recent.day.max_energy, Some(3.0));
+    }
+}
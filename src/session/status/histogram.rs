@@ -0,0 +1,105 @@
+use serde::Serialize;
+
+/// Upper bounds of the histogram buckets, in milliseconds. A final,
+/// unbounded bucket catches everything larger.
+const BUCKET_BOUNDS_MS: [f64; 13] = [
+    1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 200.0, 500.0, 1000.0, 2000.0, 5000.0, 10000.0,
+];
+
+/// A fixed-bucket histogram of durations.
+#[derive(Clone, Serialize)]
+pub struct Histogram {
+    /// Bucket upper bounds ("less than or equal"), in milliseconds.
+    le_ms: &'static [f64],
+
+    /// Observation counts per bucket; one more than there are bounds.
+    counts: [u64; BUCKET_BOUNDS_MS.len() + 1],
+
+    /// Total number of observations.
+    count: u64,
+
+    /// Sum of all observations, in milliseconds.
+    sum_ms: f64,
+
+    /// Largest observation, in milliseconds.
+    max_ms: f64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            le_ms: &BUCKET_BOUNDS_MS,
+            counts: [0; BUCKET_BOUNDS_MS.len() + 1],
+            count: 0,
+            sum_ms: 0.0,
+            max_ms: 0.0,
+        }
+    }
+}
+
+impl Histogram {
+    /// Record one observation, in seconds. Negative observations (from
+    /// clock skew, for instance) are counted as zero.
+    pub fn record(&mut self, seconds: f64) {
+        let ms = (seconds * 1000.0).max(0.0);
+        let bucket = BUCKET_BOUNDS_MS
+            .iter()
+            .position(|&bound| ms <= bound)
+            .unwrap_or(BUCKET_BOUNDS_MS.len());
+        self.counts[bucket] += 1;
+        self.count += 1;
+        self.sum_ms += ms;
+        self.max_ms = self.max_ms.max(ms);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn mean_ms(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.sum_ms / self.count as f64
+        }
+    }
+
+    pub fn max_ms(&self) -> f64 {
+        self.max_ms
+    }
+
+    /// Fold another histogram's observations into this one.
+    pub fn merge(&mut self, other: &Histogram) {
+        for (count, other_count) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *count += other_count;
+        }
+        self.count += other.count;
+        self.sum_ms += other.sum_ms;
+        self.max_ms = self.max_ms.max(other.max_ms);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Histogram;
+
+    #[test]
+    fn buckets() {
+        let mut h = Histogram::default();
+        h.record(0.0005);
+        h.record(0.25);
+        h.record(-1.0);
+        h.record(100.0);
+        assert_eq!(h.count(), 4);
+        assert_eq!(h.counts[0], 2);
+        assert_eq!(h.counts[8], 1);
+        assert_eq!(h.counts[13], 1);
+        assert_eq!(h.max_ms, 100_000.0);
+
+        let mut total = Histogram::default();
+        total.merge(&h);
+        total.merge(&h);
+        assert_eq!(total.count(), 8);
+        assert_eq!(total.mean_ms(), h.mean_ms());
+    }
+}
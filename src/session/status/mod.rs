@@ -0,0 +1,246 @@
+//! Shared, point-in-time view of the daemon's state, updated by the
+//! session's loops and read by anything that wants to report on it.
+mod histogram;
+mod publisher;
+
+pub use histogram::Histogram;
+pub use publisher::StatusPublisher;
+
+use super::action_loop::Event;
+use super::stats::RecentStats;
+use crate::datasource::Channel;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+/// Reception statistics for a single seismometer channel.
+#[derive(Clone, Default, Serialize)]
+pub struct ChannelStatus {
+    /// Number of packets received.
+    pub packets: u64,
+
+    /// Time between consecutive packet arrivals.
+    pub arrival_interval: Histogram,
+
+    /// Delay between the data timestamp of a packet (its first sample)
+    /// and its arrival.
+    pub latency: Histogram,
+
+    /// The RMS of the raw counts while the channel's flows were quiet,
+    /// over the last interval.
+    pub noise_floor_rms: Option<f64>,
+}
+
+#[derive(Clone, Default, Serialize)]
+pub struct SeismometerStatus {
+    pub channels: BTreeMap<String, ChannelStatus>,
+
+    /// The sample rate measured from packet timestamps over the last
+    /// interval, in hertz.
+    pub measured_sample_rate: Option<f64>,
+
+    /// What the seismometer's data source last failed with, while it is
+    /// waiting to be reopened.
+    pub source_error: Option<String>,
+}
+
+/// A flow's state, as its actions see it.
+#[derive(Clone, Default, Serialize)]
+pub struct FlowSnapshot {
+    pub seismometer: Option<String>,
+    pub channel: Option<String>,
+
+    /// Whether the flow is disabled in the configuration, and so isn't
+    /// run at all.
+    pub disabled: bool,
+
+    /// Whether the flow's trigger is asserted.
+    pub triggered: bool,
+
+    /// Whether the flow's trigger actions are taken: the session is
+    /// armed, and the flow hasn't been disarmed on its own.
+    pub armed: bool,
+
+    /// Whether the flow's channel is delivering data, once that is known.
+    pub available: Option<bool>,
+
+    /// The flow's DC level and energy, as of its latest status report
+    /// (if it makes them).
+    pub dc: Option<f32>,
+    pub energy: Option<f32>,
+
+    /// The levels at which the flow's trigger asserts and resets (if it
+    /// has them).
+    pub trigger_level: Option<f32>,
+    pub reset_level: Option<f32>,
+
+    /// The profile of the flow's threshold schedule which its trigger
+    /// levels are from, if any is in force.
+    pub threshold_profile: Option<String>,
+
+    /// Number of times the flow has triggered.
+    pub triggers: u64,
+
+    /// Number of warnings about the flow.
+    pub warnings: u64,
+
+    /// What the flow did over the last hour and the last day, as of when
+    /// its statistics were last published (if they are).
+    pub stats: Option<RecentStats>,
+}
+
+impl FlowSnapshot {
+    /// Take note of an event of the flow.
+    pub fn note(&mut self, event: &Event) {
+        match *event {
+            Event::Status { dc, energy } => {
+                self.dc = Some(dc);
+                self.energy = Some(energy);
+            }
+            Event::Available => self.available = Some(true),
+            Event::Unavailable => self.available = Some(false),
+            Event::Triggered { .. } => {
+                self.triggered = true;
+                self.triggers += 1;
+            }
+            Event::Reset { .. } | Event::StuckReset { .. } => self.triggered = false,
+            Event::Warning(_) => self.warnings += 1,
+            Event::Levels {
+                trigger_level,
+                reset_level,
+                ref profile,
+            } => {
+                self.trigger_level = Some(trigger_level);
+                self.reset_level = Some(reset_level);
+                self.threshold_profile.clone_from(profile);
+            }
+            _ => (),
+        }
+    }
+}
+
+#[derive(Clone, Default, Serialize)]
+pub struct StatusSnapshot {
+    pub seismometers: BTreeMap<String, SeismometerStatus>,
+
+    pub flows: BTreeMap<String, FlowSnapshot>,
+
+    /// The number of events waiting for the action loop, as of the latest
+    /// packet, and the largest number seen waiting.
+    pub action_queue_depth: usize,
+    pub max_action_queue_depth: usize,
+
+    /// The number of flows' status reports dropped (or superseded while
+    /// held back) because the action loop's queue was full.
+    pub dropped_status_events: u64,
+
+    /// The number of actions which failed, even after any retries.
+    pub failed_actions: u64,
+
+    /// The number of times the action loop failed part way through acting
+    /// on an event (or posting some state), and left the rest undone.
+    pub action_errors: u64,
+}
+
+impl StatusSnapshot {
+    pub fn channel_mut(&mut self, seismometer: &str, channel: Channel) -> &mut ChannelStatus {
+        self.seismometers
+            .entry(seismometer.to_owned())
+            .or_default()
+            .channels
+            .entry(channel.as_str().to_owned())
+            .or_default()
+    }
+
+    /// Note a flow as disabled, in place of whatever it was doing before
+    /// a reload disabled it, so that it's clear why it's silent.
+    pub fn disable_flow(&mut self, name: &str, seismometer: &str, channel: Channel) {
+        let flow = FlowSnapshot {
+            seismometer: Some(seismometer.to_owned()),
+            channel: Some(channel.as_str().to_owned()),
+            disabled: true,
+            ..FlowSnapshot::default()
+        };
+        self.flows.insert(name.to_owned(), flow);
+    }
+
+    /// Take the flows and seismometers a reloaded configuration no longer
+    /// has off the board, keeping what is known of the rest.
+    pub fn keep_only(&mut self, flows: &HashSet<&str>, seismometers: &HashSet<&str>) {
+        self.flows.retain(|name, _| flows.contains(name.as_str()));
+        self.seismometers
+            .retain(|name, _| seismometers.contains(name.as_str()));
+    }
+}
+
+/// A cloneable handle to the shared daemon status.
+#[derive(Clone, Default)]
+pub struct StatusBoard(Arc<Mutex<StatusSnapshot>>);
+
+impl StatusBoard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Modify the status in place.
+    pub fn update<R>(&self, f: impl FnOnce(&mut StatusSnapshot) -> R) -> R {
+        let mut status = self.0.lock().expect("status lock poisoned");
+        f(&mut status)
+    }
+
+    /// Take a copy of the current status.
+    pub fn snapshot(&self) -> StatusSnapshot {
+        self.0.lock().expect("status lock poisoned").clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stuck_reset_clears_trigger() {
+        let mut flow = FlowSnapshot::default();
+        flow.note(&Event::Triggered {
+            at: 1.0,
+            energy: 2.0,
+            onset: None,
+        });
+        assert!(flow.triggered);
+        flow.note(&Event::StuckReset { at: 2.0 });
+        assert!(!flow.triggered);
+        assert_eq!(flow.triggers, 1);
+    }
+
+    #[test]
+    fn disabled_flows_are_shown_disabled() {
+        let mut status = StatusSnapshot::default();
+        let mut flow = FlowSnapshot::default();
+        flow.note(&Event::Triggered {
+            at: 1.0,
+            energy: 2.0,
+            onset: None,
+        });
+        status.flows.insert("quake".into(), flow);
+        status.disable_flow("quake", "garage", Channel::Ehz);
+        let shown = serde_json::to_value(&status.flows["quake"]).expect("serializes");
+        assert_eq!(shown["disabled"], true);
+        assert_eq!(shown["triggered"], false);
+        assert_eq!(shown["seismometer"], "garage");
+    }
+
+    #[test]
+    fn forgets_flows_no_longer_configured() {
+        let mut status = StatusSnapshot::default();
+        status.flows.insert("quake".into(), FlowSnapshot::default());
+        status.flows.insert("gone".into(), FlowSnapshot::default());
+        status.channel_mut("garage", Channel::Ehz).packets = 10;
+        status.channel_mut("shed", Channel::Ehz).packets = 20;
+        let flows = HashSet::from(["quake", "new"]);
+        let seismometers = HashSet::from(["garage"]);
+        status.keep_only(&flows, &seismometers);
+        assert_eq!(status.flows.keys().collect::<Vec<_>>(), ["quake"]);
+        assert_eq!(status.seismometers.keys().collect::<Vec<_>>(), ["garage"]);
+        assert_eq!(status.channel_mut("garage", Channel::Ehz).packets, 10);
+    }
+}
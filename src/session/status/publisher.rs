@@ -0,0 +1,41 @@
+use super::StatusBoard;
+use crate::session::mqtt::{AsyncClient, ClientError, QoS};
+
+use tokio::time::Duration;
+
+/// Periodically publishes the daemon status, as JSON, to an MQTT topic.
+pub struct StatusPublisher {
+    board: StatusBoard,
+    target: Option<(AsyncClient, String)>,
+    interval: Duration,
+}
+
+impl StatusPublisher {
+    pub fn new(
+        board: StatusBoard,
+        mqtt: Option<AsyncClient>,
+        topic: Option<String>,
+        interval_s: f32,
+    ) -> Self {
+        Self {
+            board,
+            target: mqtt.zip(topic),
+            interval: Duration::from_secs_f32(interval_s),
+        }
+    }
+
+    pub async fn run(self) -> Result<(), ClientError> {
+        let Some((client, topic)) = self.target else {
+            return Ok(());
+        };
+        let mut ticker = tokio::time::interval(self.interval);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let payload = serde_json::to_vec(&self.board.snapshot()).expect("status serializes");
+            client
+                .publish(topic.as_str(), QoS::AtMostOnce, false, payload)
+                .await?;
+        }
+    }
+}
@@ -0,0 +1,212 @@
+//! Telling systemd, when it runs the daemon as a notify service, that the
+//! daemon is ready, and (if it keeps a watchdog on it) that the daemon's
+//! loops are still running, so that it is restarted should one stall.
+#[cfg(unix)]
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::time::Duration;
+
+/// A loop's sign of life, which it gives at least once per period.
+#[derive(Clone)]
+pub struct Heartbeat {
+    alive: Arc<AtomicBool>,
+    period: Duration,
+}
+
+impl Heartbeat {
+    /// Note that the loop is still running.
+    pub fn beat(&self) {
+        self.alive.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Wait until a loop is due to give a sign of life, if it gives them,
+/// or forever.
+pub async fn heartbeat_due(heartbeat: Option<&Heartbeat>) {
+    match heartbeat {
+        Some(heartbeat) => tokio::time::sleep(heartbeat.period).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// The socket to send notifications on, and where to.
+#[cfg(unix)]
+type Socket = (UnixDatagram, SocketAddr);
+
+/// There is no systemd to notify but on Unix.
+#[cfg(not(unix))]
+type Socket = std::convert::Infallible;
+
+pub struct Systemd {
+    socket: Option<Socket>,
+    /// How often systemd expects the watchdog to be fed, if at all.
+    watchdog: Option<Duration>,
+    /// The loops whose signs of life the watchdog is fed on, by name.
+    heartbeats: Vec<(String, Heartbeat)>,
+    ready: AtomicBool,
+}
+
+impl Systemd {
+    /// Notify systemd as its environment says to, if at all.
+    pub fn from_env() -> Self {
+        let var = |name| std::env::var(name).ok();
+        Self::new(
+            var("NOTIFY_SOCKET").as_deref(),
+            var("WATCHDOG_USEC").as_deref(),
+            var("WATCHDOG_PID").as_deref(),
+        )
+    }
+
+    fn new(socket: Option<&str>, watchdog_usec: Option<&str>, watchdog_pid: Option<&str>) -> Self {
+        let socket = socket.and_then(|path| {
+            open_socket(path)
+                .inspect_err(|e| log::warn!("can't notify systemd on {path}: {e}"))
+                .ok()
+        });
+        // The watchdog may be meant for another process.
+        let ours = watchdog_pid.is_none_or(|pid| pid.parse() == Ok(std::process::id()));
+        let watchdog = watchdog_usec
+            .and_then(|usec| usec.parse().ok())
+            .filter(|&usec| usec > 0 && ours && socket.is_some())
+            .map(Duration::from_micros);
+        Self {
+            socket,
+            watchdog,
+            heartbeats: Vec::new(),
+            ready: AtomicBool::new(false),
+        }
+    }
+
+    /// A sign of life for a loop to give, if the watchdog is kept. It is
+    /// fed only while every loop with one gives it.
+    pub fn heartbeat(&mut self, name: &str) -> Option<Heartbeat> {
+        let heartbeat = Heartbeat {
+            alive: Arc::new(AtomicBool::new(true)),
+            period: self.watchdog? / 4,
+        };
+        self.heartbeats.push((name.to_owned(), heartbeat.clone()));
+        Some(heartbeat)
+    }
+
+    /// Tell systemd that the daemon is ready, the first time this is
+    /// called.
+    pub fn ready(&self) {
+        if !self.ready.swap(true, Ordering::Relaxed) {
+            self.notify("READY=1");
+        }
+    }
+
+    /// Tell systemd that the daemon is restarting with a new
+    /// configuration. It is ready again once the new session is.
+    pub fn reloading(&self) {
+        self.notify("RELOADING=1");
+    }
+
+    /// Tell systemd that the daemon is shutting down.
+    pub fn stopping(&self) {
+        self.notify("STOPPING=1");
+    }
+
+    /// Feed the watchdog, if it is kept, for as long as every loop gives
+    /// signs of life.
+    pub async fn run_watchdog(&self) {
+        let Some(watchdog) = self.watchdog else {
+            return;
+        };
+        let mut ticker = tokio::time::interval(watchdog / 2);
+        loop {
+            ticker.tick().await;
+            let mut alive = true;
+            for (name, heartbeat) in self.heartbeats.iter() {
+                if !heartbeat.alive.swap(false, Ordering::Relaxed) {
+                    log::error!("{name} has stalled, not feeding watchdog");
+                    alive = false;
+                }
+            }
+            if alive {
+                self.notify("WATCHDOG=1");
+            }
+        }
+    }
+
+    /// Send a notification. Failures are ignored, as systemd will notice
+    /// any that matter.
+    fn notify(&self, state: &str) {
+        if let Some(socket) = self.socket.as_ref() {
+            send(socket, state);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn open_socket(path: &str) -> std::io::Result<Socket> {
+    let address = match path.strip_prefix('@') {
+        Some(name) => abstract_address(name)?,
+        None => SocketAddr::from_pathname(path)?,
+    };
+    Ok((UnixDatagram::unbound()?, address))
+}
+
+#[cfg(not(unix))]
+fn open_socket(_path: &str) -> std::io::Result<Socket> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(unix)]
+fn send((socket, address): &Socket, state: &str) {
+    let _ = socket.send_to_addr(state.as_bytes(), address);
+}
+
+#[cfg(not(unix))]
+fn send(socket: &Socket, _state: &str) {
+    match *socket {}
+}
+
+#[cfg(target_os = "linux")]
+fn abstract_address(name: &str) -> std::io::Result<SocketAddr> {
+    use std::os::linux::net::SocketAddrExt;
+    SocketAddr::from_abstract_name(name)
+}
+
+/// Sockets in the abstract namespace are peculiar to Linux (as is
+/// systemd).
+#[cfg(all(unix, not(target_os = "linux")))]
+fn abstract_address(_name: &str) -> std::io::Result<SocketAddr> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn notifies_ready_once_and_feeds_watchdog() {
+        let path = std::env::temp_dir().join(format!("seismo-notify-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixDatagram::bind(&path).expect("binds");
+        listener.set_nonblocking(true).expect("works");
+        let path_str = path.to_str();
+        let pid = std::process::id().to_string();
+
+        let ignored = Systemd::new(path_str, Some("40000"), Some("1"));
+        assert!(ignored.watchdog.is_none());
+
+        let mut systemd = Systemd::new(path_str, Some("40000"), Some(&pid));
+        assert!(systemd.heartbeat("stalled").is_some());
+        systemd.ready();
+        systemd.ready();
+        let mut buffer = [0u8; 64];
+        let n = listener.recv(&mut buffer).expect("notified");
+        assert_eq!(&buffer[..n], b"READY=1");
+        assert!(listener.recv(&mut buffer).is_err());
+
+        // Its first beat is taken as given, so the watchdog is fed once,
+        // then not while the loop gives no more.
+        let _ = tokio::time::timeout(Duration::from_millis(50), systemd.run_watchdog()).await;
+        let n = listener.recv(&mut buffer).expect("fed");
+        assert_eq!(&buffer[..n], b"WATCHDOG=1");
+        assert!(listener.recv(&mut buffer).is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,149 @@
+//! Sending notifications through a Telegram bot.
+use super::http;
+use super::plot::waveform_png;
+use crate::config::TelegramConfig;
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::net::TcpStream;
+use tokio::time::Duration;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+use tokio_rustls::TlsConnector;
+
+const API_HOST: &str = "api.telegram.org";
+
+/// How long to wait for a request to be answered.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Separates the parts of a photo upload.
+const BOUNDARY: &str = "rs-udp-7f3c91d2a4b8e605";
+
+#[derive(Debug, Error)]
+pub enum TelegramError {
+    #[error("no trusted root certificates found")]
+    NoCertificates,
+    #[error("unable to reach Telegram")]
+    Io(#[from] std::io::Error),
+    #[error("Telegram did not respond in time")]
+    Timeout,
+    #[error("Telegram refused request: {0}")]
+    Rejected(String),
+}
+
+/// A client of the Telegram bot API. Requests are made in the
+/// background, and failures logged, so that a slow or unreachable API
+/// doesn't hold up other actions.
+#[derive(Clone)]
+pub struct Telegram {
+    connector: TlsConnector,
+}
+
+impl Telegram {
+    /// Set up a client which trusts the system's root certificates.
+    pub fn new() -> Result<Self, TelegramError> {
+        let mut roots = RootCertStore::empty();
+        roots.add_parsable_certificates(rustls_native_certs::load_native_certs()?);
+        if roots.is_empty() {
+            return Err(TelegramError::NoCertificates);
+        }
+        let config = ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        Ok(Self {
+            connector: TlsConnector::from(Arc::new(config)),
+        })
+    }
+
+    /// Send a text message.
+    pub fn send_message(&self, config: &TelegramConfig, text: String) {
+        let body = serde_json::json!({
+            "chat_id": config.chat_id.to_string(),
+            "text": text,
+        });
+        let telegram = self.clone();
+        let token = config.bot_token().to_owned();
+        tokio::spawn(async move {
+            let body = body.to_string();
+            let result = telegram
+                .request(&token, "sendMessage", "application/json", body.as_bytes())
+                .await;
+            if let Err(e) = result {
+                log::warn!("Telegram message failed: {e}");
+            }
+        });
+    }
+
+    /// Send a plot of a waveform capture file, with a caption.
+    pub fn send_waveform(&self, config: &TelegramConfig, path: PathBuf, caption: String) {
+        let telegram = self.clone();
+        let token = config.bot_token().to_owned();
+        let chat_id = config.chat_id.to_string();
+        tokio::spawn(async move {
+            let result = async {
+                let capture = tokio::fs::read_to_string(&path).await?;
+                let samples: Vec<f32> = capture
+                    .lines()
+                    .filter_map(|line| line.split_ascii_whitespace().last()?.parse().ok())
+                    .collect();
+                let body = photo_upload(&chat_id, &caption, &waveform_png(&samples));
+                let content_type = format!("multipart/form-data; boundary={BOUNDARY}");
+                telegram
+                    .request(&token, "sendPhoto", &content_type, &body)
+                    .await
+            }
+            .await;
+            if let Err(e) = result {
+                log::warn!("Telegram waveform plot of {} failed: {e}", path.display());
+            }
+        });
+    }
+
+    /// Call a method of the bot API.
+    async fn request(
+        &self,
+        token: &str,
+        method: &str,
+        content_type: &str,
+        body: &[u8],
+    ) -> Result<(), TelegramError> {
+        let path = format!("/bot{token}/{method}");
+        let exchange = async {
+            let stream = TcpStream::connect((API_HOST, 443)).await?;
+            let domain = ServerName::try_from(API_HOST).expect("valid host name");
+            let stream = self.connector.connect(domain, stream).await?;
+            http::post(stream, API_HOST, &path, &[], content_type, body).await
+        };
+        let status = tokio::time::timeout(REQUEST_TIMEOUT, exchange)
+            .await
+            .map_err(|_| TelegramError::Timeout)??;
+        if !http::is_success(&status) {
+            return Err(TelegramError::Rejected(status));
+        }
+        Ok(())
+    }
+}
+
+/// The form posted to upload a PNG photo to a chat.
+fn photo_upload(chat_id: &str, caption: &str, png: &[u8]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(png.len() + 512);
+    for (name, value) in [("chat_id", chat_id), ("caption", caption)] {
+        body.extend_from_slice(
+            format!(
+                "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n{value}\r\n"
+            )
+            .as_bytes(),
+        );
+    }
+    body.extend_from_slice(
+        format!(
+            "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"photo\"; \
+             filename=\"waveform.png\"\r\nContent-Type: image/png\r\n\r\n"
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(png);
+    body.extend_from_slice(format!("\r\n--{BOUNDARY}--\r\n").as_bytes());
+    body
+}
@@ -0,0 +1,30 @@
+//! Stand-in for Telegram notifications when built without the "telegram"
+//! feature. Configurations that ask for them are rejected at startup, so
+//! no client is ever set up.
+use crate::config::TelegramConfig;
+
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum TelegramError {
+    #[error("Telegram support was not built in")]
+    NotBuilt,
+}
+
+#[derive(Clone)]
+pub enum Telegram {}
+
+impl Telegram {
+    pub fn new() -> Result<Self, TelegramError> {
+        Err(TelegramError::NotBuilt)
+    }
+
+    pub fn send_message(&self, _config: &TelegramConfig, _text: String) {
+        match *self {}
+    }
+
+    pub fn send_waveform(&self, _config: &TelegramConfig, _path: PathBuf, _caption: String) {
+        match *self {}
+    }
+}
@@ -5,11 +5,27 @@ pub struct ChannelState {
     pub channel: Channel,
     pub as_of: Option<Instant>,
     pub alive: Option<bool>,
+    // The newest data time seen on the channel, if data times are watched.
+    newest_data_time: Option<f64>,
+    // The channel's own timeout, in place of the checker's.
+    timeout: Option<Duration>,
+}
+
+impl ChannelState {
+    // The time by which the channel must next show signs of life, if it is
+    // to time out at all.
+    fn deadline(&self, default: Option<Duration>) -> Option<Instant> {
+        // Assertion: Caller must have "started" this checker. If not, this
+        // will cause a panic.
+        Some(self.as_of.unwrap() + self.timeout.or(default)?)
+    }
 }
 
 pub struct ChannelChecker {
     timeout: Option<Duration>,
     channel_states: Vec<ChannelState>,
+    // Whether a channel's data time must advance for it to stay alive.
+    watch_data_time: bool,
 }
 
 impl<'a> ChannelChecker {
@@ -17,9 +33,18 @@ impl<'a> ChannelChecker {
         Self {
             timeout,
             channel_states: Vec::new(),
+            watch_data_time: false,
         }
     }
 
+    // Only counts packets whose data time is newer than any seen before on
+    // their channel as signs of life, so that a device which keeps sending
+    // the same stretch of data over and over (as when its firmware hangs)
+    // times out.
+    pub fn watch_data_time(&mut self) {
+        self.watch_data_time = true;
+    }
+
     pub fn track_channel(&mut self, channel: Channel) {
         for channel_state in self.channel_states.iter_mut() {
             if channel_state.channel == channel {
@@ -31,80 +56,111 @@ impl<'a> ChannelChecker {
             channel,
             as_of: None,
             alive: None,
+            newest_data_time: None,
+            timeout: None,
         };
         self.channel_states.push(new_state);
     }
 
+    // Gives a channel a timeout of its own, in place of the checker's (as
+    // for a channel whose device only sends data when there is something
+    // to send). Given several, the channel keeps the longest.
+    pub fn set_channel_timeout(&mut self, channel: Channel, timeout: Duration) {
+        self.track_channel(channel);
+        for channel_state in self.channel_states.iter_mut() {
+            if channel_state.channel == channel {
+                channel_state.timeout = channel_state.timeout.max(Some(timeout));
+            }
+        }
+    }
+
     pub fn start(&mut self, when: Instant) {
         for channel_state in self.channel_states.iter_mut() {
             channel_state.as_of = Some(when);
         }
     }
 
-    fn oldest_not_dead(&self) -> Option<Instant> {
-        let mut oldest_not_dead = None::<Instant>;
-        for channel in self.channel_states.iter() {
-            if channel.alive.unwrap_or(true) {
-                if let Some(oldest_time) = oldest_not_dead {
-                    // Assertion: Caller must have "started" this checker.
-                    // If not, this will cause a panic.
-                    if oldest_time <= channel.as_of.unwrap() {
-                        continue;
+    // Returns Some(true) if the channel was already "alive". A return of
+    // Some(false) indicates that the caller should probably broadcast the
+    // good news that the channel is available. If data times are watched,
+    // a packet whose data time doesn't advance is no sign of life, and
+    // None is returned (without changing anything) for the caller to
+    // ignore the packet, whether or not the channel has timed out.
+    pub fn mark_channel_alive(
+        &mut self,
+        when: Instant,
+        channel: Channel,
+        data_time: f64,
+    ) -> Option<bool> {
+        for channel_state in self.channel_states.iter_mut() {
+            if channel_state.channel == channel {
+                let timeout_s = channel_state.timeout.or(self.timeout).map_or(0.0, |t| t.as_secs_f64());
+                if self.watch_data_time {
+                    match channel_state.newest_data_time {
+                        Some(newest) if data_time > newest => (),
+                        // A jump back further than the timeout is taken
+                        // for the device's clock being set back, which
+                        // time must advance from.
+                        Some(newest) if data_time < newest - timeout_s => {
+                            channel_state.newest_data_time = Some(data_time);
+                            return None;
+                        }
+                        Some(_) => return None,
+                        None => (),
                     }
+                    channel_state.newest_data_time = Some(data_time);
                 }
-                oldest_not_dead = channel.as_of
+                channel_state.as_of.replace(when);
+                return Some(channel_state.alive.replace(true).unwrap_or(false));
             }
         }
-        oldest_not_dead
+        // We weren't configured to monitor this channel. Any answer is
+        // acceptable here.
+        Some(true)
     }
 
-    // Returns true if the channel was already "alive". A return of false
-    // indicates that the caller should probably broadcast the good news
-    // that the channel is available.
-    pub fn mark_channel_alive(&mut self, when: Instant, channel: Channel) -> bool {
+    // Marks every channel that is alive as dead, as when the source they
+    // come from has failed, and returns those channels. A channel yet to
+    // be heard from is left to time out as it would have.
+    pub fn mark_all_dead(&mut self) -> Vec<Channel> {
+        let mut newly_dead = Vec::new();
         for channel_state in self.channel_states.iter_mut() {
-            if channel_state.channel == channel {
-                channel_state.as_of.replace(when);
-                return channel_state.alive.replace(true).unwrap_or(false);
+            if channel_state.alive == Some(true) {
+                channel_state.alive = Some(false);
+                newly_dead.push(channel_state.channel);
             }
         }
-        // We weren't configured to monitor this channel. Any answer is
-        // acceptable here.
-        true
+        newly_dead
     }
 
     // Returns the minimum duration that the caller should wait in order to
     // determine if any channel has stopped producing data.
     pub fn next_timeout(&self, from: Instant) -> Option<Duration> {
-        if let Some(timeout) = self.timeout {
-            if let Some(oldest) = self.oldest_not_dead() {
-                let elapsed = from.duration_since(oldest);
-                if elapsed > timeout {
-                    // Deadline already exceeded
-                    return Some(Duration::ZERO)
-                } else {
-                    return Some(timeout - elapsed)
-                }
-            }
-        }
-        // No timeout is configured and/or nothing is currently alive.
-        None
+        // None if no timeout is configured and/or nothing is currently
+        // alive. A deadline already exceeded gives zero.
+        self.channel_states
+            .iter()
+            .filter(|channel_state| channel_state.alive.unwrap_or(true))
+            .filter_map(|channel_state| channel_state.deadline(self.timeout))
+            .min()
+            .map(|deadline| deadline.saturating_duration_since(from))
     }
 
     // Notes that no channel activity has been detected as of a certain time,
     // and returns an iterator over all channels that have now timed out
     // as a result.
     pub fn timeout_iter(&'a mut self, now: Instant) -> TimeoutIter<'a> {
-        // Not to be called unless you know there's a timeout configured.
         TimeoutIter {
-            timeout_point: now - self.timeout.unwrap(),
+            now,
+            timeout: self.timeout,
             channel_state_iter: self.channel_states.iter_mut(),
         }
     }
 }
 
 pub struct TimeoutIter<'a> {
-    timeout_point: Instant,
+    now: Instant,
+    timeout: Option<Duration>,
     channel_state_iter: core::slice::IterMut<'a, ChannelState>,
 }
 
@@ -113,11 +169,11 @@ impl<'a> Iterator for TimeoutIter<'a> {
 
     fn next(&mut self) -> Option<Self::Item> {
         for channel_state in self.channel_state_iter.by_ref() {
-            if channel_state.alive.unwrap_or(true) {
-                if channel_state.as_of.unwrap() < self.timeout_point {
-                    channel_state.alive.replace(false);
-                    return Some(&*channel_state);
-                }
+            if channel_state.alive.unwrap_or(true)
+                && channel_state.deadline(self.timeout).is_some_and(|deadline| deadline < self.now)
+            {
+                channel_state.alive.replace(false);
+                return Some(&*channel_state);
             }
         }
         None
@@ -171,6 +227,93 @@ mod tests {
         let mut checker = ChannelChecker::new_for_timeout(Some(timeout));
         checker.track_channel(Channel::Ehz);
         checker.start(now);
-        checker.mark_channel_alive(now + Duration::from_secs(2), Channel::Enn);
+        checker.mark_channel_alive(now + Duration::from_secs(2), Channel::Enn, 0.0);
+    }
+
+    // A channel whose data time stops advancing times out, if data times
+    // are watched, however many packets keep arriving.
+    #[test]
+    fn frozen_data_time_times_out() {
+        let now = Instant::now();
+        let timeout = Duration::from_secs(5);
+        let mut checker = ChannelChecker::new_for_timeout(Some(timeout));
+        checker.watch_data_time();
+        checker.track_channel(Channel::Ehz);
+        checker.start(now);
+        assert_eq!(checker.mark_channel_alive(now, Channel::Ehz, 100.0), Some(false));
+        let alive = checker.mark_channel_alive(now + Duration::from_secs(1), Channel::Ehz, 100.25);
+        assert_eq!(alive, Some(true));
+        for secs in 2..8 {
+            checker.mark_channel_alive(now + Duration::from_secs(secs), Channel::Ehz, 100.25);
+        }
+        let timed_out = checker.timeout_iter(now + Duration::from_secs(8)).count();
+        assert_eq!(timed_out, 1);
+        // Replayed data doesn't bring it back, but new data does.
+        let replayed = checker.mark_channel_alive(now + Duration::from_secs(9), Channel::Ehz, 100.0);
+        assert_eq!(replayed, None);
+        let alive = checker.mark_channel_alive(now + Duration::from_secs(10), Channel::Ehz, 101.0);
+        assert_eq!(alive, Some(false));
+    }
+
+    // Packets whose data time doesn't advance are to be ignored, both
+    // while their channel is alive and once it has timed out, but only if
+    // data times are watched.
+    #[test]
+    fn frozen_packets_are_ignored() {
+        let now = Instant::now();
+        let timeout = Duration::from_secs(5);
+        let mut checker = ChannelChecker::new_for_timeout(Some(timeout));
+        checker.watch_data_time();
+        checker.track_channel(Channel::Ehz);
+        checker.start(now);
+        assert_eq!(checker.mark_channel_alive(now, Channel::Ehz, 100.0), Some(false));
+        assert_eq!(checker.mark_channel_alive(now, Channel::Ehz, 100.0), None);
+        assert_eq!(checker.timeout_iter(now + Duration::from_secs(6)).count(), 1);
+        let later = now + Duration::from_secs(7);
+        assert_eq!(checker.mark_channel_alive(later, Channel::Ehz, 100.0), None);
+        assert_eq!(checker.mark_channel_alive(later, Channel::Ehz, 99.0), None);
+
+        let mut checker = ChannelChecker::new_for_timeout(Some(timeout));
+        checker.track_channel(Channel::Ehz);
+        checker.start(now);
+        assert_eq!(checker.mark_channel_alive(now, Channel::Ehz, 100.0), Some(false));
+        assert_eq!(checker.mark_channel_alive(now, Channel::Ehz, 100.0), Some(true));
+    }
+
+    // Only channels which have been heard from die with their source.
+    #[test]
+    fn failed_source_kills_live_channels() {
+        let now = Instant::now();
+        let mut checker = ChannelChecker::new_for_timeout(Some(Duration::from_secs(5)));
+        checker.track_channel(Channel::Ehz);
+        checker.track_channel(Channel::Enz);
+        checker.start(now);
+        checker.mark_channel_alive(now, Channel::Ehz, 100.0);
+        assert_eq!(checker.mark_all_dead(), vec![Channel::Ehz]);
+        assert_eq!(checker.mark_all_dead(), vec![]);
+    }
+
+    // A channel with a timeout of its own times out by it, and one without
+    // by the checker's, if it has one.
+    #[test]
+    fn channel_timeout_overrides() {
+        let now = Instant::now();
+        let mut checker = ChannelChecker::new_for_timeout(Some(Duration::from_secs(5)));
+        checker.track_channel(Channel::Ehz);
+        checker.set_channel_timeout(Channel::Enz, Duration::from_secs(60));
+        checker.start(now);
+        assert_eq!(checker.next_timeout(now), Some(Duration::from_secs(5)));
+        let timed_out: Vec<_> = checker
+            .timeout_iter(now + Duration::from_secs(10))
+            .map(|channel_state| channel_state.channel)
+            .collect();
+        assert_eq!(timed_out, vec![Channel::Ehz]);
+        assert_eq!(checker.next_timeout(now), Some(Duration::from_secs(60)));
+
+        let mut checker = ChannelChecker::new_for_timeout(None);
+        checker.track_channel(Channel::Ehz);
+        checker.set_channel_timeout(Channel::Enz, Duration::from_secs(2));
+        checker.start(now);
+        assert_eq!(checker.next_timeout(now), Some(Duration::from_secs(2)));
     }
 }
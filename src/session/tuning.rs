@@ -0,0 +1,208 @@
+//! Adjusting flows' trigger levels while the daemon runs, as told to over
+//! MQTT, so that a threshold can be tuned without restarting.
+use super::mqtt::{incoming_publish, is_connected, AsyncClient, Event, QoS};
+use crate::config::ThresholdChange;
+
+use serde::Deserialize;
+use thiserror::Error;
+use tokio::sync::broadcast;
+
+#[derive(Debug, Error)]
+pub enum TuningError {
+    #[error("malformed adjustment")]
+    Malformed(#[from] serde_json::Error),
+    #[error("no such flow {0}")]
+    NoSuchFlow(String),
+}
+
+/// An adjustment, as received.
+#[derive(Deserialize)]
+struct Request {
+    flow: String,
+    seismometer: Option<String>,
+    trigger_level: Option<f32>,
+    reset_level: Option<f32>,
+}
+
+/// New trigger levels (and holdoff) for a flow (by id). A level not given
+/// is left as it is.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tuning {
+    pub flow_id: usize,
+    pub name: String,
+    pub trigger_level: Option<f32>,
+    pub reset_level: Option<f32>,
+    pub holdoff: Option<usize>,
+}
+
+/// Adjustments for every flow, which each instrument loop picks its own
+/// flows' out of.
+pub type TuningReceiver = broadcast::Receiver<Tuning>;
+pub type TuningSender = broadcast::Sender<Tuning>;
+
+/// A handle with which to adjust flows' triggers from outside a session
+/// (as on reloading a configuration which changes nothing else).
+pub struct TuningHandle {
+    /// Flows' names and their seismometers' names, by flow id.
+    flows: Vec<(String, String, usize)>,
+    sender: TuningSender,
+}
+
+impl TuningHandle {
+    /// Put new settings into effect for a flow's threshold trigger.
+    pub fn apply(&self, change: &ThresholdChange) {
+        let flows = self.flows.iter().filter(|(name, seismometer, _)| {
+            *name == change.flow && *seismometer == change.seismometer
+        });
+        for &(_, _, flow_id) in flows {
+            let _ = self.sender.send(Tuning {
+                flow_id,
+                name: change.flow.clone(),
+                trigger_level: Some(change.trigger_level),
+                reset_level: Some(change.reset_level),
+                holdoff: Some(change.holdoff),
+            });
+        }
+    }
+}
+
+pub struct TuningControl<'a> {
+    topic: Option<&'a str>,
+    mqtt: Option<AsyncClient>,
+    /// Flows' names and their seismometers' names, by flow id.
+    flows: Vec<(&'a str, &'a str, usize)>,
+    sender: TuningSender,
+}
+
+impl<'a> TuningControl<'a> {
+    pub fn new(topic: Option<&'a str>, mqtt: Option<AsyncClient>) -> Self {
+        Self {
+            topic,
+            mqtt,
+            flows: Vec::new(),
+            sender: broadcast::channel(16).0,
+        }
+    }
+
+    /// Accept adjustments for flows, by name and seismometer name.
+    pub fn control_flows(&mut self, flows: Vec<(&'a str, &'a str, usize)>) {
+        self.flows = flows;
+    }
+
+    /// Receive the adjustments accepted from now on.
+    pub fn subscribe(&self) -> TuningReceiver {
+        self.sender.subscribe()
+    }
+
+    /// Pass on adjustments made elsewhere (as through the control
+    /// socket) along with those received over MQTT.
+    pub fn sender(&self) -> TuningSender {
+        self.sender.clone()
+    }
+
+    /// A handle with which to adjust the flows from outside the session.
+    pub fn handle(&self) -> TuningHandle {
+        let flows = self
+            .flows
+            .iter()
+            .map(|&(name, seismometer, flow_id)| (name.to_owned(), seismometer.to_owned(), flow_id))
+            .collect();
+        TuningHandle {
+            flows,
+            sender: self.sender.clone(),
+        }
+    }
+
+    /// React to an event from the MQTT connection, (re-)subscribing to the
+    /// thresholds topic whenever a connection is made and passing on any
+    /// adjustments received on it.
+    pub fn handle_mqtt_event(&self, event: &Event) {
+        let Some(topic) = self.topic else {
+            return;
+        };
+        if is_connected(event) {
+            if let Some(client) = self.mqtt.as_ref() {
+                // The event loop is busy running this, so the request
+                // must not wait for room in the queue.
+                let _ = client.try_subscribe(topic, QoS::AtLeastOnce);
+            }
+            return;
+        }
+        let Some((_, payload)) = incoming_publish(event).filter(|(t, _)| *t == topic) else {
+            return;
+        };
+        match self.resolve(payload) {
+            Ok(tunings) => {
+                for tuning in tunings {
+                    // Nobody listening is no reason to complain.
+                    let _ = self.sender.send(tuning);
+                }
+            }
+            Err(e) => log::warn!("ignoring threshold adjustment: {e}"),
+        }
+    }
+
+    /// The adjustments that a payload asks for, for each flow it names.
+    fn resolve(&self, payload: &[u8]) -> Result<Vec<Tuning>, TuningError> {
+        let request: Request = serde_json::from_slice(payload)?;
+        let seismometer = request.seismometer.as_deref();
+        let tunings: Vec<Tuning> = self
+            .flows
+            .iter()
+            .filter(|&&(name, s, _)| name == request.flow && seismometer.is_none_or(|x| x == s))
+            .map(|&(_, _, flow_id)| Tuning {
+                flow_id,
+                name: request.flow.clone(),
+                trigger_level: request.trigger_level,
+                reset_level: request.reset_level,
+                holdoff: None,
+            })
+            .collect();
+        if tunings.is_empty() {
+            return Err(TuningError::NoSuchFlow(request.flow));
+        }
+        Ok(tunings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_flows_by_name() {
+        let mut control = TuningControl::new(Some("seismo/thresholds"), None);
+        control.control_flows(vec![
+            ("z", "garage", 0),
+            ("z", "attic", 2),
+            ("e", "attic", 3),
+        ]);
+        let ids = |payload: &str| -> Vec<usize> {
+            let tunings = control.resolve(payload.as_bytes()).expect("resolves");
+            tunings.iter().map(|t| t.flow_id).collect()
+        };
+        assert_eq!(ids(r#"{"flow": "z", "trigger_level": 2}"#), [0, 2]);
+        assert_eq!(ids(r#"{"flow": "z", "seismometer": "attic"}"#), [2]);
+        let tunings = control
+            .resolve(br#"{"flow": "e", "reset_level": 0.5}"#)
+            .expect("resolves");
+        assert_eq!(
+            tunings,
+            [Tuning {
+                flow_id: 3,
+                name: String::from("e"),
+                trigger_level: None,
+                reset_level: Some(0.5),
+                holdoff: None,
+            }]
+        );
+        assert!(matches!(
+            control.resolve(br#"{"flow": "n"}"#),
+            Err(TuningError::NoSuchFlow(_))
+        ));
+        assert!(matches!(
+            control.resolve(b"2.0"),
+            Err(TuningError::Malformed(_))
+        ));
+    }
+}
@@ -0,0 +1,202 @@
+//! Posting events to web services, optionally signed with a shared
+//! secret so that the services can tell they came from this daemon.
+use super::keyed_hash::hmac;
+use super::retry::RetryPolicy;
+use super::status::StatusBoard;
+use crate::config::{SignatureAlgorithm, WebhookConfig};
+
+use hmac::Hmac;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Client, StatusCode, Url};
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tokio::time::Duration;
+
+/// How long to wait for the service to accept a post.
+const POST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Error)]
+pub enum WebhookError {
+    #[error("webhook URL {0} is not an HTTP or HTTPS URL")]
+    UnsupportedUrl(String),
+    #[error("unable to post to webhook: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("webhook refused post: {0}")]
+    Rejected(StatusCode),
+}
+
+/// Check that a webhook's URL can be posted to.
+pub fn check_webhook_config(config: &WebhookConfig) -> Result<(), WebhookError> {
+    webhook_url(&config.url)
+        .map(|_| ())
+        .ok_or_else(|| WebhookError::UnsupportedUrl(config.url.clone()))
+}
+
+fn webhook_url(url: &str) -> Option<Url> {
+    Url::parse(url)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https") && url.has_host())
+}
+
+/// The client all posts are made with, so that connections (and the
+/// system's root certificates) are shared between them.
+fn client() -> &'static Client {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    CLIENT.get_or_init(Client::new)
+}
+
+/// Post a JSON body to a webhook in the background, signed if it has a
+/// secret. The post is retried as the actions say if it fails.
+pub fn post_webhook(
+    config: &WebhookConfig,
+    body: String,
+    retry: RetryPolicy,
+    failures: Option<&StatusBoard>,
+) {
+    let Some(url) = webhook_url(&config.url) else {
+        return;
+    };
+    let signing = config.secret.as_ref().map(|secret| Signing {
+        algorithm: config.signature_algorithm,
+        secret: secret.clone(),
+        header: config.signature_header.clone(),
+        timestamp_header: config.timestamp_header.clone(),
+    });
+    let failures = failures.cloned();
+    tokio::spawn(async move {
+        let what = format!("webhook post to {url}");
+        let _ = retry
+            .attempt(&what, failures.as_ref(), || {
+                post(url.clone(), signing.as_ref(), &body)
+            })
+            .await;
+    });
+}
+
+/// How a webhook's posts are signed.
+struct Signing {
+    algorithm: SignatureAlgorithm,
+    secret: String,
+    header: String,
+    timestamp_header: String,
+}
+
+async fn post(url: Url, signing: Option<&Signing>, body: &str) -> Result<(), WebhookError> {
+    let mut request = client()
+        .post(url)
+        .timeout(POST_TIMEOUT)
+        .header(CONTENT_TYPE, "application/json");
+    // Each attempt is signed afresh, so that a retry isn't taken for a
+    // replay.
+    if let Some(signing) = signing {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let signature = sign(
+            signing.algorithm,
+            signing.secret.as_bytes(),
+            timestamp,
+            body.as_bytes(),
+        );
+        request = request
+            .header(&signing.timestamp_header, timestamp)
+            .header(&signing.header, signature);
+    }
+    let response = request.body(body.to_owned()).send().await?;
+    if !response.status().is_success() {
+        return Err(WebhookError::Rejected(response.status()));
+    }
+    Ok(())
+}
+
+/// Sign a body, as posted at a time (in seconds since the Unix epoch):
+/// the HMAC of the time, a ".", and the body, in hex, after the
+/// algorithm's name and "=".
+fn sign(algorithm: SignatureAlgorithm, secret: &[u8], timestamp: u64, body: &[u8]) -> String {
+    let mut message = format!("{timestamp}.").into_bytes();
+    message.extend_from_slice(body);
+    let mac = match algorithm {
+        SignatureAlgorithm::Sha1 => hmac::<Hmac<Sha1>>(secret, &message),
+        SignatureAlgorithm::Sha256 => hmac::<Hmac<Sha256>>(secret, &message),
+        SignatureAlgorithm::Sha512 => hmac::<Hmac<Sha512>>(secret, &message),
+    };
+    let hex: String = mac.iter().map(|b| format!("{b:02x}")).collect();
+    format!("{}={hex}", algorithm.name())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn signs_the_time_and_body() {
+        let signature = sign(
+            SignatureAlgorithm::Sha256,
+            b"Jefe",
+            1700000000,
+            b"{\"event\":\"triggered\"}",
+        );
+        let mac = hmac::<Hmac<Sha256>>(b"Jefe", b"1700000000.{\"event\":\"triggered\"}");
+        let hex: String = mac.iter().map(|b| format!("{b:02x}")).collect();
+        assert_eq!(signature, format!("sha256={hex}"));
+        let later = sign(
+            SignatureAlgorithm::Sha256,
+            b"Jefe",
+            1700000001,
+            b"{\"event\":\"triggered\"}",
+        );
+        assert_ne!(signature, later);
+    }
+
+    #[test]
+    fn checks_urls() {
+        assert!(webhook_url("http://hooks.example/seismo").is_some());
+        assert!(webhook_url("https://hooks.example:8443/").is_some());
+        assert!(webhook_url("ftp://hooks.example/").is_none());
+        assert!(webhook_url("hooks.example").is_none());
+    }
+
+    #[tokio::test]
+    async fn posts_signed_requests() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("binds");
+        let url = format!("http://{}/hook", listener.local_addr().expect("bound"));
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.expect("accepts");
+            let mut request = Vec::new();
+            let mut buffer = [0; 4096];
+            while !request.ends_with(b"{}") {
+                let read = stream.read(&mut buffer).await.expect("reads");
+                request.extend_from_slice(&buffer[..read]);
+            }
+            let response = "HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n";
+            stream.write_all(response.as_bytes()).await.expect("writes");
+            String::from_utf8(request).expect("UTF-8").to_lowercase()
+        });
+        let signing = Signing {
+            algorithm: SignatureAlgorithm::Sha256,
+            secret: String::from("s3cret"),
+            header: String::from("X-Signature"),
+            timestamp_header: String::from("X-Signature-Timestamp"),
+        };
+        let url = Url::parse(&url).expect("valid");
+        post(url, Some(&signing), "{}").await.expect("posts");
+        let request = server.await.expect("serves");
+        assert!(request.starts_with("post /hook "), "{request}");
+        let header = |name: &str| {
+            let line = request.lines().find(|line| line.starts_with(name));
+            line.and_then(|line| line.split_once(": "))
+                .map(|(_, value)| value)
+        };
+        let timestamp: u64 = header("x-signature-timestamp:")
+            .and_then(|timestamp| timestamp.parse().ok())
+            .expect("timestamped");
+        let signature = sign(SignatureAlgorithm::Sha256, b"s3cret", timestamp, b"{}");
+        assert_eq!(header("x-signature:"), Some(signature.as_str()));
+    }
+}
@@ -0,0 +1,26 @@
+//! Stand-in for webhook actions when built without the "webhook"
+//! feature. Configurations that ask for them are rejected at startup, so
+//! nothing is ever posted.
+use super::retry::RetryPolicy;
+use super::status::StatusBoard;
+use crate::config::WebhookConfig;
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum WebhookError {
+    #[error("webhook support was not built in")]
+    NotBuilt,
+}
+
+pub fn check_webhook_config(_config: &WebhookConfig) -> Result<(), WebhookError> {
+    Err(WebhookError::NotBuilt)
+}
+
+pub fn post_webhook(
+    _config: &WebhookConfig,
+    _body: String,
+    _retry: RetryPolicy,
+    _failures: Option<&StatusBoard>,
+) {
+}
@@ -0,0 +1,92 @@
+//! A thread of an instrument's own for its flows' signal processing, so
+//! that heavy filtering can't hold up the runtime which receives packets
+//! and watches for timeouts, without starting a blocking task for every
+//! packet.
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::mpsc;
+use std::thread;
+use thiserror::Error;
+use tokio::sync::oneshot;
+
+#[derive(Error, Debug)]
+#[error("signal processing panicked: {0}")]
+pub struct WorkerPanic(String);
+
+type Job = Box<dyn FnOnce() + Send>;
+
+pub struct Worker {
+    jobs: mpsc::Sender<Job>,
+}
+
+impl Worker {
+    /// Start a worker thread, named for what it works for. The thread
+    /// ends once the worker is dropped.
+    pub fn new(name: &str) -> std::io::Result<Self> {
+        let (jobs, queue) = mpsc::channel::<Job>();
+        thread::Builder::new()
+            .name(format!("{name} dsp"))
+            .spawn(move || {
+                for job in queue {
+                    job();
+                }
+            })?;
+        Ok(Self { jobs })
+    }
+
+    /// Run a function over some state on the worker thread, handing the
+    /// state back with the function's result. Should the function panic,
+    /// the state is still handed back (as the function left it), so that
+    /// it isn't lost with the panic.
+    pub async fn run<T, R, F>(&self, mut state: T, f: F) -> (T, Result<R, WorkerPanic>)
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: FnOnce(&mut T) -> R + Send + 'static,
+    {
+        let (reply, result) = oneshot::channel();
+        let job = Box::new(move || {
+            let result = catch_unwind(AssertUnwindSafe(|| f(&mut state)));
+            let _ = reply.send((state, result.map_err(describe_panic)));
+        });
+        // Panics are caught, so the thread runs for as long as the worker
+        // is held.
+        self.jobs.send(job).expect("worker thread runs");
+        result.await.expect("worker thread replies")
+    }
+}
+
+fn describe_panic(panic: Box<dyn std::any::Any + Send>) -> WorkerPanic {
+    let message = match (panic.downcast_ref::<&str>(), panic.downcast_ref::<String>()) {
+        (Some(message), _) => message.to_string(),
+        (None, Some(message)) => message.clone(),
+        (None, None) => String::from("unknown cause"),
+    };
+    WorkerPanic(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn it_keeps_state_through_panics() {
+        let worker = Worker::new("test").expect("starts");
+        let (state, sum) = worker
+            .run(vec![1, 2, 3], |v: &mut Vec<i32>| {
+                v.push(4);
+                v.iter().sum::<i32>()
+            })
+            .await;
+        assert_eq!(sum.expect("runs"), 10);
+        let (state, panicked) = worker
+            .run(state, |v: &mut Vec<i32>| {
+                v.push(5);
+                panic!("filter blew up")
+            })
+            .await;
+        assert_eq!(state, vec![1, 2, 3, 4, 5]);
+        assert!(panicked.is_err_and(|e| e.to_string().contains("filter blew up")));
+        let (_, len) = worker.run(state, |v: &mut Vec<i32>| v.len()).await;
+        assert_eq!(len.expect("still runs"), 5);
+    }
+}
@@ -18,28 +18,74 @@ pub enum AffineError {}
 ///
 /// 1. Subtract "offset" from sample.
 /// 2. Multiply result by "gain".
+///
+/// Optionally, the offset is calibrated rather than given: it is taken
+/// as the mean of the first so many samples, during which the output is
+/// held at zero.
 pub struct AffineTransform<T>
 where
     T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand,
 {
     offset: T,
     gain: T,
+    calibration: Option<Calibration>,
+}
+
+/// Progress of an offset calibration.
+struct Calibration {
+    samples: usize,
+    seen: usize,
+    sum: f64,
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> AffineTransform<T> {
+    /// The offset being removed, unless it is still being calibrated.
+    pub fn offset(&self) -> Option<T> {
+        match self.calibration {
+            Some(_) => None,
+            None => Some(self.offset),
+        }
+    }
+
+    fn calibrate(&mut self, x: T) -> T {
+        let Some(calibration) = self.calibration.as_mut() else {
+            return (x - self.offset) * self.gain;
+        };
+        calibration.sum += x.to_f64().unwrap_or(0.0);
+        calibration.seen += 1;
+        if calibration.seen >= calibration.samples {
+            let mean = calibration.sum / calibration.seen as f64;
+            self.offset = T::from(mean).unwrap_or(T::zero());
+            self.calibration = None;
+        }
+        T::zero()
+    }
 }
 
 impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> SignalBlock<T>
     for AffineTransform<T>
 {
-    fn reset(&mut self) {}
+    fn reset(&mut self) {
+        // A completed calibration is kept; one in progress starts over.
+        if let Some(calibration) = self.calibration.as_mut() {
+            calibration.seen = 0;
+            calibration.sum = 0.0;
+        }
+    }
 
-    fn process(&mut self, input: &ndarray::Array1<T>) -> ndarray::Array1<T> {
-        let result = (input - self.offset) * self.gain;
-        result
+    fn process_into(&mut self, input: &ndarray::ArrayView1<T>, out: &mut ndarray::Array1<T>) {
+        if self.calibration.is_some() {
+            out.zip_mut_with(input, |o, &x| *o = self.calibrate(x));
+        } else {
+            out.zip_mut_with(input, |o, &x| *o = (x - self.offset) * self.gain);
+        }
     }
 }
 
 pub struct AffineTransformBuilder<T> {
     offset: Option<T>,
     gain: Option<T>,
+    calibration_samples: Option<usize>,
 }
 
 impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> Default
@@ -55,6 +101,7 @@ impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> AffineTrans
         Self {
             offset: None,
             gain: None,
+            calibration_samples: None,
         }
     }
 
@@ -70,11 +117,26 @@ impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> AffineTrans
         self
     }
 
+    /// Instead of a given offset, subtract the mean of the first `n`
+    /// input samples, outputting zero until they have been seen.
+    pub fn calibrate_offset(mut self, n: usize) -> Self {
+        self.calibration_samples.replace(n);
+        self
+    }
+
     /// Construct an affine transform.
     pub fn build(self) -> Result<AffineTransform<T>, AffineError> {
         let mut result = AffineTransform {
             offset: self.offset.unwrap_or(T::zero()),
             gain: self.gain.unwrap_or(T::one()),
+            calibration: self
+                .calibration_samples
+                .filter(|&n| n > 0)
+                .map(|samples| Calibration {
+                    samples,
+                    seen: 0,
+                    sum: 0.0,
+                }),
         };
         result.reset();
         Ok(result)
@@ -84,13 +146,32 @@ impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> AffineTrans
 #[cfg(test)]
 mod tests {
     use super::AffineTransformBuilder;
+    use crate::signal::SignalBlock;
+    use ndarray::Array1;
 
     #[test]
     fn test_one() {
         AffineTransformBuilder::new()
-            .offset(15000 as f32)
+            .offset(15000_f32)
             .gain(0.00004)
             .build()
             .expect("build");
     }
+
+    #[test]
+    fn calibrates_offset() {
+        let mut affine = AffineTransformBuilder::new()
+            .gain(2.0_f32)
+            .calibrate_offset(4)
+            .build()
+            .expect("build");
+        let input = Array1::from_vec(vec![99.0, 101.0, 100.0, 100.0, 103.0, 97.0]);
+        let output = affine.process(&input);
+        assert_eq!(output.to_vec(), vec![0.0, 0.0, 0.0, 0.0, 6.0, -6.0]);
+        assert_eq!(affine.offset(), Some(100.0));
+
+        // Resets keep the calibration.
+        affine.reset();
+        assert_eq!(affine.process(&input)[0], -2.0);
+    }
 }
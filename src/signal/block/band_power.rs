@@ -0,0 +1,250 @@
+use std::collections::VecDeque;
+use std::iter::Sum;
+use std::sync::Arc;
+
+use ndarray::ScalarOperand;
+use num_traits::One;
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+use thiserror::Error;
+
+pub use num_traits::{Float, Zero};
+pub use sci_rs::na::RealField;
+
+use crate::signal::SignalBlock;
+
+#[derive(Error, Debug)]
+pub enum BandPowerError {
+    #[error("band must lie between zero and the Nyquist frequency")]
+    BandOutOfRange,
+    #[error("band must be at least one frequency bin wide")]
+    BandTooNarrow,
+    #[error("window must be at least four samples long")]
+    WindowTooShort,
+}
+
+/// Signal processing block which estimates the power of its input in a
+/// frequency band, from the power spectral density over a sliding
+/// window. Optionally, the power is given relative to that of a second,
+/// reference band, so that triggers may look for a spectral signature
+/// (such as earthquake energy in 1-10 Hz, relative to wind or traffic
+/// noise elsewhere).
+///
+/// A new estimate is made every half window, and held until the next.
+pub struct BandPower<T>
+where
+    T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand,
+{
+    fft: Arc<dyn Fft<f64>>,
+    window: Vec<f64>,
+    /// Scale from squared FFT magnitudes to one-sided power spectral
+    /// density, times the width of a frequency bin.
+    scale: f64,
+    band: (usize, usize),
+    reference: Option<(usize, usize)>,
+
+    history: VecDeque<f64>,
+    /// Samples since the last estimate.
+    since: usize,
+    power: T,
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> BandPower<T> {
+    fn estimate(&mut self) -> T {
+        let mut buffer: Vec<Complex<f64>> = self
+            .history
+            .iter()
+            .zip(self.window.iter())
+            .map(|(x, w)| Complex::new(x * w, 0.0))
+            .collect();
+        self.fft.process(&mut buffer);
+        let power = |(low, high): (usize, usize)| -> f64 {
+            buffer[low..high]
+                .iter()
+                .map(|c| c.norm_sqr() * self.scale)
+                .sum()
+        };
+        let band = power(self.band);
+        let estimate = match self.reference {
+            Some(reference) => band / power(reference).max(f64::MIN_POSITIVE),
+            None => band,
+        };
+        T::from(estimate).unwrap_or(T::zero())
+    }
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> SignalBlock<T>
+    for BandPower<T>
+{
+    fn reset(&mut self) {
+        self.history.clear();
+        self.since = 0;
+        self.power = T::zero();
+    }
+
+    fn process_into(&mut self, input: &ndarray::ArrayView1<T>, out: &mut ndarray::Array1<T>) {
+        let len = self.window.len();
+        out.zip_mut_with(input, |o, &x| {
+            if self.history.len() == len {
+                self.history.pop_front();
+            }
+            self.history.push_back(x.to_f64().unwrap_or(0.0));
+            self.since += 1;
+            if self.history.len() == len && self.since >= len / 2 {
+                self.since = 0;
+                self.power = self.estimate();
+            }
+            *o = self.power
+        });
+    }
+}
+
+pub struct BandPowerBuilder<T> {
+    sample_rate_hz: Option<T>,
+    window_s: Option<T>,
+    band: Option<(T, T)>,
+    reference: Option<(T, T)>,
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> Default
+    for BandPowerBuilder<T>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> BandPowerBuilder<T> {
+    pub fn new() -> Self {
+        Self {
+            sample_rate_hz: None,
+            window_s: None,
+            band: None,
+            reference: None,
+        }
+    }
+
+    /// Interpret samples as coming in at a sample rate.
+    pub fn sample_rate(mut self, hz: T) -> Self {
+        self.sample_rate_hz.replace(hz);
+        self
+    }
+
+    /// Length of the window over which the spectrum is estimated, in
+    /// seconds.
+    pub fn window_s(mut self, s: T) -> Self {
+        self.window_s.replace(s);
+        self
+    }
+
+    /// The band whose power to estimate, from `low_hz` up to `high_hz`.
+    /// If not given, the whole spectrum is used.
+    pub fn band(mut self, low_hz: T, high_hz: T) -> Self {
+        self.band.replace((low_hz, high_hz));
+        self
+    }
+
+    /// Divide the band's power by that of another band.
+    pub fn reference_band(mut self, low_hz: T, high_hz: T) -> Self {
+        self.reference.replace((low_hz, high_hz));
+        self
+    }
+
+    /// Construct a band power estimator.
+    pub fn build(self) -> Result<BandPower<T>, BandPowerError> {
+        let sample_rate_hz = self
+            .sample_rate_hz
+            .unwrap_or(T::one())
+            .to_f64()
+            .unwrap_or(1.0);
+        let window_s = self
+            .window_s
+            .map_or(Some(5.0), |s| s.to_f64())
+            .unwrap_or(0.0);
+        let len = (window_s * sample_rate_hz).round() as usize;
+        if len < 4 {
+            return Err(BandPowerError::WindowTooShort);
+        }
+        let bin_hz = sample_rate_hz / len as f64;
+
+        // Bins from the one nearest `low` up to, but not including, the
+        // one nearest `high`.
+        let bins = |(low, high): (T, T)| -> Result<(usize, usize), BandPowerError> {
+            let (low, high) = (low.to_f64().unwrap_or(-1.0), high.to_f64().unwrap_or(-1.0));
+            if low < 0.0 || high > sample_rate_hz / 2.0 {
+                return Err(BandPowerError::BandOutOfRange);
+            }
+            let (low, high) = (
+                (low / bin_hz).round() as usize,
+                (high / bin_hz).round() as usize,
+            );
+            if high <= low {
+                return Err(BandPowerError::BandTooNarrow);
+            }
+            Ok((low, high))
+        };
+        let nyquist_hz = T::from(sample_rate_hz / 2.0).unwrap_or(T::one());
+        let band = bins(self.band.unwrap_or((T::zero(), nyquist_hz)))?;
+        let reference = self.reference.map(bins).transpose()?;
+
+        // Hann window.
+        let window: Vec<f64> = (0..len)
+            .map(|i| 0.5 - 0.5 * (2.0 * std::f64::consts::PI * i as f64 / len as f64).cos())
+            .collect();
+        let window_power: f64 = window.iter().map(|w| w * w).sum();
+        let result = BandPower {
+            fft: FftPlanner::new().plan_fft_forward(len),
+            scale: 2.0 / (sample_rate_hz * window_power) * bin_hz,
+            window,
+            band,
+            reference,
+            history: VecDeque::with_capacity(len),
+            since: 0,
+            power: T::zero(),
+        };
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BandPowerBuilder;
+    use crate::signal::SignalBlock;
+    use ndarray::Array1;
+
+    #[test]
+    fn measures_band_power() {
+        // A 5 Hz sinusoid of amplitude 2 has a power of 2.
+        let rate = 100.0_f32;
+        let wave = Array1::from_iter(
+            (0..1000).map(|i| 2.0 * (2.0 * std::f32::consts::PI * 5.0 * i as f32 / rate).sin()),
+        );
+        let mut in_band = BandPowerBuilder::new()
+            .sample_rate(rate)
+            .window_s(2.0)
+            .band(1.0, 10.0)
+            .build()
+            .expect("works");
+        let power = *in_band.process(&wave).last().unwrap();
+        assert!((power - 2.0).abs() < 0.1, "{power}");
+
+        let mut out_of_band = BandPowerBuilder::new()
+            .sample_rate(rate)
+            .window_s(2.0)
+            .band(20.0, 40.0)
+            .build()
+            .expect("works");
+        let power = *out_of_band.process(&wave).last().unwrap();
+        assert!(power < 0.01, "{power}");
+
+        // Ratios.
+        let mut ratio = BandPowerBuilder::new()
+            .sample_rate(rate)
+            .window_s(2.0)
+            .band(1.0, 10.0)
+            .reference_band(20.0, 40.0)
+            .build()
+            .expect("works");
+        let noisy = wave.mapv(|x| x + 0.01);
+        assert!(*ratio.process(&noisy).last().unwrap() > 100.0);
+    }
+}
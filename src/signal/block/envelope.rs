@@ -0,0 +1,168 @@
+use std::iter::Sum;
+
+use ndarray::ScalarOperand;
+use num_traits::One;
+use thiserror::Error;
+
+pub use num_traits::{Float, Zero};
+pub use sci_rs::na::RealField;
+
+use crate::signal::SignalBlock;
+
+#[derive(Error, Debug)]
+pub enum EnvelopeError {
+    #[error("Attack alpha is out of range (0-1)")]
+    AttackOutOfRange,
+    #[error("Release alpha is out of range (0-1)")]
+    ReleaseOutOfRange,
+}
+
+/// Envelope follower, for use on a rectified signal.
+///
+/// A one-pole smoother whose decay rate (alpha) depends on direction: a
+/// quick "attack" alpha while the input rises above the envelope, so
+/// that sharp transients are caught without the lag of a symmetric
+/// smoother, and a slower "release" alpha while it falls below.
+///
+/// Smoothing delays the envelope's rise behind the signal's by the attack
+/// smoother's group delay, alpha / (1 - alpha) samples, which is reported
+/// by [`EnvelopeFollower::delay_s`] so that trigger times can be moved
+/// back by it.
+pub struct EnvelopeFollower<T>
+where
+    T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand,
+{
+    attack: T,
+    release: T,
+    sample_rate_hz: T,
+    envelope: T,
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> EnvelopeFollower<T> {
+    /// Seconds by which the envelope lags a rise in the signal.
+    pub fn delay_s(&self) -> T {
+        if self.attack < T::one() {
+            self.attack / (T::one() - self.attack) / self.sample_rate_hz
+        } else {
+            // The envelope never rises at all.
+            T::zero()
+        }
+    }
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> SignalBlock<T>
+    for EnvelopeFollower<T>
+{
+    fn reset(&mut self) {
+        self.envelope = T::zero();
+    }
+
+    fn process_into(&mut self, input: &ndarray::ArrayView1<T>, out: &mut ndarray::Array1<T>) {
+        out.zip_mut_with(input, |o, &x| {
+            let alpha = if x > self.envelope {
+                self.attack
+            } else {
+                self.release
+            };
+            self.envelope = alpha * self.envelope + (T::one() - alpha) * x;
+            *o = self.envelope
+        });
+    }
+}
+
+pub struct EnvelopeFollowerBuilder<T> {
+    attack: Option<T>,
+    release: Option<T>,
+    sample_rate_hz: Option<T>,
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> Default
+    for EnvelopeFollowerBuilder<T>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> EnvelopeFollowerBuilder<T> {
+    pub fn new() -> Self {
+        Self {
+            attack: None,
+            release: None,
+            sample_rate_hz: None,
+        }
+    }
+
+    /// Interpret samples as coming in at a sample rate.
+    pub fn sample_rate(mut self, hz: T) -> Self {
+        self.sample_rate_hz.replace(hz);
+        self
+    }
+
+    /// Momentum coefficient while the input is rising.
+    /// (0.0 => jumps straight to every peak)
+    pub fn attack(mut self, alpha: T) -> Self {
+        self.attack.replace(alpha);
+        self
+    }
+
+    /// Momentum coefficient while the input is falling.
+    /// (1.0 => holds peaks forever)
+    pub fn release(mut self, alpha: T) -> Self {
+        self.release.replace(alpha);
+        self
+    }
+
+    /// Construct an envelope follower block.
+    pub fn build(self) -> Result<EnvelopeFollower<T>, EnvelopeError> {
+        let attack = self.attack.unwrap_or(T::zero());
+        let release = self.release.unwrap_or(T::zero());
+        if attack < T::zero() || attack > T::one() {
+            return Err(EnvelopeError::AttackOutOfRange);
+        }
+        if release < T::zero() || release > T::one() {
+            return Err(EnvelopeError::ReleaseOutOfRange);
+        }
+        Ok(EnvelopeFollower {
+            attack,
+            release,
+            sample_rate_hz: self.sample_rate_hz.unwrap_or(T::one()),
+            envelope: T::zero(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EnvelopeFollowerBuilder;
+    use crate::signal::SignalBlock;
+    use ndarray::Array1;
+
+    #[test]
+    fn catches_transients() {
+        let mut follower = EnvelopeFollowerBuilder::new()
+            .attack(0.0_f32)
+            .release(0.9)
+            .build()
+            .expect("works");
+        let mut input = Array1::zeros(20);
+        input[5] = 1.0;
+        let output = follower.process(&input);
+        assert_eq!(output[4], 0.0);
+        assert_eq!(output[5], 1.0);
+        assert!((output[6] - 0.9).abs() < 1e-6);
+        assert!(output[19] > 0.0 && output[19] < output[6]);
+    }
+
+    #[test]
+    fn reports_its_delay() {
+        let follower = EnvelopeFollowerBuilder::new()
+            .attack(0.75_f32)
+            .release(0.99)
+            .sample_rate(100.0)
+            .build()
+            .expect("works");
+        // Three samples, at 100Hz.
+        assert!((follower.delay_s() - 0.03).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,282 @@
+use std::iter::Sum;
+
+use ndarray::ScalarOperand;
+use num_traits::One;
+use sci_rs::signal::filter::design::butter_dyn;
+use sci_rs::signal::filter::design::DigitalFilter;
+use sci_rs::signal::filter::design::FilterBandType;
+use sci_rs::signal::filter::design::FilterOutputType;
+use sci_rs::signal::filter::design::Sos;
+use sci_rs::signal::filter::design::SosFormatFilter;
+use thiserror::Error;
+
+pub use num_traits::{Float, Zero};
+pub use sci_rs::na::RealField;
+
+use crate::signal::SignalBlock;
+
+use super::super::filter::sosfilt::sosfilt_in_place;
+
+#[derive(Error, Debug)]
+pub enum FilterBankError {
+    #[error("filter bank needs at least one band")]
+    NoBands,
+    #[error("band must lie between zero and the Nyquist frequency")]
+    BandOutOfRange,
+    #[error("output refers to a band the filter bank doesn't have")]
+    OutputOutOfRange,
+    #[error("Alpha is out of range (0-1)")]
+    AlphaOutOfRange,
+    #[error("failed to create filter")]
+    FilterFailure,
+}
+
+/// Which of a filter bank's band energies it outputs.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FilterBankOutput {
+    /// The energy in one band.
+    Band(usize),
+    /// The energy in one band divided by the energy in another.
+    Ratio(usize, usize),
+}
+
+impl Default for FilterBankOutput {
+    fn default() -> Self {
+        Self::Band(0)
+    }
+}
+
+/// One band of a filter bank.
+struct Band<T: RealField + Copy> {
+    taps: Vec<Sos<T>>,
+    memory: Vec<Sos<T>>,
+    /// Smoothed energy, as of the last sample processed.
+    energy: T,
+    /// Smoothed energy at each sample of the last input.
+    energies: Vec<T>,
+}
+
+/// Signal processing block which splits its input into several
+/// frequency bands with Butterworth band-pass filters, and measures the
+/// smoothed energy (squared, then one-pole low-pass filtered) in each.
+/// It outputs the energy in one band, or the ratio of the energies in
+/// two, so that triggers may tell apart sources with different spectra
+/// (footsteps are mostly above 8 Hz, say, and earthquakes below).
+pub struct FilterBank<T>
+where
+    T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand,
+{
+    bands: Vec<Band<T>>,
+    alpha: T,
+    output: FilterBankOutput,
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> FilterBank<T> {
+    /// The smoothed energy in each band, as of the last sample
+    /// processed.
+    pub fn energies(&self) -> impl Iterator<Item = T> + '_ {
+        self.bands.iter().map(|band| band.energy)
+    }
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> SignalBlock<T>
+    for FilterBank<T>
+{
+    fn reset(&mut self) {
+        for band in self.bands.iter_mut() {
+            band.memory = band.taps.clone();
+            band.energy = T::zero();
+        }
+    }
+
+    fn process_into(&mut self, input: &ndarray::ArrayView1<T>, out: &mut ndarray::Array1<T>) {
+        let alpha = self.alpha;
+        let beta = T::one() - alpha;
+        for band in self.bands.iter_mut() {
+            band.energies.clear();
+            band.energies.extend(input.iter());
+            sosfilt_in_place(&mut band.energies, &mut band.memory);
+            let mut energy = band.energy;
+            for x in band.energies.iter_mut() {
+                energy = alpha * energy + beta * *x * *x;
+                *x = energy;
+            }
+            band.energy = energy;
+        }
+        match self.output {
+            FilterBankOutput::Band(i) => {
+                for (o, &e) in out.iter_mut().zip(self.bands[i].energies.iter()) {
+                    *o = e;
+                }
+            }
+            FilterBankOutput::Ratio(i, j) => {
+                let energies = self.bands[i].energies.iter();
+                let references = self.bands[j].energies.iter();
+                for (o, (&e, &r)) in out.iter_mut().zip(energies.zip(references)) {
+                    *o = e / Float::max(r, T::min_positive_value());
+                }
+            }
+        }
+    }
+}
+
+pub struct FilterBankBuilder<T> {
+    sample_rate_hz: Option<T>,
+    bands: Vec<(T, T)>,
+    order: Option<usize>,
+    alpha: Option<T>,
+    output: Option<FilterBankOutput>,
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> Default
+    for FilterBankBuilder<T>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> FilterBankBuilder<T> {
+    pub fn new() -> Self {
+        Self {
+            sample_rate_hz: None,
+            bands: Vec::new(),
+            order: None,
+            alpha: None,
+            output: None,
+        }
+    }
+
+    /// Interpret samples as coming in at a sample rate.
+    pub fn sample_rate(mut self, hz: T) -> Self {
+        self.sample_rate_hz.replace(hz);
+        self
+    }
+
+    /// Add a band, from `low_hz` up to `high_hz`. Bands are numbered in
+    /// the order they're added, from zero.
+    pub fn band(mut self, low_hz: T, high_hz: T) -> Self {
+        self.bands.push((low_hz, high_hz));
+        self
+    }
+
+    /// Band-pass filter order.
+    pub fn order(mut self, order: usize) -> Self {
+        self.order.replace(order);
+        self
+    }
+
+    /// Decay rate of the energy smoothing filter.
+    pub fn alpha(mut self, alpha: T) -> Self {
+        self.alpha.replace(alpha);
+        self
+    }
+
+    /// Which band energy, or ratio of band energies, to output.
+    pub fn output(mut self, output: FilterBankOutput) -> Self {
+        self.output.replace(output);
+        self
+    }
+
+    /// Construct a filter bank.
+    pub fn build(self) -> Result<FilterBank<T>, FilterBankError> {
+        if self.bands.is_empty() {
+            return Err(FilterBankError::NoBands);
+        }
+        let output = self.output.unwrap_or_default();
+        let highest = match output {
+            FilterBankOutput::Band(i) => i,
+            FilterBankOutput::Ratio(i, j) => i.max(j),
+        };
+        if highest >= self.bands.len() {
+            return Err(FilterBankError::OutputOutOfRange);
+        }
+        let alpha = self.alpha.unwrap_or(T::from(0.99).unwrap_or(T::zero()));
+        if alpha < T::zero() || alpha > T::one() {
+            return Err(FilterBankError::AlphaOutOfRange);
+        }
+        let sample_rate_hz = self.sample_rate_hz.unwrap_or(T::one());
+        let nyquist_hz = sample_rate_hz / (T::one() + T::one());
+        let order = self.order.unwrap_or(4);
+        let bands = self
+            .bands
+            .into_iter()
+            .map(|(low_hz, high_hz)| {
+                if low_hz <= T::zero() || high_hz >= nyquist_hz || high_hz <= low_hz {
+                    return Err(FilterBankError::BandOutOfRange);
+                }
+                let filter = butter_dyn(
+                    order,
+                    [low_hz, high_hz].to_vec(),
+                    Some(FilterBandType::Bandpass),
+                    Some(false),
+                    Some(FilterOutputType::Sos),
+                    Some(sample_rate_hz),
+                );
+                let DigitalFilter::Sos(SosFormatFilter { sos }) = filter else {
+                    return Err(FilterBankError::FilterFailure);
+                };
+                Ok(Band {
+                    memory: sos.clone(),
+                    taps: sos,
+                    energy: T::zero(),
+                    energies: Vec::new(),
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(FilterBank {
+            bands,
+            alpha,
+            output,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FilterBankBuilder, FilterBankOutput};
+    use crate::signal::SignalBlock;
+    use ndarray::Array1;
+
+    #[test]
+    fn separates_bands() {
+        let rate = 100.0_f32;
+        let tone = |hz: f32| {
+            Array1::from_iter(
+                (0..1000).map(move |i| (2.0 * std::f32::consts::PI * hz * i as f32 / rate).sin()),
+            )
+        };
+        let bank = |output| {
+            FilterBankBuilder::new()
+                .sample_rate(rate)
+                .band(0.7, 2.0)
+                .band(8.0, 20.0)
+                .output(output)
+                .build()
+                .expect("works")
+        };
+
+        // A 1 Hz sinusoid of amplitude 1 has a power of 1/2, nearly all
+        // of it in the low band.
+        let mut low = bank(FilterBankOutput::Band(0));
+        let energy = *low.process(&tone(1.0)).last().unwrap();
+        assert!((energy - 0.5).abs() < 0.1, "{energy}");
+        assert!(low.energies().nth(1).unwrap() < 0.01);
+
+        // Ratios.
+        let mut ratio = bank(FilterBankOutput::Ratio(1, 0));
+        assert!(*ratio.process(&tone(12.0)).last().unwrap() > 100.0);
+        ratio.reset();
+        assert!(*ratio.process(&tone(1.0)).last().unwrap() < 0.01);
+    }
+
+    #[test]
+    fn rejects_missing_output_band() {
+        let result = FilterBankBuilder::<f32>::new()
+            .sample_rate(100.0)
+            .band(1.0, 2.0)
+            .output(FilterBankOutput::Ratio(0, 1))
+            .build();
+        assert!(result.is_err());
+    }
+}
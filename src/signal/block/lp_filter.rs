@@ -8,7 +8,6 @@ use sci_rs::signal::filter::design::FilterBandType;
 use sci_rs::signal::filter::design::FilterOutputType;
 use sci_rs::signal::filter::design::Sos;
 use sci_rs::signal::filter::design::SosFormatFilter;
-use sci_rs::signal::filter::sosfilt_dyn;
 use thiserror::Error;
 
 pub use num_traits::{Float, Zero};
@@ -16,6 +15,8 @@ pub use sci_rs::na::RealField;
 
 use crate::signal::SignalBlock;
 
+use super::super::filter::sosfilt::sosfilt_in_place;
+
 #[derive(Error, Debug)]
 pub enum LPFError {
     #[error("failed to create filter")]
@@ -46,8 +47,10 @@ impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> SignalBlock
         self.memory = self.taps.clone();
     }
 
-    fn process(&mut self, input: &ndarray::Array1<T>) -> ndarray::Array1<T> {
-        ndarray::Array1::from_iter(sosfilt_dyn(input, self.memory.as_mut_slice()))
+    fn process_into(&mut self, input: &ndarray::ArrayView1<T>, out: &mut ndarray::Array1<T>) {
+        out.assign(input);
+        let data = out.as_slice_mut().expect("standard layout");
+        sosfilt_in_place(data, self.memory.as_mut_slice());
     }
 }
 
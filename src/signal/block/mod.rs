@@ -1,4 +1,11 @@
 pub mod affine;
+pub mod band_power;
+pub mod envelope;
+pub mod filter_bank;
 pub mod lp_filter;
+pub mod notch;
 pub mod one_pole;
-pub mod rectify;
\ No newline at end of file
+pub mod rectify;
+pub mod response;
+pub mod rms;
+pub mod taper;
\ No newline at end of file
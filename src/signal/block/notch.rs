@@ -0,0 +1,153 @@
+use std::iter::Sum;
+
+use ndarray::ScalarOperand;
+use num_traits::One;
+use sci_rs::signal::filter::design::Sos;
+use thiserror::Error;
+
+pub use num_traits::{Float, Zero};
+pub use sci_rs::na::RealField;
+
+use crate::signal::SignalBlock;
+
+use super::super::filter::sosfilt::sosfilt_in_place;
+
+#[derive(Error, Debug)]
+pub enum NotchError {
+    #[error("notch frequency must be between zero and the Nyquist frequency")]
+    FrequencyOutOfRange,
+    #[error("notch Q must be positive")]
+    QOutOfRange,
+}
+
+/// Second-order notch (band-stop) filter, for removing a single
+/// interfering frequency such as mains hum.
+pub struct NotchFilter<T>
+where
+    T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand,
+{
+    taps: Sos<T>,
+    memory: Sos<T>,
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> SignalBlock<T>
+    for NotchFilter<T>
+{
+    fn reset(&mut self) {
+        self.memory = self.taps;
+    }
+
+    fn process_into(&mut self, input: &ndarray::ArrayView1<T>, out: &mut ndarray::Array1<T>) {
+        out.assign(input);
+        let data = out.as_slice_mut().expect("standard layout");
+        sosfilt_in_place(data, std::slice::from_mut(&mut self.memory));
+    }
+}
+
+pub struct NotchFilterBuilder<T> {
+    sample_rate_hz: Option<T>,
+    center_hz: Option<T>,
+    q: Option<T>,
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> Default
+    for NotchFilterBuilder<T>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> NotchFilterBuilder<T> {
+    pub fn new() -> Self {
+        Self {
+            sample_rate_hz: None,
+            center_hz: None,
+            q: None,
+        }
+    }
+
+    /// Interpret samples as coming in at a sample rate.
+    pub fn sample_rate(mut self, hz: T) -> Self {
+        self.sample_rate_hz.replace(hz);
+        self
+    }
+
+    /// The frequency to remove.
+    pub fn center_hz(mut self, hz: T) -> Self {
+        self.center_hz.replace(hz);
+        self
+    }
+
+    /// Quality factor: the center frequency divided by the width of the
+    /// stop band. Higher values give a narrower notch.
+    pub fn q(mut self, q: T) -> Self {
+        self.q.replace(q);
+        self
+    }
+
+    /// Construct a notch filter block.
+    pub fn build(self) -> Result<NotchFilter<T>, NotchError> {
+        let two = T::one() + T::one();
+        let sample_rate_hz = self.sample_rate_hz.unwrap_or(two);
+        let center_hz = self.center_hz.unwrap_or(T::one() / two);
+        let q = self.q.unwrap_or(T::one());
+        if center_hz <= T::zero() || center_hz >= sample_rate_hz / two {
+            return Err(NotchError::FrequencyOutOfRange);
+        }
+        if q <= T::zero() {
+            return Err(NotchError::QOutOfRange);
+        }
+
+        // The notch biquad from the "Audio EQ Cookbook", normalized so
+        // that a0 is one.
+        let w0 = two * <T as RealField>::pi() * center_hz / sample_rate_hz;
+        let cos_w0 = Float::cos(w0);
+        let alpha = Float::sin(w0) / (two * q);
+        let a0 = T::one() + alpha;
+        let b = [T::one() / a0, -two * cos_w0 / a0, T::one() / a0];
+        let a = [T::one(), -two * cos_w0 / a0, (T::one() - alpha) / a0];
+        let taps = Sos::new(b, a);
+        let mut result = NotchFilter { taps, memory: taps };
+        result.reset();
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NotchError, NotchFilterBuilder};
+    use crate::signal::SignalBlock;
+
+    #[test]
+    fn removes_center_frequency() {
+        let mut notch = NotchFilterBuilder::new()
+            .sample_rate(200.0_f32)
+            .center_hz(60.0)
+            .q(10.0)
+            .build()
+            .expect("works");
+        let tone = |hz: f32| {
+            ndarray::Array1::from_iter(
+                (0..2000).map(|i| (2.0 * std::f32::consts::PI * hz * i as f32 / 200.0).sin()),
+            )
+        };
+        let peak =
+            |out: ndarray::Array1<f32>| out.iter().skip(1000).fold(0.0_f32, |m, v| m.max(v.abs()));
+
+        assert!(peak(notch.process(&tone(60.0))) < 0.01);
+        notch.reset();
+        assert!(peak(notch.process(&tone(5.0))) > 0.95);
+    }
+
+    #[test]
+    fn test_fails() {
+        let err = NotchFilterBuilder::new()
+            .sample_rate(100.0_f32)
+            .center_hz(60.0)
+            .build()
+            .err()
+            .unwrap_or_else(|| panic!("expecting an error"));
+        assert!(matches!(err, NotchError::FrequencyOutOfRange));
+    }
+}
@@ -9,7 +9,7 @@ pub use sci_rs::na::RealField;
 
 use crate::signal::SignalBlock;
 
-use super::super::filter::lfilter::{lfilt_dyn, Ba};
+use super::super::filter::lfilter::{lfilt_in_place, Ba};
 
 #[derive(Clone, Copy, Default)]
 pub enum FilterType {
@@ -48,11 +48,13 @@ impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> SignalBlock
     for OnePoleFilter<T>
 {
     fn reset(&mut self) {
-        self.memory = self.taps.clone();
+        self.memory = self.taps;
     }
 
-    fn process(&mut self, input: &ndarray::Array1<T>) -> ndarray::Array1<T> {
-        ndarray::Array1::from_iter(lfilt_dyn(input, &mut self.memory))
+    fn process_into(&mut self, input: &ndarray::ArrayView1<T>, out: &mut ndarray::Array1<T>) {
+        out.assign(input);
+        let data = out.as_slice_mut().expect("standard layout");
+        lfilt_in_place(data, &mut self.memory);
     }
 }
 
@@ -116,9 +118,9 @@ impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> OnePoleFilt
         }
         let b = [b0, b1];
         let zi0 = T::zero();
-        let ba = Ba { b, a1: a1, zi0 };
+        let ba = Ba { b, a1, zi0 };
         let mut result = OnePoleFilter {
-            taps: ba.clone(),
+            taps: ba,
             memory: ba,
         };
         result.reset();
@@ -133,7 +135,7 @@ mod tests {
     #[test]
     fn test_one() {
         OnePoleFilterBuilder::new()
-            .alpha(0.99 as f32)
+            .alpha(0.99_f32)
             .pass(super::FilterType::LowPass)
             .build()
             .expect("works");
@@ -142,7 +144,7 @@ mod tests {
     #[test]
     fn test_fails() {
         let err = OnePoleFilterBuilder::new()
-            .alpha(2.0 as f32)
+            .alpha(2.0_f32)
             .pass(super::FilterType::HighPass)
             .build()
             .err()
@@ -39,10 +39,10 @@ pub struct Rectify {
 impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> SignalBlock<T> for Rectify {
     fn reset(&mut self) {}
 
-    fn process(&mut self, input: &ndarray::Array1<T>) -> ndarray::Array1<T> {
+    fn process_into(&mut self, input: &ndarray::ArrayView1<T>, out: &mut ndarray::Array1<T>) {
         match self.rectify_type {
-            RectifyType::Square => input.pow2(),
-            RectifyType::Absolute => input.abs(),
+            RectifyType::Square => out.zip_mut_with(input, |o, &x| *o = x * x),
+            RectifyType::Absolute => out.zip_mut_with(input, |o, &x| *o = Float::abs(x)),
         }
     }
 }
@@ -0,0 +1,266 @@
+use std::iter::Sum;
+
+use ndarray::ScalarOperand;
+use num_traits::One;
+use sci_rs::na::Complex;
+use sci_rs::signal::filter::design::bilinear_zpk_dyn;
+use sci_rs::signal::filter::design::zpk2sos_dyn;
+use sci_rs::signal::filter::design::Sos;
+use sci_rs::signal::filter::design::ZpkFormatFilter;
+use thiserror::Error;
+
+pub use num_traits::{Float, Zero};
+pub use sci_rs::na::RealField;
+
+use crate::signal::SignalBlock;
+
+use super::super::filter::sosfilt::sosfilt_in_place;
+
+#[derive(Error, Debug)]
+pub enum ResponseError {
+    #[error("complex poles and zeros must come in conjugate pairs")]
+    UnpairedRoot,
+    #[error("sensitivity and normalization must be non-zero")]
+    ZeroGain,
+    #[error("corner frequencies must lie between zero and the Nyquist frequency")]
+    CornerOutOfRange,
+}
+
+/// Signal processing block which removes an instrument's response, as
+/// described by the poles and zeros of its transfer function (in
+/// radians per second), its normalization factor and its sensitivity
+/// (in counts per unit of ground motion), so that its output is in true
+/// ground velocity or acceleration.
+///
+/// Exactly inverting the response would amplify frequencies the
+/// instrument barely records without bound, so the inverse is limited:
+/// zeros slower than a low corner frequency (such as those at the
+/// origin) are moved to it, which high-passes the output there, and if
+/// the response has more poles than zeros, the output is low-passed at a
+/// high corner frequency.
+pub struct ResponseRemoval<T>
+where
+    T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand,
+{
+    taps: Vec<Sos<T>>,
+    memory: Vec<Sos<T>>,
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> SignalBlock<T>
+    for ResponseRemoval<T>
+{
+    fn reset(&mut self) {
+        self.memory = self.taps.clone();
+    }
+
+    fn process_into(&mut self, input: &ndarray::ArrayView1<T>, out: &mut ndarray::Array1<T>) {
+        out.assign(input);
+        let data = out.as_slice_mut().expect("standard layout");
+        sosfilt_in_place(data, self.memory.as_mut_slice());
+    }
+}
+
+pub struct ResponseRemovalBuilder<T> {
+    sample_rate_hz: Option<T>,
+    zeros: Vec<(T, T)>,
+    poles: Vec<(T, T)>,
+    normalization: Option<T>,
+    sensitivity: Option<T>,
+    low_corner_hz: Option<T>,
+    high_corner_hz: Option<T>,
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> Default
+    for ResponseRemovalBuilder<T>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> ResponseRemovalBuilder<T> {
+    pub fn new() -> Self {
+        Self {
+            sample_rate_hz: None,
+            zeros: Vec::new(),
+            poles: Vec::new(),
+            normalization: None,
+            sensitivity: None,
+            low_corner_hz: None,
+            high_corner_hz: None,
+        }
+    }
+
+    /// Interpret samples as coming in at a sample rate.
+    pub fn sample_rate(mut self, hz: T) -> Self {
+        self.sample_rate_hz.replace(hz);
+        self
+    }
+
+    /// Add a zero of the instrument's transfer function, in radians per
+    /// second.
+    pub fn zero(mut self, re: T, im: T) -> Self {
+        self.zeros.push((re, im));
+        self
+    }
+
+    /// Add a pole of the instrument's transfer function, in radians per
+    /// second.
+    pub fn pole(mut self, re: T, im: T) -> Self {
+        self.poles.push((re, im));
+        self
+    }
+
+    /// Factor normalizing the poles and zeros' response to one at the
+    /// instrument's reference frequency ("A0").
+    pub fn normalization(mut self, a0: T) -> Self {
+        self.normalization.replace(a0);
+        self
+    }
+
+    /// Counts output per unit of ground motion, at the reference
+    /// frequency.
+    pub fn sensitivity(mut self, counts: T) -> Self {
+        self.sensitivity.replace(counts);
+        self
+    }
+
+    /// Frequency below which the output is high-passed.
+    pub fn low_corner_hz(mut self, hz: T) -> Self {
+        self.low_corner_hz.replace(hz);
+        self
+    }
+
+    /// Frequency above which the output is low-passed, if the response
+    /// has more poles than zeros.
+    pub fn high_corner_hz(mut self, hz: T) -> Self {
+        self.high_corner_hz.replace(hz);
+        self
+    }
+
+    /// Construct a response removal block.
+    pub fn build(self) -> Result<ResponseRemoval<T>, ResponseError> {
+        let f64_of = |v: T| v.to_f64().unwrap_or(0.0);
+        let roots = |roots: &[(T, T)]| -> Result<Vec<Complex<f64>>, ResponseError> {
+            let roots: Vec<Complex<f64>> = roots
+                .iter()
+                .map(|&(re, im)| Complex::new(f64_of(re), f64_of(im)))
+                .collect();
+            let paired = roots
+                .iter()
+                .all(|r| r.im == 0.0 || roots.iter().any(|s| *s == r.conj()));
+            if !paired {
+                return Err(ResponseError::UnpairedRoot);
+            }
+            Ok(roots)
+        };
+        let zeros = roots(&self.zeros)?;
+        let poles = roots(&self.poles)?;
+
+        let gain = self.sensitivity.map_or(1.0, f64_of) * self.normalization.map_or(1.0, f64_of);
+        if gain == 0.0 {
+            return Err(ResponseError::ZeroGain);
+        }
+        let sample_rate_hz = self.sample_rate_hz.map_or(1.0, f64_of);
+        let nyquist_hz = sample_rate_hz / 2.0;
+        let low_corner_hz = self.low_corner_hz.map_or(0.1, f64_of);
+        let high_corner_hz = self.high_corner_hz.map_or(0.8 * nyquist_hz, f64_of);
+        if low_corner_hz <= 0.0 || high_corner_hz >= nyquist_hz || high_corner_hz <= low_corner_hz {
+            return Err(ResponseError::CornerOutOfRange);
+        }
+        let low_corner = 2.0 * std::f64::consts::PI * low_corner_hz;
+        let high_corner = 2.0 * std::f64::consts::PI * high_corner_hz;
+
+        // The inverse's poles are the response's zeros, and its zeros the
+        // response's poles. Zeros in the right half-plane are reflected
+        // into the left (which leaves the magnitude response alone), so
+        // that the inverse is stable. A zero of the response at (or near)
+        // the origin would be an integrator in the inverse; moving it to
+        // the low corner leaves the gain above the corner as it was.
+        let mut inverse_poles: Vec<Complex<f64>> = zeros
+            .iter()
+            .map(|z| {
+                if z.norm() < low_corner {
+                    Complex::new(-low_corner, 0.0)
+                } else {
+                    Complex::new(-z.re.abs(), z.im)
+                }
+            })
+            .collect();
+        let inverse_zeros = poles;
+        let mut inverse_gain = 1.0 / gain;
+
+        // Each added pole is compensated for, to leave the gain below the
+        // high corner as it was.
+        while inverse_poles.len() < inverse_zeros.len() {
+            inverse_poles.push(Complex::new(-high_corner, 0.0));
+            inverse_gain *= high_corner;
+        }
+
+        let zpk = ZpkFormatFilter::new(inverse_zeros, inverse_poles, inverse_gain);
+        let digital = bilinear_zpk_dyn(zpk, sample_rate_hz);
+        let order = digital.p.len();
+        let sos = zpk2sos_dyn(order, digital, None, Some(false)).sos;
+        let to_t = |v: f64| T::from(v).unwrap_or(T::zero());
+        let taps: Vec<Sos<T>> = sos
+            .iter()
+            .map(|s| Sos::new(s.b.map(to_t), s.a.map(to_t)))
+            .collect();
+        let mut result = ResponseRemoval {
+            memory: taps.clone(),
+            taps,
+        };
+        result.reset();
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ResponseRemovalBuilder;
+    use crate::signal::SignalBlock;
+    use ndarray::Array1;
+
+    #[test]
+    fn removes_geophone_response() {
+        // A 4.5 Hz geophone, damped at 0.7 of critical, with a
+        // sensitivity of 1000 counts per m/s.
+        let rate = 100.0_f64;
+        let w0 = 2.0 * std::f64::consts::PI * 4.5;
+        let (re, im) = (-0.7 * w0, w0 * (1.0_f64 - 0.49).sqrt());
+        let mut response = ResponseRemovalBuilder::new()
+            .sample_rate(rate)
+            .zero(0.0, 0.0)
+            .zero(0.0, 0.0)
+            .pole(re, im)
+            .pole(re, -im)
+            .sensitivity(1000.0)
+            .build()
+            .expect("works");
+
+        // Counts recorded for ground motion at 2 Hz, below the corner,
+        // where the geophone's response has fallen off.
+        let w = 2.0 * std::f64::consts::PI * 2.0;
+        let gain = w * w / ((w0 * w0 - w * w).powi(2) + (2.0 * 0.7 * w0 * w).powi(2)).sqrt();
+        let counts =
+            Array1::from_iter((0..3000).map(|i| 1000.0 * gain * (w * i as f64 / rate).sin()));
+
+        // Once the startup transient has died away, the ground velocity
+        // was 1 m/s.
+        let velocity = response.process(&counts);
+        let peak = velocity
+            .iter()
+            .skip(2800)
+            .fold(0.0_f64, |peak, v| peak.max(v.abs()));
+        assert!((peak - 1.0).abs() < 0.05, "{peak}");
+    }
+
+    #[test]
+    fn rejects_unpaired_poles() {
+        let result = ResponseRemovalBuilder::<f32>::new()
+            .sample_rate(100.0)
+            .pole(-1.0, 1.0)
+            .build();
+        assert!(result.is_err());
+    }
+}
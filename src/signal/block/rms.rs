@@ -0,0 +1,137 @@
+use std::collections::VecDeque;
+use std::iter::Sum;
+
+use ndarray::ScalarOperand;
+use num_traits::One;
+use thiserror::Error;
+
+pub use num_traits::{Float, Zero};
+pub use sci_rs::na::RealField;
+
+use crate::signal::SignalBlock;
+
+#[derive(Error, Debug)]
+pub enum RmsError {
+    #[error("RMS window must be at least one sample long")]
+    WindowTooShort,
+}
+
+/// Running RMS over a sliding window.
+///
+/// Unlike a one-pole smoother of the squared signal, it forgets a
+/// transient entirely once the transient has left the window. (Until
+/// the window first fills, the RMS is of the samples seen so far.)
+pub struct RunningRms<T>
+where
+    T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand,
+{
+    /// Recent squared samples, and their sum. The sum is kept in double
+    /// precision so that adding and removing samples doesn't accumulate
+    /// error.
+    window: VecDeque<f64>,
+    window_len: usize,
+    sum: f64,
+    _phantom: std::marker::PhantomData<T>,
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> SignalBlock<T>
+    for RunningRms<T>
+{
+    fn reset(&mut self) {
+        self.window.clear();
+        self.sum = 0.0;
+    }
+
+    fn process_into(&mut self, input: &ndarray::ArrayView1<T>, out: &mut ndarray::Array1<T>) {
+        out.zip_mut_with(input, |o, &x| {
+            if self.window.len() == self.window_len {
+                if let Some(old) = self.window.pop_front() {
+                    self.sum -= old;
+                }
+            }
+            let x = x.to_f64().unwrap_or(0.0);
+            self.window.push_back(x * x);
+            self.sum += x * x;
+            let mean = self.sum.max(0.0) / self.window.len() as f64;
+            *o = T::from(mean.sqrt()).unwrap_or(T::zero())
+        });
+    }
+}
+
+pub struct RunningRmsBuilder<T> {
+    sample_rate_hz: Option<T>,
+    window_s: Option<T>,
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> Default
+    for RunningRmsBuilder<T>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> RunningRmsBuilder<T> {
+    pub fn new() -> Self {
+        Self {
+            sample_rate_hz: None,
+            window_s: None,
+        }
+    }
+
+    /// Interpret samples as coming in at a sample rate.
+    pub fn sample_rate(mut self, hz: T) -> Self {
+        self.sample_rate_hz.replace(hz);
+        self
+    }
+
+    /// Length of the window, in seconds.
+    pub fn window_s(mut self, s: T) -> Self {
+        self.window_s.replace(s);
+        self
+    }
+
+    /// Construct a running RMS block.
+    pub fn build(self) -> Result<RunningRms<T>, RmsError> {
+        let sample_rate_hz = self.sample_rate_hz.unwrap_or(T::one());
+        let window_s = self.window_s.unwrap_or(T::one());
+        let window_len = Float::round(window_s * sample_rate_hz)
+            .to_usize()
+            .unwrap_or(0);
+        if window_len == 0 {
+            return Err(RmsError::WindowTooShort);
+        }
+        let result = RunningRms {
+            window: VecDeque::with_capacity(window_len),
+            window_len,
+            sum: 0.0,
+            _phantom: std::marker::PhantomData,
+        };
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RunningRmsBuilder;
+    use crate::signal::SignalBlock;
+    use ndarray::Array1;
+
+    #[test]
+    fn forgets_transients() {
+        let mut rms = RunningRmsBuilder::new()
+            .sample_rate(10.0_f32)
+            .window_s(1.0)
+            .build()
+            .expect("works");
+        let quiet = Array1::from_iter((0..20).map(|i| if i % 2 == 0 { 1.0 } else { -1.0 }));
+        assert_eq!(*rms.process(&quiet).last().unwrap(), 1.0);
+
+        // A spike raises the RMS for exactly one window.
+        let output = rms.process(&Array1::from_elem(1, 9.0));
+        assert_eq!(output[0], 3.0);
+        let output = rms.process(&quiet);
+        assert_eq!(output[8], 3.0);
+        assert_eq!(output[9], 1.0);
+    }
+}
@@ -0,0 +1,171 @@
+use std::iter::Sum;
+
+use ndarray::ScalarOperand;
+use num_traits::One;
+use thiserror::Error;
+
+pub use num_traits::{Float, Zero};
+pub use sci_rs::na::RealField;
+
+use crate::signal::SignalBlock;
+
+#[derive(Error, Debug)]
+pub enum TaperError {
+    #[error("taper chunks must be at least two samples long")]
+    ChunkTooShort,
+    #[error("Tukey alpha is out of range (0-1)")]
+    AlphaOutOfRange,
+}
+
+/// Shape of a taper window.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TaperType<T> {
+    /// Raised cosine across the whole chunk.
+    Hann,
+    /// Flat, with raised cosine edges which together take up some
+    /// fraction (alpha) of the chunk. An alpha of one is a Hann window,
+    /// and of zero, no taper at all.
+    Tukey(T),
+}
+
+/// Signal processing block which divides its input into consecutive
+/// chunks of a fixed length (regardless of how it is packetized), and
+/// multiplies each by a window which tapers to zero at its edges. Blocks
+/// which analyze their input chunk by chunk may then do so without the
+/// artifacts that the chunks' abrupt edges would cause.
+pub struct Taper<T>
+where
+    T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand,
+{
+    window: Vec<T>,
+    /// Position within the current chunk.
+    position: usize,
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> SignalBlock<T> for Taper<T> {
+    fn reset(&mut self) {
+        self.position = 0;
+    }
+
+    fn process_into(&mut self, input: &ndarray::ArrayView1<T>, out: &mut ndarray::Array1<T>) {
+        out.zip_mut_with(input, |o, &x| {
+            *o = x * self.window[self.position];
+            self.position = (self.position + 1) % self.window.len();
+        });
+    }
+}
+
+pub struct TaperBuilder<T> {
+    sample_rate_hz: Option<T>,
+    chunk_s: Option<T>,
+    window: Option<TaperType<T>>,
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> Default for TaperBuilder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> TaperBuilder<T> {
+    pub fn new() -> Self {
+        Self {
+            sample_rate_hz: None,
+            chunk_s: None,
+            window: None,
+        }
+    }
+
+    /// Interpret samples as coming in at a sample rate.
+    pub fn sample_rate(mut self, hz: T) -> Self {
+        self.sample_rate_hz.replace(hz);
+        self
+    }
+
+    /// Length of each chunk, in seconds.
+    pub fn chunk_s(mut self, s: T) -> Self {
+        self.chunk_s.replace(s);
+        self
+    }
+
+    /// Shape of the window.
+    pub fn window(mut self, window: TaperType<T>) -> Self {
+        self.window.replace(window);
+        self
+    }
+
+    /// Construct a taper.
+    pub fn build(self) -> Result<Taper<T>, TaperError> {
+        let sample_rate_hz = self.sample_rate_hz.unwrap_or(T::one());
+        let chunk_s = self.chunk_s.unwrap_or(T::one());
+        let len = Float::round(chunk_s * sample_rate_hz)
+            .to_usize()
+            .unwrap_or(0);
+        if len < 2 {
+            return Err(TaperError::ChunkTooShort);
+        }
+        let alpha = match self.window.unwrap_or(TaperType::Hann) {
+            TaperType::Hann => T::one(),
+            TaperType::Tukey(alpha) => alpha,
+        };
+        if alpha < T::zero() || alpha > T::one() {
+            return Err(TaperError::AlphaOutOfRange);
+        }
+
+        // Each edge is a half-period of a raised cosine, spanning half of
+        // alpha's fraction of the chunk.
+        let half = T::one() / (T::one() + T::one());
+        let span = T::from(len - 1).unwrap_or(T::one());
+        let edge = alpha * span * half;
+        let window = (0..len)
+            .map(|i| {
+                let from_edge = T::from(i.min(len - 1 - i)).unwrap_or(T::zero());
+                if from_edge >= edge {
+                    T::one()
+                } else {
+                    half - half * Float::cos(<T as RealField>::pi() * from_edge / edge)
+                }
+            })
+            .collect();
+        let result = Taper {
+            window,
+            position: 0,
+        };
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TaperBuilder, TaperType};
+    use crate::signal::SignalBlock;
+    use ndarray::Array1;
+
+    #[test]
+    fn tapers_chunks() {
+        let mut hann = TaperBuilder::new()
+            .sample_rate(10.0_f64)
+            .chunk_s(0.5)
+            .build()
+            .expect("works");
+        let ones = Array1::from_elem(3, 1.0);
+        let mut output = hann.process(&ones).to_vec();
+        output.extend(hann.process(&ones));
+        let expected = [0.0, 0.5, 1.0, 0.5, 0.0, 0.0];
+        for (o, e) in output.iter().zip(expected) {
+            assert!((o - e).abs() < 1e-12, "{output:?}");
+        }
+
+        let mut tukey = TaperBuilder::new()
+            .sample_rate(10.0_f64)
+            .chunk_s(1.0)
+            .window(TaperType::Tukey(0.5))
+            .build()
+            .expect("works");
+        let output = tukey.process(&Array1::from_elem(10, 1.0));
+        assert_eq!(output[0], 0.0);
+        assert!(output[1] > 0.0 && output[1] < 1.0);
+        assert_eq!(output.slice(ndarray::s![3..7]).sum(), 4.0);
+        assert_eq!(output[9], 0.0);
+    }
+}
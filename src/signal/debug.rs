@@ -1,6 +1,7 @@
 use ndarray::Array1;
 use num_traits::Float;
-use std::path::Path;
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
 use std::{fmt::Display, fs::File, io::Write};
 use thiserror::Error;
 
@@ -10,7 +11,7 @@ pub enum ObserverError {
     DumpFileError(#[from] std::io::Error),
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FilterStep {
     Input,
     Affined,
@@ -25,8 +26,26 @@ pub enum FilterObserver<T> {
 }
 
 impl<T: Float + Display> FilterObserver<T> {
-    pub fn new_channel_dumper(path: &Path) -> Result<FilterObserver<T>, ObserverError> {
-        let c = ChannelDumper::new(path)?;
+    /// A dumper of samples at a rate, timed by the data time of each
+    /// packet.
+    pub fn new_channel_dumper(
+        path: &Path,
+        sample_rate_hz: f32,
+    ) -> Result<FilterObserver<T>, ObserverError> {
+        let c = ChannelDumper::new(path, sample_rate_hz)?;
+        Ok(FilterObserver::ChannelDumper(Box::new(c)))
+    }
+
+    /// A dumper which appends to its file, as CSV (after a header line)
+    /// if asked, and rotates it once it grows past a size, if given one.
+    pub fn new_appending_dumper(
+        path: &Path,
+        sample_rate_hz: f32,
+        csv: bool,
+        max_size: Option<u64>,
+        keep: usize,
+    ) -> Result<FilterObserver<T>, ObserverError> {
+        let c = ChannelDumper::appending(path, sample_rate_hz, csv, max_size, keep)?;
         Ok(FilterObserver::ChannelDumper(Box::new(c)))
     }
 
@@ -34,16 +53,42 @@ impl<T: Float + Display> FilterObserver<T> {
         Ok(FilterObserver::NullObserver)
     }
 
-    pub fn observe(&mut self, step: FilterStep, n: usize, input: &ndarray::Array1<T>) {
-        match self {
-            Self::NullObserver => (),
-            Self::ChannelDumper(d) => d.observe(step, n, input),
+    /// Whether observations are recorded at all, so that callers may
+    /// skip preparing them.
+    pub fn is_active(&self) -> bool {
+        !matches!(self, Self::NullObserver)
+    }
+
+    /// Note the data time of the first sample of the packet about to be
+    /// observed.
+    pub fn start_packet(&mut self, timestamp: f64) {
+        if let Self::ChannelDumper(d) = self {
+            d.start_s = timestamp;
+        }
+    }
+
+    /// Record a step of the packet's processing. Should the dump fail to
+    /// be written, it is logged and given up.
+    pub fn observe(&mut self, step: FilterStep, input: &ndarray::Array1<T>) {
+        let Self::ChannelDumper(d) = self else {
+            return;
+        };
+        if let Err(e) = d.observe(step, input) {
+            log::error!("can't dump to {}, no longer dumping: {e}", d.path.display());
+            *self = Self::NullObserver;
         }
     }
 }
 
 pub struct ChannelDumper<T> {
     f: File,
+    path: PathBuf,
+    csv: bool,
+    sample_period_s: f64,
+
+    /// The data time of the first sample of the packet being observed.
+    start_s: f64,
+    rotation: Option<Rotation>,
     input: ndarray::Array1<T>,
     affine: ndarray::Array1<T>,
     filtered: ndarray::Array1<T>,
@@ -51,17 +96,63 @@ pub struct ChannelDumper<T> {
     energy: ndarray::Array1<T>,
 }
 
+/// When a dump file is rotated, how many rotated files are kept, and how
+/// big the file now is.
+struct Rotation {
+    max_size: u64,
+    keep: usize,
+    size: u64,
+}
+
+const CSV_HEADER: &str = "time,input,affined,filtered,dc_removed,energy\n";
+
 impl<T: Float> ChannelDumper<T> {
-    pub fn new(path: &Path) -> Result<ChannelDumper<T>, ObserverError> {
+    pub fn new(path: &Path, sample_rate_hz: f32) -> Result<ChannelDumper<T>, ObserverError> {
         let fh = std::fs::File::create(path)?;
-        Ok(ChannelDumper {
-            f: fh,
+        Ok(Self::writing_to(fh, path, sample_rate_hz, false, None))
+    }
+
+    fn appending(
+        path: &Path,
+        sample_rate_hz: f32,
+        csv: bool,
+        max_size: Option<u64>,
+        keep: usize,
+    ) -> Result<ChannelDumper<T>, ObserverError> {
+        let mut fh = OpenOptions::new().create(true).append(true).open(path)?;
+        let mut size = fh.metadata()?.len();
+        if csv && size == 0 {
+            fh.write_all(CSV_HEADER.as_bytes())?;
+            size = CSV_HEADER.len() as u64;
+        }
+        let rotation = max_size.map(|max_size| Rotation {
+            max_size,
+            keep,
+            size,
+        });
+        Ok(Self::writing_to(fh, path, sample_rate_hz, csv, rotation))
+    }
+
+    fn writing_to(
+        f: File,
+        path: &Path,
+        sample_rate_hz: f32,
+        csv: bool,
+        rotation: Option<Rotation>,
+    ) -> Self {
+        ChannelDumper {
+            f,
+            path: path.to_owned(),
+            csv,
+            sample_period_s: 1.0 / f64::from(sample_rate_hz),
+            start_s: 0.0,
+            rotation,
             input: Array1::<T>::from_vec(vec![]),
             affine: Array1::<T>::from_vec(vec![]),
             filtered: Array1::<T>::from_vec(vec![]),
             dc_removed: Array1::<T>::from_vec(vec![]),
             energy: Array1::<T>::from_vec(vec![]),
-        })
+        }
     }
 
     fn check_all_received(&self, n: usize) {
@@ -77,7 +168,7 @@ impl<T: Float> ChannelDumper<T> {
 }
 
 impl<T: Float + Display> ChannelDumper<T> {
-    fn observe(&mut self, step: FilterStep, n: usize, input: &ndarray::Array1<T>) {
+    fn observe(&mut self, step: FilterStep, input: &ndarray::Array1<T>) -> std::io::Result<()> {
         match step {
             FilterStep::Input => self.input = input.clone(),
             FilterStep::Affined => self.affine = input.clone(),
@@ -87,17 +178,123 @@ impl<T: Float + Display> ChannelDumper<T> {
                 self.energy = input.clone();
                 self.check_all_received(self.energy.len());
 
+                let separator = if self.csv { "," } else { " " };
+                let mut lines = String::new();
                 for i in 0..self.input.len() {
-                    let off: f32 = ((n + i) as f32) / 100.0;
-                    let inp = self.input[i];
-                    let aff = self.affine[i];
-                    let fil = self.filtered[i];
-                    let dc = self.dc_removed[i];
-                    let energy = self.energy[i];
-                    writeln!(self.f, "{off} {inp} {aff} {fil} {dc} {energy}")
-                        .expect("can't dump to file");
+                    let time_s = self.start_s + i as f64 * self.sample_period_s;
+                    let columns = [
+                        self.input[i],
+                        self.affine[i],
+                        self.filtered[i],
+                        self.dc_removed[i],
+                        self.energy[i],
+                    ];
+                    lines.push_str(&format!("{time_s:.6}"));
+                    for column in columns {
+                        lines.push_str(separator);
+                        lines.push_str(&column.to_string());
+                    }
+                    lines.push('\n');
                 }
+                self.f.write_all(lines.as_bytes())?;
+                if let Some(rotation) = self.rotation.as_mut() {
+                    rotation.size += lines.len() as u64;
+                    if rotation.size >= rotation.max_size {
+                        if let Err(e) = self.rotate() {
+                            log::warn!("can't rotate dump {}: {e}", self.path.display());
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Shift each rotated file along one, the oldest falling off the end,
+    /// and the current file into first place, and start another.
+    fn rotate(&mut self) -> std::io::Result<()> {
+        let Some(rotation) = self.rotation.as_mut() else {
+            return Ok(());
+        };
+        if rotation.keep == 0 {
+            std::fs::remove_file(&self.path)?;
+        } else {
+            for n in (1..rotation.keep).rev() {
+                match std::fs::rename(rotated(&self.path, n), rotated(&self.path, n + 1)) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+                    _ => (),
+                }
+            }
+            std::fs::rename(&self.path, rotated(&self.path, 1))?;
+        }
+        self.f = File::create(&self.path)?;
+        rotation.size = 0;
+        if self.csv {
+            self.f.write_all(CSV_HEADER.as_bytes())?;
+            rotation.size = CSV_HEADER.len() as u64;
+        }
+        Ok(())
+    }
+}
+
+fn rotated(path: &Path, n: usize) -> PathBuf {
+    let mut path = path.to_owned().into_os_string();
+    path.push(format!(".{n}"));
+    PathBuf::from(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_rotates_csv_dumps() {
+        let dir = std::env::temp_dir().join(format!("rs-udp-dump-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("create dir");
+        let path = dir.join("flow.csv");
+        let mut dumper =
+            ChannelDumper::<f32>::appending(&path, 100.0, true, Some(140), 2).expect("opens");
+        let samples = Array1::from_vec(vec![1.0, 2.0, 3.0]);
+        for n in 0..4 {
+            dumper.start_s = 1000.0 + n as f64 * 0.03;
+            for step in [
+                FilterStep::Input,
+                FilterStep::Affined,
+                FilterStep::Filtered,
+                FilterStep::DCRemove,
+                FilterStep::Energy,
+            ] {
+                dumper.observe(step, &samples).expect("writes");
             }
         }
+        let current = std::fs::read_to_string(&path).expect("current");
+        let first = std::fs::read_to_string(rotated(&path, 2)).expect("rotated");
+        std::fs::remove_dir_all(&dir).expect("remove dir");
+        // Every second set of samples takes the file past its limit: the
+        // first two sets are rotated out twice, the next two once, and the
+        // file starts over.
+        assert_eq!(current, CSV_HEADER);
+        assert!(first.starts_with(&format!(
+            "{CSV_HEADER}1000.000000,1,1,1,1,1\n1000.010000,2,2,2,2,2\n"
+        )));
+        assert!(first.ends_with("1000.050000,3,3,3,3,3\n"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn it_gives_up_failed_dumps() {
+        let mut observer = FilterObserver::<f32>::new_channel_dumper(Path::new("/dev/full"), 100.0)
+            .expect("opens");
+        let samples = Array1::from_vec(vec![1.0; 1000]);
+        for step in [
+            FilterStep::Input,
+            FilterStep::Affined,
+            FilterStep::Filtered,
+            FilterStep::DCRemove,
+            FilterStep::Energy,
+        ] {
+            observer.observe(step, &samples);
+        }
+        assert!(!observer.is_active());
     }
 }
@@ -0,0 +1,244 @@
+use std::collections::VecDeque;
+use std::iter::Sum;
+
+use super::super::{Event, EventBlock};
+use super::latch::TriggerLatch;
+use ndarray::ScalarOperand;
+use num_traits::One;
+use thiserror::Error;
+
+pub use num_traits::{Float, Zero};
+pub use sci_rs::na::RealField;
+
+#[derive(Error, Debug)]
+pub enum AdaptiveError {
+    #[error("trigger ratio is lower than reset ratio")]
+    ThresholdError,
+    #[error("noise floor window must be at least one second long")]
+    WindowTooShort,
+}
+
+/// Signal processing block that judges whether a signal has gone above
+/// or below some multiple of its own noise floor.
+///
+/// The noise floor is the median, over a long window, of the signal's
+/// mean level in each second. It isn't updated while triggered, so that
+/// events don't raise it.
+pub struct AdaptiveTrigger<T>
+where
+    T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand,
+{
+    trigger_ratio: T,
+    reset_ratio: T,
+    holdoff: usize,
+
+    /// When the trigger asserts and resets.
+    latch: TriggerLatch,
+
+    /// Samples per second, over which each mean level is taken.
+    block_len: usize,
+    block_sum: T,
+    block_count: usize,
+
+    /// Recent mean levels, and the most the window holds.
+    levels: VecDeque<T>,
+    window_len: usize,
+    noise_floor: Option<T>,
+
+    /// Number of samples processed so far.
+    processed: usize,
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> AdaptiveTrigger<T> {
+    fn update_noise_floor(&mut self, v: T) {
+        self.block_sum += v;
+        self.block_count += 1;
+        if self.block_count < self.block_len {
+            return;
+        }
+        let mean = self.block_sum / T::from(self.block_count).unwrap_or(T::one());
+        self.block_sum = T::zero();
+        self.block_count = 0;
+        if self.levels.len() == self.window_len {
+            self.levels.pop_front();
+        }
+        self.levels.push_back(mean);
+
+        // Trigger only once the estimate covers a tenth of the window.
+        if self.levels.len() * 10 >= self.window_len {
+            let mut sorted: Vec<T> = self.levels.iter().copied().collect();
+            sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+            self.noise_floor = Some(sorted[sorted.len() / 2]);
+        }
+    }
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> EventBlock<T>
+    for AdaptiveTrigger<T>
+{
+    fn reset(&mut self) {
+        self.latch.reset();
+        self.block_sum = T::zero();
+        self.block_count = 0;
+        self.levels.clear();
+        self.noise_floor = None;
+        self.processed = 0;
+    }
+
+    fn process(&mut self, input: &ndarray::Array1<T>, mut obs: impl FnMut(Event<T>)) {
+        for &v in input {
+            if let Some(floor) = self.noise_floor.filter(|_| self.processed > self.holdoff) {
+                let (over, under) = (
+                    v > self.trigger_ratio * floor,
+                    v <= self.reset_ratio * floor,
+                );
+                self.latch.judge(self.processed, over, under, &mut obs);
+            }
+            if !self.latch.triggered() {
+                self.update_noise_floor(v);
+            }
+            self.processed += 1
+        }
+    }
+}
+
+pub struct AdaptiveTriggerBuilder<T> {
+    sample_rate_hz: Option<T>,
+    window_s: Option<T>,
+    trigger_ratio: Option<T>,
+    reset_ratio: Option<T>,
+    holdoff: Option<usize>,
+    min_duration: Option<usize>,
+    max_duration: Option<usize>,
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> Default
+    for AdaptiveTriggerBuilder<T>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> AdaptiveTriggerBuilder<T> {
+    pub fn new() -> Self {
+        Self {
+            sample_rate_hz: None,
+            window_s: None,
+            trigger_ratio: None,
+            reset_ratio: None,
+            holdoff: None,
+            min_duration: None,
+            max_duration: None,
+        }
+    }
+
+    /// Interpret samples as coming in at a sample rate.
+    pub fn sample_rate(mut self, hz: T) -> Self {
+        self.sample_rate_hz.replace(hz);
+        self
+    }
+
+    /// Length of the window over which the noise floor is estimated, in
+    /// seconds.
+    pub fn window_s(mut self, s: T) -> Self {
+        self.window_s.replace(s);
+        self
+    }
+
+    /// Multiple of the noise floor at which to trigger.
+    pub fn trigger_ratio(mut self, ratio: T) -> Self {
+        self.trigger_ratio.replace(ratio);
+        self
+    }
+
+    /// Multiple of the noise floor at which to reset trigger.
+    pub fn reset_ratio(mut self, ratio: T) -> Self {
+        self.reset_ratio.replace(ratio);
+        self
+    }
+
+    /// Disable trigger until some number of samples have been processed.
+    pub fn holdoff(mut self, n: usize) -> Self {
+        self.holdoff.replace(n);
+        self
+    }
+
+    /// Trigger only once the level has been exceeded for some number of
+    /// consecutive samples.
+    pub fn min_duration(mut self, n: usize) -> Self {
+        self.min_duration.replace(n);
+        self
+    }
+
+    /// Forcibly reset the trigger once it has been asserted for some
+    /// number of samples, and hold it off until the level next falls to
+    /// the reset level.
+    pub fn max_duration(mut self, n: usize) -> Self {
+        self.max_duration.replace(n);
+        self
+    }
+
+    /// Construct a trigger.
+    pub fn build(self) -> Result<AdaptiveTrigger<T>, AdaptiveError> {
+        let two = T::one() + T::one();
+        let trigger_ratio = self.trigger_ratio.unwrap_or(two + two);
+        let reset_ratio = self.reset_ratio.unwrap_or(two);
+        if trigger_ratio < reset_ratio {
+            return Err(AdaptiveError::ThresholdError);
+        }
+        let sample_rate_hz = self.sample_rate_hz.unwrap_or(T::one());
+        let window_s = self.window_s.unwrap_or(T::from(300).unwrap_or(T::one()));
+        let window_len = window_s.to_usize().unwrap_or(0);
+        if window_len == 0 {
+            return Err(AdaptiveError::WindowTooShort);
+        }
+        let result = AdaptiveTrigger {
+            trigger_ratio,
+            reset_ratio,
+            holdoff: self.holdoff.unwrap_or(0),
+            latch: TriggerLatch::new(self.min_duration.unwrap_or(1), self.max_duration),
+            block_len: Float::round(sample_rate_hz).to_usize().unwrap_or(1).max(1),
+            block_sum: T::zero(),
+            block_count: 0,
+            levels: VecDeque::with_capacity(window_len),
+            window_len,
+            noise_floor: None,
+            processed: 0,
+        };
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AdaptiveTriggerBuilder;
+    use crate::signal::{Event, EventBlock};
+    use ndarray::Array1;
+
+    #[test]
+    fn follows_noise_floor() {
+        let mut trigger = AdaptiveTriggerBuilder::new()
+            .sample_rate(10.0_f32)
+            .window_s(20.0)
+            .trigger_ratio(4.0)
+            .reset_ratio(2.0)
+            .build()
+            .expect("works");
+        let mut events = Vec::new();
+
+        // A noisy site: a level of 3 isn't remarkable...
+        let noise = Array1::from_elem(200, 2.0);
+        trigger.process(&noise, |e| events.push(e));
+        trigger.process(&Array1::from_elem(5, 3.0), |e| events.push(e));
+        assert!(events.is_empty());
+
+        // ...but 10 is.
+        trigger.process(&Array1::from_elem(5, 10.0), |e| events.push(e));
+        trigger.process(&Array1::from_elem(5, 2.0), |e| events.push(e));
+        assert!(matches!(
+            events.as_slice(),
+            [Event::Triggered(_), Event::Reset(_)]
+        ));
+    }
+}
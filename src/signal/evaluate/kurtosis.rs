@@ -0,0 +1,333 @@
+use std::collections::VecDeque;
+use std::iter::Sum;
+
+use super::super::{Event, EventBlock};
+use super::latch::TriggerLatch;
+use ndarray::ScalarOperand;
+use num_traits::One;
+use thiserror::Error;
+
+pub use num_traits::{Float, Zero};
+pub use sci_rs::na::RealField;
+
+#[derive(Error, Debug)]
+pub enum KurtosisError {
+    #[error("trigger level is lower than reset level")]
+    ThresholdError,
+    #[error("kurtosis window must be at least four samples long")]
+    WindowTooShort,
+}
+
+/// Onset detector which triggers on the (excess) kurtosis of a signal
+/// over a sliding window. Kurtosis measures how impulsive a signal is,
+/// so it rises sharply at the onset of a P wave but hardly at all for
+/// emergent noise (such as traffic) which grows gradually, however loud.
+///
+/// Gaussian noise has a kurtosis of zero.
+pub struct KurtosisTrigger<T>
+where
+    T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand,
+{
+    trigger_level: f64,
+    reset_level: f64,
+    holdoff: usize,
+
+    /// When the trigger asserts and resets.
+    latch: TriggerLatch,
+
+    /// Samples in the window, and their central moments. The moments are
+    /// worked out afresh once the window has turned over (counting
+    /// samples added since), so that rounding error can't build up.
+    window: VecDeque<f64>,
+    window_len: usize,
+    moments: Moments,
+    added: usize,
+
+    /// Number of samples processed so far.
+    processed: usize,
+
+    _phantom: std::marker::PhantomData<T>,
+}
+
+/// The mean of some samples, and the sums of the second, third and fourth
+/// powers of their deviations from it, kept up to date as samples come
+/// and go (after Welford, and Pébay's higher moments), rather than from
+/// sums of raw powers, which cancel badly.
+#[derive(Clone, Copy, Default)]
+struct Moments {
+    n: usize,
+    mean: f64,
+    m2: f64,
+    m3: f64,
+    m4: f64,
+}
+
+impl Moments {
+    fn of(samples: &VecDeque<f64>) -> Self {
+        let mut moments = Self {
+            n: samples.len(),
+            ..Self::default()
+        };
+        moments.mean = samples.iter().sum::<f64>() / moments.n.max(1) as f64;
+        for x in samples {
+            let d = x - moments.mean;
+            moments.m2 += d * d;
+            moments.m3 += d * d * d;
+            moments.m4 += d * d * d * d;
+        }
+        moments
+    }
+
+    fn add(&mut self, x: f64) {
+        let n = (self.n + 1) as f64;
+        let delta = x - self.mean;
+        let delta_n = delta / n;
+        let term = delta * delta_n * (n - 1.0);
+        self.mean += delta_n;
+        self.m4 += term * delta_n * delta_n * (n * n - 3.0 * n + 3.0)
+            + 6.0 * delta_n * delta_n * self.m2
+            - 4.0 * delta_n * self.m3;
+        self.m3 += term * delta_n * (n - 2.0) - 3.0 * delta_n * self.m2;
+        self.m2 += term;
+        self.n += 1;
+    }
+
+    /// Undo the addition of a sample, given that it was added last (which,
+    /// as the moments don't depend on order, any of them may be taken as).
+    fn remove(&mut self, x: f64) {
+        if self.n <= 1 {
+            *self = Self::default();
+            return;
+        }
+        let n = self.n as f64;
+        let mean = (n * self.mean - x) / (n - 1.0);
+        let delta = x - mean;
+        let delta_n = delta / n;
+        let term = delta * delta_n * (n - 1.0);
+        self.mean = mean;
+        self.m2 -= term;
+        self.m3 -= term * delta_n * (n - 2.0) - 3.0 * delta_n * self.m2;
+        self.m4 -= term * delta_n * delta_n * (n * n - 3.0 * n + 3.0)
+            + 6.0 * delta_n * delta_n * self.m2
+            - 4.0 * delta_n * self.m3;
+        self.n -= 1;
+    }
+
+    /// The excess kurtosis of the samples.
+    fn kurtosis(&self) -> f64 {
+        if self.m2 <= 0.0 {
+            return 0.0;
+        }
+        self.n as f64 * self.m4 / (self.m2 * self.m2) - 3.0
+    }
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> KurtosisTrigger<T> {
+    /// Add a sample to the window, returning the kurtosis of the window
+    /// once it is full.
+    fn kurtosis(&mut self, x: f64) -> Option<f64> {
+        if self.window.len() == self.window_len {
+            if let Some(old) = self.window.pop_front() {
+                self.moments.remove(old);
+            }
+        }
+        self.window.push_back(x);
+        self.moments.add(x);
+        self.added += 1;
+        if self.added == self.window_len {
+            self.moments = Moments::of(&self.window);
+            self.added = 0;
+        }
+        if self.window.len() < self.window_len {
+            return None;
+        }
+        Some(self.moments.kurtosis())
+    }
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> EventBlock<T>
+    for KurtosisTrigger<T>
+{
+    fn reset(&mut self) {
+        self.latch.reset();
+        self.window.clear();
+        self.moments = Moments::default();
+        self.added = 0;
+        self.processed = 0;
+    }
+
+    fn process(&mut self, input: &ndarray::Array1<T>, mut obs: impl FnMut(Event<T>)) {
+        for &v in input {
+            let kurtosis = self.kurtosis(v.to_f64().unwrap_or(0.0));
+            if let Some(k) = kurtosis.filter(|_| self.processed > self.holdoff) {
+                let (over, under) = (k > self.trigger_level, k <= self.reset_level);
+                self.latch.judge(self.processed, over, under, &mut obs);
+            }
+            self.processed += 1
+        }
+    }
+}
+
+pub struct KurtosisTriggerBuilder<T> {
+    sample_rate_hz: Option<T>,
+    window_s: Option<T>,
+    trigger_level: Option<T>,
+    reset_level: Option<T>,
+    holdoff: Option<usize>,
+    min_duration: Option<usize>,
+    max_duration: Option<usize>,
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> Default
+    for KurtosisTriggerBuilder<T>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> KurtosisTriggerBuilder<T> {
+    pub fn new() -> Self {
+        Self {
+            sample_rate_hz: None,
+            window_s: None,
+            trigger_level: None,
+            reset_level: None,
+            holdoff: None,
+            min_duration: None,
+            max_duration: None,
+        }
+    }
+
+    /// Interpret samples as coming in at a sample rate.
+    pub fn sample_rate(mut self, hz: T) -> Self {
+        self.sample_rate_hz.replace(hz);
+        self
+    }
+
+    /// Length of the window over which kurtosis is measured, in seconds.
+    pub fn window_s(mut self, s: T) -> Self {
+        self.window_s.replace(s);
+        self
+    }
+
+    /// Kurtosis at which to trigger.
+    pub fn trigger(mut self, level: T) -> Self {
+        self.trigger_level.replace(level);
+        self
+    }
+
+    /// Kurtosis at which to reset trigger.
+    pub fn reset(mut self, level: T) -> Self {
+        self.reset_level.replace(level);
+        self
+    }
+
+    /// Disable trigger until some number of samples have been processed.
+    pub fn holdoff(mut self, n: usize) -> Self {
+        self.holdoff.replace(n);
+        self
+    }
+
+    /// Trigger only once the level has been exceeded for some number of
+    /// consecutive samples.
+    pub fn min_duration(mut self, n: usize) -> Self {
+        self.min_duration.replace(n);
+        self
+    }
+
+    /// Forcibly reset the trigger once it has been asserted for some
+    /// number of samples, and hold it off until the kurtosis next falls
+    /// to the reset level.
+    pub fn max_duration(mut self, n: usize) -> Self {
+        self.max_duration.replace(n);
+        self
+    }
+
+    /// Construct a detector.
+    pub fn build(self) -> Result<KurtosisTrigger<T>, KurtosisError> {
+        let trigger_level = self.trigger_level.map_or(Some(5.0), |l| l.to_f64());
+        let reset_level = self.reset_level.map_or(Some(1.0), |l| l.to_f64());
+        let (Some(trigger_level), Some(reset_level)) = (trigger_level, reset_level) else {
+            return Err(KurtosisError::ThresholdError);
+        };
+        if trigger_level < reset_level {
+            return Err(KurtosisError::ThresholdError);
+        }
+        let sample_rate_hz = self.sample_rate_hz.unwrap_or(T::one());
+        let window_s = self.window_s.unwrap_or(T::from(3).unwrap_or(T::one()));
+        let window_len = Float::round(window_s * sample_rate_hz)
+            .to_usize()
+            .unwrap_or(0);
+        if window_len < 4 {
+            return Err(KurtosisError::WindowTooShort);
+        }
+        let result = KurtosisTrigger {
+            trigger_level,
+            reset_level,
+            holdoff: self.holdoff.unwrap_or(0),
+            latch: TriggerLatch::new(self.min_duration.unwrap_or(1), self.max_duration),
+            window: VecDeque::with_capacity(window_len),
+            window_len,
+            moments: Moments::default(),
+            added: 0,
+            processed: 0,
+            _phantom: std::marker::PhantomData,
+        };
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KurtosisTriggerBuilder, Moments};
+    use crate::signal::{Event, EventBlock};
+    use ndarray::Array1;
+    use std::collections::VecDeque;
+
+    #[test]
+    fn slides_central_moments() {
+        // Samples far from zero, whose raw powers would swamp their
+        // deviations.
+        let samples: Vec<f64> = (0..50).map(|i| 1.0e6 + (i as f64 * 0.7).sin()).collect();
+        let mut window = VecDeque::new();
+        let mut moments = Moments::default();
+        for &x in samples.iter() {
+            if window.len() == 20 {
+                moments.remove(window.pop_front().expect("full"));
+            }
+            window.push_back(x);
+            moments.add(x);
+        }
+        let exact = Moments::of(&window);
+        assert!((moments.mean - exact.mean).abs() < 1e-6);
+        assert!((moments.kurtosis() - exact.kurtosis()).abs() < 1e-6);
+        assert!(exact.kurtosis() < 0.0, "{}", exact.kurtosis());
+    }
+
+    #[test]
+    fn triggers_on_impulsive_onset() {
+        let mut detector = KurtosisTriggerBuilder::new()
+            .sample_rate(10.0_f32)
+            .window_s(5.0)
+            .build()
+            .expect("works");
+        let mut events = Vec::new();
+
+        // A sinusoid growing steadily louder is not impulsive...
+        let emergent =
+            Array1::from_iter((0..200).map(|i| (1.0 + i as f32 / 20.0) * (i as f32 * 1.3).sin()));
+        let steady = Array1::from_iter((0..100).map(|i| 11.0 * (i as f32 * 1.3).sin()));
+        detector.process(&emergent, |e| events.push(e));
+        detector.process(&steady, |e| events.push(e));
+        assert!(events.is_empty());
+
+        // ...but a sudden spike is.
+        detector.process(&Array1::from_elem(1, 200.0), |e| events.push(e));
+        detector.process(&steady, |e| events.push(e));
+        assert!(matches!(
+            events.as_slice(),
+            [Event::Triggered(_), Event::Reset(_)]
+        ));
+    }
+}
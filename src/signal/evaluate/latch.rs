@@ -0,0 +1,128 @@
+use super::super::Event;
+use ndarray::ScalarOperand;
+use num_traits::{Float, One, Zero};
+use sci_rs::na::RealField;
+
+/// When a trigger asserts and resets, given how each sample measures up
+/// against its levels. The trigger blocks differ only in what they
+/// measure a signal by, and share this.
+pub struct TriggerLatch {
+    triggered: bool,
+
+    /// Number of consecutive samples which must exceed the trigger level
+    /// before triggering, and the number which have so far.
+    min_duration: usize,
+    above: usize,
+
+    /// Number of samples after which to forcibly reset a trigger, and
+    /// whether the trigger is held off after such a reset (until a sample
+    /// falls to the reset level).
+    max_duration: Option<usize>,
+    stuck: bool,
+
+    /// Sample at which the trigger last asserted.
+    triggered_at: usize,
+}
+
+impl TriggerLatch {
+    /// Trigger only once the trigger level has been exceeded for
+    /// `min_duration` consecutive samples (at least one), and forcibly
+    /// reset a trigger once it has been asserted for `max_duration`, if
+    /// given.
+    pub fn new(min_duration: usize, max_duration: Option<usize>) -> Self {
+        Self {
+            triggered: false,
+            min_duration: min_duration.max(1),
+            above: 0,
+            max_duration,
+            stuck: false,
+            triggered_at: 0,
+        }
+    }
+
+    /// Whether the trigger is asserted.
+    pub fn triggered(&self) -> bool {
+        self.triggered
+    }
+
+    pub fn reset(&mut self) {
+        self.triggered = false;
+        self.above = 0;
+        self.stuck = false;
+    }
+
+    /// Judge sample number `at`, given whether it is over the trigger
+    /// level and whether it is at or under the reset level, telling `obs`
+    /// of the trigger asserting or resetting.
+    pub fn judge<T: RealField + Float + Copy + One + Zero + ScalarOperand>(
+        &mut self,
+        at: usize,
+        over: bool,
+        under: bool,
+        obs: &mut impl FnMut(Event<T>),
+    ) {
+        if over {
+            self.above += 1;
+        } else {
+            self.above = 0;
+        }
+        if !self.triggered && !self.stuck && self.above >= self.min_duration {
+            obs(Event::Triggered(at));
+            self.triggered = true;
+            self.triggered_at = at;
+        }
+        if under {
+            if self.triggered {
+                obs(Event::Reset(at));
+                self.triggered = false
+            }
+            self.stuck = false;
+        } else if self.triggered
+            && self
+                .max_duration
+                .is_some_and(|max| at - self.triggered_at >= max)
+        {
+            obs(Event::StuckReset(at));
+            self.triggered = false;
+            self.stuck = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn judge(latch: &mut TriggerLatch, samples: &[(bool, bool)]) -> Vec<Event<f32>> {
+        let mut events = Vec::new();
+        for (at, &(over, under)) in samples.iter().enumerate() {
+            latch.judge(at, over, under, &mut |e| events.push(e));
+        }
+        events
+    }
+
+    #[test]
+    fn holds_off_after_a_stuck_reset() {
+        let mut latch = TriggerLatch::new(2, Some(3));
+        let over = (true, false);
+        let between = (false, false);
+        let under = (false, true);
+        let events = judge(
+            &mut latch,
+            &[
+                over, over, over, between, between, over, over, under, over, over,
+            ],
+        );
+        assert!(matches!(
+            events.as_slice(),
+            [
+                Event::Triggered(1),
+                Event::StuckReset(4),
+                Event::Triggered(9)
+            ]
+        ));
+        assert!(latch.triggered());
+        latch.reset();
+        assert!(!latch.triggered());
+    }
+}
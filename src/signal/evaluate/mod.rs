@@ -1 +1,6 @@
+pub mod adaptive;
+pub mod kurtosis;
+mod latch;
+pub mod template;
 pub mod threshold;
+pub mod z_detector;
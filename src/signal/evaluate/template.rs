@@ -0,0 +1,274 @@
+use std::collections::VecDeque;
+use std::iter::Sum;
+use std::path::Path;
+
+use super::super::{Event, EventBlock};
+use super::latch::TriggerLatch;
+use ndarray::ScalarOperand;
+use num_traits::One;
+use thiserror::Error;
+
+pub use num_traits::{Float, Zero};
+pub use sci_rs::na::RealField;
+
+#[derive(Error, Debug)]
+pub enum TemplateError {
+    #[error("trigger correlation is lower than reset correlation")]
+    ThresholdError,
+    #[error("template matcher needs at least one template")]
+    NoTemplates,
+    #[error("template must be at least two samples long, and not constant")]
+    TemplateTooShort,
+    #[error("unable to read template file")]
+    ReadFailed(#[from] std::io::Error),
+    #[error("unparseable sample on line {0} of template file")]
+    UnparsableSample(usize),
+}
+
+/// Read a waveform template from a text file of one sample per line. If
+/// a line has several columns (such as a time and a sample), the last is
+/// taken as the sample.
+pub fn read_template(path: &Path) -> Result<Vec<f64>, TemplateError> {
+    std::fs::read_to_string(path)?
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| line.split_ascii_whitespace().last().map(|v| (i, v)))
+        .map(|(i, v)| {
+            v.parse()
+                .map_err(|_| TemplateError::UnparsableSample(i + 1))
+        })
+        .collect()
+}
+
+/// Detector which cross-correlates the signal against stored waveform
+/// templates (of repeating quarry blasts, say, or other known local
+/// sources), and triggers when the normalized correlation with any of
+/// them exceeds a level.
+///
+/// Templates must have been recorded at the same point in a flow's
+/// pipeline as the detector sits, so that they are filtered alike.
+pub struct TemplateMatcher<T>
+where
+    T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand,
+{
+    trigger_level: f64,
+    reset_level: f64,
+    holdoff: usize,
+
+    /// When the trigger asserts and resets.
+    latch: TriggerLatch,
+
+    /// Templates, each with zero mean and unit norm.
+    templates: Vec<Vec<f64>>,
+
+    /// The most recent samples, as many as the longest template.
+    window: VecDeque<f64>,
+    window_len: usize,
+
+    /// Number of samples processed so far.
+    processed: usize,
+
+    _phantom: std::marker::PhantomData<T>,
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> TemplateMatcher<T> {
+    /// The normalized correlation of the most recent samples with a
+    /// template, if enough samples have been seen.
+    fn correlation(&self, template: &[f64]) -> Option<f64> {
+        let n = template.len();
+        let skip = self.window.len().checked_sub(n)?;
+        let (dot, sum, sum_sq) = self
+            .window
+            .iter()
+            .skip(skip)
+            .zip(template)
+            .fold((0.0, 0.0, 0.0), |(dot, sum, sum_sq), (x, t)| {
+                (dot + x * t, sum + x, sum_sq + x * x)
+            });
+        // The template's mean is zero, so the signal's needn't be
+        // removed from the dot product, only from its norm.
+        let norm_sq = sum_sq - sum * sum / n as f64;
+        (norm_sq > 0.0).then(|| dot / norm_sq.sqrt())
+    }
+
+    /// Add a sample, returning the best correlation with any template.
+    fn best_correlation(&mut self, x: f64) -> Option<f64> {
+        if self.window.len() == self.window_len {
+            self.window.pop_front();
+        }
+        self.window.push_back(x);
+        self.templates
+            .iter()
+            .filter_map(|template| self.correlation(template))
+            .max_by(f64::total_cmp)
+    }
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> EventBlock<T>
+    for TemplateMatcher<T>
+{
+    fn reset(&mut self) {
+        self.latch.reset();
+        self.window.clear();
+        self.processed = 0;
+    }
+
+    fn process(&mut self, input: &ndarray::Array1<T>, mut obs: impl FnMut(Event<T>)) {
+        for &v in input {
+            let correlation = self.best_correlation(v.to_f64().unwrap_or(0.0));
+            if let Some(c) = correlation.filter(|_| self.processed > self.holdoff) {
+                let (over, under) = (c > self.trigger_level, c <= self.reset_level);
+                self.latch.judge(self.processed, over, under, &mut obs);
+            }
+            self.processed += 1
+        }
+    }
+}
+
+pub struct TemplateMatcherBuilder<T> {
+    templates: Vec<Vec<f64>>,
+    trigger_level: Option<T>,
+    reset_level: Option<T>,
+    holdoff: Option<usize>,
+    min_duration: Option<usize>,
+    max_duration: Option<usize>,
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> Default
+    for TemplateMatcherBuilder<T>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> TemplateMatcherBuilder<T> {
+    pub fn new() -> Self {
+        Self {
+            templates: Vec::new(),
+            trigger_level: None,
+            reset_level: None,
+            holdoff: None,
+            min_duration: None,
+            max_duration: None,
+        }
+    }
+
+    /// Add a waveform template to match against.
+    pub fn template(mut self, samples: Vec<f64>) -> Self {
+        self.templates.push(samples);
+        self
+    }
+
+    /// Correlation at which to trigger.
+    pub fn trigger(mut self, level: T) -> Self {
+        self.trigger_level.replace(level);
+        self
+    }
+
+    /// Correlation at which to reset trigger.
+    pub fn reset(mut self, level: T) -> Self {
+        self.reset_level.replace(level);
+        self
+    }
+
+    /// Disable trigger until some number of samples have been processed.
+    pub fn holdoff(mut self, n: usize) -> Self {
+        self.holdoff.replace(n);
+        self
+    }
+
+    /// Trigger only once the level has been exceeded for some number of
+    /// consecutive samples.
+    pub fn min_duration(mut self, n: usize) -> Self {
+        self.min_duration.replace(n);
+        self
+    }
+
+    /// Forcibly reset the trigger once it has been asserted for some
+    /// number of samples, and hold it off until the correlation next
+    /// falls to the reset level.
+    pub fn max_duration(mut self, n: usize) -> Self {
+        self.max_duration.replace(n);
+        self
+    }
+
+    /// Construct a template matcher.
+    pub fn build(self) -> Result<TemplateMatcher<T>, TemplateError> {
+        let trigger_level = self.trigger_level.map_or(Some(0.7), |l| l.to_f64());
+        let reset_level = self.reset_level.map_or(Some(0.5), |l| l.to_f64());
+        let (Some(trigger_level), Some(reset_level)) = (trigger_level, reset_level) else {
+            return Err(TemplateError::ThresholdError);
+        };
+        if trigger_level < reset_level {
+            return Err(TemplateError::ThresholdError);
+        }
+        if self.templates.is_empty() {
+            return Err(TemplateError::NoTemplates);
+        }
+        let templates = self
+            .templates
+            .into_iter()
+            .map(|template| {
+                let n = template.len() as f64;
+                let mean = template.iter().sum::<f64>() / n;
+                let centered: Vec<f64> = template.iter().map(|t| t - mean).collect();
+                let norm = centered.iter().map(|t| t * t).sum::<f64>().sqrt();
+                if template.len() < 2 || norm <= 0.0 {
+                    return Err(TemplateError::TemplateTooShort);
+                }
+                Ok(centered.into_iter().map(|t| t / norm).collect::<Vec<f64>>())
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let window_len = templates.iter().map(Vec::len).max().unwrap_or(0);
+        let result = TemplateMatcher {
+            trigger_level,
+            reset_level,
+            holdoff: self.holdoff.unwrap_or(0),
+            latch: TriggerLatch::new(self.min_duration.unwrap_or(1), self.max_duration),
+            templates,
+            window: VecDeque::with_capacity(window_len),
+            window_len,
+            processed: 0,
+            _phantom: std::marker::PhantomData,
+        };
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TemplateMatcherBuilder;
+    use crate::signal::{Event, EventBlock};
+    use ndarray::Array1;
+
+    #[test]
+    fn triggers_on_matching_waveform() {
+        // A decaying chirp, as a blast might record as.
+        let blast: Vec<f64> = (0..50)
+            .map(|i| {
+                let t = i as f64 / 10.0;
+                (-t).exp() * (t * (3.0 + t)).sin()
+            })
+            .collect();
+        let mut matcher = TemplateMatcherBuilder::new()
+            .template(blast.clone())
+            .build()
+            .expect("works");
+        let mut events = Vec::new();
+
+        // A loud sinusoid doesn't match...
+        let other = Array1::from_iter((0..200).map(|i| 100.0 * (i as f64 * 0.7).sin()));
+        matcher.process(&other, |e| events.push(e));
+        assert!(events.is_empty());
+
+        // ...but a quieter, offset copy of the template does.
+        let copy = Array1::from_iter(blast.iter().map(|v| 5.0 + 0.1 * v));
+        matcher.process(&copy, |e| events.push(e));
+        matcher.process(&other, |e| events.push(e));
+        assert!(matches!(
+            events.as_slice(),
+            [Event::Triggered(249), Event::Reset(_)]
+        ));
+    }
+}
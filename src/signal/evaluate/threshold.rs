@@ -1,6 +1,7 @@
 use std::iter::Sum;
 
 use super::super::{Event, EventBlock};
+use super::latch::TriggerLatch;
 use ndarray::ScalarOperand;
 use num_traits::One;
 use thiserror::Error;
@@ -23,9 +24,11 @@ where
 {
     trigger: T,
     reset: T,
-    triggered: bool,
     holdoff: usize,
 
+    /// When the trigger asserts and resets.
+    latch: TriggerLatch,
+
     /// Number of samples processed so far.
     processed: usize,
 }
@@ -34,31 +37,57 @@ impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> EventBlock<
     for ThresholdTrigger<T>
 {
     fn reset(&mut self) {
-        self.triggered = false;
+        self.latch.reset();
         self.processed = 0;
     }
 
-    fn process(&mut self, input: &ndarray::Array1<T>, mut obs: impl FnMut(Event<T>) -> ()) {
+    fn process(&mut self, input: &ndarray::Array1<T>, mut obs: impl FnMut(Event<T>)) {
         for &v in input {
             if self.processed > self.holdoff {
-                if !self.triggered && v > self.trigger {
-                    obs(Event::Triggered(self.processed));
-                    self.triggered = true
-                }
-                if self.triggered && v <= self.reset {
-                    obs(Event::Reset(self.processed));
-                    self.triggered = false
-                }
+                let (over, under) = (v > self.trigger, v <= self.reset);
+                self.latch.judge(self.processed, over, under, &mut obs);
             }
             self.processed += 1
         }
     }
 }
 
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> ThresholdTrigger<T> {
+    /// The levels at which the trigger asserts and resets.
+    pub fn levels(&self) -> (T, T) {
+        (self.trigger, self.reset)
+    }
+
+    /// Change the levels at which the trigger asserts and resets (leaving
+    /// either as it is if not given), from the next sample on.
+    pub fn set_levels(
+        &mut self,
+        trigger: Option<T>,
+        reset: Option<T>,
+    ) -> Result<(), ThresholdError> {
+        let trigger = trigger.unwrap_or(self.trigger);
+        let reset = reset.unwrap_or(self.reset);
+        if trigger < reset {
+            return Err(ThresholdError::ThresholdError);
+        }
+        self.trigger = trigger;
+        self.reset = reset;
+        Ok(())
+    }
+
+    /// Change the number of samples to process (from the first) before
+    /// enabling the trigger, which disables it again if fewer have been.
+    pub fn set_holdoff(&mut self, n: usize) {
+        self.holdoff = n;
+    }
+}
+
 pub struct ThresholdTriggerBuilder<T> {
     trigger: Option<T>,
     reset: Option<T>,
     holdoff: Option<usize>,
+    min_duration: Option<usize>,
+    max_duration: Option<usize>,
 }
 
 impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> Default
@@ -75,6 +104,8 @@ impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> ThresholdTr
             trigger: None,
             reset: None,
             holdoff: None,
+            min_duration: None,
+            max_duration: None,
         }
     }
 
@@ -96,6 +127,21 @@ impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> ThresholdTr
         self
     }
 
+    /// Trigger only once the level has been exceeded for some number of
+    /// consecutive samples.
+    pub fn min_duration(mut self, n: usize) -> Self {
+        self.min_duration.replace(n);
+        self
+    }
+
+    /// Forcibly reset the trigger once it has been asserted for some
+    /// number of samples, and hold it off until the level next falls to
+    /// the reset level.
+    pub fn max_duration(mut self, n: usize) -> Self {
+        self.max_duration.replace(n);
+        self
+    }
+
     /// Construct a trigger.
     pub fn build(self) -> Result<ThresholdTrigger<T>, ThresholdError> {
         let trigger = self.trigger.unwrap_or(T::one());
@@ -106,8 +152,8 @@ impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> ThresholdTr
         let result = ThresholdTrigger {
             trigger,
             reset,
-            triggered: false,
             holdoff: self.holdoff.unwrap_or(0),
+            latch: TriggerLatch::new(self.min_duration.unwrap_or(1), self.max_duration),
             processed: 0,
         };
         Ok(result)
@@ -117,13 +163,75 @@ impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> ThresholdTr
 #[cfg(test)]
 mod tests {
     use super::ThresholdTriggerBuilder;
+    use crate::signal::{Event, EventBlock};
+    use ndarray::array;
 
     #[test]
     fn test_one() {
         ThresholdTriggerBuilder::new()
-            .trigger(0.5 as f32)
+            .trigger(0.5_f32)
+            .reset(0.2)
+            .build()
+            .expect("works");
+    }
+
+    #[test]
+    fn ignores_brief_excursions() {
+        let mut trigger = ThresholdTriggerBuilder::new()
+            .trigger(0.5_f32)
+            .reset(0.2)
+            .min_duration(3)
+            .build()
+            .expect("works");
+        let mut events = Vec::new();
+        trigger.process(&array![0.0, 0.0, 1.0, 1.0, 0.0, 1.0], |e| events.push(e));
+        assert!(events.is_empty());
+        trigger.process(&array![1.0, 1.0, 0.0], |e| events.push(e));
+        assert!(matches!(
+            events.as_slice(),
+            [Event::Triggered(7), Event::Reset(8)]
+        ));
+    }
+
+    #[test]
+    fn adjusts_levels() {
+        let mut trigger = ThresholdTriggerBuilder::new()
+            .trigger(0.5_f32)
+            .reset(0.2)
+            .build()
+            .expect("works");
+        assert!(trigger.set_levels(Some(0.1), None).is_err());
+        assert_eq!(trigger.levels(), (0.5, 0.2));
+        trigger.set_levels(Some(2.0), None).expect("works");
+        let mut events = Vec::new();
+        trigger.process(&array![1.0, 3.0, 1.0], |e| events.push(e));
+        assert!(matches!(events.as_slice(), [Event::Triggered(1)]));
+        trigger.set_levels(None, Some(1.0)).expect("works");
+        trigger.process(&array![1.0], |e| events.push(e));
+        assert!(matches!(
+            events.as_slice(),
+            [Event::Triggered(1), Event::Reset(3)]
+        ));
+    }
+
+    #[test]
+    fn resets_stuck_trigger() {
+        let mut trigger = ThresholdTriggerBuilder::new()
+            .trigger(0.5_f32)
             .reset(0.2)
+            .max_duration(3)
             .build()
             .expect("works");
+        let mut events = Vec::new();
+        trigger.process(&array![0.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0], |e| {
+            events.push(e)
+        });
+        assert!(matches!(
+            events.as_slice(),
+            [Event::Triggered(1), Event::StuckReset(4)]
+        ));
+        events.clear();
+        trigger.process(&array![0.0, 1.0], |e| events.push(e));
+        assert!(matches!(events.as_slice(), [Event::Triggered(8)]));
     }
 }
@@ -0,0 +1,297 @@
+use std::collections::VecDeque;
+use std::iter::Sum;
+
+use super::super::{Event, EventBlock};
+use super::latch::TriggerLatch;
+use ndarray::ScalarOperand;
+use num_traits::One;
+use thiserror::Error;
+
+pub use num_traits::{Float, Zero};
+pub use sci_rs::na::RealField;
+
+#[derive(Error, Debug)]
+pub enum ZDetectorError {
+    #[error("trigger Z is lower than reset Z")]
+    ThresholdError,
+    #[error("short-term window must be at least one sample long")]
+    ShortWindowTooShort,
+    #[error("long-term window must be longer than the short-term window")]
+    LongWindowTooShort,
+}
+
+/// Z-detector (Swindell and Snider, 1977): triggers on the short-term
+/// average of a signal's energy, measured in standard deviations from
+/// its long-term mean. Normalizing by the running variance lets it
+/// tolerate changing noise conditions better than a fixed threshold.
+///
+/// The long-term statistics aren't updated while triggered, so that
+/// events don't inflate them.
+pub struct ZDetector<T>
+where
+    T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand,
+{
+    trigger_z: T,
+    reset_z: T,
+    holdoff: usize,
+
+    /// When the trigger asserts and resets.
+    latch: TriggerLatch,
+
+    /// Recent squared samples, and their sum. The sum is kept in double
+    /// precision, and worked out afresh once the window has turned over
+    /// (counting samples added since), so that adding and removing
+    /// samples doesn't accumulate error.
+    short: VecDeque<f64>,
+    short_len: usize,
+    short_sum: f64,
+    short_added: usize,
+
+    /// Exponentially weighted mean and variance of the short-term
+    /// average, and their decay.
+    mean: T,
+    variance: T,
+    alpha: T,
+    long_len: usize,
+
+    /// Number of samples processed so far.
+    processed: usize,
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> ZDetector<T> {
+    fn short_term_average(&mut self, v: T) -> T {
+        if self.short.len() == self.short_len {
+            if let Some(old) = self.short.pop_front() {
+                self.short_sum -= old;
+            }
+        }
+        let v = v.to_f64().unwrap_or(0.0);
+        self.short.push_back(v * v);
+        self.short_sum += v * v;
+        self.short_added += 1;
+        if self.short_added == self.short_len {
+            self.short_sum = self.short.iter().sum();
+            self.short_added = 0;
+        }
+        let mean = self.short_sum.max(0.0) / self.short.len() as f64;
+        T::from(mean).unwrap_or(T::zero())
+    }
+
+    fn update_statistics(&mut self, sta: T) {
+        if self.processed == 0 {
+            self.mean = sta;
+            return;
+        }
+        let delta = sta - self.mean;
+        self.mean += self.alpha * delta;
+        self.variance = (T::one() - self.alpha) * (self.variance + self.alpha * delta * delta);
+    }
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> EventBlock<T>
+    for ZDetector<T>
+{
+    fn reset(&mut self) {
+        self.latch.reset();
+        self.short.clear();
+        self.short_sum = 0.0;
+        self.short_added = 0;
+        self.mean = T::zero();
+        self.variance = T::zero();
+        self.processed = 0;
+    }
+
+    fn process(&mut self, input: &ndarray::Array1<T>, mut obs: impl FnMut(Event<T>)) {
+        for &v in input {
+            let sta = self.short_term_average(v);
+            let deviation = Float::sqrt(self.variance);
+            if self.processed > self.holdoff.max(self.long_len) && deviation > T::zero() {
+                let z = (sta - self.mean) / deviation;
+                let (over, under) = (z > self.trigger_z, z <= self.reset_z);
+                self.latch.judge(self.processed, over, under, &mut obs);
+            }
+            if !self.latch.triggered() {
+                self.update_statistics(sta);
+            }
+            self.processed += 1
+        }
+    }
+}
+
+pub struct ZDetectorBuilder<T> {
+    sample_rate_hz: Option<T>,
+    short_s: Option<T>,
+    long_s: Option<T>,
+    trigger_z: Option<T>,
+    reset_z: Option<T>,
+    holdoff: Option<usize>,
+    min_duration: Option<usize>,
+    max_duration: Option<usize>,
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> Default
+    for ZDetectorBuilder<T>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> ZDetectorBuilder<T> {
+    pub fn new() -> Self {
+        Self {
+            sample_rate_hz: None,
+            short_s: None,
+            long_s: None,
+            trigger_z: None,
+            reset_z: None,
+            holdoff: None,
+            min_duration: None,
+            max_duration: None,
+        }
+    }
+
+    /// Interpret samples as coming in at a sample rate.
+    pub fn sample_rate(mut self, hz: T) -> Self {
+        self.sample_rate_hz.replace(hz);
+        self
+    }
+
+    /// Length of the window over which the short-term average energy is
+    /// taken, in seconds.
+    pub fn short_s(mut self, s: T) -> Self {
+        self.short_s.replace(s);
+        self
+    }
+
+    /// Time constant of the long-term statistics, in seconds.
+    pub fn long_s(mut self, s: T) -> Self {
+        self.long_s.replace(s);
+        self
+    }
+
+    /// Number of standard deviations at which to trigger.
+    pub fn trigger_z(mut self, z: T) -> Self {
+        self.trigger_z.replace(z);
+        self
+    }
+
+    /// Number of standard deviations at which to reset trigger.
+    pub fn reset_z(mut self, z: T) -> Self {
+        self.reset_z.replace(z);
+        self
+    }
+
+    /// Disable trigger until some number of samples have been processed.
+    pub fn holdoff(mut self, n: usize) -> Self {
+        self.holdoff.replace(n);
+        self
+    }
+
+    /// Trigger only once the level has been exceeded for some number of
+    /// consecutive samples.
+    pub fn min_duration(mut self, n: usize) -> Self {
+        self.min_duration.replace(n);
+        self
+    }
+
+    /// Forcibly reset the trigger once it has been asserted for some
+    /// number of samples, and hold it off until Z next falls to the
+    /// reset level.
+    pub fn max_duration(mut self, n: usize) -> Self {
+        self.max_duration.replace(n);
+        self
+    }
+
+    /// Construct a detector.
+    pub fn build(self) -> Result<ZDetector<T>, ZDetectorError> {
+        let two = T::one() + T::one();
+        let trigger_z = self.trigger_z.unwrap_or(two + T::one());
+        let reset_z = self.reset_z.unwrap_or(T::one());
+        if trigger_z < reset_z {
+            return Err(ZDetectorError::ThresholdError);
+        }
+        let sample_rate_hz = self.sample_rate_hz.unwrap_or(T::one());
+        let short_s = self.short_s.unwrap_or(T::one());
+        let long_s = self.long_s.unwrap_or(T::from(30).unwrap_or(T::one()));
+        let short_len = Float::round(short_s * sample_rate_hz)
+            .to_usize()
+            .unwrap_or(0);
+        if short_len == 0 {
+            return Err(ZDetectorError::ShortWindowTooShort);
+        }
+        let long_len = Float::round(long_s * sample_rate_hz)
+            .to_usize()
+            .unwrap_or(0);
+        if long_len <= short_len {
+            return Err(ZDetectorError::LongWindowTooShort);
+        }
+        let result = ZDetector {
+            trigger_z,
+            reset_z,
+            holdoff: self.holdoff.unwrap_or(0),
+            latch: TriggerLatch::new(self.min_duration.unwrap_or(1), self.max_duration),
+            short: VecDeque::with_capacity(short_len),
+            short_len,
+            short_sum: 0.0,
+            short_added: 0,
+            mean: T::zero(),
+            variance: T::zero(),
+            alpha: T::one() / T::from(long_len).unwrap_or(T::one()),
+            long_len,
+            processed: 0,
+        };
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ZDetectorBuilder;
+    use crate::signal::{Event, EventBlock};
+    use ndarray::Array1;
+
+    #[test]
+    fn triggers_relative_to_variance() {
+        let mut detector = ZDetectorBuilder::new()
+            .sample_rate(10.0_f32)
+            .short_s(1.0)
+            .long_s(10.0)
+            .build()
+            .expect("works");
+        let mut events = Vec::new();
+
+        // Noise alternating between quiet and loud seconds varies enough
+        // that a burst no louder than its loud seconds isn't remarkable...
+        let noise = Array1::from_iter((0..300).map(|i| if (i / 10) % 2 == 0 { 1.0 } else { 2.0 }));
+        detector.process(&noise, |e| events.push(e));
+        assert!(events.is_empty());
+
+        // ...but a much louder one is.
+        detector.process(&Array1::from_elem(10, 10.0), |e| events.push(e));
+        detector.process(&noise, |e| events.push(e));
+        assert!(matches!(
+            events.as_slice(),
+            [Event::Triggered(_), Event::Reset(_)]
+        ));
+    }
+
+    #[test]
+    fn short_term_average_does_not_drift() {
+        let mut detector = ZDetectorBuilder::new()
+            .sample_rate(10.0_f32)
+            .short_s(1.0)
+            .long_s(10.0)
+            .build()
+            .expect("works");
+        // Large samples followed by small ones would leave a sum kept in
+        // single precision far from zero.
+        for v in [3000.0, 0.001] {
+            for _ in 0..10_000 {
+                detector.short_term_average(v);
+            }
+        }
+        let sta = detector.short_term_average(0.001);
+        assert!((sta - 0.000001).abs() < 1e-12, "{sta}");
+    }
+}
@@ -1,8 +1,3 @@
-extern crate alloc;
-
-use alloc::vec::Vec;
-use std::borrow::Borrow;
-
 pub use sci_rs::na::RealField;
 
 #[derive(Debug, Clone, Copy)]
@@ -17,21 +12,23 @@ pub struct Ba<F: RealField + Copy> {
 }
 
 ///
-/// Filter data through a very restricted numerator/denominator
+/// Filter data in place through a very restricted numerator/denominator
 /// aka "BA" filter.
 ///
-pub fn lfilt_dyn<YI, F>(y: YI, ba: &mut Ba<F>) -> Vec<F>
+/// The coefficients and delay are kept in locals for the length of the
+/// loop, so that they stay in registers.
+///
+pub fn lfilt_in_place<F>(data: &mut [F], ba: &mut Ba<F>)
 where
     F: RealField + Copy,
-    YI: IntoIterator,
-    YI::Item: Borrow<F>,
 {
-    y.into_iter()
-        .map(|yi0| {
-            let x_new = *yi0.borrow() - ba.a1 * ba.zi0;
-            let x = x_new * ba.b[0] + ba.zi0 * ba.b[1];
-            ba.zi0 = x_new;
-            x
-        })
-        .collect::<Vec<_>>()
+    let [b0, b1] = ba.b;
+    let a1 = ba.a1;
+    let mut zi0 = ba.zi0;
+    for x in data.iter_mut() {
+        let x_new = *x - a1 * zi0;
+        *x = x_new * b0 + zi0 * b1;
+        zi0 = x_new;
+    }
+    ba.zi0 = zi0;
 }
@@ -1 +1,2 @@
-pub mod lfilter;
\ No newline at end of file
+pub mod lfilter;
+pub mod sosfilt;
\ No newline at end of file
@@ -0,0 +1,80 @@
+pub use sci_rs::na::RealField;
+use sci_rs::signal::filter::design::Sos;
+
+///
+/// Filter data in place through cascaded second order sections, as
+/// `sci_rs::signal::filter::sosfilt_dyn` does.
+///
+/// The recursion rules out vectorizing across samples, so instead each
+/// section is run over the whole of the data in turn, with its
+/// coefficients and delays held in locals (and so in registers), and the
+/// loop unrolled by four.
+///
+pub fn sosfilt_in_place<F>(data: &mut [F], sos: &mut [Sos<F>])
+where
+    F: RealField + Copy,
+{
+    for section in sos.iter_mut() {
+        let [b0, b1, b2] = section.b;
+        let [_, a1, a2] = section.a;
+        let mut zi0 = section.zi0;
+        let mut zi1 = section.zi1;
+        let mut step = |x: &mut F| {
+            let x_curr = *x;
+            let x_new = b0 * x_curr + zi0;
+            zi0 = b1 * x_curr - a1 * x_new + zi1;
+            zi1 = b2 * x_curr - a2 * x_new;
+            *x = x_new;
+        };
+        let mut chunks = data.chunks_exact_mut(4);
+        for chunk in &mut chunks {
+            step(&mut chunk[0]);
+            step(&mut chunk[1]);
+            step(&mut chunk[2]);
+            step(&mut chunk[3]);
+        }
+        for x in chunks.into_remainder() {
+            step(x);
+        }
+        section.zi0 = zi0;
+        section.zi1 = zi1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sosfilt_in_place;
+    use sci_rs::signal::filter::design::{
+        butter_dyn, DigitalFilter, FilterBandType, FilterOutputType, SosFormatFilter,
+    };
+    use sci_rs::signal::filter::sosfilt_dyn;
+
+    #[test]
+    fn matches_sosfilt_dyn() {
+        let DigitalFilter::Sos(SosFormatFilter { sos }) = butter_dyn(
+            6,
+            [5.0_f64].to_vec(),
+            Some(FilterBandType::Lowpass),
+            Some(false),
+            Some(FilterOutputType::Sos),
+            Some(100.0),
+        ) else {
+            panic!("expected second order sections");
+        };
+        let input: Vec<f64> = (0..103).map(|i| ((i * 37) % 11) as f64 - 5.0).collect();
+
+        let mut expected_sos = sos.clone();
+        let expected = sosfilt_dyn(input.iter(), &mut expected_sos);
+
+        // In uneven pieces, to exercise the remainder and the carrying
+        // of delays from one call to the next.
+        let mut actual_sos = sos.clone();
+        let mut actual = input.clone();
+        let (first, second) = actual.split_at_mut(50);
+        sosfilt_in_place(first, &mut actual_sos);
+        sosfilt_in_place(second, &mut actual_sos);
+        for (a, e) in actual.iter().zip(expected.iter()) {
+            assert!((a - e).abs() < 1e-9, "{a} != {e}");
+        }
+    }
+}
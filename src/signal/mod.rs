@@ -2,19 +2,38 @@ mod block;
 mod debug;
 mod evaluate;
 mod filter;
+mod picker;
 
 use block::{
-    affine::AffineTransform, lp_filter::LowPassFilter, one_pole::OnePoleFilter, rectify::Rectify,
+    affine::AffineTransform, band_power::BandPower, envelope::EnvelopeFollower,
+    filter_bank::FilterBank, lp_filter::LowPassFilter, notch::NotchFilter, one_pole::OnePoleFilter,
+    rectify::Rectify, response::ResponseRemoval, rms::RunningRms, taper::Taper,
 };
+use evaluate::adaptive::AdaptiveTrigger;
+use evaluate::kurtosis::KurtosisTrigger;
+use evaluate::template::TemplateMatcher;
 use evaluate::threshold::ThresholdTrigger;
+use evaluate::z_detector::ZDetector;
 
 pub use block::affine::{AffineError, AffineTransformBuilder};
+pub use block::band_power::{BandPowerBuilder, BandPowerError};
+pub use block::envelope::{EnvelopeError, EnvelopeFollowerBuilder};
+pub use block::filter_bank::{FilterBankBuilder, FilterBankError, FilterBankOutput};
 pub use block::lp_filter::{LPFError, LowPassFilterBuilder};
+pub use block::notch::{NotchError, NotchFilterBuilder};
 pub use block::one_pole::{FilterType as OnePoleFilterType, OnePoleError, OnePoleFilterBuilder};
 pub use block::rectify::{RectifyBuilder, RectifyError, RectifyType};
+pub use block::response::{ResponseError, ResponseRemovalBuilder};
+pub use block::rms::{RmsError, RunningRmsBuilder};
+pub use block::taper::{TaperBuilder, TaperError, TaperType};
+pub use evaluate::adaptive::{AdaptiveError, AdaptiveTriggerBuilder};
+pub use evaluate::kurtosis::{KurtosisError, KurtosisTriggerBuilder};
+pub use evaluate::template::{read_template, TemplateError, TemplateMatcherBuilder};
 pub use evaluate::threshold::{ThresholdError, ThresholdTriggerBuilder};
+pub use evaluate::z_detector::{ZDetectorBuilder, ZDetectorError};
 
 pub use debug::{FilterObserver, FilterStep, ObserverError};
+pub use picker::{ArAicPicker, ArAicPickerBuilder, PickerError};
 
 use ndarray::ScalarOperand;
 use num_traits::{Float, One, Zero};
@@ -26,6 +45,8 @@ use std::iter::Sum;
 pub enum Event<T: RealField + Float + Copy + One + Zero + ScalarOperand> {
     Triggered(usize),
     Reset(usize),
+    /// Reset forcibly, after staying triggered for too long.
+    StuckReset(usize),
     MaximumFound(usize, T),
 }
 
@@ -36,7 +57,17 @@ where
     T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand,
 {
     fn reset(&mut self);
-    fn process(&mut self, input: &ndarray::Array1<T>) -> ndarray::Array1<T>;
+
+    /// Process some input samples into an output buffer of the same
+    /// length, so that callers may reuse buffers from one call to the
+    /// next.
+    fn process_into(&mut self, input: &ndarray::ArrayView1<T>, out: &mut ndarray::Array1<T>);
+
+    fn process(&mut self, input: &ndarray::Array1<T>) -> ndarray::Array1<T> {
+        let mut out = ndarray::Array1::zeros(input.len());
+        self.process_into(&input.view(), &mut out);
+        out
+    }
 }
 
 /// A signal processing block which operates on some input samples and optionally
@@ -46,7 +77,7 @@ where
     T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand,
 {
     fn reset(&mut self);
-    fn process(&mut self, input: &ndarray::Array1<T>, obs: impl FnMut(Event<T>) -> ());
+    fn process(&mut self, input: &ndarray::Array1<T>, obs: impl FnMut(Event<T>));
 }
 
 pub enum ProcessingBlock<T>
@@ -54,29 +85,62 @@ where
     T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand,
 {
     AffineTransform(Box<AffineTransform<T>>),
+    BandPower(Box<BandPower<T>>),
+    EnvelopeFollower(Box<EnvelopeFollower<T>>),
+    FilterBank(Box<FilterBank<T>>),
     LowPassFilter(Box<LowPassFilter<T>>),
+    NotchFilter(Box<NotchFilter<T>>),
     OnePoleFilter(Box<OnePoleFilter<T>>),
     Rectify(Rectify),
+    ResponseRemoval(Box<ResponseRemoval<T>>),
+    RunningRms(Box<RunningRms<T>>),
+    Taper(Box<Taper<T>>),
 }
 
 impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> SignalBlock<T>
     for ProcessingBlock<T>
 {
-    fn process(&mut self, input: &ndarray::Array1<T>) -> ndarray::Array1<T> {
+    fn process_into(&mut self, input: &ndarray::ArrayView1<T>, out: &mut ndarray::Array1<T>) {
         match self {
-            ProcessingBlock::AffineTransform(a) => a.process(input),
-            ProcessingBlock::LowPassFilter(l) => l.process(input),
-            ProcessingBlock::OnePoleFilter(o) => o.process(input),
-            ProcessingBlock::Rectify(r) => r.process(input),
+            ProcessingBlock::AffineTransform(a) => a.process_into(input, out),
+            ProcessingBlock::BandPower(b) => b.process_into(input, out),
+            ProcessingBlock::EnvelopeFollower(e) => e.process_into(input, out),
+            ProcessingBlock::FilterBank(f) => f.process_into(input, out),
+            ProcessingBlock::LowPassFilter(l) => l.process_into(input, out),
+            ProcessingBlock::NotchFilter(n) => n.process_into(input, out),
+            ProcessingBlock::OnePoleFilter(o) => o.process_into(input, out),
+            ProcessingBlock::Rectify(r) => r.process_into(input, out),
+            ProcessingBlock::ResponseRemoval(r) => r.process_into(input, out),
+            ProcessingBlock::RunningRms(r) => r.process_into(input, out),
+            ProcessingBlock::Taper(t) => t.process_into(input, out),
         }
     }
 
     fn reset(&mut self) {
         match self {
             ProcessingBlock::AffineTransform(a) => a.reset(),
+            ProcessingBlock::BandPower(b) => b.reset(),
+            ProcessingBlock::EnvelopeFollower(e) => e.reset(),
+            ProcessingBlock::FilterBank(f) => f.reset(),
             ProcessingBlock::LowPassFilter(l) => l.reset(),
+            ProcessingBlock::NotchFilter(n) => n.reset(),
             ProcessingBlock::OnePoleFilter(o) => o.reset(),
             ProcessingBlock::Rectify(r) => <Rectify as SignalBlock<T>>::reset(r),
+            ProcessingBlock::ResponseRemoval(r) => r.reset(),
+            ProcessingBlock::RunningRms(r) => r.reset(),
+            ProcessingBlock::Taper(t) => t.reset(),
+        }
+    }
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> ProcessingBlock<T> {
+    /// Seconds by which the block's output lags a rise in its input, as
+    /// compensated for in trigger times. Only the envelope follower's
+    /// smoothing is accounted for.
+    pub fn delay_s(&self) -> T {
+        match self {
+            ProcessingBlock::EnvelopeFollower(e) => e.delay_s(),
+            _ => T::zero(),
         }
     }
 }
@@ -85,6 +149,10 @@ where
     T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand,
 {
     ThresholdTrigger(Box<ThresholdTrigger<T>>),
+    AdaptiveTrigger(Box<AdaptiveTrigger<T>>),
+    ZDetector(Box<ZDetector<T>>),
+    KurtosisTrigger(Box<KurtosisTrigger<T>>),
+    TemplateMatcher(Box<TemplateMatcher<T>>),
 }
 
 impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> EventBlock<T>
@@ -93,12 +161,20 @@ impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> EventBlock<
     fn reset(&mut self) {
         match self {
             Self::ThresholdTrigger(t) => t.reset(),
+            Self::AdaptiveTrigger(t) => t.reset(),
+            Self::ZDetector(t) => t.reset(),
+            Self::KurtosisTrigger(t) => t.reset(),
+            Self::TemplateMatcher(t) => t.reset(),
         }
     }
 
-    fn process(&mut self, input: &ndarray::Array1<T>, obs: impl FnMut(Event<T>) -> ()) {
+    fn process(&mut self, input: &ndarray::Array1<T>, obs: impl FnMut(Event<T>)) {
         match self {
             Self::ThresholdTrigger(t) => t.process(input, obs),
+            Self::AdaptiveTrigger(t) => t.process(input, obs),
+            Self::ZDetector(t) => t.process(input, obs),
+            Self::KurtosisTrigger(t) => t.process(input, obs),
+            Self::TemplateMatcher(t) => t.process(input, obs),
         }
     }
 }
@@ -111,6 +187,30 @@ impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> From<Affine
     }
 }
 
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> From<BandPower<T>>
+    for ProcessingBlock<T>
+{
+    fn from(value: BandPower<T>) -> Self {
+        Self::BandPower(Box::new(value))
+    }
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> From<EnvelopeFollower<T>>
+    for ProcessingBlock<T>
+{
+    fn from(value: EnvelopeFollower<T>) -> Self {
+        Self::EnvelopeFollower(Box::new(value))
+    }
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> From<FilterBank<T>>
+    for ProcessingBlock<T>
+{
+    fn from(value: FilterBank<T>) -> Self {
+        Self::FilterBank(Box::new(value))
+    }
+}
+
 impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> From<LowPassFilter<T>>
     for ProcessingBlock<T>
 {
@@ -119,6 +219,14 @@ impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> From<LowPas
     }
 }
 
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> From<NotchFilter<T>>
+    for ProcessingBlock<T>
+{
+    fn from(value: NotchFilter<T>) -> Self {
+        Self::NotchFilter(Box::new(value))
+    }
+}
+
 impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> From<OnePoleFilter<T>>
     for ProcessingBlock<T>
 {
@@ -135,6 +243,30 @@ impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> From<Rectif
     }
 }
 
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> From<ResponseRemoval<T>>
+    for ProcessingBlock<T>
+{
+    fn from(value: ResponseRemoval<T>) -> Self {
+        Self::ResponseRemoval(Box::new(value))
+    }
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> From<RunningRms<T>>
+    for ProcessingBlock<T>
+{
+    fn from(value: RunningRms<T>) -> Self {
+        Self::RunningRms(Box::new(value))
+    }
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> From<Taper<T>>
+    for ProcessingBlock<T>
+{
+    fn from(value: Taper<T>) -> Self {
+        Self::Taper(Box::new(value))
+    }
+}
+
 impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> From<ThresholdTrigger<T>>
     for EventGeneratingBlock<T>
 {
@@ -142,3 +274,35 @@ impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> From<Thresh
         Self::ThresholdTrigger(Box::new(value))
     }
 }
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> From<AdaptiveTrigger<T>>
+    for EventGeneratingBlock<T>
+{
+    fn from(value: AdaptiveTrigger<T>) -> Self {
+        Self::AdaptiveTrigger(Box::new(value))
+    }
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> From<ZDetector<T>>
+    for EventGeneratingBlock<T>
+{
+    fn from(value: ZDetector<T>) -> Self {
+        Self::ZDetector(Box::new(value))
+    }
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> From<KurtosisTrigger<T>>
+    for EventGeneratingBlock<T>
+{
+    fn from(value: KurtosisTrigger<T>) -> Self {
+        Self::KurtosisTrigger(Box::new(value))
+    }
+}
+
+impl<T: RealField + Float + Copy + Sum + One + Zero + ScalarOperand> From<TemplateMatcher<T>>
+    for EventGeneratingBlock<T>
+{
+    fn from(value: TemplateMatcher<T>) -> Self {
+        Self::TemplateMatcher(Box::new(value))
+    }
+}
@@ -0,0 +1,239 @@
+//! Refinement of trigger times to phase onset times.
+use std::collections::VecDeque;
+
+use num_traits::Float;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum PickerError {
+    #[error("AR order must be at least one")]
+    OrderTooLow,
+    #[error("picking window must be at least eight times the AR order")]
+    WindowTooShort,
+}
+
+/// AR-AIC phase picker (after Sleeman and van Eck, 1999).
+///
+/// Keeps a window of recent samples. When asked to pick around a
+/// trigger, it fits autoregressive models to the noise well before the
+/// trigger and to the signal after it, and picks the onset as the point
+/// which best divides the window into samples predicted by one model and
+/// samples predicted by the other (the minimum of the Akaike Information
+/// Criterion).
+pub struct ArAicPicker<T> {
+    sample_rate_hz: f64,
+    order: usize,
+    window: VecDeque<f64>,
+    window_len: usize,
+    _phantom: std::marker::PhantomData<T>,
+}
+
+impl<T: Float> ArAicPicker<T> {
+    /// Add samples to the window.
+    pub fn observe(&mut self, input: &ndarray::Array1<T>) {
+        for &v in input {
+            if self.window.len() == self.window_len {
+                self.window.pop_front();
+            }
+            self.window.push_back(v.to_f64().unwrap_or(0.0));
+        }
+    }
+
+    /// The sample rate that the window's length was reckoned at.
+    pub fn sample_rate_hz(&self) -> f64 {
+        self.sample_rate_hz
+    }
+
+    /// Pick the onset of a phase which triggered `trigger_back` samples
+    /// before the most recent one. Returns how long before the most
+    /// recent sample the onset was, in seconds.
+    pub fn pick(&self, trigger_back: usize) -> Option<f64> {
+        let n = self.window.len();
+        let p = self.order;
+        if n < 8 * p || trigger_back >= n {
+            return None;
+        }
+        let mean = self.window.iter().sum::<f64>() / n as f64;
+        let x: Vec<f64> = self.window.iter().map(|v| v - mean).collect();
+
+        // The onset is before the trigger, so the first half of the
+        // window up to it is taken as noise. There may be very little
+        // signal after the trigger yet, so take at least enough samples
+        // to fit its model.
+        let trigger = n - 1 - trigger_back;
+        let noise_end = (trigger / 2).max(4 * p);
+        let signal_start = trigger.min(n - 4 * p);
+        let noise_model = fit_ar(&x[..noise_end], p);
+        let signal_model = fit_ar(&x[signal_start..], p);
+
+        let residuals = |model: &[f64]| -> Vec<f64> {
+            (p..n)
+                .map(|t| {
+                    let predicted: f64 = (0..p).map(|i| model[i] * x[t - 1 - i]).sum();
+                    (x[t] - predicted).powi(2)
+                })
+                .collect()
+        };
+        let noise_residuals = residuals(&noise_model);
+        let signal_residuals = residuals(&signal_model);
+
+        // Noise residuals summed up to each sample, and signal residuals
+        // summed from each sample on.
+        let m = n - p;
+        let mut noise_sums = vec![0.0; m + 1];
+        for t in 0..m {
+            noise_sums[t + 1] = noise_sums[t] + noise_residuals[t];
+        }
+        let mut signal_sums = vec![0.0; m + 1];
+        for t in (0..m).rev() {
+            signal_sums[t] = signal_sums[t + 1] + signal_residuals[t];
+        }
+        let aic = |k: usize| -> f64 {
+            let (before, after) = (k as f64, (m - k) as f64);
+            let noise_variance = noise_sums[k] / before + f64::MIN_POSITIVE;
+            let signal_variance = signal_sums[k] / after + f64::MIN_POSITIVE;
+            before * noise_variance.ln() + after * signal_variance.ln()
+        };
+        let onset = (1..m)
+            .min_by(|a, b| aic(*a).total_cmp(&aic(*b)))
+            .map(|k| k + p)?;
+        Some((n - 1 - onset) as f64 / self.sample_rate_hz)
+    }
+}
+
+/// Fit an autoregressive model of some order to samples, by solving the
+/// Yule-Walker equations with the Levinson-Durbin recursion. Returns the
+/// coefficients predicting each sample from the ones before it, most
+/// recent first.
+fn fit_ar(x: &[f64], order: usize) -> Vec<f64> {
+    let autocorrelation: Vec<f64> = (0..=order)
+        .map(|lag| {
+            x.iter()
+                .zip(x.iter().skip(lag))
+                .map(|(a, b)| a * b)
+                .sum::<f64>()
+                / x.len() as f64
+        })
+        .collect();
+    let mut coefficients = vec![0.0; order];
+    let mut error = autocorrelation[0];
+    if error <= 0.0 {
+        return coefficients;
+    }
+    for i in 0..order {
+        let reflection = (autocorrelation[i + 1]
+            - (0..i)
+                .map(|j| coefficients[j] * autocorrelation[i - j])
+                .sum::<f64>())
+            / error;
+        let previous = coefficients.clone();
+        coefficients[i] = reflection;
+        for j in 0..i {
+            coefficients[j] = previous[j] - reflection * previous[i - 1 - j];
+        }
+        error *= 1.0 - reflection * reflection;
+        if error <= 0.0 {
+            break;
+        }
+    }
+    coefficients
+}
+
+pub struct ArAicPickerBuilder<T> {
+    sample_rate_hz: Option<T>,
+    window_s: Option<T>,
+    order: Option<usize>,
+}
+
+impl<T: Float> Default for ArAicPickerBuilder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Float> ArAicPickerBuilder<T> {
+    pub fn new() -> Self {
+        Self {
+            sample_rate_hz: None,
+            window_s: None,
+            order: None,
+        }
+    }
+
+    /// Interpret samples as coming in at a sample rate.
+    pub fn sample_rate(mut self, hz: T) -> Self {
+        self.sample_rate_hz.replace(hz);
+        self
+    }
+
+    /// Length of the window of samples to pick within, in seconds.
+    pub fn window_s(mut self, s: T) -> Self {
+        self.window_s.replace(s);
+        self
+    }
+
+    /// Order of the autoregressive models.
+    pub fn order(mut self, order: usize) -> Self {
+        self.order.replace(order);
+        self
+    }
+
+    /// Construct a picker.
+    pub fn build(self) -> Result<ArAicPicker<T>, PickerError> {
+        let order = self.order.unwrap_or(4);
+        if order == 0 {
+            return Err(PickerError::OrderTooLow);
+        }
+        let sample_rate_hz = self
+            .sample_rate_hz
+            .and_then(|hz| hz.to_f64())
+            .unwrap_or(1.0);
+        let window_s = self.window_s.map_or(Some(10.0), |s| s.to_f64());
+        let window_len = window_s.map_or(0, |s| (s * sample_rate_hz).round() as usize);
+        if window_len < 8 * order {
+            return Err(PickerError::WindowTooShort);
+        }
+        let result = ArAicPicker {
+            sample_rate_hz,
+            order,
+            window: VecDeque::with_capacity(window_len),
+            window_len,
+            _phantom: std::marker::PhantomData,
+        };
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ArAicPickerBuilder;
+    use ndarray::Array1;
+
+    #[test]
+    fn picks_onset_before_trigger() {
+        let mut picker = ArAicPickerBuilder::new()
+            .sample_rate(100.0_f32)
+            .window_s(10.0)
+            .build()
+            .expect("works");
+
+        // Pseudo-random noise, with a 5 Hz arrival at sample 500.
+        let mut seed: u32 = 1;
+        let samples = Array1::from_iter((0..600).map(|i| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            let noise = (seed >> 16) as f32 / 32768.0 - 1.0;
+            let arrival = if i >= 500 {
+                10.0 * (2.0 * std::f32::consts::PI * 5.0 * (i - 500) as f32 / 100.0).sin()
+            } else {
+                0.0
+            };
+            noise + arrival
+        }));
+        picker.observe(&samples);
+
+        // Triggered a third of a second late, at sample 533; the onset
+        // was a second before the last sample.
+        let onset = picker.pick(66).expect("picks");
+        assert!((onset - 0.99).abs() < 0.05, "{onset}");
+    }
+}
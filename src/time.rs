@@ -0,0 +1,124 @@
+//! Conversion of UNIX epoch timestamps into UTC calendar time (and into
+//! the host's local time of day).
+use chrono::{DateTime, Local, Timelike};
+use std::fmt::Display;
+
+/// A broken-down UTC time.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct UtcTime {
+    pub year: i64,
+    pub month: u8,
+    pub day: u8,
+    pub day_of_year: u16,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    pub microsecond: u32,
+}
+
+impl UtcTime {
+    /// Convert a UNIX epoch timestamp (seconds) into calendar time.
+    pub fn from_epoch(timestamp: f64) -> UtcTime {
+        Self::from_epoch_micros((timestamp * 1_000_000.0).round() as i64)
+    }
+
+    /// Convert a UNIX epoch timestamp (microseconds) into calendar time.
+    pub fn from_epoch_micros(micros: i64) -> UtcTime {
+        const MICROS_PER_DAY: i64 = 86_400 * 1_000_000;
+        let days = micros.div_euclid(MICROS_PER_DAY);
+        let day_micros = micros.rem_euclid(MICROS_PER_DAY);
+        let (year, month, day) = civil_from_days(days);
+        let seconds = day_micros / 1_000_000;
+        UtcTime {
+            year,
+            month,
+            day,
+            day_of_year: (days - days_from_civil(year, 1, 1) + 1) as u16,
+            hour: (seconds / 3600) as u8,
+            minute: ((seconds / 60) % 60) as u8,
+            second: (seconds % 60) as u8,
+            microsecond: (day_micros % 1_000_000) as u32,
+        }
+    }
+
+    /// A compact, filename-friendly form: "20241212T222146Z".
+    pub fn compact(&self) -> String {
+        format!(
+            "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+impl Display for UtcTime {
+    /// RFC 3339 form, with millisecond precision.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+            self.year,
+            self.month,
+            self.day,
+            self.hour,
+            self.minute,
+            self.second,
+            self.microsecond / 1000
+        )
+    }
+}
+
+/// The local time of day, in the host's time zone, of a UNIX epoch
+/// timestamp (seconds), in minutes since midnight.
+pub fn local_minute_of_day(timestamp: f64) -> u16 {
+    let Some(utc) = DateTime::from_timestamp(timestamp.floor() as i64, 0) else {
+        let utc = UtcTime::from_epoch(timestamp);
+        return utc.hour as u16 * 60 + utc.minute as u16;
+    };
+    let local = utc.with_timezone(&Local);
+    local.hour() as u16 * 60 + local.minute() as u16
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian calendar date.
+pub fn days_from_civil(year: i64, month: u8, day: u8) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let m = month as i64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Proleptic Gregorian calendar date of a day count since 1970-01-01.
+pub fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u8;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UtcTime;
+
+    #[test]
+    fn from_epoch() {
+        let t = UtcTime::from_epoch(1734042106.042);
+        assert_eq!(t.to_string(), "2024-12-12T22:21:46.042Z");
+        assert_eq!(t.day_of_year, 347);
+        assert_eq!(t.compact(), "20241212T222146Z");
+        // 2000-12-31, last day of a leap year.
+        let t = UtcTime::from_epoch(978_220_800.0);
+        assert_eq!((t.year, t.month, t.day, t.day_of_year), (2000, 12, 31, 366));
+        assert_eq!(
+            UtcTime::from_epoch(-1.0).to_string(),
+            "1969-12-31T23:59:59.000Z"
+        );
+    }
+}